@@ -0,0 +1,81 @@
+//! Adaptive body buffers against a fixed 64 KiB buffer, for an API that
+//! answers with small JSON-sized bodies and for a host serving large files.
+//! Bodies are chunked, so nothing but the learned size tells the client how
+//! much to preallocate.
+//!
+//! Run with `cargo bench --bench body_buffers`.
+
+use std::time::{Duration, Instant};
+
+use async_std::io::{ReadExt, WriteExt};
+use async_std::net::TcpListener;
+use async_std::task;
+use zjhttpc::client::ZJHttpClient;
+use zjhttpc::methods;
+use zjhttpc::requestx::Request;
+use zjhttpc::response::BodyBufferBounds;
+
+/// Keep-alive server answering `GET /<n>` with an `n`-byte chunked body.
+async fn spawn_server() -> u16 {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    task::spawn(async move {
+        while let Ok((mut stream, _)) = listener.accept().await {
+            task::spawn(async move {
+                let mut buf = vec![0u8; 4096];
+                loop {
+                    let mut head = Vec::new();
+                    while !head.ends_with(b"\r\n\r\n") {
+                        match stream.read(&mut buf).await {
+                            Ok(0) | Err(_) => return,
+                            Ok(n) => head.extend_from_slice(&buf[..n]),
+                        }
+                    }
+                    let head = String::from_utf8_lossy(&head);
+                    let len: usize = head.split(' ').nth(1).unwrap().trim_start_matches('/').parse().unwrap();
+                    let mut wire = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n".to_vec();
+                    for chunk in vec![b'x'; len].chunks(64 * 1024) {
+                        wire.extend_from_slice(format!("{:x}\r\n", chunk.len()).as_bytes());
+                        wire.extend_from_slice(chunk);
+                        wire.extend_from_slice(b"\r\n");
+                    }
+                    wire.extend_from_slice(b"0\r\n\r\n");
+                    if stream.write_all(&wire).await.is_err() {
+                        return;
+                    }
+                }
+            });
+        }
+    });
+    port
+}
+
+/// Time `rounds` sequential GETs of `len`-byte bodies, read with `body_bytes`.
+async fn run(bounds: BodyBufferBounds, port: u16, len: usize, rounds: usize) -> Duration {
+    let client = ZJHttpClient::builder().set_body_buffer_bounds(bounds).build().unwrap();
+    let url = format!("http://127.0.0.1:{port}/{len}");
+    let start = Instant::now();
+    for _ in 0..rounds {
+        let mut req = Request::new(methods::GET, &url).unwrap();
+        let mut resp = Box::pin(client.send(&mut req)).await.unwrap();
+        assert_eq!(resp.body_bytes().await.unwrap().len(), len);
+    }
+    start.elapsed()
+}
+
+fn main() {
+    task::block_on(async {
+        let port = spawn_server().await;
+        let fixed = BodyBufferBounds { min: 64 * 1024, max: 64 * 1024 };
+        let adaptive = BodyBufferBounds::default();
+        for (workload, len, rounds) in [("small (2 KB x 5000)", 2_000, 5_000), ("large (100 MB x 5)", 100_000_000, 5)] {
+            let fixed_time = run(fixed, port, len, rounds).await;
+            let adaptive_time = run(adaptive, port, len, rounds).await;
+            println!(
+                "{workload:<22} fixed 64 KiB: {fixed_time:>10.2?}  adaptive: {adaptive_time:>10.2?}  \
+                 (preallocated per body: 64 KiB vs {} KiB)",
+                adaptive.size_for(Some(len as u64)) / 1024
+            );
+        }
+    });
+}
@@ -0,0 +1,173 @@
+//! Allocations spent on response header names: a realistic 25-header
+//! response, whose names are all in the well-known table, against the same
+//! response with its names swapped for unknown ones, which are stored as
+//! owned strings. Measured for building the `HeaderMap` alone and for a whole
+//! `send()` plus `body_bytes()` over loopback.
+//!
+//! Run with `cargo bench --bench header_names`.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use async_std::io::{ReadExt, WriteExt};
+use async_std::net::TcpListener;
+use async_std::task;
+use zjhttpc::client::ZJHttpClient;
+use zjhttpc::header::HeaderMap;
+use zjhttpc::methods;
+use zjhttpc::requestx::Request;
+
+/// Counts every allocation and reallocation, in any thread.
+struct Counting;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        unsafe { System.realloc(ptr, layout, new_size) }
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+const BODY: &str = "{\"ok\":true}";
+
+/// The 25 header lines of a typical CDN-fronted API response.
+fn realistic_headers() -> Vec<(String, String)> {
+    [
+        ("Date", "Wed, 07 Mar 2026 12:00:00 GMT"),
+        ("Server", "nginx/1.25.3"),
+        ("Content-Type", "application/json; charset=utf-8"),
+        ("Content-Length", "11"),
+        ("Connection", "keep-alive"),
+        ("Keep-Alive", "timeout=60"),
+        ("Cache-Control", "private, max-age=0, must-revalidate"),
+        ("ETag", "W/\"5e-18c3f1a2b4d\""),
+        ("Last-Modified", "Tue, 06 Mar 2026 09:30:00 GMT"),
+        ("Expires", "Wed, 07 Mar 2026 12:00:00 GMT"),
+        ("Vary", "Accept-Encoding, Origin"),
+        ("Accept-Ranges", "bytes"),
+        ("Age", "0"),
+        ("Via", "1.1 varnish"),
+        ("X-Cache", "MISS"),
+        ("Strict-Transport-Security", "max-age=31536000; includeSubDomains"),
+        ("X-Content-Type-Options", "nosniff"),
+        ("X-Frame-Options", "DENY"),
+        ("Referrer-Policy", "strict-origin-when-cross-origin"),
+        ("Content-Security-Policy", "default-src 'none'"),
+        ("Access-Control-Allow-Origin", "*"),
+        ("Set-Cookie", "session=abc123; Path=/; HttpOnly; Secure"),
+        ("Set-Cookie", "theme=dark; Path=/; Max-Age=31536000"),
+        ("Alt-Svc", "h3=\":443\"; ma=86400"),
+        ("X-Request-Id", "9f2c1e7a-4b3d-4c2a-8e1f-0a9b8c7d6e5f"),
+    ]
+    .into_iter()
+    .map(|(name, value)| (name.to_string(), value.to_string()))
+    .collect()
+}
+
+/// The same lines with every name but the framing ones replaced by a name
+/// the table does not know.
+fn unknown_headers() -> Vec<(String, String)> {
+    realistic_headers()
+        .into_iter()
+        .enumerate()
+        .map(|(i, (name, value))| match name.as_str() {
+            "Content-Length" | "Connection" => (name, value),
+            _ => (format!("X-Vendor-Field-{i}"), value),
+        })
+        .collect()
+}
+
+fn head(headers: &[(String, String)]) -> Vec<u8> {
+    let mut head = String::from("HTTP/1.1 200 OK\r\n");
+    for (name, value) in headers {
+        head.push_str(&format!("{name}: {value}\r\n"));
+    }
+    head.push_str("\r\n");
+    head.push_str(BODY);
+    head.into_bytes()
+}
+
+/// Keep-alive server answering every request with `response`.
+async fn spawn_server(response: Vec<u8>) -> u16 {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    task::spawn(async move {
+        while let Ok((mut stream, _)) = listener.accept().await {
+            let response = response.clone();
+            task::spawn(async move {
+                let mut buf = vec![0u8; 4096];
+                loop {
+                    let mut head = Vec::new();
+                    while !head.ends_with(b"\r\n\r\n") {
+                        match stream.read(&mut buf).await {
+                            Ok(0) | Err(_) => return,
+                            Ok(n) => head.extend_from_slice(&buf[..n]),
+                        }
+                    }
+                    if stream.write_all(&response).await.is_err() {
+                        return;
+                    }
+                }
+            });
+        }
+    });
+    port
+}
+
+/// Allocations per round and time for `rounds` rounds of `f`.
+fn measure(rounds: usize, mut f: impl FnMut()) -> (f64, Duration) {
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    let start = Instant::now();
+    for _ in 0..rounds {
+        f();
+    }
+    let elapsed = start.elapsed();
+    ((ALLOCATIONS.load(Ordering::Relaxed) - before) as f64 / rounds as f64, elapsed)
+}
+
+fn main() {
+    let rounds = 20_000;
+    for (label, headers) in [("well-known names", realistic_headers()), ("unknown names", unknown_headers())] {
+        let (allocs, time) = measure(rounds, || {
+            let map: HeaderMap = headers.iter().map(|(name, value)| (name.as_str(), value.as_str())).collect();
+            assert_eq!(map.get("content-length"), Some("11"));
+        });
+        println!("HeaderMap, {label:<17} {allocs:>6.1} allocations/response  {time:>10.2?} for {rounds}");
+    }
+
+    let rounds = 2_000;
+    task::block_on(async {
+        let client = ZJHttpClient::builder().build().unwrap();
+        for (label, headers) in [("well-known names", realistic_headers()), ("unknown names", unknown_headers())] {
+            let url = format!("http://127.0.0.1:{}/", spawn_server(head(&headers)).await);
+            // One warm-up request opens the pooled connection.
+            let mut req = Request::new(methods::GET, &url).unwrap();
+            Box::pin(client.send(&mut req)).await.unwrap().body_bytes().await.unwrap();
+
+            let before = ALLOCATIONS.load(Ordering::Relaxed);
+            let start = Instant::now();
+            for _ in 0..rounds {
+                let mut req = Request::new(methods::GET, &url).unwrap();
+                let mut resp = Box::pin(client.send(&mut req)).await.unwrap();
+                assert_eq!(resp.body_bytes().await.unwrap(), BODY.as_bytes());
+            }
+            let time = start.elapsed();
+            let allocs = (ALLOCATIONS.load(Ordering::Relaxed) - before) as f64 / rounds as f64;
+            println!("send(),    {label:<17} {allocs:>6.1} allocations/request   {time:>10.2?} for {rounds}");
+        }
+    });
+}
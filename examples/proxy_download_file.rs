@@ -48,7 +48,7 @@ fn main() -> Result<()> {
                     response.status_code(),
                     body.chars().take(500).collect::<String>()
                 ),
-            }.build().into());
+            }.build());
         }
 
         let output_path = Path::new(OUTPUT);
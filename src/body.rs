@@ -1,7 +1,10 @@
 use crate::error::{MultipartContentLengthSnafu, Result};
 use async_std::fs::File;
+use std::collections::HashMap;
 use std::fmt;
 use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
 
 
 /// Request body types
@@ -10,6 +13,8 @@ pub enum Body {
     Str(String),
     /// Stream body (for streaming data)
     Stream(Box<dyn async_std::io::Read + Unpin + Send + Sync>),
+    /// Stream body of unknown length, sent chunked
+    StreamChunked(Box<dyn async_std::io::Read + Unpin + Send + Sync>),
     /// Bytes body
     Bytes(Vec<u8>),
     /// Multipart form data
@@ -23,6 +28,7 @@ impl fmt::Debug for Body {
         match self {
             Body::Str(s) => f.debug_tuple("Str").field(&s.len()).finish(),
             Body::Stream(_) => f.debug_tuple("Stream").finish(),
+            Body::StreamChunked(_) => f.debug_tuple("StreamChunked").finish(),
             Body::Bytes(b) => f.debug_tuple("Bytes").field(&b.len()).finish(),
             Body::MultipartForm(form) => f.debug_tuple("MultipartForm").field(&form.fields.len()).finish(),
             Body::None => f.debug_tuple("None").finish(),
@@ -68,20 +74,13 @@ impl BodyForm {
         self
     }
 
-    /// Serialize the form data to application/x-www-form-urlencoded format
+    /// Serialize the form data to application/x-www-form-urlencoded format,
+    /// escaped like [`crate::encoding::encode_form_pairs`].
     #[must_use]
     pub fn serialize(&self) -> String {
-        self.fields
-            .iter()
-            .map(|(key, value)| {
-                format!(
-                    "{}={}",
-                    url_encode(key),
-                    url_encode(value)
-                )
-            })
-            .collect::<Vec<_>>()
-            .join("&")
+        let mut out = String::new();
+        crate::encoding::encode_form_pairs(self.fields.iter().map(|(k, v)| (k, v)), &mut out);
+        out
     }
 
     /// Get the number of fields in the form
@@ -97,6 +96,14 @@ impl BodyForm {
     }
 }
 
+/// Collect pairs into a form, e.g. from a `HashMap<String, String>`;
+/// fields keep the iteration order.
+impl<K: AsRef<str>, V: AsRef<str>> FromIterator<(K, V)> for BodyForm {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(pairs: I) -> Self {
+        pairs.into_iter().fold(BodyForm::new(), |form, (key, value)| form.add(key, value))
+    }
+}
+
 impl fmt::Debug for BodyForm {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("BodyForm")
@@ -105,34 +112,6 @@ impl fmt::Debug for BodyForm {
     }
 }
 
-/// URL encode a string (percent encoding)
-fn url_encode(s: &str) -> String {
-    let mut result = String::new();
-    for byte in s.bytes() {
-        match byte {
-            // Unreserved characters - don't encode
-            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' |
-            b'-' | b'_' | b'.' | b'~' => {
-                result.push(byte as char);
-            }
-            // Space becomes +
-            b' ' => {
-                result.push('+');
-            }
-            // Everything else is percent-encoded
-            b => {
-                let high = (b >> 4) & 0x0f;
-                let low = b & 0x0f;
-                let hex = |n: u8| if n < 10 { b'0' + n } else { b'A' + n - 10 };
-                result.push('%');
-                result.push(hex(high) as char);
-                result.push(hex(low) as char);
-            }
-        }
-    }
-    result
-}
-
 /// Represents a single field in a multipart form
 pub enum MultipartField {
     /// A text field with name and value
@@ -149,12 +128,57 @@ pub enum MultipartField {
         Option<String>,
         Option<String>,
     ),
+    /// A stream of known length: (name, stream, len, filename, content_type)
+    SizedStream(
+        String,
+        Box<dyn async_std::io::Read + Unpin + Send + Sync>,
+        u64,
+        Option<String>,
+        Option<String>,
+    ),
+}
+
+/// How far the content of one multipart part has been written, see
+/// [`BodyMultipartForm::on_part_progress`].
+#[derive(Debug, Clone, Copy)]
+pub struct PartProgress<'a> {
+    /// Position of the part in the form, from 0.
+    pub index: usize,
+    pub name: &'a str,
+    /// Content bytes of this part written so far (headers not included).
+    pub sent: u64,
+    /// The part's content is complete.
+    pub done: bool,
+}
+
+pub(crate) type PartProgressFn = Arc<dyn Fn(&PartProgress<'_>) + Send + Sync>;
+
+/// Shared flag to abandon a multipart upload, see
+/// [`BodyMultipartForm::set_cancel_token`]. Clones share the flag.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
 }
 
 /// Multipart form data for multipart/form-data
 pub struct BodyMultipartForm {
     pub(crate) fields: Vec<MultipartField>,
     pub(crate) boundary: String,
+    /// Progress callbacks by field index.
+    pub(crate) progress: HashMap<usize, PartProgressFn>,
+    pub(crate) cancel: Option<CancelToken>,
 }
 
 impl BodyMultipartForm {
@@ -164,9 +188,22 @@ impl BodyMultipartForm {
         Self {
             fields: Vec::new(),
             boundary: generate_boundary(),
+            progress: HashMap::new(),
+            cancel: None,
         }
     }
 
+    /// Build a form with one text field per serialized field of `value`.
+    ///
+    /// Field names and values follow
+    /// [`Request::set_body_form_serde`](crate::requestx::Request::set_body_form_serde):
+    /// `None` fields are left out, sequences and nested structs become
+    /// bracketed names such as `tags[0]`.
+    pub fn from_serialize(value: &impl serde::Serialize) -> Result<Self> {
+        let encoded = serde_qs::to_string(value)?;
+        Ok(crate::encoding::decode_form_pairs(&encoded).fold(Self::new(), |form, (name, value)| form.add(name, value)))
+    }
+
     /// Add a text field to the form
     ///
     /// # Arguments
@@ -376,6 +413,72 @@ impl BodyMultipartForm {
         self
     }
 
+    /// Add a stream of exactly `len` bytes. Unlike [`add_stream`](Self::add_stream),
+    /// the form keeps a computed Content-Length and is not sent chunked, so
+    /// any reader (a decoder, a pipe, a slice of a larger file) can be
+    /// uploaded to servers that refuse chunked bodies. Bytes past `len` are
+    /// not read; a stream that ends early fails the send with
+    /// [`ZjhttpcError::MultipartContentLength`](crate::ZjhttpcError::MultipartContentLength)
+    /// and the connection is closed.
+    ///
+    /// # Examples
+    /// ```
+    /// use zjhttpc::body::BodyMultipartForm;
+    /// use async_std::io::Cursor;
+    ///
+    /// let data = b"col1,col2\n1,2\n";
+    /// let form = BodyMultipartForm::new()
+    ///     .add("note", "nightly export")
+    ///     .add_stream_with_len(
+    ///         "report",
+    ///         Box::new(Cursor::new(data)),
+    ///         data.len() as u64,
+    ///         Some("report.csv"),
+    ///         Some("text/csv"),
+    ///     );
+    /// assert!(!form.has_stream_field());
+    /// ```
+    #[must_use]
+    pub fn add_stream_with_len(
+        mut self,
+        name: impl AsRef<str>,
+        stream: Box<dyn async_std::io::Read + Unpin + Send + Sync>,
+        len: u64,
+        filename: Option<impl AsRef<str>>,
+        content_type: Option<impl AsRef<str>>,
+    ) -> Self {
+        self.fields.push(MultipartField::SizedStream(
+            name.as_ref().to_owned(),
+            stream,
+            len,
+            filename.map(|f| f.as_ref().to_owned()),
+            content_type.map(|c| c.as_ref().to_owned()),
+        ));
+        self
+    }
+
+    /// Call `f` as the content of the most recently added part is written:
+    /// after every write, and once more with `done` set when the part is
+    /// complete. No effect on an empty form. The body bytes, and so the
+    /// computed Content-Length, do not change.
+    #[must_use]
+    pub fn on_part_progress(mut self, f: impl Fn(&PartProgress<'_>) + Send + Sync + 'static) -> Self {
+        if let Some(index) = self.fields.len().checked_sub(1) {
+            self.progress.insert(index, Arc::new(f));
+        }
+        self
+    }
+
+    /// Check `token` before writing each part. Once it is cancelled, `send()`
+    /// stops at the next part boundary with
+    /// [`ZjhttpcError::UploadCancelled`](crate::ZjhttpcError::UploadCancelled)
+    /// and the connection, holding an incomplete body, is closed.
+    #[must_use]
+    pub fn set_cancel_token(mut self, token: CancelToken) -> Self {
+        self.cancel = Some(token);
+        self
+    }
+
     /// Get the boundary string for this form
     #[must_use]
     pub fn boundary(&self) -> &str {
@@ -394,7 +497,8 @@ impl BodyMultipartForm {
         self.fields.is_empty()
     }
 
-    /// Check if any field is a Stream variant (unknown length).
+    /// Check if any field is a Stream variant (unknown length); such a form
+    /// is sent chunked.
     #[must_use]
     pub fn has_stream_field(&self) -> bool {
         self.fields.iter().any(|f| matches!(f, MultipartField::Stream(..)))
@@ -466,6 +570,22 @@ impl BodyMultipartForm {
                     total += meta.len();
                     total += 2; // \r\n
                 }
+                MultipartField::SizedStream(name, _, len, filename_opt, content_type_opt) => {
+                    let filename = filename_opt
+                        .as_deref()
+                        .unwrap_or("filename");
+                    let content_type = content_type_opt
+                        .as_deref()
+                        .unwrap_or_else(|| detect_mime_type(filename));
+
+                    total += format!(
+                        "Content-Disposition: form-data; name=\"{}\"; filename=\"{}\"\r\n",
+                        name, filename
+                    ).len() as u64;
+                    total += format!("Content-Type: {}\r\n\r\n", content_type).len() as u64;
+                    total += len;
+                    total += 2; // \r\n
+                }
                 MultipartField::Stream(..) => {
                     return Err(MultipartContentLengthSnafu {
                         message: "cannot compute content-length for Stream fields; use chunked encoding".to_string()
@@ -492,6 +612,8 @@ impl fmt::Debug for BodyMultipartForm {
         f.debug_struct("BodyMultipartForm")
             .field("boundary", &self.boundary)
             .field("fields_count", &self.fields.len())
+            .field("progress_callbacks", &self.progress.len())
+            .field("cancel", &self.cancel)
             .finish()
     }
 }
@@ -601,6 +723,16 @@ mod tests {
         assert_eq!(serialized, "tags=rust&tags=http&tags=async");
     }
 
+    #[test]
+    fn test_body_form_from_map() {
+        let map = HashMap::from([("q".to_string(), "a&b=c d".to_string())]);
+        let form: BodyForm = map.into_iter().collect();
+        assert_eq!(form.serialize(), "q=a%26b%3Dc+d");
+
+        let form: BodyForm = [("名", "値"), ("k", "")].into_iter().collect();
+        assert_eq!(form.serialize(), "%E5%90%8D=%E5%80%A4&k=");
+    }
+
     #[test]
     fn test_body_form_chainable() {
         let form = BodyForm::new()
@@ -611,27 +743,46 @@ mod tests {
         assert_eq!(form.len(), 3);
     }
 
+    fn encode_form(s: &str) -> String {
+        crate::encoding::encode(s, crate::encoding::FORM).into_owned()
+    }
+
     #[test]
     fn test_url_encode_unreserved() {
-        assert_eq!(url_encode("abc123-_.~"), "abc123-_.~");
+        assert_eq!(encode_form("abc123-_.~"), "abc123-_.~");
     }
 
     #[test]
     fn test_url_encode_space() {
-        assert_eq!(url_encode("hello world"), "hello+world");
+        assert_eq!(encode_form("hello world"), "hello+world");
     }
 
     #[test]
     fn test_url_encode_special() {
-        assert_eq!(url_encode("user@example.com"), "user%40example.com");
-        assert_eq!(url_encode("/path/to/file"), "%2Fpath%2Fto%2Ffile");
-        assert_eq!(url_encode("query=value"), "query%3Dvalue");
+        assert_eq!(encode_form("user@example.com"), "user%40example.com");
+        assert_eq!(encode_form("/path/to/file"), "%2Fpath%2Fto%2Ffile");
+        assert_eq!(encode_form("query=value"), "query%3Dvalue");
     }
 
     #[test]
     fn test_url_encode_unicode() {
-        let encoded = url_encode("你好");
+        let encoded = encode_form("你好");
         assert!(encoded.starts_with("%"));
         assert!(encoded.contains("%"));
     }
+
+    #[async_std::test]
+    async fn test_progress_callbacks_keep_content_length() {
+        let form = || BodyMultipartForm::new().add("a", "1").add("b", "22");
+        let plain = form();
+        let mut watched = form()
+            .on_part_progress(|_| {})
+            .set_cancel_token(CancelToken::new());
+        watched.boundary = plain.boundary.clone();
+        assert_eq!(watched.progress.keys().collect::<Vec<_>>(), [&1]);
+        assert_eq!(
+            watched.compute_content_length().await.unwrap(),
+            plain.compute_content_length().await.unwrap()
+        );
+    }
 }
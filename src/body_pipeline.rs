@@ -0,0 +1,531 @@
+//! The stages a response body passes through between the connection and
+//! the caller, always in this order:
+//!
+//! 1. count the bytes as received (framing removed, content codings not),
+//!    which feeds the origin's learned body size;
+//! 2. check `Content-MD5`, which RFC 1864 computes over the body as sent,
+//!    content codings included;
+//! 3. undo the content codings, see [`crate::decompress`];
+//! 4. stop a decompression bomb: decoded bytes past
+//!    `max_decompression_ratio` times the bytes received (once there are more
+//!    than [`RATIO_GRACE`] of them) fail with `DecompressionBomb`, and the
+//!    connection is closed rather than pooled;
+//! 5. enforce the body size limit on the decoded bytes, so a compressed body
+//!    stops at the limit whatever its size on the wire;
+//! 6. for `body_bytes`, charge the decoded bytes to the client's memory
+//!    budget, see [`crate::memory_budget`];
+//! 7. check the SHA-256 the request expects of the body it hands out.
+//!
+//! Every body reader of [`Response`] — `body_bytes` and the methods built on
+//! it, `body_reader`, `bytes_stream`, `copy_body_to` and `save_to_file` —
+//! reads through one pipeline, built from the response's settings when the
+//! read starts. `body_managed_stream`, `peek_body` and the SSE stream see
+//! the body as received.
+//!
+//! Checksums are only known once the body has ended. So that a body damaged
+//! in transit is reported as such, a later stage that fails while a
+//! `Content-MD5` is still to be checked holds its error: the rest of the body
+//! is only hashed, and at its end the checksum decides between
+//! `ChecksumMismatch` and the held error.
+
+use std::borrow::Cow;
+use std::pin::Pin;
+use std::task::{Context, Poll, ready};
+
+use crate::client::ConnectionPool;
+use crate::config::EffectiveConfig;
+use crate::decompress::Decoder;
+use crate::error::{BodyTooLargeSnafu, ChecksumMismatchSnafu, DecompressionBombSnafu, Result, ZjhttpcError};
+use crate::header::HeaderMap;
+use crate::md5::Md5;
+use crate::memory_budget::Reservation;
+use crate::response::Response;
+
+/// What a stage does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum StageKind {
+    CountWire,
+    WireChecksum,
+    Decode,
+    Ratio,
+    Limit,
+    Budget,
+    DecodedChecksum,
+}
+
+/// Decoded bytes a body may reach whatever its ratio, so short bodies that
+/// compress well are never taken for bombs.
+pub(crate) const RATIO_GRACE: u64 = 1024 * 1024;
+
+enum Stage {
+    CountWire(u64),
+    WireChecksum { md5: Md5, expected: String },
+    Decode(Decoder),
+    Ratio { max: u64, decoded: u64 },
+    Limit { limit: u64, seen: u64 },
+    Budget { reservation: Reservation, seen: u64 },
+    DecodedChecksum { sha256: ring::digest::Context, expected: [u8; 32] },
+}
+
+impl Stage {
+    fn kind(&self) -> StageKind {
+        match self {
+            Stage::CountWire(_) => StageKind::CountWire,
+            Stage::WireChecksum { .. } => StageKind::WireChecksum,
+            Stage::Decode(_) => StageKind::Decode,
+            Stage::Ratio { .. } => StageKind::Ratio,
+            Stage::Limit { .. } => StageKind::Limit,
+            Stage::Budget { .. } => StageKind::Budget,
+            Stage::DecodedChecksum { .. } => StageKind::DecodedChecksum,
+        }
+    }
+
+    /// Run `data` through; `wire` is the length of the body received so far.
+    fn push<'a>(&mut self, data: Cow<'a, [u8]>, wire: u64) -> Result<Cow<'a, [u8]>> {
+        match self {
+            Stage::CountWire(count) => *count += data.len() as u64,
+            Stage::WireChecksum { md5, .. } => md5.update(&data),
+            Stage::Decode(decoder) => return decoder.push(&data).map(Cow::Owned),
+            Stage::Ratio { max, decoded } => {
+                *decoded += data.len() as u64;
+                if *decoded > max.saturating_mul(wire).max(RATIO_GRACE) {
+                    return Err(DecompressionBombSnafu { wire_bytes: wire, decoded_bytes: *decoded }.build());
+                }
+            }
+            Stage::Limit { limit, seen } => {
+                *seen += data.len() as u64;
+                if *seen > *limit {
+                    return Err(BodyTooLargeSnafu { length: None, limit: *limit }.build());
+                }
+            }
+            Stage::Budget { reservation, seen } => {
+                *seen += data.len() as u64;
+                reservation.grow_to(*seen)?;
+            }
+            Stage::DecodedChecksum { sha256, .. } => sha256.update(&data),
+        }
+        Ok(data)
+    }
+
+    /// Take the last of the body, `rest`, and check what can only be checked
+    /// at its end.
+    fn finish(&mut self, rest: Vec<u8>, wire: u64) -> Result<Vec<u8>> {
+        let rest = self.push(Cow::Owned(rest), wire)?.into_owned();
+        match self {
+            Stage::WireChecksum { md5, expected } => {
+                let actual = base64_simd::STANDARD.encode_to_string(std::mem::replace(md5, Md5::new()).finish());
+                if actual != *expected {
+                    return Err(mismatch("md5", expected.clone(), actual));
+                }
+            }
+            Stage::Decode(decoder) => {
+                let mut rest = rest;
+                rest.extend(decoder.finish()?);
+                return Ok(rest);
+            }
+            Stage::DecodedChecksum { sha256, expected } => {
+                let actual = sha256.clone().finish();
+                if actual.as_ref() != expected {
+                    return Err(mismatch("sha256", hex(expected), hex(actual.as_ref())));
+                }
+            }
+            Stage::CountWire(_) | Stage::Ratio { .. } | Stage::Limit { .. } | Stage::Budget { .. } => {}
+        }
+        Ok(rest)
+    }
+}
+
+fn mismatch(algorithm: &str, expected: String, actual: String) -> ZjhttpcError {
+    ChecksumMismatchSnafu { algorithm, expected, actual }.build()
+}
+
+fn hex(digest: &[u8]) -> String {
+    digest.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// The stages one response body runs through, see the module docs.
+pub(crate) struct BodyPipeline {
+    stages: Vec<Stage>,
+    /// Error of a stage after a pending wire checksum, reported at the end.
+    held: Option<ZjhttpcError>,
+}
+
+impl BodyPipeline {
+    /// The pipeline for reading `resp`'s body, limited to `cap` decoded bytes
+    /// besides any limit the response's settings set.
+    pub(crate) fn for_response(resp: &Response, cap: u64) -> Self {
+        BodyPipeline::new(resp.effective_config.as_ref(), &resp.headers, cap)
+    }
+
+    /// Stages for a body with response headers `headers`, read with `cfg`;
+    /// without settings only the counting and `cap` apply.
+    pub(crate) fn new(cfg: Option<&EffectiveConfig>, headers: &HeaderMap, cap: u64) -> Self {
+        let mut stages = vec![Stage::CountWire(0)];
+        if cfg.is_some_and(|c| c.verify_content_md5)
+            && let Some(expected) = headers.get(crate::header::CONTENT_MD5)
+        {
+            stages.push(Stage::WireChecksum { md5: Md5::new(), expected: expected.trim().to_string() });
+        }
+        let encodings = headers.get_all(crate::header::CONTENT_ENCODING).into_iter().flatten();
+        if cfg.is_some_and(|c| c.auto_decompress)
+            && let Some(codings) = crate::decompress::codings(encodings.map(String::as_str))
+            && !codings.is_empty()
+        {
+            stages.push(Stage::Decode(Decoder::new(&codings)));
+            if let Some(max) = cfg.and_then(|c| c.max_decompression_ratio) {
+                stages.push(Stage::Ratio { max, decoded: 0 });
+            }
+        }
+        let limit = cfg.and_then(|c| c.max_body_bytes).map_or(cap, |max| max.min(cap));
+        if limit < u64::MAX {
+            stages.push(Stage::Limit { limit, seen: 0 });
+        }
+        if let Some(expected) = cfg.and_then(|c| c.expected_sha256) {
+            stages.push(Stage::DecodedChecksum { sha256: ring::digest::Context::new(&ring::digest::SHA256), expected });
+        }
+        BodyPipeline { stages, held: None }
+    }
+
+    /// Charge the decoded bytes to `reservation`, which already covers what
+    /// the reader set aside up front.
+    pub(crate) fn charge_to(&mut self, reservation: Reservation) {
+        let at = self.stages.iter().position(|s| s.kind() == StageKind::DecodedChecksum).unwrap_or(self.stages.len());
+        self.stages.insert(at, Stage::Budget { reservation, seen: 0 });
+    }
+
+    /// Whether what comes out differs in length from what goes in.
+    pub(crate) fn decodes(&self) -> bool {
+        self.stages.iter().any(|stage| matches!(stage, Stage::Decode(_)))
+    }
+
+    /// Most decoded bytes the body may have; `u64::MAX` for no limit.
+    pub(crate) fn limit(&self) -> u64 {
+        self.stages
+            .iter()
+            .find_map(|stage| match stage {
+                Stage::Limit { limit, .. } => Some(*limit),
+                _ => None,
+            })
+            .unwrap_or(u64::MAX)
+    }
+
+    /// Body bytes received so far.
+    pub(crate) fn wire_len(&self) -> u64 {
+        match self.stages.first() {
+            Some(Stage::CountWire(count)) => *count,
+            _ => 0,
+        }
+    }
+
+    /// Run the next piece of the body as received through the stages and
+    /// return what comes out; unchanged input comes back borrowed.
+    pub(crate) fn push<'a>(&mut self, wire: &'a [u8]) -> Result<Cow<'a, [u8]>> {
+        let received = self.wire_len() + wire.len() as u64;
+        let mut data = Cow::Borrowed(wire);
+        for i in 0..self.runnable() {
+            data = match self.stages[i].push(data, received) {
+                Ok(data) => data,
+                Err(e) => return self.fail_at(i, e).map(|()| Cow::Borrowed(&[][..])),
+            };
+        }
+        if self.held.is_some() {
+            return Ok(Cow::Borrowed(&[]));
+        }
+        Ok(data)
+    }
+
+    /// The body has ended: flush the stages and run the end-of-body checks,
+    /// first stage first. Returns the last bytes to hand out.
+    pub(crate) fn finish(&mut self) -> Result<Vec<u8>> {
+        let received = self.wire_len();
+        let mut data = Vec::new();
+        for i in 0..self.runnable() {
+            data = self.stages[i].finish(data, received)?;
+        }
+        match self.held.take() {
+            Some(err) => Err(err),
+            None => Ok(data),
+        }
+    }
+
+    /// How many stages still run: all of them, or once an error is held only
+    /// those up to the wire checksum.
+    fn runnable(&self) -> usize {
+        match self.held {
+            Some(_) => self.stages.iter().position(|s| s.kind() == StageKind::WireChecksum).map_or(0, |i| i + 1),
+            None => self.stages.len(),
+        }
+    }
+
+    /// Hold the error `err` of stage `i` when a wire checksum comes before
+    /// it, otherwise return it.
+    fn fail_at(&mut self, i: usize, err: ZjhttpcError) -> Result<()> {
+        if self.stages[..i].iter().any(|s| s.kind() == StageKind::WireChecksum) {
+            self.held = Some(err);
+            return Ok(());
+        }
+        Err(err)
+    }
+}
+
+/// An async reader of a body through a [`BodyPipeline`]. Stage errors come
+/// out as `io::Error`s wrapping the `ZjhttpcError`, which converting back
+/// with `?` restores.
+pub(crate) struct PipelineReader<R> {
+    inner: R,
+    pipeline: BodyPipeline,
+    /// Output that did not fit the caller's buffer yet.
+    pending: Vec<u8>,
+    pending_pos: usize,
+    state: ReaderState,
+    /// Pool and id of the connection the body comes over, see
+    /// [`closing_bombs`](Self::closing_bombs).
+    connection: Option<(ConnectionPool, u64)>,
+}
+
+enum ReaderState {
+    Reading,
+    Done,
+    Failed(ZjhttpcError),
+}
+
+impl<R> PipelineReader<R> {
+    pub(crate) fn new(inner: R, pipeline: BodyPipeline) -> Self {
+        PipelineReader {
+            inner,
+            pipeline,
+            pending: Vec::new(),
+            pending_pos: 0,
+            state: ReaderState::Reading,
+            connection: None,
+        }
+    }
+
+    /// Close connection `id` if it is in `pool` when the body turns out to
+    /// be a decompression bomb: the framed stream pools it as soon as the
+    /// last byte is read, which can be before the bomb is noticed. Before
+    /// that the connection goes down with this reader.
+    pub(crate) fn closing_bombs(mut self, pool: ConnectionPool, id: u64) -> Self {
+        self.connection = Some((pool, id));
+        self
+    }
+
+    pub(crate) fn pipeline(&self) -> &BodyPipeline {
+        &self.pipeline
+    }
+}
+
+impl<R: async_std::io::Read + Unpin> async_std::io::Read for PipelineReader<R> {
+    fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<std::io::Result<usize>> {
+        let this = &mut *self;
+        loop {
+            if this.pending_pos < this.pending.len() {
+                let n = buf.len().min(this.pending.len() - this.pending_pos);
+                buf[..n].copy_from_slice(&this.pending[this.pending_pos..this.pending_pos + n]);
+                this.pending_pos += n;
+                return Poll::Ready(Ok(n));
+            }
+            match &this.state {
+                ReaderState::Reading => {}
+                ReaderState::Done => return Poll::Ready(Ok(0)),
+                ReaderState::Failed(err) => return Poll::Ready(Err(std::io::Error::other(err.clone()))),
+            }
+            if buf.is_empty() {
+                return Poll::Ready(Ok(0));
+            }
+            let n = ready!(Pin::new(&mut this.inner).poll_read(cx, buf))?;
+            let out = if n == 0 {
+                this.state = ReaderState::Done;
+                this.pipeline.finish().map(Cow::Owned)
+            } else {
+                this.pipeline.push(&buf[..n])
+            };
+            match out {
+                // Passed through unchanged: it is in `buf` already.
+                Ok(Cow::Borrowed(out)) if out.len() == n => return Poll::Ready(Ok(n)),
+                Ok(out) => {
+                    this.pending = out.into_owned();
+                    this.pending_pos = 0;
+                }
+                Err(err) => {
+                    if let (ZjhttpcError::DecompressionBomb { .. }, Some((pool, id))) = (&err, &this.connection) {
+                        pool.evict_connection(*id);
+                    }
+                    this.state = ReaderState::Failed(err.clone());
+                    return Poll::Ready(Err(std::io::Error::other(err)));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use async_std::io::ReadExt;
+
+    use super::*;
+    use crate::client::ZJHttpClient;
+    use crate::requestx::Request;
+
+    const TEXT: &[u8] = b"hello hello hello hello hello hello, compressed world";
+
+    fn gzip(data: &[u8]) -> Vec<u8> {
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn content_md5(data: &[u8]) -> String {
+        let mut md5 = Md5::new();
+        md5.update(data);
+        base64_simd::STANDARD.encode_to_string(md5.finish())
+    }
+
+    fn sha256(data: &[u8]) -> [u8; 32] {
+        ring::digest::digest(&ring::digest::SHA256, data).as_ref().try_into().unwrap()
+    }
+
+    /// A pipeline for a gzip response with `Content-MD5: md5`, read by a
+    /// decompressing, MD5-checking client with the request's `configure`.
+    fn pipeline(md5: Option<&str>, configure: impl FnOnce(Request) -> Request) -> BodyPipeline {
+        let client = ZJHttpClient::builder().set_auto_decompress(true).set_verify_content_md5(true).build().unwrap();
+        let req = configure(Request::new("GET", "http://example.com/").unwrap());
+        let cfg = client.effective_config(&req);
+        let mut headers = HeaderMap::new();
+        headers.append("content-encoding", "gzip").unwrap();
+        if let Some(md5) = md5 {
+            headers.append("content-md5", md5).unwrap();
+        }
+        BodyPipeline::new(Some(&cfg), &headers, u64::MAX)
+    }
+
+    /// Run `wire` through in small pieces, as a connection would deliver it.
+    fn run(mut pipeline: BodyPipeline, wire: &[u8]) -> Result<Vec<u8>> {
+        let mut out = Vec::new();
+        for piece in wire.chunks(7) {
+            out.extend_from_slice(&pipeline.push(piece)?);
+        }
+        out.extend(pipeline.finish()?);
+        Ok(out)
+    }
+
+    #[test]
+    fn test_stage_order() {
+        use StageKind::*;
+        let md5 = content_md5(b"x");
+        let cases: [(&str, BodyPipeline, &[StageKind]); 4] = [
+            ("no settings", BodyPipeline::new(None, &HeaderMap::new(), u64::MAX), &[CountWire]),
+            ("capped", BodyPipeline::new(None, &HeaderMap::new(), 10), &[CountWire, Limit]),
+            ("gzip without a checksum header", pipeline(None, |r| r), &[CountWire, Decode, Ratio]),
+            (
+                "everything",
+                pipeline(Some(&md5), |r| r.set_max_body_bytes(10).set_expected_sha256([0; 32])),
+                &[CountWire, WireChecksum, Decode, Ratio, Limit, DecodedChecksum],
+            ),
+        ];
+        for (name, pipeline, expected) in cases {
+            assert_eq!(pipeline.stages.iter().map(Stage::kind).collect::<Vec<_>>(), expected, "{name}");
+        }
+    }
+
+    #[test]
+    fn test_content_md5_covers_the_encoded_body() {
+        let wire = gzip(TEXT);
+        assert_eq!(run(pipeline(Some(&content_md5(&wire)), |r| r), &wire).unwrap(), TEXT);
+
+        let err = run(pipeline(Some(&content_md5(TEXT)), |r| r), &wire).unwrap_err();
+        assert!(matches!(&err, ZjhttpcError::ChecksumMismatch { algorithm, .. } if algorithm == "md5"), "{err:?}");
+    }
+
+    #[test]
+    fn test_limit_applies_to_decoded_bytes() {
+        let bomb = gzip(&vec![b'a'; 100_000]);
+        assert!(bomb.len() < 1000);
+        let err = run(pipeline(Some(&content_md5(&bomb)), |r| r.set_max_body_bytes(1000)), &bomb).unwrap_err();
+        assert!(matches!(err, ZjhttpcError::BodyTooLarge { length: None, limit: 1000, .. }), "{err:?}");
+
+        // Longer on the wire than the limit, shorter once decoded.
+        let small = gzip(b"hi");
+        assert!(small.len() > 10);
+        assert_eq!(run(pipeline(Some(&content_md5(&small)), |r| r.set_max_body_bytes(10)), &small).unwrap(), b"hi");
+    }
+
+    #[test]
+    fn test_ratio_stops_decompression_bombs() {
+        let bomb = gzip(&vec![0; 16 * 1024 * 1024]);
+        let err = run(pipeline(Some(&content_md5(&bomb)), |r| r), &bomb).unwrap_err();
+        let ZjhttpcError::DecompressionBomb { wire_bytes, decoded_bytes, .. } = err else {
+            panic!("{err:?}");
+        };
+        assert!(decoded_bytes > RATIO_GRACE && decoded_bytes > 100 * wire_bytes, "{wire_bytes} -> {decoded_bytes}");
+        assert!(decoded_bytes < 2 * RATIO_GRACE, "stopped late: {decoded_bytes}");
+
+        // Under the grace amount any ratio is fine.
+        let small = vec![0; RATIO_GRACE as usize];
+        assert_eq!(run(pipeline(None, |r| r), &gzip(&small)).unwrap(), small);
+    }
+
+    #[test]
+    fn test_wire_checksum_is_reported_before_later_stages() {
+        let bomb = gzip(&vec![b'a'; 100_000]);
+        let mut corrupt = gzip(TEXT);
+        corrupt[12] ^= 0xff;
+
+        // A bad checksum wins over the limit and the decoding error it explains...
+        for (name, wire) in [("bomb", &bomb), ("corrupt", &corrupt)] {
+            let err = run(pipeline(Some("bm90IHRoZSBkaWdlc3Q="), |r| r.set_max_body_bytes(1000)), wire).unwrap_err();
+            assert!(matches!(err, ZjhttpcError::ChecksumMismatch { .. }), "{name}: {err:?}");
+        }
+        // ...and a good one lets them through.
+        let err = run(pipeline(Some(&content_md5(&bomb)), |r| r.set_max_body_bytes(1000)), &bomb).unwrap_err();
+        assert!(matches!(err, ZjhttpcError::BodyTooLarge { .. }), "{err:?}");
+        let err = run(pipeline(Some(&content_md5(&corrupt)), |r| r), &corrupt).unwrap_err();
+        assert!(matches!(err, ZjhttpcError::Decompress { .. }), "{err:?}");
+        // Without a wire checksum the limit fails at once.
+        let mut limited = pipeline(None, |r| r.set_max_body_bytes(1000));
+        assert!(bomb.chunks(64).any(|piece| limited.push(piece).is_err()));
+    }
+
+    #[test]
+    fn test_sha256_covers_the_decoded_body() {
+        let wire = gzip(TEXT);
+        let md5 = content_md5(&wire);
+        assert_eq!(run(pipeline(Some(&md5), |r| r.set_expected_sha256(sha256(TEXT))), &wire).unwrap(), TEXT);
+
+        let err = run(pipeline(Some(&md5), |r| r.set_expected_sha256(sha256(&wire))), &wire).unwrap_err();
+        let ZjhttpcError::ChecksumMismatch { algorithm, expected, actual, .. } = err else {
+            panic!("{err:?}");
+        };
+        assert_eq!((algorithm.as_str(), expected, actual), ("sha256", hex(&sha256(&wire)), hex(&sha256(TEXT))));
+    }
+
+    #[test]
+    fn test_wire_len_counts_bytes_as_received() {
+        let wire = gzip(TEXT);
+        let mut pipeline = pipeline(None, |r| r);
+        pipeline.push(&wire).unwrap();
+        pipeline.finish().unwrap();
+        assert_eq!(pipeline.wire_len(), wire.len() as u64);
+        assert!(pipeline.decodes());
+    }
+
+    #[test]
+    fn test_reader_hands_out_decoded_bytes_and_errors() {
+        let wire = gzip(TEXT);
+        let mut reader = PipelineReader::new(async_std::io::Cursor::new(wire.clone()), pipeline(None, |r| r));
+        let mut out = Vec::new();
+        async_std::task::block_on(reader.read_to_end(&mut out)).unwrap();
+        assert_eq!(out, TEXT);
+        assert_eq!(reader.pipeline().wire_len(), wire.len() as u64);
+
+        let mut reader = PipelineReader::new(
+            async_std::io::Cursor::new(wire),
+            pipeline(None, |r| r.set_expected_sha256([0; 32])),
+        );
+        let err = async_std::task::block_on(reader.read_to_end(&mut Vec::new())).unwrap_err();
+        let err = ZjhttpcError::from(err);
+        assert!(matches!(err, ZjhttpcError::ChecksumMismatch { .. }), "{err:?}");
+    }
+}
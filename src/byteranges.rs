@@ -0,0 +1,134 @@
+//! Partial content (RFC 7233): `Content-Range` values and
+//! `multipart/byteranges` bodies.
+
+use crate::error::{InvalidByteRangesSnafu, Result};
+
+/// One range of a 206 Partial Content response.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ByteRangePart {
+    /// First and last byte position, both inclusive.
+    pub range: (u64, u64),
+    /// Complete length of the representation; `None` when the server sent `*`.
+    pub total: Option<u64>,
+    pub data: Vec<u8>,
+}
+
+/// Parse a `Content-Range` value such as `bytes 0-99/1000` or `bytes 0-99/*`.
+/// Returns `None` for other units, unsatisfied ranges (`bytes */1000`) and
+/// positions that contradict each other.
+pub fn parse_content_range(value: &str) -> Option<((u64, u64), Option<u64>)> {
+    let rest = value.trim().strip_prefix("bytes ")?;
+    let (range, total) = rest.split_once('/')?;
+    let (first, last) = range.trim().split_once('-')?;
+    let (first, last) = (first.parse::<u64>().ok()?, last.parse::<u64>().ok()?);
+    let total = match total.trim() {
+        "*" => None,
+        total => Some(total.parse::<u64>().ok()?),
+    };
+    if first > last || total.is_some_and(|total| last >= total) {
+        return None;
+    }
+    Some(((first, last), total))
+}
+
+/// The boundary of a `multipart/byteranges` content type, `None` for any
+/// other media type.
+pub(crate) fn byteranges_boundary(content_type: &str) -> Option<String> {
+    let mut parts = content_type.split(';');
+    let media_type = parts.next()?.trim();
+    if !media_type.eq_ignore_ascii_case("multipart/byteranges") {
+        return None;
+    }
+    parts.find_map(|param| {
+        let (name, value) = param.split_once('=')?;
+        name.trim()
+            .eq_ignore_ascii_case("boundary")
+            .then(|| value.trim().trim_matches('"').to_string())
+    })
+}
+
+/// Split a `multipart/byteranges` body into its parts. Each part's length is
+/// taken from its `Content-Range`, so range data that happens to contain the
+/// boundary is not cut short.
+pub fn parse_byteranges(body: &[u8], boundary: &str) -> Result<Vec<ByteRangePart>> {
+    let delimiter = format!("--{boundary}");
+    let delimiter = delimiter.as_bytes();
+    let start = find(body, delimiter).ok_or_else(|| invalid("no boundary delimiter in body"))?;
+    let mut rest = &body[start + delimiter.len()..];
+    let mut parts = Vec::new();
+    loop {
+        if rest.starts_with(b"--") {
+            return Ok(parts);
+        }
+        rest = rest
+            .strip_prefix(b"\r\n")
+            .ok_or_else(|| invalid("boundary delimiter not followed by CRLF"))?;
+        let headers_end = find(rest, b"\r\n\r\n").ok_or_else(|| invalid("part headers are not terminated"))?;
+        let headers = std::str::from_utf8(&rest[..headers_end]).map_err(|_| invalid("part headers are not UTF-8"))?;
+        let content_range = headers
+            .split("\r\n")
+            .filter_map(|line| line.split_once(':'))
+            .find(|(name, _)| name.trim().eq_ignore_ascii_case("content-range"))
+            .map(|(_, value)| value)
+            .ok_or_else(|| invalid("part without Content-Range"))?;
+        let (range, total) = parse_content_range(content_range)
+            .ok_or_else(|| invalid(&format!("invalid Content-Range: {}", content_range.trim())))?;
+        rest = &rest[headers_end + 4..];
+        let len = (range.1 - range.0)
+            .checked_add(1)
+            .and_then(|len| usize::try_from(len).ok())
+            .ok_or_else(|| invalid("range too large"))?;
+        if rest.len() < len {
+            return Err(invalid(&format!("part {}-{} is truncated", range.0, range.1)));
+        }
+        parts.push(ByteRangePart { range, total, data: rest[..len].to_vec() });
+        rest = rest[len..]
+            .strip_prefix(b"\r\n")
+            .and_then(|after| after.strip_prefix(delimiter))
+            .ok_or_else(|| invalid(&format!("part {}-{} not followed by a boundary", range.0, range.1)))?;
+    }
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+fn invalid(message: &str) -> crate::error::ZjhttpcError {
+    InvalidByteRangesSnafu { message }.build()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_content_range() {
+        assert_eq!(parse_content_range("bytes 0-99/1000"), Some(((0, 99), Some(1000))));
+        assert_eq!(parse_content_range("bytes 5-5/*"), Some(((5, 5), None)));
+        assert_eq!(parse_content_range("bytes */1000"), None);
+        assert_eq!(parse_content_range("bytes 10-5/1000"), None);
+        assert_eq!(parse_content_range("bytes 0-1000/1000"), None);
+        assert_eq!(parse_content_range("items 0-1/2"), None);
+    }
+
+    #[test]
+    fn test_byteranges_boundary() {
+        assert_eq!(byteranges_boundary("multipart/byteranges; boundary=THIS").as_deref(), Some("THIS"));
+        assert_eq!(byteranges_boundary("Multipart/ByteRanges; boundary=\"a b\"").as_deref(), Some("a b"));
+        assert_eq!(byteranges_boundary("multipart/form-data; boundary=x"), None);
+    }
+
+    #[test]
+    fn test_part_data_may_contain_the_delimiter() {
+        let body = b"--B\r\nContent-Range: bytes 0-6/10\r\n\r\n\r\n--B\r\n\r\n--B--\r\n";
+        let parts = parse_byteranges(body, "B").unwrap();
+        assert_eq!(parts, vec![ByteRangePart { range: (0, 6), total: Some(10), data: b"\r\n--B\r\n".to_vec() }]);
+    }
+
+    #[test]
+    fn test_truncated_part_is_rejected() {
+        let body = b"--B\r\nContent-Range: bytes 0-99/100\r\n\r\nshort\r\n--B--\r\n";
+        let err = parse_byteranges(body, "B").unwrap_err();
+        assert!(err.to_string().contains("part 0-99 is truncated"), "{err}");
+    }
+}
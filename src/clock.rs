@@ -0,0 +1,39 @@
+//! Time source for the client's time-dependent parts.
+//!
+//! Pool idle eviction, warm-connection maintenance and the request/response
+//! timestamps used for `Age` calculations read time through the client's
+//! [`Clock`], so tests can replace it with a manually advanced one
+//! (`testing::MockClock`). I/O timeouts (connect, header and body reads) are
+//! not affected: they bound real socket waits.
+
+use std::time::{Duration, Instant, SystemTime};
+
+use futures::future::BoxFuture;
+
+/// Source of the current time and of sleeps.
+pub trait Clock: Send + Sync + 'static {
+    /// Monotonic time, for measuring durations.
+    fn now_instant(&self) -> Instant;
+    /// Wall-clock time, for timestamps compared with HTTP dates.
+    fn now_system(&self) -> SystemTime;
+    /// Completes once `duration` has passed on this clock.
+    fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()>;
+}
+
+/// Default clock: the operating system's clocks and `async_std::task::sleep`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now_instant(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn now_system(&self) -> SystemTime {
+        SystemTime::now()
+    }
+
+    fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()> {
+        Box::pin(async_std::task::sleep(duration))
+    }
+}
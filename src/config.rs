@@ -0,0 +1,650 @@
+//! The settings that actually apply to one request.
+//!
+//! Most knobs exist both on [`ZJHttpClient`] (the `global_*` fields) and on
+//! [`Request`]. [`EffectiveConfig::resolve`] is the single place where the two
+//! are merged; `send()` resolves once per request and every later stage reads
+//! from the result, so [`ZJHttpClient::effective_config`] always reports what a
+//! real send would use.
+//!
+//! [`check_client`] holds the sanity rules `ZJHttpClientBuilder::build`
+//! applies to the client-level settings.
+
+use std::time::Duration;
+
+use crate::{client::ZJHttpClient, proxy::HttpsProxyOption, requestx::Request, response::BodyBufferBounds, throttle::Rate};
+
+/// Where the root certificates for a TLS connection come from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum TrustStoreSource {
+    /// The operating system's native certificates.
+    System,
+    /// `ZJHttpClient::global_trust_store_pem`, or roots swapped in with
+    /// `ZJHttpClient::reload_trust_store`.
+    Client,
+    /// `Request::trust_store_pem`.
+    Request,
+}
+
+/// Limits of the client's connection pool.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct PoolLimits {
+    pub max_per_key: usize,
+    pub max_total: usize,
+    pub idle_timeout: Duration,
+}
+
+impl PoolLimits {
+    pub fn new(max_per_key: usize, max_total: usize, idle_timeout: Duration) -> Self {
+        PoolLimits { max_per_key, max_total, idle_timeout }
+    }
+}
+
+/// Request settings after applying request-level overrides on top of the
+/// client defaults. A request-level value always wins when it is set.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct EffectiveConfig {
+    /// Bounds DNS resolution and the transport connect (or proxy CONNECT).
+    pub connect_timeout: Duration,
+    pub send_header_timeout: Duration,
+    pub read_header_timeout: Duration,
+    /// Until the first byte of the response; `None` for no separate limit.
+    pub first_byte_timeout: Option<Duration>,
+    /// From the first byte of the response until its head is complete;
+    /// `None` for no separate limit.
+    pub header_complete_timeout: Option<Duration>,
+    /// `None` means reading the body is not time limited.
+    pub read_body_timeout: Option<Duration>,
+    /// Bounds the whole `send()` and then the body read; `None` for no
+    /// overall limit.
+    pub total_timeout: Option<Duration>,
+    /// Time after which no further redirect hop or stale-connection retry is
+    /// started, measured from the start of the first attempt; `None` for no
+    /// limit.
+    pub max_total_attempt_duration: Option<Duration>,
+    /// Proxy the request goes through, `None` for a direct connection.
+    pub proxy: Option<HttpsProxyOption>,
+    /// A plain `http` request handed to `proxy` with an absolute-form
+    /// request-target (`GET http://host/path HTTP/1.1`) instead of through a
+    /// `CONNECT` tunnel.
+    pub proxy_forwarding: bool,
+    pub trust_store: TrustStoreSource,
+    pub max_header_bytes: usize,
+    pub max_request_line_bytes: usize,
+    pub pool: PoolLimits,
+    /// Pace for the request body, `None` for unlimited.
+    pub upload_rate: Option<Rate>,
+    /// Pace for the response body, `None` for unlimited.
+    pub download_rate: Option<Rate>,
+    /// Headers the client writes after the request's own headers, in order.
+    /// A `Host` entry, only added in passthrough mode, is written before them.
+    pub auto_headers: Vec<(&'static str, String)>,
+    /// Whether gzip and deflate bodies are decoded by the body readers of
+    /// `Response`.
+    pub auto_decompress: bool,
+    /// Copied from `ClientInner::max_decompression_ratio`.
+    pub max_decompression_ratio: Option<u64>,
+    /// Whether bodies are checked against their `Content-MD5` header.
+    pub verify_content_md5: bool,
+    /// Longest decoded body the body readers hand out, `None` for unlimited.
+    pub max_body_bytes: Option<u64>,
+    /// Copied from `Request::expected_sha256`.
+    pub expected_sha256: Option<[u8; 32]>,
+    /// Copied from `ClientInner::body_buffer_bounds`.
+    pub body_buffer_bounds: BodyBufferBounds,
+}
+
+impl EffectiveConfig {
+    pub(crate) fn resolve(client: &ZJHttpClient, req: &Request) -> Self {
+        let trust_store = if req.trust_store_pem.is_some() {
+            TrustStoreSource::Request
+        } else if client.global_trust_store_pem.is_some() || client.tls_config.is_reloaded() {
+            TrustStoreSource::Client
+        } else {
+            TrustStoreSource::System
+        };
+        let proxy = req
+            .proxy
+            .as_ref()
+            .or(client.global_proxy.as_ref())
+            .or_else(|| client.proxy_env.as_ref().and_then(|env| env.proxy_for(&req.url)))
+            .cloned();
+        let proxy_forwarding = proxy.is_some() && req.url.scheme() == "http";
+        let mut auto_headers = auto_headers(req, client.auto_decompress);
+        if proxy_forwarding
+            && let Some(auth) = proxy.as_ref().and_then(HttpsProxyOption::authorization)
+            && !req.headers.contains_key("proxy-authorization")
+        {
+            auto_headers.push(("Proxy-Authorization", auth));
+        }
+        // A Cookie header set on the request replaces the jar's.
+        if let Some(jar) = &client.cookie_jar
+            && !req.passthrough
+            && !req.headers.contains_key("cookie")
+            && let Some(cookies) = jar.header_value(&req.url, client.clock.now_system())
+        {
+            auto_headers.push(("Cookie", cookies));
+        }
+        EffectiveConfig {
+            connect_timeout: req.connect_timeout.unwrap_or(client.global_connect_timeout),
+            send_header_timeout: req.send_header_timeout.unwrap_or(client.global_send_header_timeout),
+            read_header_timeout: req.read_header_timeout.unwrap_or(client.global_read_header_timeout),
+            first_byte_timeout: req.first_byte_timeout.or(client.global_first_byte_timeout),
+            header_complete_timeout: req.header_complete_timeout.or(client.global_header_complete_timeout),
+            read_body_timeout: req.read_body_timeout.or(client.global_read_body_timeout),
+            total_timeout: req.total_timeout.or(client.global_total_timeout),
+            max_total_attempt_duration: req.max_total_attempt_duration.or(client.global_max_total_attempt_duration),
+            proxy,
+            proxy_forwarding,
+            trust_store,
+            max_header_bytes: client.global_max_header_bytes,
+            max_request_line_bytes: client.global_max_request_line_bytes,
+            pool: PoolLimits {
+                max_per_key: client.connection_pool.max_per_key,
+                max_total: client.connection_pool.max_total,
+                idle_timeout: client.connection_pool.idle_timeout,
+            },
+            upload_rate: req.upload_rate.or(client.global_upload_rate),
+            download_rate: req.download_rate.or(client.global_download_rate),
+            auto_headers,
+            auto_decompress: client.auto_decompress,
+            max_decompression_ratio: client.max_decompression_ratio,
+            verify_content_md5: client.verify_content_md5,
+            max_body_bytes: req.max_body_bytes.or(client.global_max_body_bytes),
+            expected_sha256: req.expected_sha256,
+            body_buffer_bounds: client.body_buffer_bounds,
+        }
+    }
+}
+
+fn auto_headers(req: &Request, auto_decompress: bool) -> Vec<(&'static str, String)> {
+    if req.passthrough {
+        return passthrough_headers(req);
+    }
+    let mut headers = Vec::new();
+    if let Some(ct) = &req.content_type
+        && !req.headers.contains_key("content-type")
+    {
+        headers.push(("Content-Type", ct.to_string()));
+    }
+    if req.use_chunked {
+        headers.push(("Transfer-Encoding", "chunked".to_string()));
+    } else if crate::methods::expects_body(req.method) || req.has_body() {
+        headers.push(("Content-Length", req.content_length.to_string()));
+    }
+    if auto_decompress && !req.headers.contains_key("accept-encoding") {
+        headers.push(("Accept-Encoding", "gzip, deflate".to_string()));
+    }
+    headers.push(("Connection", "keep-alive".to_string()));
+    headers
+}
+
+/// The headers HTTP/1.1 cannot do without, when the caller did not set
+/// them; see [`Request::passthrough_mode`].
+fn passthrough_headers(req: &Request) -> Vec<(&'static str, String)> {
+    let mut headers = Vec::new();
+    if !req.headers.contains_key("host") {
+        let host = req.url.host_str().unwrap_or_default();
+        let host = match req.url.port() {
+            Some(port) => format!("{host}:{port}"),
+            None => host.to_string(),
+        };
+        headers.push(("Host", host));
+    }
+    if !req.headers.contains_key("content-length") && !req.headers.contains_key("transfer-encoding") {
+        if req.use_chunked {
+            headers.push(("Transfer-Encoding", "chunked".to_string()));
+        } else if crate::methods::expects_body(req.method) || req.has_body() {
+            headers.push(("Content-Length", req.content_length.to_string()));
+        }
+    }
+    headers
+}
+
+/// One problem `ZJHttpClientBuilder::build` found in a client configuration.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ConfigIssue {
+    /// The client fields involved, e.g. `["global_connect_timeout"]`.
+    pub fields: Vec<&'static str>,
+    /// What is wrong with them.
+    pub problem: String,
+    /// How to fix it.
+    pub fix: String,
+}
+
+impl std::fmt::Display for ConfigIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {} ({})", self.fields.join(", "), self.problem, self.fix)
+    }
+}
+
+fn issue(fields: &[&'static str], problem: impl Into<String>, fix: impl Into<String>) -> ConfigIssue {
+    ConfigIssue { fields: fields.to_vec(), problem: problem.into(), fix: fix.into() }
+}
+
+/// Shortest request line there is: `GET / HTTP/1.1`.
+const MIN_REQUEST_LINE_BYTES: usize = 14;
+
+/// Check the client-level settings. Returns the errors, which make
+/// `build()` fail, and the warnings: legal but most likely unintended.
+pub(crate) fn check_client(client: &ZJHttpClient) -> (Vec<ConfigIssue>, Vec<ConfigIssue>) {
+    let mut errors = Vec::new();
+    let mut warnings = Vec::new();
+
+    let timeouts = [
+        ("global_connect_timeout", Some(client.global_connect_timeout)),
+        ("global_send_header_timeout", Some(client.global_send_header_timeout)),
+        ("global_read_header_timeout", Some(client.global_read_header_timeout)),
+        ("global_read_body_timeout", client.global_read_body_timeout),
+        ("global_first_byte_timeout", client.global_first_byte_timeout),
+        ("global_header_complete_timeout", client.global_header_complete_timeout),
+        ("global_total_timeout", client.global_total_timeout),
+    ];
+    for (field, timeout) in timeouts {
+        match timeout {
+            Some(Duration::ZERO) => errors.push(issue(
+                &[field],
+                "is zero, so every request would time out immediately",
+                "use a positive duration, or leave it unset for the default",
+            )),
+            Some(timeout) if timeout < Duration::from_secs(1) => warnings.push(issue(
+                &[field],
+                format!("is {timeout:?}, short enough to fail on ordinary network latency"),
+                "use at least one second unless the peer is known to be local",
+            )),
+            _ => {}
+        }
+    }
+
+    if client.global_max_header_bytes == 0 {
+        errors.push(issue(
+            &["global_max_header_bytes"],
+            "is zero, so no response head can be read",
+            "use the default of 64 KiB or another positive limit",
+        ));
+    } else if client.global_max_header_bytes < 1024 {
+        warnings.push(issue(
+            &["global_max_header_bytes"],
+            format!("is {} bytes, less than many servers send in a single Set-Cookie", client.global_max_header_bytes),
+            "use at least 1 KiB",
+        ));
+    }
+    if client.global_max_request_line_bytes < MIN_REQUEST_LINE_BYTES {
+        errors.push(issue(
+            &["global_max_request_line_bytes"],
+            format!(
+                "is {} bytes, shorter than the shortest request line (`GET / HTTP/1.1`, {MIN_REQUEST_LINE_BYTES} bytes)",
+                client.global_max_request_line_bytes
+            ),
+            "use the default of 8 KiB or another limit that fits the longest URL you send",
+        ));
+    }
+
+    let pool = &client.connection_pool;
+    if pool.max_per_key > pool.max_total {
+        warnings.push(issue(
+            &["connection_pool.max_per_key", "connection_pool.max_total"],
+            format!(
+                "the per-host limit ({}) is above the total limit ({}), so it is never reached",
+                pool.max_per_key, pool.max_total
+            ),
+            "lower max_per_key to at most max_total",
+        ));
+    }
+    if pool.idle_timeout.is_zero() && pool.max_total > 0 {
+        warnings.push(issue(
+            &["connection_pool.idle_timeout", "connection_pool.max_total"],
+            "connections are pooled but expire immediately, so none is ever reused",
+            "set max_total to 0 to disable pooling, or use a positive idle timeout",
+        ));
+    }
+
+    if client.https_only
+        && let Some(proxy) = &client.global_proxy
+        && proxy.url.scheme() == "http"
+        && proxy.cred.is_some()
+    {
+        errors.push(issue(
+            &["https_only", "global_proxy"],
+            format!("the proxy {} takes credentials over plain http, which https_only is meant to rule out", proxy.addr),
+            "use an https:// proxy URL, or a proxy without credentials",
+        ));
+    }
+
+    if client.dns_cache.as_ref().is_some_and(|cache| cache.ttl().is_zero()) {
+        errors.push(issue(
+            &["dns_cache"],
+            "has a zero TTL, so it never answers a lookup",
+            "use a positive TTL, or dns_cache_ttl(None) to turn the cache off",
+        ));
+    }
+
+    if client.global_max_body_bytes == Some(0) {
+        errors.push(issue(
+            &["global_max_body_bytes"],
+            "is zero, so only empty bodies can be read",
+            "use a positive limit, or leave it unset for no limit",
+        ));
+    }
+
+    if client.max_decompression_ratio == Some(0) {
+        errors.push(issue(
+            &["max_decompression_ratio"],
+            "is zero, so every compressed body fails",
+            "use a ratio of at least 1 (100 by default), or None for no limit",
+        ));
+    }
+
+    let bounds = client.body_buffer_bounds;
+    if bounds.min == 0 || bounds.min > bounds.max {
+        errors.push(issue(
+            &["body_buffer_bounds.min", "body_buffer_bounds.max"],
+            format!("min ({}) must be positive and at most max ({})", bounds.min, bounds.max),
+            "use BodyBufferBounds::default() (8 KiB to 1 MiB), or equal min and max for a fixed size",
+        ));
+    }
+
+    (errors, warnings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::methods;
+
+    fn client() -> ZJHttpClient {
+        ZJHttpClient::builder()
+            .set_global_connect_timeout(Duration::from_secs(1))
+            .set_global_send_header_timeout(Duration::from_secs(2))
+            .set_global_read_header_timeout(Duration::from_secs(3))
+            .set_global_read_body_timeout(Duration::from_secs(4))
+            .set_global_proxy(HttpsProxyOption::new("http://127.0.0.1:8080").unwrap())
+            .set_global_trust_store_pem(crate::misc::TrustStorePem::Bytes(Vec::new()))
+            .build()
+            .unwrap()
+    }
+
+    fn request() -> Request {
+        Request::new(methods::GET, "http://example.com/").unwrap()
+    }
+
+    #[test]
+    fn test_client_defaults_apply_without_overrides() {
+        let cfg = client().effective_config(&request());
+        assert_eq!(cfg.connect_timeout, Duration::from_secs(1));
+        assert_eq!(cfg.send_header_timeout, Duration::from_secs(2));
+        assert_eq!(cfg.read_header_timeout, Duration::from_secs(3));
+        assert_eq!(cfg.read_body_timeout, Some(Duration::from_secs(4)));
+        assert_eq!(cfg.proxy.unwrap().url.host_str(), Some("127.0.0.1"));
+        assert_eq!(cfg.trust_store, TrustStoreSource::Client);
+    }
+
+    #[test]
+    fn test_request_overrides_win() {
+        let req = request()
+            .set_connect_timeout(Duration::from_secs(10))
+            .set_send_header_timeout(Duration::from_secs(20))
+            .set_read_header_timeout(Duration::from_secs(30))
+            .set_read_body_timeout(Duration::from_secs(40))
+            .set_proxy_from_url("http://127.0.0.2:3128")
+            .unwrap();
+        let mut req = req;
+        req.trust_store_pem = Some(crate::misc::TrustStorePem::Bytes(Vec::new()));
+        let cfg = client().effective_config(&req);
+        assert_eq!(cfg.connect_timeout, Duration::from_secs(10));
+        assert_eq!(cfg.send_header_timeout, Duration::from_secs(20));
+        assert_eq!(cfg.read_header_timeout, Duration::from_secs(30));
+        assert_eq!(cfg.read_body_timeout, Some(Duration::from_secs(40)));
+        assert_eq!(cfg.proxy.unwrap().url.host_str(), Some("127.0.0.2"));
+        assert_eq!(cfg.trust_store, TrustStoreSource::Request);
+    }
+
+    #[test]
+    fn test_only_plain_http_is_forwarded() {
+        let client = ZJHttpClient::builder()
+            .set_global_proxy(HttpsProxyOption::new("http://u:p@127.0.0.1:8080").unwrap())
+            .build()
+            .unwrap();
+        let cfg = client.effective_config(&request());
+        assert!(cfg.proxy_forwarding);
+        assert!(cfg.auto_headers.contains(&("Proxy-Authorization", "Basic dTpw".to_string())));
+
+        let cfg = client.effective_config(&Request::new(methods::GET, "https://example.com/").unwrap());
+        assert!(!cfg.proxy_forwarding, "https goes through a CONNECT tunnel");
+        assert!(!cfg.auto_headers.iter().any(|(key, _)| *key == "Proxy-Authorization"));
+    }
+
+    #[test]
+    fn test_builtin_defaults() {
+        let cfg = ZJHttpClient::builder().build().unwrap().effective_config(&request());
+        assert_eq!(cfg.connect_timeout, Duration::from_secs(3));
+        assert_eq!(cfg.read_body_timeout, None);
+        assert!(cfg.proxy.is_none());
+        assert_eq!(cfg.trust_store, TrustStoreSource::System);
+        assert_eq!(cfg.max_header_bytes, 64 * 1024);
+        assert_eq!(cfg.max_request_line_bytes, 8 * 1024);
+        assert_eq!(
+            cfg.pool,
+            PoolLimits { max_per_key: 30, max_total: 1000, idle_timeout: Duration::from_secs(90) }
+        );
+    }
+
+    #[test]
+    fn test_pool_limits_follow_client() {
+        let client = ZJHttpClient::builder()
+            .build()
+            .unwrap()
+            .set_pool_config(2, 5, Duration::from_secs(7));
+        let cfg = client.effective_config(&request());
+        assert_eq!(cfg.pool, PoolLimits { max_per_key: 2, max_total: 5, idle_timeout: Duration::from_secs(7) });
+    }
+
+    #[test]
+    fn test_auto_headers() {
+        let req = request()
+            .set_body_string("hello")
+            .set_content_type("text/plain")
+            .put_expect_continue();
+        let cfg = client().effective_config(&req);
+        assert_eq!(
+            cfg.auto_headers,
+            vec![
+                ("Content-Type", "text/plain".to_string()),
+                ("Content-Length", "5".to_string()),
+                ("Connection", "keep-alive".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_explicit_content_type_header_suppresses_auto_header() {
+        let mut req = request().set_header("Content-Type", "application/json");
+        req.content_type = Some("text/plain".into());
+        req.use_chunked = true;
+        let cfg = client().effective_config(&req);
+        assert_eq!(
+            cfg.auto_headers,
+            vec![
+                ("Transfer-Encoding", "chunked".to_string()),
+                ("Connection", "keep-alive".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_auto_decompress_asks_for_gzip_unless_the_request_chose() {
+        let decompressing = ZJHttpClient::builder().set_auto_decompress(true).build().unwrap();
+        let cfg = decompressing.effective_config(&request());
+        assert!(cfg.auto_decompress);
+        assert_eq!(
+            cfg.auto_headers,
+            vec![
+                ("Accept-Encoding", "gzip, deflate".to_string()),
+                ("Connection", "keep-alive".to_string()),
+            ]
+        );
+
+        let cfg = decompressing.effective_config(&request().set_header("Accept-Encoding", "gzip"));
+        assert_eq!(cfg.auto_headers, vec![("Connection", "keep-alive".to_string())]);
+        assert!(!client().effective_config(&request()).auto_decompress);
+    }
+
+    /// Fields named by each issue, in order.
+    fn fields(issues: &[ConfigIssue]) -> Vec<Vec<&'static str>> {
+        issues.iter().map(|issue| issue.fields.clone()).collect()
+    }
+
+    #[test]
+    fn test_config_rules() {
+        use crate::client::ConnectionPoolInner;
+        use std::sync::Arc;
+
+        type Configure = fn(&mut crate::client::ZJHttpClientBuilder);
+        fn pool(max_per_key: usize, max_total: usize, idle_timeout: Duration) -> Arc<ConnectionPoolInner> {
+            Arc::new(ConnectionPoolInner::new(max_per_key, max_total, idle_timeout))
+        }
+        // (rule, configure, fields of the expected errors, fields of the expected warnings)
+        let table: &[(&str, Configure, &[&str], &[&str])] = &[
+            ("defaults are clean", |_| {}, &[], &[]),
+            ("zero connect timeout", |b| { b.set_global_connect_timeout(Duration::ZERO); }, &["global_connect_timeout"], &[]),
+            ("zero send header timeout", |b| { b.set_global_send_header_timeout(Duration::ZERO); }, &["global_send_header_timeout"], &[]),
+            ("zero read header timeout", |b| { b.set_global_read_header_timeout(Duration::ZERO); }, &["global_read_header_timeout"], &[]),
+            ("zero read body timeout", |b| { b.set_global_read_body_timeout(Duration::ZERO); }, &["global_read_body_timeout"], &[]),
+            ("zero total timeout", |b| { b.set_global_total_timeout(Duration::ZERO); }, &["global_total_timeout"], &[]),
+            ("sub-second timeout", |b| { b.set_global_connect_timeout(Duration::from_millis(200)); }, &[], &["global_connect_timeout"]),
+            ("one second is fine", |b| { b.set_global_read_body_timeout(Duration::from_secs(1)); }, &[], &[]),
+            ("zero header limit", |b| { b.set_global_max_header_bytes(0); }, &["global_max_header_bytes"], &[]),
+            ("tiny header limit", |b| { b.set_global_max_header_bytes(512); }, &[], &["global_max_header_bytes"]),
+            ("request line limit below minimum", |b| { b.set_global_max_request_line_bytes(13); }, &["global_max_request_line_bytes"], &[]),
+            ("request line limit at minimum", |b| { b.set_global_max_request_line_bytes(14); }, &[], &[]),
+            ("per-key limit above total", |b| { b.set_connection_pool(pool(50, 10, Duration::from_secs(90))); }, &[], &["connection_pool.max_per_key"]),
+            ("pooled but zero idle timeout", |b| { b.set_connection_pool(pool(5, 10, Duration::ZERO)); }, &[], &["connection_pool.idle_timeout"]),
+            ("pooling disabled", |b| { b.set_connection_pool(pool(0, 0, Duration::ZERO)); }, &[], &[]),
+            ("body buffer min above max", |b| { b.set_body_buffer_bounds(BodyBufferBounds { min: 2048, max: 1024 }); }, &["body_buffer_bounds.min"], &[]),
+            ("zero body limit", |b| { b.set_global_max_body_bytes(0); }, &["global_max_body_bytes"], &[]),
+            ("zero decompression ratio", |b| { b.set_max_decompression_ratio(0); }, &["max_decompression_ratio"], &[]),
+            ("fixed body buffer", |b| { b.set_body_buffer_bounds(BodyBufferBounds { min: 4096, max: 4096 }); }, &[], &[]),
+            ("https_only with a credentialed http proxy", |b| { b.set_https_only(true).set_global_proxy(HttpsProxyOption::new("http://u:p@127.0.0.1:3128").unwrap()); }, &["https_only"], &[]),
+            ("https_only with an anonymous http proxy", |b| { b.set_https_only(true).set_global_proxy(HttpsProxyOption::new("http://127.0.0.1:3128").unwrap()); }, &[], &[]),
+            ("zero DNS cache TTL", |b| { b.dns_cache_ttl(Some(Duration::ZERO)); }, &["dns_cache"], &[]),
+            ("DNS cache off", |b| { b.dns_cache_ttl(None); }, &[], &[]),
+            ("credentialed http proxy alone", |b| { b.set_global_proxy(HttpsProxyOption::new("http://u:p@127.0.0.1:3128").unwrap()); }, &[], &[]),
+        ];
+        for (rule, configure, expected_errors, expected_warnings) in table {
+            let mut builder = ZJHttpClient::builder();
+            configure(&mut builder);
+            let first = |issues: &[ConfigIssue]| issues.iter().map(|i| i.fields[0]).collect::<Vec<_>>();
+            match builder.build_with_warnings() {
+                Ok((_, warnings)) => {
+                    assert!(expected_errors.is_empty(), "{rule}: expected errors {expected_errors:?}");
+                    assert_eq!(first(&warnings), *expected_warnings, "{rule}");
+                }
+                Err(crate::ZjhttpcError::InvalidConfig { issues, .. }) => {
+                    assert_eq!(first(&issues), *expected_errors, "{rule}");
+                }
+                Err(other) => panic!("{rule}: unexpected {other:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_build_reports_every_issue_at_once() {
+        let err = ZJHttpClient::builder()
+            .set_global_connect_timeout(Duration::ZERO)
+            .set_global_read_header_timeout(Duration::ZERO)
+            .set_global_max_header_bytes(0)
+            .build()
+            .expect_err("three invalid settings");
+        let crate::ZjhttpcError::InvalidConfig { issues, .. } = &err else { panic!("{err:?}") };
+        assert_eq!(
+            fields(issues),
+            [vec!["global_connect_timeout"], vec!["global_read_header_timeout"], vec!["global_max_header_bytes"]]
+        );
+        assert!(issues.iter().all(|issue| !issue.fix.is_empty()));
+        let message = err.to_string();
+        assert!(message.contains("global_connect_timeout: is zero"), "{message}");
+        assert!(message.contains("global_max_header_bytes"), "{message}");
+    }
+
+    /// The framing line `serialize_head` writes for `req`, if any.
+    fn framing_header(req: &Request) -> Option<String> {
+        let head = crate::client::serialize_head(&client().effective_config(req), req).unwrap();
+        String::from_utf8(head)
+            .unwrap()
+            .split("\r\n")
+            .find(|line| line.starts_with("Content-Length:") || line.starts_with("Transfer-Encoding:"))
+            .map(str::to_string)
+    }
+
+    #[test]
+    fn test_content_length_per_method_and_body_state() {
+        let chunked = |req: Request| {
+            let mut req = req.set_body_stream(async_std::io::empty(), 0);
+            req.use_chunked = true;
+            req
+        };
+        type Build = fn(Request) -> Request;
+        let states: [(&str, Build); 4] = [
+            ("absent", |req| req),
+            ("empty", Request::set_body_empty),
+            ("non-empty", |req| req.set_body_string("hi")),
+            ("chunked", chunked),
+        ];
+        let cl0 = Some("Content-Length: 0");
+        let cl2 = Some("Content-Length: 2");
+        let te = Some("Transfer-Encoding: chunked");
+        let bodiless = [None, cl0, cl2, te];
+        let with_body = [cl0, cl0, cl2, te];
+        let table = [
+            (methods::GET, bodiless),
+            (methods::HEAD, bodiless),
+            (methods::DELETE, bodiless),
+            (methods::OPTIONS, bodiless),
+            (methods::POST, with_body),
+            (methods::PUT, with_body),
+            (methods::PATCH, with_body),
+        ];
+        for (method, expected) in table {
+            for ((state, build), expected) in states.iter().zip(expected) {
+                let req = build(Request::new(method, "http://example.com/").unwrap());
+                assert_eq!(framing_header(&req).as_deref(), expected, "{method} with {state} body");
+            }
+        }
+    }
+
+    #[test]
+    fn test_announced_length_counts_as_a_body() {
+        // The split API sets only the length; the body is written later.
+        let req = Request::new(methods::DELETE, "http://example.com/").unwrap().set_content_length(4);
+        assert_eq!(framing_header(&req).as_deref(), Some("Content-Length: 4"));
+    }
+
+    #[async_std::test]
+    async fn test_response_carries_the_config_it_was_sent_with() {
+        use async_std::io::{ReadExt, WriteExt};
+        let listener = async_std::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        async_std::task::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut head = Vec::new();
+            let mut byte = [0u8; 1];
+            while !head.ends_with(b"\r\n\r\n") && stream.read(&mut byte).await.unwrap() == 1 {
+                head.push(byte[0]);
+            }
+            stream.write_all(b"HTTP/1.1 204 No Content\r\nContent-Length: 0\r\n\r\n").await.unwrap();
+        });
+        let client = ZJHttpClient::builder().build().unwrap();
+        let mut req = Request::new(methods::GET, format!("http://127.0.0.1:{port}/"))
+            .unwrap()
+            .set_read_header_timeout(Duration::from_secs(5));
+        let expected = client.effective_config(&req);
+        let resp = client.send(&mut req).await.unwrap();
+        let cfg = resp.effective_config.as_ref().unwrap();
+        assert_eq!(cfg.read_header_timeout, Duration::from_secs(5));
+        assert_eq!(cfg.auto_headers, expected.auto_headers);
+        assert_eq!(cfg.pool, expected.pool);
+    }
+}
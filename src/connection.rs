@@ -0,0 +1,207 @@
+//! A request held open as a handle: the body is written piece by piece,
+//! then the response is read.
+//!
+//! [`ZJHttpClient::connect`] resolves and dials right away;
+//! [`ZJHttpClient::connect_lazy`] only validates the request and serializes
+//! its head, and leaves the network alone until the first body write (or
+//! [`Connection::finish`]). A prepared request that is dropped before that
+//! never resolved a name or opened a connection, so it can be handed to
+//! another component that may still decide not to send.
+//! [`Connection::ensure_connected`] does the resolve and dial early, e.g. to
+//! warm up while the body is still being produced.
+//!
+//! The handle moves through [`ConnectionState`] in one direction:
+//! `Prepared`, `Connected`, `SendingBody`, then `finish` reads the response.
+//! The head is written before the first body byte, and the response is only
+//! read once the body is complete. A step that fails leaves the handle
+//! `Failed`. Dropping a handle that is connected closes the connection.
+
+use std::net::SocketAddr;
+
+use async_std::io::WriteExt;
+
+use crate::{
+    body::Body,
+    client::{
+        ZJHttpClient, pick_or_connect_stream, read_headers_to_resp, resolve_ips, send_header_or_retry,
+        write_chunk, write_chunk_terminator,
+    },
+    config::EffectiveConfig,
+    error::{InvalidRequestSnafu, RequestPhase, Result, ZjhttpcError},
+    requestx::Request,
+    response::Response,
+    stream::BoxedStream,
+    summary::Attempts,
+};
+
+/// Where a [`Connection`] is; see the [module docs](self).
+pub enum ConnectionState {
+    /// Nothing resolved or dialled yet.
+    Prepared,
+    /// A connection is open (or was picked from the pool); the head is not
+    /// written yet.
+    Connected { stream: BoxedStream, addr: SocketAddr, reused: bool },
+    /// The head is written; `remaining` bytes of Content-Length are left,
+    /// ignored for a chunked body.
+    SendingBody { stream: BoxedStream, addr: SocketAddr, reused: bool, remaining: u64 },
+    /// A step failed; the handle can't be used any more.
+    Failed,
+}
+
+impl std::fmt::Debug for ConnectionState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConnectionState::Prepared => f.write_str("Prepared"),
+            ConnectionState::Connected { addr, reused, .. } => {
+                f.debug_struct("Connected").field("addr", addr).field("reused", reused).finish()
+            }
+            ConnectionState::SendingBody { addr, reused, remaining, .. } => f
+                .debug_struct("SendingBody")
+                .field("addr", addr)
+                .field("reused", reused)
+                .field("remaining", remaining)
+                .finish(),
+            ConnectionState::Failed => f.write_str("Failed"),
+        }
+    }
+}
+
+/// A request whose body is written through the handle, from
+/// [`ZJHttpClient::connect`] or [`ZJHttpClient::connect_lazy`].
+pub struct Connection {
+    client: ZJHttpClient,
+    req: Request,
+    cfg: EffectiveConfig,
+    head: Vec<u8>,
+    state: ConnectionState,
+}
+
+impl Connection {
+    /// Validate `req` and serialize its head; nothing goes to the network.
+    /// The body is written through the handle, so `req.body` must be empty;
+    /// its framing comes from `use_chunked` or `content_length`.
+    pub(crate) fn prepare(client: &ZJHttpClient, mut req: Request) -> Result<Self> {
+        if !matches!(req.body, Body::None) {
+            return Err(InvalidRequestSnafu {
+                message: "a Connection's body is written through the handle, not Request::body".to_string(),
+            }
+            .build());
+        }
+        let (cfg, head) = client.prepare_head(&mut req)?;
+        Ok(Connection { client: client.clone(), req, cfg, head, state: ConnectionState::Prepared })
+    }
+
+    /// The request being sent.
+    pub fn request(&self) -> &Request {
+        &self.req
+    }
+
+    /// How far the handle got.
+    pub fn state(&self) -> &ConnectionState {
+        &self.state
+    }
+
+    /// Whether a connection is open, i.e. the handle left `Prepared`.
+    pub fn is_connected(&self) -> bool {
+        matches!(self.state, ConnectionState::Connected { .. } | ConnectionState::SendingBody { .. })
+    }
+
+    /// Resolve and dial now, if that hasn't happened yet.
+    pub async fn ensure_connected(&mut self) -> Result<()> {
+        match self.state {
+            ConnectionState::Prepared => {}
+            ConnectionState::Failed => return Err(failed()),
+            _ => return Ok(()),
+        }
+        self.state = ConnectionState::Failed;
+        let addrs = resolve_ips(&self.client, &self.cfg, &self.req).await?;
+        let (stream, reused, addr) = pick_or_connect_stream(&self.client, &self.cfg, &self.req, &addrs, None).await?;
+        self.state = ConnectionState::Connected { stream, addr, reused };
+        Ok(())
+    }
+
+    /// Connect if needed and write the head, once.
+    async fn ensure_head_written(&mut self) -> Result<()> {
+        self.ensure_connected().await?;
+        if let ConnectionState::SendingBody { .. } = self.state {
+            return Ok(());
+        }
+        let ConnectionState::Connected { stream, addr, reused } =
+            std::mem::replace(&mut self.state, ConnectionState::Failed)
+        else {
+            unreachable!("ensure_connected left the handle connected");
+        };
+        let (stream, reused, _) = send_header_or_retry(
+            &self.client,
+            &self.cfg,
+            &self.req,
+            &self.head,
+            stream,
+            reused,
+            &addr,
+            &mut Attempts::default(),
+        )
+        .await?;
+        self.state = ConnectionState::SendingBody { stream, addr, reused, remaining: self.req.content_length };
+        Ok(())
+    }
+
+    /// Write and flush one piece of the body, connecting and writing the head
+    /// first if this is the first write. With chunked transfer encoding each
+    /// call becomes one chunk; otherwise the total must not exceed the
+    /// declared Content-Length.
+    pub async fn write_body_chunk(&mut self, data: impl AsRef<[u8]>) -> Result<()> {
+        let data = data.as_ref();
+        self.ensure_head_written().await?;
+        let chunked = self.req.use_chunked;
+        let ConnectionState::SendingBody { stream, remaining, .. } = &mut self.state else {
+            unreachable!("the head was just written");
+        };
+        if chunked {
+            write_chunk(stream, data).await.map_err(send_body_error)?;
+        } else {
+            if data.len() as u64 > *remaining {
+                return Err(InvalidRequestSnafu {
+                    message: format!(
+                        "body chunk of {} bytes exceeds the remaining {} bytes of Content-Length",
+                        data.len(),
+                        remaining
+                    ),
+                }
+                .build());
+            }
+            stream.write_all(data).await.map_err(send_body_error)?;
+            *remaining -= data.len() as u64;
+        }
+        stream.flush().await.map_err(send_body_error)
+    }
+
+    /// End the body and read the response head, connecting and writing the
+    /// head first if nothing was written yet.
+    pub async fn finish(mut self) -> Result<Response> {
+        self.ensure_head_written().await?;
+        let ConnectionState::SendingBody { mut stream, addr, reused, remaining } =
+            std::mem::replace(&mut self.state, ConnectionState::Failed)
+        else {
+            unreachable!("the head was just written");
+        };
+        if self.req.use_chunked {
+            write_chunk_terminator(&mut stream).await.map_err(send_body_error)?;
+        } else if remaining > 0 {
+            return Err(InvalidRequestSnafu {
+                message: format!("request body ended {remaining} bytes short of Content-Length"),
+            }
+            .build());
+        }
+        stream.flush().await.map_err(send_body_error)?;
+        read_headers_to_resp(&self.client, &self.cfg, &mut self.req, stream, addr, reused).await
+    }
+}
+
+fn send_body_error(e: std::io::Error) -> ZjhttpcError {
+    ZjhttpcError::from(e).with_phase(RequestPhase::SendBody)
+}
+
+fn failed() -> ZjhttpcError {
+    InvalidRequestSnafu { message: "the connection failed earlier and can't be used".to_string() }.build()
+}
@@ -0,0 +1,47 @@
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use async_std::{future::timeout, net::TcpStream};
+use futures::future::BoxFuture;
+
+use crate::{
+    error::{ConnectionSnafu, ConnectionTimeoutSnafu, Result},
+    stream::BoxedStream,
+};
+
+/// Opens the transport connection to an already resolved address.
+///
+/// `host` is the name the address was resolved from, for implementations that
+/// want to act per host. TLS is layered on top of whatever stream the
+/// connector returns, so a connector only deals with the raw transport.
+/// Connections through a proxy are opened by [`crate::proxy::ProxyConnector`]
+/// and do not go through this trait.
+pub trait Connector: Send + Sync + 'static {
+    fn connect<'a>(
+        &'a self,
+        host: &'a str,
+        addr: SocketAddr,
+        connect_timeout: Duration,
+    ) -> BoxFuture<'a, Result<BoxedStream>>;
+}
+
+/// Default connector: a plain TCP connection bounded by the connect timeout.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TcpConnector;
+
+impl Connector for TcpConnector {
+    fn connect<'a>(
+        &'a self,
+        _host: &'a str,
+        addr: SocketAddr,
+        connect_timeout: Duration,
+    ) -> BoxFuture<'a, Result<BoxedStream>> {
+        Box::pin(async move {
+            match timeout(connect_timeout, TcpStream::connect(addr)).await {
+                Ok(Ok(stream)) => Ok(Box::new(stream) as BoxedStream),
+                Ok(Err(e)) => Err(ConnectionSnafu { message: format!("TCP connection failed: {e}") }.build()),
+                Err(_) => Err(ConnectionTimeoutSnafu { duration: connect_timeout }.build()),
+            }
+        })
+    }
+}
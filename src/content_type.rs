@@ -110,3 +110,116 @@ pub const TEXT_CSV: &str = "text/csv";
 
 /// Markdown content type
 pub const TEXT_MARKDOWN: &str = "text/markdown";
+
+/// How many body bytes [`Response::sniff_content_type`](crate::response::Response::sniff_content_type)
+/// peeks before deciding.
+pub const SNIFF_LEN: usize = 512;
+
+/// What [`sniff`] recognised from the first bytes of a body.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SniffedType {
+    /// Leading `{` or `[` after optional whitespace (and BOM).
+    Json,
+    Gzip,
+    Png,
+    Jpeg,
+    /// An HTML doctype or `<html` tag after optional whitespace (and BOM).
+    Html,
+    /// A UTF-8 byte order mark followed by neither JSON nor HTML.
+    Utf8Text,
+    /// Anything else.
+    OctetStream,
+}
+
+impl SniffedType {
+    /// The media type a server should have sent for this body.
+    pub fn media_type(self) -> &'static str {
+        match self {
+            SniffedType::Json => APPLICATION_JSON,
+            SniffedType::Gzip => APPLICATION_GZIP,
+            SniffedType::Png => IMAGE_PNG,
+            SniffedType::Jpeg => IMAGE_JPEG,
+            SniffedType::Html => TEXT_HTML,
+            SniffedType::Utf8Text => TEXT_PLAIN,
+            SniffedType::OctetStream => APPLICATION_OCTET_STREAM,
+        }
+    }
+}
+
+const UTF8_BOM: &[u8] = b"\xEF\xBB\xBF";
+const GZIP_MAGIC: &[u8] = b"\x1F\x8B";
+const PNG_MAGIC: &[u8] = b"\x89PNG\r\n\x1A\n";
+const JPEG_MAGIC: &[u8] = b"\xFF\xD8\xFF";
+
+/// Guess a body's type from its first bytes. Deterministic and cheap: only
+/// magic numbers and the first non-whitespace byte are looked at, so an
+/// empty or whitespace-only prefix is `OctetStream`.
+pub fn sniff(prefix: &[u8]) -> SniffedType {
+    if prefix.starts_with(GZIP_MAGIC) {
+        return SniffedType::Gzip;
+    }
+    if prefix.starts_with(PNG_MAGIC) {
+        return SniffedType::Png;
+    }
+    if prefix.starts_with(JPEG_MAGIC) {
+        return SniffedType::Jpeg;
+    }
+    let (has_bom, text) = match prefix.strip_prefix(UTF8_BOM) {
+        Some(rest) => (true, rest),
+        None => (false, prefix),
+    };
+    let start = text.iter().position(|b| !b.is_ascii_whitespace()).unwrap_or(text.len());
+    let text = &text[start..];
+    match text.first() {
+        Some(b'{' | b'[') => SniffedType::Json,
+        _ if starts_with_ignore_case(text, b"<!doctype html") || starts_with_ignore_case(text, b"<html") => {
+            SniffedType::Html
+        }
+        _ if has_bom => SniffedType::Utf8Text,
+        _ => SniffedType::OctetStream,
+    }
+}
+
+fn starts_with_ignore_case(haystack: &[u8], needle: &[u8]) -> bool {
+    haystack.len() >= needle.len() && haystack[..needle.len()].eq_ignore_ascii_case(needle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sniff_corpus() {
+        let corpus: &[(&[u8], SniffedType)] = &[
+            (b"{\"a\":1}", SniffedType::Json),
+            (b"[1,2,3]", SniffedType::Json),
+            (b" \r\n\t{\"a\":1}", SniffedType::Json),
+            (b"\xEF\xBB\xBF{\"a\":1}", SniffedType::Json),
+            (b"\xEF\xBB\xBF  [", SniffedType::Json),
+            (b"\x1F\x8B\x08\x00", SniffedType::Gzip),
+            (b"\x89PNG\r\n\x1A\n\x00\x00", SniffedType::Png),
+            (b"\x89PNG", SniffedType::OctetStream),
+            (b"\xFF\xD8\xFF\xE0", SniffedType::Jpeg),
+            (b"<!DOCTYPE html><html>", SniffedType::Html),
+            (b"\n  <!doctype HTML>", SniffedType::Html),
+            (b"<html><body>502</body></html>", SniffedType::Html),
+            (b"\xEF\xBB\xBF<!DOCTYPE html>", SniffedType::Html),
+            (b"\xEF\xBB\xBFhello", SniffedType::Utf8Text),
+            (b"\xEF\xBB\xBF", SniffedType::Utf8Text),
+            (b"<?xml version=\"1.0\"?>", SniffedType::OctetStream),
+            (b"hello", SniffedType::OctetStream),
+            (b"   ", SniffedType::OctetStream),
+            (b"", SniffedType::OctetStream),
+        ];
+        for (prefix, expected) in corpus {
+            assert_eq!(sniff(prefix), *expected, "prefix {:?}", String::from_utf8_lossy(prefix));
+        }
+    }
+
+    #[test]
+    fn test_sniffed_media_types() {
+        assert_eq!(SniffedType::Json.media_type(), APPLICATION_JSON);
+        assert_eq!(SniffedType::Utf8Text.media_type(), TEXT_PLAIN);
+        assert_eq!(SniffedType::OctetStream.media_type(), APPLICATION_OCTET_STREAM);
+    }
+}
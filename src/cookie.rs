@@ -1,3 +1,18 @@
+//! Cookies: parsing and writing `Set-Cookie` and `Cookie` values
+//! ([`parse_set_cookie`], [`SetCookie::to_header_value`],
+//! [`parse_cookie_header`], [`format_cookie_header`]), and the
+//! [`CookieJar`] a client can keep between requests, built on them.
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use tracing::trace;
+use url::Url;
+
+use crate::error::{InvalidHeaderSnafu, Result};
+use crate::log_target::WIRE;
+use crate::public_suffix::is_public_suffix;
+
 /// HTTP Cookie representation with attributes
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Cookie {
@@ -27,46 +42,498 @@ impl Cookie {
     {
         set_cookie_values
             .into_iter()
-            .filter_map(|value| Self::parse_one(value))
+            .filter_map(|value| parse_set_cookie(value).ok())
+            .map(|cookie| Cookie { name: cookie.name, value: cookie.value })
             .collect()
     }
 
-    /// Parse a single Set-Cookie header value
-    fn parse_one(set_cookie_value: &str) -> Option<Self> {
-        // Set-Cookie format: "name=value; Attribute1; Attribute2"
-        // We only care about the name=value part
-        let trimmed = set_cookie_value.trim();
-        if trimmed.is_empty() {
-            return None;
+    /// Format cookies for Cookie header
+    /// Converts Vec<Cookie> to "name=value; name2=value2" format
+    pub fn format_for_request_cookie_header(cookies: &[Self]) -> String {
+        format_cookie_header(cookies.iter().map(|c| (&c.name, &c.value)))
+    }
+}
+
+/// The `SameSite` attribute of a `Set-Cookie`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SameSite {
+    Strict,
+    Lax,
+    None,
+}
+
+impl SameSite {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SameSite::Strict => "Strict",
+            SameSite::Lax => "Lax",
+            SameSite::None => "None",
         }
+    }
+}
+
+/// A parsed `Set-Cookie` value, see [`parse_set_cookie`]. Fields are public
+/// so a gateway can rewrite them and write the result back with
+/// [`to_header_value`](Self::to_header_value).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SetCookie {
+    pub name: String,
+    pub value: String,
+    pub expires: Option<SystemTime>,
+    /// Seconds; zero or less asks to delete the cookie.
+    pub max_age: Option<i64>,
+    /// Without the leading dot servers often add.
+    pub domain: Option<String>,
+    pub path: Option<String>,
+    pub secure: bool,
+    pub http_only: bool,
+    pub same_site: Option<SameSite>,
+    /// Attributes not covered above, in order, as `(name, value)`; `None`
+    /// for a flag without `=`.
+    pub extensions: Vec<(String, Option<String>)>,
+}
 
-        // Split by ';' to separate name=value from attributes
-        let first_part = trimmed.split(';').next()?;
+impl SetCookie {
+    /// A cookie without attributes.
+    pub fn new(name: impl Into<String>, value: impl Into<String>) -> Self {
+        SetCookie {
+            name: name.into(),
+            value: value.into(),
+            expires: None,
+            max_age: None,
+            domain: None,
+            path: None,
+            secure: false,
+            http_only: false,
+            same_site: None,
+            extensions: Vec::new(),
+        }
+    }
 
-        // Split by '=' to get name and value
-        let mut parts = first_part.splitn(2, '=');
-        let name = parts.next()?.trim();
-        let value = parts.next().unwrap_or("").trim();
+    /// The `Set-Cookie` value for this cookie. `Expires` is written as an
+    /// IMF-fixdate.
+    pub fn to_header_value(&self) -> String {
+        let mut out = format!("{}={}", self.name, self.value);
+        if let Some(path) = &self.path {
+            out.push_str(&format!("; Path={path}"));
+        }
+        if let Some(domain) = &self.domain {
+            out.push_str(&format!("; Domain={domain}"));
+        }
+        if let Some(expires) = self.expires {
+            out.push_str(&format!("; Expires={}", httpdate::fmt_http_date(expires.max(UNIX_EPOCH))));
+        }
+        if let Some(max_age) = self.max_age {
+            out.push_str(&format!("; Max-Age={max_age}"));
+        }
+        if self.secure {
+            out.push_str("; Secure");
+        }
+        if self.http_only {
+            out.push_str("; HttpOnly");
+        }
+        if let Some(same_site) = self.same_site {
+            out.push_str(&format!("; SameSite={}", same_site.as_str()));
+        }
+        for (name, value) in &self.extensions {
+            match value {
+                Some(value) => out.push_str(&format!("; {name}={value}")),
+                None => out.push_str(&format!("; {name}")),
+            }
+        }
+        out
+    }
+}
 
-        if name.is_empty() {
-            return None;
+/// Parse a `Set-Cookie` value the lenient way browsers do (RFC 6265 §5.2).
+/// Name and value are trimmed but may contain spaces; a pair without `=`
+/// is a name with an empty value. Attribute names are case-insensitive and
+/// the last of a duplicated attribute wins. An attribute with an invalid
+/// value (an `Expires` that is no date, a non-numeric `Max-Age`, a `Path`
+/// not starting with `/`, an empty `Domain`, an unknown `SameSite`) is
+/// ignored. Unknown attributes are kept in `extensions`. Fails with
+/// `InvalidHeader` only when there is no cookie name.
+pub fn parse_set_cookie(value: &str) -> Result<SetCookie> {
+    let mut parts = value.split(';');
+    let pair = parts.next().unwrap_or_default();
+    let (name, cookie_value) = pair.split_once('=').unwrap_or((pair, ""));
+    let name = name.trim();
+    if name.is_empty() {
+        return InvalidHeaderSnafu { message: format!("Set-Cookie without a cookie name: {value:?}") }.fail();
+    }
+    let mut cookie = SetCookie::new(name, cookie_value.trim());
+    for attribute in parts {
+        let (key, arg) = match attribute.split_once('=') {
+            Some((key, arg)) => (key.trim(), Some(arg.trim())),
+            None => (attribute.trim(), None),
+        };
+        let arg_or_empty = arg.unwrap_or_default();
+        if key.is_empty() {
+            continue;
+        } else if key.eq_ignore_ascii_case("expires") {
+            if let Some(expires) = parse_cookie_date(arg_or_empty) {
+                cookie.expires = Some(expires);
+            }
+        } else if key.eq_ignore_ascii_case("max-age") {
+            if let Some(max_age) = parse_max_age(arg_or_empty) {
+                cookie.max_age = Some(max_age);
+            }
+        } else if key.eq_ignore_ascii_case("domain") {
+            let domain = arg_or_empty.trim_start_matches('.');
+            if !domain.is_empty() {
+                cookie.domain = Some(domain.to_string());
+            }
+        } else if key.eq_ignore_ascii_case("path") {
+            if arg_or_empty.starts_with('/') {
+                cookie.path = Some(arg_or_empty.to_string());
+            }
+        } else if key.eq_ignore_ascii_case("secure") {
+            cookie.secure = true;
+        } else if key.eq_ignore_ascii_case("httponly") {
+            cookie.http_only = true;
+        } else if key.eq_ignore_ascii_case("samesite") {
+            let same_site = match arg_or_empty.to_ascii_lowercase().as_str() {
+                "strict" => Some(SameSite::Strict),
+                "lax" => Some(SameSite::Lax),
+                "none" => Some(SameSite::None),
+                _ => None,
+            };
+            cookie.same_site = same_site.or(cookie.same_site);
+        } else {
+            cookie.extensions.push((key.to_string(), arg.map(str::to_string)));
+        }
+    }
+    Ok(cookie)
+}
+
+/// Split a `Cookie` request header into `(name, value)` pairs, trimmed and
+/// in order. A pair without `=` is a name with an empty value; empty
+/// pairs are skipped.
+pub fn parse_cookie_header(value: &str) -> Vec<(String, String)> {
+    value
+        .split(';')
+        .filter_map(|pair| {
+            let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+            let name = name.trim();
+            (!name.is_empty()).then(|| (name.to_string(), value.trim().to_string()))
+        })
+        .collect()
+}
+
+/// Join `(name, value)` pairs into a `Cookie` request header value,
+/// `a=1; b=2`.
+pub fn format_cookie_header<K, V>(pairs: impl IntoIterator<Item = (K, V)>) -> String
+where
+    K: AsRef<str>,
+    V: AsRef<str>,
+{
+    pairs
+        .into_iter()
+        .map(|(name, value)| format!("{}={}", name.as_ref(), value.as_ref()))
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+/// A cookie held by a [`CookieJar`], with the scope its `Set-Cookie`
+/// attributes gave it (RFC 6265 §5.3).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StoredCookie {
+    pub name: String,
+    pub value: String,
+    /// Lowercase, without a leading dot.
+    pub domain: String,
+    /// Sent only to `domain` itself, not its subdomains: the `Set-Cookie`
+    /// had no `Domain` attribute.
+    pub host_only: bool,
+    pub path: String,
+    /// `None` for a session cookie, kept as long as the jar.
+    pub expires: Option<SystemTime>,
+    /// Sent only over HTTPS.
+    pub secure: bool,
+}
+
+impl StoredCookie {
+    /// A session cookie for exactly `host`, on every path, e.g. to pre-seed
+    /// a jar. Adjust the public fields for anything narrower or wider.
+    pub fn new(name: impl Into<String>, value: impl Into<String>, host: &str) -> Self {
+        StoredCookie {
+            name: name.into(),
+            value: value.into(),
+            domain: normalize_host(host),
+            host_only: true,
+            path: "/".to_string(),
+            expires: None,
+            secure: false,
         }
+    }
+
+    /// Parse a `Set-Cookie` value received for `url` at `now`, see
+    /// [`from_set_cookie`](Self::from_set_cookie).
+    pub fn parse(set_cookie: &str, url: &Url, now: SystemTime) -> Option<Self> {
+        Self::from_set_cookie(parse_set_cookie(set_cookie).ok()?, url, now)
+    }
 
-        Some(Cookie {
-            name: name.to_string(),
-            value: value.to_string(),
+    /// Scope `cookie`, received for `url` at `now`. `None` when its
+    /// `Domain` does not cover the host of `url`, or it is `Secure` but did
+    /// not come over HTTPS.
+    pub fn from_set_cookie(cookie: SetCookie, url: &Url, now: SystemTime) -> Option<Self> {
+        let host = normalize_host(url.host_str()?);
+        if cookie.secure && url.scheme() != "https" {
+            return None;
+        }
+        let (domain, host_only) = match cookie.domain.as_deref().map(normalize_host) {
+            Some(domain) if domain_matches(&host, &domain) => {
+                // A public suffix such as `com` or `co.uk` would cover every
+                // site under it: only the host itself may name one, and the
+                // cookie is then host-only (RFC 6265 §5.3 step 5).
+                if is_public_suffix(&domain) {
+                    if domain != host {
+                        return None;
+                    }
+                    (domain, true)
+                } else {
+                    (domain, false)
+                }
+            }
+            Some(_) => return None,
+            None => (host, true),
+        };
+        // Max-Age wins over Expires; zero or less expires the cookie at once,
+        // one too large for SystemTime never expires in practice.
+        let expires = match cookie.max_age {
+            Some(seconds) if seconds <= 0 => Some(UNIX_EPOCH),
+            Some(seconds) => Some(
+                now.checked_add(Duration::from_secs(seconds as u64))
+                    .unwrap_or(now + Duration::from_secs(u32::MAX as u64)),
+            ),
+            None => cookie.expires,
+        };
+        Some(StoredCookie {
+            name: cookie.name,
+            value: cookie.value,
+            domain,
+            host_only,
+            path: cookie.path.unwrap_or_else(|| default_path(url.path())),
+            expires,
+            secure: cookie.secure,
         })
     }
 
-    /// Format cookies for Cookie header
-    /// Converts Vec<Cookie> to "name=value; name2=value2" format
-    pub fn format_for_request_cookie_header(cookies: &[Self]) -> String {
-        cookies
-            .iter()
-            .map(|c| format!("{}={}", c.name, c.value))
-            .collect::<Vec<_>>()
-            .join("; ")
+    /// Whether this cookie goes with a request to `url`, ignoring expiry.
+    pub fn matches(&self, url: &Url) -> bool {
+        let Some(host) = url.host_str() else {
+            return false;
+        };
+        let host = normalize_host(host);
+        let host_ok = if self.host_only { host == self.domain } else { domain_matches(&host, &self.domain) };
+        host_ok && path_matches(url.path(), &self.path) && (!self.secure || url.scheme() == "https")
+    }
+
+    fn is_expired(&self, now: SystemTime) -> bool {
+        self.expires.is_some_and(|expires| expires <= now)
+    }
+
+    fn same_slot(&self, other: &StoredCookie) -> bool {
+        self.name == other.name && self.domain == other.domain && self.path == other.path
+    }
+}
+
+/// Cookies a client keeps between requests, see `ZJHttpClientBuilder::cookie_store`.
+/// Clones share their cookies.
+///
+/// After each response the client stores its `Set-Cookie` values; before
+/// each request it sends the live cookies whose domain, path and `Secure`
+/// flag match the URL. Expired cookies are dropped when met. Expiry is
+/// measured on the client's [`Clock`](crate::clock::Clock).
+#[derive(Debug, Clone, Default)]
+pub struct CookieJar {
+    cookies: Arc<Mutex<Vec<StoredCookie>>>,
+}
+
+impl CookieJar {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// An independent jar starting with a copy of this one's cookies.
+    pub fn fork(&self) -> Self {
+        CookieJar { cookies: Arc::new(Mutex::new(self.cookies.lock().unwrap().clone())) }
+    }
+
+    /// Add `cookie`, replacing one with the same name, domain and path.
+    /// A replaced cookie keeps its place in the send order.
+    pub fn insert(&self, cookie: StoredCookie) {
+        let mut cookies = self.cookies.lock().unwrap();
+        match cookies.iter_mut().find(|stored| stored.same_slot(&cookie)) {
+            Some(stored) => *stored = cookie,
+            None => cookies.push(cookie),
+        }
+    }
+
+    /// Remove the cookie `name` stored for exactly `domain` and `path`.
+    /// Returns whether there was one.
+    pub fn remove(&self, domain: &str, path: &str, name: &str) -> bool {
+        let probe = StoredCookie { path: path.to_string(), ..StoredCookie::new(name, "", domain) };
+        let mut cookies = self.cookies.lock().unwrap();
+        let before = cookies.len();
+        cookies.retain(|stored| !stored.same_slot(&probe));
+        cookies.len() != before
+    }
+
+    pub fn clear(&self) {
+        self.cookies.lock().unwrap().clear();
+    }
+
+    /// Every stored cookie, expired or not, in the order they were first set.
+    pub fn cookies(&self) -> Vec<StoredCookie> {
+        self.cookies.lock().unwrap().clone()
+    }
+
+    /// Store the `Set-Cookie` `values` of a response to `url` received at
+    /// `now`. Invalid values are ignored; an already expired cookie removes
+    /// the one it would replace.
+    pub fn observe<'a>(&self, url: &Url, values: impl IntoIterator<Item = &'a str>, now: SystemTime) {
+        for value in values {
+            let Some(cookie) = StoredCookie::parse(value, url, now) else {
+                trace!(target: WIRE, url = %url, value, "ignoring Set-Cookie");
+                continue;
+            };
+            if cookie.is_expired(now) {
+                self.cookies.lock().unwrap().retain(|stored| !stored.same_slot(&cookie));
+            } else {
+                self.insert(cookie);
+            }
+        }
+    }
+
+    /// The live cookies for a request to `url` at `now`, longest path first,
+    /// then in the order they were set (RFC 6265 §5.4). Expired cookies are
+    /// dropped.
+    pub fn matching(&self, url: &Url, now: SystemTime) -> Vec<StoredCookie> {
+        let mut cookies = self.cookies.lock().unwrap();
+        cookies.retain(|stored| !stored.is_expired(now));
+        let mut matching: Vec<_> = cookies.iter().filter(|stored| stored.matches(url)).cloned().collect();
+        // Stable, so equal paths keep the insertion order.
+        matching.sort_by_key(|stored| std::cmp::Reverse(stored.path.len()));
+        matching
+    }
+
+    /// The `Cookie` header value for a request to `url` at `now`, `None`
+    /// when no cookie matches.
+    pub fn header_value(&self, url: &Url, now: SystemTime) -> Option<String> {
+        let matching = self.matching(url, now);
+        (!matching.is_empty()).then(|| format_cookie_header(matching.iter().map(|c| (&c.name, &c.value))))
+    }
+}
+
+/// Lowercase `host` without a trailing dot.
+fn normalize_host(host: &str) -> String {
+    host.trim_end_matches('.').to_ascii_lowercase()
+}
+
+/// RFC 6265 §5.1.3: `host` is `domain` or a subdomain of it. IP addresses
+/// only match themselves.
+fn domain_matches(host: &str, domain: &str) -> bool {
+    if host == domain {
+        return true;
+    }
+    let is_ip = host.starts_with('[') || host.parse::<std::net::Ipv4Addr>().is_ok();
+    !is_ip && host.strip_suffix(domain).is_some_and(|prefix| prefix.ends_with('.'))
+}
+
+/// RFC 6265 §5.1.4: the directory of `path`, up to but not including its
+/// last `/`, or `/` at the top.
+fn default_path(path: &str) -> String {
+    match path.rfind('/') {
+        Some(0) | None => "/".to_string(),
+        Some(end) => path[..end].to_string(),
+    }
+}
+
+/// RFC 6265 §5.1.4: `request` is `cookie` or lies below it.
+fn path_matches(request: &str, cookie: &str) -> bool {
+    request == cookie
+        || request.strip_prefix(cookie).is_some_and(|rest| cookie.ends_with('/') || rest.starts_with('/'))
+}
+
+/// A `Max-Age` value: an optional `-` and digits. Out-of-range numbers
+/// saturate.
+fn parse_max_age(value: &str) -> Option<i64> {
+    let digits = value.strip_prefix('-').unwrap_or(value);
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
     }
+    Some(value.parse().unwrap_or(if value.starts_with('-') { i64::MIN } else { i64::MAX }))
+}
+
+/// An `Expires` date, read with the cookie-date algorithm of RFC 6265
+/// §5.1.1: the first tokens that look like a time, a day of month, a month
+/// name and a year are taken in any order, and everything else is ignored.
+/// This accepts the HTTP date formats as well as variants such as
+/// `Wed, 21-Oct-15 07:28:00 GMT`. Two-digit years 70-99 are 19xx, 00-69
+/// 20xx.
+fn parse_cookie_date(value: &str) -> Option<SystemTime> {
+    const MONTHS: [&str; 12] = ["jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec"];
+    let is_delimiter = |c: char| matches!(c, '\t' | ' '..='/' | ';'..='@' | '['..='`' | '{'..='~');
+    // Leading digits of `token` when there are `min..=max` of them and
+    // anything after them starts with a non-digit.
+    let number = |token: &str, min: usize, max: usize| -> Option<(i64, usize)> {
+        let len = token.bytes().take_while(u8::is_ascii_digit).count();
+        (min..=max).contains(&len).then(|| (token[..len].parse().unwrap(), len))
+    };
+    let (mut time, mut day, mut month, mut year) = (None, None, None, None);
+    for token in value.split(is_delimiter).filter(|token| !token.is_empty()) {
+        if time.is_none() {
+            let hms = (|| {
+                let (hour, len) = number(token, 1, 2)?;
+                let rest = token[len..].strip_prefix(':')?;
+                let (minute, len) = number(rest, 1, 2)?;
+                let rest = rest[len..].strip_prefix(':')?;
+                let (second, _) = number(rest, 1, 2)?;
+                Some((hour, minute, second))
+            })();
+            if hms.is_some() {
+                time = hms;
+                continue;
+            }
+        }
+        if day.is_none()
+            && let Some((d, _)) = number(token, 1, 2)
+        {
+            day = Some(d);
+            continue;
+        }
+        if month.is_none()
+            && let Some(prefix) = token.get(..3)
+            && let Some(m) = MONTHS.iter().position(|name| prefix.eq_ignore_ascii_case(name))
+        {
+            month = Some(m as i64 + 1);
+            continue;
+        }
+        if year.is_none()
+            && let Some((y, _)) = number(token, 2, 4)
+        {
+            year = Some(y);
+        }
+    }
+    let ((hour, minute, second), day, month, mut year) = (time?, day?, month?, year?);
+    if (70..=99).contains(&year) {
+        year += 1900;
+    } else if (0..=69).contains(&year) {
+        year += 2000;
+    }
+    let days_in_month = match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    };
+    if !(1..=days_in_month).contains(&day) || year < 1601 || hour > 23 || minute > 59 || second > 59 {
+        return None;
+    }
+    let seconds = crate::ocsp::days_from_civil(year, month, day) * 86_400 + hour * 3_600 + minute * 60 + second;
+    // Dates before 1970 are long expired; the epoch stands in for them.
+    Some(u64::try_from(seconds).map_or(UNIX_EPOCH, |seconds| UNIX_EPOCH + Duration::from_secs(seconds)))
 }
 
 #[cfg(test)]
@@ -142,4 +609,205 @@ mod tests {
         assert_eq!(cookie.name, "test");
         assert_eq!(cookie.value, "value");
     }
+
+    fn url(s: &str) -> Url {
+        Url::parse(s).unwrap()
+    }
+
+    fn at(seconds: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(seconds)
+    }
+
+    #[test]
+    fn test_stored_cookie_scope() {
+        let now = at(1_000_000);
+        let c = StoredCookie::parse("a=1", &url("http://www.example.com/docs/page"), now).unwrap();
+        assert_eq!((c.domain.as_str(), c.host_only, c.path.as_str()), ("www.example.com", true, "/docs"));
+        assert_eq!(c.expires, None);
+
+        let c = StoredCookie::parse("a=1; Domain=.Example.COM; Path=/", &url("http://www.example.com/"), now).unwrap();
+        assert_eq!((c.domain.as_str(), c.host_only), ("example.com", false));
+        assert!(c.matches(&url("http://example.com/x")));
+        assert!(c.matches(&url("http://api.example.com/")));
+        assert!(!c.matches(&url("http://badexample.com/")));
+
+        // Rejected: a foreign domain, a public suffix, Secure over plain HTTP.
+        for (value, from) in [
+            ("a=1; Domain=other.com", "http://www.example.com/"),
+            ("a=1; Domain=com", "http://example.com/"),
+            ("a=1; Domain=co.uk", "http://evil.co.uk/"),
+            ("a=1; Domain=.CO.UK", "http://www.evil.co.uk/"),
+            ("a=1; Domain=github.io", "https://user.github.io/"),
+            ("a=1; Domain=example.com", "http://127.0.0.1/"),
+            ("a=1; Secure", "http://example.com/"),
+            ("=1", "http://example.com/"),
+        ] {
+            assert_eq!(StoredCookie::parse(value, &url(from), now), None, "{value}");
+        }
+        // The host itself may name a public suffix; the cookie is host-only.
+        let c = StoredCookie::parse("a=1; Domain=localhost", &url("http://localhost/"), now).unwrap();
+        assert_eq!((c.domain.as_str(), c.host_only), ("localhost", true));
+        let c = StoredCookie::parse("a=1; Domain=co.uk", &url("http://co.uk/"), now).unwrap();
+        assert_eq!((c.domain.as_str(), c.host_only), ("co.uk", true));
+        let c = StoredCookie::parse("a=1; Domain=evil.co.uk", &url("http://www.evil.co.uk/"), now).unwrap();
+        assert_eq!((c.domain.as_str(), c.host_only), ("evil.co.uk", false));
+    }
+
+    #[test]
+    fn test_stored_cookie_expiry() {
+        let now = at(1_000_000);
+        let from = url("https://example.com/");
+        let parse = |value| StoredCookie::parse(value, &from, now).unwrap().expires;
+        assert_eq!(parse("a=1; Max-Age=60"), Some(at(1_000_060)));
+        assert_eq!(parse("a=1; Max-Age=0"), Some(UNIX_EPOCH));
+        assert_eq!(parse("a=1; Expires=Sun, 06 Nov 1994 08:49:37 GMT"), Some(at(784_111_777)));
+        assert_eq!(parse("a=1; Expires=Sun, 06-Nov-1994 08:49:37 GMT"), Some(at(784_111_777)));
+        // Max-Age wins whatever the order.
+        assert_eq!(parse("a=1; Max-Age=60; Expires=Sun, 06 Nov 1994 08:49:37 GMT"), Some(at(1_000_060)));
+        assert_eq!(parse("a=1; Expires=garbage"), None);
+    }
+
+    #[test]
+    fn test_path_matching() {
+        assert_eq!(default_path(""), "/");
+        assert_eq!(default_path("/"), "/");
+        assert_eq!(default_path("/login"), "/");
+        assert_eq!(default_path("/a/b/c"), "/a/b");
+        assert!(path_matches("/docs", "/docs"));
+        assert!(path_matches("/docs/x", "/docs"));
+        assert!(path_matches("/docs/x", "/docs/"));
+        assert!(!path_matches("/docsets", "/docs"));
+        assert!(!path_matches("/", "/docs"));
+    }
+
+    #[test]
+    fn test_jar_sends_matching_cookies_in_order() {
+        let jar = CookieJar::new();
+        let now = at(1_000_000);
+        jar.observe(
+            &url("https://example.com/"),
+            ["root=1", "deep=2; Path=/a/b", "mid=3; Path=/a", "safe=4; Secure", "short=5; Max-Age=10"],
+            now,
+        );
+        assert_eq!(jar.header_value(&url("https://example.com/a/b/c"), now).unwrap(), "deep=2; mid=3; root=1; safe=4; short=5");
+        assert_eq!(jar.header_value(&url("http://example.com/a"), now).unwrap(), "mid=3; root=1; short=5");
+        assert_eq!(jar.header_value(&url("https://other.com/"), now), None);
+
+        // Expired cookies are dropped once met; Max-Age=0 deletes at once.
+        let later = at(1_000_010);
+        assert_eq!(jar.header_value(&url("http://example.com/"), later).unwrap(), "root=1");
+        assert_eq!(jar.cookies().len(), 4);
+        jar.observe(&url("https://example.com/"), ["root=gone; Max-Age=0"], later);
+        assert_eq!(jar.header_value(&url("http://example.com/"), later), None);
+    }
+
+    #[test]
+    fn test_jar_replaces_and_forks() {
+        let jar = CookieJar::new();
+        jar.insert(StoredCookie::new("a", "1", "Example.com."));
+        jar.insert(StoredCookie::new("b", "2", "example.com"));
+        jar.insert(StoredCookie::new("a", "3", "example.com"));
+        let now = at(0);
+        assert_eq!(jar.header_value(&url("http://example.com/"), now).unwrap(), "a=3; b=2");
+
+        let fork = jar.fork();
+        assert!(fork.remove("example.com", "/", "a"));
+        assert!(!fork.remove("example.com", "/", "a"));
+        assert_eq!(fork.header_value(&url("http://example.com/"), now).unwrap(), "b=2");
+        assert_eq!(jar.clone().header_value(&url("http://example.com/"), now).unwrap(), "a=3; b=2");
+        jar.clear();
+        assert!(jar.cookies().is_empty());
+    }
+
+
+    #[test]
+    fn test_parse_set_cookie_quirks() {
+        let at = |seconds| Some(UNIX_EPOCH + Duration::from_secs(seconds));
+        let cases: &[(&str, SetCookie)] = &[
+            (
+                "id=a3fWa; Expires=Wed, 21 Oct 2015 07:28:00 GMT; Secure; HttpOnly",
+                SetCookie { expires: at(1_445_412_480), secure: true, http_only: true, ..SetCookie::new("id", "a3fWa") },
+            ),
+            // Unquoted value with spaces; quotes are part of the value.
+            ("greeting = hello world ; Path=/", SetCookie { path: Some("/".into()), ..SetCookie::new("greeting", "hello world") }),
+            ("q=\"a b\"", SetCookie::new("q", "\"a b\"")),
+            ("flag", SetCookie::new("flag", "")),
+            // Case-insensitive names; the last duplicate wins; invalid values are ignored.
+            (
+                "a=1; PATH=/x; path=/y; path=relative; DOMAIN=.Example.com; domain=",
+                SetCookie { path: Some("/y".into()), domain: Some("Example.com".into()), ..SetCookie::new("a", "1") },
+            ),
+            ("a=1; Max-Age=60; max-age=soon; Max-Age=+5", SetCookie { max_age: Some(60), ..SetCookie::new("a", "1") }),
+            ("a=1; Max-Age=-1", SetCookie { max_age: Some(-1), ..SetCookie::new("a", "1") }),
+            ("a=1; Max-Age=99999999999999999999", SetCookie { max_age: Some(i64::MAX), ..SetCookie::new("a", "1") }),
+            ("a=1; SameSite=lax; SameSite=bogus", SetCookie { same_site: Some(SameSite::Lax), ..SetCookie::new("a", "1") }),
+            ("a=1; Expires=never; Expires=", SetCookie::new("a", "1")),
+            // Unknown attributes are kept in order, flags without a value.
+            (
+                "a=1; Priority=High; Partitioned;; ",
+                SetCookie {
+                    extensions: vec![("Priority".into(), Some("High".into())), ("Partitioned".into(), None)],
+                    ..SetCookie::new("a", "1")
+                },
+            ),
+        ];
+        for (value, expected) in cases {
+            assert_eq!(&parse_set_cookie(value).unwrap(), expected, "{value}");
+        }
+        for value in ["", "=1", " =x; Path=/", ";Secure"] {
+            assert!(parse_set_cookie(value).is_err(), "{value:?}");
+        }
+    }
+
+    #[test]
+    fn test_cookie_dates() {
+        let at = |seconds| Some(UNIX_EPOCH + Duration::from_secs(seconds));
+        for (value, expected) in [
+            ("Sun, 06 Nov 1994 08:49:37 GMT", at(784_111_777)),
+            ("Sunday, 06-Nov-94 08:49:37 GMT", at(784_111_777)),
+            ("Sun Nov  6 08:49:37 1994", at(784_111_777)),
+            ("Sun, 06-Nov-1994 08:49:37 GMT", at(784_111_777)),
+            ("06 November 1994 8:49:37", at(784_111_777)),
+            ("Wed, 21-Oct-15 07:28:00 GMT", at(1_445_412_480)),
+            ("Thu, 01 Jan 1970 00:00:00 GMT", at(0)),
+            ("Thu, 01 Jan 1920 00:00:00 GMT", at(0)),
+            ("Thu, 29 Feb 2024 00:00:00 GMT", at(1_709_164_800)),
+            ("Thu, 29 Feb 2023 00:00:00 GMT", None),
+            ("Thu, 01 Jan 1970 24:00:00 GMT", None),
+            ("Thu, 01 Foo 1970 00:00:00 GMT", None),
+            ("Thu, 01 Jan 1600 00:00:00 GMT", None),
+            ("01 Jan 1970", None),
+            ("", None),
+        ] {
+            assert_eq!(parse_cookie_date(value), expected, "{value}");
+        }
+    }
+
+    #[test]
+    fn test_set_cookie_round_trip() {
+        let value = "sid=x y; Path=/app; Domain=example.com; Expires=Sun, 06 Nov 1994 08:49:37 GMT; \
+                     Max-Age=0; Secure; HttpOnly; SameSite=None; Priority=High; Partitioned";
+        let cookie = parse_set_cookie(value).unwrap();
+        assert_eq!(cookie.to_header_value(), value);
+        assert_eq!(parse_set_cookie(&cookie.to_header_value()).unwrap(), cookie);
+        assert_eq!(SetCookie::new("a", "").to_header_value(), "a=");
+    }
+
+    #[test]
+    fn test_cookie_header() {
+        assert_eq!(
+            parse_cookie_header(" a=1;b = two words ; ;flag; c=x=y"),
+            vec![
+                ("a".to_string(), "1".to_string()),
+                ("b".to_string(), "two words".to_string()),
+                ("flag".to_string(), String::new()),
+                ("c".to_string(), "x=y".to_string()),
+            ]
+        );
+        assert_eq!(parse_cookie_header(""), vec![]);
+        assert_eq!(format_cookie_header([("a", "1"), ("b", "2")]), "a=1; b=2");
+        assert_eq!(format_cookie_header(parse_cookie_header("a=1;b=2")), "a=1; b=2");
+        assert_eq!(format_cookie_header(Vec::<(String, String)>::new()), "");
+    }
+
 }
@@ -0,0 +1,247 @@
+//! Decoding of `Content-Encoding: gzip` and `deflate` response bodies.
+//!
+//! With `ZJHttpClientBuilder::set_auto_decompress(true)` the client sends
+//! `Accept-Encoding: gzip, deflate` (unless the request sets its own), and
+//! the body readers of `Response` — `body_bytes` and the methods built on
+//! it, `body_reader`, `bytes_stream`, `copy_body_to` and `save_to_file` —
+//! hand back the decoded body. The body is still framed by its encoded
+//! length, so the connection is read exactly as far as the response goes.
+//! `body_managed_stream`, `peek_body` and the response headers stay as
+//! received.
+//!
+//! A `Content-Encoding` naming anything other than `gzip`, `x-gzip`,
+//! `deflate` or `identity` leaves the body as it is.
+//!
+//! A body that decodes to more than `max_decompression_ratio` (100 by
+//! default) times its encoded length fails with `DecompressionBomb` once it
+//! passes 1 MiB decoded, and its connection is closed.
+
+use std::io::Write;
+
+use crate::error::{BodyTooLargeSnafu, DecompressSnafu, Result};
+
+/// A content coding this module can undo.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContentCoding {
+    Gzip,
+    /// zlib-wrapped deflate as RFC 9110 defines it; raw deflate streams,
+    /// which some servers send instead, are accepted too.
+    Deflate,
+}
+
+impl ContentCoding {
+    pub fn name(self) -> &'static str {
+        match self {
+            ContentCoding::Gzip => "gzip",
+            ContentCoding::Deflate => "deflate",
+        }
+    }
+}
+
+/// The codings named by `Content-Encoding` header values, in the order the
+/// server applied them. `None` when one of them is not supported.
+pub fn codings<'a>(values: impl IntoIterator<Item = &'a str>) -> Option<Vec<ContentCoding>> {
+    let mut codings = Vec::new();
+    for coding in values.into_iter().flat_map(|v| v.split(',')) {
+        match coding.trim().to_ascii_lowercase().as_str() {
+            "gzip" | "x-gzip" => codings.push(ContentCoding::Gzip),
+            "deflate" => codings.push(ContentCoding::Deflate),
+            "identity" | "" => {}
+            _ => return None,
+        }
+    }
+    Some(codings)
+}
+
+/// Undo `codings` on `body`, last applied first. A decoded body longer than
+/// `limit` bytes fails with `BodyTooLarge`.
+pub fn decode(codings: &[ContentCoding], body: Vec<u8>, limit: u64) -> Result<Vec<u8>> {
+    let mut decoder = Decoder::new(codings);
+    let mut decoded = Vec::new();
+    let within_limit = |decoded: &Vec<u8>| match decoded.len() as u64 > limit {
+        true => Err(BodyTooLargeSnafu { length: None, limit }.build()),
+        false => Ok(()),
+    };
+    // Piece by piece, so a bomb stops near the limit instead of inflating whole.
+    for piece in body.chunks(8 * 1024) {
+        decoded.extend(decoder.push(piece)?);
+        within_limit(&decoded)?;
+    }
+    decoded.extend(decoder.finish()?);
+    within_limit(&decoded)?;
+    Ok(decoded)
+}
+
+/// Incremental [`decode`]: feed the body as it arrives, get back what
+/// decodes so far. Limits are up to the caller.
+pub(crate) struct Decoder {
+    /// One per coding, last applied first.
+    layers: Vec<Layer>,
+    /// Whether any body came in; an empty body decodes to nothing.
+    seen_input: bool,
+}
+
+impl Decoder {
+    pub(crate) fn new(codings: &[ContentCoding]) -> Self {
+        Decoder { layers: codings.iter().rev().map(|&coding| Layer::new(coding)).collect(), seen_input: false }
+    }
+
+    pub(crate) fn push(&mut self, body: &[u8]) -> Result<Vec<u8>> {
+        self.seen_input |= !body.is_empty();
+        let mut data = body.to_vec();
+        for layer in &mut self.layers {
+            data = layer.write(&data)?;
+        }
+        Ok(data)
+    }
+
+    /// Finish every layer; fails when the body stops mid-stream.
+    pub(crate) fn finish(&mut self) -> Result<Vec<u8>> {
+        if !self.seen_input {
+            return Ok(Vec::new());
+        }
+        let mut data = Vec::new();
+        for layer in &mut self.layers {
+            let mut out = layer.write(&data)?;
+            out.extend(layer.finish()?);
+            data = out;
+        }
+        Ok(data)
+    }
+}
+
+struct Layer {
+    coding: ContentCoding,
+    state: LayerState,
+}
+
+enum LayerState {
+    Gzip(flate2::write::MultiGzDecoder<Vec<u8>>),
+    Zlib(flate2::write::ZlibDecoder<Vec<u8>>),
+    RawDeflate(flate2::write::DeflateDecoder<Vec<u8>>),
+    /// Deflate before its first two bytes tell which flavour it is.
+    Sniffing(Vec<u8>),
+}
+
+impl Layer {
+    fn new(coding: ContentCoding) -> Self {
+        let state = match coding {
+            ContentCoding::Gzip => LayerState::Gzip(flate2::write::MultiGzDecoder::new(Vec::new())),
+            ContentCoding::Deflate => LayerState::Sniffing(Vec::new()),
+        };
+        Layer { coding, state }
+    }
+
+    fn write(&mut self, data: &[u8]) -> Result<Vec<u8>> {
+        if let LayerState::Sniffing(head) = &mut self.state {
+            head.extend_from_slice(data);
+            return if head.len() < 2 { Ok(Vec::new()) } else { self.settle_deflate() };
+        }
+        self.io(|state| match state {
+            LayerState::Gzip(d) => d.write_all(data).map(|_| std::mem::take(d.get_mut())),
+            LayerState::Zlib(d) => d.write_all(data).map(|_| std::mem::take(d.get_mut())),
+            LayerState::RawDeflate(d) => d.write_all(data).map(|_| std::mem::take(d.get_mut())),
+            LayerState::Sniffing(_) => Ok(Vec::new()),
+        })
+    }
+
+    fn finish(&mut self) -> Result<Vec<u8>> {
+        let mut out = match self.state {
+            LayerState::Sniffing(_) => self.settle_deflate()?,
+            _ => Vec::new(),
+        };
+        out.extend(self.io(|state| match state {
+            LayerState::Gzip(d) => d.try_finish().map(|_| std::mem::take(d.get_mut())),
+            LayerState::Zlib(d) => d.try_finish().map(|_| std::mem::take(d.get_mut())),
+            LayerState::RawDeflate(d) => d.try_finish().map(|_| std::mem::take(d.get_mut())),
+            LayerState::Sniffing(_) => Ok(Vec::new()),
+        })?);
+        Ok(out)
+    }
+
+    /// Pick the deflate flavour from the bytes seen so far and decode them.
+    fn settle_deflate(&mut self) -> Result<Vec<u8>> {
+        let LayerState::Sniffing(head) = &mut self.state else {
+            return Ok(Vec::new());
+        };
+        let head = std::mem::take(head);
+        self.state = if is_zlib_header(&head) {
+            LayerState::Zlib(flate2::write::ZlibDecoder::new(Vec::new()))
+        } else {
+            LayerState::RawDeflate(flate2::write::DeflateDecoder::new(Vec::new()))
+        };
+        self.write(&head)
+    }
+
+    fn io(&mut self, op: impl FnOnce(&mut LayerState) -> std::io::Result<Vec<u8>>) -> Result<Vec<u8>> {
+        let coding = self.coding;
+        op(&mut self.state).map_err(|e| DecompressSnafu { encoding: coding.name(), message: e.to_string() }.build())
+    }
+}
+
+/// Whether `body` starts with a zlib header (RFC 1950 §2.2): deflate
+/// method, and the first two bytes a multiple of 31.
+fn is_zlib_header(body: &[u8]) -> bool {
+    matches!(body, [cmf, flg, ..] if cmf & 0x0f == 8 && (u16::from(*cmf) << 8 | u16::from(*flg)) % 31 == 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::*;
+    use crate::error::ZjhttpcError;
+
+    const TEXT: &[u8] = b"hello hello hello hello, compressed world";
+
+    fn gzip(data: &[u8]) -> Vec<u8> {
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn test_codings() {
+        use ContentCoding::*;
+        assert_eq!(codings(["gzip"]), Some(vec![Gzip]));
+        assert_eq!(codings(["X-Gzip"]), Some(vec![Gzip]));
+        assert_eq!(codings(["deflate, gzip"]), Some(vec![Deflate, Gzip]));
+        assert_eq!(codings(["identity"]), Some(vec![]));
+        assert_eq!(codings(["gzip", "br"]), None);
+    }
+
+    #[test]
+    fn test_gzip_and_both_deflate_flavours() {
+        assert_eq!(decode(&[ContentCoding::Gzip], gzip(TEXT), u64::MAX).unwrap(), TEXT);
+
+        let mut zlib = flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+        zlib.write_all(TEXT).unwrap();
+        assert_eq!(decode(&[ContentCoding::Deflate], zlib.finish().unwrap(), u64::MAX).unwrap(), TEXT);
+
+        let mut raw = flate2::write::DeflateEncoder::new(Vec::new(), flate2::Compression::default());
+        raw.write_all(TEXT).unwrap();
+        assert_eq!(decode(&[ContentCoding::Deflate], raw.finish().unwrap(), u64::MAX).unwrap(), TEXT);
+    }
+
+    #[test]
+    fn test_stacked_codings_are_undone_in_reverse() {
+        let mut zlib = flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+        zlib.write_all(TEXT).unwrap();
+        let body = gzip(&zlib.finish().unwrap());
+        assert_eq!(decode(&[ContentCoding::Deflate, ContentCoding::Gzip], body, u64::MAX).unwrap(), TEXT);
+    }
+
+    #[test]
+    fn test_garbage_names_the_encoding() {
+        let err = decode(&[ContentCoding::Gzip], b"not gzip at all".to_vec(), u64::MAX).unwrap_err();
+        assert!(matches!(&err, ZjhttpcError::Decompress { encoding, .. } if encoding == "gzip"), "{err:?}");
+        assert!(err.to_string().contains("not valid gzip"), "{err}");
+    }
+
+    #[test]
+    fn test_decoded_size_is_limited() {
+        let bomb = gzip(&vec![0u8; 1 << 20]);
+        let err = decode(&[ContentCoding::Gzip], bomb, 1000).unwrap_err();
+        assert!(matches!(err, ZjhttpcError::BodyTooLarge { length: None, limit: 1000, .. }), "{err:?}");
+    }
+}
@@ -0,0 +1,265 @@
+//! Deprecation signals a server attaches to its responses: `Deprecation`
+//! (RFC 9745, and the earlier drafts), `Sunset` (RFC 8594), `Link` with
+//! `rel="deprecation"`, and the legacy `Warning` header (RFC 7234 §5.5).
+//!
+//! [`Response::deprecation`](crate::response::Response::deprecation) and
+//! [`Response::warnings`](crate::response::Response::warnings) read them off a
+//! response; a client built with
+//! [`on_deprecated_response`](crate::client::ZJHttpClientBuilder::on_deprecated_response)
+//! is told about every such response `send()` returns.
+
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::{header::HeaderMap, link::parse_link_header, response::Response};
+
+/// Hook set with [`ZJHttpClientBuilder::on_deprecated_response`](crate::client::ZJHttpClientBuilder::on_deprecated_response).
+pub type DeprecationHook = Arc<dyn Fn(&Response) + Send + Sync>;
+
+/// What a response says about the deprecation of the resource.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeprecationInfo {
+    /// When the resource was (or will be) deprecated. `None` when the
+    /// `Deprecation` header only says `true`, is missing or unreadable.
+    pub deprecated_since: Option<SystemTime>,
+    /// When the resource is expected to stop responding, from `Sunset`.
+    pub sunset: Option<SystemTime>,
+    /// Target of the first `Link` with `rel="deprecation"`, resolved against
+    /// the response URL when relative.
+    pub link: Option<url::Url>,
+}
+
+impl DeprecationInfo {
+    /// Read the deprecation headers; `None` when there are none. A header
+    /// that can't be parsed still counts as a signal, with its field left
+    /// `None`, except a `Deprecation` value that is neither a date nor `true`.
+    pub fn from_headers(headers: &HeaderMap, base: Option<&url::Url>) -> Option<Self> {
+        let deprecation = headers.get("deprecation").map(str::trim);
+        let deprecated = deprecation.is_some_and(|v| v.eq_ignore_ascii_case("true") || parse_deprecation_date(v).is_some());
+        let sunset = headers.get("sunset");
+        let link = headers
+            .get_all("link")
+            .into_iter()
+            .flatten()
+            .flat_map(|v| parse_link_header(v))
+            .find(|l| l.has_rel("deprecation"));
+        if !deprecated && sunset.is_none() && link.is_none() {
+            return None;
+        }
+        Some(DeprecationInfo {
+            deprecated_since: deprecation.and_then(parse_deprecation_date),
+            sunset: sunset.and_then(|v| httpdate::parse_http_date(v.trim()).ok()),
+            link: link.and_then(|l| match base {
+                Some(base) => base.join(&l.uri).ok(),
+                None => url::Url::parse(&l.uri).ok(),
+            }),
+        })
+    }
+}
+
+/// A `Deprecation` date: `@<unix seconds>` as RFC 9745 writes it, or an
+/// HTTP-date as the drafts before it did.
+fn parse_deprecation_date(value: &str) -> Option<SystemTime> {
+    match value.strip_prefix('@') {
+        Some(secs) => {
+            let secs: i64 = secs.parse().ok()?;
+            match u64::try_from(secs) {
+                Ok(secs) => UNIX_EPOCH.checked_add(Duration::from_secs(secs)),
+                Err(_) => UNIX_EPOCH.checked_sub(Duration::from_secs(secs.unsigned_abs())),
+            }
+        }
+        None => httpdate::parse_http_date(value).ok(),
+    }
+}
+
+/// One value of a `Warning` header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Warning {
+    /// Three-digit warn-code, e.g. 299 for a miscellaneous persistent warning.
+    pub code: u16,
+    /// Host (and port) or pseudonym of whoever added the warning; `-` when unknown.
+    pub agent: String,
+    /// The warn-text, quotes and escapes removed.
+    pub text: String,
+    /// The optional warn-date.
+    pub date: Option<SystemTime>,
+}
+
+/// Parse a `Warning` header value holding any number of comma separated
+/// warnings. Malformed warnings are skipped rather than failing the whole
+/// header.
+pub fn parse_warning_header(value: &str) -> Vec<Warning> {
+    let mut warnings = Vec::new();
+    let mut rest = value;
+    loop {
+        rest = rest.trim_start_matches([' ', '\t', ',']);
+        if rest.is_empty() {
+            return warnings;
+        }
+        match parse_one(rest) {
+            Some((warning, remaining)) => {
+                warnings.push(warning);
+                rest = remaining;
+            }
+            None => rest = rest.split_once(',').map_or("", |(_, after)| after),
+        }
+    }
+}
+
+/// Parse `code SP agent SP "text" [SP "date"]` and return the rest after it.
+fn parse_one(input: &str) -> Option<(Warning, &str)> {
+    let (code, rest) = input.split_once(' ')?;
+    if code.len() != 3 || !code.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let (agent, rest) = rest.trim_start().split_once(' ')?;
+    let (text, mut rest) = quoted(rest.trim_start())?;
+    let mut date = None;
+    let after_text = rest.trim_start_matches([' ', '\t']);
+    if after_text.starts_with('"') {
+        let (raw, remaining) = quoted(after_text)?;
+        date = httpdate::parse_http_date(&raw).ok();
+        rest = remaining;
+    }
+    let rest = rest.trim_start_matches([' ', '\t']);
+    if !rest.is_empty() && !rest.starts_with(',') {
+        return None;
+    }
+    Some((Warning { code: code.parse().ok()?, agent: agent.to_string(), text, date }, rest))
+}
+
+/// Unquote the quoted-string at the start of `input`; returns it and the rest.
+fn quoted(input: &str) -> Option<(String, &str)> {
+    let mut chars = input.strip_prefix('"')?.char_indices();
+    let mut out = String::new();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => return Some((out, &input[i + 2..])),
+            '\\' => out.push(chars.next()?.1),
+            c => out.push(c),
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(pairs: &[(&str, &str)]) -> HeaderMap {
+        let mut map = HeaderMap::new();
+        for (k, v) in pairs {
+            map.append(k, v).unwrap();
+        }
+        map
+    }
+
+    fn at(secs: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(secs)
+    }
+
+    #[test]
+    fn test_deprecation_date_formats() {
+        let cases: &[(&str, Option<SystemTime>)] = &[
+            ("@1688169599", Some(at(1688169599))),
+            ("@0", Some(at(0))),
+            ("@-86400", UNIX_EPOCH.checked_sub(Duration::from_secs(86400))),
+            ("Sun, 11 Nov 2018 23:59:59 GMT", Some(at(1541980799))),
+            ("Sunday, 11-Nov-18 23:59:59 GMT", Some(at(1541980799))),
+            ("Sun Nov 11 23:59:59 2018", Some(at(1541980799))),
+            ("@", None),
+            ("@12x", None),
+            ("2018-11-11", None),
+        ];
+        for (value, expected) in cases {
+            assert_eq!(parse_deprecation_date(value), *expected, "{value}");
+        }
+    }
+
+    #[test]
+    fn test_deprecation_info_from_headers() {
+        let base = url::Url::parse("https://api.example.com/v1/items").unwrap();
+        let sunset = ("sunset", "Wed, 11 Nov 2026 23:59:59 GMT");
+        let link = ("link", "</docs/v1-deprecation>; rel=\"deprecation\"; type=\"text/html\"");
+        type Case<'a> = (&'a str, &'a [(&'a str, &'a str)], Option<DeprecationInfo>);
+        let cases: &[Case] = &[
+            ("none", &[("content-type", "text/plain")], None),
+            ("unknown value only", &[("deprecation", "soon")], None),
+            (
+                "true only",
+                &[("deprecation", "true")],
+                Some(DeprecationInfo { deprecated_since: None, sunset: None, link: None }),
+            ),
+            (
+                "sf-date",
+                &[("deprecation", "@1688169599")],
+                Some(DeprecationInfo { deprecated_since: Some(at(1688169599)), sunset: None, link: None }),
+            ),
+            (
+                "sunset only",
+                &[sunset],
+                Some(DeprecationInfo { deprecated_since: None, sunset: Some(at(1794441599)), link: None }),
+            ),
+            (
+                "bad sunset still signals",
+                &[("sunset", "tomorrow")],
+                Some(DeprecationInfo { deprecated_since: None, sunset: None, link: None }),
+            ),
+            (
+                "all three",
+                &[("deprecation", "@1688169599"), sunset, ("link", "</next>; rel=next"), link],
+                Some(DeprecationInfo {
+                    deprecated_since: Some(at(1688169599)),
+                    sunset: Some(at(1794441599)),
+                    link: Some(url::Url::parse("https://api.example.com/docs/v1-deprecation").unwrap()),
+                }),
+            ),
+            (
+                "link in a combined header",
+                &[("link", "</next>; rel=next, <https://docs.example.com/dep>; rel=\"deprecation\"")],
+                Some(DeprecationInfo {
+                    deprecated_since: None,
+                    sunset: None,
+                    link: Some(url::Url::parse("https://docs.example.com/dep").unwrap()),
+                }),
+            ),
+        ];
+        for (name, pairs, expected) in cases {
+            assert_eq!(DeprecationInfo::from_headers(&headers(pairs), Some(&base)), *expected, "{name}");
+        }
+    }
+
+    #[test]
+    fn test_relative_link_without_base_is_dropped() {
+        let info = DeprecationInfo::from_headers(&headers(&[("link", "</dep>; rel=deprecation")]), None).unwrap();
+        assert_eq!(info.link, None);
+    }
+
+    #[test]
+    fn test_parse_warning_header() {
+        let warning = |code, agent: &str, text: &str, date| Warning {
+            code,
+            agent: agent.to_string(),
+            text: text.to_string(),
+            date,
+        };
+        let cases: &[(&str, Vec<Warning>)] = &[
+            ("299 - \"Deprecated API\"", vec![warning(299, "-", "Deprecated API", None)]),
+            (
+                "110 cache.example:8080 \"Response is \\\"stale\\\"\" \"Sun, 11 Nov 2018 23:59:59 GMT\"",
+                vec![warning(110, "cache.example:8080", "Response is \"stale\"", Some(at(1541980799)))],
+            ),
+            (
+                "299 api \"a, b\", 214 proxy \"Transformation applied\"",
+                vec![warning(299, "api", "a, b", None), warning(214, "proxy", "Transformation applied", None)],
+            ),
+            ("99 - \"short code\", 299 - \"kept\"", vec![warning(299, "-", "kept", None)]),
+            ("299 - unquoted, 199 - \"kept\"", vec![warning(199, "-", "kept", None)]),
+            ("299 - \"unterminated", vec![]),
+            ("", vec![]),
+        ];
+        for (value, expected) in cases {
+            assert_eq!(parse_warning_header(value), *expected, "{value}");
+        }
+    }
+}
@@ -0,0 +1,248 @@
+//! Duplex mode: write the request body while the response is already arriving.
+//!
+//! Obtained from [`ZJHttpClient::send_head_duplex`]. The connection is split into
+//! a [`DuplexWriter`] for the body and a [`DuplexReader`] for the response, so
+//! both can be driven at the same time (e.g. with `futures::join!` or from two
+//! tasks).
+//!
+//! Only use this against servers that are known to answer before the request
+//! body is complete: streaming RPC endpoints, echo/long-poll style handlers and
+//! the like. Many servers and most intermediaries read the whole request before
+//! replying; against those the response head will only arrive after
+//! [`DuplexWriter::finish`], so awaiting it before finishing the body deadlocks.
+//! Duplex connections are never returned to the connection pool.
+
+use std::io;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use async_std::io::WriteExt;
+use futures::io::{ReadHalf, WriteHalf};
+
+use crate::{
+    client::{read_response_head, write_chunk, write_chunk_terminator},
+    config::EffectiveConfig,
+    error::{InvalidRequestSnafu, RequestPhase, Result, ZjhttpcError},
+    requestx::Request,
+    response::Response,
+    stream::BoxedStream,
+};
+
+/// Write side of a duplex request.
+pub struct DuplexWriter {
+    half: WriteHalf<BoxedStream>,
+    chunked: bool,
+    remaining: u64,
+}
+
+impl DuplexWriter {
+    pub(crate) fn new(half: WriteHalf<BoxedStream>, chunked: bool, content_length: u64) -> Self {
+        DuplexWriter {
+            half,
+            chunked,
+            remaining: content_length,
+        }
+    }
+
+    /// Write and flush one piece of the request body. With chunked transfer
+    /// encoding each call becomes one chunk; otherwise the total must not
+    /// exceed the declared Content-Length.
+    pub async fn write_body_chunk(&mut self, data: impl AsRef<[u8]>) -> Result<()> {
+        let data = data.as_ref();
+        if self.chunked {
+            write_chunk(&mut self.half, data)
+                .await
+                .map_err(|e| ZjhttpcError::from(e).with_phase(RequestPhase::SendBody))?;
+        } else {
+            if data.len() as u64 > self.remaining {
+                return Err(InvalidRequestSnafu {
+                    message: format!(
+                        "body chunk of {} bytes exceeds the remaining {} bytes of Content-Length",
+                        data.len(),
+                        self.remaining
+                    ),
+                }
+                .build());
+            }
+            self.half
+                .write_all(data)
+                .await
+                .map_err(|e| ZjhttpcError::from(e).with_phase(RequestPhase::SendBody))?;
+            self.remaining -= data.len() as u64;
+        }
+        self.half
+            .flush()
+            .await
+            .map_err(|e| ZjhttpcError::from(e).with_phase(RequestPhase::SendBody))?;
+        Ok(())
+    }
+
+    /// End the request body.
+    pub async fn finish(mut self) -> Result<()> {
+        if self.chunked {
+            write_chunk_terminator(&mut self.half)
+                .await
+                .map_err(|e| ZjhttpcError::from(e).with_phase(RequestPhase::SendBody))?;
+        } else if self.remaining > 0 {
+            return Err(InvalidRequestSnafu {
+                message: format!("request body ended {} bytes short of Content-Length", self.remaining),
+            }
+            .build());
+        }
+        self.half
+            .flush()
+            .await
+            .map_err(|e| ZjhttpcError::from(e).with_phase(RequestPhase::SendBody))?;
+        Ok(())
+    }
+}
+
+/// Read side of a duplex request.
+pub struct DuplexReader<'a> {
+    half: ReadHalf<BoxedStream>,
+    cfg: EffectiveConfig,
+    req: &'a Request,
+    addr: SocketAddr,
+    reused: bool,
+}
+
+impl<'a> DuplexReader<'a> {
+    pub(crate) fn new(
+        half: ReadHalf<BoxedStream>,
+        cfg: EffectiveConfig,
+        req: &'a Request,
+        addr: SocketAddr,
+        reused: bool,
+    ) -> Self {
+        DuplexReader { half, cfg, req, addr, reused }
+    }
+
+    /// Wait for the response head. May be awaited while the body is still being
+    /// written through the [`DuplexWriter`].
+    pub async fn read_response_head(self) -> Result<Response> {
+        let stream: BoxedStream = Box::new(ReadOnlyHalf(self.half));
+        read_response_head(&self.cfg, self.req, stream, self.addr, self.reused, None).await
+    }
+}
+
+/// Adapts the read half to [`BoxedStream`]; writes fail since the write half
+/// belongs to the [`DuplexWriter`].
+struct ReadOnlyHalf(ReadHalf<BoxedStream>);
+
+impl async_std::io::Read for ReadOnlyHalf {
+    fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.0).poll_read(cx, buf)
+    }
+}
+
+impl async_std::io::Write for ReadOnlyHalf {
+    fn poll_write(self: Pin<&mut Self>, _cx: &mut Context<'_>, _buf: &[u8]) -> Poll<io::Result<usize>> {
+        Poll::Ready(Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "the write half of a duplex connection is owned by DuplexWriter",
+        )))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{client::ZJHttpClient, methods};
+    use async_std::io::{BufReader, ReadExt};
+    use async_std::io::prelude::BufReadExt;
+    use async_std::net::TcpListener;
+
+    /// Answers the head immediately, then echoes each request chunk back as a response chunk.
+    async fn spawn_echo_server() -> u16 {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        async_std::task::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut writer = stream.clone();
+            let mut reader = BufReader::new(stream);
+            let mut line = String::new();
+            loop {
+                line.clear();
+                reader.read_line(&mut line).await.unwrap();
+                if line == "\r\n" {
+                    break;
+                }
+            }
+            writer
+                .write_all(b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n")
+                .await
+                .unwrap();
+            loop {
+                line.clear();
+                reader.read_line(&mut line).await.unwrap();
+                let size = usize::from_str_radix(line.trim(), 16).unwrap();
+                let mut data = vec![0u8; size + 2];
+                reader.read_exact(&mut data).await.unwrap();
+                data.truncate(size);
+                if size == 0 {
+                    writer.write_all(b"0\r\n\r\n").await.unwrap();
+                    return;
+                }
+                writer.write_all(format!("{size:x}\r\n").as_bytes()).await.unwrap();
+                writer.write_all(&data).await.unwrap();
+                writer.write_all(b"\r\n").await.unwrap();
+            }
+        });
+        port
+    }
+
+    #[async_std::test]
+    async fn test_duplex_requires_opt_in() {
+        let client = ZJHttpClient::builder().build().unwrap();
+        let mut req = Request::new(methods::POST, "http://127.0.0.1:1/").unwrap();
+        let err = client.send_head_duplex(&mut req).await.err().expect("should fail");
+        assert!(matches!(err, ZjhttpcError::InvalidRequest { .. }), "got {err:?}");
+    }
+
+    #[async_std::test]
+    async fn test_duplex_body_and_response_flow_concurrently() {
+        let port = spawn_echo_server().await;
+        let client = ZJHttpClient::builder().build().unwrap();
+        let mut req = Request::new(methods::POST, format!("http://127.0.0.1:{port}/echo"))
+            .unwrap()
+            .put_duplex();
+        req.use_chunked = true;
+
+        let (mut writer, reader) = client.send_head_duplex(&mut req).await.unwrap();
+        // The head arrives before any body byte has been written.
+        let mut resp = reader.read_response_head().await.unwrap();
+        assert_eq!(resp.status_code(), 200);
+        let mut body = resp.body_managed_stream().unwrap();
+
+        let mut buf = [0u8; 16];
+        for msg in ["ping", "pong"] {
+            writer.write_body_chunk(msg).await.unwrap();
+            let n = body.read(&mut buf).await.unwrap();
+            assert_eq!(&buf[..n], msg.as_bytes());
+        }
+        writer.finish().await.unwrap();
+        assert_eq!(body.read(&mut buf).await.unwrap(), 0);
+    }
+
+    #[async_std::test]
+    async fn test_duplex_fixed_length_overflow_is_rejected() {
+        let port = spawn_echo_server().await;
+        let client = ZJHttpClient::builder().build().unwrap();
+        let mut req = Request::new(methods::POST, format!("http://127.0.0.1:{port}/"))
+            .unwrap()
+            .set_content_length(2)
+            .put_duplex();
+        let (mut writer, _reader) = client.send_head_duplex(&mut req).await.unwrap();
+        let err = writer.write_body_chunk("abc").await.unwrap_err();
+        assert!(matches!(err, ZjhttpcError::InvalidRequest { .. }), "got {err:?}");
+    }
+}
@@ -0,0 +1,320 @@
+//! Percent-encoding with the character sets the crate uses, so form bodies,
+//! query strings and request targets all escape the same bytes.
+//!
+//! Letters and digits are never escaped; each [`EncodeSet`] lists the other
+//! ASCII bytes it leaves as they are. The `*_into` functions append to a
+//! caller's buffer, the others borrow their input when nothing changes.
+
+use std::borrow::Cow;
+
+/// The bytes an encoding leaves unescaped, and how it writes a space.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EncodeSet {
+    /// Bit `b` set: ASCII byte `b` is written as is.
+    keep: u128,
+    space_as_plus: bool,
+}
+
+impl EncodeSet {
+    const fn new(extra: &[u8], space_as_plus: bool) -> Self {
+        let mut keep = 0u128;
+        let mut b = 0u8;
+        while b < 128 {
+            if b.is_ascii_alphanumeric() {
+                keep |= 1 << b;
+            }
+            b += 1;
+        }
+        let mut i = 0;
+        while i < extra.len() {
+            keep |= 1 << extra[i];
+            i += 1;
+        }
+        EncodeSet { keep, space_as_plus }
+    }
+
+    /// Whether `b` is written as is.
+    pub const fn keeps(&self, b: u8) -> bool {
+        b < 128 && (self.keep >> b) & 1 == 1
+    }
+
+    /// Whether a space is written as `+` (and `+` decoded as a space).
+    pub const fn space_as_plus(&self) -> bool {
+        self.space_as_plus
+    }
+}
+
+/// `application/x-www-form-urlencoded` keys and values, in bodies and in
+/// query strings built from pairs: only unreserved bytes kept, space as `+`.
+pub const FORM: &EncodeSet = &EncodeSet::new(b"-._~", true);
+
+/// Form keys: [`FORM`] plus `[` and `]`, which bracket syntax (`tags[0]`,
+/// `user[name]`) needs literally for serde_qs and PHP- or Rails-style
+/// parsers to read nested fields.
+pub const FORM_KEY: &EncodeSet = &EncodeSet::new(b"-._~[]", true);
+
+/// A single key or value in a query string: RFC 3986 query characters
+/// except the pair delimiters `&`, `=`, `+` and `#`.
+pub const QUERY: &EncodeSet = &EncodeSet::new(b"-._~!$'()*,;:@/?", false);
+
+/// RFC 3986 unreserved bytes only, space as `%20`: the strict encoding
+/// request signing (e.g. AWS SigV4) requires for query keys and values.
+pub const STRICT: &EncodeSet = &EncodeSet::new(b"-._~", false);
+
+/// One path segment: RFC 3986 `pchar`, so `/`, `?` and `#` are escaped.
+pub const PATH_SEGMENT: &EncodeSet = &EncodeSet::new(b"-._~!$&'()*+,;=:@", false);
+
+/// A user name or password in the userinfo of a URL. `:` is escaped since
+/// it separates the two.
+pub const USERINFO: &EncodeSet = &EncodeSet::new(b"-._~!$&'()*+,;=", false);
+
+/// Path and query of an already assembled request target: every byte
+/// allowed in RFC 3986 `path` and `query`, plus `%` so existing escapes
+/// pass through.
+pub(crate) const REQUEST_TARGET: &EncodeSet = &EncodeSet::new(b"-._~!$&'()*+,;=:@/?%", false);
+
+const HEX: &[u8; 16] = b"0123456789ABCDEF";
+
+/// Append `input` to `out`, escaping every byte `set` does not keep.
+pub fn encode_into(input: &[u8], set: &EncodeSet, out: &mut String) {
+    for &b in input {
+        if set.keeps(b) {
+            out.push(b as char);
+        } else if b == b' ' && set.space_as_plus {
+            out.push('+');
+        } else {
+            out.push('%');
+            out.push(HEX[usize::from(b >> 4)] as char);
+            out.push(HEX[usize::from(b & 0x0f)] as char);
+        }
+    }
+}
+
+/// Escape `input` with `set`; borrowed when nothing needs escaping.
+pub fn encode<'a>(input: &'a str, set: &EncodeSet) -> Cow<'a, str> {
+    if input.bytes().all(|b| set.keeps(b)) {
+        return Cow::Borrowed(input);
+    }
+    let mut out = String::with_capacity(input.len() + input.len() / 2);
+    encode_into(input.as_bytes(), set, &mut out);
+    Cow::Owned(out)
+}
+
+/// Append the decoded bytes of `input` to `out`. `%XX` escapes are decoded
+/// whatever the set; `+` becomes a space only for sets that encode a space
+/// as `+`. A `%` not followed by two hex digits is kept literally.
+pub fn decode_into(input: &[u8], set: &EncodeSet, out: &mut Vec<u8>) {
+    let mut i = 0;
+    while i < input.len() {
+        match input[i] {
+            b'%' if i + 2 < input.len() => match (hex_value(input[i + 1]), hex_value(input[i + 2])) {
+                (Some(high), Some(low)) => {
+                    out.push(high << 4 | low);
+                    i += 3;
+                    continue;
+                }
+                _ => out.push(b'%'),
+            },
+            b'+' if set.space_as_plus => out.push(b' '),
+            b => out.push(b),
+        }
+        i += 1;
+    }
+}
+
+/// Decode `input` with `set`; borrowed when it contains no escapes.
+pub fn decode<'a>(input: &'a str, set: &EncodeSet) -> Cow<'a, [u8]> {
+    if !input.bytes().any(|b| b == b'%' || (b == b'+' && set.space_as_plus)) {
+        return Cow::Borrowed(input.as_bytes());
+    }
+    let mut out = Vec::with_capacity(input.len());
+    decode_into(input.as_bytes(), set, &mut out);
+    Cow::Owned(out)
+}
+
+/// Append `key=value` pairs joined by `&` to `out`, keys escaped with
+/// [`FORM_KEY`] and values with [`FORM`]. A separator is added first when
+/// `out` is not empty.
+pub fn encode_form_pairs<K, V>(pairs: impl IntoIterator<Item = (K, V)>, out: &mut String)
+where
+    K: AsRef<str>,
+    V: AsRef<str>,
+{
+    for (key, value) in pairs {
+        if !out.is_empty() {
+            out.push('&');
+        }
+        encode_into(key.as_ref().as_bytes(), FORM_KEY, out);
+        out.push('=');
+        encode_into(value.as_ref().as_bytes(), FORM, out);
+    }
+}
+
+/// Split a form-urlencoded string into decoded pairs. A pair without `=`
+/// has an empty value; bytes that are not UTF-8 after decoding are replaced
+/// with U+FFFD.
+pub fn decode_form_pairs(input: &str) -> impl Iterator<Item = (String, String)> + '_ {
+    input.split('&').filter(|pair| !pair.is_empty()).map(|pair| {
+        let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+        let text = |s: &str| String::from_utf8_lossy(&decode(s, FORM)).into_owned();
+        (text(key), text(value))
+    })
+}
+
+/// Rewrite a query string with every key and value decoded (`+` as a space)
+/// and re-escaped with [`STRICT`]; a pair without `=` gets an empty value.
+/// With `sorted`, pairs are stable-sorted by escaped key, so repeated keys
+/// keep their relative order.
+pub fn canonical_query(query: &str, sorted: bool) -> String {
+    let mut decoded = Vec::new();
+    let mut strict = |s: &str| {
+        decoded.clear();
+        decode_into(s.as_bytes(), FORM, &mut decoded);
+        let mut out = String::with_capacity(s.len());
+        encode_into(&decoded, STRICT, &mut out);
+        out
+    };
+    let mut pairs: Vec<(String, String)> = query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (strict(key), strict(value))
+        })
+        .collect();
+    if sorted {
+        pairs.sort_by(|(a, _), (b, _)| a.cmp(b));
+    }
+    let mut out = String::with_capacity(query.len());
+    for (key, value) in &pairs {
+        if !out.is_empty() {
+            out.push('&');
+        }
+        out.push_str(key);
+        out.push('=');
+        out.push_str(value);
+    }
+    out
+}
+
+fn hex_value(b: u8) -> Option<u8> {
+    match b {
+        b'0'..=b'9' => Some(b - b'0'),
+        b'a'..=b'f' => Some(b - b'a' + 10),
+        b'A'..=b'F' => Some(b - b'A' + 10),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALNUM: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789";
+
+    /// Every byte value against the set's documented keep list.
+    fn assert_set(set: &EncodeSet, extra: &[u8]) {
+        for b in 0..=255u8 {
+            let mut out = String::new();
+            encode_into(&[b], set, &mut out);
+            let expected = if ALNUM.contains(&b) || extra.contains(&b) {
+                (b as char).to_string()
+            } else if b == b' ' && set.space_as_plus() {
+                "+".to_string()
+            } else {
+                format!("%{b:02X}")
+            };
+            assert_eq!(out, expected, "byte {b:#04x}");
+
+            let mut decoded = Vec::new();
+            decode_into(out.as_bytes(), set, &mut decoded);
+            assert_eq!(decoded, [b], "round trip of byte {b:#04x}");
+        }
+    }
+
+    #[test]
+    fn test_set_tables() {
+        assert_set(FORM, b"-._~");
+        assert_set(FORM_KEY, b"-._~[]");
+        assert_set(QUERY, b"-._~!$'()*,;:@/?");
+        assert_set(STRICT, b"-._~");
+        assert_set(PATH_SEGMENT, b"-._~!$&'()*+,;=:@");
+        assert_set(USERINFO, b"-._~!$&'()*+,;=");
+    }
+
+    #[test]
+    fn test_known_vectors() {
+        let cases: &[(&str, &EncodeSet, &str)] = &[
+            ("hello world", FORM, "hello+world"),
+            ("hello world", QUERY, "hello%20world"),
+            ("hello world", PATH_SEGMENT, "hello%20world"),
+            ("a+b=c&d", FORM, "a%2Bb%3Dc%26d"),
+            ("a+b=c&d", QUERY, "a%2Bb%3Dc%26d"),
+            ("a+b=c&d", PATH_SEGMENT, "a+b=c&d"),
+            ("user@example.com", FORM, "user%40example.com"),
+            ("user@example.com", QUERY, "user@example.com"),
+            ("/path/to/file", PATH_SEGMENT, "%2Fpath%2Fto%2Ffile"),
+            ("/path/to/file", QUERY, "/path/to/file"),
+            ("50%#off", QUERY, "50%25%23off"),
+            ("p@ss:word", USERINFO, "p%40ss%3Aword"),
+            ("tags[0]", FORM, "tags%5B0%5D"),
+            ("tags[0]", FORM_KEY, "tags[0]"),
+            ("你好", FORM, "%E4%BD%A0%E5%A5%BD"),
+            ("/a b/%41?x=[1]", REQUEST_TARGET, "/a%20b/%41?x=%5B1%5D"),
+        ];
+        for (input, set, expected) in cases {
+            assert_eq!(encode(input, set), *expected, "encoding {input:?}");
+            if *set != REQUEST_TARGET {
+                assert_eq!(decode(expected, set), input.as_bytes(), "decoding {expected:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_unchanged_input_is_borrowed() {
+        assert!(matches!(encode("plain-text_1.0~", FORM), Cow::Borrowed(_)));
+        assert!(matches!(decode("a+b", QUERY), Cow::Borrowed(_)));
+        assert!(matches!(decode("a+b", FORM), Cow::Owned(_)));
+    }
+
+    #[test]
+    fn test_malformed_escapes_are_kept() {
+        assert_eq!(decode("100%", FORM), b"100%".as_slice());
+        assert_eq!(decode("%zz%4", QUERY), b"%zz%4".as_slice());
+        assert_eq!(decode("%e4%bd%a0", QUERY), "你".as_bytes());
+    }
+
+    #[test]
+    fn test_encode_into_appends() {
+        let mut out = String::from("x=");
+        encode_into(b"a b", QUERY, &mut out);
+        assert_eq!(out, "x=a%20b");
+    }
+
+    #[test]
+    fn test_canonical_query() {
+        assert_eq!(canonical_query("b=2&a=1&b=1&a=0", true), "a=1&a=0&b=2&b=1");
+        assert_eq!(canonical_query("b=2&a=1", false), "b=2&a=1");
+        assert_eq!(canonical_query("flag&x=", true), "flag=&x=");
+        assert_eq!(canonical_query("p=a%2Fb&q=%7Euser&r=a+b&s=a%20b&t=*", false), "p=a%2Fb&q=~user&r=a%20b&s=a%20b&t=%2A");
+        assert_eq!(canonical_query("", true), "");
+    }
+
+    #[test]
+    fn test_form_pairs() {
+        let mut out = String::from("existing=1");
+        encode_form_pairs([("name", "Alice & Bob"), ("tags[0]", "http client"), ("empty", "")], &mut out);
+        assert_eq!(out, "existing=1&name=Alice+%26+Bob&tags[0]=http+client&empty=");
+
+        let pairs: Vec<_> = decode_form_pairs("a=1&&b&c=x%3Dy+z&d=%FF").collect();
+        assert_eq!(
+            pairs,
+            [
+                ("a".to_string(), "1".to_string()),
+                ("b".to_string(), String::new()),
+                ("c".to_string(), "x=y z".to_string()),
+                ("d".to_string(), "\u{FFFD}".to_string()),
+            ]
+        );
+    }
+}
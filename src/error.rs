@@ -2,6 +2,8 @@ use std::sync::Arc;
 use std::time::Duration;
 use snafu::Snafu;
 
+use crate::config::ConfigIssue;
+
 /// Error type for zjhttpc operations.
 ///
 /// All public API functions return `Result<T, ZjhttpcError>`.
@@ -16,7 +18,7 @@ use snafu::Snafu;
 #[non_exhaustive]
 pub enum ZjhttpcError {
     // URL / Request validation
-    #[snafu(display("URL parse error: {source} at {location}"))]
+    #[snafu(display("[ZJ-URL-001] URL parse error: {source} at {location}"))]
     InvalidUrl {
         #[snafu(source)]
         source: url::ParseError,
@@ -24,42 +26,83 @@ pub enum ZjhttpcError {
         location: snafu::Location,
     },
 
-    #[snafu(display("no host in URL at {location}"))]
+    #[snafu(display("[ZJ-URL-002] no host in URL at {location}"))]
     NoHost {
         #[snafu(implicit)]
         location: snafu::Location,
     },
 
-    #[snafu(display("URL must have a valid port at {location}"))]
+    #[snafu(display("[ZJ-URL-003] URL must have a valid port at {location}"))]
     NoPort {
         #[snafu(implicit)]
         location: snafu::Location,
     },
 
-    #[snafu(display("unsupported scheme: {scheme} at {location}"))]
+    #[snafu(display("[ZJ-URL-004] unsupported scheme: {scheme} at {location}"))]
     UnsupportedScheme {
         scheme: String,
         #[snafu(implicit)]
         location: snafu::Location,
     },
 
+    #[snafu(display("[ZJ-REQ-001] invalid request: {message} at {location}"))]
+    InvalidRequest {
+        message: String,
+        #[snafu(implicit)]
+        location: snafu::Location,
+    },
+
+    /// `ZJHttpClientBuilder::build` rejected the client settings; `issues`
+    /// lists every problem found.
+    #[snafu(display("[ZJ-CONFIG-001] invalid client configuration: {} at {location}", format_issues(issues)))]
+    InvalidConfig {
+        issues: Vec<ConfigIssue>,
+        #[snafu(implicit)]
+        location: snafu::Location,
+    },
+
+    /// The serialized request line (method, target and version) is longer than
+    /// `ZJHttpClient::global_max_request_line_bytes`.
+    #[snafu(display("[ZJ-REQ-002] request line is {len} bytes, exceeding the {limit}-byte limit at {location}"))]
+    RequestLineTooLong {
+        len: usize,
+        limit: usize,
+        #[snafu(implicit)]
+        location: snafu::Location,
+    },
+
+    #[snafu(display("[ZJ-REQ-003] invalid header: {message} at {location}"))]
+    InvalidHeader {
+        message: String,
+        #[snafu(implicit)]
+        location: snafu::Location,
+    },
+
     // DNS
-    #[snafu(display("DNS resolution failed: {message} at {location}"))]
+    #[snafu(display("[ZJ-DNS-001] DNS resolution failed: {message} at {location}"))]
     Dns {
         message: String,
         #[snafu(implicit)]
         location: snafu::Location,
     },
 
+    #[snafu(display("[ZJ-DNS-002] no permitted address for {host}: the address filter rejected {rejected:?} at {location}"))]
+    NoPermittedAddress {
+        host: String,
+        rejected: Vec<std::net::SocketAddr>,
+        #[snafu(implicit)]
+        location: snafu::Location,
+    },
+
     // Connection
-    #[snafu(display("connection failed: {message} at {location}"))]
+    #[snafu(display("[ZJ-CONN-001] connection failed: {message} at {location}"))]
     Connection {
         message: String,
         #[snafu(implicit)]
         location: snafu::Location,
     },
 
-    #[snafu(display("connection timeout after {duration:?} at {location}"))]
+    #[snafu(display("[ZJ-TIMEOUT-CONNECT] connection timeout after {duration:?} at {location}"))]
     ConnectionTimeout {
         duration: Duration,
         #[snafu(implicit)]
@@ -67,59 +110,130 @@ pub enum ZjhttpcError {
     },
 
     // TLS / Certificate
-    #[snafu(display("TLS error: {message} at {location}"))]
+    #[snafu(display("[ZJ-TLS-001] TLS error: {message} at {location}"))]
     Tls {
         message: String,
         #[snafu(implicit)]
         location: snafu::Location,
     },
 
-    #[snafu(display("certificate error: {message} at {location}"))]
+    #[snafu(display("[ZJ-TLS-002] certificate error: {message} at {location}"))]
     Certificate {
         message: String,
         #[snafu(implicit)]
         location: snafu::Location,
     },
 
+    /// `require_ocsp_staple` is set and the server did not staple an OCSP
+    /// response to its handshake.
+    #[snafu(display("[ZJ-TLS-003] the server did not staple an OCSP response at {location}"))]
+    OcspStapleMissing {
+        #[snafu(implicit)]
+        location: snafu::Location,
+    },
+
+    /// `require_ocsp_staple` is set and the stapled OCSP response was not
+    /// accepted: revoked, expired, badly signed or malformed.
+    #[snafu(display("[ZJ-TLS-004] invalid stapled OCSP response: {reason} at {location}"))]
+    OcspStapleInvalid {
+        reason: String,
+        #[snafu(implicit)]
+        location: snafu::Location,
+    },
+
     // Proxy
-    #[snafu(display("proxy error: {message} at {location}"))]
+    #[snafu(display("[ZJ-PROXY-001] proxy error: {message} at {location}"))]
     Proxy {
         message: String,
         #[snafu(implicit)]
         location: snafu::Location,
     },
 
+    /// The proxy answered `CONNECT` with a status other than 2xx, e.g. 407
+    /// when it wants (other) credentials.
+    #[snafu(display("[ZJ-PROXY-002] proxy refused the tunnel to {target} with {status}: {status_line} at {location}"))]
+    ProxyRefused {
+        status: u16,
+        /// `host:port` the tunnel was asked for.
+        target: String,
+        status_line: String,
+        #[snafu(implicit)]
+        location: snafu::Location,
+    },
+
     // Timeout
-    #[snafu(display("send header timeout after {duration:?} at {location}"))]
+    #[snafu(display("[ZJ-TIMEOUT-SEND-HEADERS] send header timeout after {duration:?} at {location}"))]
     SendHeaderTimeout {
         duration: Duration,
         #[snafu(implicit)]
         location: snafu::Location,
     },
 
-    #[snafu(display("read header timeout after {duration:?} at {location}"))]
+    #[snafu(display("[ZJ-TIMEOUT-HEADERS] read header timeout after {duration:?} at {location}"))]
     ReadHeaderTimeout {
         duration: Duration,
         #[snafu(implicit)]
         location: snafu::Location,
     },
 
-    #[snafu(display("read body timeout after {duration:?} at {location}"))]
+    /// No byte of the response arrived within `first_byte_timeout`: the
+    /// server is not answering.
+    #[snafu(display("[ZJ-TIMEOUT-FIRST-BYTE] no response byte within {duration:?} at {location}"))]
+    FirstByteTimeout {
+        duration: Duration,
+        #[snafu(implicit)]
+        location: snafu::Location,
+    },
+
+    /// The response head started but was not complete within
+    /// `header_complete_timeout`: something is dribbling the headers.
+    #[snafu(display("[ZJ-TIMEOUT-HEADERS-COMPLETE] response head not complete {duration:?} after its first byte at {location}"))]
+    HeaderCompleteTimeout {
+        duration: Duration,
+        #[snafu(implicit)]
+        location: snafu::Location,
+    },
+
+    #[snafu(display("[ZJ-TIMEOUT-BODY] read body timeout after {duration:?} at {location}"))]
     ReadBodyTimeout {
         duration: Duration,
         #[snafu(implicit)]
         location: snafu::Location,
     },
 
+    /// `send()`, or reading the body after it, ran past `total_timeout`.
+    #[snafu(display("[ZJ-TIMEOUT-TOTAL] request not done within the total timeout of {duration:?} at {location}"))]
+    TotalTimeout {
+        duration: Duration,
+        #[snafu(implicit)]
+        location: snafu::Location,
+    },
+
     // Response parsing
-    #[snafu(display("invalid HTTP response: {message} at {location}"))]
+    #[snafu(display("[ZJ-PROTO-001] invalid HTTP response: {message} at {location}"))]
     InvalidResponse {
         message: String,
         #[snafu(implicit)]
         location: snafu::Location,
     },
 
-    #[snafu(display("response headers exceeded limit ({actual} > {max}) at {location}"))]
+    /// The response did not start with a valid HTTP/1.x status line, i.e. the
+    /// peer is not speaking HTTP/1.x on this connection.
+    #[snafu(display(
+        "[ZJ-PROTO-002] invalid status line {status_line:?} from {addr} (reused connection: {reused}): {message} at {location}"
+    ))]
+    InvalidStatusLine {
+        /// The first line of the response, lossily decoded and cut to 128 bytes.
+        status_line: String,
+        message: String,
+        addr: std::net::SocketAddr,
+        /// Whether the connection came from the pool.
+        reused: bool,
+        #[snafu(implicit)]
+        location: snafu::Location,
+    },
+
+    #[snafu(display("[ZJ-PROTO-003] response headers exceeded limit ({actual} > {max}) at {location}"))]
     ResponseTooLarge {
         actual: usize,
         max: usize,
@@ -127,7 +241,7 @@ pub enum ZjhttpcError {
         location: snafu::Location,
     },
 
-    #[snafu(display("unexpected EOF: {message} at {location}"))]
+    #[snafu(display("[ZJ-CONN-002] unexpected EOF: {message} at {location}"))]
     UnexpectedEof {
         message: String,
         #[snafu(implicit)]
@@ -135,13 +249,70 @@ pub enum ZjhttpcError {
     },
 
     // Body
-    #[snafu(display("response body has already been read at {location}"))]
+    #[snafu(display("[ZJ-PROTO-004] invalid partial content response: {message} at {location}"))]
+    InvalidByteRanges {
+        message: String,
+        #[snafu(implicit)]
+        location: snafu::Location,
+    },
+
+    #[snafu(display("[ZJ-BODY-001] response body has already been read at {location}"))]
     BodyAlreadyRead {
         #[snafu(implicit)]
         location: snafu::Location,
     },
 
-    #[snafu(display("JSON parsing failed: {message} at {location}"))]
+    /// The connection ended before the declared Content-Length was received.
+    #[snafu(display("[ZJ-CONN-003] response body truncated: received {received} of {expected} bytes at {location}"))]
+    BodyTruncated {
+        expected: u64,
+        received: u64,
+        #[snafu(implicit)]
+        location: snafu::Location,
+    },
+
+    /// The body is too long to be buffered in one allocation on this
+    /// platform (more than `limit` bytes); stream it with
+    /// `Response::copy_body_to` or `save_to_file` instead. `length` is the
+    /// `Content-Length`, if the response had one.
+    #[snafu(display("[ZJ-BODY-006] response body does not fit in memory: {length:?} bytes, limit {limit} at {location}"))]
+    BodyTooLarge {
+        length: Option<u64>,
+        limit: u64,
+        #[snafu(implicit)]
+        location: snafu::Location,
+    },
+
+    /// The body of a 101 Switching Protocols response was read; the connection
+    /// belongs to the new protocol, see `Response::into_upgraded_stream`.
+    #[snafu(display("[ZJ-BODY-002] the server switched protocols (101), the response has no body at {location}"))]
+    ProtocolSwitched {
+        #[snafu(implicit)]
+        location: snafu::Location,
+    },
+
+    /// `Response::into_upgraded_stream` on a response other than 101.
+    #[snafu(display("[ZJ-BODY-003] cannot take an upgraded stream from a {status} response at {location}"))]
+    NotUpgraded {
+        status: u16,
+        #[snafu(implicit)]
+        location: snafu::Location,
+    },
+
+    /// The response media type does not match what the request expected
+    /// (`Request::expect_json` / `expect_text`).
+    #[snafu(display("[ZJ-BODY-004] expected {expected}, server sent {actual} — first 200 bytes: {preview} at {location}"))]
+    UnexpectedContentType {
+        expected: String,
+        actual: String,
+        preview: String,
+        #[snafu(implicit)]
+        location: snafu::Location,
+    },
+
+    /// The body is not the JSON `body_json` / `body_json_as` expected;
+    /// `preview` holds its first 200 bytes.
+    #[snafu(display("[ZJ-BODY-005] JSON parsing failed: {message} — first 200 bytes: {preview} at {location}"))]
     JsonParsing {
         message: String,
         preview: String,
@@ -149,8 +320,86 @@ pub enum ZjhttpcError {
         location: snafu::Location,
     },
 
+    /// The body did not decode under the `Content-Encoding` the response
+    /// declared (see `ZJHttpClientBuilder::set_auto_decompress`).
+    #[snafu(display("[ZJ-BODY-007] response body is not valid {encoding}: {message} at {location}"))]
+    Decompress {
+        encoding: String,
+        message: String,
+        #[snafu(implicit)]
+        location: snafu::Location,
+    },
+
+    /// A compressed body decoded to more than `max_decompression_ratio`
+    /// (see `ZJHttpClientBuilder::set_max_decompression_ratio`) times the
+    /// `wire_bytes` received for it. The connection is not reused.
+    #[snafu(display("[ZJ-BODY-010] decompression bomb: {wire_bytes} bytes received decoded to more than {decoded_bytes} at {location}"))]
+    DecompressionBomb {
+        wire_bytes: u64,
+        decoded_bytes: u64,
+        #[snafu(implicit)]
+        location: snafu::Location,
+    },
+
+    /// The body does not hash to the digest it was checked against: the
+    /// response's `Content-MD5` (see `ZJHttpClientBuilder::set_verify_content_md5`)
+    /// or `Request::set_expected_sha256`. Digests are hex.
+    #[snafu(display("[ZJ-BODY-008] response body {algorithm} is {actual}, expected {expected} at {location}"))]
+    ChecksumMismatch {
+        algorithm: String,
+        expected: String,
+        actual: String,
+        #[snafu(implicit)]
+        location: snafu::Location,
+    },
+
+    /// A body reader that buffers could not reserve `requested` more bytes of
+    /// the client's memory budget: `in_use` of `limit` bytes were held by
+    /// other responses; see [`crate::memory_budget`].
+    #[snafu(display("[ZJ-BODY-009] memory budget exhausted: {requested} more bytes wanted, {in_use} of {limit} in use at {location}"))]
+    MemoryBudgetExceeded {
+        requested: u64,
+        in_use: u64,
+        limit: u64,
+        #[snafu(implicit)]
+        location: snafu::Location,
+    },
+
+    /// `send()` followed `limit` redirects (`ZJHttpClientBuilder::set_max_redirects`)
+    /// and was sent on once more, to `url`.
+    #[snafu(display("[ZJ-REDIRECT-001] more than {limit} redirects, the last one to {url} at {location}"))]
+    TooManyRedirects {
+        limit: usize,
+        url: String,
+        #[snafu(implicit)]
+        location: snafu::Location,
+    },
+
+    /// A 307 or 308 redirect asks for the request body again, but it was a
+    /// stream that has already been sent.
+    #[snafu(display("[ZJ-REDIRECT-002] cannot follow the {status} redirect to {url}: the stream body cannot be sent twice at {location}"))]
+    RedirectBodyNotReplayable {
+        status: u16,
+        url: String,
+        #[snafu(implicit)]
+        location: snafu::Location,
+    },
+
+    /// `send()` refused to follow a redirect to `url`: the
+    /// [`RedirectPolicy`](crate::redirect::RedirectPolicy) vetoed it, or the
+    /// hop failed the scheme, `https_only` or address filter check. `chain`
+    /// lists the URLs requested before it, the original request first.
+    #[snafu(display("[ZJ-REDIRECT-003] redirect to {url} blocked: {reason} (after {}) at {location}", chain.join(" -> ")))]
+    RedirectBlocked {
+        url: String,
+        reason: String,
+        chain: Vec<String>,
+        #[snafu(implicit)]
+        location: snafu::Location,
+    },
+
     // Query serialization (serde_qs::Error is not Clone, so we keep its display string)
-    #[snafu(display("query serialization error: {message} at {location}"))]
+    #[snafu(display("[ZJ-REQ-004] query serialization error: {message} at {location}"))]
     QuerySerialize {
         message: String,
         #[snafu(implicit)]
@@ -158,24 +407,302 @@ pub enum ZjhttpcError {
     },
 
     // Multipart
-    #[snafu(display("multipart content-length computation failed: {message} at {location}"))]
+    #[snafu(display("[ZJ-REQ-005] multipart content-length computation failed: {message} at {location}"))]
     MultipartContentLength {
         message: String,
         #[snafu(implicit)]
         location: snafu::Location,
     },
 
+    #[snafu(display("[ZJ-REQ-006] upload cancelled after {parts_sent} multipart part(s) at {location}"))]
+    UploadCancelled {
+        parts_sent: usize,
+        #[snafu(implicit)]
+        location: snafu::Location,
+    },
+
+    /// A `Request::set_body_json` value failed to serialize.
+    #[snafu(display("[ZJ-REQ-007] JSON serialization error: {message} at {location}"))]
+    JsonSerialize {
+        message: String,
+        #[snafu(implicit)]
+        location: snafu::Location,
+    },
+
     // IO
-    #[snafu(display("{source} at {location}"))]
+    #[snafu(display("[ZJ-IO-001] {source} at {location}"))]
     Io {
         #[snafu(source(from(std::io::Error, Arc::new)))]
         source: Arc<std::io::Error>,
+        /// Stage of the request the IO error surfaced in, filled in by `send()`.
+        phase: Option<RequestPhase>,
         #[snafu(implicit)]
         location: snafu::Location,
     },
 }
 
+/// Stage of the request lifecycle an error was raised in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum RequestPhase {
+    Resolve,
+    Connect,
+    Tls,
+    SendHeader,
+    SendBody,
+    ReadHeader,
+    ReadBody,
+}
+
+/// Broad class of an error, see [`ZjhttpcError::category`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ErrorCategory {
+    /// Resolving, connecting or talking to the peer failed, or the connection
+    /// broke off.
+    Network,
+    /// The TLS handshake failed or the certificate was rejected.
+    Tls,
+    /// The peer sent something that is not valid HTTP/1.x.
+    Protocol,
+    /// A configured timeout ran out.
+    Timeout,
+    /// The request or client as the caller set it up cannot be sent: bad
+    /// URL, header or settings, or an upload the caller cancelled.
+    Config,
+    /// The response is valid HTTP but cannot be used the way the caller asked,
+    /// e.g. a body of the wrong type or one that was already read.
+    Http,
+}
+
 impl ZjhttpcError {
+    /// Stable short code of the variant, e.g. `ZJ-DNS-001` or
+    /// `ZJ-TIMEOUT-HEADERS`. Display output starts with it in brackets, so
+    /// logs can be classified without matching on the message. Codes are
+    /// never changed or reused once released.
+    pub fn code(&self) -> &'static str {
+        match self {
+            ZjhttpcError::InvalidUrl { .. } => "ZJ-URL-001",
+            ZjhttpcError::NoHost { .. } => "ZJ-URL-002",
+            ZjhttpcError::NoPort { .. } => "ZJ-URL-003",
+            ZjhttpcError::UnsupportedScheme { .. } => "ZJ-URL-004",
+            ZjhttpcError::InvalidRequest { .. } => "ZJ-REQ-001",
+            ZjhttpcError::InvalidConfig { .. } => "ZJ-CONFIG-001",
+            ZjhttpcError::RequestLineTooLong { .. } => "ZJ-REQ-002",
+            ZjhttpcError::InvalidHeader { .. } => "ZJ-REQ-003",
+            ZjhttpcError::Dns { .. } => "ZJ-DNS-001",
+            ZjhttpcError::NoPermittedAddress { .. } => "ZJ-DNS-002",
+            ZjhttpcError::Connection { .. } => "ZJ-CONN-001",
+            ZjhttpcError::ConnectionTimeout { .. } => "ZJ-TIMEOUT-CONNECT",
+            ZjhttpcError::Tls { .. } => "ZJ-TLS-001",
+            ZjhttpcError::Certificate { .. } => "ZJ-TLS-002",
+            ZjhttpcError::OcspStapleMissing { .. } => "ZJ-TLS-003",
+            ZjhttpcError::OcspStapleInvalid { .. } => "ZJ-TLS-004",
+            ZjhttpcError::Proxy { .. } => "ZJ-PROXY-001",
+            ZjhttpcError::ProxyRefused { .. } => "ZJ-PROXY-002",
+            ZjhttpcError::SendHeaderTimeout { .. } => "ZJ-TIMEOUT-SEND-HEADERS",
+            ZjhttpcError::ReadHeaderTimeout { .. } => "ZJ-TIMEOUT-HEADERS",
+            ZjhttpcError::FirstByteTimeout { .. } => "ZJ-TIMEOUT-FIRST-BYTE",
+            ZjhttpcError::HeaderCompleteTimeout { .. } => "ZJ-TIMEOUT-HEADERS-COMPLETE",
+            ZjhttpcError::ReadBodyTimeout { .. } => "ZJ-TIMEOUT-BODY",
+            ZjhttpcError::TotalTimeout { .. } => "ZJ-TIMEOUT-TOTAL",
+            ZjhttpcError::InvalidResponse { .. } => "ZJ-PROTO-001",
+            ZjhttpcError::InvalidStatusLine { .. } => "ZJ-PROTO-002",
+            ZjhttpcError::ResponseTooLarge { .. } => "ZJ-PROTO-003",
+            ZjhttpcError::UnexpectedEof { .. } => "ZJ-CONN-002",
+            ZjhttpcError::InvalidByteRanges { .. } => "ZJ-PROTO-004",
+            ZjhttpcError::BodyAlreadyRead { .. } => "ZJ-BODY-001",
+            ZjhttpcError::BodyTruncated { .. } => "ZJ-CONN-003",
+            ZjhttpcError::ProtocolSwitched { .. } => "ZJ-BODY-002",
+            ZjhttpcError::NotUpgraded { .. } => "ZJ-BODY-003",
+            ZjhttpcError::UnexpectedContentType { .. } => "ZJ-BODY-004",
+            ZjhttpcError::JsonParsing { .. } => "ZJ-BODY-005",
+            ZjhttpcError::BodyTooLarge { .. } => "ZJ-BODY-006",
+            ZjhttpcError::Decompress { .. } => "ZJ-BODY-007",
+            ZjhttpcError::DecompressionBomb { .. } => "ZJ-BODY-010",
+            ZjhttpcError::ChecksumMismatch { .. } => "ZJ-BODY-008",
+            ZjhttpcError::MemoryBudgetExceeded { .. } => "ZJ-BODY-009",
+            ZjhttpcError::TooManyRedirects { .. } => "ZJ-REDIRECT-001",
+            ZjhttpcError::RedirectBodyNotReplayable { .. } => "ZJ-REDIRECT-002",
+            ZjhttpcError::RedirectBlocked { .. } => "ZJ-REDIRECT-003",
+            ZjhttpcError::QuerySerialize { .. } => "ZJ-REQ-004",
+            ZjhttpcError::MultipartContentLength { .. } => "ZJ-REQ-005",
+            ZjhttpcError::UploadCancelled { .. } => "ZJ-REQ-006",
+            ZjhttpcError::JsonSerialize { .. } => "ZJ-REQ-007",
+            ZjhttpcError::Io { .. } => "ZJ-IO-001",
+        }
+    }
+
+    /// Broad class of the error, for metrics and policies.
+    pub fn category(&self) -> ErrorCategory {
+        match self {
+            ZjhttpcError::Dns { .. }
+            | ZjhttpcError::Connection { .. }
+            | ZjhttpcError::Proxy { .. }
+            | ZjhttpcError::ProxyRefused { .. }
+            | ZjhttpcError::UnexpectedEof { .. }
+            | ZjhttpcError::BodyTruncated { .. }
+            | ZjhttpcError::Io { .. } => ErrorCategory::Network,
+            ZjhttpcError::Tls { .. }
+            | ZjhttpcError::Certificate { .. }
+            | ZjhttpcError::OcspStapleMissing { .. }
+            | ZjhttpcError::OcspStapleInvalid { .. } => ErrorCategory::Tls,
+            ZjhttpcError::InvalidResponse { .. }
+            | ZjhttpcError::InvalidStatusLine { .. }
+            | ZjhttpcError::ResponseTooLarge { .. }
+            | ZjhttpcError::InvalidByteRanges { .. } => ErrorCategory::Protocol,
+            ZjhttpcError::ConnectionTimeout { .. }
+            | ZjhttpcError::SendHeaderTimeout { .. }
+            | ZjhttpcError::ReadHeaderTimeout { .. }
+            | ZjhttpcError::FirstByteTimeout { .. }
+            | ZjhttpcError::HeaderCompleteTimeout { .. }
+            | ZjhttpcError::ReadBodyTimeout { .. }
+            | ZjhttpcError::TotalTimeout { .. } => ErrorCategory::Timeout,
+            ZjhttpcError::InvalidUrl { .. }
+            | ZjhttpcError::NoHost { .. }
+            | ZjhttpcError::NoPort { .. }
+            | ZjhttpcError::UnsupportedScheme { .. }
+            | ZjhttpcError::InvalidRequest { .. }
+            | ZjhttpcError::InvalidConfig { .. }
+            | ZjhttpcError::RequestLineTooLong { .. }
+            | ZjhttpcError::InvalidHeader { .. }
+            | ZjhttpcError::NoPermittedAddress { .. }
+            | ZjhttpcError::QuerySerialize { .. }
+            | ZjhttpcError::MultipartContentLength { .. }
+            | ZjhttpcError::UploadCancelled { .. }
+            | ZjhttpcError::JsonSerialize { .. } => ErrorCategory::Config,
+            ZjhttpcError::BodyAlreadyRead { .. }
+            | ZjhttpcError::ProtocolSwitched { .. }
+            | ZjhttpcError::NotUpgraded { .. }
+            | ZjhttpcError::UnexpectedContentType { .. }
+            | ZjhttpcError::JsonParsing { .. }
+            | ZjhttpcError::BodyTooLarge { .. }
+            | ZjhttpcError::Decompress { .. }
+            | ZjhttpcError::DecompressionBomb { .. }
+            | ZjhttpcError::ChecksumMismatch { .. }
+            | ZjhttpcError::MemoryBudgetExceeded { .. }
+            | ZjhttpcError::TooManyRedirects { .. }
+            | ZjhttpcError::RedirectBodyNotReplayable { .. }
+            | ZjhttpcError::RedirectBlocked { .. } => ErrorCategory::Http,
+        }
+    }
+
+    /// Whether sending the same request again may well succeed: network
+    /// failures, timeouts and connections that broke off. Stale-connection
+    /// retries in `send()` key off this. Configuration, TLS and most protocol
+    /// errors are permanent; garbage instead of a status line only counts as
+    /// transient on a pooled connection, which the server may have reused
+    /// for something else.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            ZjhttpcError::Dns { .. }
+            | ZjhttpcError::Connection { .. }
+            | ZjhttpcError::ConnectionTimeout { .. }
+            | ZjhttpcError::SendHeaderTimeout { .. }
+            | ZjhttpcError::ReadHeaderTimeout { .. }
+            | ZjhttpcError::FirstByteTimeout { .. }
+            | ZjhttpcError::HeaderCompleteTimeout { .. }
+            | ZjhttpcError::ReadBodyTimeout { .. }
+            | ZjhttpcError::TotalTimeout { .. }
+            | ZjhttpcError::UnexpectedEof { .. }
+            | ZjhttpcError::BodyTruncated { .. }
+            | ZjhttpcError::MemoryBudgetExceeded { .. } => true,
+            ZjhttpcError::InvalidStatusLine { reused, .. } => *reused,
+            ZjhttpcError::Io { source, .. } => is_transient_io(source.kind()),
+            ZjhttpcError::InvalidUrl { .. }
+            | ZjhttpcError::NoHost { .. }
+            | ZjhttpcError::NoPort { .. }
+            | ZjhttpcError::UnsupportedScheme { .. }
+            | ZjhttpcError::InvalidRequest { .. }
+            | ZjhttpcError::InvalidConfig { .. }
+            | ZjhttpcError::RequestLineTooLong { .. }
+            | ZjhttpcError::InvalidHeader { .. }
+            | ZjhttpcError::NoPermittedAddress { .. }
+            | ZjhttpcError::Tls { .. }
+            | ZjhttpcError::Certificate { .. }
+            | ZjhttpcError::OcspStapleMissing { .. }
+            | ZjhttpcError::OcspStapleInvalid { .. }
+            | ZjhttpcError::Proxy { .. }
+            | ZjhttpcError::ProxyRefused { .. }
+            | ZjhttpcError::InvalidResponse { .. }
+            | ZjhttpcError::ResponseTooLarge { .. }
+            | ZjhttpcError::InvalidByteRanges { .. }
+            | ZjhttpcError::BodyAlreadyRead { .. }
+            | ZjhttpcError::ProtocolSwitched { .. }
+            | ZjhttpcError::NotUpgraded { .. }
+            | ZjhttpcError::UnexpectedContentType { .. }
+            | ZjhttpcError::JsonParsing { .. }
+            | ZjhttpcError::BodyTooLarge { .. }
+            | ZjhttpcError::Decompress { .. }
+            | ZjhttpcError::DecompressionBomb { .. }
+            | ZjhttpcError::ChecksumMismatch { .. }
+            | ZjhttpcError::TooManyRedirects { .. }
+            | ZjhttpcError::RedirectBodyNotReplayable { .. }
+            | ZjhttpcError::RedirectBlocked { .. }
+            | ZjhttpcError::QuerySerialize { .. }
+            | ZjhttpcError::MultipartContentLength { .. }
+            | ZjhttpcError::UploadCancelled { .. }
+            | ZjhttpcError::JsonSerialize { .. } => false,
+        }
+    }
+
+    /// Returns the request stage this error belongs to.
+    ///
+    /// Most variants imply their stage; IO errors carry the stage `send()` (or
+    /// the body readers) observed them in. Errors raised while building the
+    /// request, before any network activity, return `None`.
+    pub fn phase(&self) -> Option<RequestPhase> {
+        match self {
+            ZjhttpcError::Dns { .. } | ZjhttpcError::NoPermittedAddress { .. } => Some(RequestPhase::Resolve),
+            ZjhttpcError::Connection { .. }
+            | ZjhttpcError::ConnectionTimeout { .. }
+            | ZjhttpcError::Proxy { .. }
+            | ZjhttpcError::ProxyRefused { .. } => Some(RequestPhase::Connect),
+            ZjhttpcError::Tls { .. }
+            | ZjhttpcError::Certificate { .. }
+            | ZjhttpcError::OcspStapleMissing { .. }
+            | ZjhttpcError::OcspStapleInvalid { .. } => Some(RequestPhase::Tls),
+            ZjhttpcError::SendHeaderTimeout { .. } => Some(RequestPhase::SendHeader),
+            ZjhttpcError::ReadHeaderTimeout { .. }
+            | ZjhttpcError::FirstByteTimeout { .. }
+            | ZjhttpcError::HeaderCompleteTimeout { .. }
+            | ZjhttpcError::InvalidResponse { .. }
+            | ZjhttpcError::InvalidStatusLine { .. }
+            | ZjhttpcError::ResponseTooLarge { .. }
+            | ZjhttpcError::UnexpectedEof { .. } => Some(RequestPhase::ReadHeader),
+            ZjhttpcError::ReadBodyTimeout { .. }
+            | ZjhttpcError::BodyAlreadyRead { .. }
+            | ZjhttpcError::BodyTruncated { .. }
+            | ZjhttpcError::ProtocolSwitched { .. }
+            | ZjhttpcError::InvalidByteRanges { .. }
+            | ZjhttpcError::UnexpectedContentType { .. }
+            | ZjhttpcError::JsonParsing { .. }
+            | ZjhttpcError::BodyTooLarge { .. }
+            | ZjhttpcError::Decompress { .. }
+            | ZjhttpcError::DecompressionBomb { .. }
+            | ZjhttpcError::ChecksumMismatch { .. }
+            | ZjhttpcError::MemoryBudgetExceeded { .. } => Some(RequestPhase::ReadBody),
+            ZjhttpcError::UploadCancelled { .. } => Some(RequestPhase::SendBody),
+            ZjhttpcError::Io { phase, .. } => *phase,
+            _ => None,
+        }
+    }
+
+    /// Whether this is an IO error saying the peer closed or reset the
+    /// connection.
+    pub(crate) fn is_connection_lost(&self) -> bool {
+        use std::io::ErrorKind::*;
+        matches!(self, ZjhttpcError::Io { source, .. } if matches!(source.kind(), BrokenPipe | ConnectionReset | ConnectionAborted))
+    }
+
+    /// Tags an untagged IO error with `phase`; other errors are returned unchanged.
+    pub(crate) fn with_phase(mut self, phase: RequestPhase) -> Self {
+        if let ZjhttpcError::Io { phase: p @ None, .. } = &mut self {
+            *p = Some(phase);
+        }
+        self
+    }
+
     /// Returns the source code location where this error was constructed, if available.
     pub fn location(&self) -> Option<&snafu::Location> {
         Some(match self {
@@ -183,37 +710,122 @@ impl ZjhttpcError {
             | ZjhttpcError::NoHost { location }
             | ZjhttpcError::NoPort { location }
             | ZjhttpcError::UnsupportedScheme { location, .. }
+            | ZjhttpcError::InvalidRequest { location, .. }
+            | ZjhttpcError::InvalidConfig { location, .. }
+            | ZjhttpcError::RequestLineTooLong { location, .. }
+            | ZjhttpcError::InvalidHeader { location, .. }
             | ZjhttpcError::Dns { location, .. }
+            | ZjhttpcError::NoPermittedAddress { location, .. }
             | ZjhttpcError::Connection { location, .. }
             | ZjhttpcError::ConnectionTimeout { location, .. }
             | ZjhttpcError::Tls { location, .. }
             | ZjhttpcError::Certificate { location, .. }
+            | ZjhttpcError::OcspStapleMissing { location, .. }
+            | ZjhttpcError::OcspStapleInvalid { location, .. }
             | ZjhttpcError::Proxy { location, .. }
+            | ZjhttpcError::ProxyRefused { location, .. }
             | ZjhttpcError::SendHeaderTimeout { location, .. }
             | ZjhttpcError::ReadHeaderTimeout { location, .. }
+            | ZjhttpcError::FirstByteTimeout { location, .. }
+            | ZjhttpcError::HeaderCompleteTimeout { location, .. }
             | ZjhttpcError::ReadBodyTimeout { location, .. }
+            | ZjhttpcError::TotalTimeout { location, .. }
             | ZjhttpcError::InvalidResponse { location, .. }
+            | ZjhttpcError::InvalidStatusLine { location, .. }
             | ZjhttpcError::ResponseTooLarge { location, .. }
             | ZjhttpcError::UnexpectedEof { location, .. }
+            | ZjhttpcError::InvalidByteRanges { location, .. }
             | ZjhttpcError::BodyAlreadyRead { location }
+            | ZjhttpcError::BodyTruncated { location, .. }
+            | ZjhttpcError::ProtocolSwitched { location }
+            | ZjhttpcError::NotUpgraded { location, .. }
+            | ZjhttpcError::UnexpectedContentType { location, .. }
             | ZjhttpcError::JsonParsing { location, .. }
+            | ZjhttpcError::BodyTooLarge { location, .. }
+            | ZjhttpcError::Decompress { location, .. }
+            | ZjhttpcError::DecompressionBomb { location, .. }
+            | ZjhttpcError::ChecksumMismatch { location, .. }
+            | ZjhttpcError::MemoryBudgetExceeded { location, .. }
+            | ZjhttpcError::TooManyRedirects { location, .. }
+            | ZjhttpcError::RedirectBodyNotReplayable { location, .. }
+            | ZjhttpcError::RedirectBlocked { location, .. }
             | ZjhttpcError::QuerySerialize { location, .. }
             | ZjhttpcError::MultipartContentLength { location, .. }
+            | ZjhttpcError::UploadCancelled { location, .. }
+            | ZjhttpcError::JsonSerialize { location, .. }
             | ZjhttpcError::Io { location, .. } => location,
         })
     }
 }
 
+/// IO errors that come from the connection or the peer rather than from the
+/// request itself.
+fn is_transient_io(kind: std::io::ErrorKind) -> bool {
+    use std::io::ErrorKind::*;
+    matches!(
+        kind,
+        ConnectionRefused
+            | ConnectionReset
+            | ConnectionAborted
+            | NotConnected
+            | BrokenPipe
+            | TimedOut
+            | Interrupted
+            | WouldBlock
+            | UnexpectedEof
+            | HostUnreachable
+            | NetworkUnreachable
+            | NetworkDown
+    )
+}
+
+fn format_issues(issues: &[ConfigIssue]) -> String {
+    issues.iter().map(ToString::to_string).collect::<Vec<_>>().join("; ")
+}
+
 #[track_caller]
 fn caller_location() -> snafu::Location {
     snafu::Location::default()
 }
 
+/// Payload of the `UnexpectedEof` io error a fixed-length body stream returns
+/// when the connection drops early; `From<io::Error>` turns it back into
+/// [`ZjhttpcError::BodyTruncated`].
+#[derive(Debug)]
+pub(crate) struct TruncatedBody {
+    pub expected: u64,
+    pub received: u64,
+}
+
+impl std::fmt::Display for TruncatedBody {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "response body truncated: received {} of {} bytes",
+            self.received, self.expected
+        )
+    }
+}
+
+impl std::error::Error for TruncatedBody {}
+
 impl From<std::io::Error> for ZjhttpcError {
     #[track_caller]
     fn from(e: std::io::Error) -> Self {
+        if let Some(t) = e.get_ref().and_then(|inner| inner.downcast_ref::<TruncatedBody>()) {
+            return ZjhttpcError::BodyTruncated {
+                expected: t.expected,
+                received: t.received,
+                location: caller_location(),
+            };
+        }
+        // A reader that fails with one of ours, e.g. the body pipeline's.
+        if let Some(inner) = e.get_ref().and_then(|inner| inner.downcast_ref::<ZjhttpcError>()) {
+            return inner.clone();
+        }
         ZjhttpcError::Io {
             source: Arc::new(e),
+            phase: None,
             location: caller_location(),
         }
     }
@@ -282,4 +894,165 @@ mod tests {
             other => panic!("expected Io, got {other:?}"),
         }
     }
+
+    #[test]
+    fn phase_is_derived_from_variant_or_io_tag() {
+        let dns = DnsSnafu { message: "x".to_string() }.build();
+        assert_eq!(dns.phase(), Some(RequestPhase::Resolve));
+        let io = ZjhttpcError::from(std::io::Error::other("boom"));
+        assert_eq!(io.phase(), None);
+        let io = io.with_phase(RequestPhase::SendBody);
+        assert_eq!(io.phase(), Some(RequestPhase::SendBody));
+        // an existing tag is kept
+        assert_eq!(io.with_phase(RequestPhase::ReadBody).phase(), Some(RequestPhase::SendBody));
+        assert_eq!(NoHostSnafu.build().phase(), None);
+    }
+
+    /// One error of every variant.
+    fn samples() -> Vec<ZjhttpcError> {
+        let d = Duration::from_secs(1);
+        let msg = || "x".to_string();
+        let addr: std::net::SocketAddr = ([127, 0, 0, 1], 80).into();
+        vec![
+            ZjhttpcError::from(url::ParseError::EmptyHost),
+            NoHostSnafu.build(),
+            NoPortSnafu.build(),
+            UnsupportedSchemeSnafu { scheme: "ftp" }.build(),
+            InvalidRequestSnafu { message: msg() }.build(),
+            InvalidConfigSnafu { issues: Vec::new() }.build(),
+            RequestLineTooLongSnafu { len: 9usize, limit: 8usize }.build(),
+            InvalidHeaderSnafu { message: msg() }.build(),
+            DnsSnafu { message: msg() }.build(),
+            NoPermittedAddressSnafu { host: "h", rejected: vec![addr] }.build(),
+            ConnectionSnafu { message: msg() }.build(),
+            ConnectionTimeoutSnafu { duration: d }.build(),
+            TlsSnafu { message: msg() }.build(),
+            CertificateSnafu { message: msg() }.build(),
+            OcspStapleMissingSnafu.build(),
+            OcspStapleInvalidSnafu { reason: msg() }.build(),
+            ProxySnafu { message: msg() }.build(),
+            ProxyRefusedSnafu { status: 407u16, target: "h:443", status_line: msg() }.build(),
+            SendHeaderTimeoutSnafu { duration: d }.build(),
+            ReadHeaderTimeoutSnafu { duration: d }.build(),
+            FirstByteTimeoutSnafu { duration: d }.build(),
+            HeaderCompleteTimeoutSnafu { duration: d }.build(),
+            ReadBodyTimeoutSnafu { duration: d }.build(),
+            TotalTimeoutSnafu { duration: d }.build(),
+            InvalidResponseSnafu { message: msg() }.build(),
+            InvalidStatusLineSnafu { status_line: "SSH-2.0", message: msg(), addr, reused: false }.build(),
+            ResponseTooLargeSnafu { actual: 9usize, max: 8usize }.build(),
+            UnexpectedEofSnafu { message: msg() }.build(),
+            InvalidByteRangesSnafu { message: msg() }.build(),
+            BodyAlreadyReadSnafu.build(),
+            BodyTruncatedSnafu { expected: 9u64, received: 8u64 }.build(),
+            ProtocolSwitchedSnafu.build(),
+            NotUpgradedSnafu { status: 200u16 }.build(),
+            UnexpectedContentTypeSnafu { expected: "json", actual: "html", preview: "" }.build(),
+            JsonParsingSnafu { message: msg(), preview: "" }.build(),
+            BodyTooLargeSnafu { length: Some(9u64), limit: 8u64 }.build(),
+            DecompressSnafu { encoding: "gzip", message: msg() }.build(),
+            DecompressionBombSnafu { wire_bytes: 1u64, decoded_bytes: 200u64 }.build(),
+            ChecksumMismatchSnafu { algorithm: "md5", expected: msg(), actual: msg() }.build(),
+            MemoryBudgetExceededSnafu { requested: 9u64, in_use: 8u64, limit: 16u64 }.build(),
+            TooManyRedirectsSnafu { limit: 5usize, url: "http://a/" }.build(),
+            RedirectBodyNotReplayableSnafu { status: 307u16, url: "http://a/" }.build(),
+            RedirectBlockedSnafu { url: "http://b/", reason: msg(), chain: vec![msg()] }.build(),
+            QuerySerializeSnafu { message: msg() }.build(),
+            MultipartContentLengthSnafu { message: msg() }.build(),
+            UploadCancelledSnafu { parts_sent: 1usize }.build(),
+            JsonSerializeSnafu { message: msg() }.build(),
+            ZjhttpcError::from(std::io::Error::from(std::io::ErrorKind::ConnectionReset)),
+        ]
+    }
+
+    /// The released codes. No wildcard arm: a new variant does not compile
+    /// until it is given a code here (and in `code`, `category`,
+    /// `is_transient` and `samples`).
+    fn released_code(err: &ZjhttpcError) -> &'static str {
+        match err {
+            ZjhttpcError::InvalidUrl { .. } => "ZJ-URL-001",
+            ZjhttpcError::NoHost { .. } => "ZJ-URL-002",
+            ZjhttpcError::NoPort { .. } => "ZJ-URL-003",
+            ZjhttpcError::UnsupportedScheme { .. } => "ZJ-URL-004",
+            ZjhttpcError::InvalidRequest { .. } => "ZJ-REQ-001",
+            ZjhttpcError::InvalidConfig { .. } => "ZJ-CONFIG-001",
+            ZjhttpcError::RequestLineTooLong { .. } => "ZJ-REQ-002",
+            ZjhttpcError::InvalidHeader { .. } => "ZJ-REQ-003",
+            ZjhttpcError::Dns { .. } => "ZJ-DNS-001",
+            ZjhttpcError::NoPermittedAddress { .. } => "ZJ-DNS-002",
+            ZjhttpcError::Connection { .. } => "ZJ-CONN-001",
+            ZjhttpcError::ConnectionTimeout { .. } => "ZJ-TIMEOUT-CONNECT",
+            ZjhttpcError::Tls { .. } => "ZJ-TLS-001",
+            ZjhttpcError::Certificate { .. } => "ZJ-TLS-002",
+            ZjhttpcError::OcspStapleMissing { .. } => "ZJ-TLS-003",
+            ZjhttpcError::OcspStapleInvalid { .. } => "ZJ-TLS-004",
+            ZjhttpcError::Proxy { .. } => "ZJ-PROXY-001",
+            ZjhttpcError::ProxyRefused { .. } => "ZJ-PROXY-002",
+            ZjhttpcError::SendHeaderTimeout { .. } => "ZJ-TIMEOUT-SEND-HEADERS",
+            ZjhttpcError::ReadHeaderTimeout { .. } => "ZJ-TIMEOUT-HEADERS",
+            ZjhttpcError::FirstByteTimeout { .. } => "ZJ-TIMEOUT-FIRST-BYTE",
+            ZjhttpcError::HeaderCompleteTimeout { .. } => "ZJ-TIMEOUT-HEADERS-COMPLETE",
+            ZjhttpcError::ReadBodyTimeout { .. } => "ZJ-TIMEOUT-BODY",
+            ZjhttpcError::TotalTimeout { .. } => "ZJ-TIMEOUT-TOTAL",
+            ZjhttpcError::InvalidResponse { .. } => "ZJ-PROTO-001",
+            ZjhttpcError::InvalidStatusLine { .. } => "ZJ-PROTO-002",
+            ZjhttpcError::ResponseTooLarge { .. } => "ZJ-PROTO-003",
+            ZjhttpcError::UnexpectedEof { .. } => "ZJ-CONN-002",
+            ZjhttpcError::InvalidByteRanges { .. } => "ZJ-PROTO-004",
+            ZjhttpcError::BodyAlreadyRead { .. } => "ZJ-BODY-001",
+            ZjhttpcError::BodyTruncated { .. } => "ZJ-CONN-003",
+            ZjhttpcError::ProtocolSwitched { .. } => "ZJ-BODY-002",
+            ZjhttpcError::NotUpgraded { .. } => "ZJ-BODY-003",
+            ZjhttpcError::UnexpectedContentType { .. } => "ZJ-BODY-004",
+            ZjhttpcError::JsonParsing { .. } => "ZJ-BODY-005",
+            ZjhttpcError::BodyTooLarge { .. } => "ZJ-BODY-006",
+            ZjhttpcError::Decompress { .. } => "ZJ-BODY-007",
+            ZjhttpcError::DecompressionBomb { .. } => "ZJ-BODY-010",
+            ZjhttpcError::ChecksumMismatch { .. } => "ZJ-BODY-008",
+            ZjhttpcError::MemoryBudgetExceeded { .. } => "ZJ-BODY-009",
+            ZjhttpcError::TooManyRedirects { .. } => "ZJ-REDIRECT-001",
+            ZjhttpcError::RedirectBodyNotReplayable { .. } => "ZJ-REDIRECT-002",
+            ZjhttpcError::RedirectBlocked { .. } => "ZJ-REDIRECT-003",
+            ZjhttpcError::QuerySerialize { .. } => "ZJ-REQ-004",
+            ZjhttpcError::MultipartContentLength { .. } => "ZJ-REQ-005",
+            ZjhttpcError::UploadCancelled { .. } => "ZJ-REQ-006",
+            ZjhttpcError::JsonSerialize { .. } => "ZJ-REQ-007",
+            ZjhttpcError::Io { .. } => "ZJ-IO-001",
+        }
+    }
+
+    #[test]
+    fn every_variant_has_a_stable_code_and_category() {
+        let samples = samples();
+        let variants: std::collections::HashSet<_> = samples.iter().map(std::mem::discriminant).collect();
+        assert_eq!(variants.len(), samples.len(), "one sample per variant");
+        let codes: std::collections::HashSet<_> = samples.iter().map(ZjhttpcError::code).collect();
+        assert_eq!(codes.len(), samples.len(), "codes are unique");
+        for err in &samples {
+            assert_eq!(err.code(), released_code(err), "released codes never change");
+            assert!(err.to_string().starts_with(&format!("[{}] ", err.code())), "{err}");
+            let _ = err.category();
+            let _ = err.is_transient();
+        }
+    }
+
+    #[test]
+    fn categories_and_transience() {
+        let timeout = ReadHeaderTimeoutSnafu { duration: Duration::from_secs(1) }.build();
+        assert_eq!((timeout.category(), timeout.is_transient()), (ErrorCategory::Timeout, true));
+        let cert = CertificateSnafu { message: "expired".to_string() }.build();
+        assert_eq!((cert.category(), cert.is_transient()), (ErrorCategory::Tls, false));
+        let reset = ZjhttpcError::from(std::io::Error::from(std::io::ErrorKind::ConnectionReset));
+        assert_eq!((reset.category(), reset.is_transient()), (ErrorCategory::Network, true));
+        let denied = ZjhttpcError::from(std::io::Error::from(std::io::ErrorKind::PermissionDenied));
+        assert!(!denied.is_transient());
+
+        let addr: std::net::SocketAddr = ([127, 0, 0, 1], 80).into();
+        let garbage = |reused| {
+            InvalidStatusLineSnafu { status_line: "SSH-2.0", message: "x", addr, reused }.build()
+        };
+        assert_eq!(garbage(false).category(), ErrorCategory::Protocol);
+        assert!(!garbage(false).is_transient());
+        assert!(garbage(true).is_transient(), "a pooled connection may have been reused by the server");
+    }
 }
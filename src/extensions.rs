@@ -0,0 +1,84 @@
+//! Typed, caller-provided data attached to a request.
+
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// A map holding at most one value per type, for context such as a tenant id
+/// or an operation name that travels with a request without becoming a
+/// header. The response of a request carries a copy.
+///
+/// Values are stored behind an `Arc`: cloning the map shares them, and they
+/// are read-only once inserted.
+#[derive(Clone, Default)]
+pub struct Extensions {
+    map: HashMap<TypeId, Arc<dyn Any + Send + Sync>>,
+}
+
+impl Extensions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Store `value`, returning the previous value of the same type.
+    pub fn insert<T: Send + Sync + 'static>(&mut self, value: T) -> Option<Arc<T>> {
+        self.map
+            .insert(TypeId::of::<T>(), Arc::new(value))
+            .and_then(|old| old.downcast().ok())
+    }
+
+    pub fn get<T: Send + Sync + 'static>(&self) -> Option<&T> {
+        self.map.get(&TypeId::of::<T>()).and_then(|v| v.downcast_ref())
+    }
+
+    pub fn remove<T: Send + Sync + 'static>(&mut self) -> Option<Arc<T>> {
+        self.map.remove(&TypeId::of::<T>()).and_then(|old| old.downcast().ok())
+    }
+
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+}
+
+impl std::fmt::Debug for Extensions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Extensions").field("len", &self.map.len()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq)]
+    struct Tenant(&'static str);
+
+    #[test]
+    fn test_insert_get_remove_by_type() {
+        let mut ext = Extensions::new();
+        assert!(ext.insert(Tenant("a")).is_none());
+        ext.insert(7u32);
+        assert_eq!(ext.get::<Tenant>(), Some(&Tenant("a")));
+        assert_eq!(ext.get::<u32>(), Some(&7));
+        assert_eq!(ext.get::<u64>(), None);
+
+        assert_eq!(ext.insert(Tenant("b")).as_deref(), Some(&Tenant("a")));
+        assert_eq!(ext.len(), 2);
+        assert_eq!(ext.remove::<Tenant>().as_deref(), Some(&Tenant("b")));
+        assert_eq!(ext.get::<Tenant>(), None);
+    }
+
+    #[test]
+    fn test_clone_shares_values() {
+        let mut ext = Extensions::new();
+        ext.insert(Tenant("a"));
+        let copy = ext.clone();
+        assert!(std::ptr::eq(ext.get::<Tenant>().unwrap(), copy.get::<Tenant>().unwrap()));
+        ext.insert(Tenant("b"));
+        assert_eq!(copy.get::<Tenant>(), Some(&Tenant("a")));
+    }
+}
@@ -0,0 +1,147 @@
+//! Request headers an intermediary adds or removes when it forwards a
+//! request: `Forwarded` (RFC 7239), the legacy `X-Forwarded-*` fields and
+//! `Via`, and the hop-by-hop fields (RFC 7230 §6.1). Used by
+//! [`Request::as_forwarded`](crate::requestx::Request::as_forwarded) and
+//! [`Request::strip_hop_by_hop`](crate::requestx::Request::strip_hop_by_hop).
+
+use std::borrow::Cow;
+use std::net::IpAddr;
+
+use crate::header::{CONNECTION, HeaderMap, is_tchar};
+
+pub(crate) const X_FORWARDED_FOR: &str = "X-Forwarded-For";
+pub(crate) const X_FORWARDED_PROTO: &str = "X-Forwarded-Proto";
+pub(crate) const X_FORWARDED_HOST: &str = "X-Forwarded-Host";
+
+/// The `Via` entry this client adds: protocol version and pseudonym.
+pub(crate) const VIA_ENTRY: &str = "1.1 zjhttpc";
+
+/// Fields that only mean something on one connection, removed before a
+/// request is forwarded in addition to the ones `Connection` names.
+const HOP_BY_HOP: [&str; 7] =
+    ["connection", "keep-alive", "proxy-connection", "te", "trailer", "transfer-encoding", "upgrade"];
+
+/// One `Forwarded` element: `for=<client>;proto=<proto>;host=<host>`.
+pub(crate) fn element(client: IpAddr, proto: &str, host: &str) -> String {
+    format!("for={};proto={};host={}", node(client), value(proto), value(host))
+}
+
+/// A node identifier for an address. IPv6 is bracketed and, since `:` and
+/// `[` are not token characters, quoted (RFC 7239 §6).
+fn node(addr: IpAddr) -> String {
+    match addr {
+        IpAddr::V4(v4) => v4.to_string(),
+        IpAddr::V6(v6) => format!("\"[{v6}]\""),
+    }
+}
+
+/// `s` as a token, or as a quoted-string when it is not one.
+fn value(s: &str) -> Cow<'_, str> {
+    if !s.is_empty() && s.bytes().all(is_tchar) {
+        return Cow::Borrowed(s);
+    }
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('"');
+    for c in s.chars() {
+        if c == '"' || c == '\\' {
+            quoted.push('\\');
+        }
+        quoted.push(c);
+    }
+    quoted.push('"');
+    Cow::Owned(quoted)
+}
+
+/// Add `entry` to the end of the list header `name`. Whatever is there is
+/// kept, several lines included, and joined into one comma-separated value
+/// with `entry` last.
+pub(crate) fn append(headers: &mut HeaderMap, name: &str, entry: &str) {
+    let joined: Vec<&str> = headers
+        .get_all(name)
+        .into_iter()
+        .flatten()
+        .map(|v| v.trim())
+        .filter(|v| !v.is_empty())
+        .chain([entry])
+        .collect();
+    headers.insert_unchecked(name, joined.join(", "));
+}
+
+/// Remove every field `Connection` names, then the [`HOP_BY_HOP`] ones.
+/// `Host` stays even when named: the request cannot go without it.
+pub(crate) fn strip_hop_by_hop(headers: &mut HeaderMap) {
+    let named: Vec<String> = headers
+        .get_all(CONNECTION)
+        .into_iter()
+        .flatten()
+        .flat_map(|v| v.split(','))
+        .map(|name| name.trim().to_owned())
+        .filter(|name| !name.is_empty() && !name.eq_ignore_ascii_case("host"))
+        .collect();
+    for name in named.iter().map(String::as_str).chain(HOP_BY_HOP) {
+        headers.remove(name);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_element() {
+        let cases = [
+            ("192.0.2.43", "http", "example.com", "for=192.0.2.43;proto=http;host=example.com"),
+            ("2001:db8:cafe::17", "https", "example.com", "for=\"[2001:db8:cafe::17]\";proto=https;host=example.com"),
+            ("::ffff:192.0.2.1", "https", "a.test", "for=\"[::ffff:192.0.2.1]\";proto=https;host=a.test"),
+            ("192.0.2.43", "http", "example.com:8080", "for=192.0.2.43;proto=http;host=\"example.com:8080\""),
+            ("192.0.2.43", "http", "[2001:db8::1]:443", "for=192.0.2.43;proto=http;host=\"[2001:db8::1]:443\""),
+            ("192.0.2.43", "http", "a\"b\\c", "for=192.0.2.43;proto=http;host=\"a\\\"b\\\\c\""),
+            ("192.0.2.43", "", "", "for=192.0.2.43;proto=\"\";host=\"\""),
+        ];
+        for (client, proto, host, expected) in cases {
+            assert_eq!(element(client.parse().unwrap(), proto, host), expected, "{client} {proto} {host}");
+        }
+    }
+
+    #[test]
+    fn test_append_joins_existing_lines() {
+        let mut headers = HeaderMap::new();
+        append(&mut headers, "Via", VIA_ENTRY);
+        assert_eq!(headers.get("via"), Some("1.1 zjhttpc"));
+        append(&mut headers, "Via", VIA_ENTRY);
+        assert_eq!(headers.get_all("via").unwrap().len(), 1);
+        assert_eq!(headers.get("via"), Some("1.1 zjhttpc, 1.1 zjhttpc"));
+
+        let mut headers = HeaderMap::new();
+        headers.append_unchecked("Forwarded", "for=_hidden;by=_proxy1");
+        headers.append_unchecked("forwarded", " for=unknown ");
+        headers.append_unchecked("forwarded", "");
+        append(&mut headers, "Forwarded", "for=192.0.2.1");
+        let (name, values) = headers.iter().next().unwrap();
+        assert_eq!(name, "Forwarded");
+        assert_eq!(values.iter().collect::<Vec<_>>(), ["for=_hidden;by=_proxy1, for=unknown, for=192.0.2.1"]);
+    }
+
+    #[test]
+    fn test_strip_hop_by_hop() {
+        let mut headers: HeaderMap = [
+            ("Host", "example.com"),
+            ("Connection", "close, X-Hop"),
+            ("connection", "x-other , , Host"),
+            ("X-Hop", "1"),
+            ("X-Other", "2"),
+            ("Keep-Alive", "timeout=5"),
+            ("TE", "trailers"),
+            ("Trailer", "Expires"),
+            ("Upgrade", "websocket"),
+            ("Proxy-Connection", "keep-alive"),
+            ("Transfer-Encoding", "chunked"),
+            ("X-End-To-End", "3"),
+        ]
+        .into_iter()
+        .collect();
+        strip_hop_by_hop(&mut headers);
+        let names: Vec<_> = headers.iter().map(|(name, _)| name).collect();
+        assert_eq!(names, ["Host", "X-End-To-End"]);
+    }
+}
@@ -1,3 +1,9 @@
+use std::borrow::Cow;
+
+use indexmap::{IndexMap, IndexSet};
+
+use crate::error::{InvalidHeaderSnafu, Result};
+
 // Common HTTP header name constants
 // These provide type-safe, documented header names for common HTTP headers
 // Reference: https://www.iana.org/assignments/message-headers/message-headers.xhtml
@@ -56,6 +62,10 @@ pub const EXPECT: &str = "Expect";
 /// Example: `From: user@example.com`
 pub const FROM: &str = "From";
 
+/// Forwarded header - identifies the client and the proxies a request went through (RFC 7239)
+/// Example: `Forwarded: for=192.0.2.60;proto=http;host=example.com`
+pub const FORWARDED: &str = "Forwarded";
+
 /// Host header - specifies the domain name and port number of the server
 /// Example: `Host: www.example.com:8080`
 pub const HOST: &str = "Host";
@@ -162,6 +172,10 @@ pub const CONTENT_LANGUAGE: &str = "Content-Language";
 /// Example: `Content-Location: /index.html`
 pub const CONTENT_LOCATION: &str = "Content-Location";
 
+/// Content-MD5 header - base64 MD5 digest of the body as sent, content codings included (RFC 1864)
+/// Example: `Content-MD5: Q2hlY2sgSW50ZWdyaXR5IQ==`
+pub const CONTENT_MD5: &str = "Content-MD5";
+
 /// Content-Range header - indicates where in a full body message a partial message belongs
 /// Example: `Content-Range: bytes 0-1023/2048`
 pub const CONTENT_RANGE: &str = "Content-Range";
@@ -210,4 +224,621 @@ pub const VARY: &str = "Vary";
 /// Example: `WWW-Authenticate: Bearer realm="example"`
 pub const WWW_AUTHENTICATE: &str = "WWW-Authenticate";
 
-// TODO: implement general Headers struct
\ No newline at end of file
+/// Header names common enough to be worth storing without allocating: the
+/// constants above plus frequent response headers, each with its lowercased
+/// form. A [`HeaderMap`] keys these by the static lowercased name, and keeps
+/// the spelling for free when it is one of the two.
+const WELL_KNOWN: [(&str, &str); 67] = [
+    (ACCEPT, "accept"),
+    (ACCEPT_CHARSET, "accept-charset"),
+    (ACCEPT_ENCODING, "accept-encoding"),
+    (ACCEPT_LANGUAGE, "accept-language"),
+    (AUTHORIZATION, "authorization"),
+    (CACHE_CONTROL, "cache-control"),
+    (CONNECTION, "connection"),
+    (CONTENT_LENGTH, "content-length"),
+    (CONTENT_TYPE, "content-type"),
+    (COOKIE, "cookie"),
+    (DATE, "date"),
+    (EXPECT, "expect"),
+    (FROM, "from"),
+    (FORWARDED, "forwarded"),
+    (HOST, "host"),
+    (IF_MATCH, "if-match"),
+    (IF_MODIFIED_SINCE, "if-modified-since"),
+    (IF_NONE_MATCH, "if-none-match"),
+    (IF_RANGE, "if-range"),
+    (IF_UNMODIFIED_SINCE, "if-unmodified-since"),
+    (MAX_FORWARDS, "max-forwards"),
+    (ORIGIN, "origin"),
+    (PRAGMA, "pragma"),
+    (PROXY_AUTHORIZATION, "proxy-authorization"),
+    (RANGE, "range"),
+    (REFERER, "referer"),
+    (TE, "te"),
+    (TRAILER, "trailer"),
+    (TRANSFER_ENCODING, "transfer-encoding"),
+    (UPGRADE, "upgrade"),
+    (USER_AGENT, "user-agent"),
+    (VIA, "via"),
+    (WARNING, "warning"),
+    (ACCEPT_RANGES, "accept-ranges"),
+    (AGE, "age"),
+    (ALLOW, "allow"),
+    (CONTENT_DISPOSITION, "content-disposition"),
+    (CONTENT_ENCODING, "content-encoding"),
+    (CONTENT_LANGUAGE, "content-language"),
+    (CONTENT_LOCATION, "content-location"),
+    (CONTENT_MD5, "content-md5"),
+    (CONTENT_RANGE, "content-range"),
+    (ETAG, "etag"),
+    (EXPIRES, "expires"),
+    (LAST_MODIFIED, "last-modified"),
+    (LOCATION, "location"),
+    (PROXY_AUTHENTICATE, "proxy-authenticate"),
+    (REFRESH, "refresh"),
+    (RETRY_AFTER, "retry-after"),
+    (SERVER, "server"),
+    (SET_COOKIE, "set-cookie"),
+    (VARY, "vary"),
+    (WWW_AUTHENTICATE, "www-authenticate"),
+    ("Keep-Alive", "keep-alive"),
+    ("Link", "link"),
+    ("Strict-Transport-Security", "strict-transport-security"),
+    ("Access-Control-Allow-Origin", "access-control-allow-origin"),
+    ("Access-Control-Allow-Credentials", "access-control-allow-credentials"),
+    ("Content-Security-Policy", "content-security-policy"),
+    ("Referrer-Policy", "referrer-policy"),
+    ("X-Content-Type-Options", "x-content-type-options"),
+    ("X-Frame-Options", "x-frame-options"),
+    ("X-XSS-Protection", "x-xss-protection"),
+    ("Alt-Svc", "alt-svc"),
+    ("X-Request-Id", "x-request-id"),
+    ("X-Forwarded-For", "x-forwarded-for"),
+    ("X-Cache", "x-cache"),
+];
+
+fn well_known(name: &str) -> Option<&'static (&'static str, &'static str)> {
+    WELL_KNOWN.iter().find(|(canonical, _)| canonical.len() == name.len() && canonical.eq_ignore_ascii_case(name))
+}
+
+/// The map key of `name`, only allocated for an unknown name with capitals.
+fn lookup_key(name: &str) -> Cow<'_, str> {
+    match well_known(name) {
+        Some(&(_, lower)) => Cow::Borrowed(lower),
+        None if name.bytes().any(|b| b.is_ascii_uppercase()) => Cow::Owned(name.to_ascii_lowercase()),
+        None => Cow::Borrowed(name),
+    }
+}
+
+/// The stored key and spelling for a new `name`, whose `lookup_key` is `key`.
+fn interned(name: &str, key: Cow<'_, str>) -> (Cow<'static, str>, Cow<'static, str>) {
+    match well_known(name) {
+        Some(&(canonical, lower)) => {
+            let spelling = match name {
+                _ if name == canonical => Cow::Borrowed(canonical),
+                _ if name == lower => Cow::Borrowed(lower),
+                _ => Cow::Owned(name.to_owned()),
+            };
+            (Cow::Borrowed(lower), spelling)
+        }
+        None => (Cow::Owned(key.into_owned()), Cow::Owned(name.to_owned())),
+    }
+}
+
+// ========== Header Map ==========
+
+/// Header storage shared by [`Request`](crate::requestx::Request) and
+/// [`Response`](crate::response::Response).
+///
+/// - Names are matched case-insensitively; the spelling of the first insertion
+///   is kept and is what goes on the wire.
+/// - Names keep their insertion order, and so do the values of each name.
+/// - A name can hold several values (repeating a value is a no-op).
+/// - [`insert`](Self::insert) and [`append`](Self::append) reject names that
+///   are not RFC 7230 tokens and values containing control characters, so
+///   user input can't smuggle extra header lines.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HeaderMap {
+    // keyed by the lowercased name; well-known names borrow theirs from
+    // `WELL_KNOWN`
+    entries: IndexMap<Cow<'static, str>, HeaderEntry>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct HeaderEntry {
+    name: Cow<'static, str>,
+    values: IndexSet<String>,
+}
+
+impl HeaderMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of distinct header names.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn contains_key(&self, name: impl AsRef<str>) -> bool {
+        self.entries.contains_key(lookup_key(name.as_ref()).as_ref())
+    }
+
+    /// First value of `name`.
+    pub fn get(&self, name: impl AsRef<str>) -> Option<&str> {
+        self.get_all(name)
+            .and_then(|values| values.first())
+            .map(String::as_str)
+    }
+
+    /// All values of `name`, in insertion order.
+    pub fn get_all(&self, name: impl AsRef<str>) -> Option<&IndexSet<String>> {
+        self.entries
+            .get(lookup_key(name.as_ref()).as_ref())
+            .map(|entry| &entry.values)
+    }
+
+    /// Replace every value of `name` with `value`.
+    pub fn insert(&mut self, name: impl AsRef<str>, value: impl AsRef<str>) -> Result<()> {
+        validate_header(name.as_ref(), value.as_ref())?;
+        self.insert_unchecked(name, value);
+        Ok(())
+    }
+
+    /// Add `value` to the values of `name`.
+    pub fn append(&mut self, name: impl AsRef<str>, value: impl AsRef<str>) -> Result<()> {
+        validate_header(name.as_ref(), value.as_ref())?;
+        self.append_unchecked(name, value);
+        Ok(())
+    }
+
+    /// Remove `name`, returning its values.
+    pub fn remove(&mut self, name: impl AsRef<str>) -> Option<IndexSet<String>> {
+        self.entries
+            .shift_remove(lookup_key(name.as_ref()).as_ref())
+            .map(|entry| entry.values)
+    }
+
+    /// Iterate over `(name, values)` in insertion order.
+    pub fn iter(&self) -> Iter<'_> {
+        Iter(self.entries.values())
+    }
+
+    /// Check every name and value, as [`insert`](Self::insert) does. Useful for
+    /// maps built through the `From` conversions or the unchecked setters.
+    pub fn validate(&self) -> Result<()> {
+        for entry in self.entries.values() {
+            if entry.name.eq_ignore_ascii_case(AUTHORIZATION) && entry.values.len() > 1 {
+                return Err(InvalidHeaderSnafu {
+                    message: format!("{AUTHORIZATION} must have a single value, got {}", entry.values.len()),
+                }
+                .build());
+            }
+            for value in &entry.values {
+                validate_header(&entry.name, value)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Like [`insert`](Self::insert) without validation. Callers must validate
+    /// before the map reaches the wire.
+    pub(crate) fn insert_unchecked(&mut self, name: impl AsRef<str>, value: impl AsRef<str>) {
+        let name = name.as_ref();
+        let values = IndexSet::from([value.as_ref().to_owned()]);
+        let key = lookup_key(name);
+        match self.entries.get_mut(key.as_ref()) {
+            Some(entry) => entry.values = values,
+            None => {
+                let (key, name) = interned(name, key);
+                self.entries.insert(key, HeaderEntry { name, values });
+            }
+        }
+    }
+
+    /// Like [`append`](Self::append) without validation.
+    pub(crate) fn append_unchecked(&mut self, name: impl AsRef<str>, value: impl AsRef<str>) {
+        let name = name.as_ref();
+        let value = value.as_ref().to_owned();
+        let key = lookup_key(name);
+        match self.entries.get_mut(key.as_ref()) {
+            Some(entry) => {
+                entry.values.insert(value);
+            }
+            None => {
+                let (key, name) = interned(name, key);
+                self.entries.insert(key, HeaderEntry { name, values: IndexSet::from([value]) });
+            }
+        }
+    }
+}
+
+/// Iterator over the `(name, values)` pairs of a [`HeaderMap`].
+pub struct Iter<'a>(indexmap::map::Values<'a, Cow<'static, str>, HeaderEntry>);
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = (&'a str, &'a IndexSet<String>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next().map(|entry| (entry.name.as_ref(), &entry.values))
+    }
+}
+
+impl<'a> IntoIterator for &'a HeaderMap {
+    type Item = (&'a str, &'a IndexSet<String>);
+    type IntoIter = Iter<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<K: AsRef<str>, V: AsRef<str>> Extend<(K, V)> for HeaderMap {
+    /// Appends without validation; see [`HeaderMap::validate`].
+    fn extend<T: IntoIterator<Item = (K, V)>>(&mut self, iter: T) {
+        for (name, value) in iter {
+            self.append_unchecked(name, value);
+        }
+    }
+}
+
+impl<K: AsRef<str>, V: AsRef<str>> FromIterator<(K, V)> for HeaderMap {
+    fn from_iter<T: IntoIterator<Item = (K, V)>>(iter: T) -> Self {
+        let mut map = HeaderMap::new();
+        map.extend(iter);
+        map
+    }
+}
+
+impl From<hashbrown::HashMap<String, IndexSet<String>>> for HeaderMap {
+    fn from(map: hashbrown::HashMap<String, IndexSet<String>>) -> Self {
+        map.into_iter()
+            .flat_map(|(k, values)| values.into_iter().map(move |v| (k.clone(), v)))
+            .collect()
+    }
+}
+
+impl From<hashbrown::HashMap<String, String>> for HeaderMap {
+    fn from(map: hashbrown::HashMap<String, String>) -> Self {
+        map.into_iter().collect()
+    }
+}
+
+impl From<std::collections::HashMap<String, IndexSet<String>>> for HeaderMap {
+    fn from(map: std::collections::HashMap<String, IndexSet<String>>) -> Self {
+        map.into_iter()
+            .flat_map(|(k, values)| values.into_iter().map(move |v| (k.clone(), v)))
+            .collect()
+    }
+}
+
+impl From<std::collections::HashMap<String, String>> for HeaderMap {
+    fn from(map: std::collections::HashMap<String, String>) -> Self {
+        map.into_iter().collect()
+    }
+}
+
+// ========== Vary ==========
+
+/// Request headers whose list elements carry their preference in q-values,
+/// so neither their order nor the case of their tokens matters.
+const NEGOTIATION_HEADERS: [&str; 4] = [ACCEPT, ACCEPT_CHARSET, ACCEPT_ENCODING, ACCEPT_LANGUAGE];
+
+/// Whether requests with headers `a` and `b` select the same stored response,
+/// whose `Vary` field list is `vary` (RFC 7234 §4.1).
+///
+/// `vary` takes header names or whole `Vary` values (`"Accept-Encoding,
+/// Accept-Language"`), e.g. straight from `headers.get_all(VARY)`. For every
+/// named header:
+/// - absent on both sides matches; absent on one side never does, not even
+///   against an empty value
+/// - repeated fields are combined into one comma-separated list
+/// - list elements are compared after trimming whitespace, in order, except
+///   for `Accept`, `Accept-Charset`, `Accept-Encoding` and `Accept-Language`,
+///   whose elements are compared as a case-insensitive set
+///   (`gzip, br` matches `br,gzip`, and `gzip;q=0.5` matches `GZIP; q=0.5`)
+///
+/// A `*` in `vary` matches nothing.
+pub fn equivalent<'v>(a: &HeaderMap, b: &HeaderMap, vary: impl IntoIterator<Item = &'v str>) -> bool {
+    let Some(names) = vary_names(vary) else {
+        return false;
+    };
+    names.iter().all(|name| normalized_field(a, name) == normalized_field(b, name))
+}
+
+/// A hash of the headers named in `vary`, normalized as [`equivalent`] does:
+/// equivalent requests have equal fingerprints. The hash (64-bit FNV-1a) is
+/// stable across processes and releases, so it can be persisted. With `*` in
+/// `vary` every request gets the same fingerprint, and [`equivalent`] decides.
+pub fn selective_fingerprint<'v>(headers: &HeaderMap, vary: impl IntoIterator<Item = &'v str>) -> u64 {
+    let mut hash = Fnv1a::default();
+    let Some(names) = vary_names(vary) else {
+        hash.write(b"*");
+        return hash.0;
+    };
+    for name in &names {
+        hash.write(name.as_bytes());
+        match normalized_field(headers, name) {
+            None => hash.write(&[0xFE, 0]),
+            Some(elements) => {
+                hash.write(&[0xFE, 1]);
+                for element in elements {
+                    hash.write(element.as_bytes());
+                    hash.write(&[0xFF]);
+                }
+            }
+        }
+    }
+    hash.0
+}
+
+/// The lower-cased, sorted field names of a `Vary` list; `None` for `*`.
+fn vary_names<'v>(vary: impl IntoIterator<Item = &'v str>) -> Option<Vec<String>> {
+    let mut names = Vec::new();
+    for name in vary.into_iter().flat_map(|value| value.split(',')).map(str::trim) {
+        match name {
+            "" => {}
+            "*" => return None,
+            name => names.push(name.to_ascii_lowercase()),
+        }
+    }
+    names.sort();
+    names.dedup();
+    Some(names)
+}
+
+/// The list elements of `name` in `headers`, `None` when it is absent.
+fn normalized_field(headers: &HeaderMap, name: &str) -> Option<Vec<String>> {
+    let values = headers.get_all(name)?;
+    let elements = values.iter().flat_map(|value| value.split(',')).map(str::trim).filter(|e| !e.is_empty());
+    if !NEGOTIATION_HEADERS.iter().any(|h| h.eq_ignore_ascii_case(name)) {
+        return Some(elements.map(str::to_owned).collect());
+    }
+    let mut elements: Vec<String> = elements.map(normalized_preference).collect();
+    elements.sort();
+    elements.dedup();
+    Some(elements)
+}
+
+/// `Gzip ; Q = 0.5` -> `gzip;q=0.5`
+fn normalized_preference(element: &str) -> String {
+    element
+        .split(';')
+        .map(|param| match param.split_once('=') {
+            Some((key, value)) => format!("{}={}", key.trim(), value.trim()),
+            None => param.trim().to_owned(),
+        })
+        .collect::<Vec<_>>()
+        .join(";")
+        .to_ascii_lowercase()
+}
+
+struct Fnv1a(u64);
+
+impl Default for Fnv1a {
+    fn default() -> Self {
+        Fnv1a(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Fnv1a {
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 = (self.0 ^ u64::from(*byte)).wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    }
+}
+
+/// RFC 7230 `token`: the only characters allowed in a header name.
+pub(crate) fn is_tchar(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b)
+}
+
+pub(crate) fn validate_header(name: &str, value: &str) -> Result<()> {
+    if name.is_empty() || !name.bytes().all(is_tchar) {
+        return Err(InvalidHeaderSnafu {
+            message: format!("invalid header name {name:?}"),
+        }
+        .build());
+    }
+    if value.bytes().any(|b| b.is_ascii_control() && b != b'\t') {
+        return Err(InvalidHeaderSnafu {
+            message: format!("invalid value for header {name}: {value:?}"),
+        }
+        .build());
+    }
+    if name.eq_ignore_ascii_case(AUTHORIZATION) {
+        let scheme = value.split(' ').next().unwrap_or_default();
+        if scheme.is_empty() || !scheme.bytes().all(is_tchar) {
+            return Err(InvalidHeaderSnafu {
+                message: format!("invalid {AUTHORIZATION} scheme {scheme:?}"),
+            }
+            .build());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lookup_is_case_insensitive() {
+        let mut map = HeaderMap::new();
+        map.insert("Content-Type", "text/plain").unwrap();
+        assert_eq!(map.get("content-type"), Some("text/plain"));
+        assert_eq!(map.get(CONTENT_TYPE), Some("text/plain"));
+        assert!(map.contains_key("CONTENT-TYPE"));
+        assert_eq!(map.get("accept"), None);
+    }
+
+    #[test]
+    fn test_insert_replaces_and_keeps_first_spelling() {
+        let mut map = HeaderMap::new();
+        map.insert("X-Trace", "a").unwrap();
+        map.insert("x-trace", "b").unwrap();
+        assert_eq!(map.len(), 1);
+        let (name, values) = map.iter().next().unwrap();
+        assert_eq!(name, "X-Trace");
+        assert_eq!(values.iter().collect::<Vec<_>>(), ["b"]);
+    }
+
+    #[test]
+    fn test_append_keeps_value_order() {
+        let mut map = HeaderMap::new();
+        map.append("Cookie", "a=1").unwrap();
+        map.append("cookie", "b=2").unwrap();
+        map.append("COOKIE", "a=1").unwrap();
+        let values = map.get_all("cookie").unwrap();
+        assert_eq!(values.iter().collect::<Vec<_>>(), ["a=1", "b=2"]);
+        assert_eq!(map.get("cookie"), Some("a=1"));
+    }
+
+    #[test]
+    fn test_names_keep_insertion_order() {
+        let mut map = HeaderMap::new();
+        for name in ["Host", "User-Agent", "Accept", "X-A"] {
+            map.insert(name, "v").unwrap();
+        }
+        map.remove("user-agent");
+        map.insert("User-Agent", "v").unwrap();
+        let names: Vec<_> = map.iter().map(|(n, _)| n).collect();
+        assert_eq!(names, ["Host", "Accept", "X-A", "User-Agent"]);
+    }
+
+    #[test]
+    fn test_well_known_names_are_not_allocated() {
+        let headers: HeaderMap =
+            [("Content-Type", "a"), ("content-length", "1"), ("ETAG", "b"), ("X-Custom", "c")].into_iter().collect();
+        let stored: Vec<_> = headers
+            .entries
+            .iter()
+            .map(|(key, entry)| (matches!(key, Cow::Borrowed(_)), matches!(entry.name, Cow::Borrowed(_))))
+            .collect();
+        assert_eq!(stored, [(true, true), (true, true), (true, false), (false, false)]);
+        let names: Vec<_> = headers.iter().map(|(name, _)| name).collect();
+        assert_eq!(names, ["Content-Type", "content-length", "ETAG", "X-Custom"]);
+        assert_eq!(headers.get("etag"), Some("b"));
+        assert_eq!(headers.get("x-CUSTOM"), Some("c"));
+    }
+
+    #[test]
+    fn test_well_known_table_is_lowercased() {
+        for (canonical, lower) in WELL_KNOWN {
+            assert_eq!(canonical.to_ascii_lowercase(), lower);
+        }
+    }
+
+    #[test]
+    fn test_remove() {
+        let mut map = HeaderMap::new();
+        map.append("A", "1").unwrap();
+        map.append("a", "2").unwrap();
+        let removed = map.remove("A").unwrap();
+        assert_eq!(removed.len(), 2);
+        assert!(map.is_empty());
+        assert!(map.remove("A").is_none());
+    }
+
+    #[test]
+    fn test_rejects_invalid_names() {
+        let mut map = HeaderMap::new();
+        for name in ["", "Bad Name", "X-A\r\nX-B", "X:Y", "caf\u{e9}"] {
+            let err = map.insert(name, "v").unwrap_err();
+            assert!(matches!(err, crate::ZjhttpcError::InvalidHeader { .. }), "{name:?}");
+        }
+        assert!(map.is_empty());
+    }
+
+    #[test]
+    fn test_rejects_control_characters_in_values() {
+        let mut map = HeaderMap::new();
+        for value in ["ok\r\nX-Admin: true", "a\nb", "a\0b", "a\x7fb"] {
+            assert!(map.append("X-Test", value).is_err(), "{value:?}");
+        }
+        map.insert("X-Test", "tab\tand spaces, \u{e9}").unwrap();
+        map.insert("X-Empty", "").unwrap();
+    }
+
+    #[test]
+    fn test_from_maps_and_validate() {
+        let mut std_map = std::collections::HashMap::new();
+        std_map.insert("Accept".to_string(), "*/*".to_string());
+        let map = HeaderMap::from(std_map);
+        assert_eq!(map.get("accept"), Some("*/*"));
+        assert!(map.validate().is_ok());
+
+        let mut hb_map = hashbrown::HashMap::new();
+        hb_map.insert("X-Multi".to_string(), IndexSet::from(["1".to_string(), "2".to_string()]));
+        let map = HeaderMap::from(hb_map);
+        assert_eq!(map.get_all("x-multi").unwrap().len(), 2);
+
+        let map: HeaderMap = [("X-Bad", "a\r\nb")].into_iter().collect();
+        assert!(map.validate().is_err());
+    }
+
+    #[test]
+    fn test_authorization_is_single_valued_with_token_scheme() {
+        let mut map = HeaderMap::new();
+        map.insert("Authorization", "Bearer abc.def").unwrap();
+        map.validate().unwrap();
+        map.append_unchecked("authorization", "Basic eA==");
+        assert!(map.validate().is_err());
+
+        assert!(map.insert("Authorization", "").is_err());
+        assert!(map.insert("Authorization", "Bad/Scheme x").is_err());
+        map.insert("Authorization", "Negotiate").unwrap();
+        assert_eq!(map.get_all("authorization").unwrap().len(), 1);
+    }
+
+    fn headers(fields: &[(&str, &str)]) -> HeaderMap {
+        fields.iter().copied().collect()
+    }
+
+    #[test]
+    fn test_equivalent_under_vary() {
+        let gzip_br = headers(&[("Accept-Encoding", "gzip, br")]);
+        let cases: &[(&str, HeaderMap, HeaderMap, &str, bool)] = &[
+            ("list order of a negotiation header", gzip_br.clone(), headers(&[("accept-encoding", "br,gzip")]), "Accept-Encoding", true),
+            ("repeated fields combine", gzip_br.clone(), headers(&[("Accept-Encoding", "gzip"), ("Accept-Encoding", "br")]), "accept-encoding", true),
+            ("token case and parameter spacing", headers(&[("Accept-Language", "en;q=0.5, FR")]), headers(&[("Accept-Language", "fr,EN ; q = 0.5")]), "Accept-Language", true),
+            ("different parameters", headers(&[("Accept", "text/html;q=0.5")]), headers(&[("Accept", "text/html")]), "Accept", false),
+            ("missing on one side", gzip_br.clone(), HeaderMap::new(), "Accept-Encoding", false),
+            ("missing on both sides", HeaderMap::new(), HeaderMap::new(), "Accept-Encoding", true),
+            ("absent is not empty", headers(&[("Accept-Encoding", "")]), HeaderMap::new(), "Accept-Encoding", false),
+            ("order matters elsewhere", headers(&[("X-Tenant", "a, b")]), headers(&[("X-Tenant", "b, a")]), "X-Tenant", false),
+            ("whitespace does not", headers(&[("X-Tenant", "a,b")]), headers(&[("x-tenant", " a ,  b")]), "X-Tenant", true),
+            ("case matters elsewhere", headers(&[("X-Tenant", "a")]), headers(&[("X-Tenant", "A")]), "X-Tenant", false),
+            ("unlisted headers are ignored", headers(&[("Accept-Encoding", "gzip"), ("X-A", "1")]), headers(&[("Accept-Encoding", "gzip"), ("X-A", "2")]), "Accept-Encoding", true),
+            ("several names in one value", headers(&[("Accept", "*/*"), ("X-A", "1")]), headers(&[("Accept", "*/*"), ("X-A", "2")]), "Accept, X-A", false),
+            ("Vary: * matches nothing", HeaderMap::new(), HeaderMap::new(), "*", false),
+            ("* anywhere in the list", gzip_br.clone(), gzip_br.clone(), "Accept-Encoding, *", false),
+            ("empty Vary", headers(&[("X-A", "1")]), headers(&[("X-A", "2")]), "", true),
+        ];
+        for (name, a, b, vary, expected) in cases {
+            assert_eq!(equivalent(a, b, [*vary]), *expected, "{name}");
+            assert_eq!(equivalent(b, a, [*vary]), *expected, "{name}, swapped");
+            if *expected {
+                assert_eq!(selective_fingerprint(a, [*vary]), selective_fingerprint(b, [*vary]), "{name}");
+            } else if *vary != "*" && !vary.ends_with('*') {
+                assert_ne!(selective_fingerprint(a, [*vary]), selective_fingerprint(b, [*vary]), "{name}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_selective_fingerprint_is_stable() {
+        let map = headers(&[("Accept-Encoding", "gzip, br"), ("X-A", "1")]);
+        // Vary names are case-insensitive and their order does not matter.
+        let fingerprint = selective_fingerprint(&map, ["accept-encoding", "X-A"]);
+        assert_eq!(fingerprint, selective_fingerprint(&map, ["x-a, Accept-Encoding"]));
+        assert_eq!(fingerprint, 0x1ca5_9dbd_ad90_6d11);
+        assert_eq!(selective_fingerprint(&HeaderMap::new(), []), 0xcbf2_9ce4_8422_2325);
+    }
+}
@@ -0,0 +1,207 @@
+//! HTTP Strict Transport Security (RFC 6797).
+//!
+//! With HSTS on (see `ZJHttpClientBuilder::hsts`), a `Strict-Transport-Security`
+//! header received over HTTPS records its host in the client's [`HstsStore`].
+//! Until the entry expires, `send()` rewrites `http://` requests to that host
+//! (and its subdomains with `includeSubDomains`) to `https://` before
+//! connecting. Port 80 becomes 443; any other explicit port is kept.
+//!
+//! The header is ignored over plain HTTP and for IP-address hosts. Expiry is
+//! measured on the client's [`Clock`](crate::clock::Clock).
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+use tracing::debug;
+
+use crate::log_target::CONNECT;
+
+/// A known HSTS host.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HstsEntry {
+    pub include_subdomains: bool,
+    pub expires: SystemTime,
+}
+
+/// The hosts a client upgrades to HTTPS. Clones share their entries.
+#[derive(Debug, Clone, Default)]
+pub struct HstsStore {
+    entries: Arc<Mutex<HashMap<String, HstsEntry>>>,
+}
+
+/// The directives of a `Strict-Transport-Security` value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HstsPolicy {
+    pub max_age: Duration,
+    pub include_subdomains: bool,
+}
+
+impl HstsPolicy {
+    /// Parse a `Strict-Transport-Security` value. `None` when `max-age` is
+    /// missing or malformed, or a directive appears twice (RFC 6797 §6.1).
+    pub fn parse(value: &str) -> Option<Self> {
+        let mut max_age = None;
+        let mut include_subdomains = false;
+        for directive in value.split(';') {
+            let directive = directive.trim();
+            if directive.is_empty() {
+                continue;
+            }
+            let (name, arg) = match directive.split_once('=') {
+                Some((name, arg)) => (name.trim(), Some(arg.trim().trim_matches('"'))),
+                None => (directive, None),
+            };
+            if name.eq_ignore_ascii_case("max-age") {
+                let seconds = arg?.parse::<u64>().ok()?;
+                if max_age.replace(Duration::from_secs(seconds)).is_some() {
+                    return None;
+                }
+            } else if name.eq_ignore_ascii_case("includesubdomains") {
+                if include_subdomains {
+                    return None;
+                }
+                include_subdomains = true;
+            }
+        }
+        Some(HstsPolicy { max_age: max_age?, include_subdomains })
+    }
+}
+
+/// Lowercase `host` without a trailing dot.
+fn normalize(host: &str) -> String {
+    host.trim_end_matches('.').to_ascii_lowercase()
+}
+
+impl HstsStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// An independent store starting with a copy of this one's entries.
+    pub fn fork(&self) -> Self {
+        HstsStore { entries: Arc::new(Mutex::new(self.entries.lock().unwrap().clone())) }
+    }
+
+    /// Add or replace the entry for `host`, e.g. from a preload list.
+    pub fn preload(&self, host: &str, include_subdomains: bool, expires: SystemTime) {
+        self.entries
+            .lock()
+            .unwrap()
+            .insert(normalize(host), HstsEntry { include_subdomains, expires });
+    }
+
+    /// Forget `host`. Returns whether it was known.
+    pub fn remove(&self, host: &str) -> bool {
+        self.entries.lock().unwrap().remove(&normalize(host)).is_some()
+    }
+
+    /// The entry recorded for exactly `host`, expired or not.
+    pub fn get(&self, host: &str) -> Option<HstsEntry> {
+        self.entries.lock().unwrap().get(&normalize(host)).copied()
+    }
+
+    /// Apply a `Strict-Transport-Security` `value` received from `host` over
+    /// HTTPS at `now`. `max-age=0` removes the entry; an invalid value is
+    /// ignored.
+    pub fn observe(&self, host: &str, value: &str, now: SystemTime) {
+        let Some(policy) = HstsPolicy::parse(value) else {
+            debug!(target: CONNECT, host, value, "ignoring invalid Strict-Transport-Security");
+            return;
+        };
+        if policy.max_age.is_zero() {
+            self.remove(host);
+            return;
+        }
+        // A max-age too large for SystemTime never expires in practice.
+        let expires = now.checked_add(policy.max_age).unwrap_or(now + Duration::from_secs(u32::MAX as u64));
+        self.preload(host, policy.include_subdomains, expires);
+    }
+
+    /// Whether requests to `host` must use HTTPS at `now`: `host` or, with
+    /// `includeSubDomains`, one of its parent domains has a live entry.
+    /// Expired entries met on the way are dropped.
+    pub fn is_secure_host(&self, host: &str, now: SystemTime) -> bool {
+        let host = normalize(host);
+        let mut entries = self.entries.lock().unwrap();
+        let mut candidate = host.as_str();
+        loop {
+            if let Some(entry) = entries.get(candidate).copied() {
+                if entry.expires <= now {
+                    entries.remove(candidate);
+                } else if candidate == host || entry.include_subdomains {
+                    return true;
+                }
+            }
+            match candidate.split_once('.') {
+                Some((_, parent)) => candidate = parent,
+                None => return false,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_policy() {
+        let cases = [
+            ("max-age=31536000", Some((31536000, false))),
+            ("max-age=\"60\"; includeSubDomains", Some((60, true))),
+            ("  MAX-AGE = 5 ;INCLUDESUBDOMAINS; preload", Some((5, true))),
+            ("max-age=0", Some((0, false))),
+            ("includeSubDomains", None),
+            ("max-age=abc", None),
+            ("max-age=-1", None),
+            ("max-age=1; max-age=2", None),
+            ("max-age=1; includeSubDomains; includeSubDomains", None),
+            ("", None),
+        ];
+        for (value, expected) in cases {
+            let parsed = HstsPolicy::parse(value).map(|p| (p.max_age.as_secs(), p.include_subdomains));
+            assert_eq!(parsed, expected, "{value:?}");
+        }
+    }
+
+    #[test]
+    fn test_observe_and_expire() {
+        let store = HstsStore::new();
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000);
+        store.observe("Example.COM.", "max-age=60", now);
+        assert!(store.is_secure_host("example.com", now));
+        assert!(!store.is_secure_host("www.example.com", now), "no includeSubDomains");
+        assert!(store.is_secure_host("example.com", now + Duration::from_secs(59)));
+        assert!(!store.is_secure_host("example.com", now + Duration::from_secs(60)));
+        assert_eq!(store.get("example.com"), None, "expired entries are dropped");
+    }
+
+    #[test]
+    fn test_include_subdomains() {
+        let store = HstsStore::new();
+        let now = SystemTime::UNIX_EPOCH;
+        store.observe("example.com", "max-age=60; includeSubDomains", now);
+        assert!(store.is_secure_host("a.b.example.com", now));
+        assert!(!store.is_secure_host("notexample.com", now));
+        assert!(!store.is_secure_host("com", now));
+    }
+
+    #[test]
+    fn test_max_age_zero_removes() {
+        let store = HstsStore::new();
+        let now = SystemTime::UNIX_EPOCH;
+        store.preload("example.com", true, now + Duration::from_secs(3600));
+        store.observe("example.com", "max-age=0", now);
+        assert!(!store.is_secure_host("example.com", now));
+    }
+
+    #[test]
+    fn test_invalid_header_keeps_entry() {
+        let store = HstsStore::new();
+        let now = SystemTime::UNIX_EPOCH;
+        store.observe("example.com", "max-age=60", now);
+        store.observe("example.com", "max-age=nope", now);
+        assert!(store.is_secure_host("example.com", now));
+    }
+}
@@ -0,0 +1,109 @@
+//! JSON Text Sequences (RFC 7464, `application/json-seq`) framing.
+//!
+//! Each record is an RS byte (0x1E), a JSON text and a LF. [`RecordSplitter`]
+//! only cuts the body into records; [`crate::response::Response::json_seq`]
+//! feeds it from the framed body stream (so chunked decoding and connection
+//! return stay in the response) and deserializes each record.
+
+/// Record separator that starts every record.
+pub const RS: u8 = 0x1E;
+
+/// What [`crate::response::Response::json_seq_with`] does with a record that
+/// does not deserialize.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RecordErrors {
+    /// Yield the error and end the stream.
+    #[default]
+    Stop,
+    /// Yield the error in place of the record and carry on with the next one.
+    Skip,
+}
+
+/// Cuts a byte stream into records at RS bytes.
+///
+/// A record is complete once the next RS arrives or the input ends, so the
+/// final record needs no trailing LF. Records holding only whitespace (such as
+/// between consecutive RS bytes) are dropped; bytes before the first RS that
+/// are not whitespace come out as a record of their own and fail to parse.
+#[derive(Debug, Default)]
+pub struct RecordSplitter {
+    buf: Vec<u8>,
+}
+
+impl RecordSplitter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, bytes: &[u8]) {
+        self.buf.extend_from_slice(bytes);
+    }
+
+    /// The next complete record, without its RS and surrounding whitespace.
+    /// With `at_eof`, whatever is left counts as the final record.
+    pub fn next_record(&mut self, at_eof: bool) -> Option<Vec<u8>> {
+        loop {
+            // Skip the RS that starts the buffer, if any; the record runs to the next one.
+            let start = usize::from(self.buf.first() == Some(&RS));
+            let end = match self.buf[start..].iter().position(|&b| b == RS) {
+                Some(i) => start + i,
+                None if at_eof && !self.buf.is_empty() => self.buf.len(),
+                None => return None,
+            };
+            let record: Vec<u8> = self.buf.drain(..end).skip(start).collect();
+            let record = record.trim_ascii();
+            if !record.is_empty() {
+                return Some(record.to_vec());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn split_all(pieces: &[&[u8]]) -> Vec<String> {
+        let mut splitter = RecordSplitter::new();
+        let mut records = Vec::new();
+        for piece in pieces {
+            splitter.push(piece);
+            while let Some(record) = splitter.next_record(false) {
+                records.push(String::from_utf8(record).unwrap());
+            }
+        }
+        while let Some(record) = splitter.next_record(true) {
+            records.push(String::from_utf8(record).unwrap());
+        }
+        records
+    }
+
+    #[test]
+    fn test_record_splitting() {
+        let cases: &[(&[&[u8]], &[&str])] = &[
+            (&[b"\x1e{\"a\":1}\n\x1e{\"a\":2}\n"], &[r#"{"a":1}"#, r#"{"a":2}"#]),
+            // Split across pushes, including right after an RS.
+            (&[b"\x1e{\"a\"", b":1}\n\x1e", b"[2]\n"], &[r#"{"a":1}"#, "[2]"]),
+            // Final record without its LF.
+            (&[b"\x1e1\n\x1e2"], &["1", "2"]),
+            // Consecutive separators and blank records.
+            (&[b"\x1e\x1e \n\x1etrue\n\x1e"], &["true"]),
+            // Junk before the first separator is a record of its own.
+            (&[b"oops\x1e3\n"], &["oops", "3"]),
+            (&[b""], &[]),
+        ];
+        for (pieces, expected) in cases {
+            assert_eq!(split_all(pieces), *expected, "{pieces:?}");
+        }
+    }
+
+    #[test]
+    fn test_record_waits_for_next_separator() {
+        let mut splitter = RecordSplitter::new();
+        splitter.push(b"\x1e{\"a\":1}\n");
+        assert_eq!(splitter.next_record(false), None);
+        splitter.push(b"\x1e");
+        assert_eq!(splitter.next_record(false).as_deref(), Some(&br#"{"a":1}"#[..]));
+        assert_eq!(splitter.next_record(true), None);
+    }
+}
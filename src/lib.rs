@@ -1,16 +1,47 @@
 pub mod body;
+mod body_pipeline;
+pub mod byteranges;
 pub mod client;
+pub mod clock;
+pub mod config;
+pub mod connection;
+pub mod connector;
 pub mod content_type;
 pub mod cookie;
+pub mod decompress;
+pub mod deprecation;
+pub mod duplex;
+pub mod encoding;
 pub mod error;
-pub use error::{Result, ZjhttpcError};
+pub use error::{ErrorCategory, RequestPhase, Result, ZjhttpcError};
+pub mod extensions;
+mod forwarded;
 pub mod header;
+pub mod hsts;
+pub mod json_seq;
+pub mod link;
+pub mod log_target;
+mod md5;
+pub mod memory_budget;
 pub mod methods;
 pub mod misc;
+mod ocsp;
+pub mod prelude;
 pub mod proxy;
+mod public_suffix;
+pub mod redirect;
 pub mod requestx;
+pub mod resolver;
 pub mod response;
+pub mod single_flight;
+pub mod spill;
 pub mod sse;
 pub mod stream;
+pub mod summary;
+pub mod throttle;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+mod trust_store;
+pub mod warm;
 
 pub use url;
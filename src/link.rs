@@ -0,0 +1,179 @@
+//! `Link` header parsing (RFC 8288).
+
+/// One link from a `Link` header: the target URI and its parameters.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Link {
+    /// Target as written between `<` and `>`; relative references are not resolved.
+    pub uri: String,
+    /// Parameters in order, names lowercased, quotes and escapes removed.
+    /// A parameter without a value has an empty string.
+    pub params: Vec<(String, String)>,
+}
+
+impl Link {
+    /// First value of the named parameter.
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// The `rel` parameter, e.g. `preload`.
+    pub fn rel(&self) -> Option<&str> {
+        self.param("rel")
+    }
+
+    /// Whether the space separated `rel` parameter contains `rel` (case-insensitive).
+    pub fn has_rel(&self, rel: &str) -> bool {
+        self.rel()
+            .is_some_and(|v| v.split_ascii_whitespace().any(|r| r.eq_ignore_ascii_case(rel)))
+    }
+}
+
+/// Parse a `Link` header value holding any number of comma separated links.
+/// Malformed links are skipped rather than failing the whole header.
+pub fn parse_link_header(value: &str) -> Vec<Link> {
+    let mut links = Vec::new();
+    let mut rest = value;
+    loop {
+        rest = rest.trim_start_matches([' ', '\t', ',']);
+        if rest.is_empty() {
+            return links;
+        }
+        match parse_one(rest) {
+            Some((link, remaining)) => {
+                links.push(link);
+                rest = remaining;
+            }
+            None => rest = skip_element(rest),
+        }
+    }
+}
+
+/// Parse `<uri> *( ";" param )` and return the rest after it.
+fn parse_one(input: &str) -> Option<(Link, &str)> {
+    let input = input.strip_prefix('<')?;
+    let end = input.find('>')?;
+    let uri = input[..end].trim().to_string();
+    let mut rest = &input[end + 1..];
+    let mut params = Vec::new();
+    loop {
+        rest = rest.trim_start_matches([' ', '\t']);
+        let Some(after_semi) = rest.strip_prefix(';') else {
+            break;
+        };
+        let after_semi = after_semi.trim_start_matches([' ', '\t']);
+        let name_len = after_semi.find(['=', ';', ',', ' ', '\t']).unwrap_or(after_semi.len());
+        let name = after_semi[..name_len].to_ascii_lowercase();
+        rest = after_semi[name_len..].trim_start_matches([' ', '\t']);
+        let value = if let Some(after_eq) = rest.strip_prefix('=') {
+            let after_eq = after_eq.trim_start_matches([' ', '\t']);
+            let (value, remaining) = parse_param_value(after_eq);
+            rest = remaining;
+            value
+        } else {
+            String::new()
+        };
+        if !name.is_empty() {
+            params.push((name, value));
+        }
+    }
+    if !(rest.is_empty() || rest.starts_with(',')) {
+        return None;
+    }
+    Some((Link { uri, params }, rest))
+}
+
+/// A token or a quoted string.
+fn parse_param_value(input: &str) -> (String, &str) {
+    if let Some(quoted) = input.strip_prefix('"') {
+        let mut value = String::new();
+        let mut chars = quoted.char_indices();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '\\' => {
+                    if let Some((_, escaped)) = chars.next() {
+                        value.push(escaped);
+                    }
+                }
+                '"' => return (value, &quoted[i + 1..]),
+                c => value.push(c),
+            }
+        }
+        (value, "")
+    } else {
+        let end = input.find([';', ',', ' ', '\t']).unwrap_or(input.len());
+        (input[..end].to_string(), &input[end..])
+    }
+}
+
+/// Skip to the next top-level comma, honouring `<...>` and quoted strings.
+fn skip_element(input: &str) -> &str {
+    let mut in_uri = false;
+    let mut in_quotes = false;
+    let mut escaped = false;
+    for (i, c) in input.char_indices() {
+        if escaped {
+            escaped = false;
+            continue;
+        }
+        match c {
+            '\\' if in_quotes => escaped = true,
+            '"' if !in_uri => in_quotes = !in_quotes,
+            '<' if !in_quotes => in_uri = true,
+            '>' if !in_quotes => in_uri = false,
+            ',' if !in_uri && !in_quotes => return &input[i + 1..],
+            _ => {}
+        }
+    }
+    ""
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_single_link() {
+        let links = parse_link_header("</style.css>; rel=preload; as=style");
+        assert_eq!(links.len(), 1);
+        assert_eq!(links[0].uri, "/style.css");
+        assert_eq!(links[0].rel(), Some("preload"));
+        assert_eq!(links[0].param("as"), Some("style"));
+    }
+
+    #[test]
+    fn test_parse_multiple_links_and_quoted_values() {
+        let links = parse_link_header(
+            r#"</a.js>; rel="preload"; as=script, <https://cdn.example/b.woff2>; rel="preload next"; title="a, \"b\""; crossorigin"#,
+        );
+        assert_eq!(links.len(), 2);
+        assert_eq!(links[0].uri, "/a.js");
+        assert!(links[0].has_rel("preload"));
+        assert_eq!(links[1].uri, "https://cdn.example/b.woff2");
+        assert!(links[1].has_rel("next"));
+        assert_eq!(links[1].param("title"), Some(r#"a, "b""#));
+        assert_eq!(links[1].param("crossorigin"), Some(""));
+    }
+
+    #[test]
+    fn test_param_names_are_case_insensitive() {
+        let links = parse_link_header("</x>; REL=Preload");
+        assert_eq!(links[0].params, vec![("rel".to_string(), "Preload".to_string())]);
+        assert!(links[0].has_rel("preload"));
+    }
+
+    #[test]
+    fn test_malformed_link_is_skipped() {
+        let links = parse_link_header(r#"no-brackets; rel=x, </ok>; rel=preload"#);
+        assert_eq!(links.len(), 1);
+        assert_eq!(links[0].uri, "/ok");
+    }
+
+    #[test]
+    fn test_empty_header() {
+        assert!(parse_link_header("").is_empty());
+        assert!(parse_link_header(" , ").is_empty());
+    }
+}
@@ -0,0 +1,26 @@
+//! `tracing` targets the client logs under, one per subsystem, so each can be
+//! filtered on its own with the usual `EnvFilter` syntax, e.g.
+//! `RUST_LOG=zjhttpc=warn,zjhttpc::pool=debug`.
+//!
+//! Levels follow one taxonomy across all targets:
+//!
+//! - `trace`: protocol details and pool bookkeeping, several events per request.
+//! - `debug`: connection lifecycle: connections opened, proxy tunnels set up,
+//!   pooled connections closed, trust roots reloaded.
+//! - `warn`: anomalies worth acting on: a pooled connection that failed and
+//!   was replaced, protocol garbage from a peer, certificates that could not
+//!   be parsed, a trust store or warm-connection task that keeps failing.
+//!
+//! Nothing is logged at `info` or `error`; a successful request never logs
+//! above `debug`. Errors returned to the caller are not logged.
+
+/// Connection pool: check-outs, returns, evictions, warm connections.
+pub const POOL: &str = "zjhttpc::pool";
+/// Opening connections, directly or through a proxy.
+pub const CONNECT: &str = "zjhttpc::connect";
+/// Trust store loading and reloading.
+pub const TLS: &str = "zjhttpc::tls";
+/// Bytes on the wire: request bodies written, response heads parsed.
+pub const WIRE: &str = "zjhttpc::wire";
+/// Response body decoding and saving.
+pub const BODY: &str = "zjhttpc::body";
@@ -0,0 +1,453 @@
+//! A byte budget for response bodies buffered in memory, shared by every
+//! request of a client.
+//!
+//! Per-request limits (`set_max_body_bytes`, `global_max_header_bytes`) do
+//! not bound the total when thousands of requests buffer at once. With
+//! [`ClientInner::memory_budget`](crate::client::ClientInner::memory_budget)
+//! set, the paths that buffer a body reserve their bytes against one
+//! [`MemoryBudget`] before allocating and give them back when done:
+//!
+//! - `Response::body_bytes` and the readers built on it (`body_string`,
+//!   `body_json`, `byteranges`, ...) reserve the `Content-Length` up front,
+//!   or their initial buffer when the length is unknown or the body is
+//!   decoded, and then charge every byte past that as it arrives. The
+//!   reservation ends when the read returns; the buffer it hands out is the
+//!   caller's.
+//! - `Response::peek_body` (so `body_preview`, `sniff_content_type` and the
+//!   single-flight leader) holds its peeked bytes until they are read on or
+//!   the response is dropped.
+//! - Each single-flight follower's copy of the leader's body holds its bytes
+//!   until the copy is dropped; a follower whose copy does not fit sends on
+//!   its own.
+//!
+//! An up-front reservation that does not fit waits up to
+//! [`MemoryBudget::with_max_wait`] (on the client's [`Clock`]) for other
+//! reservations to end, then fails with `MemoryBudgetExceeded`; bytes
+//! charged during a read, and single-flight copies, fail at once.
+//!
+//! Streaming readers (`body_reader`, `bytes_stream`, `copy_body_to`,
+//! `save_to_file`, the SSE and JSON-sequence streams) hold one buffer at a
+//! time and are not charged. Neither are response heads, each bounded by
+//! `global_max_header_bytes`, nor request bodies kept for replay, which
+//! spill to disk (see [`crate::spill`]).
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use async_std::channel::{Receiver, Sender};
+use futures::future::Either;
+
+use crate::clock::Clock;
+use crate::error::{MemoryBudgetExceededSnafu, Result};
+
+/// Bytes a client may hold in buffered response bodies at once. Clones share
+/// the count.
+///
+/// ```
+/// use std::time::Duration;
+/// use zjhttpc::client::ZJHttpClient;
+/// use zjhttpc::memory_budget::MemoryBudget;
+///
+/// let client = ZJHttpClient::builder()
+///     .set_memory_budget(MemoryBudget::new(256 << 20).with_max_wait(Duration::from_millis(50)))
+///     .build()
+///     .unwrap();
+/// assert_eq!(client.buffered_bytes(), 0);
+/// ```
+#[derive(Debug, Clone)]
+pub struct MemoryBudget {
+    max_bytes: u64,
+    max_wait: Duration,
+    state: Arc<Mutex<BudgetState>>,
+}
+
+#[derive(Debug, Default)]
+struct BudgetState {
+    in_use: u64,
+    /// Reservations waiting for room; dropping a sender wakes its waiter.
+    waiters: Vec<Sender<()>>,
+}
+
+impl MemoryBudget {
+    /// A budget of `max_bytes`, whose up-front reservations wait up to
+    /// 100 ms for room.
+    pub fn new(max_bytes: u64) -> Self {
+        MemoryBudget { max_bytes, max_wait: Duration::from_millis(100), state: Arc::default() }
+    }
+
+    /// How long an up-front reservation waits for room before failing;
+    /// zero fails at once.
+    pub fn with_max_wait(mut self, max_wait: Duration) -> Self {
+        self.max_wait = max_wait;
+        self
+    }
+
+    pub fn max_bytes(&self) -> u64 {
+        self.max_bytes
+    }
+
+    pub fn max_wait(&self) -> Duration {
+        self.max_wait
+    }
+
+    /// Bytes reserved right now.
+    pub fn in_use(&self) -> u64 {
+        self.lock().in_use
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, BudgetState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Reserve `bytes` if they fit now.
+    pub(crate) fn try_reserve(&self, bytes: u64) -> Result<Reservation> {
+        let mut state = self.lock();
+        self.take(&mut state, bytes)?;
+        Ok(Reservation { budget: self.clone(), bytes })
+    }
+
+    /// Reserve `bytes`, waiting up to `max_wait` on `clock` for other
+    /// reservations to make room.
+    pub(crate) async fn reserve(&self, bytes: u64, clock: &dyn Clock) -> Result<Reservation> {
+        let mut deadline = None;
+        loop {
+            let woken = match self.reserve_or_wait(bytes)? {
+                Either::Left(reservation) => return Ok(reservation),
+                Either::Right(woken) => woken,
+            };
+            let deadline = deadline.get_or_insert_with(|| clock.sleep(self.max_wait));
+            if let Either::Right(_) = futures::future::select(std::pin::pin!(woken.recv()), deadline).await {
+                return self.try_reserve(bytes);
+            }
+        }
+    }
+
+    /// Reserve `bytes`, or hand out a receiver that wakes once a reservation
+    /// ends. Fails when there is no waiting: `bytes` can never fit, or
+    /// `max_wait` is zero.
+    fn reserve_or_wait(&self, bytes: u64) -> Result<Either<Reservation, Receiver<()>>> {
+        let mut state = self.lock();
+        match self.take(&mut state, bytes) {
+            Ok(()) => Ok(Either::Left(Reservation { budget: self.clone(), bytes })),
+            Err(err) if bytes > self.max_bytes || self.max_wait.is_zero() => Err(err),
+            Err(_) => {
+                let (wake, woken) = async_std::channel::bounded(1);
+                state.waiters.push(wake);
+                Ok(Either::Right(woken))
+            }
+        }
+    }
+
+    fn take(&self, state: &mut BudgetState, bytes: u64) -> Result<()> {
+        match state.in_use.checked_add(bytes) {
+            Some(total) if total <= self.max_bytes => {
+                state.in_use = total;
+                Ok(())
+            }
+            _ => Err(MemoryBudgetExceededSnafu { requested: bytes, in_use: state.in_use, limit: self.max_bytes }.build()),
+        }
+    }
+
+    fn release(&self, bytes: u64) {
+        let mut state = self.lock();
+        state.in_use = state.in_use.saturating_sub(bytes);
+        state.waiters.clear();
+    }
+}
+
+/// Bytes held against a [`MemoryBudget`], given back on drop.
+#[derive(Debug)]
+pub(crate) struct Reservation {
+    budget: MemoryBudget,
+    bytes: u64,
+}
+
+impl Reservation {
+    /// Make the reservation at least `total` bytes, failing at once when the
+    /// difference does not fit.
+    pub(crate) fn grow_to(&mut self, total: u64) -> Result<()> {
+        if total > self.bytes {
+            let mut state = self.budget.lock();
+            self.budget.take(&mut state, total - self.bytes)?;
+            self.bytes = total;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Reservation {
+    fn drop(&mut self) {
+        self.budget.release(self.bytes);
+    }
+}
+
+/// A client's budget together with the clock its reservations wait on.
+#[derive(Clone)]
+pub(crate) struct BudgetGate {
+    budget: MemoryBudget,
+    clock: Arc<dyn Clock>,
+}
+
+impl BudgetGate {
+    pub(crate) fn new(budget: MemoryBudget, clock: Arc<dyn Clock>) -> Self {
+        BudgetGate { budget, clock }
+    }
+
+    pub(crate) async fn reserve(&self, bytes: u64) -> Result<Reservation> {
+        self.budget.reserve(bytes, self.clock.as_ref()).await
+    }
+
+    pub(crate) fn try_reserve(&self, bytes: u64) -> Result<Reservation> {
+        self.budget.try_reserve(bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ZjhttpcError;
+    use crate::testing::MockClock;
+
+    #[test]
+    fn reservations_add_up_and_are_released_on_drop() {
+        let budget = MemoryBudget::new(100);
+        let mut a = budget.try_reserve(60).unwrap();
+        let err = budget.try_reserve(50).unwrap_err();
+        assert!(
+            matches!(err, ZjhttpcError::MemoryBudgetExceeded { requested: 50, in_use: 60, limit: 100, .. }),
+            "{err:?}"
+        );
+        a.grow_to(40).unwrap();
+        a.grow_to(100).unwrap();
+        assert_eq!(budget.in_use(), 100);
+        assert!(a.grow_to(101).is_err());
+        drop(a);
+        assert_eq!(budget.in_use(), 0);
+        let _b = budget.try_reserve(100).unwrap();
+    }
+
+    #[test]
+    fn waiting_reservation_gets_room_or_times_out() {
+        async_std::task::block_on(async {
+            let clock = MockClock::new();
+            let budget = MemoryBudget::new(10).with_max_wait(Duration::from_secs(1));
+            let first = budget.try_reserve(8).unwrap();
+
+            // Room made before the wait is over.
+            let waiting = budget.reserve(5, &clock);
+            let release = async {
+                while clock.pending_sleeps() == 0 {
+                    async_std::task::yield_now().await;
+                }
+                drop(first);
+            };
+            let (second, ()) = futures::join!(waiting, release);
+            let second = second.unwrap();
+            assert_eq!(budget.in_use(), 5);
+
+            // No room before the wait is over.
+            let waiting = budget.reserve(6, &clock);
+            let time_out = async {
+                while clock.pending_sleeps() == 0 {
+                    async_std::task::yield_now().await;
+                }
+                clock.advance(Duration::from_secs(1));
+            };
+            let (third, ()) = futures::join!(waiting, time_out);
+            assert!(matches!(third, Err(ZjhttpcError::MemoryBudgetExceeded { requested: 6, in_use: 5, .. })));
+
+            // More than the whole budget fails without waiting.
+            assert!(budget.reserve(11, &clock).await.is_err());
+            assert_eq!(clock.pending_sleeps(), 0);
+            drop(second);
+            assert_eq!(budget.in_use(), 0);
+        });
+    }
+
+    // ==================== Through the client ====================
+
+    use std::net::SocketAddr;
+    use std::pin::Pin;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use std::task::{Context, Poll, Waker};
+
+    use futures::StreamExt;
+    use futures::future::{BoxFuture, join_all};
+
+    use crate::client::ZJHttpClient;
+    use crate::connector::Connector;
+    use crate::requestx::Request;
+    use crate::response::Response;
+    use crate::stream::BoxedStream;
+
+    const BODY: usize = 1000;
+
+    /// Holds back every response body until opened.
+    #[derive(Default)]
+    struct Gate {
+        open: AtomicBool,
+        wakers: Mutex<Vec<Waker>>,
+    }
+
+    impl Gate {
+        fn open(&self) {
+            self.open.store(true, Ordering::SeqCst);
+            self.wakers.lock().unwrap().drain(..).for_each(Waker::wake);
+        }
+    }
+
+    /// A connection answering with a `BODY`-byte response whose body only
+    /// comes once the gate is open.
+    struct GatedStream {
+        reply: Vec<u8>,
+        head_len: usize,
+        pos: usize,
+        gate: Arc<Gate>,
+    }
+
+    impl async_std::io::Read for GatedStream {
+        fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<std::io::Result<usize>> {
+            if self.pos >= self.head_len && !self.gate.open.load(Ordering::SeqCst) {
+                self.gate.wakers.lock().unwrap().push(cx.waker().clone());
+                if !self.gate.open.load(Ordering::SeqCst) {
+                    return Poll::Pending;
+                }
+            }
+            let end = if self.pos < self.head_len { self.head_len } else { self.reply.len() };
+            let n = buf.len().min(end - self.pos);
+            buf[..n].copy_from_slice(&self.reply[self.pos..self.pos + n]);
+            self.pos += n;
+            Poll::Ready(Ok(n))
+        }
+    }
+
+    impl async_std::io::Write for GatedStream {
+        fn poll_write(self: Pin<&mut Self>, _cx: &mut Context<'_>, buf: &[u8]) -> Poll<std::io::Result<usize>> {
+            Poll::Ready(Ok(buf.len()))
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    struct GatedConnector(Arc<Gate>);
+
+    impl Connector for GatedConnector {
+        fn connect<'a>(&'a self, _: &'a str, _: SocketAddr, _: Duration) -> BoxFuture<'a, Result<BoxedStream>> {
+            let head = format!("HTTP/1.1 200 OK\r\nContent-Length: {BODY}\r\n\r\n").into_bytes();
+            let head_len = head.len();
+            let reply = [head, vec![b'x'; BODY]].concat();
+            let stream = GatedStream { reply, head_len, pos: 0, gate: self.0.clone() };
+            Box::pin(async move { Ok(Box::new(stream) as BoxedStream) })
+        }
+    }
+
+    async fn responses(client: &ZJHttpClient, n: usize) -> Vec<Response> {
+        let mut responses = Vec::new();
+        for _ in 0..n {
+            let mut req = Request::new("GET", "http://127.0.0.1:8080/").unwrap();
+            responses.push(Box::pin(client.send(&mut req)).await.unwrap());
+        }
+        responses
+    }
+
+    fn gated_client(gate: &Arc<Gate>, clock: &MockClock, budget: MemoryBudget) -> ZJHttpClient {
+        ZJHttpClient::builder()
+            .set_connector(Arc::new(GatedConnector(gate.clone())) as Arc<dyn Connector>)
+            .set_clock(Arc::new(clock.clone()))
+            .set_memory_budget(budget)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn one_body_too_many_fails_after_the_wait() {
+        async_std::task::block_on(async {
+            let (gate, clock) = (Arc::new(Gate::default()), MockClock::new());
+            let budget = MemoryBudget::new(3 * BODY as u64).with_max_wait(Duration::from_secs(1));
+            let client = gated_client(&gate, &clock, budget);
+            let mut responses = responses(&client, 4).await;
+
+            let finished = AtomicUsize::new(0);
+            let reads = join_all(responses.iter_mut().map(|resp| async {
+                let result = resp.body_bytes().await;
+                finished.fetch_add(1, Ordering::SeqCst);
+                result
+            }));
+            let drive = async {
+                // Three reads hold the budget, the fourth waits for room.
+                while clock.pending_sleeps() == 0 {
+                    async_std::task::yield_now().await;
+                }
+                assert_eq!(client.buffered_bytes(), 3 * BODY as u64);
+                clock.advance(Duration::from_secs(1));
+                while finished.load(Ordering::SeqCst) == 0 {
+                    async_std::task::yield_now().await;
+                }
+                gate.open();
+            };
+            let (results, ()) = futures::join!(reads, drive);
+
+            let errors: Vec<_> = results.iter().filter_map(|r| r.as_ref().err()).collect();
+            assert_eq!(errors.len(), 1, "{results:?}");
+            assert!(
+                matches!(errors[0], ZjhttpcError::MemoryBudgetExceeded { requested: 1000, in_use: 3000, limit: 3000, .. }),
+                "{errors:?}"
+            );
+            for body in results.iter().filter_map(|r| r.as_ref().ok()) {
+                assert_eq!(body.len(), BODY);
+            }
+            assert_eq!(client.buffered_bytes(), 0);
+        });
+    }
+
+    #[test]
+    fn waiting_body_goes_ahead_when_another_finishes() {
+        async_std::task::block_on(async {
+            let (gate, clock) = (Arc::new(Gate::default()), MockClock::new());
+            let client = gated_client(&gate, &clock, MemoryBudget::new(BODY as u64));
+            let mut responses = responses(&client, 2).await;
+
+            let reads = join_all(responses.iter_mut().map(Response::body_bytes));
+            let drive = async {
+                while clock.pending_sleeps() == 0 {
+                    async_std::task::yield_now().await;
+                }
+                gate.open();
+            };
+            let (results, ()) = futures::join!(reads, drive);
+            for result in results {
+                assert_eq!(result.unwrap().len(), BODY);
+            }
+            assert_eq!(client.buffered_bytes(), 0);
+        });
+    }
+
+    #[test]
+    fn peeks_are_held_and_streams_are_exempt() {
+        async_std::task::block_on(async {
+            let (gate, clock) = (Arc::new(Gate::default()), MockClock::new());
+            gate.open();
+            let client = gated_client(&gate, &clock, MemoryBudget::new(BODY as u64 / 2));
+            let mut responses = responses(&client, 3).await;
+
+            // Never fits: fails without waiting, the body stays unread.
+            let err = responses[0].body_bytes().await.unwrap_err();
+            assert!(matches!(err, ZjhttpcError::MemoryBudgetExceeded { requested: 1000, .. }), "{err:?}");
+            assert_eq!(clock.pending_sleeps(), 0);
+
+            let chunks: Vec<_> = responses[1].bytes_stream().collect().await;
+            assert_eq!(chunks.into_iter().map(|chunk| chunk.unwrap().len()).sum::<usize>(), BODY);
+
+            assert_eq!(responses[2].peek_body(100).await.unwrap().len(), 100);
+            assert_eq!(client.buffered_bytes(), 100);
+            drop(responses);
+            assert_eq!(client.buffered_bytes(), 0);
+        });
+    }
+}
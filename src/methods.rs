@@ -1,8 +1,123 @@
-pub const GET: &'static str = "GET";
-pub const PUT: &'static str = "PUT";
-pub const DELETE: &'static str = "DELETE";
-pub const POST: &'static str = "POST";
-pub const OPTIONS: &'static str = "OPTIONS";
-pub const HEAD: &'static str = "HEAD";
-pub const CONNECT: &'static str = "CONNECT";
+use std::collections::BTreeSet;
 
+pub const GET: &str = "GET";
+pub const PUT: &str = "PUT";
+pub const DELETE: &str = "DELETE";
+pub const POST: &str = "POST";
+pub const OPTIONS: &str = "OPTIONS";
+pub const HEAD: &str = "HEAD";
+pub const CONNECT: &str = "CONNECT";
+pub const PATCH: &str = "PATCH";
+pub const TRACE: &str = "TRACE";
+
+/// The constant for a method token read off the wire. Method names are
+/// case-sensitive, so `get` is not `GET`.
+pub fn from_token(token: &str) -> Option<&'static str> {
+    [GET, PUT, DELETE, POST, OPTIONS, HEAD, CONNECT, PATCH, TRACE].into_iter().find(|m| *m == token)
+}
+
+/// Whether requests with this method conventionally carry a body. These
+/// always announce their length, `Content-Length: 0` included; other methods
+/// only send `Content-Length` when a body was set.
+pub fn expects_body(method: &str) -> bool {
+    matches!(method, POST | PUT | PATCH)
+}
+
+
+/// Whether a response to `method` with `status` has a body. Responses to
+/// HEAD, 2xx responses to CONNECT (the connection is a tunnel from then on),
+/// 1xx, 204 and 304 never do, whatever their framing headers say
+/// (RFC 9112 §6.3).
+pub fn response_has_body(method: &str, status: u16) -> bool {
+    match status {
+        100..=199 | 204 | 304 => false,
+        200..=299 if method == CONNECT => false,
+        _ => method != HEAD,
+    }
+}
+
+/// Method tokens, as listed by an `Allow` header. Method names are
+/// case-sensitive, see [`from_token`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MethodSet(BTreeSet<String>);
+
+impl MethodSet {
+    /// Parse a comma separated list of method tokens, skipping anything that
+    /// is not a token.
+    pub fn parse(value: &str) -> Self {
+        value
+            .split(',')
+            .map(str::trim)
+            .filter(|m| !m.is_empty() && m.bytes().all(crate::header::is_tchar))
+            .collect()
+    }
+
+    pub fn contains(&self, method: &str) -> bool {
+        self.0.contains(method)
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// An empty `Allow` means the resource allows no method at all.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// The methods in byte order.
+    pub fn iter(&self) -> impl Iterator<Item = &str> {
+        self.0.iter().map(String::as_str)
+    }
+}
+
+impl<S: Into<String>> FromIterator<S> for MethodSet {
+    fn from_iter<I: IntoIterator<Item = S>>(iter: I) -> Self {
+        MethodSet(iter.into_iter().map(Into::into).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_response_has_body() {
+        let cases: &[(&str, u16, bool)] = &[
+            (GET, 200, true),
+            (GET, 404, true),
+            (GET, 100, false),
+            (GET, 103, false),
+            (GET, 204, false),
+            (GET, 304, false),
+            (HEAD, 200, false),
+            (HEAD, 404, false),
+            (CONNECT, 200, false),
+            (CONNECT, 299, false),
+            (CONNECT, 407, true),
+            (CONNECT, 502, true),
+            (OPTIONS, 200, true),
+            (POST, 201, true),
+        ];
+        for (method, status, expected) in cases {
+            assert_eq!(response_has_body(method, *status), *expected, "{method} {status}");
+        }
+    }
+
+    #[test]
+    fn test_method_set_parse() {
+        let cases: &[(&str, &[&str])] = &[
+            ("GET, HEAD, OPTIONS", &[GET, HEAD, OPTIONS]),
+            ("GET,POST,,GET", &[GET, POST]),
+            (" PROPFIND ,get", &["PROPFIND", "get"]),
+            ("GET, bad method, PUT", &[GET, PUT]),
+            ("", &[]),
+        ];
+        for (value, expected) in cases {
+            assert_eq!(MethodSet::parse(value), expected.iter().copied().collect(), "{value}");
+        }
+        let set = MethodSet::parse("GET, HEAD");
+        assert!(set.contains(GET) && !set.contains("get"));
+        assert_eq!(set.iter().collect::<Vec<_>>(), [GET, HEAD]);
+    }
+}
@@ -0,0 +1,605 @@
+//! Stapled OCSP checks for `ZJHttpClient::require_ocsp_staple`.
+//!
+//! [`StapleVerifier`] runs the usual webpki chain and name checks, then
+//! requires the OCSP response the server stapled to its handshake (RFC 6960)
+//! to:
+//! - be a successful basic response with an entry for the server certificate
+//! - be signed by the certificate's issuer, or by a responder certificate the
+//!   issuer signed for `id-kp-OCSPSigning`
+//! - be current: `thisUpdate` not in the future and `nextUpdate` not passed,
+//!   both with [`CLOCK_SKEW`] of slack; a response without `nextUpdate` is
+//!   accepted for [`MAX_AGE_WITHOUT_NEXT_UPDATE`] after `thisUpdate`
+//! - report the certificate as good
+//!
+//! The issuer is looked up among the certificates the server sent, then among
+//! the trust roots. Failures surface as `ZjhttpcError::OcspStapleMissing` or
+//! `ZjhttpcError::OcspStapleInvalid` through [`handshake_error`].
+
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use ring::{digest, signature};
+use rustls::client::{ServerCertVerified, ServerCertVerifier, WebPkiVerifier};
+use rustls::{Certificate, CertificateError, RootCertStore, ServerName};
+
+use crate::error::{OcspStapleInvalidSnafu, OcspStapleMissingSnafu, ZjhttpcError};
+
+/// Slack for the difference between our clock and the responder's.
+pub(crate) const CLOCK_SKEW: Duration = Duration::from_secs(5 * 60);
+/// How long a response that names no `nextUpdate` is trusted.
+pub(crate) const MAX_AGE_WITHOUT_NEXT_UPDATE: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// Why a stapled response was not accepted; carried through rustls inside
+/// `CertificateError::Other`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum OcspError {
+    Missing,
+    Invalid { reason: String },
+}
+
+impl std::fmt::Display for OcspError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OcspError::Missing => f.write_str("the server did not staple an OCSP response"),
+            OcspError::Invalid { reason } => write!(f, "invalid stapled OCSP response: {reason}"),
+        }
+    }
+}
+
+impl std::error::Error for OcspError {}
+
+fn invalid(reason: impl Into<String>) -> OcspError {
+    OcspError::Invalid { reason: reason.into() }
+}
+
+/// The typed error for a failed handshake whose cause was the OCSP check,
+/// if it was.
+pub(crate) fn handshake_error(err: &std::io::Error) -> Option<ZjhttpcError> {
+    let tls = err.get_ref()?.downcast_ref::<rustls::Error>()?;
+    let rustls::Error::InvalidCertificate(CertificateError::Other(other)) = tls else {
+        return None;
+    };
+    Some(match other.downcast_ref::<OcspError>()? {
+        OcspError::Missing => OcspStapleMissingSnafu.build(),
+        OcspError::Invalid { reason } => OcspStapleInvalidSnafu { reason: reason.clone() }.build(),
+    })
+}
+
+/// Webpki verification plus the stapled OCSP checks.
+pub(crate) struct StapleVerifier {
+    webpki: WebPkiVerifier,
+    /// DER of the trust roots, to find the issuer of a certificate that
+    /// was signed by a root directly.
+    roots: Vec<Vec<u8>>,
+}
+
+impl StapleVerifier {
+    pub(crate) fn new(root_store: RootCertStore, roots: Vec<Vec<u8>>) -> Self {
+        Self { webpki: WebPkiVerifier::new(root_store, None), roots }
+    }
+}
+
+impl ServerCertVerifier for StapleVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &Certificate,
+        intermediates: &[Certificate],
+        server_name: &ServerName,
+        scts: &mut dyn Iterator<Item = &[u8]>,
+        ocsp_response: &[u8],
+        now: SystemTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let verified = self.webpki.verify_server_cert(end_entity, intermediates, server_name, scts, ocsp_response, now)?;
+        let candidates = intermediates.iter().map(|c| c.0.as_slice()).chain(self.roots.iter().map(Vec::as_slice));
+        check_staple(ocsp_response, &end_entity.0, candidates, now)
+            .map_err(|e| rustls::Error::InvalidCertificate(CertificateError::Other(Arc::new(e))))?;
+        Ok(verified)
+    }
+}
+
+/// Check `response` for the certificate `end_entity`, whose issuer is one of
+/// `candidates` (DER).
+pub(crate) fn check_staple<'a>(
+    response: &[u8],
+    end_entity: &[u8],
+    candidates: impl IntoIterator<Item = &'a [u8]>,
+    now: SystemTime,
+) -> Result<(), OcspError> {
+    if response.is_empty() {
+        return Err(OcspError::Missing);
+    }
+    let cert = Cert::parse(end_entity).map_err(|e| invalid(format!("server certificate: {e}")))?;
+    let issuer = candidates
+        .into_iter()
+        .filter_map(|der| Cert::parse(der).ok())
+        .find(|c| c.subject == cert.issuer && c.verifies(&cert).is_ok())
+        .ok_or_else(|| invalid("the issuer of the server certificate is unknown"))?;
+    let basic = BasicResponse::parse(response).map_err(invalid)?;
+    let single = basic
+        .responses
+        .iter()
+        .find(|r| r.matches(&cert, &issuer))
+        .ok_or_else(|| invalid("no entry for the server certificate"))?;
+    let delegated;
+    let signer = if basic.responder.is(&issuer) {
+        &issuer
+    } else {
+        delegated = basic.delegated_signer(&issuer, now)?;
+        &delegated
+    };
+    signer
+        .key
+        .verify(basic.signature_algorithm, basic.tbs, basic.signature)
+        .map_err(|e| invalid(format!("bad signature: {e}")))?;
+    single.check_time(now)?;
+    match single.status {
+        Status::Good => Ok(()),
+        Status::Revoked => Err(invalid("the certificate is revoked")),
+        Status::Unknown => Err(invalid("the responder does not know the certificate")),
+    }
+}
+
+mod oid {
+    pub const OCSP_BASIC: &[u8] = &[0x2B, 0x06, 0x01, 0x05, 0x05, 0x07, 0x30, 0x01, 0x01];
+    pub const OCSP_SIGNING: &[u8] = &[0x2B, 0x06, 0x01, 0x05, 0x05, 0x07, 0x03, 0x09];
+    pub const EXT_KEY_USAGE: &[u8] = &[0x55, 0x1D, 0x25];
+    pub const SHA1: &[u8] = &[0x2B, 0x0E, 0x03, 0x02, 0x1A];
+    pub const SHA256: &[u8] = &[0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x01];
+    pub const EC_PUBLIC_KEY: &[u8] = &[0x2A, 0x86, 0x48, 0xCE, 0x3D, 0x02, 0x01];
+    pub const P256: &[u8] = &[0x2A, 0x86, 0x48, 0xCE, 0x3D, 0x03, 0x01, 0x07];
+    pub const P384: &[u8] = &[0x2B, 0x81, 0x04, 0x00, 0x22];
+    pub const RSA: &[u8] = &[0x2A, 0x86, 0x48, 0x86, 0xF7, 0x0D, 0x01, 0x01, 0x01];
+    pub const ECDSA_SHA256: &[u8] = &[0x2A, 0x86, 0x48, 0xCE, 0x3D, 0x04, 0x03, 0x02];
+    pub const ECDSA_SHA384: &[u8] = &[0x2A, 0x86, 0x48, 0xCE, 0x3D, 0x04, 0x03, 0x03];
+    pub const RSA_SHA256: &[u8] = &[0x2A, 0x86, 0x48, 0x86, 0xF7, 0x0D, 0x01, 0x01, 0x0B];
+    pub const RSA_SHA384: &[u8] = &[0x2A, 0x86, 0x48, 0x86, 0xF7, 0x0D, 0x01, 0x01, 0x0C];
+    pub const RSA_SHA512: &[u8] = &[0x2A, 0x86, 0x48, 0x86, 0xF7, 0x0D, 0x01, 0x01, 0x0D];
+}
+
+mod tag {
+    pub const BOOLEAN: u8 = 0x01;
+    pub const INTEGER: u8 = 0x02;
+    pub const BIT_STRING: u8 = 0x03;
+    pub const OCTET_STRING: u8 = 0x04;
+    pub const OID: u8 = 0x06;
+    pub const ENUMERATED: u8 = 0x0A;
+    pub const UTC_TIME: u8 = 0x17;
+    pub const GENERALIZED_TIME: u8 = 0x18;
+    pub const SEQUENCE: u8 = 0x30;
+    /// `[n]` with implicit tagging of a primitive type.
+    pub const fn context(n: u8) -> u8 {
+        0x80 | n
+    }
+    /// `[n]` around a constructed (or explicitly tagged) value.
+    pub const fn context_constructed(n: u8) -> u8 {
+        0xA0 | n
+    }
+}
+
+/// A cursor over DER: one tag byte, definite lengths of up to four bytes.
+#[derive(Clone, Copy)]
+struct Der<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Der<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    fn peek(&self, tag: u8) -> bool {
+        self.data.first() == Some(&tag)
+    }
+
+    /// The next element: its tag, its contents and its complete encoding.
+    fn any(&mut self) -> Result<(u8, &'a [u8], &'a [u8]), String> {
+        let [tag, first, rest @ ..] = self.data else {
+            return Err("truncated DER".to_string());
+        };
+        let (len, header) = match *first {
+            n if n < 0x80 => (n as usize, 2),
+            n @ 0x81..=0x84 => {
+                let count = (n & 0x7F) as usize;
+                let bytes = rest.get(..count).ok_or("truncated DER length")?;
+                (bytes.iter().fold(0usize, |acc, b| acc << 8 | *b as usize), 2 + count)
+            }
+            n => return Err(format!("unsupported DER length byte {n:#04x}")),
+        };
+        let end = header.checked_add(len).filter(|end| *end <= self.data.len()).ok_or("truncated DER value")?;
+        let (element, remaining) = self.data.split_at(end);
+        self.data = remaining;
+        Ok((*tag, &element[header..], element))
+    }
+
+    /// The contents of the next element, which must be tagged `tag`.
+    fn read(&mut self, tag: u8) -> Result<&'a [u8], String> {
+        self.read_element(tag).map(|(contents, _)| contents)
+    }
+
+    fn read_element(&mut self, expected: u8) -> Result<(&'a [u8], &'a [u8]), String> {
+        let (tag, contents, element) = self.any()?;
+        if tag != expected {
+            return Err(format!("expected DER tag {expected:#04x}, found {tag:#04x}"));
+        }
+        Ok((contents, element))
+    }
+
+    fn optional(&mut self, tag: u8) -> Result<Option<&'a [u8]>, String> {
+        if self.peek(tag) { self.read(tag).map(Some) } else { Ok(None) }
+    }
+
+    /// A BIT STRING without unused bits, as bytes.
+    fn bit_string(&mut self) -> Result<&'a [u8], String> {
+        match self.read(tag::BIT_STRING)? {
+            [0, bits @ ..] => Ok(bits),
+            _ => Err("BIT STRING with unused bits".to_string()),
+        }
+    }
+
+    /// The OID of an AlgorithmIdentifier and its parameters, if any.
+    fn algorithm(&mut self) -> Result<(&'a [u8], Option<&'a [u8]>), String> {
+        let mut alg = Der::new(self.read(tag::SEQUENCE)?);
+        let oid = alg.read(tag::OID)?;
+        let params = alg.optional(tag::OID)?;
+        Ok((oid, params))
+    }
+
+    fn time(&mut self) -> Result<SystemTime, String> {
+        let (tag, contents, _) = self.any()?;
+        let text = std::str::from_utf8(contents).map_err(|_| "time is not ASCII")?;
+        match tag {
+            tag::GENERALIZED_TIME => parse_time(text, false),
+            tag::UTC_TIME => parse_time(text, true),
+            _ => Err(format!("expected a time, found DER tag {tag:#04x}")),
+        }
+    }
+}
+
+/// `YYYYMMDDHHMMSS[.fff]Z`, or `YYMMDDHHMMSSZ` for UTCTime.
+fn parse_time(text: &str, utc_time: bool) -> Result<SystemTime, String> {
+    let bad = || format!("malformed time {text:?}");
+    let text = text.strip_suffix('Z').ok_or_else(bad)?;
+    let text = text.split_once('.').map_or(text, |(whole, _)| whole);
+    let (year, rest) = if utc_time {
+        let yy: i64 = text.get(..2).and_then(|s| s.parse().ok()).ok_or_else(bad)?;
+        (if yy < 50 { 2000 + yy } else { 1900 + yy }, &text[2..])
+    } else {
+        (text.get(..4).and_then(|s| s.parse().ok()).ok_or_else(bad)?, text.get(4..).ok_or_else(bad)?)
+    };
+    if rest.len() != 10 || !rest.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(bad());
+    }
+    let field = |i: usize| rest[i..i + 2].parse::<i64>().unwrap();
+    let (month, day, hour, minute, second) = (field(0), field(2), field(4), field(6), field(8));
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) || hour > 23 || minute > 59 || second > 60 {
+        return Err(bad());
+    }
+    let secs = days_from_civil(year, month, day) * 86_400 + hour * 3_600 + minute * 60 + second;
+    let secs = u64::try_from(secs).map_err(|_| bad())?;
+    Ok(UNIX_EPOCH + Duration::from_secs(secs))
+}
+
+/// Days since 1970-01-01 of a proleptic Gregorian date.
+pub(crate) fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// A subject public key ring can verify with.
+struct PublicKey<'a> {
+    algorithm: &'a [u8],
+    curve: Option<&'a [u8]>,
+    bits: &'a [u8],
+}
+
+impl PublicKey<'_> {
+    /// Verify `signature` over `message`, made with the AlgorithmIdentifier
+    /// OID `algorithm`.
+    fn verify(&self, algorithm: &[u8], message: &[u8], signature: &[u8]) -> Result<(), String> {
+        let scheme: &dyn signature::VerificationAlgorithm = match (self.algorithm, self.curve, algorithm) {
+            (oid::EC_PUBLIC_KEY, Some(oid::P256), oid::ECDSA_SHA256) => &signature::ECDSA_P256_SHA256_ASN1,
+            (oid::EC_PUBLIC_KEY, Some(oid::P256), oid::ECDSA_SHA384) => &signature::ECDSA_P256_SHA384_ASN1,
+            (oid::EC_PUBLIC_KEY, Some(oid::P384), oid::ECDSA_SHA256) => &signature::ECDSA_P384_SHA256_ASN1,
+            (oid::EC_PUBLIC_KEY, Some(oid::P384), oid::ECDSA_SHA384) => &signature::ECDSA_P384_SHA384_ASN1,
+            (oid::RSA, _, oid::RSA_SHA256) => &signature::RSA_PKCS1_2048_8192_SHA256,
+            (oid::RSA, _, oid::RSA_SHA384) => &signature::RSA_PKCS1_2048_8192_SHA384,
+            (oid::RSA, _, oid::RSA_SHA512) => &signature::RSA_PKCS1_2048_8192_SHA512,
+            _ => return Err("unsupported signature algorithm".to_string()),
+        };
+        signature::UnparsedPublicKey::new(scheme, self.bits)
+            .verify(message, signature)
+            .map_err(|_| "signature does not verify".to_string())
+    }
+}
+
+/// The parts of an X.509 certificate the checks look at.
+struct Cert<'a> {
+    tbs: &'a [u8],
+    signature_algorithm: &'a [u8],
+    signature: &'a [u8],
+    serial: &'a [u8],
+    /// Complete DER of the issuer and subject Names.
+    issuer: &'a [u8],
+    subject: &'a [u8],
+    not_before: SystemTime,
+    not_after: SystemTime,
+    key: PublicKey<'a>,
+    ocsp_signing: bool,
+}
+
+impl<'a> Cert<'a> {
+    fn parse(der: &'a [u8]) -> Result<Self, String> {
+        let mut cert = Der::new(Der::new(der).read(tag::SEQUENCE)?);
+        let (tbs_contents, tbs) = cert.read_element(tag::SEQUENCE)?;
+        let (signature_algorithm, _) = cert.algorithm()?;
+        let signature = cert.bit_string()?;
+
+        let mut fields = Der::new(tbs_contents);
+        fields.optional(tag::context_constructed(0))?;
+        let serial = fields.read(tag::INTEGER)?;
+        fields.read(tag::SEQUENCE)?;
+        let (_, issuer) = fields.read_element(tag::SEQUENCE)?;
+        let mut validity = Der::new(fields.read(tag::SEQUENCE)?);
+        let not_before = validity.time()?;
+        let not_after = validity.time()?;
+        let (_, subject) = fields.read_element(tag::SEQUENCE)?;
+        let mut spki = Der::new(fields.read(tag::SEQUENCE)?);
+        let (algorithm, curve) = spki.algorithm()?;
+        let bits = spki.bit_string()?;
+        fields.optional(tag::context(1))?;
+        fields.optional(tag::context(2))?;
+        let ocsp_signing = match fields.optional(tag::context_constructed(3))? {
+            Some(extensions) => has_ocsp_signing(extensions)?,
+            None => false,
+        };
+        Ok(Self {
+            tbs,
+            signature_algorithm,
+            signature,
+            serial,
+            issuer,
+            subject,
+            not_before,
+            not_after,
+            key: PublicKey { algorithm, curve, bits },
+            ocsp_signing,
+        })
+    }
+
+    /// Whether `cert` carries this certificate's signature.
+    fn verifies(&self, cert: &Cert) -> Result<(), String> {
+        self.key.verify(cert.signature_algorithm, cert.tbs, cert.signature)
+    }
+}
+
+/// Whether the `[3]` extensions list `id-kp-OCSPSigning` in extended key usage.
+fn has_ocsp_signing(extensions: &[u8]) -> Result<bool, String> {
+    let mut list = Der::new(Der::new(extensions).read(tag::SEQUENCE)?);
+    while !list.is_empty() {
+        let mut extension = Der::new(list.read(tag::SEQUENCE)?);
+        if extension.read(tag::OID)? != oid::EXT_KEY_USAGE {
+            continue;
+        }
+        extension.optional(tag::BOOLEAN)?;
+        let mut usages = Der::new(Der::new(extension.read(tag::OCTET_STRING)?).read(tag::SEQUENCE)?);
+        while !usages.is_empty() {
+            if usages.read(tag::OID)? == oid::OCSP_SIGNING {
+                return Ok(true);
+            }
+        }
+    }
+    Ok(false)
+}
+
+enum ResponderId<'a> {
+    /// Complete DER of the responder's Name.
+    ByName(&'a [u8]),
+    /// SHA-1 of the responder's public key bits.
+    ByKey(&'a [u8]),
+}
+
+impl ResponderId<'_> {
+    fn is(&self, cert: &Cert) -> bool {
+        match self {
+            ResponderId::ByName(name) => *name == cert.subject,
+            ResponderId::ByKey(hash) => digest::digest(&digest::SHA1_FOR_LEGACY_USE_ONLY, cert.key.bits).as_ref() == *hash,
+        }
+    }
+}
+
+struct BasicResponse<'a> {
+    tbs: &'a [u8],
+    signature_algorithm: &'a [u8],
+    signature: &'a [u8],
+    responder: ResponderId<'a>,
+    responses: Vec<SingleResponse<'a>>,
+    certs: Vec<&'a [u8]>,
+}
+
+impl<'a> BasicResponse<'a> {
+    fn parse(der: &'a [u8]) -> Result<Self, String> {
+        let mut response = Der::new(Der::new(der).read(tag::SEQUENCE)?);
+        match response.read(tag::ENUMERATED)? {
+            [0] => {}
+            status => return Err(format!("responder status {}", status.last().copied().unwrap_or_default())),
+        }
+        let mut response_bytes = Der::new(Der::new(response.read(tag::context_constructed(0))?).read(tag::SEQUENCE)?);
+        if response_bytes.read(tag::OID)? != oid::OCSP_BASIC {
+            return Err("not a basic OCSP response".to_string());
+        }
+        let mut basic = Der::new(Der::new(response_bytes.read(tag::OCTET_STRING)?).read(tag::SEQUENCE)?);
+        let (tbs_contents, tbs) = basic.read_element(tag::SEQUENCE)?;
+        let (signature_algorithm, _) = basic.algorithm()?;
+        let signature = basic.bit_string()?;
+        let mut certs = Vec::new();
+        if let Some(list) = basic.optional(tag::context_constructed(0))? {
+            let mut list = Der::new(Der::new(list).read(tag::SEQUENCE)?);
+            while !list.is_empty() {
+                certs.push(list.read_element(tag::SEQUENCE)?.1);
+            }
+        }
+
+        let mut data = Der::new(tbs_contents);
+        data.optional(tag::context_constructed(0))?;
+        let responder = match data.any()? {
+            (0xA1, name, _) => ResponderId::ByName(Der::new(name).read_element(tag::SEQUENCE)?.1),
+            (0xA2, key, _) => ResponderId::ByKey(Der::new(key).read(tag::OCTET_STRING)?),
+            (tag, ..) => return Err(format!("bad responder id tag {tag:#04x}")),
+        };
+        data.read(tag::GENERALIZED_TIME)?;
+        let mut list = Der::new(data.read(tag::SEQUENCE)?);
+        let mut responses = Vec::new();
+        while !list.is_empty() {
+            responses.push(SingleResponse::parse(list.read(tag::SEQUENCE)?)?);
+        }
+        Ok(Self { tbs, signature_algorithm, signature, responder, responses, certs })
+    }
+
+    /// A responder certificate in the response that the issuer signed for
+    /// OCSP, valid at `now`, which signed the response on the issuer's behalf.
+    fn delegated_signer(&self, issuer: &Cert, now: SystemTime) -> Result<Cert<'a>, OcspError> {
+        self.certs
+            .iter()
+            .filter_map(|der| Cert::parse(der).ok())
+            .find(|cert| {
+                self.responder.is(cert)
+                    && cert.issuer == issuer.subject
+                    && cert.ocsp_signing
+                    && issuer.verifies(cert).is_ok()
+                    && cert.not_before <= now + CLOCK_SKEW
+                    && now <= cert.not_after + CLOCK_SKEW
+            })
+            .ok_or_else(|| invalid("not signed by the issuer or an authorized responder"))
+    }
+}
+
+enum Status {
+    Good,
+    Revoked,
+    Unknown,
+}
+
+struct SingleResponse<'a> {
+    hash_algorithm: &'a [u8],
+    issuer_name_hash: &'a [u8],
+    issuer_key_hash: &'a [u8],
+    serial: &'a [u8],
+    status: Status,
+    this_update: SystemTime,
+    next_update: Option<SystemTime>,
+}
+
+impl<'a> SingleResponse<'a> {
+    fn parse(der: &'a [u8]) -> Result<Self, String> {
+        let mut single = Der::new(der);
+        let mut cert_id = Der::new(single.read(tag::SEQUENCE)?);
+        let (hash_algorithm, _) = cert_id.algorithm()?;
+        let issuer_name_hash = cert_id.read(tag::OCTET_STRING)?;
+        let issuer_key_hash = cert_id.read(tag::OCTET_STRING)?;
+        let serial = cert_id.read(tag::INTEGER)?;
+        let status = match single.any()?.0 {
+            0x80 => Status::Good,
+            0xA1 => Status::Revoked,
+            0x82 => Status::Unknown,
+            tag => return Err(format!("bad certificate status tag {tag:#04x}")),
+        };
+        let this_update = single.time()?;
+        let next_update = match single.optional(tag::context_constructed(0))? {
+            Some(time) => Some(Der::new(time).time()?),
+            None => None,
+        };
+        Ok(Self { hash_algorithm, issuer_name_hash, issuer_key_hash, serial, status, this_update, next_update })
+    }
+
+    fn matches(&self, cert: &Cert, issuer: &Cert) -> bool {
+        let algorithm = match self.hash_algorithm {
+            oid::SHA1 => &digest::SHA1_FOR_LEGACY_USE_ONLY,
+            oid::SHA256 => &digest::SHA256,
+            _ => return false,
+        };
+        self.serial == cert.serial
+            && digest::digest(algorithm, issuer.subject).as_ref() == self.issuer_name_hash
+            && digest::digest(algorithm, issuer.key.bits).as_ref() == self.issuer_key_hash
+    }
+
+    fn check_time(&self, now: SystemTime) -> Result<(), OcspError> {
+        if self.this_update > now + CLOCK_SKEW {
+            return Err(invalid("thisUpdate is in the future"));
+        }
+        let expires = match self.next_update {
+            Some(next_update) => next_update + CLOCK_SKEW,
+            None => self.this_update + MAX_AGE_WITHOUT_NEXT_UPDATE,
+        };
+        if now > expires {
+            return Err(invalid("the response has expired"));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CA: &[u8] = include_bytes!("../tests/fixtures/ocsp_ca.pem");
+    const SERVER_CERT: &[u8] = include_bytes!("../tests/fixtures/ocsp_server_cert.pem");
+    const GOOD: &[u8] = include_bytes!("../tests/fixtures/ocsp_good.der");
+    const REVOKED: &[u8] = include_bytes!("../tests/fixtures/ocsp_revoked.der");
+
+    fn der(pem: &[u8]) -> Vec<u8> {
+        rustls_pemfile::certs(&mut &pem[..]).next().unwrap().unwrap().to_vec()
+    }
+
+    fn this_update(response: &[u8]) -> SystemTime {
+        BasicResponse::parse(response).unwrap().responses[0].this_update
+    }
+
+    fn check(response: &[u8], now: SystemTime) -> Result<(), OcspError> {
+        let ca = der(CA);
+        check_staple(response, &der(SERVER_CERT), [ca.as_slice()], now)
+    }
+
+    #[test]
+    fn test_good_staple_is_accepted_until_next_update() {
+        let issued = this_update(GOOD);
+        assert_eq!(check(GOOD, issued + Duration::from_secs(86_400)), Ok(()));
+        // Issued for 3650 days.
+        let expired = check(GOOD, issued + Duration::from_secs(3651 * 86_400)).unwrap_err();
+        assert_eq!(expired, invalid("the response has expired"));
+        let early = check(GOOD, issued - 2 * CLOCK_SKEW).unwrap_err();
+        assert_eq!(early, invalid("thisUpdate is in the future"));
+    }
+
+    #[test]
+    fn test_revoked_missing_and_forged_staples_are_rejected() {
+        let now = this_update(REVOKED) + Duration::from_secs(60);
+        assert_eq!(check(REVOKED, now).unwrap_err(), invalid("the certificate is revoked"));
+        assert_eq!(check(&[], now).unwrap_err(), OcspError::Missing);
+        let mut forged = GOOD.to_vec();
+        let last = forged.len() - 1;
+        forged[last] ^= 1;
+        assert!(matches!(check(&forged, now), Err(OcspError::Invalid { reason }) if reason.starts_with("bad signature")));
+        assert!(matches!(check(&GOOD[..20], now), Err(OcspError::Invalid { .. })));
+        // The staple is for the server certificate, not for its issuer.
+        let ca = der(CA);
+        let wrong_cert = check_staple(GOOD, &ca, [ca.as_slice()], now).unwrap_err();
+        assert_eq!(wrong_cert, invalid("no entry for the server certificate"));
+    }
+
+    #[test]
+    fn test_parse_time() {
+        assert_eq!(parse_time("19700101000000Z", false), Ok(UNIX_EPOCH));
+        assert_eq!(parse_time("20000301000000.5Z", false), Ok(UNIX_EPOCH + Duration::from_secs(951_868_800)));
+        assert_eq!(parse_time("491231235959Z", true), Ok(UNIX_EPOCH + Duration::from_secs(2_524_607_999)));
+        assert!(parse_time("20001301000000Z", false).is_err());
+        assert!(parse_time("2000010100000Z", false).is_err());
+    }
+}
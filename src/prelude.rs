@@ -0,0 +1,28 @@
+//! The everyday types in one import:
+//!
+//! ```
+//! use zjhttpc::prelude::*;
+//!
+//! let client = ZJHttpClient::new();
+//! let req = Request::new(methods::GET, "https://example.com/").unwrap();
+//! assert_eq!(client.effective_config(&req).total_timeout, Some(std::time::Duration::from_secs(300)));
+//! ```
+//!
+//! [`Result`](crate::Result) is left out so the glob import does not shadow
+//! `std::result::Result`.
+//!
+//! `tests/public_api.txt` lists every public item of the crate, so a change to
+//! the public surface shows up as a failing `cargo test` until the listing is
+//! regenerated (see `tests/public_api.rs`).
+
+pub use crate::body::Body;
+pub use crate::client::{ZJHttpClient, ZJHttpClientBuilder};
+pub use crate::clock::Clock;
+pub use crate::connector::Connector;
+pub use crate::cookie::{Cookie, CookieJar};
+pub use crate::error::{ErrorCategory, RequestPhase, ZjhttpcError};
+pub use crate::header::HeaderMap;
+pub use crate::methods;
+pub use crate::requestx::Request;
+pub use crate::resolver::Resolver;
+pub use crate::response::Response;
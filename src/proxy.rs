@@ -10,16 +10,18 @@ use async_tls::TlsConnector;
 use rustls::{Certificate, ClientConfig};
 use rustls_native_certs::load_native_certs;
 use rustls_pemfile;
-use tracing::{debug, error};
+use tracing::{debug, warn};
 use url::Url;
 
 use crate::error::{
     CertificateSnafu, ConnectionSnafu, ConnectionTimeoutSnafu, DnsSnafu, InvalidUrlSnafu,
-    NoPortSnafu, ProxySnafu, Result, TlsSnafu,
+    NoPortSnafu, ProxyRefusedSnafu, ProxySnafu, Result, TlsSnafu,
 };
 use snafu::prelude::*;
+use crate::log_target::{CONNECT, TLS};
+use crate::methods;
 use crate::misc::TrustStorePem;
-use crate::stream::BoxedStream;
+use crate::stream::{BoxedStream, ChainRead, SliceRead};
 
 #[derive(Clone, Debug)]
 pub struct HttpsProxyOption {
@@ -82,6 +84,14 @@ impl HttpsProxyOption {
         })
     }
 
+    /// `Proxy-Authorization` value for the proxy's credentials, if any.
+    pub fn authorization(&self) -> Option<String> {
+        self.cred.as_ref().map(|cred| {
+            let auth = format!("{}:{}", cred.username, cred.password);
+            format!("Basic {}", base64_simd::STANDARD.encode_to_string(auth.as_bytes()))
+        })
+    }
+
     pub fn from_url(url: Url) -> Result<Self> {
         let host = url
             .host_str()
@@ -120,158 +130,175 @@ impl HttpsProxyOption {
     }
 }
 
+/// Proxies named by the conventional environment variables, see
+/// `ZJHttpClientBuilder::proxy_from_env`.
+///
+/// `http_proxy` (or `HTTP_PROXY`) applies to `http` URLs and `https_proxy`
+/// (or `HTTPS_PROXY`) to `https` ones; the lower-case spelling wins.
+/// `no_proxy` (or `NO_PROXY`) is a comma-separated list of hosts that are
+/// reached directly: an entry matches the host itself and its subdomains
+/// (`example.com` and `.example.com` both cover `api.example.com`), IP
+/// addresses match exactly, and `*` turns the proxy off for every host.
+#[derive(Clone, Debug, Default)]
+pub struct ProxyEnv {
+    pub http: Option<HttpsProxyOption>,
+    pub https: Option<HttpsProxyOption>,
+    pub no_proxy: Vec<String>,
+}
+
+impl ProxyEnv {
+    /// Read the process environment. A variable holding an unusable proxy
+    /// URL is logged and ignored.
+    pub fn from_env() -> Self {
+        Self::from_vars(|name| std::env::var(name).ok())
+    }
+
+    /// Like [`from_env`](Self::from_env), looking variables up with `var`.
+    pub fn from_vars(var: impl Fn(&str) -> Option<String>) -> Self {
+        let lookup = |name: &str| {
+            var(name)
+                .or_else(|| var(&name.to_ascii_uppercase()))
+                .filter(|value| !value.trim().is_empty())
+        };
+        let proxy = |name: &str| {
+            let value = lookup(name)?;
+            HttpsProxyOption::new(value.trim())
+                .inspect_err(|err| warn!(target: CONNECT, variable = name, %err, "ignoring proxy from the environment"))
+                .ok()
+        };
+        ProxyEnv {
+            http: proxy("http_proxy"),
+            https: proxy("https_proxy"),
+            no_proxy: lookup("no_proxy")
+                .map(|value| {
+                    value
+                        .split(',')
+                        .map(|entry| entry.trim().trim_start_matches("*.").trim_start_matches('.').to_ascii_lowercase())
+                        .filter(|entry| !entry.is_empty())
+                        .collect()
+                })
+                .unwrap_or_default(),
+        }
+    }
+
+    /// The proxy for `url`, `None` when it goes direct.
+    pub fn proxy_for(&self, url: &Url) -> Option<&HttpsProxyOption> {
+        let proxy = match url.scheme() {
+            "http" => self.http.as_ref(),
+            "https" => self.https.as_ref(),
+            _ => None,
+        }?;
+        let host = url.host_str()?.trim_start_matches('[').trim_end_matches(']').to_ascii_lowercase();
+        let bypass = self.no_proxy.iter().any(|entry| {
+            let entry = entry.trim_start_matches('[').trim_end_matches(']');
+            entry == "*"
+                || host == entry
+                || (host.len() > entry.len() && host.ends_with(entry) && host[..host.len() - entry.len()].ends_with('.'))
+        });
+        (!bypass).then_some(proxy)
+    }
+}
+
 #[derive(Clone)]
 pub struct ProxyConnector {
     proxy: HttpsProxyOption,
-    tls_config: Arc<ClientConfig>,
+    /// Roots for the TLS connection to an `https://` proxy; `None` for an
+    /// `http://` one, which needs no certificates loaded.
+    tls_config: Option<Arc<ClientConfig>>,
 }
 
 impl ProxyConnector {
     pub fn new(proxy: HttpsProxyOption) -> Result<Self> {
-        let tls_config = create_proxy_tls_config()?;
-        Ok(Self {
-            proxy,
-            tls_config: Arc::new(tls_config),
-        })
+        let tls_config = match proxy.url.scheme() {
+            "https" => Some(Arc::new(create_proxy_tls_config()?)),
+            _ => None,
+        };
+        Ok(Self { proxy, tls_config })
     }
 
     pub fn new_with_trust_store(
         proxy: HttpsProxyOption,
         trust_store: &Option<TrustStorePem>,
     ) -> Result<Self> {
-        let tls_config = create_proxy_tls_config_with_trust_store(trust_store)?;
-        Ok(Self {
-            proxy,
-            tls_config: Arc::new(tls_config),
-        })
+        let tls_config = match proxy.url.scheme() {
+            "https" => Some(Arc::new(create_proxy_tls_config_with_trust_store(trust_store)?)),
+            _ => None,
+        };
+        Ok(Self { proxy, tls_config })
     }
 
+    /// Open a tunnel to `target_host:target_port` with `CONNECT`.
     pub async fn connect(&self, target_host: &str, target_port: u16, connect_timeout: Duration) -> Result<BoxedStream> {
-        let proxy_addr = self.proxy.addr;
-
-        if self.proxy.url.scheme() == "https" {
-            self.connect_https_proxy(proxy_addr, target_host, target_port, connect_timeout)
-                .await
-        } else {
-            self.connect_http_proxy(proxy_addr, target_host, target_port, connect_timeout)
-                .await
-        }
-    }
+        let mut stream = self.open(connect_timeout).await?;
 
-    async fn connect_http_proxy(
-        &self,
-        proxy_addr: SocketAddr,
-        target_host: &str,
-        target_port: u16,
-        connect_timeout: Duration,
-    ) -> Result<BoxedStream> {
-        // Create TCP stream with connect timeout
-        let mut tcp_stream = match async_std::future::timeout(connect_timeout, TcpStream::connect(&proxy_addr)).await {
-            Ok(Ok(stream)) => stream,
-            Ok(Err(e)) => return Err(ConnectionSnafu { message: format!("HTTP proxy connection failed: {e}") }.build()),
-            Err(_) => return Err(ConnectionTimeoutSnafu { duration: connect_timeout }.build()),
-        };
-
-        let connect_request = format!(
+        let mut connect_request = format!(
             "CONNECT {}:{} HTTP/1.1\r\nHost: {}:{}\r\nProxy-Connection: Keep-Alive\r\n",
             target_host, target_port, target_host, target_port
         );
+        if let Some(auth) = self.proxy.authorization() {
+            connect_request.push_str(&format!("Proxy-Authorization: {auth}\r\n"));
+        }
+        connect_request.push_str("\r\n");
 
-        let connect_request = if let Some(cred) = &self.proxy.cred {
-            let auth = format!("{}:{}", cred.username, cred.password);
-            let encoded_auth = base64_simd::STANDARD.encode_to_string(auth.as_bytes());
-            format!(
-                "{}Proxy-Authorization: Basic {}\r\n\r\n",
-                connect_request, encoded_auth
-            )
-        } else {
-            format!("{}\r\n", connect_request)
-        };
-
-        tcp_stream
+        stream
             .write_all(connect_request.as_bytes())
             .await
             .map_err(|e| ProxySnafu { message: format!("failed to send CONNECT request to proxy: {e}") }.build())?;
-        tcp_stream
+        stream
             .flush()
             .await
             .map_err(|e| ProxySnafu { message: format!("failed to flush proxy connection: {e}") }.build())?;
 
-        read_connect_response(&mut tcp_stream).await?;
+        let early = read_connect_response(&mut stream, &format!("{target_host}:{target_port}")).await?;
 
         debug!(
-            "HTTP proxy CONNECT successful to {}:{}",
-            target_host, target_port
+            target: CONNECT,
+            "{} proxy CONNECT successful to {}:{}",
+            self.kind(), target_host, target_port
         );
-        Ok(Box::new(tcp_stream))
+        Ok(with_early_bytes(stream, &early))
     }
 
-    async fn connect_https_proxy(
-        &self,
-        proxy_addr: SocketAddr,
-        target_host: &str,
-        target_port: u16,
-        connect_timeout: Duration,
-    ) -> Result<BoxedStream> {
-        let tls_connector: TlsConnector = self.tls_config.clone().into();
-
-        // Create TCP stream with connect timeout
+    /// Open a connection to the proxy itself: TCP, with TLS on top for an
+    /// `https://` proxy. Plain `http` requests are forwarded over it in
+    /// absolute form; [`connect`](Self::connect) tunnels through it.
+    pub async fn open(&self, connect_timeout: Duration) -> Result<BoxedStream> {
+        let proxy_addr = self.proxy.addr;
         let tcp_stream = match async_std::future::timeout(connect_timeout, TcpStream::connect(&proxy_addr)).await {
             Ok(Ok(stream)) => stream,
-            Ok(Err(e)) => return Err(ConnectionSnafu { message: format!("HTTPS proxy connection failed: {e}") }.build()),
+            Ok(Err(e)) => {
+                return Err(ConnectionSnafu { message: format!("{} proxy connection failed: {e}", self.kind()) }.build());
+            }
             Err(_) => return Err(ConnectionTimeoutSnafu { duration: connect_timeout }.build()),
         };
+        let Some(tls_config) = &self.tls_config else {
+            return Ok(Box::new(tcp_stream));
+        };
 
         let proxy_host = self
             .proxy
             .url
             .host_str()
             .ok_or_else(|| ProxySnafu { message: "proxy URL must have a host".to_string() }.build())?;
-
+        let tls_connector: TlsConnector = tls_config.clone().into();
         let tls_stream = tls_connector
             .connect(proxy_host, tcp_stream)
             .await
             .map_err(|e| TlsSnafu { message: format!("failed to establish TLS connection to HTTPS proxy: {e}") }.build())?;
+        Ok(Box::new(tls_stream))
+    }
 
-        let connect_request = format!(
-            "CONNECT {}:{} HTTP/1.1\r\nHost: {}:{}\r\nProxy-Connection: Keep-Alive\r\n",
-            target_host, target_port, target_host, target_port
-        );
-
-        let connect_request = if let Some(cred) = &self.proxy.cred {
-            let auth = format!("{}:{}", cred.username, cred.password);
-            let encoded_auth = base64_simd::STANDARD.encode_to_string(auth.as_bytes());
-            format!(
-                "{}Proxy-Authorization: Basic {}\r\n\r\n",
-                connect_request, encoded_auth
-            )
-        } else {
-            format!("{}\r\n", connect_request)
-        };
-
-        let mut stream = Box::new(tls_stream);
-        stream
-            .write_all(connect_request.as_bytes())
-            .await
-            .map_err(|e| ProxySnafu { message: format!("failed to send CONNECT request to HTTPS proxy: {e}") }.build())?;
-        stream
-            .flush()
-            .await
-            .map_err(|e| ProxySnafu { message: format!("failed to flush proxy connection: {e}") }.build())?;
-
-        read_connect_response(&mut stream).await?;
-
-        debug!(
-            "HTTPS proxy CONNECT successful to {}:{}",
-            target_host, target_port
-        );
-        Ok(stream)
+    fn kind(&self) -> &'static str {
+        if self.tls_config.is_some() { "HTTPS" } else { "HTTP" }
     }
 }
 
 /// Read the proxy CONNECT response fully by looping until \\r\\n\\r\\n is found.
-/// Returns Ok(()) if the response status is 200, or Err with the response text otherwise.
-async fn read_connect_response<S>(stream: &mut S) -> Result<()>
+/// Returns the tunnelled bytes that arrived with the response if the status is
+/// 2xx (a server that speaks first, like SMTP, may already have sent some).
+/// Another status fails with `ProxyRefused`, a response that is not HTTP with
+/// `Proxy`.
+async fn read_connect_response<S>(stream: &mut S, target: &str) -> Result<Vec<u8>>
 where
     S: async_std::io::Read + Unpin,
 {
@@ -288,16 +315,35 @@ where
         }
         filled += n;
 
-        if filled >= 4 && buf[..filled].windows(4).any(|w| w == b"\r\n\r\n") {
-            if !buf.starts_with(b"HTTP/1.1 200") && !buf.starts_with(b"HTTP/1.0 200") {
-                let text = String::from_utf8_lossy(&buf[..filled]);
-                return Err(ProxySnafu { message: format!("proxy CONNECT failed: {}", text.trim()) }.build());
-            }
-            return Ok(());
+        if let Some(end) = buf[..filled].windows(4).position(|w| w == b"\r\n\r\n") {
+            let line_end = buf[..end].windows(2).position(|w| w == b"\r\n").unwrap_or(end);
+            let status_line = String::from_utf8_lossy(&buf[..line_end]).into_owned();
+            let status = status_line
+                .strip_prefix("HTTP/1.1 ")
+                .or_else(|| status_line.strip_prefix("HTTP/1.0 "))
+                .and_then(|rest| rest.get(..3))
+                .and_then(|code| code.parse::<u16>().ok());
+            return match status {
+                // Whatever its headers say, a 2xx to CONNECT has no body:
+                // everything after the head belongs to the tunnel.
+                Some(status) if status >= 200 && !methods::response_has_body(methods::CONNECT, status) => {
+                    Ok(buf[end + 4..filled].to_vec())
+                }
+                Some(status) => Err(ProxyRefusedSnafu { status, target, status_line }.build()),
+                None => Err(ProxySnafu { message: format!("proxy CONNECT failed: {}", status_line.trim()) }.build()),
+            };
         }
     }
 }
 
+/// Put `early` bytes read past the CONNECT response back in front of `stream`.
+fn with_early_bytes(stream: BoxedStream, early: &[u8]) -> BoxedStream {
+    if early.is_empty() {
+        return stream;
+    }
+    Box::new(ChainRead::new(SliceRead::new(early), stream))
+}
+
 fn create_proxy_tls_config() -> Result<ClientConfig> {
     let mut root_store = rustls::RootCertStore::empty();
     let cert_result = load_native_certs();
@@ -338,7 +384,7 @@ fn create_proxy_tls_config_with_trust_store(
                 .filter_map(|re| match re {
                     Ok(c) => Some(c),
                     Err(err) => {
-                        error!(?err, "failed to parse cert");
+                        warn!(target: TLS, ?err, "failed to parse cert");
                         None
                     }
                 })
@@ -352,7 +398,7 @@ fn create_proxy_tls_config_with_trust_store(
                 .filter_map(|re| match re {
                     Ok(c) => Some(c),
                     Err(err) => {
-                        error!(?err, "failed to parse cert");
+                        warn!(target: TLS, ?err, "failed to parse cert");
                         None
                     }
                 })
@@ -486,4 +532,43 @@ mod tests {
             assert!(elapsed < Duration::from_secs(2)); // Should timeout within ~1 second
         })
     }
+
+    #[test]
+    fn test_proxy_env_picks_by_scheme_and_honours_no_proxy() {
+        let vars = |name: &str| {
+            match name {
+                "http_proxy" => Some("http://127.0.0.1:3128"),
+                "HTTP_PROXY" => Some("http://127.0.0.2:3128"),
+                "HTTPS_PROXY" => Some("http://u:p@127.0.0.3:8080"),
+                "NO_PROXY" => Some("localhost, .internal.example, 10.0.0.7,[::1]"),
+                _ => None,
+            }
+            .map(str::to_string)
+        };
+        let env = ProxyEnv::from_vars(vars);
+        let proxy_for = |url: &str| env.proxy_for(&Url::parse(url).unwrap()).map(|p| p.addr.to_string());
+
+        assert_eq!(proxy_for("http://example.com/").as_deref(), Some("127.0.0.1:3128"), "lower case wins");
+        assert_eq!(proxy_for("https://example.com/").as_deref(), Some("127.0.0.3:8080"));
+        assert_eq!(proxy_for("http://localhost:8080/"), None);
+        assert_eq!(proxy_for("http://api.internal.example/"), None);
+        assert_eq!(proxy_for("http://internal.example/"), None);
+        assert_eq!(proxy_for("http://notinternal.example/").as_deref(), Some("127.0.0.1:3128"));
+        assert_eq!(proxy_for("http://10.0.0.7/"), None);
+        assert_eq!(proxy_for("http://[::1]:80/"), None);
+        assert_eq!(proxy_for("ftp://example.com/"), None);
+        assert_eq!(env.https.unwrap().authorization().as_deref(), Some("Basic dTpw"));
+    }
+
+    #[test]
+    fn test_proxy_env_star_and_bad_values() {
+        let env = ProxyEnv::from_vars(|name| match name {
+            "http_proxy" => Some("socks5://127.0.0.1:1080".to_string()),
+            "https_proxy" => Some("http://127.0.0.1:3128".to_string()),
+            "no_proxy" => Some("*".to_string()),
+            _ => None,
+        });
+        assert!(env.http.is_none(), "unsupported proxy scheme is ignored");
+        assert!(env.proxy_for(&Url::parse("https://example.com/").unwrap()).is_none());
+    }
 }
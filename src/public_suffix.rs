@@ -0,0 +1,52 @@
+//! Public suffixes (domains under which anyone can register a name, such as
+//! `com` or `co.uk`), for the cookie `Domain` check of RFC 6265 §5.3 step 5.
+//!
+//! Every single label counts as one. Of the multi-label suffixes of the
+//! Public Suffix List (<https://publicsuffix.org>) only the widely used ones
+//! below are known; a domain under a suffix missing here is treated as
+//! registrable.
+
+/// Multi-label public suffixes, lowercase, sorted.
+const MULTI_LABEL: &[&str] = &[
+    "ac.cn", "ac.il", "ac.in", "ac.jp", "ac.kr", "ac.nz", "ac.th", "ac.uk", "ac.za", "ad.jp", //
+    "appspot.com", "asn.au", "azurewebsites.net", "blogspot.com", "cloudfront.net", "co.id", //
+    "co.il", "co.in", "co.jp", "co.kr", "co.nz", "co.th", "co.uk", "co.za", "com.ar", "com.au", //
+    "com.br", "com.cn", "com.es", "com.hk", "com.mx", "com.my", "com.pl", "com.sg", "com.tr", //
+    "com.tw", "com.ua", "com.vn", "ed.jp", "edu.au", "edu.br", "edu.cn", "edu.hk", "edu.in", //
+    "edu.mx", "edu.my", "edu.sg", "edu.tr", "edu.tw", "firebaseapp.com", "github.io", //
+    "githubusercontent.com", "gitlab.io", "go.id", "go.jp", "go.kr", "go.th", "gob.ar", //
+    "gob.mx", "gov.au", "gov.br", "gov.cn", "gov.hk", "gov.il", "gov.in", "gov.my", "gov.sg", //
+    "gov.tr", "gov.tw", "gov.uk", "gov.za", "govt.nz", "gr.jp", "herokuapp.com", "id.au", //
+    "idv.hk", "idv.tw", "lg.jp", "ltd.uk", "me.uk", "ne.jp", "ne.kr", "net.ar", "net.au", //
+    "net.br", "net.cn", "net.hk", "net.in", "net.mx", "net.my", "net.nz", "net.pl", "net.sg", //
+    "net.tr", "net.tw", "net.ua", "net.uk", "net.vn", "net.za", "netlify.app", "nhs.uk", //
+    "nom.es", "or.id", "or.jp", "or.kr", "or.th", "org.ar", "org.au", "org.br", "org.cn", //
+    "org.es", "org.hk", "org.il", "org.in", "org.mx", "org.my", "org.nz", "org.pl", "org.sg", //
+    "org.tr", "org.tw", "org.ua", "org.uk", "org.vn", "org.za", "pages.dev", "plc.uk", //
+    "police.uk", "s3.amazonaws.com", "sch.uk", "vercel.app", "web.app", "workers.dev",
+];
+
+/// Whether `domain` (lowercase, without a leading dot) is a public suffix.
+pub(crate) fn is_public_suffix(domain: &str) -> bool {
+    !domain.contains('.') || MULTI_LABEL.binary_search(&domain).is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_list_is_sorted() {
+        assert!(MULTI_LABEL.windows(2).all(|w| w[0] < w[1]));
+    }
+
+    #[test]
+    fn test_is_public_suffix() {
+        for domain in ["com", "uk", "localhost", "co.uk", "com.au", "github.io"] {
+            assert!(is_public_suffix(domain), "{domain}");
+        }
+        for domain in ["example.com", "evil.co.uk", "bbc.co.uk", "user.github.io", "uk.com"] {
+            assert!(!is_public_suffix(domain), "{domain}");
+        }
+    }
+}
@@ -0,0 +1,58 @@
+//! Which redirects `send()` may follow, on top of
+//! [`max_redirects`](crate::client::ClientInner::max_redirects).
+
+use std::sync::Arc;
+
+use url::Url;
+
+/// Vets the target of every redirect hop before anything is resolved or
+/// sent for it. Set with `ZJHttpClientBuilder::set_redirect_policy`.
+///
+/// Besides the policy, each hop is checked like a request of its own: only
+/// `http` and `https`, no plain `http` on an
+/// [`https_only`](crate::client::ClientInner::https_only) client, and the
+/// [`AddressFilter`](crate::resolver::AddressFilter) on the hop's resolved
+/// addresses. A hop that fails any of these ends `send()` with
+/// [`ZjhttpcError::RedirectBlocked`](crate::ZjhttpcError::RedirectBlocked).
+///
+/// ```
+/// use zjhttpc::client::ZJHttpClient;
+/// use zjhttpc::redirect::RedirectPolicy;
+///
+/// let client = ZJHttpClient::builder()
+///     .set_max_redirects(5)
+///     .set_redirect_policy(
+///         RedirectPolicy::new().validate_target(Box::new(|url| url.host_str() != Some("169.254.169.254"))),
+///     )
+///     .build()
+///     .unwrap();
+/// ```
+#[derive(Clone, Default)]
+pub struct RedirectPolicy {
+    validate_target: Option<TargetCheck>,
+}
+
+type TargetCheck = Arc<dyn Fn(&Url) -> bool + Send + Sync>;
+
+impl RedirectPolicy {
+    /// Follow every redirect the other checks allow.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Follow a redirect only when `f` returns true for its target URL.
+    pub fn validate_target(mut self, f: Box<dyn Fn(&Url) -> bool + Send + Sync>) -> Self {
+        self.validate_target = Some(Arc::from(f));
+        self
+    }
+
+    pub fn allows(&self, target: &Url) -> bool {
+        self.validate_target.as_ref().is_none_or(|f| f(target))
+    }
+}
+
+impl std::fmt::Debug for RedirectPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RedirectPolicy").field("validate_target", &self.validate_target.is_some()).finish()
+    }
+}
@@ -1,57 +1,179 @@
 use async_std::fs::File;
 use futures::io::BufReader;
-use hashbrown::HashMap;
 use indexmap::IndexSet;
 use serde::Serialize;
 use std::borrow::Cow;
+use std::net::IpAddr;
 use std::time::Duration;
 use url::Url;
 
 use crate::{
     body::{Body, BodyForm, BodyMultipartForm},
     cookie::Cookie,
-    error::{NoHostSnafu, Result},
+    extensions::Extensions,
+    header::HeaderMap,
+    error::{InvalidHeaderSnafu, InvalidRequestSnafu, JsonSerializeSnafu, NoHostSnafu, Result},
     misc::TrustStorePem,
     proxy::HttpsProxyOption,
+    response::EarlyHints,
+    spill::ReplaySpill,
+    throttle::Rate,
 };
 use snafu::OptionExt;
 
 pub struct Request {
     pub method: &'static str,
     pub url: Url,
-    pub headers: HashMap<String, IndexSet<String>>,
-    pub expect_continue: bool,
+    pub headers: HeaderMap,
+    /// Opt-in for [`crate::client::ZJHttpClient::send_head_duplex`].
+    pub duplex: bool,
+    /// Ignore the response's framing headers, see [`Request::set_raw_body_framing`].
+    pub raw_body_framing: bool,
+    /// See [`Request::set_sent_request_capture`].
+    pub sent_request_capture: SentRequestCapture,
+    /// How `send()` rewrites the query, see [`Request::canonicalize_query`].
+    pub query_canonicalization: CanonicalizeMode,
+    /// Ports tried in order when connecting to the URL's port fails, see
+    /// [`Request::set_port_fallbacks`].
+    pub port_fallbacks: Vec<u16>,
+    /// Skip the connection pool for this send, see
+    /// [`Request::set_fresh_connection`].
+    pub fresh_connection: bool,
+    /// Send the caller's headers only, see [`Request::passthrough_mode`].
+    pub passthrough: bool,
     pub content_type: Option<Cow<'static, str>>,
     pub basic_auth: Option<(String, String)>,
     pub content_length: u64,
     pub send_header_timeout: Option<Duration>,
     pub read_header_timeout: Option<Duration>,
     pub read_body_timeout: Option<Duration>,
+    pub first_byte_timeout: Option<Duration>,
+    pub header_complete_timeout: Option<Duration>,
     pub connect_timeout: Option<Duration>,
+    /// Overrides `ZJHttpClient::global_total_timeout`.
+    pub total_timeout: Option<Duration>,
+    /// Overrides `ZJHttpClient::global_max_total_attempt_duration`.
+    pub max_total_attempt_duration: Option<Duration>,
     pub body: Body,
     pub use_chunked: bool,
     pub trust_store_pem: Option<TrustStorePem>,
     pub proxy: Option<HttpsProxyOption>,
+    /// Overrides `ZJHttpClient::global_upload_rate`.
+    pub upload_rate: Option<Rate>,
+    /// Overrides `ZJHttpClient::global_download_rate`.
+    pub download_rate: Option<Rate>,
+    pub early_hints_handler: Option<EarlyHintsHandler>,
+    /// Body type the caller expects back, see [`Request::expect_json`].
+    pub expected_body: Option<ExpectedBody>,
+    /// Sniff the body when the `Content-Type` does not match `expected_body`,
+    /// see [`Request::set_lenient_content_type`].
+    pub lenient_content_type: bool,
+    /// Caller data that travels with the request and is copied onto its response.
+    pub extensions: Extensions,
+    /// Overrides `ZJHttpClient::global_max_body_bytes`.
+    pub max_body_bytes: Option<u64>,
+    /// SHA-256 the decoded response body must have, see [`Request::set_expected_sha256`].
+    pub expected_sha256: Option<[u8; 32]>,
+    /// Disk copy of a streamed body for resending, see [`Request::set_replay_spill`].
+    pub replay_spill: Option<ReplaySpill>,
 }
 
+/// The kind of response body a request expects, recorded by
+/// [`Request::expect_json`], [`Request::expect_text`] and [`Request::expect_bytes`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExpectedBody {
+    Json,
+    Text,
+    Bytes,
+}
+
+impl ExpectedBody {
+    /// Value of the `Accept` header sent for this expectation.
+    pub fn accept(self) -> &'static str {
+        match self {
+            ExpectedBody::Json => "application/json",
+            ExpectedBody::Text => "text/*",
+            ExpectedBody::Bytes => "*/*",
+        }
+    }
+
+    /// Whether a response `Content-Type` satisfies the expectation. Parameters
+    /// such as `charset` are ignored; `+json` suffixes count as JSON.
+    pub fn matches(self, content_type: &str) -> bool {
+        let media_type = content_type.split(';').next().unwrap_or("").trim().to_ascii_lowercase();
+        match self {
+            ExpectedBody::Json => media_type == "application/json" || media_type.ends_with("+json"),
+            ExpectedBody::Text => media_type.starts_with("text/"),
+            ExpectedBody::Bytes => true,
+        }
+    }
+}
+
+/// Whether `send()` records the head it wrote on the response, see
+/// [`Request::set_sent_request_capture`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SentRequestCapture {
+    /// Record the head with credential header values redacted.
+    #[default]
+    Redacted,
+    /// Record the head exactly as written, credentials included.
+    Unredacted,
+    /// Record nothing.
+    Off,
+}
+
+/// The `Expect` header of a request, see [`Request::set_expectation`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum Expectation {
+    #[default]
+    None,
+    /// `Expect: 100-continue`: wait for the server's go-ahead before
+    /// sending the body.
+    Continue,
+    /// Any other expectation, sent verbatim.
+    Custom(String),
+}
+
+/// How `send()` writes the query string, see [`Request::canonicalize_query`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CanonicalizeMode {
+    /// Write the query exactly as the URL holds it.
+    #[default]
+    None,
+    /// Keep the pairs in the order they were built, each key and value
+    /// re-escaped with [`crate::encoding::STRICT`].
+    Preserve,
+    /// Like `Preserve`, then stable-sorted by escaped key, so repeated keys
+    /// keep their relative order. The form request signing (SigV4) expects.
+    SortedByKey,
+}
+
+/// Callback for 103 Early Hints, see [`Request::set_early_hints_handler`].
+pub type EarlyHintsHandler = Box<dyn Fn(EarlyHints) + Send + Sync>;
+
 const LIB_VERSION: &str = env!("CARGO_PKG_VERSION");
 
+fn default_user_agent() -> String {
+    format!("zjhttpc/{LIB_VERSION} (powered by Jinhui)")
+}
+
 impl Request {
-    #[must_use]
     pub fn new(method: &'static str, url: impl AsRef<str>) -> Result<Self> {
-        let url: Url = url.as_ref().parse()?;
+        Self::from_url(method, url.as_ref().parse()?)
+    }
+
+    /// Like [`Request::new`] for a URL that is already parsed; it is sent as
+    /// is, without a round trip through its string form.
+    pub fn from_url(method: &'static str, url: Url) -> Result<Self> {
         let host = url.host_str().with_context(|| NoHostSnafu)?;
-        let mut headers = HashMap::new();
-        headers.insert("host".to_owned(), IndexSet::from([host.to_owned()]));
-        headers.insert(
-            "user-agent".to_owned(),
-            IndexSet::from([format!("zjhttpc/{LIB_VERSION} (powered by Jinhui)")]),
-        );
+        let mut headers = HeaderMap::new();
+        headers.insert_unchecked("host", host);
+        headers.insert_unchecked("user-agent", default_user_agent());
         Ok(Request {
             method,
             url,
             headers,
-            expect_continue: false,
+            duplex: false,
             content_type: None,
             basic_auth: None,
             body: Body::None,
@@ -60,39 +182,198 @@ impl Request {
             send_header_timeout: None,
             read_header_timeout: None,
             read_body_timeout: None,
+            first_byte_timeout: None,
+            header_complete_timeout: None,
             connect_timeout: None,
+            total_timeout: None,
+            max_total_attempt_duration: None,
             trust_store_pem: None,
             proxy: None,
+            upload_rate: None,
+            download_rate: None,
+            early_hints_handler: None,
+            expected_body: None,
+            lenient_content_type: false,
+            extensions: Extensions::new(),
+            max_body_bytes: None,
+            expected_sha256: None,
+            raw_body_framing: false,
+            sent_request_capture: SentRequestCapture::default(),
+            query_canonicalization: CanonicalizeMode::default(),
+            port_fallbacks: Vec::new(),
+            fresh_connection: false,
+            passthrough: false,
+            replay_spill: None,
         })
     }
 
+    /// Build a request from a captured HTTP/1.x message: the request line,
+    /// the header fields and, after the blank line, the body. Useful for
+    /// replaying traffic through the client's pooling and TLS.
+    ///
+    /// The URL is the origin-form request-target (`/path?query`) joined to
+    /// the `Host` header, over `http`. `target_host_override` replaces the
+    /// `Host` header as the destination, and may carry a scheme
+    /// (`"https://staging.example.com:8443"`); the `Host` header sent is then
+    /// the override's host. An absolute-form target (`http://host/path`)
+    /// brings its own scheme and host, unless overridden.
+    ///
+    /// Header fields keep their order and spelling, the default
+    /// `User-Agent` is not added. `Content-Length` is left to the client and
+    /// must match the bytes after the head. The hop-by-hop fields
+    /// `Connection`, `Keep-Alive`, `Proxy-Connection`, `Transfer-Encoding`
+    /// and `Upgrade` are refused with [`ZjhttpcError::InvalidHeader`]: the
+    /// client manages the connection and the body framing itself, so a
+    /// chunked capture has to be de-chunked first.
+    ///
+    /// [`ZjhttpcError::InvalidHeader`]: crate::ZjhttpcError::InvalidHeader
+    pub fn parse_from_head(bytes: &[u8], target_host_override: Option<&str>) -> Result<Self> {
+        let (req, body, declared) = Self::parse_message_head(bytes, target_host_override)?;
+        check_declared_length(declared, body.len() as u64)?;
+        if declared.is_none() && body.is_empty() {
+            return Ok(req);
+        }
+        Ok(req.set_body_slice(body))
+    }
+
+    /// Like [`parse_from_head`](Self::parse_from_head) for a message whose
+    /// body is streamed from `body` instead of following the head in memory.
+    /// `head` must end at the blank line.
+    pub fn parse_from_head_with_body<R>(
+        head: &[u8],
+        target_host_override: Option<&str>,
+        body: R,
+        length: u64,
+    ) -> Result<Self>
+    where
+        R: async_std::io::Read + Unpin + Send + Sync + 'static,
+    {
+        let (req, rest, declared) = Self::parse_message_head(head, target_host_override)?;
+        if !rest.is_empty() {
+            return Err(InvalidRequestSnafu {
+                message: format!("{} bytes follow the head; pass the whole body as the reader", rest.len()),
+            }
+            .build());
+        }
+        check_declared_length(declared, length)?;
+        Ok(req.set_body_stream(body, length))
+    }
+
+    /// The request described by the head at the start of `bytes`, the bytes
+    /// after the head, and the `Content-Length` the head declared.
+    fn parse_message_head<'a>(
+        bytes: &'a [u8],
+        target_host_override: Option<&str>,
+    ) -> Result<(Self, &'a [u8], Option<u64>)> {
+        let invalid = |message: String| InvalidRequestSnafu { message }.build();
+        let head_len = bytes
+            .windows(4)
+            .position(|w| w == b"\r\n\r\n")
+            .map(|pos| pos + 4)
+            .ok_or_else(|| invalid("no blank line ends the message head".to_string()))?;
+        let head = std::str::from_utf8(&bytes[..head_len])
+            .map_err(|e| invalid(format!("message head is not valid UTF-8: {e}")))?;
+        // The head ends with CRLF CRLF, so the split always succeeds.
+        let (line, fields) = head.split_once("\r\n").unwrap_or((head, ""));
+        let mut parts = line.split(' ');
+        let (Some(method), Some(target), Some(version), None) = (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            return Err(invalid(format!("malformed request line {line:?}")));
+        };
+        if version != "HTTP/1.1" && version != "HTTP/1.0" {
+            return Err(invalid(format!("unsupported HTTP version {version:?}")));
+        }
+        let method = crate::methods::from_token(method).ok_or_else(|| invalid(format!("unsupported method {method:?}")))?;
+        let fields = crate::client::parse_headers(fields).map_err(invalid)?;
+        if let Some((name, _)) = fields.iter().find(|(name, _)| HOP_BY_HOP.iter().any(|h| name.eq_ignore_ascii_case(h))) {
+            return Err(InvalidHeaderSnafu {
+                message: format!("{name} is a hop-by-hop header the client sets itself; remove it from the message"),
+            }
+            .build());
+        }
+
+        let host_header = fields.iter().find(|(name, _)| name.eq_ignore_ascii_case("host")).map(|(_, value)| *value);
+        let origin = match (target_host_override, host_header) {
+            (Some(host), _) | (None, Some(host)) if host.contains("://") => host.trim_end_matches('/').to_string(),
+            (Some(host), _) | (None, Some(host)) => format!("http://{host}"),
+            (None, None) => String::new(),
+        };
+        let url = if target.starts_with('/') {
+            if origin.is_empty() {
+                return Err(invalid("origin-form request-target without a Host header or override".to_string()));
+            }
+            Url::parse(&format!("{origin}{target}"))?
+        } else if target.contains("://") {
+            let absolute = Url::parse(target)?;
+            match target_host_override {
+                Some(_) => Url::parse(&format!(
+                    "{origin}{}",
+                    &absolute[url::Position::BeforePath..url::Position::AfterQuery]
+                ))?,
+                None => absolute,
+            }
+        } else {
+            return Err(invalid(format!("request-target {target:?} is neither origin-form nor absolute-form")));
+        };
+
+        let mut req = Request::from_url(method, url)?;
+        req.headers = HeaderMap::new();
+        if target_host_override.is_some() || host_header.is_none() {
+            let host = req.url.host_str().unwrap_or_default().to_string();
+            req.headers.insert("Host", host)?;
+        }
+        let mut declared = None;
+        for (name, value) in fields {
+            if name.eq_ignore_ascii_case("content-length") {
+                let len = value.trim().parse::<u64>().map_err(|_| invalid(format!("invalid Content-Length {value:?}")))?;
+                if declared.is_some_and(|d| d != len) {
+                    return Err(invalid("conflicting Content-Length headers".to_string()));
+                }
+                declared = Some(len);
+            } else if !(name.eq_ignore_ascii_case("host") && target_host_override.is_some()) {
+                req.headers.append(name, value)?;
+            }
+        }
+        Ok((req, &bytes[head_len..], declared))
+    }
+
+    pub fn url(&self) -> &Url {
+        &self.url
+    }
+
+    /// Change the URL in place before sending. The `Host` header was set
+    /// from the URL the request was built with; replace it with
+    /// `set_header` when changing the host.
+    pub fn url_mut(&mut self) -> &mut Url {
+        &mut self.url
+    }
+
     pub fn method(mut self, method: &'static str) -> Self {
         self.method = method;
         self
     }
 
+    // The header setters stay infallible for chaining; names and values are
+    // validated when the request is sent (see `HeaderMap::validate`).
+
     pub fn add_header(mut self, key: impl AsRef<str>, value: impl AsRef<str>) -> Self {
-        if let Some(v) = self.headers.get_mut(key.as_ref()) {
-            v.insert(value.as_ref().to_owned());
-        } else {
-            self.headers.insert(
-                key.as_ref().to_owned(),
-                IndexSet::from([value.as_ref().to_owned()]),
-            );
-        }
+        self.headers.append_unchecked(key, value);
         self
     }
 
     pub fn set_header(mut self, key: impl AsRef<str>, value: impl AsRef<str>) -> Self {
-        self.headers.insert(
-            key.as_ref().to_owned(),
-            IndexSet::from([value.as_ref().to_owned()]),
-        );
+        self.headers.insert_unchecked(key, value);
         self
     }
 
-    pub fn set_headers(mut self, headers: HashMap<String, IndexSet<String>>) -> Self {
-        self.headers.extend(headers);
+    /// Replace the values of every header present in `headers`.
+    pub fn set_headers(mut self, headers: impl Into<HeaderMap>) -> Self {
+        for (key, values) in headers.into().iter() {
+            self.headers.remove(key);
+            for value in values {
+                self.headers.append_unchecked(key, value);
+            }
+        }
         self
     }
 
@@ -100,8 +381,9 @@ impl Request {
         mut self,
         headers: std::collections::HashMap<String, String>,
     ) -> Self {
-        self.headers
-            .extend(headers.into_iter().map(|(k, v)| (k, IndexSet::from([v]))));
+        for (key, value) in headers {
+            self.headers.insert_unchecked(key, value);
+        }
         self
     }
 
@@ -128,37 +410,273 @@ impl Request {
     /// ```
     pub fn set_cookie(mut self, cookies: &[Cookie]) -> Self {
         let cookie_header = Cookie::format_for_request_cookie_header(cookies);
-        self.headers.insert(
-            crate::header::COOKIE.to_owned(),
-            IndexSet::from([cookie_header]),
-        );
+        self.headers.insert_unchecked(crate::header::COOKIE, cookie_header);
         self
     }
 
+    /// Replace the query with `queries` serialized by serde_qs, escaped like
+    /// [`crate::encoding::encode_form_pairs`].
     pub fn set_queries_serde(mut self, queries: &impl Serialize) -> Result<Self> {
-        let s = serde_qs::to_string(queries)?;
-        self.url.set_query(Some(s.as_str()));
+        let query = form_encode_serde(queries)?;
+        self.url.set_query(Some(query.as_str()));
         Ok(self)
     }
 
+    /// Append `key=value` to the query, escaped like
+    /// [`crate::encoding::encode_form_pairs`].
     pub fn add_query(mut self, key: &str, value: &str) -> Self {
-        self.url.query_pairs_mut().append_pair(key, value);
+        let mut query = self.url.query().unwrap_or_default().to_string();
+        crate::encoding::encode_form_pairs([(key, value)], &mut query);
+        self.url.set_query(Some(query.as_str()));
         self
     }
 
+    /// First value of `key`, whatever its case; names differing only in case
+    /// are one header (see [`HeaderMap`]).
     pub fn header_one(&self, key: impl AsRef<str>) -> Option<&String> {
-        self.headers.get(key.as_ref()).and_then(|set| set.first())
+        self.headers.get_all(key).and_then(|set| set.first())
     }
 
+    /// Every value of `key`, whatever its case, in insertion order.
     pub fn header_all(&self, key: impl AsRef<str>) -> Option<&IndexSet<String>> {
-        self.headers.get(key.as_ref())
+        self.headers.get_all(key)
     }
 
-    pub fn put_expect_continue(mut self) -> Self {
-        self.expect_continue = true;
+    /// Record that an intermediary forwards this request for
+    /// `original_client`, who asked for `host` over `proto`: appends a
+    /// `Forwarded` element (RFC 7239), the legacy `X-Forwarded-For`,
+    /// `X-Forwarded-Proto` and `X-Forwarded-Host` values and `Via: 1.1
+    /// zjhttpc`. Values already there are kept, the new ones are
+    /// comma-joined after them, so every header stays one line listing the
+    /// hops in order. Usually paired with [`strip_hop_by_hop`](Self::strip_hop_by_hop).
+    pub fn as_forwarded(mut self, original_client: IpAddr, proto: &str, host: &str) -> Self {
+        use crate::forwarded::{X_FORWARDED_FOR, X_FORWARDED_HOST, X_FORWARDED_PROTO, append, element};
+        append(&mut self.headers, crate::header::FORWARDED, &element(original_client, proto, host));
+        append(&mut self.headers, X_FORWARDED_FOR, &original_client.to_string());
+        append(&mut self.headers, X_FORWARDED_PROTO, proto);
+        append(&mut self.headers, X_FORWARDED_HOST, host);
+        append(&mut self.headers, crate::header::VIA, crate::forwarded::VIA_ENTRY);
         self
     }
 
+    /// Remove the fields that only apply to the connection the request came
+    /// in on: every field `Connection` names (except `Host`), then
+    /// `Connection`, `Keep-Alive`, `Proxy-Connection`, `TE`, `Trailer`,
+    /// `Transfer-Encoding` and `Upgrade`. The client sets its own connection
+    /// and framing headers when it sends the request on.
+    pub fn strip_hop_by_hop(mut self) -> Self {
+        crate::forwarded::strip_hop_by_hop(&mut self.headers);
+        self
+    }
+
+    /// Shorthand for `set_expectation(Expectation::Continue)`.
+    pub fn put_expect_continue(self) -> Self {
+        self.set_expectation(Expectation::Continue)
+    }
+
+    /// Set or remove the `Expect` header. The header map is the only place
+    /// the expectation is kept, so a later `set_header("Expect", ..)`
+    /// replaces it; see [`expectation`](Self::expectation).
+    pub fn set_expectation(mut self, expectation: Expectation) -> Self {
+        match expectation {
+            Expectation::None => {
+                self.headers.remove(crate::header::EXPECT);
+            }
+            Expectation::Continue => self.headers.insert_unchecked(crate::header::EXPECT, "100-continue"),
+            Expectation::Custom(value) => self.headers.insert_unchecked(crate::header::EXPECT, value),
+        }
+        self
+    }
+
+    /// The expectation the `Expect` header carries. Only
+    /// [`Expectation::Continue`] makes `send()` wait for `100 Continue`
+    /// before writing the body; a custom one is sent and the body follows
+    /// right away.
+    pub fn expectation(&self) -> Expectation {
+        match self.headers.get(crate::header::EXPECT) {
+            None => Expectation::None,
+            Some(value) if value.trim().eq_ignore_ascii_case("100-continue") => Expectation::Continue,
+            Some(value) => Expectation::Custom(value.to_string()),
+        }
+    }
+
+    /// Allow this request to be sent in duplex mode, where the body is written
+    /// while the response is already being read. See
+    /// [`ZJHttpClient::send_head_duplex`](crate::client::ZJHttpClient::send_head_duplex).
+    pub fn put_duplex(mut self) -> Self {
+        self.duplex = true;
+        self
+    }
+
+    /// Read the response body as raw connection bytes up to EOF, ignoring
+    /// `Transfer-Encoding` and `Content-Length`. For servers that announce a
+    /// framing they don't actually use. The connection is never reused.
+    pub fn set_raw_body_framing(mut self, raw: bool) -> Self {
+        self.raw_body_framing = raw;
+        self
+    }
+
+    /// When the TCP connect to the URL's port is refused or times out, try
+    /// these ports on the same address, in order. Only the connect is
+    /// retried, never a request that has written anything. `Response::addr`
+    /// tells which port was used, and the connection is pooled under it.
+    /// Not applied to requests sent through a proxy.
+    pub fn set_port_fallbacks(mut self, ports: &[u16]) -> Self {
+        self.port_fallbacks = ports.to_vec();
+        self
+    }
+
+    /// Resolve the host and dial a new connection for this send even when the
+    /// DNS cache holds an answer and the pool an idle connection, e.g. right
+    /// after a DNS failover. The new answer replaces the cached one and the
+    /// new connection is returned to the pool afterwards as usual; use
+    /// [`crate::client::ZJHttpClient::evict_host`] to also close the idle
+    /// connections left from before.
+    pub fn set_fresh_connection(mut self, fresh: bool) -> Self {
+        self.fresh_connection = fresh;
+        self
+    }
+
+    /// Send exactly the headers the caller set, in the order they were
+    /// first set and with the name casing used then, for forwarding a
+    /// client's request unchanged.
+    ///
+    /// Turning it on drops the `Host` and `User-Agent` headers `Request::new`
+    /// added, unless they were changed since; turning it off again does not
+    /// bring them back. While on, `send()` adds only what HTTP/1.1 needs:
+    ///
+    /// - `Host`, first, when the caller set none (with the port if the URL
+    ///   has a non-default one);
+    /// - `Content-Length` or `Transfer-Encoding: chunked` for the body, when
+    ///   the caller set neither. A caller `Content-Length` must equal the
+    ///   body length, and a caller `Transfer-Encoding: chunked` requires a
+    ///   chunked body (`use_chunked`), or `send()` fails with
+    ///   [`InvalidRequest`](crate::ZjhttpcError::InvalidRequest).
+    ///
+    /// No `Connection: keep-alive` and no `Content-Type` from the body
+    /// setters are added. The request line is still normalized: the path
+    /// and query are percent-encoded to the request-target character set.
+    /// Header values go through [`HeaderMap`], so all values of one name are
+    /// written on consecutive lines and a value repeated for the same name
+    /// is written once. Options that set headers on purpose (`set_basic_auth`,
+    /// `expect_json`, `put_expect_continue`, cookies) still do.
+    /// [`Response::sent_request`](crate::response::Response::sent_request)
+    /// shows the head as written.
+    pub fn passthrough_mode(mut self, on: bool) -> Self {
+        if on && !self.passthrough {
+            if self.headers.get(crate::header::USER_AGENT) == Some(default_user_agent().as_str()) {
+                self.headers.remove(crate::header::USER_AGENT);
+            }
+            let default_host = self.url.host_str().unwrap_or_default();
+            if self.headers.iter().any(|(name, values)| {
+                name == "host" && values.len() == 1 && values.first().map(String::as_str) == Some(default_host)
+            }) {
+                self.headers.remove("host");
+            }
+        }
+        self.passthrough = on;
+        self
+    }
+
+    /// Ask for the given byte ranges, as `(first, last)` with `last` inclusive
+    /// or `None` for "to the end": `[(0, Some(99)), (200, None)]` sends
+    /// `Range: bytes=0-99,200-`. Read the answer with
+    /// [`Response::byteranges`](crate::response::Response::byteranges).
+    pub fn set_ranges(self, ranges: &[(u64, Option<u64>)]) -> Self {
+        let spec = ranges
+            .iter()
+            .map(|(first, last)| match last {
+                Some(last) => format!("{first}-{last}"),
+                None => format!("{first}-"),
+            })
+            .collect::<Vec<_>>()
+            .join(",");
+        self.set_header(crate::header::RANGE, format!("bytes={spec}"))
+    }
+
+    /// Rewrite the query when the request is sent: every pair is decoded and
+    /// re-escaped with [`crate::encoding::STRICT`] (`%2F` stays `%2F`,
+    /// `%7E` becomes `~`, `+` becomes `%20`), a pair without `=` gets an
+    /// empty value, and [`CanonicalizeMode::SortedByKey`] also sorts the
+    /// pairs. Applied at send time, so queries added afterwards are covered;
+    /// [`wire_query`](Self::wire_query) returns the result for signing.
+    pub fn canonicalize_query(mut self, mode: CanonicalizeMode) -> Self {
+        self.query_canonicalization = mode;
+        self
+    }
+
+    /// The query as `send()` will write it, after
+    /// [`canonicalize_query`](Self::canonicalize_query). `None` when the URL
+    /// has no query.
+    pub fn wire_query(&self) -> Option<Cow<'_, str>> {
+        let query = self.url.query()?;
+        Some(match self.query_canonicalization {
+            CanonicalizeMode::None => Cow::Borrowed(query),
+            CanonicalizeMode::Preserve => Cow::Owned(crate::encoding::canonical_query(query, false)),
+            CanonicalizeMode::SortedByKey => Cow::Owned(crate::encoding::canonical_query(query, true)),
+        })
+    }
+
+    /// How the head written by `send()` is kept in `Response::sent_request`.
+    /// Redacted by default; `Off` skips the copy on hot paths.
+    pub fn set_sent_request_capture(mut self, capture: SentRequestCapture) -> Self {
+        self.sent_request_capture = capture;
+        self
+    }
+
+    /// Call `handler` for every 103 Early Hints response, as soon as its head is
+    /// parsed and before the final response arrives. It runs on the task reading
+    /// the response, so it must not block: spawn a task for any follow-up fetch.
+    pub fn set_early_hints_handler(mut self, handler: EarlyHintsHandler) -> Self {
+        self.early_hints_handler = Some(handler);
+        self
+    }
+
+    /// Attach a value to the request, replacing any earlier value of the same
+    /// type. It is not sent; the response carries it in `Response::extensions`.
+    pub fn insert_ext<T: Send + Sync + 'static>(mut self, value: T) -> Self {
+        self.extensions.insert(value);
+        self
+    }
+
+    pub fn get_ext<T: Send + Sync + 'static>(&self) -> Option<&T> {
+        self.extensions.get()
+    }
+
+    /// Expect a JSON response: sends `Accept: application/json`, and
+    /// [`Response::body_json`](crate::response::Response::body_json) then fails with
+    /// `UnexpectedContentType` when the server answers with another media type.
+    pub fn expect_json(self) -> Self {
+        self.expect(ExpectedBody::Json)
+    }
+
+    /// Expect a `text/*` response, see [`expect_json`](Self::expect_json).
+    pub fn expect_text(self) -> Self {
+        self.expect(ExpectedBody::Text)
+    }
+
+    /// Expect a response of any media type; sends `Accept: */*`.
+    pub fn expect_bytes(self) -> Self {
+        self.expect(ExpectedBody::Bytes)
+    }
+
+    /// When the response `Content-Type` does not match the expectation set by
+    /// [`expect_json`](Self::expect_json) or [`expect_text`](Self::expect_text),
+    /// sniff the first body bytes before failing: a `text/plain` body that
+    /// starts with `{` still passes `expect_json`. Off by default.
+    pub fn set_lenient_content_type(mut self, lenient: bool) -> Self {
+        self.lenient_content_type = lenient;
+        self
+    }
+
+    fn expect(mut self, expected: ExpectedBody) -> Self {
+        self.headers.insert_unchecked(crate::header::ACCEPT, expected.accept());
+        self.expected_body = Some(expected);
+        self
+    }
+
+    /// Checked like a header value when the request is sent: control
+    /// characters fail with `InvalidHeader`.
     pub fn set_content_type(mut self, content_type: impl Into<Cow<'static, str>>) -> Self {
         self.content_type = Some(content_type.into());
         self
@@ -169,13 +687,68 @@ impl Request {
         self
     }
 
+    /// Use HTTP Basic authentication. The `Authorization` header lives in
+    /// [`Request::headers`]; an explicitly set `Authorization` header (through
+    /// [`set_header`](Self::set_header) or [`set_authorization`](Self::set_authorization))
+    /// always takes precedence, whichever was called first.
     pub fn set_basic_auth(mut self, username: impl AsRef<str>, password: impl AsRef<str>) -> Self {
+        let previous = self.basic_auth_header();
         self.basic_auth = Some((username.as_ref().to_owned(), password.as_ref().to_owned()));
+        let current = self.headers.get(crate::header::AUTHORIZATION).map(str::to_owned);
+        if (current.is_none() || current == previous)
+            && let Some(value) = self.basic_auth_header()
+        {
+            self.headers.insert_unchecked(crate::header::AUTHORIZATION, value);
+        }
+        self
+    }
+
+    /// Set `Authorization: <scheme> <credentials>` for any scheme (`Bearer`,
+    /// `Digest`, ...). Replaces any previous value; the scheme must be an RFC
+    /// 7230 token, which `send()` checks along with the other headers.
+    pub fn set_authorization(mut self, scheme: impl AsRef<str>, credentials: impl AsRef<str>) -> Self {
+        let value = format!("{} {}", scheme.as_ref(), credentials.as_ref());
+        self.headers.insert_unchecked(crate::header::AUTHORIZATION, value.trim_end());
+        self
+    }
+
+    fn basic_auth_header(&self) -> Option<String> {
+        self.basic_auth.as_ref().map(|(username, password)| {
+            let encoded = base64_simd::STANDARD.encode_to_string(format!("{username}:{password}"));
+            format!("Basic {encoded}")
+        })
+    }
+
+    /// Turn `basic_auth` into the `Authorization` header unless one is already
+    /// set. `send()` calls this, so assigning the `basic_auth` field directly works too.
+    pub(crate) fn apply_basic_auth(&mut self) {
+        if self.headers.contains_key(crate::header::AUTHORIZATION) {
+            return;
+        }
+        if let Some(value) = self.basic_auth_header() {
+            self.headers.insert_unchecked(crate::header::AUTHORIZATION, value);
+        }
+    }
+
+    /// Send a present but empty body: `Content-Length: 0` goes out even for
+    /// GET, HEAD, DELETE and OPTIONS, which otherwise omit it when no body
+    /// was set.
+    pub fn set_body_empty(mut self) -> Self {
+        self.content_length = 0;
+        self.use_chunked = false;
+        self.body = Body::Bytes(Vec::new());
         self
     }
 
+    /// Whether a body was set, even an empty one, or a length announced for
+    /// one written later through the split API.
+    pub(crate) fn has_body(&self) -> bool {
+        !matches!(self.body, Body::None) || self.content_length > 0
+    }
+
     pub fn set_body_string(mut self, body: impl AsRef<str>) -> Self {
         self.content_length = body.as_ref().len() as u64;
+        self.use_chunked = false;
         self.body = Body::Str(body.as_ref().to_owned());
         self
     }
@@ -185,16 +758,70 @@ impl Request {
         R: async_std::io::Read + Unpin + Send + Sync + 'static,
     {
         self.content_length = length;
+        self.use_chunked = false;
         self.body = Body::Stream(Box::new(body));
         self
     }
 
+    /// Stream a body whose length is not known up front, e.g. the output of
+    /// a compressor. It is sent with `Transfer-Encoding: chunked`, one chunk
+    /// per read from `body`, and ends with the zero-length chunk when `body`
+    /// reaches EOF. With [`put_expect_continue`](Self::put_expect_continue)
+    /// the first chunk goes out after the `100 Continue`.
+    ///
+    /// The body can only be read once, so a send that fails after the head
+    /// was written is not retried on a fresh connection, unless
+    /// [`set_replay_spill`](Self::set_replay_spill) keeps a copy.
+    pub fn set_body_stream_chunked<R>(mut self, body: R) -> Self
+    where
+        R: async_std::io::Read + Unpin + Send + Sync + 'static,
+    {
+        self.content_length = 0;
+        self.use_chunked = true;
+        self.body = Body::StreamChunked(Box::new(body));
+        self
+    }
+
+    /// Copy a streamed body into a temporary file in `dir` while it is first
+    /// sent, so that `send()` can send it again where it otherwise can't: on
+    /// a fresh connection after a pooled one failed before answering, and to
+    /// a 307 or 308 redirect. Bodies over `max_bytes` are not copied.
+    ///
+    /// If the file can't be written the body is simply not replayable; the
+    /// upload goes on. The file is removed when `send()` returns. Other body
+    /// types are replayable anyway and ignore this.
+    pub fn set_replay_spill(mut self, dir: impl Into<std::path::PathBuf>, max_bytes: u64) -> Self {
+        self.replay_spill = Some(ReplaySpill::new(dir, max_bytes));
+        self
+    }
+
+    /// Whether the body can be sent again: anything but a stream, or a
+    /// stream whose replay spill holds all of it.
+    pub(crate) fn body_is_replayable(&self) -> bool {
+        !matches!(self.body, Body::Stream(_) | Body::StreamChunked(_))
+            || self.replay_spill.as_ref().is_some_and(ReplaySpill::is_complete)
+    }
+
+    /// Point a stream body back at the start of its spilled copy, keeping
+    /// its framing. Other bodies are left alone.
+    pub(crate) fn rewind_body(&mut self) -> Result<()> {
+        let Some(file) = self.replay_spill.as_ref().map(ReplaySpill::reader).transpose()?.flatten() else {
+            return Ok(());
+        };
+        match &mut self.body {
+            Body::Stream(stream) | Body::StreamChunked(stream) => *stream = Box::new(file),
+            _ => {}
+        }
+        Ok(())
+    }
+
     pub async fn set_body_file(mut self, file_path: impl AsRef<std::path::Path>) -> Result<Self> {
         let p = async_std::path::PathBuf::from(file_path.as_ref());
         let len = p.metadata().await?.len();
         self.content_length = len;
         let file = File::open(p).await?;
         let buf_reader = BufReader::new(file);
+        self.use_chunked = false;
         self.body = Body::Stream(Box::new(buf_reader));
         Ok(self)
     }
@@ -202,6 +829,7 @@ impl Request {
     pub fn set_body_slice(mut self, body: impl AsRef<[u8]>) -> Self {
         let bytes = body.as_ref();
         self.content_length = bytes.len() as u64;
+        self.use_chunked = false;
         self.body = Body::Bytes(bytes.to_vec());
         self
     }
@@ -212,7 +840,9 @@ impl Request {
     /// "application/x-www-form-urlencoded", overwriting any previous value.
     ///
     /// # Arguments
-    /// * `form` - A BodyForm instance containing the form fields
+    /// * `form` - A BodyForm instance containing the form fields; any
+    ///   iterator of pairs, such as a `HashMap<String, String>`, collects
+    ///   into one
     ///
     /// # Examples
     /// ```
@@ -241,11 +871,53 @@ impl Request {
 
         // Set the body
         self.content_length = serialized.len() as u64;
+        self.use_chunked = false;
         self.body = Body::Str(serialized);
 
         self
     }
 
+    /// Set an application/x-www-form-urlencoded body from any `Serialize`
+    /// value, encoded with serde_qs like [`set_queries_serde`](Self::set_queries_serde).
+    ///
+    /// The value should be a struct or a map. `None` fields are left out;
+    /// sequences and nested structs use serde_qs's bracket syntax
+    /// (`tags[0]=a&tags[1]=b`, `user[name]=alice`), which servers decode
+    /// with serde_qs or another bracket-aware parser. A bare value such as a
+    /// number or a string has no field name and fails with `QuerySerialize`.
+    pub fn set_body_form_serde(mut self, form: &impl Serialize) -> Result<Self> {
+        let serialized = form_encode_serde(form)?;
+        self.content_type = Some(Cow::Borrowed("application/x-www-form-urlencoded"));
+        self.content_length = serialized.len() as u64;
+        self.use_chunked = false;
+        self.body = Body::Str(serialized);
+        Ok(self)
+    }
+
+    /// Set an `application/json` body from any `Serialize` value, including
+    /// a pre-built `serde_json::Value`. A value serde_json can't serialize,
+    /// e.g. a map with non-string keys, fails with `JsonSerialize`.
+    pub fn set_body_json(self, value: &impl Serialize) -> Result<Self> {
+        let json = serde_json::to_string(value).map_err(|e| JsonSerializeSnafu { message: e.to_string() }.build())?;
+        Ok(self.set_json_string(json))
+    }
+
+    /// [`set_body_json`](Self::set_body_json), indented for people reading
+    /// the wire.
+    pub fn set_body_json_pretty(self, value: &impl Serialize) -> Result<Self> {
+        let json =
+            serde_json::to_string_pretty(value).map_err(|e| JsonSerializeSnafu { message: e.to_string() }.build())?;
+        Ok(self.set_json_string(json))
+    }
+
+    fn set_json_string(mut self, json: String) -> Self {
+        self.content_type = Some(Cow::Borrowed(crate::content_type::APPLICATION_JSON));
+        self.content_length = json.len() as u64;
+        self.use_chunked = false;
+        self.body = Body::Str(json);
+        self
+    }
+
     /// Set the request body as multipart/form-data.
     ///
     /// This method automatically sets the Content-Type header to
@@ -305,6 +977,21 @@ impl Request {
         self
     }
 
+    /// Limit the wait for the first byte of the response, within the read
+    /// header timeout. Tripping it fails with `FirstByteTimeout`.
+    pub fn set_first_byte_timeout(mut self, dur: Duration) -> Self {
+        self.first_byte_timeout = Some(dur);
+        self
+    }
+
+    /// Limit the time from the first byte of the response until its head is
+    /// complete, within the read header timeout. Tripping it fails with
+    /// `HeaderCompleteTimeout`.
+    pub fn set_header_complete_timeout(mut self, dur: Duration) -> Self {
+        self.header_complete_timeout = Some(dur);
+        self
+    }
+
     /// Deprecated: Use set_read_header_timeout instead
     pub fn set_header_timeout(mut self, dur: Duration) -> Self {
         self.read_header_timeout = Some(dur);
@@ -322,10 +1009,80 @@ impl Request {
         Ok(self)
     }
 
+    /// Limit connecting for this request: the TCP connect and, for `https`,
+    /// the TLS handshake (or the proxy handshakes) share this one budget.
     pub fn set_connect_timeout(mut self, dur: Duration) -> Self {
         self.connect_timeout = Some(dur);
         self
     }
+
+    /// Limit the whole `send()` of this request and the body read after it,
+    /// like [`global_total_timeout`](crate::client::ClientInner::global_total_timeout)
+    /// but for this request only. Tripping it fails with `TotalTimeout`.
+    pub fn set_total_timeout(mut self, dur: Duration) -> Self {
+        self.total_timeout = Some(dur);
+        self
+    }
+
+    /// Stop following redirects and retrying stale connections once `dur`
+    /// has passed since the first attempt began, like
+    /// [`global_max_total_attempt_duration`](crate::client::ClientInner::global_max_total_attempt_duration)
+    /// but for this request only.
+    pub fn set_max_total_attempt_duration(mut self, dur: Duration) -> Self {
+        self.max_total_attempt_duration = Some(dur);
+        self
+    }
+
+    /// Pace this request's body to `rate`, see [`crate::throttle`].
+    pub fn set_upload_rate(mut self, rate: Rate) -> Self {
+        self.upload_rate = Some(rate);
+        self
+    }
+
+    /// Pace the response body to `rate`, see [`crate::throttle`].
+    pub fn set_download_rate(mut self, rate: Rate) -> Self {
+        self.download_rate = Some(rate);
+        self
+    }
+
+    /// Fail reading a response body that decodes to more than `max` bytes
+    /// with `BodyTooLarge`, see `ZJHttpClient::global_max_body_bytes`.
+    pub fn set_max_body_bytes(mut self, max: u64) -> Self {
+        self.max_body_bytes = Some(max);
+        self
+    }
+
+    /// Check the response body, as the body readers hand it out (decoded when
+    /// the client decompresses), against this SHA-256. A body that does not
+    /// match fails with `ChecksumMismatch` once it has been read to its end.
+    pub fn set_expected_sha256(mut self, digest: [u8; 32]) -> Self {
+        self.expected_sha256 = Some(digest);
+        self
+    }
+}
+
+/// Header fields [`Request::parse_from_head`] refuses: they describe the
+/// connection or the body framing, which the client manages.
+const HOP_BY_HOP: [&str; 5] = ["connection", "keep-alive", "proxy-connection", "transfer-encoding", "upgrade"];
+
+fn check_declared_length(declared: Option<u64>, actual: u64) -> Result<()> {
+    match declared {
+        Some(declared) if declared != actual => Err(InvalidRequestSnafu {
+            message: format!("Content-Length is {declared} but the body is {actual} bytes"),
+        }
+        .build()),
+        _ => Ok(()),
+    }
+}
+
+/// Serialize with serde_qs, then re-escape every pair with
+/// [`crate::encoding::encode_form_pairs`] so the output does not depend on
+/// serde_qs's own escaping rules.
+fn form_encode_serde(value: &impl Serialize) -> Result<String> {
+    let serialized = serde_qs::to_string(value)?;
+    let mut out = String::with_capacity(serialized.len());
+    crate::encoding::encode_form_pairs(crate::encoding::decode_form_pairs(&serialized), &mut out);
+    Ok(out)
 }
 
 #[cfg(test)]
@@ -370,7 +1127,7 @@ mod tests {
         url.query_pairs_mut().append_pair("a", "b");
         url.query_pairs_mut().append_pair("c", "d");
         // url.set_query(Some("c=d"));
-        println!("{x}", x = url.to_string())
+        println!("{x}", x = url)
     }
 
     #[test]
@@ -423,6 +1180,27 @@ mod tests {
         assert_eq!(request.connect_timeout, None);
     }
 
+    #[test]
+    fn test_from_url_matches_new() {
+        let url: Url = "https://example.com:8443/a?b=c".parse().unwrap();
+        let from_url = Request::from_url("GET", url.clone()).unwrap();
+        let from_str = Request::new("GET", url.as_str()).unwrap();
+        assert_eq!(from_url.url(), &url);
+        assert_eq!(from_url.header_one("host"), from_str.header_one("host"));
+        assert_eq!(from_url.header_one("user-agent"), from_str.header_one("user-agent"));
+
+        let err = Request::from_url("GET", "mailto:someone@example.com".parse().unwrap()).err().unwrap();
+        assert!(matches!(err, crate::ZjhttpcError::NoHost { .. }), "{err:?}");
+    }
+
+    #[test]
+    fn test_url_mut_edits_in_place() {
+        let mut request = Request::new("GET", "http://example.com/v1/items").unwrap();
+        request.url_mut().set_path("/v2/items");
+        request.url_mut().query_pairs_mut().append_pair("page", "2");
+        assert_eq!(request.url().as_str(), "http://example.com/v2/items?page=2");
+    }
+
     #[test]
     fn test_add_query_to_url_without_existing_query() {
         let request = Request::new("GET", "http://example.com")
@@ -454,6 +1232,54 @@ mod tests {
         assert!(query.contains("symbol=%40%23%24%25"));
     }
 
+    #[test]
+    fn test_query_escaping_matches_form_bodies() {
+        #[derive(Serialize)]
+        struct Search {
+            q: &'static str,
+            tags: Vec<&'static str>,
+        }
+        let search = Search { q: "a+b c/d~", tags: vec!["x&y"] };
+        let expected = "q=a%2Bb+c%2Fd~&tags[0]=x%26y";
+
+        let request = Request::new("GET", "http://example.com").unwrap().set_queries_serde(&search).unwrap();
+        assert_eq!(request.url.query(), Some(expected));
+        let request = Request::new("GET", "http://example.com")
+            .unwrap()
+            .add_query("q", "a+b c/d~")
+            .add_query("tags[0]", "x&y");
+        assert_eq!(request.url.query(), Some(expected));
+        let request = Request::new("POST", "http://example.com").unwrap().set_body_form_serde(&search).unwrap();
+        assert!(matches!(&request.body, Body::Str(body) if body == expected));
+    }
+
+    #[test]
+    fn test_signing_style_canonical_query() {
+        let request = || Request::new("GET", "https://iam.example.com/?Version=2010-05-08&prefix=%7Ealice%2Fphotos")
+            .unwrap()
+            .add_query("Action", "ListUsers")
+            .add_query("tag", "b")
+            .add_query("empty", "")
+            .add_query("tag", "a")
+            .add_query("path", "2024 summer/*");
+        assert_eq!(
+            request().wire_query().as_deref(),
+            Some("Version=2010-05-08&prefix=%7Ealice%2Fphotos&Action=ListUsers&tag=b&empty=&tag=a&path=2024+summer%2F%2A")
+        );
+
+        let preserved = request().canonicalize_query(CanonicalizeMode::Preserve);
+        assert_eq!(
+            preserved.wire_query().as_deref(),
+            Some("Version=2010-05-08&prefix=~alice%2Fphotos&Action=ListUsers&tag=b&empty=&tag=a&path=2024%20summer%2F%2A")
+        );
+
+        let sorted = request().canonicalize_query(CanonicalizeMode::SortedByKey);
+        assert_eq!(
+            sorted.wire_query().as_deref(),
+            Some("Action=ListUsers&Version=2010-05-08&empty=&path=2024%20summer%2F%2A&prefix=~alice%2Fphotos&tag=b&tag=a")
+        );
+    }
+
     #[test]
     fn test_add_query_with_empty_values() {
         let request = Request::new("GET", "http://example.com")
@@ -524,7 +1350,7 @@ mod tests {
         assert_eq!(request.url.query(), Some("a=1&b=2&c=3"));
         assert!(request.headers.contains_key("Accept"));
         assert_eq!(
-            request.headers.get("Accept").unwrap().first().unwrap(),
+            request.headers.get("Accept").unwrap(),
             "application/json"
         );
     }
@@ -551,4 +1377,187 @@ mod tests {
 
         assert_eq!(request.content_type.as_deref(), Some("image/png"));
     }
+
+    #[test]
+    fn test_headers_are_case_insensitive() {
+        let request = Request::new("GET", "http://example.com")
+            .unwrap()
+            .set_header("Accept", "text/html")
+            .set_header("accept", "application/json")
+            .add_header("X-Tag", "a")
+            .add_header("x-tag", "b");
+
+        assert_eq!(request.headers.get_all("ACCEPT").unwrap().len(), 1);
+        assert_eq!(request.header_one("Accept").unwrap(), "application/json");
+        let tags: Vec<_> = request.header_all("X-TAG").unwrap().iter().collect();
+        assert_eq!(tags, ["a", "b"]);
+        assert_eq!(request.header_one("Host").unwrap(), "example.com");
+    }
+
+    #[test]
+    fn test_set_headers_replaces_listed_names_only() {
+        let mut headers = hashbrown::HashMap::new();
+        headers.insert("user-agent".to_string(), IndexSet::from(["custom".to_string()]));
+        let request = Request::new("GET", "http://example.com")
+            .unwrap()
+            .set_headers(headers);
+        assert_eq!(request.header_one("User-Agent").unwrap(), "custom");
+        assert!(request.headers.contains_key("host"));
+    }
+
+    #[test]
+    fn test_basic_auth_lives_in_header_map() {
+        let request = Request::new("GET", "http://example.com")
+            .unwrap()
+            .set_basic_auth("user", "pass");
+        assert_eq!(request.header_one("authorization").unwrap(), "Basic dXNlcjpwYXNz");
+
+        let request = request.set_basic_auth("other", "secret");
+        assert_eq!(request.header_one("authorization").unwrap(), "Basic b3RoZXI6c2VjcmV0");
+    }
+
+    #[test]
+    fn test_explicit_authorization_wins_over_basic_auth() {
+        let before = Request::new("GET", "http://example.com")
+            .unwrap()
+            .set_header("Authorization", "Bearer token")
+            .set_basic_auth("user", "pass");
+        let after = Request::new("GET", "http://example.com")
+            .unwrap()
+            .set_basic_auth("user", "pass")
+            .set_authorization("Bearer", "token");
+        for request in [before, after] {
+            assert_eq!(request.header_all("authorization").unwrap().len(), 1);
+            assert_eq!(request.header_one("authorization").unwrap(), "Bearer token");
+        }
+    }
+
+    #[test]
+    fn test_basic_auth_field_is_applied_at_send_time() {
+        let mut request = Request::new("GET", "http://example.com").unwrap();
+        request.basic_auth = Some(("user".to_string(), "pass".to_string()));
+        assert!(request.header_one("authorization").is_none());
+        request.apply_basic_auth();
+        assert_eq!(request.header_one("authorization").unwrap(), "Basic dXNlcjpwYXNz");
+    }
+
+    #[test]
+    fn test_set_authorization_custom_scheme() {
+        let request = Request::new("GET", "http://example.com")
+            .unwrap()
+            .set_authorization("Negotiate", "");
+        assert_eq!(request.header_one("authorization").unwrap(), "Negotiate");
+        assert!(request.headers.validate().is_ok());
+
+        let request = request.set_authorization("Bad/Scheme", "x");
+        assert!(request.headers.validate().is_err());
+    }
+
+    #[test]
+    fn test_expect_json_sets_accept_and_expectation() {
+        let request = Request::new("GET", "http://example.com").unwrap().expect_json();
+        assert_eq!(request.header_one("accept").unwrap(), "application/json");
+        assert_eq!(request.expected_body, Some(ExpectedBody::Json));
+        let request = request.expect_text();
+        assert_eq!(request.header_all("accept").unwrap().len(), 1);
+        assert_eq!(request.header_one("accept").unwrap(), "text/*");
+    }
+
+    #[test]
+    fn test_expected_body_matches_content_type() {
+        assert!(ExpectedBody::Json.matches("application/json"));
+        assert!(ExpectedBody::Json.matches("Application/JSON; charset=utf-8"));
+        assert!(ExpectedBody::Json.matches("application/problem+json"));
+        assert!(!ExpectedBody::Json.matches("text/html; charset=utf-8"));
+        assert!(ExpectedBody::Text.matches("text/plain;charset=gbk"));
+        assert!(!ExpectedBody::Text.matches("application/json"));
+        assert!(ExpectedBody::Bytes.matches("image/png"));
+    }
+
+    #[test]
+    fn test_parse_from_head_origin_form() {
+        let raw = b"POST /api/items?page=2 HTTP/1.1\r\nHost: example.com:8080\r\nContent-Type: application/json\r\n\
+                    Content-Length: 9\r\nX-Trace: a\r\nX-Trace: b\r\n\r\n{\"id\": 1}";
+        let req = Request::parse_from_head(raw, None).unwrap();
+        assert_eq!(req.method, crate::methods::POST);
+        assert_eq!(req.url.as_str(), "http://example.com:8080/api/items?page=2");
+        assert_eq!(req.header_one("host").unwrap(), "example.com:8080");
+        assert_eq!(req.header_all("x-trace").unwrap().len(), 2);
+        assert!(!req.headers.contains_key("content-length"));
+        assert!(!req.headers.contains_key("user-agent"), "no default User-Agent is added");
+        assert_eq!(req.content_length, 9);
+        assert!(matches!(&req.body, Body::Bytes(b) if b == b"{\"id\": 1}"));
+    }
+
+    #[test]
+    fn test_parse_from_head_override_and_absolute_form() {
+        let raw = b"GET http://proxy-seen.example/a?b=1 HTTP/1.1\r\nHost: proxy-seen.example\r\n\r\n";
+        let req = Request::parse_from_head(raw, None).unwrap();
+        assert_eq!(req.url.as_str(), "http://proxy-seen.example/a?b=1");
+        assert!(matches!(req.body, Body::None));
+
+        let req = Request::parse_from_head(raw, Some("https://staging.example:8443")).unwrap();
+        assert_eq!(req.url.as_str(), "https://staging.example:8443/a?b=1");
+        assert_eq!(req.header_one("host").unwrap(), "staging.example");
+        assert_eq!(req.header_all("host").unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_parse_from_head_refusals() {
+        let refused = |raw: &[u8]| Request::parse_from_head(raw, Some("example.com")).err().expect("refused");
+        for header in ["Connection: close", "transfer-encoding: chunked", "Upgrade: websocket"] {
+            let raw = format!("GET / HTTP/1.1\r\n{header}\r\n\r\n");
+            let err = refused(raw.as_bytes());
+            assert!(matches!(err, crate::ZjhttpcError::InvalidHeader { .. }), "{err:?}");
+            assert!(err.to_string().contains("hop-by-hop"), "{err}");
+        }
+        for raw in [
+            &b"GET / HTTP/1.1\r\nHost: example.com\r\n"[..],
+            b"BREW /pot HTTP/1.1\r\n\r\n",
+            b"GET / HTTP/2\r\n\r\n",
+            b"OPTIONS * HTTP/1.1\r\n\r\n",
+            b"POST / HTTP/1.1\r\nContent-Length: 10\r\n\r\nshort",
+        ] {
+            let err = refused(raw);
+            assert!(matches!(err, crate::ZjhttpcError::InvalidRequest { .. }), "{err:?}");
+        }
+        let err = Request::parse_from_head(b"GET /x HTTP/1.1\r\n\r\n", None).err().expect("no host");
+        assert!(err.to_string().contains("without a Host header"), "{err}");
+    }
+
+    #[test]
+    fn test_as_forwarded_chains_through_two_proxies() {
+        // The first proxy got the request from a browser over IPv6 and TLS,
+        // the second one gets it from the first proxy in plain HTTP.
+        let req = Request::new("GET", "http://backend.internal/app")
+            .unwrap()
+            .as_forwarded("2001:db8::7".parse().unwrap(), "https", "shop.example")
+            .as_forwarded("192.0.2.10".parse().unwrap(), "http", "shop.example:8080");
+        assert_eq!(
+            req.header_one("forwarded").unwrap(),
+            "for=\"[2001:db8::7]\";proto=https;host=shop.example, \
+             for=192.0.2.10;proto=http;host=\"shop.example:8080\""
+        );
+        assert_eq!(req.header_one("x-forwarded-for").unwrap(), "2001:db8::7, 192.0.2.10");
+        assert_eq!(req.header_one("x-forwarded-proto").unwrap(), "https, http");
+        assert_eq!(req.header_one("x-forwarded-host").unwrap(), "shop.example, shop.example:8080");
+        assert_eq!(req.header_one("via").unwrap(), "1.1 zjhttpc, 1.1 zjhttpc");
+
+        let head = crate::client::ZJHttpClient::new().request_head(&req).unwrap();
+        let head = String::from_utf8(head).unwrap();
+        for name in ["Forwarded", "X-Forwarded-For", "X-Forwarded-Proto", "X-Forwarded-Host", "Via"] {
+            assert_eq!(head.matches(&format!("\r\n{name}: ")).count(), 1, "{head}");
+        }
+        assert!(head.contains("\r\nVia: 1.1 zjhttpc, 1.1 zjhttpc\r\n"), "{head}");
+    }
+
+    #[test]
+    fn test_strip_hop_by_hop_keeps_end_to_end_fields() {
+        let raw = b"GET /a HTTP/1.1\r\nHost: shop.example\r\nTE: trailers\r\nX-Trace: 1\r\n\r\n";
+        let mut req = Request::parse_from_head(raw, None).unwrap();
+        req.headers.append_unchecked("Connection", "X-Trace");
+        let req = req.strip_hop_by_hop().as_forwarded("192.0.2.1".parse().unwrap(), "http", "shop.example");
+        let names: Vec<_> = req.headers.iter().map(|(name, _)| name).collect();
+        assert_eq!(names, ["Host", "Forwarded", "X-Forwarded-For", "X-Forwarded-Proto", "X-Forwarded-Host", "Via"]);
+    }
 }
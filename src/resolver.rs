@@ -0,0 +1,305 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use async_std::net::ToSocketAddrs;
+use dashmap::DashMap;
+use futures::future::BoxFuture;
+use rand::seq::IndexedRandom;
+
+use crate::error::{DnsSnafu, Result};
+
+/// Turns a host name into the socket addresses `send()` may connect to.
+///
+/// The client asks the resolver once per request, before picking a pooled
+/// connection or opening a new one, unless its [`DnsCache`] has the answer. IP literals are passed through the
+/// resolver as well (as their textual form), so a custom implementation sees
+/// every lookup the client performs.
+pub trait Resolver: Send + Sync + 'static {
+    fn resolve<'a>(&'a self, host: &'a str, port: u16) -> BoxFuture<'a, Result<Vec<SocketAddr>>>;
+}
+
+/// Default resolver backed by the operating system (`getaddrinfo`), run on the
+/// async-std blocking pool.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemResolver;
+
+impl Resolver for SystemResolver {
+    fn resolve<'a>(&'a self, host: &'a str, port: u16) -> BoxFuture<'a, Result<Vec<SocketAddr>>> {
+        Box::pin(async move {
+            let addrs = (host, port)
+                .to_socket_addrs()
+                .await
+                .map_err(|e| DnsSnafu { message: format!("failed to resolve hostname: {e}") }.build())?
+                .collect::<Vec<_>>();
+            Ok(addrs)
+        })
+    }
+}
+
+/// Successful resolutions, kept for a TTL per `host:port` so repeated
+/// requests to the same host skip the [`Resolver`].
+///
+/// Set with `ZJHttpClientBuilder::dns_cache_ttl` (60 s by default). Entries
+/// hold the resolver's answer before the [`AddressFilter`] runs, and age by
+/// the client's [`Clock`](crate::clock::Clock). A failed connect to a host
+/// drops its entry, so the next request resolves again. Failed lookups are
+/// not cached.
+#[derive(Debug, Clone)]
+pub struct DnsCache {
+    ttl: Duration,
+    entries: Arc<DashMap<(String, u16), CachedAddrs>>,
+}
+
+/// The addresses of one `host:port` and when they were resolved.
+type CachedAddrs = (Vec<SocketAddr>, Instant);
+
+impl DnsCache {
+    pub fn new(ttl: Duration) -> Self {
+        DnsCache { ttl, entries: Arc::default() }
+    }
+
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    /// The cached addresses of `host:port`, unless missing or older than the
+    /// TTL at `now`.
+    pub fn get(&self, host: &str, port: u16, now: Instant) -> Option<Vec<SocketAddr>> {
+        let key = (host.to_owned(), port);
+        let entry = self.entries.get(&key)?;
+        let (addrs, resolved_at) = &*entry;
+        if now.saturating_duration_since(*resolved_at) < self.ttl {
+            return Some(addrs.clone());
+        }
+        drop(entry);
+        self.entries.remove_if(&key, |_, (_, at)| now.saturating_duration_since(*at) >= self.ttl);
+        None
+    }
+
+    pub(crate) fn insert(&self, host: &str, port: u16, addrs: Vec<SocketAddr>, now: Instant) {
+        self.entries.insert((host.to_owned(), port), (addrs, now));
+    }
+
+    /// Forget `host:port`.
+    pub fn invalidate(&self, host: &str, port: u16) {
+        self.entries.remove(&(host.to_owned(), port));
+    }
+
+    /// Forget every entry.
+    pub fn clear(&self) {
+        self.entries.clear();
+    }
+
+    /// Number of entries, expired ones included until they are next looked up.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// An empty cache with the same TTL, for `ZJHttpClient::fork_isolated`.
+    pub(crate) fn fork(&self) -> Self {
+        DnsCache::new(self.ttl)
+    }
+}
+
+/// Decides which resolved addresses the client may connect to.
+///
+/// Set with `ZJHttpClientBuilder::set_address_filter`; it runs on the resolver
+/// output of every request, before a pooled connection is picked or a new one
+/// is opened. If it rejects every address the request fails with
+/// [`ZjhttpcError::NoPermittedAddress`](crate::ZjhttpcError::NoPermittedAddress).
+/// Requests through a proxy are filtered on the locally resolved target
+/// address, but the proxy performs its own lookup, so this is no SSRF guard
+/// behind a proxy.
+#[derive(Clone)]
+pub struct AddressFilter(Arc<dyn Fn(&SocketAddr) -> bool + Send + Sync>);
+
+impl AddressFilter {
+    /// Allow the addresses for which `f` returns true.
+    pub fn new(f: impl Fn(&SocketAddr) -> bool + Send + Sync + 'static) -> Self {
+        AddressFilter(Arc::new(f))
+    }
+
+    /// Only allow IPv4 addresses.
+    pub fn ipv4_only() -> Self {
+        Self::new(|addr| addr.is_ipv4())
+    }
+
+    /// Reject loopback, private (RFC 1918, IPv6 unique local), link-local,
+    /// shared (RFC 6598) and unspecified addresses, including their
+    /// IPv4-mapped IPv6 forms. Use it when request URLs come from untrusted
+    /// input, so they cannot reach internal services.
+    pub fn deny_private_ranges() -> Self {
+        Self::new(|addr| !is_private(addr.ip()))
+    }
+
+    pub fn allows(&self, addr: &SocketAddr) -> bool {
+        (self.0)(addr)
+    }
+}
+
+impl std::fmt::Debug for AddressFilter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("AddressFilter")
+    }
+}
+
+impl From<Box<dyn Fn(&SocketAddr) -> bool + Send + Sync>> for AddressFilter {
+    fn from(f: Box<dyn Fn(&SocketAddr) -> bool + Send + Sync>) -> Self {
+        AddressFilter(Arc::from(f))
+    }
+}
+
+/// Which of several resolved addresses a new connection goes to.
+///
+/// Set with `ZJHttpClientBuilder::set_address_selection`. It only applies when
+/// `send()` has to dial: an idle pooled connection to any of the host's
+/// addresses is reused first. The address used is reported by
+/// `Response::addr`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AddressSelection {
+    /// The first address, in resolver order.
+    First,
+    /// The next address in resolver order for each new connection to the same
+    /// `host:port`, wrapping around, so load spreads over all of them.
+    RoundRobin,
+    /// Any address, chosen at random for each new connection.
+    #[default]
+    Random,
+}
+
+/// Round-robin position per `host:port`, kept by the connection pool.
+#[derive(Debug, Default)]
+pub(crate) struct AddressRotation(DashMap<String, usize>);
+
+impl AddressSelection {
+    /// Pick the address to dial for `origin` from its non-empty `addrs`.
+    pub(crate) fn select(self, rotation: &AddressRotation, origin: &str, addrs: &[SocketAddr]) -> SocketAddr {
+        match self {
+            AddressSelection::First => addrs[0],
+            AddressSelection::RoundRobin => {
+                let mut next = rotation.0.entry(origin.to_owned()).or_insert(0);
+                let addr = addrs[*next % addrs.len()];
+                *next = next.wrapping_add(1);
+                addr
+            }
+            AddressSelection::Random => *addrs.choose(&mut rand::rng()).expect("addrs is not empty"),
+        }
+    }
+}
+
+fn is_private(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => is_private_v4(v4),
+        IpAddr::V6(v6) => match v6.to_ipv4_mapped() {
+            Some(v4) => is_private_v4(v4),
+            None => is_private_v6(v6),
+        },
+    }
+}
+
+fn is_private_v4(ip: Ipv4Addr) -> bool {
+    let [a, b, ..] = ip.octets();
+    ip.is_private()
+        || ip.is_loopback()
+        || ip.is_link_local()
+        || ip.is_unspecified()
+        || ip.is_broadcast()
+        || a == 0
+        || (a == 100 && (64..128).contains(&b))
+}
+
+fn is_private_v6(ip: Ipv6Addr) -> bool {
+    ip.is_loopback() || ip.is_unspecified() || ip.is_unique_local() || ip.is_unicast_link_local()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[async_std::test]
+    async fn test_system_resolver_ip_literal() {
+        let addrs = SystemResolver.resolve("127.0.0.1", 8080).await.unwrap();
+        assert_eq!(addrs, vec!["127.0.0.1:8080".parse::<SocketAddr>().unwrap()]);
+    }
+
+    #[test]
+    fn test_dns_cache_ttl_and_invalidation() {
+        let cache = DnsCache::new(Duration::from_secs(60));
+        let t0 = Instant::now();
+        let addrs = vec!["10.0.0.1:443".parse::<SocketAddr>().unwrap()];
+        assert_eq!(cache.get("example.com", 443, t0), None);
+        cache.insert("example.com", 443, addrs.clone(), t0);
+        assert_eq!(cache.get("example.com", 443, t0 + Duration::from_secs(59)), Some(addrs.clone()));
+        assert_eq!(cache.get("example.com", 80, t0), None, "the port is part of the key");
+        assert_eq!(cache.get("example.com", 443, t0 + Duration::from_secs(60)), None);
+        assert!(cache.is_empty(), "an expired entry is dropped when looked up");
+
+        cache.insert("example.com", 443, addrs.clone(), t0);
+        cache.insert("example.org", 443, addrs, t0);
+        cache.invalidate("example.com", 443);
+        assert_eq!((cache.get("example.com", 443, t0), cache.len()), (None, 1));
+        cache.clear();
+        assert!(cache.is_empty());
+    }
+
+    #[async_std::test]
+    async fn test_system_resolver_ipv6_literal() {
+        let addrs = SystemResolver.resolve("::1", 443).await.unwrap();
+        assert_eq!(addrs, vec!["[::1]:443".parse::<SocketAddr>().unwrap()]);
+    }
+
+    #[test]
+    fn test_address_selection() {
+        let addrs: Vec<SocketAddr> = ["10.0.0.1:80", "10.0.0.2:80", "10.0.0.3:80"].iter().map(|a| a.parse().unwrap()).collect();
+        let rotation = AddressRotation::default();
+        assert_eq!(AddressSelection::First.select(&rotation, "a:80", &addrs), addrs[0]);
+        let turns: Vec<_> = (0..4).map(|_| AddressSelection::RoundRobin.select(&rotation, "a:80", &addrs)).collect();
+        assert_eq!(turns, [addrs[0], addrs[1], addrs[2], addrs[0]]);
+        // Each host:port rotates on its own.
+        assert_eq!(AddressSelection::RoundRobin.select(&rotation, "b:80", &addrs), addrs[0]);
+        assert!(addrs.contains(&AddressSelection::Random.select(&rotation, "a:80", &addrs)));
+    }
+
+    #[test]
+    fn test_deny_private_ranges() {
+        let filter = AddressFilter::deny_private_ranges();
+        for denied in [
+            "10.1.2.3:80",
+            "172.16.0.1:80",
+            "172.31.255.255:80",
+            "192.168.1.1:80",
+            "127.0.0.1:80",
+            "169.254.169.254:80",
+            "0.0.0.0:80",
+            "100.64.0.1:80",
+            "[::1]:80",
+            "[::]:80",
+            "[fd00::1]:80",
+            "[fe80::1]:80",
+            "[::ffff:10.0.0.1]:80",
+            "[::ffff:127.0.0.1]:80",
+        ] {
+            assert!(!filter.allows(&denied.parse().unwrap()), "{denied} should be denied");
+        }
+        for allowed in ["93.184.216.34:443", "172.32.0.1:80", "100.128.0.1:80", "[2606:4700::1111]:443", "[::ffff:8.8.8.8]:53"] {
+            assert!(filter.allows(&allowed.parse().unwrap()), "{allowed} should be allowed");
+        }
+    }
+
+    #[test]
+    fn test_ipv4_only_and_custom_filter() {
+        let v4 = AddressFilter::ipv4_only();
+        assert!(v4.allows(&"1.1.1.1:80".parse().unwrap()));
+        assert!(!v4.allows(&"[2606:4700::1111]:80".parse().unwrap()));
+
+        let boxed: Box<dyn Fn(&SocketAddr) -> bool + Send + Sync> = Box::new(|addr| addr.port() == 443);
+        let port_only = AddressFilter::from(boxed);
+        assert!(port_only.allows(&"1.1.1.1:443".parse().unwrap()));
+        assert!(!port_only.allows(&"1.1.1.1:80".parse().unwrap()));
+    }
+}
@@ -1,45 +1,173 @@
-use async_std::io::ReadExt;
+use async_std::io::{ReadExt, WriteExt};
 use encoding_rs::GBK;
-use hashbrown::HashMap;
-use indexmap::IndexSet;
+use snafu::OptionExt;
 use std::net::SocketAddr;
+use std::time::{Duration, Instant, SystemTime};
 
-use tracing::error;
+use tracing::{debug, warn};
 
 use crate::{
-    client::ConnectionPool,
-    error::{BodyAlreadyReadSnafu, InvalidResponseSnafu, JsonParsingSnafu, ReadBodyTimeoutSnafu, Result, ZjhttpcError},
+    body_pipeline::{BodyPipeline, PipelineReader},
+    byteranges::{ByteRangePart, byteranges_boundary, parse_byteranges, parse_content_range},
+    client::{ConnectionPool, StreamInfo},
+    content_type::{self, SniffedType},
+    config::EffectiveConfig,
+    error::{BodyAlreadyReadSnafu, BodyTooLargeSnafu, InvalidByteRangesSnafu, TruncatedBody, JsonParsingSnafu, ReadBodyTimeoutSnafu, RequestPhase, Result,
+        NotUpgradedSnafu, ProtocolSwitchedSnafu, TotalTimeoutSnafu, UnexpectedContentTypeSnafu, ZjhttpcError},
+    extensions::Extensions,
+    header::HeaderMap,
+    json_seq::{RecordErrors, RecordSplitter},
+    deprecation::{DeprecationInfo, Warning, parse_warning_header},
+    link::{Link, parse_link_header},
+    log_target::BODY,
+    memory_budget::{BudgetGate, Reservation},
+    methods::MethodSet,
     misc::HttpVersion,
     proxy::HttpsProxyOption,
+    requestx::{ExpectedBody, Request, SentRequestCapture},
     stream::{BoxedStream, ChainRead, SliceRead},
+    throttle::{Throttle, throttled_time},
 };
 use std::sync::{
     Arc,
-    atomic::{AtomicBool, Ordering},
+    atomic::{AtomicBool, AtomicU64, Ordering},
 };
 
+/// A 103 Early Hints interim response, see [`Request::set_early_hints_handler`](crate::requestx::Request::set_early_hints_handler).
+#[derive(Debug, Clone)]
+pub struct EarlyHints {
+    /// Headers of the 103 response as sent by the server.
+    pub headers: HeaderMap,
+    /// Every link of every `Link` header, in order.
+    pub links: Vec<Link>,
+}
+
+impl EarlyHints {
+    pub(crate) fn new(headers: HeaderMap) -> Self {
+        let links = headers
+            .get_all("link")
+            .into_iter()
+            .flatten()
+            .flat_map(|v| parse_link_header(v))
+            .collect();
+        EarlyHints { headers, links }
+    }
+
+    /// Links with `rel=preload`.
+    pub fn preloads(&self) -> impl Iterator<Item = &Link> {
+        self.links.iter().filter(|l| l.has_rel("preload"))
+    }
+}
+
+/// A 1xx interim response, see [`Response::interim_headers`].
+#[derive(Debug, Clone)]
+pub struct InterimResponse {
+    pub status: u16,
+    pub headers: HeaderMap,
+}
+
+/// The request head `send()` wrote for a response, see [`Response::sent_request`].
+#[derive(Debug, Clone)]
+pub struct SentRequest {
+    pub method: &'static str,
+    pub url: url::Url,
+    /// Request line and header lines exactly as written, including the
+    /// headers the client adds (`Host`, `Content-Length`, `User-Agent`, ...).
+    /// Values of credential headers are redacted unless the request asked for
+    /// [`SentRequestCapture::Unredacted`].
+    pub head: String,
+}
+
+/// Headers whose values are replaced by [`REDACTED`] in a captured head.
+const CREDENTIAL_HEADERS: [&str; 3] = ["authorization", "proxy-authorization", "cookie"];
+const REDACTED: &str = "[redacted]";
+
+impl SentRequest {
+    pub(crate) fn capture(req: &Request, head: &[u8]) -> Option<Self> {
+        let head = String::from_utf8_lossy(head);
+        let head = match req.sent_request_capture {
+            SentRequestCapture::Off => return None,
+            SentRequestCapture::Unredacted => head.into_owned(),
+            SentRequestCapture::Redacted => redact_head(&head),
+        };
+        Some(SentRequest { method: req.method, url: req.url.clone(), head })
+    }
+
+    /// Values of header `name` as written, in order. Case-insensitive.
+    pub fn header_all(&self, name: &str) -> Vec<&str> {
+        self.head
+            .split("\r\n")
+            .skip(1)
+            .filter_map(|line| line.split_once(": "))
+            .filter(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value)
+            .collect()
+    }
+}
+
+/// Replace credential header values, keeping the auth scheme of
+/// `Authorization` / `Proxy-Authorization` so signature problems stay visible.
+fn redact_head(head: &str) -> String {
+    let mut out = String::with_capacity(head.len());
+    for (i, line) in head.split_inclusive("\r\n").enumerate() {
+        let redacted = (i > 0)
+            .then(|| line.split_once(": "))
+            .flatten()
+            .filter(|(key, _)| CREDENTIAL_HEADERS.iter().any(|h| key.eq_ignore_ascii_case(h)));
+        match redacted {
+            Some((key, value)) => {
+                out.push_str(key);
+                out.push_str(": ");
+                if !key.eq_ignore_ascii_case("cookie")
+                    && let Some((scheme, _)) = value.split_once(' ')
+                {
+                    out.push_str(scheme);
+                    out.push(' ');
+                }
+                out.push_str(REDACTED);
+                out.push_str("\r\n");
+            }
+            None => out.push_str(line),
+        }
+    }
+    out
+}
+
+/// How the client delimits a response body, see [`Response::effective_encoding`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BodyFraming {
+    /// `Content-Length` bytes.
+    ContentLength(u64),
+    /// `Transfer-Encoding: chunked`, decoded.
+    Chunked,
+    /// No framing header: the body ends when the server closes the connection.
+    ReadToEof,
+    /// Framing headers ignored on request, see
+    /// [`Request::set_raw_body_framing`](crate::requestx::Request::set_raw_body_framing).
+    Raw,
+}
+
 /// A streaming chunked decoder that processes chunks on-the-fly without buffering the entire body
 pub struct ChunkedDecoderStream {
     inner: Option<ChainedInner>,
     state: DecoderState,
-    chunk_remaining: usize,
+    chunk_remaining: u64,
     line_buffer: Vec<u8>,
     trailer_buffer: Vec<u8>,
     completion_flag: Arc<AtomicBool>,
-    addr: SocketAddr,
-    is_tls: bool,
-    proxy_used: Option<HttpsProxyOption>,
+    stream_info: StreamInfo,
     pool: Option<ConnectionPool>,
 }
 
 /// A fixed-length stream that tracks remaining bytes and returns 0 when complete
 pub struct BodyFixedLengthStream {
     inner: Option<ChainedInner>,
-    remaining: usize,
+    /// Byte counts stay `u64`: a body may be longer than `usize` on 32-bit
+    /// targets; only the length of one read is a `usize`.
+    content_length: u64,
+    remaining: u64,
     completion_flag: Arc<AtomicBool>,
-    addr: SocketAddr,
-    is_tls: bool,
-    proxy_used: Option<HttpsProxyOption>,
+    stream_info: StreamInfo,
     pool: Option<ConnectionPool>,
 }
 
@@ -47,14 +175,37 @@ pub struct BodyFixedLengthStream {
 pub struct BodyUnknownLengthStream {
     inner: Option<ChainedInner>,
     completion_flag: Arc<AtomicBool>,
-    addr: SocketAddr,
-    is_tls: bool,
-    proxy_used: Option<HttpsProxyOption>,
+    stream_info: StreamInfo,
     pool: Option<ConnectionPool>,
 }
 
 type ChainedInner = ChainRead<SliceRead, BoxedStream>;
 
+/// Cap for delta-seconds values such as `Age` (RFC 7234 §1.2.1).
+const MAX_DELTA_SECONDS: u64 = 1 << 31;
+
+/// Longest chunk-size or trailer line the chunked decoder buffers before
+/// giving up on the body.
+pub(crate) const MAX_CHUNK_LINE_BYTES: usize = 4096;
+
+/// Parse a chunk-size line (without CRLF): hex digits only, no sign, and a
+/// value that fits in `u64`. Chunks are streamed, so one larger than `usize`
+/// is fine.
+pub(crate) fn parse_chunk_size(line: &str) -> std::result::Result<u64, String> {
+    let digits = line.trim();
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err("not a hexadecimal number".to_string());
+    }
+    u64::from_str_radix(digits, 16).map_err(|_| "overflows u64".to_string())
+}
+
+fn chunk_line_too_long(what: &str) -> std::io::Error {
+    std::io::Error::new(
+        std::io::ErrorKind::InvalidData,
+        format!("chunked {what} longer than {MAX_CHUNK_LINE_BYTES} bytes"),
+    )
+}
+
 #[derive(Debug, Clone, PartialEq)]
 enum DecoderState {
     ReadingChunkSizeLine,
@@ -73,9 +224,7 @@ impl ChunkedDecoderStream {
             line_buffer: Vec::new(),
             trailer_buffer: Vec::new(),
             completion_flag: Arc::new(AtomicBool::new(false)),
-            addr: std::net::SocketAddr::from(([0, 0, 0, 0], 0)),
-            is_tls: false,
-            proxy_used: None,
+            stream_info: StreamInfo::unpooled(),
             pool: None,
         }
     }
@@ -83,9 +232,7 @@ impl ChunkedDecoderStream {
     pub(crate) fn new_with_completion_flag(
         inner: ChainedInner,
         completion_flag: Arc<AtomicBool>,
-        addr: SocketAddr,
-        is_tls: bool,
-        proxy_used: Option<HttpsProxyOption>,
+        stream_info: StreamInfo,
         pool: Option<ConnectionPool>,
     ) -> Self {
         Self {
@@ -95,9 +242,7 @@ impl ChunkedDecoderStream {
             line_buffer: Vec::new(),
             trailer_buffer: Vec::new(),
             completion_flag,
-            addr,
-            is_tls,
-            proxy_used,
+            stream_info,
             pool,
         }
     }
@@ -106,15 +251,13 @@ impl ChunkedDecoderStream {
         self.completion_flag.load(Ordering::Relaxed)
     }
 
+    /// Without a pool the connection stays in `inner`, for
+    /// [`Response::take_drained_connection`] to take back.
     fn return_stream_to_pool(&mut self) {
-        if let (Some(chain), Some(pool)) = (self.inner.take(), self.pool.as_ref()) {
-            let stream = chain.into_second();
-            let stream_info = crate::client::StreamInfo {
-                addr: self.addr,
-                is_tls: self.is_tls,
-                proxy_used: self.proxy_used.clone(),
-            };
-            pool.return_stream(stream, stream_info);
+        if let Some(pool) = &self.pool
+            && let Some(chain) = self.inner.take()
+        {
+            pool.return_stream(chain.into_second(), self.stream_info.clone());
         }
     }
 }
@@ -140,8 +283,7 @@ impl async_std::io::Read for ChunkedDecoderStream {
                     let inner_stream = match &mut self.inner {
                         Some(s) => s,
                         None => {
-                            return std::task::Poll::Ready(Err(std::io::Error::new(
-                                std::io::ErrorKind::Other,
+                            return std::task::Poll::Ready(Err(std::io::Error::other(
                                 "stream is None",
                             )));
                         }
@@ -157,6 +299,9 @@ impl async_std::io::Read for ChunkedDecoderStream {
                         }
                         std::task::Poll::Ready(Ok(_)) => {
                             self.line_buffer.push(one_byte[0]);
+                            if self.line_buffer.len() > MAX_CHUNK_LINE_BYTES {
+                                return std::task::Poll::Ready(Err(chunk_line_too_long("size line")));
+                            }
                             if self.line_buffer.ends_with(b"\r\n") {
                                 let line = &self.line_buffer[..self.line_buffer.len() - 2];
                                 let line_str = String::from_utf8_lossy(line);
@@ -166,7 +311,7 @@ impl async_std::io::Read for ChunkedDecoderStream {
                                     continue;
                                 }
 
-                                let chunk_size = match usize::from_str_radix(line_str.trim(), 16) {
+                                let chunk_size = match parse_chunk_size(&line_str) {
                                     Ok(s) => s,
                                     Err(e) => {
                                         return std::task::Poll::Ready(Err(std::io::Error::new(
@@ -204,12 +349,11 @@ impl async_std::io::Read for ChunkedDecoderStream {
                         continue;
                     }
 
-                    let to_read = std::cmp::min(buf.len(), self.chunk_remaining);
+                    let to_read = self.chunk_remaining.min(buf.len() as u64) as usize;
                     let inner_stream = match &mut self.inner {
                         Some(s) => s,
                         None => {
-                            return std::task::Poll::Ready(Err(std::io::Error::new(
-                                std::io::ErrorKind::Other,
+                            return std::task::Poll::Ready(Err(std::io::Error::other(
                                 "stream is None",
                             )));
                         }
@@ -223,7 +367,7 @@ impl async_std::io::Read for ChunkedDecoderStream {
                                 )));
                             }
 
-                            self.chunk_remaining -= n;
+                            self.chunk_remaining -= n as u64;
                             return std::task::Poll::Ready(Ok(n));
                         }
                         std::task::Poll::Ready(Err(e)) => {
@@ -236,8 +380,7 @@ impl async_std::io::Read for ChunkedDecoderStream {
                     let inner_stream = match &mut self.inner {
                         Some(s) => s,
                         None => {
-                            return std::task::Poll::Ready(Err(std::io::Error::new(
-                                std::io::ErrorKind::Other,
+                            return std::task::Poll::Ready(Err(std::io::Error::other(
                                 "stream is None",
                             )));
                         }
@@ -253,6 +396,9 @@ impl async_std::io::Read for ChunkedDecoderStream {
                         }
                         std::task::Poll::Ready(Ok(_)) => {
                             self.trailer_buffer.push(one_byte[0]);
+                            if self.trailer_buffer.len() > MAX_CHUNK_LINE_BYTES {
+                                return std::task::Poll::Ready(Err(chunk_line_too_long("trailer line")));
+                            }
                             if self.trailer_buffer.ends_with(b"\r\n") {
                                 if self.trailer_buffer.len() != 2 {
                                     let x = String::from_utf8_lossy(&self.trailer_buffer);
@@ -277,8 +423,7 @@ impl async_std::io::Read for ChunkedDecoderStream {
                     let inner_stream = match &mut self.inner {
                         Some(s) => s,
                         None => {
-                            return std::task::Poll::Ready(Err(std::io::Error::new(
-                                std::io::ErrorKind::Other,
+                            return std::task::Poll::Ready(Err(std::io::Error::other(
                                 "stream is None",
                             )));
                         }
@@ -294,6 +439,9 @@ impl async_std::io::Read for ChunkedDecoderStream {
                         }
                         std::task::Poll::Ready(Ok(_)) => {
                             self.trailer_buffer.push(one_byte[0]);
+                            if self.trailer_buffer.len() > MAX_CHUNK_LINE_BYTES {
+                                return std::task::Poll::Ready(Err(chunk_line_too_long("trailer line")));
+                            }
                             if self.trailer_buffer.ends_with(b"\r\n") {
                                 if self.trailer_buffer.len() != 2 {
                                     let x = String::from_utf8_lossy(&self.trailer_buffer);
@@ -320,34 +468,30 @@ impl async_std::io::Read for ChunkedDecoderStream {
 }
 
 impl BodyFixedLengthStream {
-    pub fn new(inner: ChainedInner, content_length: usize) -> Self {
+    pub fn new(inner: ChainedInner, content_length: u64) -> Self {
         Self {
             inner: Some(inner),
+            content_length,
             remaining: content_length,
             completion_flag: Arc::new(AtomicBool::new(false)),
-            addr: std::net::SocketAddr::from(([0, 0, 0, 0], 0)),
-            is_tls: false,
-            proxy_used: None,
+            stream_info: StreamInfo::unpooled(),
             pool: None,
         }
     }
 
     pub(crate) fn new_with_completion_flag(
         inner: ChainedInner,
-        content_length: usize,
+        content_length: u64,
         completion_flag: Arc<AtomicBool>,
-        addr: SocketAddr,
-        is_tls: bool,
-        proxy_used: Option<HttpsProxyOption>,
+        stream_info: StreamInfo,
         pool: Option<ConnectionPool>,
     ) -> Self {
         Self {
             inner: Some(inner),
+            content_length,
             remaining: content_length,
             completion_flag,
-            addr,
-            is_tls,
-            proxy_used,
+            stream_info,
             pool,
         }
     }
@@ -356,15 +500,13 @@ impl BodyFixedLengthStream {
         self.completion_flag.load(Ordering::Relaxed)
     }
 
+    /// Without a pool the connection stays in `inner`, for
+    /// [`Response::take_drained_connection`] to take back.
     fn return_stream_to_pool(&mut self) {
-        if let (Some(chain), Some(pool)) = (self.inner.take(), self.pool.as_ref()) {
-            let stream = chain.into_second();
-            let stream_info = crate::client::StreamInfo {
-                addr: self.addr,
-                is_tls: self.is_tls,
-                proxy_used: self.proxy_used.clone(),
-            };
-            pool.return_stream(stream, stream_info);
+        if let Some(pool) = &self.pool
+            && let Some(chain) = self.inner.take()
+        {
+            pool.return_stream(chain.into_second(), self.stream_info.clone());
         }
     }
 }
@@ -381,7 +523,8 @@ impl async_std::io::Read for BodyFixedLengthStream {
             return std::task::Poll::Ready(Ok(0));
         }
 
-        let to_read = std::cmp::min(buf.len(), self.remaining);
+        // At most `buf.len()`, so it fits a usize.
+        let to_read = self.remaining.min(buf.len() as u64) as usize;
         if to_read == 0 {
             self.completion_flag.store(true, Ordering::Relaxed);
             self.return_stream_to_pool();
@@ -394,14 +537,14 @@ impl async_std::io::Read for BodyFixedLengthStream {
                     if n == 0 {
                         return std::task::Poll::Ready(Err(std::io::Error::new(
                             std::io::ErrorKind::UnexpectedEof,
-                            format!(
-                                "unexpected end of stream: {} bytes remaining of declared Content-Length",
-                                self.remaining
-                            ),
+                            TruncatedBody {
+                                expected: self.content_length,
+                                received: self.content_length - self.remaining,
+                            },
                         )));
                     }
 
-                    self.remaining -= n;
+                    self.remaining -= n as u64;
                     if self.remaining == 0 {
                         self.completion_flag.store(true, Ordering::Relaxed);
                         self.return_stream_to_pool();
@@ -413,10 +556,9 @@ impl async_std::io::Read for BodyFixedLengthStream {
                 std::task::Poll::Pending => std::task::Poll::Pending,
             }
         } else {
-            return std::task::Poll::Ready(Err(std::io::Error::new(
-                std::io::ErrorKind::Other,
+            std::task::Poll::Ready(Err(std::io::Error::other(
                 "stream is None",
-            )));
+            )))
         }
     }
 }
@@ -425,17 +567,13 @@ impl BodyUnknownLengthStream {
     pub(crate) fn new_with_completion_flag(
         inner: ChainedInner,
         completion_flag: Arc<AtomicBool>,
-        addr: SocketAddr,
-        is_tls: bool,
-        proxy_used: Option<HttpsProxyOption>,
+        stream_info: StreamInfo,
         pool: Option<ConnectionPool>,
     ) -> Self {
         Self {
             inner: Some(inner),
             completion_flag,
-            addr,
-            is_tls,
-            proxy_used,
+            stream_info,
             pool,
         }
     }
@@ -444,15 +582,13 @@ impl BodyUnknownLengthStream {
         self.completion_flag.load(Ordering::Relaxed)
     }
 
+    /// Without a pool the connection stays in `inner`, for
+    /// [`Response::take_drained_connection`] to take back.
     fn return_stream_to_pool(&mut self) {
-        if let (Some(chain), Some(pool)) = (self.inner.take(), self.pool.as_ref()) {
-            let stream = chain.into_second();
-            let stream_info = crate::client::StreamInfo {
-                addr: self.addr,
-                is_tls: self.is_tls,
-                proxy_used: self.proxy_used.clone(),
-            };
-            pool.return_stream(stream, stream_info);
+        if let Some(pool) = &self.pool
+            && let Some(chain) = self.inner.take()
+        {
+            pool.return_stream(chain.into_second(), self.stream_info.clone());
         }
     }
 }
@@ -479,20 +615,35 @@ impl async_std::io::Read for BodyUnknownLengthStream {
                 std::task::Poll::Pending => std::task::Poll::Pending,
             }
         } else {
-            std::task::Poll::Ready(Err(std::io::Error::new(
-                std::io::ErrorKind::Other,
+            std::task::Poll::Ready(Err(std::io::Error::other(
                 "stream is None",
             )))
         }
     }
 }
 
+/// The body as lossy UTF-8, cut after 200 bytes for error messages.
+fn body_preview(bytes: &[u8]) -> String {
+    if bytes.len() > 200 {
+        format!(
+            "{}...(truncated, total {} bytes)",
+            String::from_utf8_lossy(&bytes[..200]),
+            bytes.len()
+        )
+    } else {
+        String::from_utf8_lossy(bytes).into_owned()
+    }
+}
+
 pub struct Response {
+    /// Address the response came from: the pooled connection's, or the
+    /// resolved address `send()` dialled (see
+    /// [`AddressSelection`](crate::resolver::AddressSelection)).
     pub addr: SocketAddr,
     pub is_tls: bool,
     pub http_version: HttpVersion,
     pub status_code: u16,
-    pub headers: HashMap<String, IndexSet<String>>,
+    pub headers: HeaderMap,
     /// If you use this raw stream directly, call mark_body_read_complete() when done
     /// If you use body_managed_stream() instead, the returned wrapper handles this automatically
     pub body_raw_stream: Option<BoxedStream>,
@@ -512,75 +663,215 @@ pub struct Response {
     /// Whether the server indicated the connection can be reused.
     /// False when the response contained `Connection: close`.
     keep_alive: bool,
+    /// When the request started being sent (`request_time` in RFC 7234 §4.2.3).
+    /// Equals `response_time` when the response was not produced by `send()`.
+    pub request_time: SystemTime,
+    /// When the response head was received (`response_time` in RFC 7234 §4.2.3).
+    pub response_time: SystemTime,
+    /// Settings the request was sent with; `None` when the response was not
+    /// produced by the client.
+    pub effective_config: Option<EffectiveConfig>,
+    /// Body type the request expected, copied from `Request::expected_body`.
+    pub expected_body: Option<ExpectedBody>,
+    /// Copied from `Request::lenient_content_type`.
+    pub lenient_content_type: bool,
+    /// Copy of `Request::extensions`; empty when the response was not produced by `send()`.
+    pub extensions: Extensions,
+    /// Whether `send()` got this response over a pooled connection rather
+    /// than one it had to open.
+    pub reused_connection: bool,
+    /// Id of the connection the response arrived on, unique within the
+    /// client's pool. `None` when the response was not produced by `send()`.
+    pub connection_id: Option<u64>,
+    /// URL the response was fetched from: the request's, or where the last
+    /// redirect `send()` followed led. `None` when the response was not
+    /// produced by `send()`.
+    pub url: Option<url::Url>,
+    /// Whether `send()` answered with a copy of an identical concurrent
+    /// request's response instead of sending, see [`crate::single_flight`].
+    pub deduplicated: bool,
+    /// Whether the server answered, and closed the connection, before
+    /// `send()` could write the whole request body; e.g. a 413 for an upload
+    /// that is too large. The connection is not reused.
+    pub request_body_truncated: bool,
+    /// Read the body as raw bytes up to EOF, see `Request::raw_body_framing`.
+    raw_body_framing: bool,
+    /// No body whatever the framing headers say, see
+    /// [`methods::response_has_body`](crate::methods::response_has_body).
+    bodiless: bool,
+    /// A 2xx to CONNECT: the connection is a tunnel, see [`Response::is_upgraded`].
+    tunnel: bool,
+    /// `host:port` the request was sent to; part of the pool key for TLS and
+    /// proxied connections. Empty when the response was not produced by `send()`.
+    pub(crate) origin: String,
+    /// See [`Response::sent_request`].
+    pub(crate) sent_request: Option<SentRequest>,
+    /// See [`Response::interim_headers`].
+    pub(crate) interim: Vec<InterimResponse>,
+    /// Body bytes read by [`Response::peek_body`] and not handed out yet.
+    peeked: Vec<u8>,
+    /// The framed body stream `peeked` was read from; body consumers read
+    /// `peeked` first, then this.
+    peeked_stream: Option<crate::stream::ReadStream>,
+    /// Nanoseconds the request and response bodies spent waiting for
+    /// bandwidth, see [`Response::throttled`].
+    pub(crate) throttled: Arc<AtomicU64>,
+    /// Paces the body when a download rate applies.
+    pub(crate) download_throttle: Option<Throttle>,
+    /// The client's memory budget, which buffering body readers reserve
+    /// against; see [`crate::memory_budget`].
+    pub(crate) memory_budget: Option<BudgetGate>,
+    /// Holds `peeked` against the memory budget.
+    peek_reservation: Option<Reservation>,
+    /// The total timeout and when it runs out, set by `send()` when one
+    /// applies; body reads stop there.
+    pub(crate) total_deadline: Option<(Duration, Instant)>,
+}
+
+/// `body_bytes` reserves at most this much from `Content-Length` before
+/// reading; a longer body grows the buffer as it arrives.
+pub const BODY_PREALLOC_MAX: usize = 16 * 1024 * 1024;
+
+/// Bounds for the initial body buffers of a response: the `body_bytes`
+/// preallocation when the length is not known up front, and each read of
+/// `bytes_stream`. Within them the size follows a decaying average of the
+/// body sizes recently received from the same origin, see
+/// [`ZJHttpClient::learned_body_size`](crate::client::ZJHttpClient::learned_body_size).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BodyBufferBounds {
+    pub min: usize,
+    pub max: usize,
+}
+
+impl Default for BodyBufferBounds {
+    /// 8 KiB to 1 MiB.
+    fn default() -> Self {
+        BodyBufferBounds { min: 8 * 1024, max: 1024 * 1024 }
+    }
+}
+
+impl BodyBufferBounds {
+    /// The buffer size for an origin whose learned body size is `learned`;
+    /// `min` when nothing was learned yet.
+    pub fn size_for(&self, learned: Option<u64>) -> usize {
+        let learned = learned.map_or(0, |n| usize::try_from(n).unwrap_or(usize::MAX));
+        learned.min(self.max).max(self.min)
+    }
+}
+
+/// Longest body `body_bytes` buffers: the most one allocation can hold.
+const MAX_BUFFERED_BODY: u64 = isize::MAX as u64;
+
+/// A dropped response whose unread body is at most this long is drained in
+/// the background so its connection can be pooled.
+pub(crate) const DRAIN_ON_DROP_MAX: u64 = 64 * 1024;
+
+/// How long a background drain may take before the connection is closed.
+pub(crate) const DRAIN_DEADLINE: Duration = Duration::from_secs(1);
+
+/// Read `stream` to its end, which pools the connection when it is a framed
+/// body stream. False when it fails, runs past [`DRAIN_ON_DROP_MAX`] bytes or
+/// takes longer than [`DRAIN_DEADLINE`].
+async fn drain(stream: &mut (impl async_std::io::Read + Unpin)) -> bool {
+    let read_to_end = async {
+        let mut buf = [0u8; 8192];
+        let mut drained = 0u64;
+        while drained <= DRAIN_ON_DROP_MAX {
+            match stream.read(&mut buf).await {
+                Ok(0) => return true,
+                Ok(n) => drained += n as u64,
+                Err(_) => return false,
+            }
+        }
+        false
+    };
+    async_std::future::timeout(DRAIN_DEADLINE, read_to_end).await.unwrap_or(false)
+}
+
+/// When a body read has to be done by: the read body timeout from the start
+/// of the read, or the end of the total timeout if that comes first.
+#[derive(Clone, Copy)]
+struct BodyDeadline {
+    at: Instant,
+    duration: Duration,
+    total: bool,
+}
+
+impl BodyDeadline {
+    fn error(&self) -> ZjhttpcError {
+        if self.total {
+            TotalTimeoutSnafu { duration: self.duration }.build()
+        } else {
+            ReadBodyTimeoutSnafu { duration: self.duration }.build()
+        }
+    }
+}
+
+/// Run the body read `read`, failing with the timeout error of `deadline`
+/// when it passes first.
+async fn within<T>(deadline: Option<BodyDeadline>, read: impl Future<Output = Result<T>>) -> Result<T> {
+    match deadline {
+        Some(deadline) => async_std::future::timeout(deadline.at.saturating_duration_since(Instant::now()), read)
+            .await
+            .unwrap_or_else(|_| Err(deadline.error())),
+        None => read.await,
+    }
 }
 
 impl Drop for Response {
     fn drop(&mut self) {
-        if self.keep_alive
-            && self.body_completion_flag.load(Ordering::Relaxed)
-            && let (Some(stream), Some(pool)) = (self.body_raw_stream.take(), self.pool.as_ref())
-        {
-            let stream_info = crate::client::StreamInfo {
-                addr: self.addr,
-                is_tls: self.is_tls,
-                proxy_used: self.proxy_used.clone(),
-            };
-            pool.return_stream(stream, stream_info);
+        let Some(pool) = self.pool.clone() else {
+            return;
+        };
+        if !self.keep_alive {
+            self.close_connection(&pool);
+            return;
         }
+        // A response without a body is complete as soon as its head is read,
+        // and so is one whose whole Content-Length came in with the head.
+        if self.bodiless || self.body_completion_flag.load(Ordering::Relaxed) || self.body_fully_buffered() {
+            if let Some(stream) = self.body_raw_stream.take() {
+                pool.return_stream(stream, self.stream_info());
+            }
+            return;
+        }
+        self.drain_in_background(&pool);
     }
 }
 
 impl Response {
+    #[allow(clippy::too_many_arguments)]
     pub(crate) fn new_from_parse_result(
-        http_version: &str,
-        status_code: &str,
-        headers_vec: Vec<(String, String)>,
+        http_version: HttpVersion,
+        status_code: u16,
+        headers: HeaderMap,
         stream: BoxedStream,
         is_tls: bool,
         addr: SocketAddr,
-        proxy_used: Option<HttpsProxyOption>,
-        read_body_timeout: Option<std::time::Duration>,
+        config: &EffectiveConfig,
         body_prefix: &[u8],
         pool: Option<ConnectionPool>,
-    ) -> std::result::Result<Self, ZjhttpcError> {
-        let http_version = match http_version {
-            "1.1" => HttpVersion::V1_1,
-            "1.0" => HttpVersion::V1_0,
-            others => {
-                return Err(InvalidResponseSnafu { message: format!("unsupported HTTP version: {others}") }.build());
-            }
-        };
-        let status_code: u16 = status_code.parse().map_err(|_| {
-            InvalidResponseSnafu { message: format!("invalid HTTP status code: {status_code}") }.build()
-        })?;
-        let mut headers: HashMap<String, IndexSet<String>> = HashMap::new();
-        for (key, value) in headers_vec {
-            match headers.get_mut(&key) {
-                Some(set) => {
-                    set.insert(value);
-                }
-                None => {
-                    let mut set = IndexSet::new();
-                    set.insert(value);
-                    headers.insert(key, set);
-                }
-            };
-        }
+        method: &str,
+    ) -> Self {
         let mut prefix_buf = [0u8; 4096];
         let prefix_len = body_prefix.len().min(4096);
         prefix_buf[..prefix_len].copy_from_slice(&body_prefix[..prefix_len]);
 
         // Per RFC 7230 §6.6: a connection token of "close" means the connection
         // must not be reused. HTTP/1.0 defaults to close unless "keep-alive" is sent.
-        let conn_value = headers.get("connection").and_then(|s| s.first());
-        let keep_alive = match (&http_version, conn_value) {
+        // After 101, or a 2xx to CONNECT, the connection speaks another
+        // protocol and is never pooled.
+        let tunnel = method == crate::methods::CONNECT && (200..300).contains(&status_code);
+        let conn_value = headers.get("connection");
+        let keep_alive = status_code != 101 && !tunnel && match (&http_version, conn_value) {
             (HttpVersion::V1_1, Some(v)) => !v.to_ascii_lowercase().contains("close"),
             (HttpVersion::V1_0, Some(v)) => v.to_ascii_lowercase().contains("keep-alive"),
             (HttpVersion::V1_1, None) => true,
             (HttpVersion::V1_0, None) => false,
         };
 
-        let resp = Response {
+        let now = SystemTime::now();
+        Response {
             is_tls,
             http_version,
             status_code,
@@ -589,14 +880,111 @@ impl Response {
             body_prefix: prefix_buf,
             body_prefix_len: prefix_len,
             addr,
-            proxy_used,
+            proxy_used: config.proxy.clone(),
             body_completion_flag: Arc::new(AtomicBool::new(false)),
-            read_body_timeout,
+            read_body_timeout: config.read_body_timeout,
             pool,
             keep_alive,
+            request_time: now,
+            response_time: now,
+            effective_config: Some(config.clone()),
+            expected_body: None,
+            lenient_content_type: false,
+            extensions: Extensions::new(),
+            reused_connection: false,
+            connection_id: None,
+            url: None,
+            deduplicated: false,
+            request_body_truncated: false,
+            raw_body_framing: false,
+            bodiless: !crate::methods::response_has_body(method, status_code),
+            tunnel,
+            origin: String::new(),
+            sent_request: None,
+            interim: Vec::new(),
+            peeked: Vec::new(),
+            peeked_stream: None,
+            throttled: Arc::default(),
+            download_throttle: None,
+            memory_budget: None,
+            peek_reservation: None,
+            total_deadline: None,
+        }
+    }
+
+    /// A copy of this response that owns no connection, for single-flight
+    /// followers. Only call it once `peek_body` has buffered the whole body.
+    /// The copy's body is charged to the memory budget, failing with
+    /// `MemoryBudgetExceeded` when it does not fit right now.
+    pub(crate) fn buffered_copy(&self) -> Result<Response> {
+        let peek_reservation = match &self.memory_budget {
+            Some(budget) => Some(budget.try_reserve(self.peeked.len() as u64)?),
+            None => None,
         };
-        return Ok(resp);
+        Ok(Response {
+            addr: self.addr,
+            is_tls: self.is_tls,
+            http_version: self.http_version.clone(),
+            status_code: self.status_code,
+            headers: self.headers.clone(),
+            body_raw_stream: None,
+            body_prefix: [0u8; 4096],
+            body_prefix_len: 0,
+            proxy_used: self.proxy_used.clone(),
+            body_completion_flag: Arc::new(AtomicBool::new(true)),
+            read_body_timeout: self.read_body_timeout,
+            pool: None,
+            keep_alive: false,
+            request_time: self.request_time,
+            response_time: self.response_time,
+            effective_config: self.effective_config.clone(),
+            expected_body: self.expected_body,
+            lenient_content_type: self.lenient_content_type,
+            extensions: Extensions::new(),
+            reused_connection: self.reused_connection,
+            connection_id: self.connection_id,
+            url: self.url.clone(),
+            deduplicated: true,
+            request_body_truncated: self.request_body_truncated,
+            raw_body_framing: self.raw_body_framing,
+            bodiless: self.bodiless,
+            tunnel: self.tunnel,
+            origin: self.origin.clone(),
+            sent_request: None,
+            interim: self.interim.clone(),
+            peeked: self.peeked.clone(),
+            peeked_stream: Some(Box::new(async_std::io::Cursor::new(Vec::new()))),
+            throttled: Arc::default(),
+            download_throttle: None,
+            memory_budget: self.memory_budget.clone(),
+            peek_reservation,
+            total_deadline: None,
+        })
+    }
+
+    pub(crate) fn stream_info(&self) -> StreamInfo {
+        StreamInfo {
+            addr: self.addr,
+            is_tls: self.is_tls,
+            proxy_used: self.proxy_used.clone(),
+            origin: self.origin.clone(),
+            keep_alive_timeout: self.keep_alive_timeout(),
+        }
+    }
+
+    /// The `timeout` parameter of the `Keep-Alive` header: how long the
+    /// server keeps the connection open while idle. The pool closes the
+    /// connection after that long, or after its own idle timeout if shorter.
+    pub fn keep_alive_timeout(&self) -> Option<Duration> {
+        self.header_all("keep-alive")
+            .iter()
+            .flat_map(|value| value.split(','))
+            .filter_map(|param| param.split_once('='))
+            .find(|(name, _)| name.trim().eq_ignore_ascii_case("timeout"))
+            .and_then(|(_, secs)| secs.trim().parse::<u64>().ok())
+            .map(Duration::from_secs)
     }
+
     pub fn status_code(&self) -> u16 {
         self.status_code
     }
@@ -605,17 +993,109 @@ impl Response {
         (200u16..300u16).contains(&self.status_code)
     }
 
+    /// Time the request and response bodies spent paced by an upload or
+    /// download rate (see [`crate::throttle`]). Grows while the body is read.
+    pub fn throttled(&self) -> Duration {
+        throttled_time(&self.throttled)
+    }
+
+    /// Whether the server switched protocols (101), or accepted a CONNECT
+    /// with a 2xx and the connection is now a tunnel. Such a response has no
+    /// body: take the connection with [`into_upgraded_stream`](Self::into_upgraded_stream).
+    pub fn is_upgraded(&self) -> bool {
+        self.status_code == 101 || self.tunnel
+    }
+
+    /// Hand the connection of a 101 Switching Protocols response, or of a
+    /// 2xx to CONNECT, to the caller, including any bytes of the new protocol
+    /// that arrived with the response head. The connection is never returned
+    /// to the pool.
+    pub fn into_upgraded_stream(mut self) -> Result<BoxedStream> {
+        if !self.is_upgraded() {
+            return Err(NotUpgradedSnafu { status: self.status_code }.build());
+        }
+        let stream = self.body_raw_stream.take().ok_or_else(|| BodyAlreadyReadSnafu.build())?;
+        let prefix = &self.body_prefix[..self.body_prefix_len];
+        if prefix.is_empty() {
+            return Ok(stream);
+        }
+        Ok(Box::new(ChainRead::new(SliceRead::new(prefix), stream)))
+    }
+
+    /// See [`Response::request_body_truncated`].
+    pub(crate) fn mark_request_body_truncated(&mut self) {
+        self.request_body_truncated = true;
+        self.keep_alive = false;
+    }
+
+    /// Ignore the framing headers and never reuse the connection.
+    pub(crate) fn use_raw_body_framing(&mut self) {
+        self.raw_body_framing = true;
+        self.keep_alive = false;
+    }
+
+    /// The framing the body is read with: a response that has no body by
+    /// [`methods::response_has_body`](crate::methods::response_has_body) is
+    /// empty, `Transfer-Encoding: chunked` wins over `Content-Length`, and a
+    /// body with neither runs to EOF.
+    pub fn effective_encoding(&self) -> BodyFraming {
+        if self.bodiless {
+            return BodyFraming::ContentLength(0);
+        }
+        if self.raw_body_framing {
+            return BodyFraming::Raw;
+        }
+        let is_chunked = self
+            .headers
+            .get_all("transfer-encoding")
+            .map(|set| set.iter().any(|v| v.contains("chunked")))
+            .unwrap_or(false);
+        match self.content_length() {
+            _ if is_chunked => BodyFraming::Chunked,
+            Some(length) => BodyFraming::ContentLength(length),
+            None => BodyFraming::ReadToEof,
+        }
+    }
+
+    fn ensure_has_body(&self) -> Result<()> {
+        if self.is_upgraded() {
+            return Err(ProtocolSwitchedSnafu.build());
+        }
+        Ok(())
+    }
+
+    /// The 1xx responses received before this one, in order: the `100
+    /// Continue` of an `Expect: 100-continue` exchange, 103 Early Hints and
+    /// any other interim status. Empty for responses not produced by `send()`
+    /// (or `DuplexReader`). Interim heads past the client's header size limit
+    /// in total are not kept.
+    pub fn interim_headers(&self) -> &[InterimResponse] {
+        &self.interim
+    }
+
+    /// The request head as `send()` wrote it for this response; on a retry,
+    /// the head of the final attempt. `None` when capture was turned off with
+    /// `Request::set_sent_request_capture` or the response did not come from
+    /// `send()`.
+    pub fn sent_request(&self) -> Option<&SentRequest> {
+        self.sent_request.as_ref()
+    }
+
+    /// A value the request was sent with, see `Request::insert_ext`.
+    pub fn get_ext<T: Send + Sync + 'static>(&self) -> Option<&T> {
+        self.extensions.get()
+    }
+
+    /// First value of `header_name`, matched case-insensitively.
     pub fn header_one(&self, header_name: impl AsRef<str>) -> Option<&str> {
-        self.headers
-            .get(&header_name.as_ref().to_ascii_lowercase())
-            .map(|x| x.first().map(|x| x.as_str()))
-            .flatten()
+        self.headers.get(header_name)
     }
 
+    /// Every value of `key`, matched case-insensitively, in the order
+    /// received.
     pub fn header_all(&self, key: impl AsRef<str>) -> Vec<&str> {
-        let key = key.as_ref().to_ascii_lowercase();
         self.headers
-            .get(&key)
+            .get_all(key)
             .map(|set| set.iter().map(|s| s.as_str()).collect())
             .unwrap_or_default()
     }
@@ -644,50 +1124,68 @@ impl Response {
     }
 
     pub async fn body_string(&mut self) -> Result<String> {
-        if self.is_body_read_complete() {
-            return Err(BodyAlreadyReadSnafu.build());
-        }
-
-        if let Some(mut stream) = self.body_managed_stream() {
-            let mut bytes: Vec<u8> = Vec::new();
-            let mut buf = [0u8; 1024];
-
-            // Apply read body timeout if set
-            let read_future = async {
-                while let n = stream.read(&mut buf).await?
-                    && n > 0
-                {
-                    bytes.extend_from_slice(&buf[..n]);
-                }
-                Ok::<(), ZjhttpcError>(())
-            };
+        let bytes = self.body_bytes().await?;
 
-            if let Some(timeout) = self.read_body_timeout {
-                async_std::future::timeout(timeout, read_future)
-                    .await
-                    .map_err(|_| ReadBodyTimeoutSnafu { duration: timeout }.build())
-                    ??;
-            } else {
-                read_future.await?;
+        // considering the encoding
+        if self.text_encoding() == GBK {
+            let (cow, _encoding, had_errors) = GBK.decode(bytes.as_slice());
+            if had_errors {
+                debug!(target: BODY, "GBK decode with errors");
             }
+            Ok(cow.to_string())
+        } else {
+            Ok(String::from_utf8_lossy(&bytes).to_string())
+        }
+    }
 
-            // considering the encoding
-            if let Some(x) = self.headers.get("content-type")
-                && x.last()
-                    .map(|x| x.to_lowercase().contains("charset=gbk"))
-                    .unwrap_or(false)
-            {
-                let (cow, _encoding, had_errors) = GBK.decode(&bytes.as_slice());
-                if had_errors {
-                    error!("GBK decode with errors");
-                }
-                return Ok(cow.to_string());
-            } else {
-                return Ok(String::from_utf8_lossy(&bytes).to_string());
+    /// The charset `body_string` decodes with: GBK when the last
+    /// `Content-Type` says `charset=gbk`, UTF-8 otherwise.
+    fn text_encoding(&self) -> &'static encoding_rs::Encoding {
+        let gbk = self
+            .headers
+            .get_all("content-type")
+            .and_then(|values| values.last())
+            .is_some_and(|value| value.to_lowercase().contains("charset=gbk"));
+        if gbk { GBK } else { encoding_rs::UTF_8 }
+    }
+
+    /// The start of the body as text for logs: at most `max_bytes` bytes,
+    /// peeked with [`peek_body`](Self::peek_body) so the body is not consumed
+    /// and can still be read, streamed or dropped afterwards.
+    ///
+    /// The bytes are decoded with the charset `body_string` would use;
+    /// invalid sequences become U+FFFD and control characters (line breaks
+    /// included) become spaces or U+FFFD, so the preview fits on one line. A
+    /// body longer than `max_bytes` is cut before the first character that
+    /// does not fit and marked `...(truncated, total N bytes)`, with the total
+    /// from `Content-Length` when known. The bytes are shown as received,
+    /// before any `Content-Encoding` is undone.
+    pub async fn body_preview(&mut self, max_bytes: usize) -> Result<String> {
+        let encoding = self.text_encoding();
+        let peeked = self.peek_body(max_bytes.saturating_add(1)).await?;
+        let truncated = peeked.len() > max_bytes;
+        let bytes = &peeked[..peeked.len().min(max_bytes)];
+
+        let mut decoder = encoding.new_decoder_without_bom_handling();
+        let mut text = String::with_capacity(decoder.max_utf8_buffer_length(bytes.len()).unwrap_or(bytes.len()));
+        // When more follows, a character split by the cut stays in the
+        // decoder instead of turning into U+FFFD.
+        let _ = decoder.decode_to_string(bytes, &mut text, !truncated);
+        let mut preview: String = text
+            .chars()
+            .map(|c| match c {
+                '\t' | '\n' | '\r' => ' ',
+                c if c.is_control() => char::REPLACEMENT_CHARACTER,
+                c => c,
+            })
+            .collect();
+        if truncated {
+            match self.content_length() {
+                Some(total) => preview.push_str(&format!("...(truncated, total {total} bytes)")),
+                None => preview.push_str("...(truncated)"),
             }
-        } else {
-            return Ok(String::new());
         }
+        Ok(preview)
     }
 
     /// Returns a streaming response body with automatic completion tracking.
@@ -695,29 +1193,219 @@ impl Response {
     /// This function provides true streaming with proper connection pool management:
     /// - For chunked responses, it decodes chunks on-the-fly without buffering the entire body in memory
     /// - For responses with Content-Length, it returns a fixed-length stream that tracks remaining bytes  
-    /// - For other responses, it wraps the raw stream in BodyUnknownLengthStream for completion tracking
+    /// - For other responses, and with raw framing (see [`effective_encoding`](Self::effective_encoding)),
+    ///   it wraps the raw stream in BodyUnknownLengthStream for completion tracking
     ///
     /// # Important Notes
     ///
-    /// - Returns `None` if the body has already been read via `body_string()` or other methods.
+    /// - Returns `None` if the body has already been read via `body_string()` or other methods,
+    ///   and for a 101 Switching Protocols response.
     /// - For chunked transfer encoding responses, automatically decodes chunks as you read.
     /// - For responses with Content-Length header, returns a BodyFixedLengthStream that tracks remaining bytes.
     /// - For other responses, returns a BodyUnknownLengthStream that detects EOF and returns the connection to pool.
     /// - All wrapper streams automatically return the connection to the pool when fully consumed (EOF reached).
     /// - Once you use this stream, you become responsible for reading it completely.
     /// - If you don't read the stream completely, the connection may not be reusable.
+    /// - Bytes looked at with [`peek_body`](Self::peek_body) come first.
     pub fn body_managed_stream(&mut self) -> Option<crate::stream::ReadStream> {
-        if self.is_body_read_complete() {
+        if let Some(stream) = self.peeked_stream.take() {
+            // The peeked bytes go to the reader, which charges what it buffers itself.
+            self.peek_reservation = None;
+            let peeked = async_std::io::Cursor::new(std::mem::take(&mut self.peeked));
+            return Some(Box::new(crate::stream::ChainRead::new(peeked, stream)));
+        }
+        self.framed_body_stream()
+    }
+
+    /// Guess the body's type from its first [`SNIFF_LEN`](crate::content_type::SNIFF_LEN)
+    /// bytes, ignoring the `Content-Type` header. The bytes are peeked with
+    /// [`peek_body`](Self::peek_body), so the body can still be read in full
+    /// afterwards.
+    pub async fn sniff_content_type(&mut self) -> Result<SniffedType> {
+        let prefix = self.peek_body(content_type::SNIFF_LEN).await?;
+        Ok(content_type::sniff(prefix))
+    }
+
+    /// Look at the first `n` bytes of the body (fewer if the body is
+    /// shorter) without consuming them: `body_bytes`, `body_string`,
+    /// `body_managed_stream` and the other body readers still return the
+    /// whole body. Reading is framed like `body_managed_stream` and bounded
+    /// by the read body and total timeouts. Peeking again with a larger `n`
+    /// reads more.
+    ///
+    /// After a peek the body is only available through those readers, not
+    /// through `body_raw_stream`.
+    ///
+    /// With a [`memory_budget`](crate::client::ClientInner::memory_budget),
+    /// the `n` bytes are reserved before reading and held until the body is
+    /// read on or the response is dropped.
+    pub async fn peek_body(&mut self, n: usize) -> Result<&[u8]> {
+        self.ensure_has_body()?;
+        let mut stream = match self.peeked_stream.take() {
+            Some(stream) => stream,
+            None if self.is_body_read_complete() => return Err(BodyAlreadyReadSnafu.build()),
+            None => match self.framed_body_stream() {
+                Some(stream) => stream,
+                None => return Ok(&[]),
+            },
+        };
+        if let Err(e) = self.reserve_peek(n).await {
+            self.peeked_stream = Some(stream);
+            return Err(e);
+        }
+        let mut peeked = std::mem::take(&mut self.peeked);
+        let read = async {
+            while peeked.len() < n {
+                let start = peeked.len();
+                peeked.resize(n, 0);
+                let got = stream.read(&mut peeked[start..]).await;
+                peeked.truncate(start + *got.as_ref().unwrap_or(&0));
+                if got? == 0 {
+                    break;
+                }
+            }
+            Ok::<(), ZjhttpcError>(())
+        };
+        let result = within(self.body_deadline(), read).await;
+        self.peeked = peeked;
+        self.peeked_stream = Some(stream);
+        result.map_err(|e| e.with_phase(RequestPhase::ReadBody))?;
+        Ok(&self.peeked[..n.min(self.peeked.len())])
+    }
+
+    /// Grow the reservation of `peeked` to `n` bytes, waiting for room only
+    /// for the first peek.
+    async fn reserve_peek(&mut self, n: usize) -> Result<()> {
+        let Some(budget) = &self.memory_budget else {
+            return Ok(());
+        };
+        match &mut self.peek_reservation {
+            Some(reservation) => reservation.grow_to(n as u64),
+            None => {
+                self.peek_reservation = Some(budget.reserve(n as u64).await?);
+                Ok(())
+            }
+        }
+    }
+
+    /// Read off a small unread body on a detached task, which pools the
+    /// connection once the body ends (the framed stream does that). Drop
+    /// cannot wait, so the connection is simply closed instead when the body
+    /// is larger than [`DRAIN_ON_DROP_MAX`], has no deterministic end, takes
+    /// longer than [`DRAIN_DEADLINE`], or the pool's drain slots are taken.
+    /// Closed cleanly where the connection is still at hand, see
+    /// [`close_connection`](Self::close_connection).
+    fn drain_in_background(&mut self, pool: &ConnectionPool) {
+        if !self.is_drainable() || (self.peeked_stream.is_none() && self.body_raw_stream.is_none()) {
+            self.close_connection(pool);
+            return;
+        }
+        let Some(slot) = pool.start_drain() else {
+            debug!(target: BODY, "too many drains running, closing connection of dropped response");
+            self.close_connection(pool);
+            return;
+        };
+        let Some(mut stream) = self.peeked_stream.take().or_else(|| self.unpaced_body_stream()) else {
+            return;
+        };
+        async_std::task::spawn(async move {
+            let _slot = slot;
+            if drain(&mut stream).await {
+                debug!(target: BODY, "drained dropped response, connection pooled");
+            } else {
+                debug!(target: BODY, "could not drain dropped response, closing connection");
+            }
+        });
+    }
+
+    /// Read off the unread body now and hand the connection back instead of
+    /// pooling it: `send()` does this between redirect hops, and carries the
+    /// connection straight into the next hop when that goes to the same pool
+    /// key. `None` when the connection is not reusable, or when the body is
+    /// one [`drain_in_background`](Self::drain_in_background) would not drain
+    /// and is left for `Drop` to deal with.
+    pub(crate) async fn take_drained_connection(&mut self) -> Option<(BoxedStream, StreamInfo)> {
+        if self.pool.is_none() || !self.keep_alive || self.peeked_stream.is_some() || self.is_upgraded() {
+            return None;
+        }
+        if self.bodiless || self.is_body_read_complete() || self.body_fully_buffered() {
+            return self.body_raw_stream.take().map(|stream| (stream, self.stream_info()));
+        }
+        if !self.is_drainable() {
             return None;
         }
+        let stream = self.body_raw_stream.take()?;
+        let chain = ChainRead::new(SliceRead::new(&self.body_prefix[..self.body_prefix_len]), stream);
+        let flag = self.body_completion_flag.clone();
+        let (drained, inner) = match self.effective_encoding() {
+            BodyFraming::Chunked => {
+                let mut decoder = ChunkedDecoderStream::new_with_completion_flag(chain, flag, self.stream_info(), None);
+                (drain(&mut decoder).await, decoder.inner.take())
+            }
+            BodyFraming::ContentLength(length) => {
+                let mut body = BodyFixedLengthStream::new_with_completion_flag(chain, length, flag, self.stream_info(), None);
+                (drain(&mut body).await, body.inner.take())
+            }
+            BodyFraming::ReadToEof | BodyFraming::Raw => return None,
+        };
+        match inner {
+            Some(chain) if drained => {
+                debug!(target: BODY, "drained redirect body, connection carried to the next hop");
+                Some((chain.into_second(), self.stream_info()))
+            }
+            _ => {
+                debug!(target: BODY, "could not drain redirect body, closing connection");
+                None
+            }
+        }
+    }
 
-        let is_chunked = self
-            .headers
-            .get("transfer-encoding")
-            .map(|set| set.iter().any(|v| v.contains("chunked")))
-            .unwrap_or(false);
+    /// Whether the body has a deterministic end no more than
+    /// [`DRAIN_ON_DROP_MAX`] bytes away.
+    fn is_drainable(&self) -> bool {
+        match self.effective_encoding() {
+            BodyFraming::ContentLength(length) => length.saturating_sub(self.body_prefix_len as u64) <= DRAIN_ON_DROP_MAX,
+            BodyFraming::Chunked => true,
+            BodyFraming::ReadToEof | BodyFraming::Raw => false,
+        }
+    }
+
+    /// Close the connection of a dropped response that will not be pooled
+    /// with TLS `close_notify` or a TCP shutdown, in the background. A
+    /// connection already wrapped by a body stream (peeked, or handed to a
+    /// drain that gave up) is dropped as it is.
+    fn close_connection(&mut self, pool: &ConnectionPool) {
+        if let Some(stream) = self.body_raw_stream.take() {
+            pool.close(stream);
+        }
+    }
+
+    /// The unread body is all in `body_prefix`: nothing of it is left on
+    /// the connection, which is at the next response already.
+    fn body_fully_buffered(&self) -> bool {
+        self.peeked_stream.is_none()
+            && matches!(self.effective_encoding(), BodyFraming::ContentLength(length) if length <= self.body_prefix_len as u64)
+    }
+
+    /// No body left to hand out: fully read, and no peeked bytes pending.
+    fn is_body_consumed(&self) -> bool {
+        self.peeked_stream.is_none() && self.is_body_read_complete()
+    }
+
+    fn framed_body_stream(&mut self) -> Option<crate::stream::ReadStream> {
+        let stream = self.unpaced_body_stream()?;
+        Some(match &self.download_throttle {
+            Some(throttle) => Box::new(throttle.wrap(stream)),
+            None => stream,
+        })
+    }
+
+    fn unpaced_body_stream(&mut self) -> Option<crate::stream::ReadStream> {
+        if self.is_body_read_complete() || self.is_upgraded() {
+            return None;
+        }
 
-        let content_length = self.content_length();
+        let framing = self.effective_encoding();
 
         if let Some(stream) = self.body_raw_stream.take() {
             let prefix = &self.body_prefix[..self.body_prefix_len];
@@ -727,33 +1415,28 @@ impl Response {
             // terminates on EOF, which means the peer has already closed the socket
             // — handing that stream back would give the next request a dead
             // connection (Broken pipe / EOF on retry).
-            let pool = if self.keep_alive && (is_chunked || content_length.is_some()) {
-                self.pool.clone()
-            } else {
-                None
+            let pool = match framing {
+                BodyFraming::Chunked | BodyFraming::ContentLength(_) if self.keep_alive => self.pool.clone(),
+                _ => None,
             };
-            if is_chunked {
+            if framing == BodyFraming::Chunked {
                 let chain =
                     crate::stream::ChainRead::new(crate::stream::SliceRead::new(prefix), stream);
                 let decoder = ChunkedDecoderStream::new_with_completion_flag(
                     chain,
                     self.body_completion_flag.clone(),
-                    self.addr,
-                    self.is_tls,
-                    self.proxy_used.clone(),
+                    self.stream_info(),
                     pool,
                 );
                 Some(Box::new(decoder) as crate::stream::ReadStream)
-            } else if let Some(length) = content_length {
+            } else if let BodyFraming::ContentLength(length) = framing {
                 let chain =
                     crate::stream::ChainRead::new(crate::stream::SliceRead::new(prefix), stream);
                 let fixed_length_stream = BodyFixedLengthStream::new_with_completion_flag(
                     chain,
-                    length as usize,
+                    length,
                     self.body_completion_flag.clone(),
-                    self.addr,
-                    self.is_tls,
-                    self.proxy_used.clone(),
+                    self.stream_info(),
                     pool,
                 );
                 Some(Box::new(fixed_length_stream) as crate::stream::ReadStream)
@@ -763,9 +1446,7 @@ impl Response {
                 let unknown_length_stream = BodyUnknownLengthStream::new_with_completion_flag(
                     chain,
                     self.body_completion_flag.clone(),
-                    self.addr,
-                    self.is_tls,
-                    self.proxy_used.clone(),
+                    self.stream_info(),
                     pool,
                 );
                 Some(Box::new(unknown_length_stream) as crate::stream::ReadStream)
@@ -775,6 +1456,47 @@ impl Response {
         }
     }
 
+    /// The body as a reader, for copying large downloads wherever they go
+    /// without holding them in memory (`async_std::io::copy`). It ends at the
+    /// end of the body — Content-Length bounded, chunked-decoded or EOF-framed
+    /// as in [`body_managed_stream`](Self::body_managed_stream) — never reads
+    /// past it, and reaching that end marks the body read and hands the
+    /// connection back to the pool. Dropping the reader earlier closes the
+    /// connection. Neither the read body timeout nor the total timeout
+    /// applies; bound reads with `async_std::io::timeout` if needed.
+    ///
+    /// The body is decoded, limited and checked as described in
+    /// [`body_bytes`](Self::body_bytes); those errors come out of the reader
+    /// as `io::Error`s that convert back into the `ZjhttpcError` with `?`.
+    ///
+    /// Fails with `BodyAlreadyRead` once the body has been taken by any
+    /// reader, even one that has not finished it, and with
+    /// `ProtocolSwitched` for a 101 response.
+    pub fn body_reader(&mut self) -> Result<impl async_std::io::Read + Unpin + Send + Sync + 'static> {
+        self.ensure_has_body()?;
+        // `None` also when another reader took the body and has not finished it.
+        self.body_pipeline_reader(u64::MAX).context(BodyAlreadyReadSnafu)
+    }
+
+    /// The body through the stages of `crate::body_pipeline`, decoding at
+    /// most `cap` bytes; `None` when [`body_managed_stream`](Self::body_managed_stream) is.
+    fn body_pipeline_reader(&mut self, cap: u64) -> Option<PipelineReader<crate::stream::ReadStream>> {
+        let pipeline = BodyPipeline::for_response(self, cap);
+        self.body_managed_stream().map(|stream| self.pipeline_reader(stream, pipeline))
+    }
+
+    fn pipeline_reader(
+        &self,
+        stream: crate::stream::ReadStream,
+        pipeline: BodyPipeline,
+    ) -> PipelineReader<crate::stream::ReadStream> {
+        let reader = PipelineReader::new(stream, pipeline);
+        match (&self.pool, self.connection_id) {
+            (Some(pool), Some(id)) => reader.closing_bombs(pool.clone(), id),
+            _ => reader,
+        }
+    }
+
     /// Returns a streaming Server-Sent Events parser over the response body.
     ///
     /// Internally calls [`body_managed_stream`](Self::body_managed_stream) and
@@ -792,61 +1514,366 @@ impl Response {
         self.body_managed_stream().map(crate::sse::SseStream::new)
     }
 
-    /// Read the entire body and return it as bytes
+    /// Read the entire body and return it as bytes, exactly as received
+    /// (chunked framing removed), for binary downloads.
     ///
     /// This method consumes the response body and reads all data into memory.
+    /// The buffer is sized from `Content-Length` up front, up to
+    /// [`BODY_PREALLOC_MAX`] so a bogus length cannot reserve arbitrary memory.
+    /// A body longer than one allocation can hold on this platform (`isize::MAX`
+    /// bytes, 2 GiB on 32-bit targets) fails with `BodyTooLarge` — up front
+    /// when `Content-Length` says so, otherwise once that much has arrived.
     /// For large bodies, consider using body_managed_stream() for streaming access.
+    ///
+    /// On a client built with `set_auto_decompress(true)`, a gzip or deflate
+    /// body is returned decoded (see [`crate::decompress`]); one that does not
+    /// decode fails with `Decompress`. A decoded body longer than the max body
+    /// size (`Request::set_max_body_bytes`) fails with `BodyTooLarge`, and one
+    /// that does not match its `Content-MD5` (with `set_verify_content_md5`)
+    /// or the request's `set_expected_sha256` with `ChecksumMismatch`.
+    /// `Content-MD5` covers the body as received, still encoded; the size
+    /// limit and the SHA-256 apply to the decoded body.
+    ///
+    /// With a [`memory_budget`](crate::client::ClientInner::memory_budget),
+    /// the body is reserved before reading and the read fails with
+    /// `MemoryBudgetExceeded` when there is no room; see
+    /// [`crate::memory_budget`].
     pub async fn body_bytes(&mut self) -> Result<Vec<u8>> {
-        if self.is_body_read_complete() {
+        self.body_bytes_up_to(MAX_BUFFERED_BODY).await
+    }
+
+    async fn body_bytes_up_to(&mut self, limit: u64) -> Result<Vec<u8>> {
+        self.ensure_has_body()?;
+        if self.is_body_consumed() {
             return Err(BodyAlreadyReadSnafu.build());
         }
 
-        if let Some(mut stream) = self.body_managed_stream() {
-            let mut bytes: Vec<u8> = Vec::new();
-            let mut buf = [0u8; 8192]; // 8KB buffer
+        let mut pipeline = BodyPipeline::for_response(self, limit);
+        let limit = pipeline.limit();
+        let (capacity, reserved) = match self.effective_encoding() {
+            // Without decoding the length on the wire is the length handed out.
+            BodyFraming::ContentLength(length) if length > limit && !pipeline.decodes() => {
+                return Err(BodyTooLargeSnafu { length: Some(length), limit }.build());
+            }
+            BodyFraming::ContentLength(length) if !pipeline.decodes() => {
+                (length.min(BODY_PREALLOC_MAX as u64) as usize, length)
+            }
+            BodyFraming::ContentLength(length) => {
+                let capacity = length.min(limit).min(BODY_PREALLOC_MAX as u64) as usize;
+                (capacity, capacity as u64)
+            }
+            _ => {
+                let capacity = self.body_buffer_size().min(usize::try_from(limit).unwrap_or(usize::MAX));
+                (capacity, capacity as u64)
+            }
+        };
+        if let Some(reservation) = self.reserve_body(reserved).await? {
+            pipeline.charge_to(reservation);
+        }
+        if let Some(stream) = self.body_managed_stream() {
+            let mut reader = self.pipeline_reader(stream, pipeline);
+            let mut bytes: Vec<u8> = Vec::with_capacity(capacity);
 
-            // Apply read body timeout if set
+            // Bounded by the read body timeout and the total timeout, if set
             let read_future = async {
-                while let n = stream.read(&mut buf).await?
-                    && n > 0
-                {
-                    bytes.extend_from_slice(&buf[..n]);
-                }
+                reader.read_to_end(&mut bytes).await?;
                 Ok::<(), ZjhttpcError>(())
             };
 
-            if let Some(timeout) = self.read_body_timeout {
-                async_std::future::timeout(timeout, read_future)
-                    .await
-                    .map_err(|_| ReadBodyTimeoutSnafu { duration: timeout }.build())
-                    ??;
-            } else {
-                read_future.await?;
-            }
+            within(self.body_deadline(), read_future)
+                .await
+                .map_err(|e| e.with_phase(RequestPhase::ReadBody))?;
 
+            self.record_body_size(reader.pipeline().wire_len());
             Ok(bytes)
         } else {
             Ok(Vec::new())
         }
     }
 
-    // reading the entire body and return a JSON object
+    /// Reserve `bytes` of the memory budget for buffering the body, taking
+    /// over the reservation of any peeked bytes, which are part of it.
+    async fn reserve_body(&mut self, bytes: u64) -> Result<Option<Reservation>> {
+        let Some(budget) = &self.memory_budget else {
+            return Ok(None);
+        };
+        match &mut self.peek_reservation {
+            Some(peeked) => peeked.grow_to(bytes).map(|()| self.peek_reservation.take()),
+            None => budget.reserve(bytes).await.map(Some),
+        }
+    }
+
+    /// The body as a stream of chunks, each holding what one read of the
+    /// underlying body produced (at most the origin's body buffer size, see
+    /// [`BodyBufferBounds`]). Framing is handled as in
+    /// [`body_managed_stream`](Self::body_managed_stream): Content-Length bounded,
+    /// chunked-decoded or EOF-framed.
+    ///
+    /// Consuming the stream to its end completes the body and lets the
+    /// connection go back to the pool; dropping it earlier leaves the
+    /// connection unused. The read body timeout, if set, bounds the whole
+    /// stream from the moment this is called, and the total timeout, if
+    /// `send()` had one, still runs. Chunks are decoded, limited and checked
+    /// as in [`body_bytes`](Self::body_bytes); a checksum mismatch is the
+    /// last item.
+    pub fn bytes_stream(&mut self) -> impl futures::Stream<Item = Result<Vec<u8>>> + '_ {
+        let unreadable = if self.is_upgraded() {
+            Some(ProtocolSwitchedSnafu.build())
+        } else if self.is_body_consumed() {
+            Some(BodyAlreadyReadSnafu.build())
+        } else {
+            None
+        };
+        let body = if unreadable.is_some() { None } else { self.body_pipeline_reader(u64::MAX) };
+        let deadline = self.body_deadline();
+        let buffer_size = self.body_buffer_size();
+        // Where the body size is recorded once the stream ends.
+        let sizes = self.pool.clone().filter(|_| !self.origin.is_empty()).map(|pool| (pool, self.origin.clone()));
+        let state = (body, unreadable, sizes);
+        futures::stream::unfold(state, move |(body, unreadable, sizes)| async move {
+            if let Some(err) = unreadable {
+                return Some((Err(err), (None, None, None)));
+            }
+            let mut body = body?;
+            let mut buf = vec![0u8; buffer_size];
+            let read = body.read(&mut buf);
+            let result = within(deadline, async { read.await.map_err(ZjhttpcError::from) }).await;
+            match result {
+                Ok(0) => {
+                    if let Some((pool, origin)) = sizes {
+                        pool.record_body_size(&origin, body.pipeline().wire_len());
+                    }
+                    None
+                }
+                Ok(n) => {
+                    buf.truncate(n);
+                    Some((Ok(buf), (Some(body), None, sizes)))
+                }
+                Err(e) => Some((Err(e.with_phase(RequestPhase::ReadBody)), (None, None, None))),
+            }
+        })
+    }
+
+    /// Deadline for a body read starting now, see [`BodyDeadline`].
+    fn body_deadline(&self) -> Option<BodyDeadline> {
+        let read = self
+            .read_body_timeout
+            .map(|duration| BodyDeadline { at: Instant::now() + duration, duration, total: false });
+        let total = self.total_deadline.map(|(duration, at)| BodyDeadline { at, duration, total: true });
+        match (read, total) {
+            (Some(read), Some(total)) => Some(if total.at < read.at { total } else { read }),
+            (read, total) => read.or(total),
+        }
+    }
+
+    /// Initial size of a body buffer for this response, see [`BodyBufferBounds`].
+    fn body_buffer_size(&self) -> usize {
+        let bounds = self.effective_config.as_ref().map(|cfg| cfg.body_buffer_bounds).unwrap_or_default();
+        bounds.size_for(self.pool.as_ref().and_then(|pool| pool.learned_body_size(&self.origin)))
+    }
+
+    /// Feed a complete body of `len` bytes into the origin's learned size.
+    fn record_body_size(&self, len: u64) {
+        if let Some(pool) = &self.pool
+            && !self.origin.is_empty()
+        {
+            pool.record_body_size(&self.origin, len);
+        }
+    }
+
+    /// The body as a JSON Text Sequence (RFC 7464, `application/json-seq`),
+    /// one item per record, deserialized as they arrive. The first record
+    /// that fails to deserialize is yielded as a `JsonParsing` error and ends
+    /// the stream; see [`json_seq_with`](Self::json_seq_with) to skip it
+    /// instead. Framing, timeout and connection return are as in
+    /// [`bytes_stream`](Self::bytes_stream).
+    pub fn json_seq<T: serde::de::DeserializeOwned>(&mut self) -> impl futures::Stream<Item = Result<T>> + '_ {
+        self.json_seq_with(RecordErrors::Stop)
+    }
+
+    /// Like [`json_seq`](Self::json_seq), choosing what a record that fails to
+    /// deserialize does. Errors reading the body always end the stream.
+    pub fn json_seq_with<T: serde::de::DeserializeOwned>(
+        &mut self,
+        on_error: RecordErrors,
+    ) -> impl futures::Stream<Item = Result<T>> + '_ {
+        use futures::StreamExt;
+
+        let chunks = Box::pin(self.bytes_stream());
+        let state = (Some(chunks), RecordSplitter::new(), false);
+        futures::stream::unfold(state, move |(mut chunks, mut splitter, mut at_eof)| async move {
+            loop {
+                let body = chunks.as_mut()?;
+                if let Some(record) = splitter.next_record(at_eof) {
+                    let item = serde_json::from_slice::<T>(&record).map_err(|e| {
+                        JsonParsingSnafu { message: e.to_string(), preview: body_preview(&record) }.build()
+                    });
+                    if item.is_err() && on_error == RecordErrors::Stop {
+                        chunks = None;
+                    }
+                    return Some((item, (chunks, splitter, at_eof)));
+                }
+                if at_eof {
+                    return None;
+                }
+                match body.next().await {
+                    Some(Ok(chunk)) => splitter.push(&chunk),
+                    Some(Err(e)) => return Some((Err(e), (None, splitter, at_eof))),
+                    None => at_eof = true,
+                }
+            }
+        })
+    }
+
+    /// The ranges of a 206 Partial Content response, see `Request::set_ranges`.
+    /// A `multipart/byteranges` body yields one part per range; a single-range
+    /// response yields one part described by its `Content-Range` header. Any
+    /// other status fails with `InvalidByteRanges`, as does a body that does
+    /// not match the ranges it claims.
+    pub async fn byteranges(&mut self) -> Result<Vec<ByteRangePart>> {
+        if self.status_code != 206 {
+            return Err(InvalidByteRangesSnafu {
+                message: format!("expected 206 Partial Content, got {}", self.status_code),
+            }
+            .build());
+        }
+        let boundary = self.header_one(crate::header::CONTENT_TYPE).and_then(byteranges_boundary);
+        if let Some(boundary) = boundary {
+            let body = self.body_bytes().await?;
+            return parse_byteranges(&body, &boundary);
+        }
+        let content_range = self.header_one(crate::header::CONTENT_RANGE).unwrap_or_default();
+        let (range, total) = parse_content_range(content_range).ok_or_else(|| {
+            InvalidByteRangesSnafu { message: format!("invalid Content-Range: {content_range:?}") }.build()
+        })?;
+        let data = self.body_bytes().await?;
+        if data.len() as u64 != range.1 - range.0 + 1 {
+            return Err(InvalidByteRangesSnafu {
+                message: format!("got {} bytes for range {}-{}", data.len(), range.0, range.1),
+            }
+            .build());
+        }
+        Ok(vec![ByteRangePart { range, total, data }])
+    }
+
+    /// Stream the body into `writer`, returning the number of bytes copied.
+    /// The body is decoded, limited and checked as in
+    /// [`body_bytes`](Self::body_bytes).
+    ///
+    /// Fails with [`ZjhttpcError::BodyTruncated`] when the connection drops
+    /// before the declared Content-Length was received. Bytes copied up to that
+    /// point have already been written to `writer`, as they have when a
+    /// checksum turns out not to match at the end of the body.
+    pub async fn copy_body_to<W>(&mut self, writer: &mut W) -> Result<u64>
+    where
+        W: async_std::io::Write + Unpin + ?Sized,
+    {
+        self.ensure_has_body()?;
+        if self.is_body_consumed() {
+            return Err(BodyAlreadyReadSnafu.build());
+        }
+
+        let Some(mut stream) = self.body_pipeline_reader(u64::MAX) else {
+            return Ok(0);
+        };
+        let mut buf = [0u8; 8192];
+        let copy_future = async {
+            let mut total = 0u64;
+            loop {
+                let n = stream.read(&mut buf).await?;
+                if n == 0 {
+                    break;
+                }
+                writer.write_all(&buf[..n]).await?;
+                total += n as u64;
+            }
+            writer.flush().await?;
+            Ok::<u64, ZjhttpcError>(total)
+        };
+
+        within(self.body_deadline(), copy_future)
+            .await
+            .map_err(|e| e.with_phase(RequestPhase::ReadBody))
+    }
+
+    /// Save the body to a new file at `path`, returning the number of bytes written.
+    ///
+    /// Fails with an `AlreadyExists` I/O error, before reading anything,
+    /// when `path` already exists. If the body can't be read completely
+    /// (e.g. [`ZjhttpcError::BodyTruncated`]), the partial file is removed
+    /// before the error is returned.
+    pub async fn save_to_file(&mut self, path: impl AsRef<std::path::Path>) -> Result<u64> {
+        let path = path.as_ref();
+        let mut file = async_std::fs::OpenOptions::new().write(true).create_new(true).open(path).await?;
+        match self.copy_body_to(&mut file).await {
+            Ok(n) => Ok(n),
+            Err(e) => {
+                drop(file);
+                if let Err(remove_err) = async_std::fs::remove_file(path).await {
+                    warn!(target: BODY, ?remove_err, ?path, "failed to remove partial body file");
+                }
+                Err(e)
+            }
+        }
+    }
+
+    /// Compare the response `Content-Type` with the request's expectation
+    /// ([`Request::expect_json`](crate::requestx::Request::expect_json) and friends).
+    ///
+    /// On a mismatch the body is read for the error's preview and
+    /// `UnexpectedContentType` is returned. Succeeds without reading anything
+    /// when nothing was expected, the types match, or the response has no
+    /// `Content-Type`. With `Request::set_lenient_content_type` a mismatch
+    /// also succeeds when [`sniff_content_type`](Self::sniff_content_type)
+    /// says the body is of the expected kind.
+    pub async fn check_content_type(&mut self) -> Result<()> {
+        let Some(expected) = self.expected_body else {
+            return Ok(());
+        };
+        let Some(actual) = self.header_one("content-type").map(str::to_owned) else {
+            return Ok(());
+        };
+        if expected.matches(&actual) {
+            return Ok(());
+        }
+        if self.lenient_content_type {
+            let sniffed = match self.sniff_content_type().await {
+                Ok(sniffed) => Some(sniffed),
+                Err(ZjhttpcError::BodyAlreadyRead { .. }) => None,
+                Err(e) => return Err(e),
+            };
+            if let Some(sniffed) = sniffed
+                && expected.matches(sniffed.media_type())
+            {
+                debug!(target: BODY, %actual, ?sniffed, "accepting mismatched content type after sniffing the body");
+                return Ok(());
+            }
+        }
+        let preview = match self.body_bytes().await {
+            Ok(bytes) => body_preview(&bytes),
+            Err(ZjhttpcError::BodyAlreadyRead { .. }) => String::new(),
+            Err(e) => return Err(e),
+        };
+        Err(UnexpectedContentTypeSnafu { expected: expected.accept(), actual, preview }.build())
+    }
+
+    /// Read the entire body as a JSON value, see [`body_json_as`](Self::body_json_as).
     pub async fn body_json(&mut self) -> Result<serde_json::Value> {
+        self.body_json_as().await
+    }
+
+    /// Read the entire body (framed and timed like [`body_bytes`](Self::body_bytes),
+    /// so the connection goes back to the pool) and deserialize it into `T`.
+    /// The body's type is checked first with [`check_content_type`](Self::check_content_type).
+    /// A body that does not parse fails with `JsonParsing`, which carries
+    /// the start of the body — typically an HTML error page.
+    pub async fn body_json_as<T: serde::de::DeserializeOwned>(&mut self) -> Result<T> {
+        self.check_content_type().await?;
         let bytes = self.body_bytes().await?;
         serde_json::from_slice(&bytes).map_err(|e| {
-            let preview = String::from_utf8_lossy(&bytes);
-            let preview = if preview.len() > 200 {
-                format!(
-                    "{}...(truncated, total {} bytes)",
-                    &preview[..200],
-                    bytes.len()
-                )
-            } else {
-                preview.into_owned()
-            };
             JsonParsingSnafu {
                 message: e.to_string(),
-                preview,
+                preview: body_preview(&bytes),
             }.build()
         })
     }
@@ -854,10 +1881,60 @@ impl Response {
     pub fn content_length(&self) -> Option<u64> {
         self.headers
             .get("content-length")
-            .and_then(|vec| vec.first())
             .and_then(|s| s.parse::<u64>().ok())
     }
 
+    /// The `Date` header as a point in time, if present and a valid HTTP-date.
+    pub fn date(&self) -> Option<SystemTime> {
+        self.header_one("date")
+            .and_then(|v| httpdate::parse_http_date(v.trim()).ok())
+    }
+
+    /// The `Deprecation`, `Sunset` and `Link rel="deprecation"` headers, or
+    /// `None` when the response carries none of them; see [`crate::deprecation`].
+    pub fn deprecation(&self) -> Option<DeprecationInfo> {
+        DeprecationInfo::from_headers(&self.headers, self.url.as_ref())
+    }
+
+    /// The methods the `Allow` header lists, across all its lines; `None`
+    /// without one. An empty set means the resource allows no method.
+    pub fn allow_methods(&self) -> Option<MethodSet> {
+        let values = self.headers.get_all("allow")?;
+        Some(MethodSet::parse(&values.iter().map(String::as_str).collect::<Vec<_>>().join(",")))
+    }
+
+    /// Every value of every `Warning` header, in order.
+    pub fn warnings(&self) -> Vec<Warning> {
+        self.header_all("warning").into_iter().flat_map(parse_warning_header).collect()
+    }
+
+    /// The `Age` header as a duration. Values that are not a non-negative
+    /// integer are ignored; values too large to represent are capped at
+    /// 2^31 seconds as RFC 7234 §1.2.1 requires.
+    pub fn age(&self) -> Option<Duration> {
+        let v = self.header_one("age")?.trim();
+        if v.is_empty() || !v.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        let secs = v.parse::<u64>().unwrap_or(MAX_DELTA_SECONDS).min(MAX_DELTA_SECONDS);
+        Some(Duration::from_secs(secs))
+    }
+
+    /// Estimated age of the response at `now`, per RFC 7234 §4.2.3.
+    ///
+    /// A missing or unparseable `Date` is treated as the receipt time, so the
+    /// apparent age is zero; a bogus `Age` counts as zero.
+    pub fn current_age(&self, now: SystemTime) -> Duration {
+        let date_value = self.date().unwrap_or(self.response_time);
+        let age_value = self.age().unwrap_or(Duration::ZERO);
+        let apparent_age = self.response_time.duration_since(date_value).unwrap_or(Duration::ZERO);
+        let response_delay = self.response_time.duration_since(self.request_time).unwrap_or(Duration::ZERO);
+        let corrected_age_value = age_value.saturating_add(response_delay);
+        let corrected_initial_age = apparent_age.max(corrected_age_value);
+        let resident_time = now.duration_since(self.response_time).unwrap_or(Duration::ZERO);
+        corrected_initial_age.saturating_add(resident_time)
+    }
+
     /// Mark the response body as successfully read.
     ///
     /// This method should be called when you have finished reading the body through
@@ -907,6 +1984,16 @@ mod tests {
 
     use super::*;
 
+    #[test]
+    fn test_body_buffer_bounds_clamp_the_learned_size() {
+        let bounds = BodyBufferBounds { min: 1024, max: 64 * 1024 };
+        assert_eq!(bounds.size_for(None), 1024);
+        assert_eq!(bounds.size_for(Some(0)), 1024);
+        assert_eq!(bounds.size_for(Some(5_000)), 5_000);
+        assert_eq!(bounds.size_for(Some(u64::MAX)), 64 * 1024);
+        assert_eq!(BodyBufferBounds::default().size_for(Some(2_000)), 8 * 1024);
+    }
+
     #[test]
     fn new_from_parse_result_and_basic_getters() {
         let x = "\r\nf5e\r\n".trim();
@@ -997,8 +2084,6 @@ mod tests {
             }
         }
 
-        impl crate::stream::RWStream for TestStream {}
-
         // Create a mock stream with some data
         let data = b"Hello, World!";
         let test_stream = TestStream {
@@ -1009,7 +2094,7 @@ mod tests {
 
         // Create BodyFixedLengthStream with exact content length
         let chain = crate::stream::ChainRead::new(crate::stream::SliceRead::new(&[]), boxed_stream);
-        let mut fixed_stream = BodyFixedLengthStream::new(chain, data.len());
+        let mut fixed_stream = BodyFixedLengthStream::new(chain, data.len() as u64);
 
         // Test reading the entire content
         let mut buffer = Vec::new();
@@ -1081,8 +2166,6 @@ mod tests {
             }
         }
 
-        impl crate::stream::RWStream for TestStream {}
-
         // Create a mock stream with some data
         let data = b"Hello, World!";
         let test_stream = TestStream {
@@ -1093,7 +2176,7 @@ mod tests {
 
         // Create BodyFixedLengthStream with exact content length
         let chain = crate::stream::ChainRead::new(crate::stream::SliceRead::new(&[]), boxed_stream);
-        let mut fixed_stream = BodyFixedLengthStream::new(chain, data.len());
+        let mut fixed_stream = BodyFixedLengthStream::new(chain, data.len() as u64);
 
         // Test reading partial content
         let mut buffer = [0u8; 5];
@@ -1174,8 +2257,6 @@ mod tests {
             }
         }
 
-        impl crate::stream::RWStream for TestChunkedStream {}
-
         // Create chunked data: "5\r\nHello\r\n6\r\n World\r\n0\r\n\r\n"
         let chunked_data = b"5\r\nHello\r\n6\r\n World\r\n0\r\n\r\n";
         let test_stream = TestChunkedStream::new(chunked_data);
@@ -1259,8 +2340,6 @@ mod tests {
             }
         }
 
-        impl crate::stream::RWStream for TestStream {}
-
         // Test with some data
         let data = b"Test data for unknown length stream";
         let test_stream = TestStream::new(data);
@@ -1272,9 +2351,7 @@ mod tests {
         let mut unknown_stream = BodyUnknownLengthStream::new_with_completion_flag(
             chain,
             completion_flag,
-            std::net::SocketAddr::from(([127, 0, 0, 1], 8080)),
-            false,
-            None,
+            StreamInfo::unpooled(),
             None,
         );
 
@@ -1375,7 +2452,6 @@ mod tests {
 
     #[test]
     fn test_response_body_successfully_readed_flag() {
-        use hashbrown::HashMap;
         use std::net::SocketAddr;
 
         // Create a mock response
@@ -1384,7 +2460,7 @@ mod tests {
             is_tls: false,
             http_version: HttpVersion::V1_1,
             status_code: 200,
-            headers: HashMap::new(),
+            headers: HeaderMap::new(),
             body_raw_stream: None,
             body_prefix: [0u8; 4096],
             body_prefix_len: 0,
@@ -1393,6 +2469,30 @@ mod tests {
             read_body_timeout: None,
             pool: None,
             keep_alive: true,
+            request_time: SystemTime::UNIX_EPOCH,
+            response_time: SystemTime::UNIX_EPOCH,
+            effective_config: None,
+            expected_body: None,
+            lenient_content_type: false,
+            extensions: Extensions::new(),
+            reused_connection: false,
+            connection_id: None,
+            url: None,
+            deduplicated: false,
+            request_body_truncated: false,
+            raw_body_framing: false,
+            bodiless: false,
+            tunnel: false,
+            origin: String::new(),
+            sent_request: None,
+            interim: Vec::new(),
+            peeked: Vec::new(),
+            peeked_stream: None,
+            throttled: Arc::default(),
+            download_throttle: None,
+            memory_budget: None,
+            peek_reservation: None,
+            total_deadline: None,
         };
 
         // Test initial state
@@ -1403,7 +2503,6 @@ mod tests {
 
     #[test]
     fn test_mark_body_read_complete() {
-        use hashbrown::HashMap;
         use std::net::SocketAddr;
 
         // Create a mock response with raw_stream
@@ -1412,7 +2511,7 @@ mod tests {
             is_tls: false,
             http_version: HttpVersion::V1_1,
             status_code: 200,
-            headers: HashMap::new(),
+            headers: HeaderMap::new(),
             body_raw_stream: None,
             body_prefix: [0u8; 4096],
             body_prefix_len: 0,
@@ -1421,6 +2520,30 @@ mod tests {
             read_body_timeout: None,
             pool: None,
             keep_alive: true,
+            request_time: SystemTime::UNIX_EPOCH,
+            response_time: SystemTime::UNIX_EPOCH,
+            effective_config: None,
+            expected_body: None,
+            lenient_content_type: false,
+            extensions: Extensions::new(),
+            reused_connection: false,
+            connection_id: None,
+            url: None,
+            deduplicated: false,
+            request_body_truncated: false,
+            raw_body_framing: false,
+            bodiless: false,
+            tunnel: false,
+            origin: String::new(),
+            sent_request: None,
+            interim: Vec::new(),
+            peeked: Vec::new(),
+            peeked_stream: None,
+            throttled: Arc::default(),
+            download_throttle: None,
+            memory_budget: None,
+            peek_reservation: None,
+            total_deadline: None,
         };
 
         // Initially not complete
@@ -1435,7 +2558,6 @@ mod tests {
 
     #[test]
     fn test_completion_flag_with_managed_stream() {
-        use hashbrown::HashMap;
         use std::net::SocketAddr;
         use std::sync::Arc;
         use std::sync::atomic::{AtomicBool, Ordering};
@@ -1447,7 +2569,7 @@ mod tests {
             is_tls: false,
             http_version: HttpVersion::V1_1,
             status_code: 200,
-            headers: HashMap::new(),
+            headers: HeaderMap::new(),
             body_raw_stream: None,
             body_prefix: [0u8; 4096],
             body_prefix_len: 0,
@@ -1456,6 +2578,30 @@ mod tests {
             read_body_timeout: None,
             pool: None,
             keep_alive: true,
+            request_time: SystemTime::UNIX_EPOCH,
+            response_time: SystemTime::UNIX_EPOCH,
+            effective_config: None,
+            expected_body: None,
+            lenient_content_type: false,
+            extensions: Extensions::new(),
+            reused_connection: false,
+            connection_id: None,
+            url: None,
+            deduplicated: false,
+            request_body_truncated: false,
+            raw_body_framing: false,
+            bodiless: false,
+            tunnel: false,
+            origin: String::new(),
+            sent_request: None,
+            interim: Vec::new(),
+            peeked: Vec::new(),
+            peeked_stream: None,
+            throttled: Arc::default(),
+            download_throttle: None,
+            memory_budget: None,
+            peek_reservation: None,
+            total_deadline: None,
         };
 
         // Initially not complete
@@ -1522,8 +2668,6 @@ mod tests {
             }
         }
 
-        impl crate::stream::RWStream for TestStream {}
-
         // Create a mock stream with some data
         let data = b"Some data";
         let test_stream = TestStream {
@@ -1608,8 +2752,6 @@ mod tests {
             }
         }
 
-        impl crate::stream::RWStream for TestStream {}
-
         // Test data
         let data = b"Hello, World! This is test data for body_bytes method.";
         let test_stream = TestStream::new(data);
@@ -1626,7 +2768,7 @@ mod tests {
             is_tls: false,
             http_version: HttpVersion::V1_1,
             status_code: 200,
-            headers,
+            headers: headers.into(),
             body_raw_stream: Some(boxed_stream),
             body_prefix: [0u8; 4096],
             body_prefix_len: 0,
@@ -1635,6 +2777,30 @@ mod tests {
             read_body_timeout: None,
             pool: None,
             keep_alive: true,
+            request_time: SystemTime::UNIX_EPOCH,
+            response_time: SystemTime::UNIX_EPOCH,
+            effective_config: None,
+            expected_body: None,
+            lenient_content_type: false,
+            extensions: Extensions::new(),
+            reused_connection: false,
+            connection_id: None,
+            url: None,
+            deduplicated: false,
+            request_body_truncated: false,
+            raw_body_framing: false,
+            bodiless: false,
+            tunnel: false,
+            origin: String::new(),
+            sent_request: None,
+            interim: Vec::new(),
+            peeked: Vec::new(),
+            peeked_stream: None,
+            throttled: Arc::default(),
+            download_throttle: None,
+            memory_budget: None,
+            peek_reservation: None,
+            total_deadline: None,
         };
 
         // Test body_bytes method
@@ -1707,8 +2873,6 @@ mod tests {
             }
         }
 
-        impl crate::stream::RWStream for TestStream {}
-
         // Test JSON data
         let json_data = br#"{"name": "test", "value": 42, "active": true}"#;
         let test_stream = TestStream::new(json_data);
@@ -1725,7 +2889,7 @@ mod tests {
             is_tls: false,
             http_version: HttpVersion::V1_1,
             status_code: 200,
-            headers,
+            headers: headers.into(),
             body_raw_stream: Some(boxed_stream),
             body_prefix: [0u8; 4096],
             body_prefix_len: 0,
@@ -1734,6 +2898,30 @@ mod tests {
             read_body_timeout: None,
             pool: None,
             keep_alive: true,
+            request_time: SystemTime::UNIX_EPOCH,
+            response_time: SystemTime::UNIX_EPOCH,
+            effective_config: None,
+            expected_body: None,
+            lenient_content_type: false,
+            extensions: Extensions::new(),
+            reused_connection: false,
+            connection_id: None,
+            url: None,
+            deduplicated: false,
+            request_body_truncated: false,
+            raw_body_framing: false,
+            bodiless: false,
+            tunnel: false,
+            origin: String::new(),
+            sent_request: None,
+            interim: Vec::new(),
+            peeked: Vec::new(),
+            peeked_stream: None,
+            throttled: Arc::default(),
+            download_throttle: None,
+            memory_budget: None,
+            peek_reservation: None,
+            total_deadline: None,
         };
 
         // Test body_json method
@@ -1810,8 +2998,6 @@ mod tests {
             }
         }
 
-        impl crate::stream::RWStream for TestStream {}
-
         // Test invalid JSON data
         let invalid_json = b"{ invalid json }";
         let test_stream = TestStream::new(invalid_json);
@@ -1822,7 +3008,7 @@ mod tests {
             is_tls: false,
             http_version: HttpVersion::V1_1,
             status_code: 200,
-            headers: hashbrown::HashMap::new(),
+            headers: HeaderMap::new(),
             body_raw_stream: Some(boxed_stream),
             body_prefix: [0u8; 4096],
             body_prefix_len: 0,
@@ -1831,6 +3017,30 @@ mod tests {
             read_body_timeout: None,
             pool: None,
             keep_alive: true,
+            request_time: SystemTime::UNIX_EPOCH,
+            response_time: SystemTime::UNIX_EPOCH,
+            effective_config: None,
+            expected_body: None,
+            lenient_content_type: false,
+            extensions: Extensions::new(),
+            reused_connection: false,
+            connection_id: None,
+            url: None,
+            deduplicated: false,
+            request_body_truncated: false,
+            raw_body_framing: false,
+            bodiless: false,
+            tunnel: false,
+            origin: String::new(),
+            sent_request: None,
+            interim: Vec::new(),
+            peeked: Vec::new(),
+            peeked_stream: None,
+            throttled: Arc::default(),
+            download_throttle: None,
+            memory_budget: None,
+            peek_reservation: None,
+            total_deadline: None,
         };
 
         // Test body_json method with invalid JSON
@@ -1894,7 +3104,6 @@ mod tests {
             std::task::Poll::Ready(Ok(()))
         }
     }
-    impl crate::stream::RWStream for MockStream {}
 
     #[test]
     fn test_body_fixed_length_stream_prefix() {
@@ -1910,9 +3119,7 @@ mod tests {
             chain,
             13,
             Arc::new(AtomicBool::new(false)),
-            "127.0.0.1:8080".parse().unwrap(),
-            false,
-            None,
+            StreamInfo::unpooled(),
             None,
         );
 
@@ -1921,6 +3128,16 @@ mod tests {
         assert_eq!(out, b"Hello, World!");
     }
 
+    #[test]
+    fn test_parse_chunk_size() {
+        assert_eq!(parse_chunk_size("1a"), Ok(26));
+        assert_eq!(parse_chunk_size(" FF "), Ok(255));
+        assert_eq!(parse_chunk_size("ffffffffffffffff"), Ok(u64::MAX));
+        for bad in ["", "+5", "-1", "0x10", "1;ext", "10000000000000000"] {
+            assert!(parse_chunk_size(bad).is_err(), "{bad:?} should be rejected");
+        }
+    }
+
     #[test]
     fn test_body_fixed_length_stream_prefix_exceeds_content_length() {
         use async_std::io::ReadExt;
@@ -1934,9 +3151,7 @@ mod tests {
             chain,
             6,
             Arc::new(AtomicBool::new(false)),
-            "127.0.0.1:8080".parse().unwrap(),
-            false,
-            None,
+            StreamInfo::unpooled(),
             None,
         );
 
@@ -1946,6 +3161,84 @@ mod tests {
         assert!(stream.is_fully_consumed());
     }
 
+    // ==================== Large length tests ====================
+    // Lengths past 4 GiB are simulated: the counters see them, no data moves.
+
+    const SIX_GIB: u64 = 6 << 30;
+
+    #[test]
+    fn test_fixed_length_counters_past_4gib() {
+        use async_std::io::ReadExt;
+
+        let data = vec![b'x'; 1 << 20];
+        let chain = crate::stream::ChainRead::new(
+            crate::stream::SliceRead::new(b""),
+            Box::new(MockStream::new(&data)) as BoxedStream,
+        );
+        let mut stream = BodyFixedLengthStream::new(chain, SIX_GIB);
+        let mut out = Vec::new();
+        let err = async_std::task::block_on(stream.read_to_end(&mut out)).unwrap_err();
+        assert_eq!(out.len(), data.len());
+        match ZjhttpcError::from(err) {
+            ZjhttpcError::BodyTruncated { expected, received, .. } => {
+                assert_eq!((expected, received), (SIX_GIB, 1 << 20));
+            }
+            other => panic!("expected BodyTruncated, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_chunk_larger_than_4gib_is_streamed() {
+        use async_std::io::ReadExt;
+
+        let chain = crate::stream::ChainRead::new(
+            crate::stream::SliceRead::new(b""),
+            Box::new(MockStream::new(b"180000000\r\nfirst bytes")) as BoxedStream,
+        );
+        let mut decoder = ChunkedDecoderStream::new_with_completion_flag(
+            chain,
+            Arc::new(AtomicBool::new(false)),
+            StreamInfo::unpooled(),
+            None,
+        );
+        let mut buf = [0u8; 64];
+        let n = async_std::task::block_on(decoder.read(&mut buf)).unwrap();
+        assert_eq!(&buf[..n], b"first bytes");
+        let err = async_std::task::block_on(decoder.read(&mut buf)).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn test_body_bytes_refuses_content_length_past_the_limit() {
+        let mut resp = response_with_headers(&[("content-length", &SIX_GIB.to_string())], at(0), at(0));
+        resp.body_raw_stream = Some(Box::new(MockStream::new(b"never read")));
+        // The limit of a 32-bit target.
+        let limit = i32::MAX as u64;
+        let err = async_std::task::block_on(resp.body_bytes_up_to(limit)).unwrap_err();
+        assert!(
+            matches!(err, ZjhttpcError::BodyTooLarge { length: Some(SIX_GIB), limit: l, .. } if l == limit),
+            "{err:?}"
+        );
+        assert!(resp.body_raw_stream.is_some(), "nothing was read");
+
+        let mut resp = response_with_headers(&[("content-length", &u64::MAX.to_string())], at(0), at(0));
+        resp.body_raw_stream = Some(Box::new(MockStream::new(b"")));
+        let err = async_std::task::block_on(resp.body_bytes()).unwrap_err();
+        assert!(matches!(err, ZjhttpcError::BodyTooLarge { length: Some(u64::MAX), .. }), "{err:?}");
+    }
+
+    #[test]
+    fn test_body_bytes_stops_unframed_body_at_the_limit() {
+        let mut resp = response_with_headers(&[("transfer-encoding", "chunked")], at(0), at(0));
+        resp.body_raw_stream = Some(Box::new(MockStream::new(b"a\r\n0123456789\r\n0\r\n\r\n")));
+        let err = async_std::task::block_on(resp.body_bytes_up_to(8)).unwrap_err();
+        assert!(matches!(err, ZjhttpcError::BodyTooLarge { length: None, limit: 8, .. }), "{err:?}");
+
+        let mut resp = response_with_headers(&[("transfer-encoding", "chunked")], at(0), at(0));
+        resp.body_raw_stream = Some(Box::new(MockStream::new(b"a\r\n0123456789\r\n0\r\n\r\n")));
+        assert_eq!(async_std::task::block_on(resp.body_bytes_up_to(10)).unwrap(), b"0123456789");
+    }
+
     #[test]
     fn test_body_fixed_length_stream_prefix_large_than_read_buffer() {
         use async_std::io::ReadExt;
@@ -1960,9 +3253,7 @@ mod tests {
             chain,
             100,
             Arc::new(AtomicBool::new(false)),
-            "127.0.0.1:8080".parse().unwrap(),
-            false,
-            None,
+            StreamInfo::unpooled(),
             None,
         );
 
@@ -1991,9 +3282,7 @@ mod tests {
         let mut stream = BodyUnknownLengthStream::new_with_completion_flag(
             chain,
             Arc::new(AtomicBool::new(false)),
-            "127.0.0.1:8080".parse().unwrap(),
-            false,
-            None,
+            StreamInfo::unpooled(),
             None,
         );
 
@@ -2014,9 +3303,7 @@ mod tests {
         let mut stream = BodyUnknownLengthStream::new_with_completion_flag(
             chain,
             Arc::new(AtomicBool::new(false)),
-            "127.0.0.1:8080".parse().unwrap(),
-            false,
-            None,
+            StreamInfo::unpooled(),
             None,
         );
 
@@ -2039,9 +3326,7 @@ mod tests {
         let mut stream = ChunkedDecoderStream::new_with_completion_flag(
             chain,
             Arc::new(AtomicBool::new(false)),
-            "127.0.0.1:8080".parse().unwrap(),
-            false,
-            None,
+            StreamInfo::unpooled(),
             None,
         );
 
@@ -2064,9 +3349,7 @@ mod tests {
         let mut stream = ChunkedDecoderStream::new_with_completion_flag(
             chain,
             Arc::new(AtomicBool::new(false)),
-            "127.0.0.1:8080".parse().unwrap(),
-            false,
-            None,
+            StreamInfo::unpooled(),
             None,
         );
 
@@ -2075,4 +3358,112 @@ mod tests {
         assert_eq!(out, b"Hello");
         assert!(stream.is_fully_consumed());
     }
+
+    fn response_with_headers(headers: &[(&str, &str)], request_time: SystemTime, response_time: SystemTime) -> Response {
+        let map: HeaderMap = headers.iter().copied().collect();
+        Response {
+            addr: SocketAddr::from(([127, 0, 0, 1], 8080)),
+            is_tls: false,
+            http_version: HttpVersion::V1_1,
+            status_code: 200,
+            headers: map,
+            body_raw_stream: None,
+            body_prefix: [0u8; 4096],
+            body_prefix_len: 0,
+            proxy_used: None,
+            body_completion_flag: Arc::new(AtomicBool::new(false)),
+            read_body_timeout: None,
+            pool: None,
+            keep_alive: true,
+            request_time,
+            response_time,
+            effective_config: None,
+            expected_body: None,
+            lenient_content_type: false,
+            extensions: Extensions::new(),
+            reused_connection: false,
+            connection_id: None,
+            url: None,
+            deduplicated: false,
+            request_body_truncated: false,
+            raw_body_framing: false,
+            bodiless: false,
+            tunnel: false,
+            origin: String::new(),
+            sent_request: None,
+            interim: Vec::new(),
+            peeked: Vec::new(),
+            peeked_stream: None,
+            throttled: Arc::default(),
+            download_throttle: None,
+            memory_budget: None,
+            peek_reservation: None,
+            total_deadline: None,
+        }
+    }
+
+    fn at(secs: u64) -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_secs(secs)
+    }
+
+    // Sun, 06 Nov 1994 08:49:37 GMT
+    const DATE_1994: u64 = 784111777;
+
+    #[test]
+    fn test_date_header_formats() {
+        for value in [
+            "Sun, 06 Nov 1994 08:49:37 GMT",
+            "Sunday, 06-Nov-94 08:49:37 GMT",
+            "Sun Nov  6 08:49:37 1994",
+        ] {
+            let resp = response_with_headers(&[("date", value)], at(0), at(0));
+            assert_eq!(resp.date(), Some(at(DATE_1994)), "{value}");
+        }
+        let resp = response_with_headers(&[("date", "yesterday")], at(0), at(0));
+        assert_eq!(resp.date(), None);
+    }
+
+    #[test]
+    fn test_age_header_parsing() {
+        let age = |v: &str| response_with_headers(&[("age", v)], at(0), at(0)).age();
+        assert_eq!(age("60"), Some(Duration::from_secs(60)));
+        assert_eq!(age(" 0 "), Some(Duration::ZERO));
+        assert_eq!(age("-5"), None);
+        assert_eq!(age("1.5"), None);
+        assert_eq!(age(""), None);
+        assert_eq!(age("99999999999999999999999"), Some(Duration::from_secs(1 << 31)));
+        assert_eq!(response_with_headers(&[], at(0), at(0)).age(), None);
+    }
+
+    #[test]
+    fn test_current_age_uses_corrected_age_value() {
+        // Date says 2s before receipt, Age says 10s and the request took 3s:
+        // corrected_age_value = 10 + 3 = 13 beats apparent_age = 2; resident 5s.
+        let date = httpdate::fmt_http_date(at(DATE_1994 - 2));
+        let resp = response_with_headers(
+            &[("date", &date), ("age", "10")],
+            at(DATE_1994 - 3),
+            at(DATE_1994),
+        );
+        assert_eq!(resp.current_age(at(DATE_1994 + 5)), Duration::from_secs(18));
+    }
+
+    #[test]
+    fn test_current_age_uses_apparent_age_when_larger() {
+        // Origin clock is 100s behind: apparent_age = 100 > age 1 + delay 0.
+        let date = httpdate::fmt_http_date(at(DATE_1994 - 100));
+        let resp = response_with_headers(&[("date", &date), ("age", "1")], at(DATE_1994), at(DATE_1994));
+        assert_eq!(resp.current_age(at(DATE_1994 + 1)), Duration::from_secs(101));
+    }
+
+    #[test]
+    fn test_current_age_tolerates_missing_date_and_bogus_age() {
+        let resp = response_with_headers(&[("age", "abc")], at(DATE_1994 - 1), at(DATE_1994));
+        // only response_delay (1s) and resident time (4s) remain
+        assert_eq!(resp.current_age(at(DATE_1994 + 4)), Duration::from_secs(5));
+        // a Date in the future of receipt, and `now` before receipt, clamp to zero
+        let date = httpdate::fmt_http_date(at(DATE_1994 + 50));
+        let resp = response_with_headers(&[("date", &date)], at(DATE_1994), at(DATE_1994));
+        assert_eq!(resp.current_age(at(DATE_1994 - 10)), Duration::ZERO);
+    }
 }
@@ -0,0 +1,183 @@
+//! Collapse identical concurrent GETs into one upstream request.
+//!
+//! With [`ZJHttpClient::single_flight`](crate::client::ZJHttpClient::single_flight)
+//! set, a body-less GET whose URL and serialized head (so every header,
+//! credentials included) match a request already in flight does not go to
+//! the network: it waits for the first request, the leader, and gets a copy
+//! of its response with the body buffered in memory. Nothing outlives the
+//! leader's exchange, so this is not a cache: a request that starts after
+//! the leader's response was shared sends on its own.
+//!
+//! Requests with identical heads are also
+//! [`equivalent`](crate::header::equivalent) under any `Vary` list, so the
+//! shared response fits every follower whatever it varies on.
+//!
+//! Followers fall back to sending themselves when the leader fails, is
+//! dropped, or its body is larger than [`SingleFlight::max_body_bytes`] or
+//! does not fit the client's [memory budget](crate::memory_budget).
+//! The leader only buffers its body when someone is waiting for it.
+
+use futures::channel::oneshot;
+use std::collections::HashMap;
+use std::sync::{
+    Arc, Mutex,
+    atomic::{AtomicU64, Ordering},
+};
+use tracing::debug;
+
+use crate::{body::Body, log_target::POOL, methods, requestx::Request, response::Response};
+
+/// Predicate set with [`SingleFlight::exclude`].
+type ExcludeFn = Arc<dyn Fn(&Request) -> bool + Send + Sync>;
+
+/// Single-flight settings plus the table of requests in flight. Clones,
+/// like clones of the client holding it, share the table.
+#[derive(Clone)]
+pub struct SingleFlight {
+    max_body_bytes: usize,
+    exclude: Option<ExcludeFn>,
+    flights: Arc<Flights>,
+}
+
+#[derive(Default)]
+struct Flights {
+    waiting: Mutex<HashMap<Vec<u8>, Vec<oneshot::Sender<Response>>>>,
+    hits: AtomicU64,
+}
+
+impl Default for SingleFlight {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SingleFlight {
+    /// Share responses of up to 1 MiB.
+    pub fn new() -> Self {
+        SingleFlight { max_body_bytes: 1024 * 1024, exclude: None, flights: Arc::default() }
+    }
+
+    /// Largest body the leader buffers to hand to followers. Bigger bodies
+    /// stay with the leader and each follower sends its own request.
+    pub fn max_body_bytes(mut self, max: usize) -> Self {
+        self.max_body_bytes = max;
+        self
+    }
+
+    /// Never deduplicate requests for which `f` returns true.
+    pub fn exclude(mut self, f: impl Fn(&Request) -> bool + Send + Sync + 'static) -> Self {
+        self.exclude = Some(Arc::new(f));
+        self
+    }
+
+    /// The same settings with an empty table and hit count of their own.
+    pub(crate) fn fork(&self) -> Self {
+        SingleFlight { max_body_bytes: self.max_body_bytes, exclude: self.exclude.clone(), flights: Arc::default() }
+    }
+
+    /// How many requests were answered with a copy of another request's
+    /// response.
+    pub fn hits(&self) -> u64 {
+        self.flights.hits.load(Ordering::Relaxed)
+    }
+
+    /// Register `req`, about to be sent with `head`. `None` when the request
+    /// is not eligible.
+    pub(crate) fn join(&self, req: &Request, head: &[u8]) -> Option<Flight> {
+        if req.method != methods::GET
+            || !matches!(req.body, Body::None)
+            || req.fresh_connection
+            || self.exclude.as_ref().is_some_and(|exclude| exclude(req))
+        {
+            return None;
+        }
+        let mut key = Vec::with_capacity(req.url.as_str().len() + 1 + head.len());
+        key.extend_from_slice(req.url.as_str().as_bytes());
+        key.push(b'\n');
+        key.extend_from_slice(head);
+
+        let mut waiting = self.flights.waiting.lock().unwrap();
+        if let Some(followers) = waiting.get_mut(&key) {
+            let (tx, rx) = oneshot::channel();
+            followers.push(tx);
+            debug!(target: POOL, url = %req.url, "joining identical request in flight");
+            return Some(Flight::Follower(rx));
+        }
+        waiting.insert(key.clone(), Vec::new());
+        Some(Flight::Leader(Leader { key: Some(key), max_body_bytes: self.max_body_bytes, flights: self.flights.clone() }))
+    }
+}
+
+impl std::fmt::Debug for SingleFlight {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SingleFlight")
+            .field("max_body_bytes", &self.max_body_bytes)
+            .field("exclude", &self.exclude.is_some())
+            .field("hits", &self.hits())
+            .finish()
+    }
+}
+
+pub(crate) enum Flight {
+    /// Send the request, then [`Leader::share`] the response.
+    Leader(Leader),
+    /// Wait for the leader's response; a cancelled receiver means send anyway.
+    Follower(oneshot::Receiver<Response>),
+}
+
+/// Ends the flight when dropped, so followers of a failed or abandoned
+/// leader stop waiting.
+pub(crate) struct Leader {
+    key: Option<Vec<u8>>,
+    max_body_bytes: usize,
+    flights: Arc<Flights>,
+}
+
+impl Leader {
+    fn land(&mut self) -> Vec<oneshot::Sender<Response>> {
+        let key = self.key.take().expect("flight landed twice");
+        self.flights.waiting.lock().unwrap().remove(&key).unwrap_or_default()
+    }
+
+    /// Hand copies of `resp` to the followers. The body is peeked, so
+    /// `resp` can still be read in full; a body over the limit or a failed
+    /// read sends the followers off on their own.
+    pub(crate) async fn share(mut self, resp: &mut Response) {
+        let followers = self.land();
+        if followers.is_empty() || resp.is_upgraded() {
+            return;
+        }
+        let limit = self.max_body_bytes;
+        match resp.peek_body(limit.saturating_add(1)).await {
+            Ok(body) if body.len() <= limit => {}
+            Ok(_) => {
+                debug!(target: POOL, limit, "response too large to share with identical requests");
+                return;
+            }
+            Err(error) => {
+                debug!(target: POOL, ?error, "could not buffer response for identical requests");
+                return;
+            }
+        }
+        for follower in followers {
+            let copy = match resp.buffered_copy() {
+                Ok(copy) => copy,
+                Err(error) => {
+                    debug!(target: POOL, ?error, "no room to copy response for identical requests");
+                    return;
+                }
+            };
+            if follower.send(copy).is_ok() {
+                self.flights.hits.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+impl Drop for Leader {
+    fn drop(&mut self) {
+        if self.key.is_some() {
+            self.land();
+        }
+    }
+}
@@ -0,0 +1,229 @@
+//! Disk-backed replay of streamed request bodies.
+//!
+//! A [`Body::Stream`](crate::body::Body::Stream) or
+//! [`Body::StreamChunked`](crate::body::Body::StreamChunked) body is read once,
+//! so a send that has to go out again (a pooled connection that failed before
+//! answering, a 307 or 308 redirect) can't repeat it. With
+//! [`Request::set_replay_spill`](crate::requestx::Request::set_replay_spill),
+//! the first transmission also copies the body into a temporary file, and a
+//! later transmission of the same `send()` reads the body from that file.
+//!
+//! Spilling is best effort: if the file can't be created or written, or the
+//! body grows past the cap, the copy is given up and the body stays
+//! non-replayable, as without a spill. The upload itself is not affected. The
+//! file is removed when `send()` returns, or when the request is dropped.
+
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use async_std::fs::File;
+use async_std::io::WriteExt;
+use tracing::debug;
+
+use crate::error::Result;
+use crate::log_target::BODY;
+
+/// Gives each spill file of the process its own name.
+static NEXT_SPILL_ID: AtomicU64 = AtomicU64::new(0);
+
+/// Where and how much of a streamed body to keep for replay, see
+/// [`Request::set_replay_spill`](crate::requestx::Request::set_replay_spill).
+#[derive(Debug)]
+pub struct ReplaySpill {
+    dir: PathBuf,
+    max_bytes: u64,
+    state: SpillState,
+}
+
+#[derive(Debug)]
+enum SpillState {
+    /// Nothing spilled yet, or the last copy was given up.
+    Idle,
+    /// The body is being sent and copied.
+    Writing(SpillFile),
+    /// The whole body is in the file.
+    Complete(SpillFile),
+}
+
+/// A temporary file, removed on drop.
+#[derive(Debug)]
+struct SpillFile {
+    path: PathBuf,
+    file: Option<File>,
+    written: u64,
+}
+
+impl Drop for SpillFile {
+    fn drop(&mut self) {
+        // The file may already be gone; there is nobody to report to.
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+impl ReplaySpill {
+    pub fn new(dir: impl Into<PathBuf>, max_bytes: u64) -> Self {
+        ReplaySpill { dir: dir.into(), max_bytes, state: SpillState::Idle }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn max_bytes(&self) -> u64 {
+        self.max_bytes
+    }
+
+    /// Whether the whole body is on disk and can be sent again.
+    pub fn is_complete(&self) -> bool {
+        matches!(self.state, SpillState::Complete(_))
+    }
+
+    /// Called before the body is written. Starts a new copy unless the body
+    /// being written is already the replay of a complete one.
+    pub(crate) async fn begin(&mut self) {
+        if self.is_complete() {
+            return;
+        }
+        self.state = SpillState::Idle;
+        let name = format!("zjhttpc-spill-{}-{}", std::process::id(), NEXT_SPILL_ID.fetch_add(1, Ordering::Relaxed));
+        let path = self.dir.join(name);
+        match async_std::fs::OpenOptions::new().write(true).create_new(true).open(&path).await {
+            Ok(file) => {
+                self.state = SpillState::Writing(SpillFile { path, file: Some(file), written: 0 });
+            }
+            Err(err) => debug!(target: BODY, path = %path.display(), %err, "cannot create replay spill file"),
+        }
+    }
+
+    /// Copy a piece of the body that was just sent.
+    pub(crate) async fn write(&mut self, data: &[u8]) {
+        let SpillState::Writing(spill) = &mut self.state else {
+            return;
+        };
+        if spill.written + data.len() as u64 > self.max_bytes {
+            debug!(target: BODY, max_bytes = self.max_bytes, "request body exceeds the replay spill cap");
+            self.state = SpillState::Idle;
+            return;
+        }
+        let Some(file) = spill.file.as_mut() else {
+            return;
+        };
+        match file.write_all(data).await {
+            Ok(()) => spill.written += data.len() as u64,
+            Err(err) => {
+                debug!(target: BODY, path = %spill.path.display(), %err, "replay spill write failed");
+                self.state = SpillState::Idle;
+            }
+        }
+    }
+
+    /// The whole body was sent: keep the copy for replay.
+    pub(crate) async fn finish(&mut self) {
+        let SpillState::Writing(mut spill) = std::mem::replace(&mut self.state, SpillState::Idle) else {
+            return;
+        };
+        if let Some(mut file) = spill.file.take()
+            && let Err(err) = file.flush().await
+        {
+            debug!(target: BODY, path = %spill.path.display(), %err, "replay spill flush failed");
+            return;
+        }
+        debug!(target: BODY, bytes = spill.written, "request body spilled for replay");
+        self.state = SpillState::Complete(spill);
+    }
+
+    /// The body was not sent in full: drop the partial copy.
+    pub(crate) fn abandon(&mut self) {
+        if let SpillState::Writing(_) = self.state {
+            self.state = SpillState::Idle;
+        }
+    }
+
+    /// A reader over the complete copy, from its start.
+    pub(crate) fn reader(&self) -> Result<Option<File>> {
+        let SpillState::Complete(spill) = &self.state else {
+            return Ok(None);
+        };
+        Ok(Some(File::from(std::fs::File::open(&spill.path)?)))
+    }
+
+    /// Remove the file, once no further transmission can need it.
+    pub(crate) fn release(&mut self) {
+        self.state = SpillState::Idle;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_std::io::ReadExt;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("zjhttpc-spill-test-{}-{name}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn files_in(dir: &Path) -> usize {
+        std::fs::read_dir(dir).unwrap().count()
+    }
+
+    #[async_std::test]
+    async fn complete_copy_is_replayed_and_removed() {
+        let dir = temp_dir("complete");
+        let mut spill = ReplaySpill::new(&dir, 10);
+        spill.begin().await;
+        spill.write(b"hello ").await;
+        spill.write(b"you").await;
+        assert!(!spill.is_complete());
+        spill.finish().await;
+        assert!(spill.is_complete());
+
+        // A replay doesn't start a new copy.
+        spill.begin().await;
+        for _ in 0..2 {
+            let mut body = String::new();
+            spill.reader().unwrap().unwrap().read_to_string(&mut body).await.unwrap();
+            assert_eq!(body, "hello you");
+        }
+        assert_eq!(files_in(&dir), 1);
+        spill.release();
+        assert_eq!(files_in(&dir), 0);
+        assert!(spill.reader().unwrap().is_none());
+    }
+
+    #[async_std::test]
+    async fn over_cap_or_unwritable_is_not_replayable() {
+        let dir = temp_dir("cap");
+        let mut spill = ReplaySpill::new(&dir, 4);
+        spill.begin().await;
+        spill.write(b"1234").await;
+        spill.write(b"5").await;
+        spill.finish().await;
+        assert!(!spill.is_complete());
+        assert_eq!(files_in(&dir), 0);
+
+        let mut spill = ReplaySpill::new(dir.join("missing"), 4);
+        spill.begin().await;
+        spill.write(b"12").await;
+        spill.finish().await;
+        assert!(!spill.is_complete());
+    }
+
+    #[async_std::test]
+    async fn abandoned_and_dropped_copies_are_removed() {
+        let dir = temp_dir("drop");
+        let mut spill = ReplaySpill::new(&dir, 100);
+        spill.begin().await;
+        spill.write(b"partial").await;
+        spill.abandon();
+        assert_eq!(files_in(&dir), 0);
+
+        spill.begin().await;
+        spill.write(b"whole").await;
+        spill.finish().await;
+        assert_eq!(files_in(&dir), 1);
+        drop(spill);
+        assert_eq!(files_in(&dir), 0);
+    }
+}
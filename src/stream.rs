@@ -2,27 +2,35 @@ use std::any::Any;
 use std::pin::Pin;
 use std::task::{Context, Poll};
 
-use async_std::{io, net::TcpStream};
-use async_tls::client::TlsStream;
+use async_std::io;
 
 pub trait AsAny {
     fn as_any(&self) -> &dyn Any;
     fn as_any_mut(&mut self) -> &mut dyn Any;
 }
-pub trait RWStream: io::Read + io::Write + Unpin + Sync + Send + 'static {}
+
+mod sealed {
+    pub trait Sealed {}
+}
+
+/// A connection the client can read and write. Sealed: every type with the
+/// bounds gets it, so a [`Connector`](crate::connector::Connector) can box any
+/// stream, but nothing outside the crate implements it by hand.
+pub trait RWStream: sealed::Sealed + io::Read + io::Write + Unpin + Sync + Send + 'static {}
+impl<T: io::Read + io::Write + Unpin + Sync + Send + 'static> sealed::Sealed for T {}
+impl<T: io::Read + io::Write + Unpin + Sync + Send + 'static> RWStream for T {}
+
 impl<T: Any + RWStream> AsAny for T {
     fn as_any(&self) -> &dyn Any {
-        return self;
+        self
     }
 
     fn as_any_mut(&mut self) -> &mut dyn Any {
-        return self;
+        self
     }
 }
 
-impl RWStream for TcpStream {}
-impl RWStream for TlsStream<TcpStream> {}
-impl RWStream for TlsStream<BoxedStream> {}
+/// A [`RWStream`] that can be downcast; sealed like it.
 pub trait AnyStream: RWStream + AsAny {}
 impl<T: RWStream + AsAny> AnyStream for T {}
 pub type BoxedStream = Box<dyn AnyStream>;
@@ -67,6 +75,22 @@ impl<A: io::Read + Unpin, B: io::Read + Unpin> io::Read for ChainRead<A, B> {
     }
 }
 
+/// Writes go to `second`, so a buffered prefix can be put in front of a
+/// connection that stays writable (see `Response::into_upgraded_stream`).
+impl<A: Unpin, B: io::Write + Unpin> io::Write for ChainRead<A, B> {
+    fn poll_write(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.second).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.second).poll_flush(cx)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.second).poll_close(cx)
+    }
+}
+
 /// A trivial async `Read` over a byte slice (no heap allocation).
 pub struct SliceRead {
     data: [u8; 4096],
@@ -0,0 +1,167 @@
+//! One summary per request, for metrics exporters.
+//!
+//! With [`ClientInner::on_complete`](crate::client::ClientInner::on_complete)
+//! set, every [`ZJHttpClient::send`](crate::client::ZJHttpClient::send) hands
+//! the hook a [`RequestSummary`] just before it returns: once per call, after
+//! stale-connection retries and redirects, whether it succeeded or failed.
+//! The split `send_header_only` / `send_body_only` calls don't report.
+//!
+//! The summary is taken when `send()` returns, which is once the response
+//! head is in: the body has not been read yet, so `bytes_received` covers the
+//! head and whatever part of the body arrived with it.
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::{error::Result, response::Response};
+
+/// Hook set with [`ZJHttpClientBuilder::on_complete`](crate::client::ZJHttpClientBuilder::on_complete).
+pub type CompletionHook = Arc<dyn Fn(&RequestSummary) + Send + Sync>;
+
+/// What one `send()` did and how it went.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestSummary {
+    /// Method the request was sent with; a redirect that switched to GET
+    /// still reports the original method.
+    pub method: &'static str,
+    /// Host of the request as it was passed to `send()`, before redirects.
+    pub host: String,
+    /// Status of the final response; `None` when `send()` failed.
+    pub status: Option<u16>,
+    /// [`ZjhttpcError::code`](crate::ZjhttpcError::code) of the failure;
+    /// `None` when `send()` succeeded.
+    pub error_code: Option<&'static str>,
+    /// From the call to `send()` until it returned.
+    pub duration: Duration,
+    /// Where `duration` went, summed over redirects.
+    pub phases: PhaseDurations,
+    /// Exchanges started: one per hop, plus one for every retry of a stale
+    /// pooled connection. 0 for a deduplicated request.
+    pub attempts: u32,
+    /// Whether a redirect or retry was given up because the request's
+    /// `max_total_attempt_duration` had run out; the result is then the last
+    /// redirect response or the error of the last attempt.
+    pub attempt_budget_exhausted: bool,
+    /// Redirects followed.
+    pub redirects: usize,
+    /// Bytes written to the connection the final response arrived on; 0
+    /// when `send()` failed or the response was deduplicated.
+    pub bytes_sent: u64,
+    /// Bytes read from that connection by the time `send()` returned.
+    pub bytes_received: u64,
+    /// See [`Response::connection_id`].
+    pub connection_id: Option<u64>,
+    /// See [`Response::reused_connection`].
+    pub reused_connection: bool,
+    /// Whether a redirect hop went out on the connection of the hop before
+    /// it, handed over directly rather than through the pool.
+    pub reused_inline: bool,
+    /// See [`Response::deduplicated`].
+    pub deduplicated: bool,
+}
+
+/// Time spent in each stage of `send()`. A stage that failed counts up to
+/// the failure; stages never reached stay zero.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PhaseDurations {
+    /// Resolving the host.
+    pub resolve: Duration,
+    /// Picking a pooled connection or connecting, TLS and proxy handshakes
+    /// included.
+    pub connect: Duration,
+    /// Writing the request head, and waiting for `100 Continue` if asked to.
+    pub send_head: Duration,
+    /// Writing the body and reading the response head, retries on a fresh
+    /// connection included.
+    pub exchange: Duration,
+}
+
+/// Collects a [`RequestSummary`] while `send()` runs.
+pub(crate) struct SummaryRecorder {
+    started: Instant,
+    method: &'static str,
+    host: String,
+    pub(crate) phases: PhaseDurations,
+    pub(crate) attempts: Attempts,
+    pub(crate) redirects: usize,
+    pub(crate) reused_inline: bool,
+}
+
+impl SummaryRecorder {
+    pub(crate) fn new(started: Instant, method: &'static str, host: &str) -> Self {
+        SummaryRecorder {
+            started,
+            method,
+            host: host.to_owned(),
+            phases: PhaseDurations::default(),
+            attempts: Attempts::default(),
+            redirects: 0,
+            reused_inline: false,
+        }
+    }
+
+    /// The summary of a `send()` that ended at `now` with `result`.
+    pub(crate) fn finish(self, result: &mut Result<Response>, now: Instant) -> RequestSummary {
+        let (status, error_code, wire, connection_id, reused_connection, deduplicated) = match result {
+            Ok(resp) => {
+                let wire = resp.body_raw_stream.as_mut().and_then(crate::client::wire_bytes);
+                (Some(resp.status_code), None, wire, resp.connection_id, resp.reused_connection, resp.deduplicated)
+            }
+            Err(e) => (None, Some(e.code()), None, None, false, false),
+        };
+        let (bytes_sent, bytes_received) = wire.unwrap_or_default();
+        RequestSummary {
+            method: self.method,
+            host: self.host,
+            status,
+            error_code,
+            duration: now.saturating_duration_since(self.started),
+            phases: self.phases,
+            attempts: self.attempts.count,
+            attempt_budget_exhausted: self.attempts.exhausted,
+            redirects: self.redirects,
+            bytes_sent,
+            bytes_received,
+            connection_id,
+            reused_connection,
+            reused_inline: self.reused_inline,
+            deduplicated,
+        }
+    }
+}
+
+/// The round trips of one `send()` and the time budget they share: every
+/// redirect hop and stale-connection retry asks [`Attempts::allow_another`]
+/// before it starts.
+#[derive(Debug, Default)]
+pub(crate) struct Attempts {
+    pub(crate) count: u32,
+    /// `max_total_attempt_duration`, measured from the start of the first
+    /// attempt; `None` for no limit.
+    limit: Option<Duration>,
+    first: Option<Instant>,
+    pub(crate) exhausted: bool,
+}
+
+impl Attempts {
+    pub(crate) fn new(limit: Option<Duration>) -> Self {
+        Attempts { limit, ..Attempts::default() }
+    }
+
+    /// Count an attempt starting at `now`.
+    pub(crate) fn start(&mut self, now: Instant) {
+        self.count += 1;
+        self.first.get_or_insert(now);
+    }
+
+    /// Whether another attempt may start at `now`. Once the budget has run
+    /// out this stays false and marks the attempts `exhausted`.
+    pub(crate) fn allow_another(&mut self, now: Instant) -> bool {
+        if let (Some(limit), Some(first)) = (self.limit, self.first)
+            && now.saturating_duration_since(first) >= limit
+        {
+            self.exhausted = true;
+        }
+        !self.exhausted
+    }
+}
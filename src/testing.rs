@@ -0,0 +1,606 @@
+//! Fault injection for chaos testing.
+//!
+//! Only compiled with the `testing` feature (and in this crate's own tests), so
+//! production builds don't carry it. A [`FaultInjection`] wraps the client's
+//! [`Resolver`] and [`Connector`], which are the points where `send()` looks a
+//! host up, opens a connection and later reads the response from it. Install it
+//! with [`ZJHttpClientBuilder::set_fault_injection`](crate::client::ZJHttpClientBuilder::set_fault_injection).
+//!
+//! ```
+//! use std::time::Duration;
+//! use zjhttpc::client::ZJHttpClient;
+//! use zjhttpc::testing::{Fault, FaultInjection, FaultTrigger};
+//!
+//! let faults = FaultInjection::new()
+//!     .with_seed(7)
+//!     .add_fault("*.example.com", Fault::DelayFirstByte(Duration::from_secs(2)), FaultTrigger::Probability(0.1))
+//!     .add_fault("api.example.com", Fault::AbortAfterBytes(100), FaultTrigger::Schedule(vec![false, true]));
+//! let client = ZJHttpClient::builder().set_fault_injection(faults).build().unwrap();
+//! ```
+//!
+//! [`MockClock`] replaces the client's [`Clock`] with one that tests advance by
+//! hand.
+//!
+//! [`cassette`] records exchanges and replays them without a server.
+
+use std::future::Future;
+use std::io;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant, SystemTime};
+
+use futures::future::BoxFuture;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+pub mod cassette;
+
+use crate::{
+    clock::Clock,
+    connector::Connector,
+    error::{Result, ZjhttpcError},
+    resolver::Resolver,
+    stream::BoxedStream,
+};
+
+/// A failure to inject.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Fault {
+    /// Sleep this long before resolving the host.
+    DelayResolve(Duration),
+    /// Fail the connect with an IO error of this kind.
+    FailConnect(io::ErrorKind),
+    /// Hold back the first byte of the response for this long.
+    DelayFirstByte(Duration),
+    /// Abort the connection once this many response bytes have been read.
+    AbortAfterBytes(usize),
+}
+
+/// Decides whether a matching fault fires on a given attempt.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FaultTrigger {
+    /// Fire on every attempt.
+    Always,
+    /// Fire with the given probability (0.0 ..= 1.0).
+    Probability(f64),
+    /// Fire on the n-th matching attempt if `schedule[n]` is true; attempts past
+    /// the end of the schedule never fire.
+    Schedule(Vec<bool>),
+}
+
+#[derive(Debug)]
+struct FaultRule {
+    host_pattern: String,
+    fault: Fault,
+    trigger: FaultTrigger,
+    attempts: AtomicUsize,
+}
+
+impl FaultRule {
+    fn matches_host(&self, host: &str) -> bool {
+        let pattern = self.host_pattern.as_str();
+        if pattern == "*" {
+            return true;
+        }
+        match pattern.strip_prefix('*') {
+            Some(suffix) => host.len() > suffix.len() && host.to_ascii_lowercase().ends_with(&suffix.to_ascii_lowercase()),
+            None => host.eq_ignore_ascii_case(pattern),
+        }
+    }
+}
+
+/// A set of fault rules, shared by every connection of the client it is installed on.
+#[derive(Debug, Clone)]
+pub struct FaultInjection {
+    rules: Vec<Arc<FaultRule>>,
+    rng: Arc<Mutex<StdRng>>,
+}
+
+impl Default for FaultInjection {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FaultInjection {
+    pub fn new() -> Self {
+        FaultInjection {
+            rules: Vec::new(),
+            rng: Arc::new(Mutex::new(StdRng::from_os_rng())),
+        }
+    }
+
+    /// Seed the random source used by [`FaultTrigger::Probability`], making runs reproducible.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.rng = Arc::new(Mutex::new(StdRng::seed_from_u64(seed)));
+        self
+    }
+
+    /// Add a fault for hosts matching `host_pattern`: an exact host name, `*`
+    /// for every host, or `*.example.com` for any subdomain of `example.com`.
+    pub fn add_fault(mut self, host_pattern: impl AsRef<str>, fault: Fault, trigger: FaultTrigger) -> Self {
+        self.rules.push(Arc::new(FaultRule {
+            host_pattern: host_pattern.as_ref().to_owned(),
+            fault,
+            trigger,
+            attempts: AtomicUsize::new(0),
+        }));
+        self
+    }
+
+    pub(crate) fn wrap_resolver(&self, inner: Arc<dyn Resolver>) -> Arc<dyn Resolver> {
+        Arc::new(FaultyResolver { inner, faults: self.clone() })
+    }
+
+    pub(crate) fn wrap_connector(&self, inner: Arc<dyn Connector>) -> Arc<dyn Connector> {
+        Arc::new(FaultyConnector { inner, faults: self.clone() })
+    }
+
+    /// Evaluate every rule for `host` accepted by `kind`, returning the faults that fire.
+    fn fire(&self, host: &str, kind: impl Fn(&Fault) -> bool) -> Vec<Fault> {
+        self.rules
+            .iter()
+            .filter(|rule| kind(&rule.fault) && rule.matches_host(host))
+            .filter(|rule| {
+                let attempt = rule.attempts.fetch_add(1, Ordering::Relaxed);
+                match &rule.trigger {
+                    FaultTrigger::Always => true,
+                    FaultTrigger::Probability(p) => {
+                        let mut rng = self.rng.lock().unwrap_or_else(|e| e.into_inner());
+                        rng.random_bool(p.clamp(0.0, 1.0))
+                    }
+                    FaultTrigger::Schedule(schedule) => schedule.get(attempt).copied().unwrap_or(false),
+                }
+            })
+            .map(|rule| rule.fault.clone())
+            .collect()
+    }
+}
+
+struct FaultyResolver {
+    inner: Arc<dyn Resolver>,
+    faults: FaultInjection,
+}
+
+impl Resolver for FaultyResolver {
+    fn resolve<'a>(&'a self, host: &'a str, port: u16) -> BoxFuture<'a, Result<Vec<SocketAddr>>> {
+        Box::pin(async move {
+            for fault in self.faults.fire(host, |f| matches!(f, Fault::DelayResolve(_))) {
+                if let Fault::DelayResolve(delay) = fault {
+                    async_std::task::sleep(delay).await;
+                }
+            }
+            self.inner.resolve(host, port).await
+        })
+    }
+}
+
+struct FaultyConnector {
+    inner: Arc<dyn Connector>,
+    faults: FaultInjection,
+}
+
+impl Connector for FaultyConnector {
+    fn connect<'a>(
+        &'a self,
+        host: &'a str,
+        addr: SocketAddr,
+        connect_timeout: Duration,
+    ) -> BoxFuture<'a, Result<BoxedStream>> {
+        Box::pin(async move {
+            if let Some(Fault::FailConnect(kind)) = self
+                .faults
+                .fire(host, |f| matches!(f, Fault::FailConnect(_)))
+                .into_iter()
+                .next()
+            {
+                return Err(ZjhttpcError::from(io::Error::new(kind, format!("injected connect fault for {host}"))));
+            }
+            let stream = self.inner.connect(host, addr, connect_timeout).await?;
+
+            let mut first_byte_delay = None;
+            let mut abort_after = None;
+            for fault in self
+                .faults
+                .fire(host, |f| matches!(f, Fault::DelayFirstByte(_) | Fault::AbortAfterBytes(_)))
+            {
+                match fault {
+                    Fault::DelayFirstByte(delay) => first_byte_delay = Some(delay),
+                    Fault::AbortAfterBytes(n) => abort_after = Some(n),
+                    _ => {}
+                }
+            }
+            if first_byte_delay.is_none() && abort_after.is_none() {
+                return Ok(stream);
+            }
+            Ok(Box::new(FaultyStream {
+                inner: stream,
+                first_byte_delay: first_byte_delay
+                    .map(|d| Mutex::new(Box::pin(async_std::task::sleep(d)) as BoxFuture<'static, ()>)),
+                abort_after,
+                read_so_far: 0,
+            }) as BoxedStream)
+        })
+    }
+}
+
+/// Stream wrapper applying the per-connection response faults.
+struct FaultyStream {
+    inner: BoxedStream,
+    // Mutex only to make the future `Sync`; it is always accessed through `&mut`.
+    first_byte_delay: Option<Mutex<BoxFuture<'static, ()>>>,
+    abort_after: Option<usize>,
+    read_so_far: usize,
+}
+
+impl async_std::io::Read for FaultyStream {
+    fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<io::Result<usize>> {
+        if let Some(delay) = &mut self.first_byte_delay {
+            let delay = delay.get_mut().unwrap_or_else(|e| e.into_inner());
+            match delay.as_mut().poll(cx) {
+                Poll::Ready(()) => self.first_byte_delay = None,
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+        let limit = match self.abort_after {
+            Some(max) if self.read_so_far >= max => {
+                return Poll::Ready(Err(io::Error::new(
+                    io::ErrorKind::ConnectionAborted,
+                    format!("injected fault: connection aborted after {max} bytes"),
+                )));
+            }
+            Some(max) => buf.len().min(max - self.read_so_far),
+            None => buf.len(),
+        };
+        match Pin::new(&mut self.inner).poll_read(cx, &mut buf[..limit]) {
+            Poll::Ready(Ok(n)) => {
+                self.read_so_far += n;
+                Poll::Ready(Ok(n))
+            }
+            other => other,
+        }
+    }
+}
+
+impl async_std::io::Write for FaultyStream {
+    fn poll_write(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_close(cx)
+    }
+}
+
+/// A [`Clock`] that only moves when told to, for testing idle eviction and
+/// other time-dependent behaviour without real waits. Clones share the time.
+///
+/// ```
+/// use std::sync::Arc;
+/// use std::time::Duration;
+/// use zjhttpc::client::ZJHttpClient;
+/// use zjhttpc::testing::MockClock;
+///
+/// let clock = MockClock::new();
+/// let client = ZJHttpClient::builder().set_clock(Arc::new(clock.clone())).build().unwrap();
+/// // ... send, then let pooled connections go idle for two minutes:
+/// clock.advance(Duration::from_secs(120));
+/// ```
+#[derive(Debug, Clone)]
+pub struct MockClock {
+    start_instant: Instant,
+    start_system: SystemTime,
+    state: Arc<Mutex<MockClockState>>,
+}
+
+#[derive(Debug, Default)]
+struct MockClockState {
+    elapsed: Duration,
+    /// Pending sleeps: the elapsed time they end at and how to wake them.
+    sleepers: Vec<(Duration, async_std::channel::Sender<()>)>,
+}
+
+impl Default for MockClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MockClock {
+    /// A clock standing at the current system time.
+    pub fn new() -> Self {
+        Self::starting_at(SystemTime::now())
+    }
+
+    /// A clock whose wall-clock time starts at `start`.
+    pub fn starting_at(start: SystemTime) -> Self {
+        MockClock {
+            start_instant: Instant::now(),
+            start_system: start,
+            state: Arc::default(),
+        }
+    }
+
+    /// Move time forward by `duration`, waking every sleep that ends by then.
+    pub fn advance(&self, duration: Duration) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.elapsed += duration;
+        let now = state.elapsed;
+        // Dropping a sender wakes its sleep.
+        state.sleepers.retain(|(wake_at, _)| *wake_at > now);
+    }
+
+    /// Move time forward to the end of the earliest pending sleep, waking it.
+    /// Returns how far time moved, or `None` when nothing is asleep.
+    pub fn advance_to_next_wake(&self) -> Option<Duration> {
+        let next = {
+            let state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+            let wake_at = state.sleepers.iter().map(|(wake_at, _)| *wake_at).min()?;
+            wake_at.saturating_sub(state.elapsed)
+        };
+        self.advance(next);
+        Some(next)
+    }
+
+    /// Run `fut` to completion, moving time to the end of each sleep as soon
+    /// as one is pending, so code that sleeps on this clock runs without
+    /// real waits.
+    pub async fn run<T>(&self, fut: impl Future<Output = T>) -> T {
+        let driver = async {
+            loop {
+                if self.advance_to_next_wake().is_none() {
+                    async_std::task::yield_now().await;
+                }
+            }
+        };
+        match futures::future::select(std::pin::pin!(fut), std::pin::pin!(driver)).await {
+            futures::future::Either::Left((out, _)) => out,
+            futures::future::Either::Right((never, _)) => never,
+        }
+    }
+
+    /// Number of sleeps waiting for the clock to advance.
+    pub fn pending_sleeps(&self) -> usize {
+        self.state.lock().unwrap_or_else(|e| e.into_inner()).sleepers.len()
+    }
+
+    fn elapsed(&self) -> Duration {
+        self.state.lock().unwrap_or_else(|e| e.into_inner()).elapsed
+    }
+}
+
+impl Clock for MockClock {
+    fn now_instant(&self) -> Instant {
+        self.start_instant + self.elapsed()
+    }
+
+    fn now_system(&self) -> SystemTime {
+        self.start_system + self.elapsed()
+    }
+
+    fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()> {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        if duration.is_zero() {
+            return Box::pin(async {});
+        }
+        let (wake, woken) = async_std::channel::bounded(1);
+        let wake_at = state.elapsed + duration;
+        state.sleepers.push((wake_at, wake));
+        Box::pin(async move {
+            let _ = woken.recv().await;
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::ZJHttpClient;
+    use crate::error::RequestPhase;
+    use crate::methods;
+    use crate::requestx::Request;
+    use async_std::io::{ReadExt, WriteExt};
+    use async_std::net::TcpListener;
+
+    const RESPONSE: &[u8] = b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello";
+    const RESPONSE_HEAD_LEN: usize = RESPONSE.len() - 5;
+
+    /// Serves `RESPONSE` for every request on every connection; returns the port.
+    async fn spawn_server() -> u16 {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        async_std::task::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                async_std::task::spawn(async move {
+                    let mut buf = Vec::new();
+                    let mut byte = [0u8; 1];
+                    loop {
+                        match stream.read(&mut byte).await {
+                            Ok(0) | Err(_) => return,
+                            Ok(_) => buf.push(byte[0]),
+                        }
+                        if buf.ends_with(b"\r\n\r\n") {
+                            buf.clear();
+                            if stream.write_all(RESPONSE).await.is_err() {
+                                return;
+                            }
+                        }
+                    }
+                });
+            }
+        });
+        port
+    }
+
+    #[async_std::test]
+    async fn test_mock_clock_wakes_sleeps_when_advanced() {
+        let clock = MockClock::starting_at(SystemTime::UNIX_EPOCH);
+        let short = async_std::task::spawn(clock.sleep(Duration::from_secs(5)));
+        let long = async_std::task::spawn(clock.sleep(Duration::from_secs(60)));
+        assert_eq!(clock.pending_sleeps(), 2);
+
+        clock.advance(Duration::from_secs(5));
+        short.await;
+        assert_eq!(clock.pending_sleeps(), 1);
+        assert_eq!(clock.now_system(), SystemTime::UNIX_EPOCH + Duration::from_secs(5));
+
+        clock.advance(Duration::from_secs(55));
+        long.await;
+        clock.sleep(Duration::ZERO).await;
+    }
+
+    fn client_with(faults: FaultInjection) -> ZJHttpClient {
+        ZJHttpClient::builder()
+            .set_fault_injection(faults)
+            .set_global_read_header_timeout(Duration::from_millis(200))
+            .build()
+            .unwrap()
+    }
+
+    #[async_std::test]
+    async fn test_delay_resolve_reports_resolve_phase() {
+        let faults = FaultInjection::new().add_fault(
+            "127.0.0.1",
+            Fault::DelayResolve(Duration::from_secs(5)),
+            FaultTrigger::Always,
+        );
+        let client = client_with(faults);
+        let mut req = Request::new(methods::GET, "http://127.0.0.1:1/")
+            .unwrap()
+            .set_connect_timeout(Duration::from_millis(50));
+        let err = client.send(&mut req).await.err().expect("request should fail");
+        assert!(matches!(err, ZjhttpcError::Dns { .. }), "got {err:?}");
+        assert_eq!(err.phase(), Some(RequestPhase::Resolve));
+    }
+
+    #[async_std::test]
+    async fn test_fail_connect_reports_connect_phase() {
+        let port = spawn_server().await;
+        let faults = FaultInjection::new().add_fault(
+            "127.0.0.1",
+            Fault::FailConnect(io::ErrorKind::ConnectionRefused),
+            FaultTrigger::Always,
+        );
+        let client = client_with(faults);
+        let mut req = Request::new(methods::GET, format!("http://127.0.0.1:{port}/")).unwrap();
+        let err = client.send(&mut req).await.err().expect("request should fail");
+        match &err {
+            ZjhttpcError::Io { source, .. } => assert_eq!(source.kind(), io::ErrorKind::ConnectionRefused),
+            other => panic!("expected Io, got {other:?}"),
+        }
+        assert_eq!(err.phase(), Some(RequestPhase::Connect));
+    }
+
+    #[async_std::test]
+    async fn test_delay_first_byte_reports_read_header_phase() {
+        let port = spawn_server().await;
+        let faults = FaultInjection::new().add_fault(
+            "127.0.0.1",
+            Fault::DelayFirstByte(Duration::from_secs(5)),
+            FaultTrigger::Always,
+        );
+        let client = client_with(faults);
+        let mut req = Request::new(methods::GET, format!("http://127.0.0.1:{port}/")).unwrap();
+        let err = client.send(&mut req).await.err().expect("request should fail");
+        assert!(matches!(err, ZjhttpcError::ReadHeaderTimeout { .. }), "got {err:?}");
+        assert_eq!(err.phase(), Some(RequestPhase::ReadHeader));
+    }
+
+    #[async_std::test]
+    async fn test_abort_inside_headers_reports_read_header_phase() {
+        let port = spawn_server().await;
+        let faults = FaultInjection::new().add_fault("127.0.0.1", Fault::AbortAfterBytes(5), FaultTrigger::Always);
+        let client = client_with(faults);
+        let mut req = Request::new(methods::GET, format!("http://127.0.0.1:{port}/")).unwrap();
+        let err = client.send(&mut req).await.err().expect("request should fail");
+        match &err {
+            ZjhttpcError::Io { source, .. } => assert_eq!(source.kind(), io::ErrorKind::ConnectionAborted),
+            other => panic!("expected Io, got {other:?}"),
+        }
+        assert_eq!(err.phase(), Some(RequestPhase::ReadHeader));
+    }
+
+    #[async_std::test]
+    async fn test_abort_inside_body_reports_read_body_phase() {
+        let port = spawn_server().await;
+        let faults = FaultInjection::new().add_fault(
+            "127.0.0.1",
+            Fault::AbortAfterBytes(RESPONSE_HEAD_LEN + 2),
+            FaultTrigger::Always,
+        );
+        let client = client_with(faults);
+        let mut req = Request::new(methods::GET, format!("http://127.0.0.1:{port}/")).unwrap();
+        let mut resp = client.send(&mut req).await.unwrap();
+        assert_eq!(resp.status_code(), 200);
+        let err = resp.body_bytes().await.unwrap_err();
+        assert_eq!(err.phase(), Some(RequestPhase::ReadBody), "got {err:?}");
+    }
+
+    #[async_std::test]
+    async fn test_non_matching_host_pattern_is_untouched() {
+        let port = spawn_server().await;
+        let faults = FaultInjection::new().add_fault(
+            "*.example.com",
+            Fault::FailConnect(io::ErrorKind::ConnectionRefused),
+            FaultTrigger::Always,
+        );
+        let client = client_with(faults);
+        let mut req = Request::new(methods::GET, format!("http://127.0.0.1:{port}/")).unwrap();
+        let mut resp = client.send(&mut req).await.unwrap();
+        assert_eq!(resp.body_string().await.unwrap(), "hello");
+    }
+
+    #[async_std::test]
+    async fn test_schedule_fires_only_on_listed_attempts() {
+        let port = spawn_server().await;
+        let faults = FaultInjection::new().add_fault(
+            "127.0.0.1",
+            Fault::FailConnect(io::ErrorKind::ConnectionReset),
+            FaultTrigger::Schedule(vec![true, false]),
+        );
+        let client = client_with(faults);
+        let url = format!("http://127.0.0.1:{port}/");
+        let err = client.send(&mut Request::new(methods::GET, &url).unwrap()).await.err().expect("request should fail");
+        assert_eq!(err.phase(), Some(RequestPhase::Connect));
+        let mut resp = client.send(&mut Request::new(methods::GET, &url).unwrap()).await.unwrap();
+        assert_eq!(resp.body_string().await.unwrap(), "hello");
+    }
+
+    #[test]
+    fn test_probability_is_deterministic_with_seed() {
+        let make = || {
+            FaultInjection::new().with_seed(42).add_fault(
+                "*",
+                Fault::AbortAfterBytes(1),
+                FaultTrigger::Probability(0.5),
+            )
+        };
+        let (a, b) = (make(), make());
+        let run = |f: &FaultInjection| (0..32).map(|_| !f.fire("host", |_| true).is_empty()).collect::<Vec<_>>();
+        let fired = run(&a);
+        assert_eq!(fired, run(&b));
+        assert!(fired.iter().any(|x| *x) && fired.iter().any(|x| !*x));
+    }
+
+    #[test]
+    fn test_host_pattern_matching() {
+        let rule = |p: &str| FaultRule {
+            host_pattern: p.to_owned(),
+            fault: Fault::AbortAfterBytes(0),
+            trigger: FaultTrigger::Always,
+            attempts: AtomicUsize::new(0),
+        };
+        assert!(rule("*").matches_host("anything"));
+        assert!(rule("*.example.com").matches_host("api.Example.com"));
+        assert!(!rule("*.example.com").matches_host("example.com"));
+        assert!(rule("Example.com").matches_host("example.com"));
+        assert!(!rule("example.com").matches_host("example.org"));
+    }
+}
@@ -0,0 +1,782 @@
+//! Byte-exact record/replay of HTTP exchanges, for hermetic tests of code
+//! built on this client.
+//!
+//! Record once against a real (or mock) server, save the [`Cassette`] next
+//! to the tests, then replay it with the network out of the picture:
+//!
+//! ```no_run
+//! # async fn run() -> zjhttpc::Result<()> {
+//! use zjhttpc::client::ZJHttpClient;
+//! use zjhttpc::testing::cassette::{Cassette, RequestMatcher};
+//!
+//! // Recording: requests go out as usual and every exchange is captured.
+//! let cassette = Cassette::new();
+//! let client = ZJHttpClient::builder().set_cassette_recording(&cassette).build()?;
+//! // ... exercise the code under test with `client` ...
+//! cassette.save("tests/cassettes/login")?;
+//!
+//! // Replay: answers come from the cassette, nothing is resolved or dialled.
+//! let cassette = Cassette::load("tests/cassettes/login")?;
+//! let client = ZJHttpClient::builder()
+//!     .set_cassette_replay(&cassette, RequestMatcher::new().header("authorization"))
+//!     .build()?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Recording sits at the [`Connector`], below the client: an entry holds the
+//! request exactly as written (head and body) and the response bytes exactly
+//! as read, and replay feeds those bytes back through the normal response
+//! parsing. TLS is layered above the connector, so only plain `http://`
+//! connections are recorded; a TLS connection passes through unrecorded.
+//!
+//! A cassette on disk is a directory holding, per entry, `NNNN.request.bin`,
+//! `NNNN.response.bin` and `NNNN.json` (where the request went, its
+//! fingerprint, and whether the server closed the connection afterwards).
+
+use std::fmt::Write as _;
+use std::io;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use futures::future::BoxFuture;
+use tracing::{debug, warn};
+
+use crate::{
+    connector::Connector,
+    error::Result,
+    log_target::WIRE,
+    resolver::Resolver,
+    stream::BoxedStream,
+};
+
+/// One request and the response read for it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CassetteEntry {
+    /// `host:port` the request was sent to.
+    pub authority: String,
+    /// Request head and body, as written.
+    pub request: Vec<u8>,
+    /// Response bytes as read, interim 1xx heads included.
+    pub response: Vec<u8>,
+    /// The server closed the connection after `response`.
+    pub closed: bool,
+}
+
+impl CassetteEntry {
+    /// Hash of the method, authority, request target and body.
+    pub fn fingerprint(&self) -> u64 {
+        let mut hash = Fnv::new();
+        match ParsedRequest::parse(&self.request) {
+            Some(req) => {
+                hash.write(req.method.as_bytes());
+                hash.write(b"\n");
+                hash.write(self.authority.as_bytes());
+                hash.write(b"\n");
+                hash.write(req.target.as_bytes());
+                hash.write(b"\n");
+                hash.write(req.body);
+            }
+            None => hash.write(&self.request),
+        }
+        hash.finish()
+    }
+}
+
+/// Recorded exchanges, in the order their requests started. Clones share
+/// the entries, so a recording client keeps adding to the cassette it was
+/// built with.
+#[derive(Debug, Clone, Default)]
+pub struct Cassette {
+    entries: Arc<Mutex<Vec<CassetteEntry>>>,
+}
+
+impl Cassette {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_entries(entries: Vec<CassetteEntry>) -> Self {
+        Cassette { entries: Arc::new(Mutex::new(entries)) }
+    }
+
+    /// A snapshot of the entries.
+    pub fn entries(&self) -> Vec<CassetteEntry> {
+        self.entries.lock().unwrap().clone()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Write the entries to `dir`, creating it if needed. Entry files from
+    /// an earlier save to the same directory are replaced.
+    pub fn save(&self, dir: impl AsRef<Path>) -> Result<()> {
+        let dir = dir.as_ref();
+        std::fs::create_dir_all(dir)?;
+        for file in std::fs::read_dir(dir)? {
+            let path = file?.path();
+            if path.file_name().and_then(|n| n.to_str()).and_then(entry_index).is_some() {
+                std::fs::remove_file(path)?;
+            }
+        }
+        for (i, entry) in self.entries().iter().enumerate() {
+            let n = i + 1;
+            let meta = serde_json::json!({
+                "authority": entry.authority,
+                "fingerprint": format!("{:016x}", entry.fingerprint()),
+                "request": ParsedRequest::parse(&entry.request).map(|r| format!("{} {}", r.method, r.target)),
+                "closed": entry.closed,
+            });
+            std::fs::write(dir.join(format!("{n:04}.request.bin")), &entry.request)?;
+            std::fs::write(dir.join(format!("{n:04}.response.bin")), &entry.response)?;
+            std::fs::write(dir.join(format!("{n:04}.json")), serde_json::to_vec_pretty(&meta).unwrap())?;
+        }
+        Ok(())
+    }
+
+    /// Read a cassette written by [`Cassette::save`].
+    pub fn load(dir: impl AsRef<Path>) -> Result<Self> {
+        let dir = dir.as_ref();
+        let mut indexes = Vec::new();
+        for file in std::fs::read_dir(dir)? {
+            let name = file?.file_name();
+            if let Some(name) = name.to_str()
+                && name.ends_with(".json")
+                && let Some(n) = entry_index(name)
+            {
+                indexes.push(n);
+            }
+        }
+        indexes.sort_unstable();
+        let mut entries = Vec::with_capacity(indexes.len());
+        for n in indexes {
+            let meta: serde_json::Value = serde_json::from_slice(&std::fs::read(dir.join(format!("{n:04}.json")))?)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{n:04}.json: {e}")))?;
+            let authority = meta["authority"]
+                .as_str()
+                .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, format!("{n:04}.json: missing authority")))?;
+            entries.push(CassetteEntry {
+                authority: authority.to_owned(),
+                request: std::fs::read(dir.join(format!("{n:04}.request.bin")))?,
+                response: std::fs::read(dir.join(format!("{n:04}.response.bin")))?,
+                closed: meta["closed"].as_bool().unwrap_or(false),
+            });
+        }
+        Ok(Self::from_entries(entries))
+    }
+
+    pub(crate) fn recorder(&self, inner: Arc<dyn Connector>) -> Arc<dyn Connector> {
+        Arc::new(RecordingConnector { inner, cassette: self.clone() })
+    }
+
+    pub(crate) fn replayer(&self, matcher: RequestMatcher) -> (Arc<dyn Resolver>, Arc<dyn Connector>) {
+        let entries = self.entries();
+        let state = Arc::new(ReplayState { used: Mutex::new(vec![false; entries.len()]), entries, matcher });
+        (Arc::new(ReplayResolver), Arc::new(ReplayConnector { state }))
+    }
+}
+
+/// The `NNNN` of a cassette file name.
+fn entry_index(name: &str) -> Option<usize> {
+    let (n, rest) = name.split_once('.')?;
+    if n.len() != 4 || !matches!(rest, "json" | "request.bin" | "response.bin") {
+        return None;
+    }
+    n.parse().ok()
+}
+
+/// (label, value) pairs compared between a request and a recorded one.
+type Criteria = Vec<(String, String)>;
+
+/// Which parts of a request must equal the recorded one for replay. Method
+/// and URL always do; add headers and the body as needed.
+#[derive(Debug, Clone, Default)]
+pub struct RequestMatcher {
+    headers: Vec<String>,
+    body: bool,
+}
+
+impl RequestMatcher {
+    /// Match on method and URL.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Also match the values of header `name` (absent matches absent).
+    pub fn header(mut self, name: impl AsRef<str>) -> Self {
+        self.headers.push(name.as_ref().to_ascii_lowercase());
+        self
+    }
+
+    /// Also match the body, by length and hash. A body held back behind
+    /// `Expect: 100-continue` is not compared.
+    pub fn body(mut self) -> Self {
+        self.body = true;
+        self
+    }
+
+    /// The compared properties of `req`.
+    fn criteria(&self, authority: &str, req: &ParsedRequest<'_>, compare_body: bool) -> Criteria {
+        let mut criteria = vec![(String::new(), format!("{} http://{authority}{}", req.method, req.target))];
+        for name in &self.headers {
+            let values: Vec<&str> = req
+                .headers
+                .iter()
+                .filter(|(n, _)| n.eq_ignore_ascii_case(name))
+                .map(|(_, v)| *v)
+                .collect();
+            let value = if values.is_empty() { "<absent>".to_string() } else { values.join(", ") };
+            criteria.push((format!("{name}: "), value));
+        }
+        if compare_body {
+            let mut hash = Fnv::new();
+            hash.write(req.body);
+            criteria.push(("body: ".to_string(), format!("{} bytes, hash {:016x}", req.body.len(), hash.finish())));
+        }
+        criteria
+    }
+}
+
+/// The parts of a serialized request that matching looks at.
+struct ParsedRequest<'a> {
+    method: &'a str,
+    target: &'a str,
+    headers: Vec<(&'a str, &'a str)>,
+    /// Whatever follows the head.
+    body: &'a [u8],
+}
+
+impl<'a> ParsedRequest<'a> {
+    fn parse(bytes: &'a [u8]) -> Option<Self> {
+        let end = bytes.windows(4).position(|w| w == b"\r\n\r\n")?;
+        let head = std::str::from_utf8(&bytes[..end]).ok()?;
+        let mut lines = head.split("\r\n");
+        let mut request_line = lines.next()?.split(' ');
+        let method = request_line.next()?;
+        let target = request_line.next()?;
+        let headers = lines
+            .filter_map(|line| line.split_once(':'))
+            .map(|(name, value)| (name.trim(), value.trim()))
+            .collect();
+        Some(ParsedRequest { method, target, headers, body: &bytes[end + 4..] })
+    }
+
+    fn expects_continue(&self) -> bool {
+        self.headers
+            .iter()
+            .any(|(n, v)| n.eq_ignore_ascii_case("expect") && v.eq_ignore_ascii_case("100-continue"))
+    }
+}
+
+/// FNV-1a, enough to tell bodies apart in a cassette.
+struct Fnv(u64);
+
+impl Fnv {
+    fn new() -> Self {
+        Fnv(0xcbf2_9ce4_8422_2325)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 = (self.0 ^ *byte as u64).wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+/// Whether `response` is nothing but complete 1xx heads, i.e. the final
+/// response of the exchange has not started yet.
+fn only_interim(mut response: &[u8]) -> bool {
+    if response.is_empty() {
+        return false;
+    }
+    while !response.is_empty() {
+        let Some(end) = response.windows(4).position(|w| w == b"\r\n\r\n") else {
+            return false;
+        };
+        if !response.starts_with(b"HTTP/1.") || response.get(9) != Some(&b'1') {
+            return false;
+        }
+        response = &response[end + 4..];
+    }
+    true
+}
+
+struct RecordingConnector {
+    inner: Arc<dyn Connector>,
+    cassette: Cassette,
+}
+
+impl Connector for RecordingConnector {
+    fn connect<'a>(
+        &'a self,
+        host: &'a str,
+        addr: SocketAddr,
+        connect_timeout: Duration,
+    ) -> BoxFuture<'a, Result<BoxedStream>> {
+        Box::pin(async move {
+            let inner = self.inner.connect(host, addr, connect_timeout).await?;
+            Ok(Box::new(RecordingStream {
+                inner,
+                cassette: self.cassette.clone(),
+                authority: format!("{host}:{}", addr.port()),
+                current: None,
+                responding: false,
+                passthrough: false,
+            }) as BoxedStream)
+        })
+    }
+}
+
+/// Splits a connection into exchanges: a write after the final response
+/// started reading begins the next entry.
+struct RecordingStream {
+    inner: BoxedStream,
+    cassette: Cassette,
+    authority: String,
+    /// Index of the entry being recorded.
+    current: Option<usize>,
+    /// Response bytes have been read since the last write.
+    responding: bool,
+    /// A TLS connection: bytes are passed on, not recorded.
+    passthrough: bool,
+}
+
+impl RecordingStream {
+    fn record_written(&mut self, bytes: &[u8]) {
+        if self.passthrough || bytes.is_empty() {
+            return;
+        }
+        if self.current.is_none() && bytes[0] == 0x16 {
+            warn!(target: WIRE, authority = %self.authority, "not recording a TLS connection");
+            self.passthrough = true;
+            return;
+        }
+        let mut entries = self.cassette.entries.lock().unwrap();
+        let index = match self.current {
+            Some(i) if !self.responding || only_interim(&entries[i].response) => i,
+            _ => {
+                entries.push(CassetteEntry {
+                    authority: self.authority.clone(),
+                    request: Vec::new(),
+                    response: Vec::new(),
+                    closed: false,
+                });
+                entries.len() - 1
+            }
+        };
+        entries[index].request.extend_from_slice(bytes);
+        self.current = Some(index);
+        self.responding = false;
+    }
+
+    fn record_read(&mut self, bytes: &[u8]) {
+        let Some(index) = self.current.filter(|_| !self.passthrough) else {
+            return;
+        };
+        let mut entries = self.cassette.entries.lock().unwrap();
+        if bytes.is_empty() {
+            entries[index].closed = true;
+        } else {
+            entries[index].response.extend_from_slice(bytes);
+        }
+        self.responding = true;
+    }
+}
+
+impl async_std::io::Read for RecordingStream {
+    fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<io::Result<usize>> {
+        let result = Pin::new(&mut self.inner).poll_read(cx, buf);
+        if let Poll::Ready(Ok(n)) = result {
+            self.record_read(&buf[..n]);
+        }
+        result
+    }
+}
+
+impl async_std::io::Write for RecordingStream {
+    fn poll_write(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        let result = Pin::new(&mut self.inner).poll_write(cx, buf);
+        if let Poll::Ready(Ok(n)) = result {
+            self.record_written(&buf[..n]);
+        }
+        result
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_close(cx)
+    }
+}
+
+/// Resolves every host to a loopback address that is never dialled, so
+/// replay works without DNS.
+struct ReplayResolver;
+
+impl Resolver for ReplayResolver {
+    fn resolve<'a>(&'a self, _host: &'a str, port: u16) -> BoxFuture<'a, Result<Vec<SocketAddr>>> {
+        Box::pin(async move { Ok(vec![SocketAddr::from(([127, 0, 0, 1], port))]) })
+    }
+}
+
+/// Entries to replay, each at most once, in recorded order.
+struct ReplayState {
+    entries: Vec<CassetteEntry>,
+    used: Mutex<Vec<bool>>,
+    matcher: RequestMatcher,
+}
+
+impl ReplayState {
+    /// Claim the first unreplayed entry matching `written`, or explain why
+    /// there is none.
+    fn take(&self, authority: &str, written: &[u8]) -> std::result::Result<&CassetteEntry, String> {
+        let Some(actual) = ParsedRequest::parse(written) else {
+            return Err(format!("cannot match an incomplete request head ({} bytes written)", written.len()));
+        };
+        let compare_body = self.matcher.body && !(actual.expects_continue() && actual.body.is_empty());
+        let wanted = self.matcher.criteria(authority, &actual, compare_body);
+
+        let mut used = self.used.lock().unwrap();
+        let mut closest: Option<(usize, usize, Criteria)> = None;
+        for (i, entry) in self.entries.iter().enumerate() {
+            if used[i] {
+                continue;
+            }
+            let Some(recorded) = ParsedRequest::parse(&entry.request) else {
+                continue;
+            };
+            let criteria = self.matcher.criteria(&entry.authority, &recorded, compare_body);
+            let mismatches = criteria.iter().zip(&wanted).filter(|(a, b)| a != b).count();
+            if mismatches == 0 {
+                used[i] = true;
+                debug!(target: WIRE, entry = i + 1, request = %wanted[0].1, "replaying cassette entry");
+                return Ok(entry);
+            }
+            if closest.as_ref().is_none_or(|(_, fewest, _)| mismatches < *fewest) {
+                closest = Some((i, mismatches, criteria));
+            }
+        }
+
+        let mut explanation = format!("no cassette entry matches {}", wanted[0].1);
+        match closest {
+            Some((i, _, recorded)) => {
+                let _ = writeln!(explanation, "\nclosest unreplayed entry #{} (fingerprint {:016x}):", i + 1, self.entries[i].fingerprint());
+                for ((label, was), (_, is)) in recorded.iter().zip(&wanted) {
+                    if was == is {
+                        let _ = writeln!(explanation, "  {label}{was}");
+                    } else {
+                        let _ = writeln!(explanation, "- {label}{was}");
+                        let _ = writeln!(explanation, "+ {label}{is}");
+                    }
+                }
+                explanation.truncate(explanation.trim_end().len());
+            }
+            None if self.entries.is_empty() => explanation.push_str(": the cassette is empty"),
+            None => {
+                let _ = write!(explanation, ": all {} entries were already replayed", self.entries.len());
+            }
+        }
+        Err(explanation)
+    }
+}
+
+struct ReplayConnector {
+    state: Arc<ReplayState>,
+}
+
+impl Connector for ReplayConnector {
+    fn connect<'a>(
+        &'a self,
+        host: &'a str,
+        addr: SocketAddr,
+        _connect_timeout: Duration,
+    ) -> BoxFuture<'a, Result<BoxedStream>> {
+        Box::pin(async move {
+            Ok(Box::new(ReplayStream {
+                state: self.state.clone(),
+                authority: format!("{host}:{}", addr.port()),
+                written: Vec::new(),
+                discard: 0,
+                response: Vec::new(),
+                position: 0,
+                close_after: false,
+            }) as BoxedStream)
+        })
+    }
+}
+
+/// Collects what the client writes and, once it reads, answers with the
+/// matching entry's response.
+struct ReplayStream {
+    state: Arc<ReplayState>,
+    authority: String,
+    written: Vec<u8>,
+    /// Bytes of the matched request still to come (a body sent after
+    /// `100 Continue`); they are not the start of a new request.
+    discard: usize,
+    response: Vec<u8>,
+    position: usize,
+    close_after: bool,
+}
+
+impl async_std::io::Read for ReplayStream {
+    fn poll_read(mut self: Pin<&mut Self>, _cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<io::Result<usize>> {
+        let this = &mut *self;
+        if this.position == this.response.len() {
+            if this.close_after {
+                return Poll::Ready(Ok(0));
+            }
+            if this.written.is_empty() {
+                // An idle connection: the server has nothing to say.
+                return Poll::Pending;
+            }
+            let entry = match this.state.take(&this.authority, &this.written) {
+                Ok(entry) => entry,
+                Err(explanation) => return Poll::Ready(Err(io::Error::other(explanation))),
+            };
+            this.discard = entry.request.len().saturating_sub(this.written.len());
+            this.written.clear();
+            this.response = entry.response.clone();
+            this.position = 0;
+            this.close_after = entry.closed;
+        }
+        let n = buf.len().min(this.response.len() - this.position);
+        buf[..n].copy_from_slice(&this.response[this.position..this.position + n]);
+        this.position += n;
+        Poll::Ready(Ok(n))
+    }
+}
+
+impl async_std::io::Write for ReplayStream {
+    fn poll_write(mut self: Pin<&mut Self>, _cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        let skip = self.discard.min(buf.len());
+        self.discard -= skip;
+        self.written.extend_from_slice(&buf[skip..]);
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::ZJHttpClient;
+    use crate::methods;
+    use crate::requestx::Request;
+    use async_std::io::{ReadExt, WriteExt};
+    use async_std::net::TcpListener;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Keep-alive server answering `{method} {path} {body}`; `/bye` gets a
+    /// body delimited by closing the connection. Returns the port and a
+    /// request counter.
+    async fn spawn_server() -> (u16, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let requests = Arc::new(AtomicUsize::new(0));
+        let counter = requests.clone();
+        async_std::task::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let counter = counter.clone();
+                async_std::task::spawn(async move {
+                    loop {
+                        let mut head = Vec::new();
+                        let mut byte = [0u8; 1];
+                        while !head.ends_with(b"\r\n\r\n") {
+                            match stream.read(&mut byte).await {
+                                Ok(1) => head.push(byte[0]),
+                                _ => return,
+                            }
+                        }
+                        counter.fetch_add(1, Ordering::SeqCst);
+                        let req = ParsedRequest::parse(&head).unwrap();
+                        let length = req
+                            .headers
+                            .iter()
+                            .find(|(n, _)| n.eq_ignore_ascii_case("content-length"))
+                            .map_or(0, |(_, v)| v.parse().unwrap());
+                        let mut body = vec![0u8; length];
+                        stream.read_exact(&mut body).await.unwrap();
+                        let reply = format!("{} {} {}", req.method, req.target, String::from_utf8_lossy(&body));
+                        if req.target == "/bye" {
+                            let _ = stream.write_all(format!("HTTP/1.1 200 OK\r\nConnection: close\r\n\r\n{reply}").as_bytes()).await;
+                            return;
+                        }
+                        let response = format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n{reply}", reply.len());
+                        if stream.write_all(response.as_bytes()).await.is_err() {
+                            return;
+                        }
+                    }
+                });
+            }
+        });
+        (port, requests)
+    }
+
+    async fn exercise(client: &ZJHttpClient, port: u16) -> Vec<String> {
+        let mut bodies = Vec::new();
+        for (method, path, body) in [
+            (methods::GET, "/a", ""),
+            (methods::GET, "/b", ""),
+            (methods::POST, "/c", "xyz"),
+            (methods::GET, "/bye", ""),
+        ] {
+            let mut req = Request::new(method, format!("http://127.0.0.1:{port}{path}")).unwrap();
+            if !body.is_empty() {
+                req = req.set_body_string(body);
+            }
+            let mut resp = Box::pin(client.send(&mut req)).await.unwrap();
+            bodies.push(resp.body_string().await.unwrap());
+        }
+        bodies
+    }
+
+    #[async_std::test]
+    async fn test_record_then_replay_without_the_server() {
+        let (port, requests) = spawn_server().await;
+        let cassette = Cassette::new();
+        let recording = ZJHttpClient::builder().set_cassette_recording(&cassette).build().unwrap();
+        let recorded = exercise(&recording, port).await;
+        assert_eq!(recorded, ["GET /a ", "GET /b ", "POST /c xyz", "GET /bye "]);
+        assert_eq!(requests.load(Ordering::SeqCst), 4);
+
+        let entries = cassette.entries();
+        assert_eq!(entries.len(), 4, "one entry per exchange, not per connection");
+        assert!(entries.iter().all(|e| e.authority == format!("127.0.0.1:{port}")));
+        assert!(entries[2].request.ends_with(b"\r\n\r\nxyz"));
+        assert_eq!(entries.iter().map(|e| e.closed).collect::<Vec<_>>(), [false, false, false, true]);
+
+        let dir = std::env::temp_dir().join(format!("zjhttpc-cassette-{port}"));
+        cassette.save(&dir).unwrap();
+        let loaded = Cassette::load(&dir).unwrap();
+        let _ = std::fs::remove_dir_all(&dir);
+
+        let replaying = ZJHttpClient::builder()
+            .set_cassette_replay(&loaded, RequestMatcher::new().body())
+            .build()
+            .unwrap();
+        assert_eq!(exercise(&replaying, port).await, recorded);
+        assert_eq!(requests.load(Ordering::SeqCst), 4, "replay must not reach the server");
+    }
+
+    #[async_std::test]
+    async fn test_unmatched_request_fails_with_closest_candidate() {
+        let cassette = Cassette::from_entries(vec![entry(
+            "GET /items HTTP/1.1\r\nHost: example.com\r\n\r\n",
+            "HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n",
+        )]);
+        let client = ZJHttpClient::builder()
+            .set_cassette_replay(&cassette, RequestMatcher::new())
+            .build()
+            .unwrap();
+        let mut req = Request::new(methods::GET, "http://example.com/item").unwrap();
+        let err = Box::pin(client.send(&mut req)).await.err().expect("nothing recorded for /item");
+        let message = err.to_string();
+        assert!(message.contains("no cassette entry matches GET http://example.com:80/item"), "{message}");
+        assert!(message.contains("- GET http://example.com:80/items\n+ GET http://example.com:80/item"), "{message}");
+    }
+
+    fn entry(request: &str, response: &str) -> CassetteEntry {
+        CassetteEntry {
+            authority: "example.com:80".to_string(),
+            request: request.as_bytes().to_vec(),
+            response: response.as_bytes().to_vec(),
+            closed: false,
+        }
+    }
+
+    #[test]
+    fn test_only_interim() {
+        assert!(only_interim(b"HTTP/1.1 100 Continue\r\n\r\n"));
+        assert!(only_interim(b"HTTP/1.1 100 Continue\r\n\r\nHTTP/1.1 103 Early Hints\r\nLink: </a>\r\n\r\n"));
+        assert!(!only_interim(b""));
+        assert!(!only_interim(b"HTTP/1.1 100 Continue\r\n\r\nHTTP/1.1 200 OK\r\n"));
+        assert!(!only_interim(b"HTTP/1.1 200 OK\r\n\r\n"));
+    }
+
+    #[test]
+    fn test_matching_claims_entries_in_order() {
+        let cassette = Cassette::from_entries(vec![
+            entry("GET /a HTTP/1.1\r\nHost: example.com\r\n\r\n", "first"),
+            entry("GET /a HTTP/1.1\r\nHost: example.com\r\n\r\n", "second"),
+        ]);
+        let entries = cassette.entries();
+        let state = ReplayState {
+            used: Mutex::new(vec![false; 2]),
+            entries,
+            matcher: RequestMatcher::new(),
+        };
+        let request = b"GET /a HTTP/1.1\r\nHost: example.com\r\nUser-Agent: x\r\n\r\n";
+        assert_eq!(state.take("example.com:80", request).unwrap().response, b"first");
+        assert_eq!(state.take("example.com:80", request).unwrap().response, b"second");
+        let err = state.take("example.com:80", request).unwrap_err();
+        assert!(err.ends_with("all 2 entries were already replayed"), "{err}");
+    }
+
+    #[test]
+    fn test_mismatch_explains_closest_entry() {
+        let state = ReplayState {
+            used: Mutex::new(vec![false; 2]),
+            entries: vec![
+                entry("POST /other HTTP/1.1\r\nX-Tenant: a\r\n\r\nxyz", ""),
+                entry("GET /items HTTP/1.1\r\nX-Tenant: a\r\n\r\n", ""),
+            ],
+            matcher: RequestMatcher::new().header("X-Tenant"),
+        };
+        let err = state.take("example.com:80", b"GET /items HTTP/1.1\r\nX-Tenant: b\r\n\r\n").unwrap_err();
+        let expected = "no cassette entry matches GET http://example.com:80/items\n\
+                        closest unreplayed entry #2 (fingerprint ";
+        assert!(err.starts_with(expected), "{err}");
+        assert!(err.ends_with("):\n  GET http://example.com:80/items\n- x-tenant: a\n+ x-tenant: b"), "{err}");
+    }
+
+    #[test]
+    fn test_body_matching() {
+        let state = ReplayState {
+            used: Mutex::new(vec![false]),
+            entries: vec![entry("POST /p HTTP/1.1\r\nContent-Length: 3\r\n\r\nabc", "")],
+            matcher: RequestMatcher::new().body(),
+        };
+        let err = state.take("example.com:80", b"POST /p HTTP/1.1\r\nContent-Length: 3\r\n\r\nabd").unwrap_err();
+        assert!(err.contains("- body: 3 bytes, hash"), "{err}");
+        // Behind Expect: 100-continue the body has not been written yet.
+        assert!(state.take("example.com:80", b"POST /p HTTP/1.1\r\nExpect: 100-continue\r\n\r\n").is_ok());
+    }
+
+    #[test]
+    fn test_save_and_load_round_trip() {
+        let dir = std::env::temp_dir().join(format!("zjhttpc-cassette-unit-{}", std::process::id()));
+        let mut closed = entry("GET /b HTTP/1.1\r\n\r\n", "HTTP/1.0 200 OK\r\n\r\nbye");
+        closed.closed = true;
+        let cassette = Cassette::from_entries(vec![entry("GET /a HTTP/1.1\r\n\r\n", "HTTP/1.1 204 No Content\r\n\r\n"), closed]);
+        // Saving fewer entries over an old recording leaves no stale files.
+        let mut longer = cassette.entries();
+        longer.push(entry("GET /c HTTP/1.1\r\n\r\n", ""));
+        Cassette::from_entries(longer).save(&dir).unwrap();
+        cassette.save(&dir).unwrap();
+        assert_eq!(Cassette::load(&dir).unwrap().entries(), cassette.entries());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,274 @@
+//! Bandwidth limits for request and response bodies.
+//!
+//! A [`Rate`] set with `ZJHttpClient::global_upload_rate` /
+//! `global_download_rate` (or per request with `Request::set_upload_rate` /
+//! `set_download_rate`) paces the body through a token bucket: up to `burst`
+//! bytes go out at once, then the writer or reader sleeps on the client's
+//! [`Clock`] until enough budget has built up. Request heads and response
+//! heads are not paced.
+//!
+//! Time spent asleep is reported by `Response::throttled`. It still counts
+//! toward the read body timeout, so give throttled downloads a timeout that
+//! allows for the rate.
+
+use std::pin::Pin;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use async_std::io;
+use futures::future::BoxFuture;
+
+use crate::clock::Clock;
+
+/// A bandwidth limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rate {
+    /// Sustained rate.
+    pub max_bytes_per_sec: u64,
+    /// Most bytes moved in one go, and the budget an idle transfer can save up.
+    pub burst: u64,
+}
+
+impl Rate {
+    /// `max_bytes_per_sec` with a burst of a tenth of a second's worth.
+    pub fn per_sec(max_bytes_per_sec: u64) -> Self {
+        let max_bytes_per_sec = max_bytes_per_sec.max(1);
+        Rate { max_bytes_per_sec, burst: (max_bytes_per_sec / 10).max(1) }
+    }
+
+    pub fn with_burst(mut self, burst: u64) -> Self {
+        self.burst = burst.max(1);
+        self
+    }
+}
+
+/// Creates the pacing wrappers of one request; every wrapper adds its sleeps
+/// to the same `throttled` total.
+#[derive(Clone)]
+pub(crate) struct Throttle {
+    rate: Rate,
+    clock: Arc<dyn Clock>,
+    throttled: Arc<AtomicU64>,
+}
+
+impl Throttle {
+    pub(crate) fn new(rate: Rate, clock: Arc<dyn Clock>, throttled: Arc<AtomicU64>) -> Self {
+        // Zero in a hand-built `Rate` would never grant a byte.
+        let rate = Rate::per_sec(rate.max_bytes_per_sec).with_burst(rate.burst);
+        Throttle { rate, clock, throttled }
+    }
+
+    pub(crate) fn wrap<S>(&self, inner: S) -> Throttled<S> {
+        Throttled {
+            inner,
+            bucket: TokenBucket {
+                rate: self.rate,
+                tokens: self.rate.burst as f64,
+                refilled_at: self.clock.now_instant(),
+            },
+            clock: self.clock.clone(),
+            throttled: self.throttled.clone(),
+            sleep: Mutex::new(None),
+        }
+    }
+}
+
+/// Nanoseconds in `throttled` as a duration.
+pub(crate) fn throttled_time(throttled: &AtomicU64) -> Duration {
+    Duration::from_nanos(throttled.load(Ordering::Relaxed))
+}
+
+struct TokenBucket {
+    rate: Rate,
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+impl TokenBucket {
+    /// Grant up to `want` bytes now, or say how long to wait until
+    /// `min(want, burst)` bytes can be granted.
+    fn take(&mut self, want: usize, now: Instant) -> Result<usize, Duration> {
+        let rate = self.rate.max_bytes_per_sec as f64;
+        let elapsed = now.saturating_duration_since(self.refilled_at).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * rate).min(self.rate.burst as f64);
+        self.refilled_at = now;
+        if self.tokens >= 1.0 {
+            let granted = (want as f64).min(self.tokens.floor()) as usize;
+            self.tokens -= granted as f64;
+            return Ok(granted);
+        }
+        let needed = (want as u64).min(self.rate.burst) as f64 - self.tokens;
+        Err(Duration::from_secs_f64(needed / rate))
+    }
+
+    fn refund(&mut self, unused: usize) {
+        self.tokens = (self.tokens + unused as f64).min(self.rate.burst as f64);
+    }
+}
+
+/// Paces reads and writes of `inner` through a token bucket.
+pub(crate) struct Throttled<S> {
+    inner: S,
+    bucket: TokenBucket,
+    clock: Arc<dyn Clock>,
+    throttled: Arc<AtomicU64>,
+    /// Pending sleep; in a mutex only so the wrapper stays `Sync`.
+    sleep: Mutex<Option<BoxFuture<'static, ()>>>,
+}
+
+impl<S> Throttled<S> {
+    pub(crate) fn into_inner(self) -> S {
+        self.inner
+    }
+
+    /// Bytes that may be moved now, out of `want`; `Pending` while asleep.
+    fn poll_budget(&mut self, cx: &mut Context<'_>, want: usize) -> Poll<usize> {
+        if want == 0 {
+            return Poll::Ready(0);
+        }
+        let sleep = self.sleep.get_mut().unwrap_or_else(|e| e.into_inner());
+        loop {
+            if let Some(pending) = sleep {
+                match pending.as_mut().poll(cx) {
+                    Poll::Ready(()) => *sleep = None,
+                    Poll::Pending => return Poll::Pending,
+                }
+            }
+            match self.bucket.take(want, self.clock.now_instant()) {
+                Ok(granted) => return Poll::Ready(granted),
+                Err(wait) => {
+                    self.throttled.fetch_add(wait.as_nanos() as u64, Ordering::Relaxed);
+                    *sleep = Some(self.clock.sleep(wait));
+                }
+            }
+        }
+    }
+}
+
+impl<S: io::Read + Unpin> io::Read for Throttled<S> {
+    fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<io::Result<usize>> {
+        let this = &mut *self;
+        let granted = std::task::ready!(this.poll_budget(cx, buf.len()));
+        let result = Pin::new(&mut this.inner).poll_read(cx, &mut buf[..granted]);
+        let used = match &result {
+            Poll::Ready(Ok(n)) => *n,
+            _ => 0,
+        };
+        this.bucket.refund(granted - used);
+        result
+    }
+}
+
+impl<S: io::Write + Unpin> io::Write for Throttled<S> {
+    fn poll_write(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        let this = &mut *self;
+        let granted = std::task::ready!(this.poll_budget(cx, buf.len()));
+        let result = Pin::new(&mut this.inner).poll_write(cx, &buf[..granted]);
+        let used = match &result {
+            Poll::Ready(Ok(n)) => *n,
+            _ => 0,
+        };
+        this.bucket.refund(granted - used);
+        result
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_close(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MockClock;
+    use async_std::io::{ReadExt, WriteExt};
+    use std::future::Future;
+
+    /// Run `fut` on `clock`, also returning the virtual time it took.
+    async fn drive<T>(clock: &MockClock, fut: impl Future<Output = T>) -> (T, Duration) {
+        let start = clock.now_instant();
+        let out = clock.run(fut).await;
+        (out, clock.now_instant() - start)
+    }
+
+    /// Records the size of every write.
+    #[derive(Default)]
+    struct WriteSizes(Vec<usize>);
+
+    impl io::Write for WriteSizes {
+        fn poll_write(mut self: Pin<&mut Self>, _cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+            self.0.push(buf.len());
+            Poll::Ready(Ok(buf.len()))
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    fn throttle(clock: &MockClock, rate: Rate) -> Throttle {
+        Throttle::new(rate, Arc::new(clock.clone()), Arc::default())
+    }
+
+    #[async_std::test]
+    async fn test_one_megabyte_at_100_kb_per_sec_takes_ten_seconds() {
+        let clock = MockClock::new();
+        let throttle = throttle(&clock, Rate::per_sec(100_000));
+        let throttled = throttle.throttled.clone();
+        let mut paced = throttle.wrap(WriteSizes::default());
+        let (sizes, took) = drive(&clock, async move {
+            paced.write_all(&vec![0u8; 1_000_000]).await.unwrap();
+            paced.into_inner().0
+        })
+        .await;
+
+        // The first 10 KB burst goes out at once, the rest at the rate.
+        assert_eq!(took, Duration::from_millis(9_900));
+        assert_eq!(throttled_time(&throttled), took);
+        assert_eq!(sizes.len(), 100);
+        assert!(sizes.iter().all(|&n| n == 10_000), "{sizes:?}");
+    }
+
+    #[async_std::test]
+    async fn test_burst_caps_each_read() {
+        let clock = MockClock::new();
+        let rate = Rate::per_sec(1_000).with_burst(300);
+        let mut paced = throttle(&clock, rate).wrap(async_std::io::Cursor::new(vec![7u8; 1_000]));
+        let (reads, took) = drive(&clock, async move {
+            let mut reads = Vec::new();
+            let mut buf = [0u8; 4096];
+            loop {
+                match paced.read(&mut buf).await.unwrap() {
+                    0 => return reads,
+                    n => reads.push(n),
+                }
+            }
+        })
+        .await;
+        assert_eq!(reads, [300, 300, 300, 100]);
+        // A reader can't tell the body is nearly over, so the last read also
+        // waits for a whole burst; the unused budget is refunded.
+        assert_eq!(took, Duration::from_millis(900));
+    }
+
+    #[test]
+    fn test_idle_time_refills_up_to_the_burst() {
+        let now = Instant::now();
+        let mut bucket = TokenBucket { rate: Rate::per_sec(1_000), tokens: 0.0, refilled_at: now };
+        assert_eq!(bucket.take(50, now), Err(Duration::from_millis(50)));
+        assert_eq!(bucket.take(500, now + Duration::from_secs(60)), Ok(100));
+        bucket.refund(40);
+        assert_eq!(bucket.take(500, now + Duration::from_secs(60)), Ok(40));
+    }
+}
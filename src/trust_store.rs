@@ -0,0 +1,116 @@
+//! The client's TLS configuration, built lazily from
+//! `ZJHttpClient::global_trust_store_pem` and swappable at runtime.
+//!
+//! A reload builds the new `rustls::ClientConfig` first and only swaps it in
+//! when that succeeds, so a bad bundle never replaces a working one. The
+//! caller is responsible for evicting pooled connections verified under the
+//! old roots (`ConnectionPoolInner::retire_tls_connections`).
+
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::SystemTime;
+
+use tracing::{debug, warn};
+
+use crate::{client::build_tls_config, error::Result, log_target::TLS, misc::TrustStorePem};
+
+#[derive(Default)]
+pub(crate) struct TrustStoreCache {
+    state: RwLock<State>,
+}
+
+#[derive(Default)]
+struct State {
+    config: Option<Arc<rustls::ClientConfig>>,
+    /// Set once the client's roots were replaced at runtime.
+    reloaded: bool,
+    watched: Option<WatchedFile>,
+}
+
+struct WatchedFile {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+}
+
+/// What [`TrustStoreCache::current`] did on its way to the config.
+pub(crate) struct Current {
+    pub config: Arc<rustls::ClientConfig>,
+    /// The watched file changed and the new roots are now active.
+    pub reloaded: bool,
+}
+
+impl TrustStoreCache {
+    /// The active config, building it from `initial` on first use. When a
+    /// file is watched and its mtime changed since it was last read, the
+    /// file is re-read first; if that fails the error is logged and the
+    /// previous config stays active. `require_ocsp_staple` is the client's
+    /// setting of the same name, applied to every config built here.
+    pub(crate) fn current(&self, initial: &Option<TrustStorePem>, require_ocsp_staple: bool) -> Result<Current> {
+        let reloaded = self.refresh_watched(require_ocsp_staple);
+        if let Some(config) = &self.state.read().unwrap().config {
+            return Ok(Current { config: config.clone(), reloaded });
+        }
+        let config = Arc::new(build_tls_config(initial, require_ocsp_staple)?);
+        let mut state = self.state.write().unwrap();
+        let config = state.config.get_or_insert(config).clone();
+        Ok(Current { config, reloaded })
+    }
+
+    /// Build a config from `pem` and make it the active one.
+    pub(crate) fn replace(&self, pem: TrustStorePem, require_ocsp_staple: bool) -> Result<()> {
+        let config = Arc::new(build_tls_config(&Some(pem), require_ocsp_staple)?);
+        let mut state = self.state.write().unwrap();
+        state.config = Some(config);
+        state.reloaded = true;
+        state.watched = None;
+        Ok(())
+    }
+
+    /// Load `path` now and re-read it whenever its mtime changes.
+    pub(crate) fn watch(&self, path: &Path, require_ocsp_staple: bool) -> Result<()> {
+        let modified = modified(path);
+        self.replace(TrustStorePem::Path(path.to_path_buf()), require_ocsp_staple)?;
+        self.state.write().unwrap().watched = Some(WatchedFile { path: path.to_path_buf(), modified });
+        Ok(())
+    }
+
+    pub(crate) fn is_reloaded(&self) -> bool {
+        self.state.read().unwrap().reloaded
+    }
+
+    /// Returns whether new roots were swapped in.
+    fn refresh_watched(&self, require_ocsp_staple: bool) -> bool {
+        let (path, seen) = match &self.state.read().unwrap().watched {
+            Some(watched) => (watched.path.clone(), watched.modified),
+            None => return false,
+        };
+        let modified = modified(&path);
+        if modified == seen {
+            return false;
+        }
+        let result = build_tls_config(&Some(TrustStorePem::Path(path.clone())), require_ocsp_staple);
+        let mut state = self.state.write().unwrap();
+        match &mut state.watched {
+            // Remember the mtime even when the file is bad, so a broken bundle
+            // is reported once rather than on every connect.
+            Some(watched) if watched.path == path && watched.modified == seen => watched.modified = modified,
+            // Another caller got here first.
+            _ => return false,
+        }
+        match result {
+            Ok(config) => {
+                debug!(target: TLS, ?path, "trust store file changed, reloaded");
+                state.config = Some(Arc::new(config));
+                true
+            }
+            Err(err) => {
+                warn!(target: TLS, ?path, %err, "failed to reload trust store file, keeping the previous roots");
+                false
+            }
+        }
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
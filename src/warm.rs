@@ -0,0 +1,177 @@
+//! Keeping pooled connections to a host warm, so latency-critical requests
+//! never pay for a fresh connect or TLS handshake.
+//!
+//! Started with [`ZJHttpClient::maintain_warm_connections`]. A background task
+//! keeps `target_count` idle connections to the host in the client's pool and
+//! replaces each one before the server's keep-alive window would close it.
+//! While the host's pool key is in close mode (see
+//! [`ClientInner::close_mode_cooldown`](crate::client::ClientInner::close_mode_cooldown))
+//! the task opens nothing.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::Duration;
+
+use tracing::{debug, warn};
+
+use crate::{
+    client::{StreamInfo, ZJHttpClient, build_connection_key, connect_fresh_stream, request_origin, resolve_ip},
+    config::EffectiveConfig,
+    error::{InvalidRequestSnafu, Result},
+    log_target::POOL,
+    methods,
+    requestx::Request,
+};
+
+/// How many connections to keep warm and when to replace them.
+#[derive(Debug, Clone)]
+pub struct WarmPolicy {
+    /// Idle connections to keep in the pool for the host.
+    pub target_count: usize,
+    /// Replace a connection once it has been idle this long. Set it below the
+    /// server's keep-alive timeout, leaving room for `check_interval`.
+    pub refresh_after: Duration,
+    /// How often the pool is checked.
+    pub check_interval: Duration,
+    /// Upper bound of the delay between checks while connecting keeps failing;
+    /// the delay doubles from `check_interval` on every failed check.
+    pub max_backoff: Duration,
+}
+
+impl WarmPolicy {
+    /// Keep `target_count` connections, replaced after 4 s of idleness (below
+    /// the common 5 s server default), checked every 500 ms.
+    pub fn new(target_count: usize) -> Self {
+        WarmPolicy {
+            target_count,
+            refresh_after: Duration::from_secs(4),
+            check_interval: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(30),
+        }
+    }
+
+    pub fn set_refresh_after(mut self, refresh_after: Duration) -> Self {
+        self.refresh_after = refresh_after;
+        self
+    }
+
+    pub fn set_check_interval(mut self, check_interval: Duration) -> Self {
+        self.check_interval = check_interval;
+        self
+    }
+
+    pub fn set_max_backoff(mut self, max_backoff: Duration) -> Self {
+        self.max_backoff = max_backoff;
+        self
+    }
+}
+
+#[derive(Debug, Default)]
+struct WarmStats {
+    connects: AtomicU64,
+    failures: AtomicU64,
+}
+
+/// Handle of a running warm-connection task. Dropping it (or calling
+/// [`stop`](Self::stop)) ends the task; connections already pooled stay.
+#[derive(Debug)]
+pub struct WarmConnections {
+    stop: Arc<AtomicBool>,
+    stats: Arc<WarmStats>,
+}
+
+impl WarmConnections {
+    /// Connections the task opened. Connections opened by requests are not
+    /// counted here.
+    pub fn maintenance_connects(&self) -> u64 {
+        self.stats.connects.load(Ordering::Relaxed)
+    }
+
+    /// Checks that failed to resolve the host or to connect.
+    pub fn maintenance_failures(&self) -> u64 {
+        self.stats.failures.load(Ordering::Relaxed)
+    }
+
+    pub fn stop(self) {}
+}
+
+impl Drop for WarmConnections {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+pub(crate) fn spawn(client: &ZJHttpClient, url: &str, policy: WarmPolicy) -> Result<WarmConnections> {
+    let req = Request::new(methods::GET, url)?;
+    if !matches!(req.url.scheme(), "http" | "https") {
+        return Err(InvalidRequestSnafu { message: format!("cannot keep {url} warm: unsupported scheme") }.build());
+    }
+    let cfg = client.effective_config(&req);
+    if cfg.proxy.is_some() {
+        return Err(InvalidRequestSnafu { message: "warm connections through a proxy are not supported".to_string() }
+            .build());
+    }
+    let stop = Arc::new(AtomicBool::new(false));
+    let stats = Arc::new(WarmStats::default());
+    let task = Task { client: client.clone(), req, cfg, policy, stop: stop.clone(), stats: stats.clone() };
+    async_std::task::spawn(task.run());
+    Ok(WarmConnections { stop, stats })
+}
+
+struct Task {
+    client: ZJHttpClient,
+    req: Request,
+    cfg: EffectiveConfig,
+    policy: WarmPolicy,
+    stop: Arc<AtomicBool>,
+    stats: Arc<WarmStats>,
+}
+
+impl Task {
+    async fn run(self) {
+        let mut delay = Duration::ZERO;
+        loop {
+            self.client.clock.sleep(delay).await;
+            if self.stop.load(Ordering::Relaxed) {
+                return;
+            }
+            delay = match self.top_up().await {
+                Ok(()) => self.policy.check_interval,
+                Err(err) => {
+                    self.stats.failures.fetch_add(1, Ordering::Relaxed);
+                    let delay = (delay * 2).clamp(self.policy.check_interval, self.policy.max_backoff);
+                    warn!(target: POOL, url = %self.req.url, %err, ?delay, "keeping connections warm failed");
+                    delay
+                }
+            };
+        }
+    }
+
+    /// Replace stale connections and open new ones until the target is met
+    /// or the pool limits are reached.
+    async fn top_up(&self) -> Result<()> {
+        let addr = resolve_ip(&self.client, &self.cfg, &self.req).await?;
+        let info = StreamInfo {
+            addr,
+            is_tls: self.req.url.scheme() == "https",
+            proxy_used: None,
+            origin: request_origin(&self.req.url),
+            keep_alive_timeout: None,
+        };
+        let key = build_connection_key(&info);
+        let pool = &self.client.connection_pool;
+        if pool.in_close_mode(&key) {
+            debug!(target: POOL, ?addr, "server is closing connections, not keeping them warm");
+            return Ok(());
+        }
+        let mut live = pool.retain_fresh(&key, self.policy.refresh_after);
+        while live < self.policy.target_count && pool.has_room(&key) {
+            let stream = connect_fresh_stream(&self.client, &self.cfg, &self.req, &addr).await?;
+            self.stats.connects.fetch_add(1, Ordering::Relaxed);
+            debug!(target: POOL, ?addr, initiator = "maintenance", "opened warm connection");
+            pool.return_stream(stream, info.clone());
+            live += 1;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,83 @@
+mod common;
+
+use futures::StreamExt;
+use zjhttpc::client::ZJHttpClient;
+use zjhttpc::methods;
+use zjhttpc::requestx::Request;
+use zjhttpc::response::BodyBufferBounds;
+
+use common::MockServer;
+
+/// Keep-alive server answering `GET /<n>` with an `n`-byte chunked body.
+async fn spawn_server() -> u16 {
+    MockServer::new(|req| {
+        let len: usize = req.path().trim_start_matches('/').parse().unwrap();
+        let mut wire = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n".to_vec();
+        for chunk in vec![b'x'; len].chunks(16 * 1024) {
+            wire.extend_from_slice(format!("{:x}\r\n", chunk.len()).as_bytes());
+            wire.extend_from_slice(chunk);
+            wire.extend_from_slice(b"\r\n");
+        }
+        wire.extend_from_slice(b"0\r\n\r\n");
+        wire
+    })
+    .spawn()
+    .await
+    .port
+}
+
+async fn send(client: &ZJHttpClient, port: u16, len: usize) -> zjhttpc::response::Response {
+    let mut req = Request::new(methods::GET, format!("http://127.0.0.1:{port}/{len}")).unwrap();
+    Box::pin(client.send(&mut req)).await.unwrap()
+}
+
+#[async_std::test]
+async fn body_sizes_are_learned_per_origin() {
+    let port = spawn_server().await;
+    let origin = format!("127.0.0.1:{port}");
+    let client = ZJHttpClient::builder().build().unwrap();
+    assert_eq!(client.learned_body_size(&origin), None);
+
+    assert_eq!(send(&client, port, 2_000).await.body_bytes().await.unwrap().len(), 2_000);
+    assert_eq!(client.learned_body_size(&origin), Some(2_000));
+
+    // Each body moves the average a quarter of the way.
+    let mut resp = send(&client, port, 100_000).await;
+    let mut chunks = Box::pin(resp.bytes_stream());
+    let mut received = 0;
+    while let Some(chunk) = chunks.next().await {
+        received += chunk.unwrap().len();
+    }
+    drop(chunks);
+    assert_eq!(received, 100_000);
+    assert_eq!(client.learned_body_size(&origin), Some(2_000 - 500 + 25_000));
+
+    // A body that is not read to the end teaches nothing.
+    let mut resp = send(&client, port, 1_000_000).await;
+    resp.peek_body(10).await.unwrap();
+    drop(resp);
+    assert_eq!(client.learned_body_size(&origin), Some(26_500));
+    assert_eq!(client.learned_body_size("127.0.0.1:1"), None);
+}
+
+#[async_std::test]
+async fn stream_chunks_follow_the_learned_size_within_bounds() {
+    let port = spawn_server().await;
+    let client = ZJHttpClient::builder()
+        .set_body_buffer_bounds(BodyBufferBounds { min: 1024, max: 64 * 1024 })
+        .build()
+        .unwrap();
+    let largest_chunk = |mut resp: zjhttpc::response::Response| async move {
+        let mut chunks = Box::pin(resp.bytes_stream());
+        let mut largest = 0;
+        while let Some(chunk) = chunks.next().await {
+            largest = largest.max(chunk.unwrap().len());
+        }
+        largest
+    };
+    // Nothing learned yet: reads of at most `min`.
+    assert_eq!(largest_chunk(send(&client, port, 500_000).await).await, 1024);
+    // Learned 500 KB, clamped to `max`; the server writes 16 KiB chunks,
+    // so one read can return more than `min` now.
+    assert!(largest_chunk(send(&client, port, 500_000).await).await > 1024);
+}
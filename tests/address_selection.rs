@@ -0,0 +1,92 @@
+mod common;
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use futures::future::BoxFuture;
+use zjhttpc::client::ZJHttpClient;
+use zjhttpc::methods;
+use zjhttpc::requestx::Request;
+use zjhttpc::resolver::{AddressSelection, Resolver};
+
+use common::{Mock, MockServer, OK};
+
+/// Resolves every host to the same fixed addresses, in order.
+struct StaticResolver(Vec<SocketAddr>);
+
+impl Resolver for StaticResolver {
+    fn resolve<'a>(&'a self, _host: &'a str, _port: u16) -> BoxFuture<'a, zjhttpc::Result<Vec<SocketAddr>>> {
+        Box::pin(async { Ok(self.0.clone()) })
+    }
+}
+
+async fn three_listeners(selection: AddressSelection) -> (ZJHttpClient, Vec<SocketAddr>, Vec<Mock>) {
+    let mut addrs = Vec::new();
+    let mut servers = Vec::new();
+    for _ in 0..3 {
+        let server = MockServer::always(OK).spawn().await;
+        addrs.push(SocketAddr::from(([127, 0, 0, 1], server.port)));
+        servers.push(server);
+    }
+    let client = ZJHttpClient::builder()
+        .set_resolver(Arc::new(StaticResolver(addrs.clone())) as Arc<dyn Resolver>)
+        .set_address_selection(selection)
+        .build()
+        .unwrap();
+    (client, addrs, servers)
+}
+
+fn accepted(servers: &[Mock]) -> Vec<usize> {
+    servers.iter().map(Mock::accepts).collect()
+}
+
+async fn get(client: &ZJHttpClient) -> zjhttpc::response::Response {
+    let mut req = Request::new(methods::GET, "http://service.internal/").unwrap();
+    Box::pin(client.send(&mut req)).await.unwrap()
+}
+
+#[async_std::test]
+async fn round_robin_spreads_new_connections_over_all_addresses() {
+    let (client, addrs, counts) = three_listeners(AddressSelection::RoundRobin).await;
+
+    // Unread responses keep their connections busy, so every send dials.
+    let mut responses = Vec::new();
+    for _ in 0..6 {
+        responses.push(get(&client).await);
+    }
+    assert_eq!(accepted(&counts), [2, 2, 2]);
+    let used: Vec<_> = responses.iter().map(|r| r.addr).collect();
+    assert_eq!(used, [addrs[0], addrs[1], addrs[2], addrs[0], addrs[1], addrs[2]]);
+}
+
+#[async_std::test]
+async fn first_always_dials_the_first_address() {
+    let (client, addrs, counts) = three_listeners(AddressSelection::First).await;
+    let mut responses = Vec::new();
+    for _ in 0..3 {
+        responses.push(get(&client).await);
+    }
+    assert_eq!(accepted(&counts), [3, 0, 0]);
+    assert!(responses.iter().all(|r| r.addr == addrs[0]));
+}
+
+#[async_std::test]
+async fn pooled_connection_to_any_address_is_reused_before_selecting() {
+    let (client, addrs, counts) = three_listeners(AddressSelection::RoundRobin).await;
+
+    let mut first = get(&client).await;
+    assert_eq!(first.body_string().await.unwrap(), "ok");
+    drop(first);
+    // Round-robin would dial the second address next, but the idle
+    // connection to the first one is reused.
+    let mut again = get(&client).await;
+    assert!(again.reused_connection);
+    assert_eq!(again.addr, addrs[0]);
+    assert_eq!(again.body_string().await.unwrap(), "ok");
+    assert_eq!(accepted(&counts), [1, 0, 0]);
+
+    let busy = get(&client).await;
+    let next = get(&client).await;
+    assert_eq!((busy.reused_connection, next.reused_connection), (true, false));
+    assert_eq!(next.addr, addrs[1]);
+}
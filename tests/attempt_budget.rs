@@ -0,0 +1,104 @@
+mod common;
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use async_std::task;
+use zjhttpc::client::ZJHttpClient;
+use zjhttpc::methods;
+use zjhttpc::requestx::Request;
+use zjhttpc::summary::RequestSummary;
+
+use common::{MockServer, OK, Reply};
+
+const REDIRECT: &str = "HTTP/1.1 302 Found\r\nLocation: /again\r\nContent-Length: 0\r\n\r\n";
+const HOP: Duration = Duration::from_millis(100);
+const BUDGET: Duration = Duration::from_millis(250);
+
+/// Redirects every request back to itself, each answer `HOP` late.
+async fn spawn_redirect_loop() -> u16 {
+    MockServer::always(Reply::default().then_sleep(HOP).then(REDIRECT)).spawn().await.port
+}
+
+/// A client following up to 50 redirects whose hook records every summary.
+fn recording_client(budget: Option<Duration>) -> (ZJHttpClient, Arc<Mutex<Vec<RequestSummary>>>) {
+    let summaries = Arc::new(Mutex::new(Vec::new()));
+    let log = summaries.clone();
+    let mut builder = ZJHttpClient::builder();
+    builder.set_max_redirects(50).on_complete(move |summary| log.lock().unwrap().push(summary.clone()));
+    if let Some(budget) = budget {
+        builder.set_global_max_total_attempt_duration(budget);
+    }
+    (builder.build().unwrap(), summaries)
+}
+
+#[async_std::test]
+async fn redirect_loop_stops_with_the_last_redirect() {
+    let port = spawn_redirect_loop().await;
+    let (client, summaries) = recording_client(Some(BUDGET));
+    let mut req = Request::new(methods::GET, format!("http://127.0.0.1:{port}/")).unwrap();
+    let started = Instant::now();
+    let resp = Box::pin(client.send(&mut req)).await.unwrap();
+
+    assert_eq!(resp.status_code, 302);
+    assert!(started.elapsed() < BUDGET + 2 * HOP, "{:?}", started.elapsed());
+    let summaries = summaries.lock().unwrap();
+    let summary = &summaries[0];
+    assert!(summary.attempt_budget_exhausted);
+    assert!((1..5).contains(&summary.redirects), "{summary:?}");
+    assert_eq!(summary.attempts as usize, summary.redirects + 1);
+}
+
+#[async_std::test]
+async fn request_budget_overrides_the_client() {
+    let port = spawn_redirect_loop().await;
+    let (client, summaries) = recording_client(None);
+    let mut req = Request::new(methods::GET, format!("http://127.0.0.1:{port}/"))
+        .unwrap()
+        .set_max_total_attempt_duration(BUDGET);
+    assert_eq!(client.effective_config(&req).max_total_attempt_duration, Some(BUDGET));
+    let resp = Box::pin(client.send(&mut req)).await.unwrap();
+
+    assert_eq!(resp.status_code, 302);
+    assert!(summaries.lock().unwrap()[0].attempt_budget_exhausted);
+}
+
+#[async_std::test]
+async fn redirects_within_the_budget_are_followed() {
+    let port = MockServer::new(|req| match req.path() {
+        "/" => Reply::new(REDIRECT),
+        _ => Reply::new(OK),
+    })
+    .spawn()
+    .await
+    .port;
+    let (client, summaries) = recording_client(Some(Duration::from_secs(10)));
+    let mut req = Request::new(methods::GET, format!("http://127.0.0.1:{port}/")).unwrap();
+    let resp = Box::pin(client.send(&mut req)).await.unwrap();
+
+    assert_eq!(resp.status_code, 200);
+    let summaries = summaries.lock().unwrap();
+    assert_eq!((summaries[0].redirects, summaries[0].attempt_budget_exhausted), (1, false));
+}
+
+#[async_std::test]
+async fn exhausted_budget_skips_the_stale_connection_retry() {
+    // The first answer closes the connection without saying so, leaving a
+    // stale one in the pool.
+    let port = MockServer::script([Reply::new(OK).then_close()]).spawn().await.port;
+    let (client, summaries) = recording_client(None);
+    let mut req = Request::new(methods::GET, format!("http://127.0.0.1:{port}/")).unwrap();
+    let mut resp = Box::pin(client.send(&mut req)).await.unwrap();
+    assert_eq!(resp.body_string().await.unwrap(), "ok");
+    drop(resp);
+    task::sleep(Duration::from_millis(50)).await;
+
+    let mut req = Request::new(methods::GET, format!("http://127.0.0.1:{port}/"))
+        .unwrap()
+        .set_max_total_attempt_duration(Duration::ZERO);
+    let err = Box::pin(client.send(&mut req)).await.err().expect("the stale connection is not replaced");
+    assert!(err.is_transient(), "{err}");
+
+    let summaries = summaries.lock().unwrap();
+    assert_eq!((summaries[1].attempts, summaries[1].attempt_budget_exhausted), (1, true));
+}
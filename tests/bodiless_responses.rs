@@ -0,0 +1,68 @@
+mod common;
+
+use zjhttpc::client::ZJHttpClient;
+use zjhttpc::methods;
+use zjhttpc::requestx::Request;
+use zjhttpc::response::BodyFraming;
+
+use common::MockServer;
+
+/// Keep-alive server answering its requests with `replies`, in order.
+async fn spawn_server(replies: &'static [&'static str]) -> u16 {
+    MockServer::script(replies.iter().copied()).spawn().await.port
+}
+
+async fn send(client: &ZJHttpClient, method: &'static str, port: u16) -> zjhttpc::response::Response {
+    let mut req = Request::new(method, format!("http://127.0.0.1:{port}/")).unwrap();
+    Box::pin(client.send(&mut req)).await.unwrap()
+}
+
+#[async_std::test]
+async fn head_and_204_ignore_their_content_length_and_keep_the_connection() {
+    let port = spawn_server(&[
+        "HTTP/1.1 200 OK\r\nContent-Length: 1000\r\n\r\n",
+        "HTTP/1.1 204 No Content\r\nContent-Length: 7\r\n\r\n",
+        "HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok",
+    ])
+    .await;
+    let client = ZJHttpClient::builder().build().unwrap();
+
+    let mut resp = send(&client, methods::HEAD, port).await;
+    assert_eq!(resp.effective_encoding(), BodyFraming::ContentLength(0));
+    assert_eq!(resp.body_string().await.unwrap(), "");
+    drop(resp);
+    let mut resp = send(&client, methods::DELETE, port).await;
+    assert_eq!(resp.status_code, 204);
+    assert!(resp.reused_connection);
+    assert_eq!(resp.body_string().await.unwrap(), "");
+    drop(resp);
+    let mut resp = send(&client, methods::GET, port).await;
+    assert!(resp.reused_connection);
+    assert_eq!(resp.body_string().await.unwrap(), "ok");
+}
+
+#[async_std::test]
+async fn options_lists_the_allowed_methods() {
+    let port = spawn_server(&[
+        "HTTP/1.1 204 No Content\r\nAllow: GET, HEAD\r\nAllow: OPTIONS, PROPFIND\r\n\r\n",
+        "HTTP/1.1 200 OK\r\nAllow: \r\nContent-Length: 0\r\n\r\n",
+    ])
+    .await;
+    let client = ZJHttpClient::builder().build().unwrap();
+
+    let resp = send(&client, methods::OPTIONS, port).await;
+    let allowed = resp.allow_methods().unwrap();
+    assert_eq!(allowed.iter().collect::<Vec<_>>(), [methods::GET, methods::HEAD, methods::OPTIONS, "PROPFIND"]);
+    assert!(!allowed.contains(methods::POST));
+    drop(resp);
+    let resp = send(&client, methods::OPTIONS, port).await;
+    assert!(resp.reused_connection);
+    assert!(resp.allow_methods().unwrap().is_empty());
+}
+
+#[async_std::test]
+async fn response_without_allow_has_no_method_set() {
+    let port = spawn_server(&["HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n"]).await;
+    let client = ZJHttpClient::builder().build().unwrap();
+    assert!(send(&client, methods::GET, port).await.allow_methods().is_none());
+}
@@ -0,0 +1,63 @@
+mod common;
+
+use zjhttpc::ZjhttpcError;
+use zjhttpc::client::ZJHttpClient;
+use zjhttpc::methods;
+use zjhttpc::requestx::Request;
+
+use common::{MockServer, Reply};
+
+/// Answers requests with `head` followed by `body`, keeping the connection
+/// open so only the framing ends the body.
+async fn spawn_server(head: String, body: Vec<u8>) -> u16 {
+    MockServer::always(Reply::new(head).then(body)).spawn().await.port
+}
+
+/// Every byte value, including invalid UTF-8, over several reads' worth.
+fn binary(len: usize) -> Vec<u8> {
+    (0..len).map(|i| (i * 7 % 256) as u8).collect()
+}
+
+async fn download(port: u16) -> zjhttpc::response::Response {
+    let client = ZJHttpClient::builder().build().unwrap();
+    let mut req = Request::new(methods::GET, format!("http://127.0.0.1:{port}/archive.tar")).unwrap();
+    Box::pin(client.send(&mut req)).await.unwrap()
+}
+
+#[async_std::test]
+async fn content_length_body_is_returned_byte_exact() {
+    let body = binary(300_000);
+    let head = format!("HTTP/1.1 200 OK\r\nContent-Type: application/x-tar\r\nContent-Length: {}\r\n\r\n", body.len());
+    let mut resp = download(spawn_server(head, body.clone()).await).await;
+
+    assert_eq!(resp.body_bytes().await.unwrap(), body);
+    assert!(resp.is_body_read_complete());
+    let err = resp.body_bytes().await.expect_err("the body is gone");
+    assert!(matches!(err, ZjhttpcError::BodyAlreadyRead { .. }), "{err:?}");
+}
+
+#[async_std::test]
+async fn chunked_body_is_returned_byte_exact() {
+    let body = binary(20_000);
+    let mut wire = Vec::new();
+    for chunk in body.chunks(6_000) {
+        wire.extend_from_slice(format!("{:x}\r\n", chunk.len()).as_bytes());
+        wire.extend_from_slice(chunk);
+        wire.extend_from_slice(b"\r\n");
+    }
+    wire.extend_from_slice(b"0\r\n\r\n");
+    let head = "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n".to_string();
+    let mut resp = download(spawn_server(head, wire).await).await;
+
+    assert_eq!(resp.body_bytes().await.unwrap(), body);
+}
+
+#[async_std::test]
+async fn short_body_is_a_truncation_not_a_short_vec() {
+    let head = "HTTP/1.1 200 OK\r\nContent-Length: 1000000000\r\nConnection: close\r\n\r\n".to_string();
+    let port = MockServer::always(Reply::new(head).then(binary(10)).then_close()).spawn().await.port;
+    let mut resp = download(port).await;
+
+    let err = resp.body_bytes().await.expect_err("the server hung up early");
+    assert!(matches!(err, ZjhttpcError::BodyTruncated { expected: 1_000_000_000, received: 10, .. }), "{err:?}");
+}
@@ -0,0 +1,70 @@
+mod common;
+
+use serde::Deserialize;
+use zjhttpc::ZjhttpcError;
+use zjhttpc::client::ZJHttpClient;
+use zjhttpc::methods;
+use zjhttpc::requestx::Request;
+
+use common::MockServer;
+
+/// Keep-alive server answering every request with `response`.
+async fn spawn_server(response: &'static str) -> u16 {
+    MockServer::always(response).spawn().await.port
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+struct User {
+    id: u32,
+    name: String,
+}
+
+#[async_std::test]
+async fn chunked_body_deserializes_into_a_type() {
+    let port = spawn_server(
+        "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nTransfer-Encoding: chunked\r\n\r\n\
+         b\r\n{\"id\":7,\"na\r\n\
+         c\r\nme\":\"alice\"}\r\n0\r\n\r\n",
+    )
+    .await;
+    let client = ZJHttpClient::builder().build().unwrap();
+    let url = format!("http://127.0.0.1:{port}/user");
+
+    let mut resp = client.send(&mut Request::new(methods::GET, &url).unwrap()).await.unwrap();
+    let user: User = resp.body_json_as().await.unwrap();
+    assert_eq!(user, User { id: 7, name: "alice".into() });
+    assert!(resp.is_body_read_complete());
+    drop(resp);
+    assert_eq!(client.idle_connections(), 1);
+
+    let mut resp = client.send(&mut Request::new(methods::GET, &url).unwrap()).await.unwrap();
+    assert!(resp.reused_connection);
+    assert_eq!(resp.body_json().await.unwrap()["name"], "alice");
+}
+
+#[async_std::test]
+async fn parse_failure_shows_the_body() {
+    let port = spawn_server(
+        "HTTP/1.1 500 Internal Server Error\r\nContent-Length: 46\r\n\r\n\
+         <html><body>database is down</body></html>\r\n\r\n",
+    )
+    .await;
+    let client = ZJHttpClient::builder().build().unwrap();
+    let mut req = Request::new(methods::GET, format!("http://127.0.0.1:{port}/")).unwrap();
+    let mut resp = client.send(&mut req).await.unwrap();
+
+    let err = resp.body_json_as::<User>().await.expect_err("HTML is not JSON");
+    assert!(matches!(&err, ZjhttpcError::JsonParsing { preview, .. } if preview.contains("database is down")), "{err:?}");
+    assert!(err.to_string().contains("<html><body>database is down"), "{err}");
+}
+
+#[async_std::test]
+async fn wrong_shape_is_a_parse_error() {
+    let port = spawn_server("HTTP/1.1 200 OK\r\nContent-Length: 14\r\n\r\n{\"id\":\"seven\"}").await;
+    let client = ZJHttpClient::builder().build().unwrap();
+    let mut req = Request::new(methods::GET, format!("http://127.0.0.1:{port}/")).unwrap();
+    let mut resp = client.send(&mut req).await.unwrap();
+
+    let err = resp.body_json_as::<User>().await.expect_err("id is not a number");
+    assert!(matches!(&err, ZjhttpcError::JsonParsing { message, .. } if message.contains("invalid type")), "{err:?}");
+}
@@ -0,0 +1,146 @@
+mod common;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use async_std::io::ReadExt;
+use futures::StreamExt;
+use zjhttpc::ZjhttpcError;
+use zjhttpc::client::ZJHttpClient;
+use zjhttpc::methods;
+use zjhttpc::requestx::Request;
+use zjhttpc::response::Response;
+
+use common::{MockServer, Reply};
+
+const TEXT: &[u8] = b"pipeline pipeline pipeline pipeline pipeline pipeline";
+
+/// `TEXT`, gzipped.
+const GZIP_TEXT: &[u8] = b"\x1f\x8b\x08\x00\x00\x00\x00\x00\x02\x03\x2b\xc8\x2c\x48\xcd\xc9\xcc\x4b\x55\x28\x20\x81\x01\x00\xd1\xb0\xac\x2d\x35\x00\x00\x00";
+
+/// `Content-MD5` of `GZIP_TEXT`, and of `TEXT`.
+const GZIP_TEXT_MD5: &str = "NecKVojATASpTACMyANAYA==";
+const TEXT_MD5: &str = "hxwy710Cpgb3U+VI8nak5g==";
+
+/// 100,000 `z`s, gzipped into 132 bytes.
+fn bomb() -> Vec<u8> {
+    let head = b"\x1f\x8b\x08\x00\x00\x00\x00\x00\x02\x03\xed\xc1\x31\x01\x00\x00\x00\xc2\xa0\xde\x4b\x6f\x0d\x0f\xa0";
+    let tail = b"\x80\x57\x03\x24\x97\xe1\xff\xa0\x86\x01\x00";
+    [&head[..], &[0; 96], &tail[..]].concat()
+}
+
+/// `Content-MD5` of `bomb()`.
+const BOMB_MD5: &str = "3NbOZ+C1vFTsk1nFSI7lXA==";
+
+fn sha256(data: &[u8]) -> [u8; 32] {
+    ring::digest::digest(&ring::digest::SHA256, data).as_ref().try_into().unwrap()
+}
+
+/// Keep-alive server answering every request with a gzip `body` and the
+/// extra header lines `extra`.
+async fn spawn_server(body: Vec<u8>, extra: String) -> u16 {
+    let head = format!("HTTP/1.1 200 OK\r\nContent-Encoding: gzip\r\n{extra}Content-Length: {}\r\n\r\n", body.len());
+    MockServer::always(Reply::new(head).then(body)).spawn().await.port
+}
+
+fn client() -> ZJHttpClient {
+    ZJHttpClient::builder().set_auto_decompress(true).set_verify_content_md5(true).build().unwrap()
+}
+
+fn request(port: u16) -> Request {
+    Request::new(methods::GET, format!("http://127.0.0.1:{port}/")).unwrap()
+}
+
+/// The ways of reading a body, each into a `Vec`.
+#[derive(Debug, Clone, Copy)]
+enum Via {
+    BodyBytes,
+    BodyReader,
+    BytesStream,
+    CopyBodyTo,
+    SaveToFile,
+}
+
+async fn read(resp: &mut Response, via: Via) -> zjhttpc::Result<Vec<u8>> {
+    let mut out = Vec::new();
+    match via {
+        Via::BodyBytes => out = resp.body_bytes().await?,
+        Via::BodyReader => {
+            resp.body_reader()?.read_to_end(&mut out).await?;
+        }
+        Via::BytesStream => {
+            let mut chunks = Box::pin(resp.bytes_stream());
+            while let Some(chunk) = chunks.next().await {
+                out.extend(chunk?);
+            }
+        }
+        Via::CopyBodyTo => {
+            let copied = resp.copy_body_to(&mut out).await?;
+            assert_eq!(copied, out.len() as u64);
+        }
+        Via::SaveToFile => {
+            static FILES: AtomicUsize = AtomicUsize::new(0);
+            let file = FILES.fetch_add(1, Ordering::Relaxed);
+            let path = std::env::temp_dir().join(format!("zjhttpc-pipeline-{}-{file}", std::process::id()));
+            let saved = resp.save_to_file(&path).await?;
+            out = std::fs::read(&path)?;
+            std::fs::remove_file(&path)?;
+            assert_eq!(saved, out.len() as u64);
+        }
+    }
+    Ok(out)
+}
+
+const VIAS: [Via; 5] = [Via::BodyBytes, Via::BodyReader, Via::BytesStream, Via::CopyBodyTo, Via::SaveToFile];
+
+#[async_std::test]
+async fn every_reader_decodes_and_checks_the_same_way() {
+    let port = spawn_server(GZIP_TEXT.to_vec(), format!("Content-MD5: {GZIP_TEXT_MD5}\r\n")).await;
+    let client = client();
+    for via in VIAS {
+        let mut req = request(port).set_expected_sha256(sha256(TEXT)).set_max_body_bytes(TEXT.len() as u64);
+        let mut resp = Box::pin(client.send(&mut req)).await.unwrap();
+        assert_eq!(Box::pin(read(&mut resp, via)).await.unwrap(), TEXT, "{via:?}");
+
+        let mut req = request(port).set_expected_sha256(sha256(GZIP_TEXT));
+        let mut resp = Box::pin(client.send(&mut req)).await.unwrap();
+        let err = Box::pin(read(&mut resp, via)).await.expect_err("the digest is of the decoded body");
+        assert!(matches!(err, ZjhttpcError::ChecksumMismatch { .. }), "{via:?}: {err:?}");
+    }
+}
+
+#[async_std::test]
+async fn a_digest_of_the_decoded_body_is_not_a_content_md5() {
+    let port = spawn_server(GZIP_TEXT.to_vec(), format!("Content-MD5: {TEXT_MD5}\r\n")).await;
+    let mut resp = Box::pin(client().send(&mut request(port))).await.unwrap();
+    let err = resp.body_string().await.expect_err("Content-MD5 covers the gzip bytes");
+    assert!(matches!(&err, ZjhttpcError::ChecksumMismatch { algorithm, .. } if algorithm == "md5"), "{err:?}");
+    assert!(err.to_string().starts_with("[ZJ-BODY-008]"), "{err}");
+}
+
+#[async_std::test]
+async fn the_size_limit_counts_decoded_bytes() {
+    let port = spawn_server(bomb(), format!("Content-MD5: {BOMB_MD5}\r\n")).await;
+    let client = client();
+
+    let mut resp = Box::pin(client.send(&mut request(port).set_max_body_bytes(64 * 1024))).await.unwrap();
+    let err = resp.body_bytes().await.expect_err("decodes past the limit");
+    assert!(matches!(err, ZjhttpcError::BodyTooLarge { length: None, limit: 65536, .. }), "{err:?}");
+    drop(resp);
+
+    let mut resp = Box::pin(client.send(&mut request(port).set_max_body_bytes(64 * 1024))).await.unwrap();
+    let mut reader = resp.body_reader().unwrap();
+    let err = ZjhttpcError::from(reader.read_to_end(&mut Vec::new()).await.unwrap_err());
+    assert!(matches!(err, ZjhttpcError::BodyTooLarge { .. }), "{err:?}");
+}
+
+#[async_std::test]
+async fn a_bad_content_md5_wins_over_what_it_explains() {
+    let mut corrupt = GZIP_TEXT.to_vec();
+    corrupt[12] ^= 0xff;
+    let port = spawn_server(corrupt, "Content-MD5: AAAAAAAAAAAAAAAAAAAAAA==\r\n".to_string()).await;
+    let mut resp = Box::pin(client().send(&mut request(port))).await.unwrap();
+    let err = resp.body_bytes().await.expect_err("damaged in transit");
+    assert!(matches!(err, ZjhttpcError::ChecksumMismatch { .. }), "{err:?}");
+    // The rest of the body was still read, so the connection is reusable.
+    assert!(resp.is_body_read_complete());
+}
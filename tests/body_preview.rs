@@ -0,0 +1,70 @@
+mod common;
+
+use async_std::io::ReadExt;
+use zjhttpc::client::ZJHttpClient;
+use zjhttpc::methods;
+use zjhttpc::requestx::Request;
+
+use common::MockServer;
+
+/// Answers requests with `response`, keeping the connection open.
+async fn spawn_server(response: Vec<u8>) -> u16 {
+    MockServer::always(response).spawn().await.port
+}
+
+async fn get(head: &str, body: &[u8]) -> zjhttpc::response::Response {
+    let port = spawn_server([head.as_bytes(), body].concat()).await;
+    let client = ZJHttpClient::builder().build().unwrap();
+    let mut req = Request::new(methods::GET, format!("http://127.0.0.1:{port}/")).unwrap();
+    Box::pin(client.send(&mut req)).await.unwrap()
+}
+
+fn content_length(len: usize) -> String {
+    format!("HTTP/1.1 500 Internal Server Error\r\nContent-Length: {len}\r\n\r\n")
+}
+
+#[async_std::test]
+async fn short_body_is_shown_whole_and_stays_readable() {
+    let body = b"upstream timed out\r\n\ttry again\n";
+    let mut resp = get(&content_length(body.len()), body).await;
+
+    assert_eq!(resp.body_preview(500).await.unwrap(), "upstream timed out   try again ");
+    assert_eq!(resp.body_bytes().await.unwrap(), body);
+}
+
+#[async_std::test]
+async fn multi_byte_character_is_not_split() {
+    // "é" is two bytes; a cut after 4 bytes falls inside it.
+    let body = "café crème brûlée".as_bytes();
+    let mut resp = get(&content_length(body.len()), body).await;
+
+    assert_eq!(resp.body_preview(4).await.unwrap(), "caf...(truncated, total 21 bytes)");
+    assert_eq!(resp.body_preview(5).await.unwrap(), "café...(truncated, total 21 bytes)");
+    assert_eq!(resp.body_string().await.unwrap(), "café crème brûlée");
+}
+
+#[async_std::test]
+async fn binary_body_is_made_printable() {
+    let body: Vec<u8> = (0..=255).collect();
+    let mut resp = get(&content_length(body.len()), &body).await;
+
+    let preview = resp.body_preview(64).await.unwrap();
+    assert!(preview.ends_with("...(truncated, total 256 bytes)"), "{preview}");
+    assert!(!preview.chars().any(char::is_control), "{preview:?}");
+    assert!(preview.contains("!\"#$%&'()*+,-./0123456789:;<=>?"), "{preview}");
+    assert_eq!(resp.body_bytes().await.unwrap(), body, "the preview consumed nothing");
+}
+
+#[async_std::test]
+async fn charset_and_unknown_length_are_honoured() {
+    // "中文错误" in GBK, then more than the preview shows.
+    let head = "HTTP/1.1 500 Internal Server Error\r\nContent-Type: text/plain; charset=GBK\r\n\
+                Transfer-Encoding: chunked\r\n\r\n";
+    let mut body = b"a\r\n\xd6\xd0\xce\xc4\xb4\xed\xce\xf3!!\r\n0\r\n\r\n".to_vec();
+    let mut resp = get(head, &body).await;
+
+    assert_eq!(resp.body_preview(7).await.unwrap(), "中文错...(truncated)");
+    body.truncate(0);
+    resp.body_managed_stream().unwrap().read_to_end(&mut body).await.unwrap();
+    assert_eq!(body, b"\xd6\xd0\xce\xc4\xb4\xed\xce\xf3!!");
+}
@@ -0,0 +1,67 @@
+mod common;
+
+use async_std::io::ReadExt;
+use zjhttpc::ZjhttpcError;
+use zjhttpc::client::ZJHttpClient;
+use zjhttpc::methods;
+use zjhttpc::requestx::Request;
+
+use common::MockServer;
+
+/// Keep-alive server answering every request with `response`.
+async fn spawn_server(response: &'static str) -> u16 {
+    MockServer::always(response).spawn().await.port
+}
+
+async fn get(client: &ZJHttpClient, port: u16) -> zjhttpc::response::Response {
+    let mut req = Request::new(methods::GET, format!("http://127.0.0.1:{port}/file")).unwrap();
+    Box::pin(client.send(&mut req)).await.unwrap()
+}
+
+#[async_std::test]
+async fn reader_stops_at_content_length_and_pools_the_connection() {
+    let port = spawn_server("HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello").await;
+    let client = ZJHttpClient::builder().build().unwrap();
+
+    let mut resp = get(&client, port).await;
+    let mut out = Vec::new();
+    async_std::io::copy(&mut resp.body_reader().unwrap(), &mut out).await.unwrap();
+    assert_eq!(out, b"hello");
+    assert!(resp.is_body_read_complete());
+    drop(resp);
+    assert_eq!(client.idle_connections(), 1);
+
+    let mut resp = get(&client, port).await;
+    assert!(resp.reused_connection, "the reader left the connection at the next response");
+    assert_eq!(resp.body_string().await.unwrap(), "hello");
+}
+
+#[async_std::test]
+async fn reader_decodes_chunks() {
+    let port = spawn_server("HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n3\r\nbig\r\n5\r\n file\r\n0\r\n\r\n").await;
+    let client = ZJHttpClient::builder().build().unwrap();
+
+    let mut resp = get(&client, port).await;
+    let mut out = String::new();
+    resp.body_reader().unwrap().read_to_string(&mut out).await.unwrap();
+    assert_eq!(out, "big file");
+    drop(resp);
+    assert_eq!(client.idle_connections(), 1);
+}
+
+#[async_std::test]
+async fn body_can_be_taken_only_once() {
+    let port = spawn_server("HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello").await;
+    let client = ZJHttpClient::builder().build().unwrap();
+
+    let mut resp = get(&client, port).await;
+    let mut reader = resp.body_reader().unwrap();
+    let mut two = [0u8; 2];
+    reader.read_exact(&mut two).await.unwrap();
+    assert!(matches!(resp.body_reader().err(), Some(ZjhttpcError::BodyAlreadyRead { .. })));
+
+    // Stopping early: the connection cannot be reused.
+    drop(reader);
+    drop(resp);
+    assert_eq!(client.idle_connections(), 0);
+}
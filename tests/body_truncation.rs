@@ -0,0 +1,96 @@
+mod common;
+
+use async_std::io::ReadExt;
+use zjhttpc::client::ZJHttpClient;
+use zjhttpc::methods;
+use zjhttpc::requestx::Request;
+use zjhttpc::ZjhttpcError;
+
+use common::{MockServer, Reply};
+
+/// Serves one response per connection, then closes. `body` may be shorter than
+/// the declared `content_length` to simulate a server dying mid-body.
+async fn spawn_server(content_length: usize, body: &'static [u8]) -> u16 {
+    let head = format!("HTTP/1.1 200 OK\r\nContent-Length: {content_length}\r\n\r\n");
+    MockServer::always(Reply::new(head).then(body).then_close()).spawn().await.port
+}
+
+fn assert_truncated(err: &ZjhttpcError, expected: u64, received: u64) {
+    match err {
+        ZjhttpcError::BodyTruncated { expected: e, received: r, .. } => {
+            assert_eq!((*e, *r), (expected, received));
+        }
+        other => panic!("expected BodyTruncated, got {other:?}"),
+    }
+}
+
+#[async_std::test]
+async fn body_string_reports_truncation() {
+    let port = spawn_server(10, b"hello").await;
+    let client = ZJHttpClient::builder().build().unwrap();
+    let mut req = Request::new(methods::GET, format!("http://127.0.0.1:{port}/")).unwrap();
+    let mut resp = client.send(&mut req).await.unwrap();
+    let err = resp.body_string().await.unwrap_err();
+    assert_truncated(&err, 10, 5);
+}
+
+#[async_std::test]
+async fn body_bytes_reports_truncation() {
+    let port = spawn_server(10, b"hello").await;
+    let client = ZJHttpClient::builder().build().unwrap();
+    let mut req = Request::new(methods::GET, format!("http://127.0.0.1:{port}/")).unwrap();
+    let mut resp = client.send(&mut req).await.unwrap();
+    let err = resp.body_bytes().await.unwrap_err();
+    assert_truncated(&err, 10, 5);
+}
+
+#[async_std::test]
+async fn managed_stream_error_converts_to_truncation() {
+    let port = spawn_server(10, b"hello").await;
+    let client = ZJHttpClient::builder().build().unwrap();
+    let mut req = Request::new(methods::GET, format!("http://127.0.0.1:{port}/")).unwrap();
+    let mut resp = client.send(&mut req).await.unwrap();
+    let mut stream = resp.body_managed_stream().unwrap();
+    let mut out = Vec::new();
+    let io_err = stream.read_to_end(&mut out).await.unwrap_err();
+    assert_eq!(io_err.kind(), std::io::ErrorKind::UnexpectedEof);
+    assert_truncated(&ZjhttpcError::from(io_err), 10, 5);
+}
+
+#[async_std::test]
+async fn save_to_file_removes_partial_file() {
+    let port = spawn_server(10, b"hello").await;
+    let client = ZJHttpClient::builder().build().unwrap();
+    let mut req = Request::new(methods::GET, format!("http://127.0.0.1:{port}/")).unwrap();
+    let mut resp = client.send(&mut req).await.unwrap();
+    let path = std::env::temp_dir().join(format!("zjhttpc-truncated-{port}.bin"));
+    let err = resp.save_to_file(&path).await.unwrap_err();
+    assert_truncated(&err, 10, 5);
+    assert!(!path.exists(), "partial file should have been removed");
+}
+
+#[async_std::test]
+async fn save_to_file_keeps_complete_body() {
+    let port = spawn_server(5, b"hello").await;
+    let client = ZJHttpClient::builder().build().unwrap();
+    let mut req = Request::new(methods::GET, format!("http://127.0.0.1:{port}/")).unwrap();
+    let mut resp = client.send(&mut req).await.unwrap();
+    let path = std::env::temp_dir().join(format!("zjhttpc-complete-{port}.bin"));
+    assert_eq!(resp.save_to_file(&path).await.unwrap(), 5);
+    assert_eq!(std::fs::read(&path).unwrap(), b"hello");
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[async_std::test]
+async fn save_to_file_leaves_an_existing_file_alone() {
+    let port = spawn_server(10, b"hello").await;
+    let client = ZJHttpClient::builder().build().unwrap();
+    let mut req = Request::new(methods::GET, format!("http://127.0.0.1:{port}/")).unwrap();
+    let mut resp = client.send(&mut req).await.unwrap();
+    let path = std::env::temp_dir().join(format!("zjhttpc-existing-{port}.bin"));
+    std::fs::write(&path, b"precious").unwrap();
+    let err = resp.save_to_file(&path).await.unwrap_err();
+    assert!(matches!(&err, ZjhttpcError::Io { source, .. } if source.kind() == std::io::ErrorKind::AlreadyExists), "{err:?}");
+    assert_eq!(std::fs::read(&path).unwrap(), b"precious");
+    std::fs::remove_file(&path).unwrap();
+}
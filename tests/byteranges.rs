@@ -0,0 +1,73 @@
+mod common;
+
+use zjhttpc::byteranges::ByteRangePart;
+use zjhttpc::client::ZJHttpClient;
+use zjhttpc::requestx::Request;
+use zjhttpc::{ZjhttpcError, methods};
+
+use common::MockServer;
+
+fn response(status: &str, headers: &str, body: &[u8]) -> Vec<u8> {
+    let mut out = format!("HTTP/1.1 {status}\r\n{headers}Content-Length: {}\r\n\r\n", body.len()).into_bytes();
+    out.extend_from_slice(body);
+    out
+}
+
+#[async_std::test]
+async fn two_part_byteranges_response() {
+    let body = b"--3d6b6a416f9b5\r\n\
+Content-Type: text/plain\r\n\
+Content-Range: bytes 0-4/26\r\n\
+\r\n\
+abcde\r\n\
+--3d6b6a416f9b5\r\n\
+Content-Type: text/plain\r\n\
+Content-Range: bytes 20-25/26\r\n\
+\r\n\
+uvwxyz\r\n\
+--3d6b6a416f9b5--\r\n";
+    let server = MockServer::always(response(
+        "206 Partial Content",
+        "Content-Type: multipart/byteranges; boundary=3d6b6a416f9b5\r\n",
+        body,
+    ))
+    .spawn()
+    .await;
+    let client = ZJHttpClient::builder().build().unwrap();
+    let mut req = Request::new(methods::GET, server.url("/alphabet"))
+        .unwrap()
+        .set_ranges(&[(0, Some(4)), (20, None)]);
+    let mut resp = client.send(&mut req).await.unwrap();
+    assert_eq!(server.request().await.header("range"), Some("bytes=0-4,20-"));
+
+    let parts = resp.byteranges().await.unwrap();
+    assert_eq!(
+        parts,
+        vec![
+            ByteRangePart { range: (0, 4), total: Some(26), data: b"abcde".to_vec() },
+            ByteRangePart { range: (20, 25), total: Some(26), data: b"uvwxyz".to_vec() },
+        ]
+    );
+}
+
+#[async_std::test]
+async fn single_range_response() {
+    let port = MockServer::always(response("206 Partial Content", "Content-Range: bytes 10-14/*\r\n", b"klmno")).spawn().await.port;
+    let client = ZJHttpClient::builder().build().unwrap();
+    let mut req = Request::new(methods::GET, format!("http://127.0.0.1:{port}/"))
+        .unwrap()
+        .set_ranges(&[(10, Some(14))]);
+    let parts = client.send(&mut req).await.unwrap().byteranges().await.unwrap();
+    assert_eq!(parts, vec![ByteRangePart { range: (10, 14), total: None, data: b"klmno".to_vec() }]);
+}
+
+#[async_std::test]
+async fn full_response_is_not_partial_content() {
+    let port = MockServer::always(response("200 OK", "", b"everything")).spawn().await.port;
+    let client = ZJHttpClient::builder().build().unwrap();
+    let mut req = Request::new(methods::GET, format!("http://127.0.0.1:{port}/"))
+        .unwrap()
+        .set_ranges(&[(0, Some(1))]);
+    let err = client.send(&mut req).await.unwrap().byteranges().await.expect_err("not a 206");
+    assert!(matches!(err, ZjhttpcError::InvalidByteRanges { .. }), "{err:?}");
+}
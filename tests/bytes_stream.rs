@@ -0,0 +1,157 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use async_std::io::prelude::BufReadExt;
+use async_std::io::{BufReader, WriteExt};
+use async_std::net::TcpListener;
+use async_std::task;
+use futures::StreamExt;
+use zjhttpc::client::ZJHttpClient;
+use zjhttpc::methods;
+use zjhttpc::requestx::Request;
+
+/// Answers every request on a connection with `response`, counting accepted
+/// connections. With `close`, each connection is closed after one response.
+async fn spawn_server(response: Vec<u8>, close: bool) -> (u16, Arc<AtomicUsize>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let accepted = Arc::new(AtomicUsize::new(0));
+    let counter = accepted.clone();
+    task::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            counter.fetch_add(1, Ordering::SeqCst);
+            let response = response.clone();
+            task::spawn(async move {
+                let mut writer = stream.clone();
+                let mut reader = BufReader::new(stream);
+                loop {
+                    let mut line = String::new();
+                    loop {
+                        line.clear();
+                        if reader.read_line(&mut line).await.unwrap_or(0) == 0 {
+                            return;
+                        }
+                        if line == "\r\n" {
+                            break;
+                        }
+                    }
+                    if writer.write_all(&response).await.is_err() || close {
+                        return;
+                    }
+                }
+            });
+        }
+    });
+    (port, accepted)
+}
+
+fn large_body() -> Vec<u8> {
+    (0..50_000u32).map(|i| (i % 251) as u8).collect()
+}
+
+fn content_length_response() -> Vec<u8> {
+    let body = large_body();
+    let mut resp = format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n", body.len()).into_bytes();
+    resp.extend_from_slice(&body);
+    resp
+}
+
+fn chunked_response() -> Vec<u8> {
+    let mut resp = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n".to_vec();
+    for chunk in large_body().chunks(7_000) {
+        resp.extend_from_slice(format!("{:x}\r\n", chunk.len()).as_bytes());
+        resp.extend_from_slice(chunk);
+        resp.extend_from_slice(b"\r\n");
+    }
+    resp.extend_from_slice(b"0\r\n\r\n");
+    resp
+}
+
+fn eof_framed_response() -> Vec<u8> {
+    let mut resp = b"HTTP/1.1 200 OK\r\nConnection: close\r\n\r\n".to_vec();
+    resp.extend_from_slice(&large_body());
+    resp
+}
+
+async fn collect_both(response: Vec<u8>, close: bool) -> (Vec<u8>, Vec<u8>, usize) {
+    let (port, _) = spawn_server(response, close).await;
+    let client = ZJHttpClient::builder().build().unwrap();
+    let url = format!("http://127.0.0.1:{port}/");
+
+    let mut resp = client.send(&mut Request::new(methods::GET, &url).unwrap()).await.unwrap();
+    let chunks: Vec<Vec<u8>> = resp
+        .bytes_stream()
+        .map(|chunk| chunk.unwrap())
+        .collect()
+        .await;
+    assert!(chunks.iter().all(|c| !c.is_empty() && c.len() <= 8192));
+    let streamed = chunks.concat();
+
+    let mut resp = client.send(&mut Request::new(methods::GET, &url).unwrap()).await.unwrap();
+    let bytes = resp.body_bytes().await.unwrap();
+    (streamed, bytes, chunks.len())
+}
+
+#[async_std::test]
+async fn bytes_stream_matches_body_bytes_for_every_framing() {
+    for (response, close) in [
+        (content_length_response(), false),
+        (chunked_response(), false),
+        (eof_framed_response(), true),
+    ] {
+        let (streamed, bytes, chunk_count) = collect_both(response, close).await;
+        assert_eq!(streamed, bytes);
+        assert_eq!(streamed, large_body());
+        assert!(chunk_count > 1);
+    }
+}
+
+#[async_std::test]
+async fn completed_stream_reuses_the_connection() {
+    let (port, accepted) = spawn_server(content_length_response(), false).await;
+    let client = ZJHttpClient::builder().build().unwrap();
+    let url = format!("http://127.0.0.1:{port}/");
+
+    let mut resp = client.send(&mut Request::new(methods::GET, &url).unwrap()).await.unwrap();
+    let total: usize = resp.bytes_stream().map(|c| c.unwrap().len()).fold(0, |a, b| async move { a + b }).await;
+    assert_eq!(total, large_body().len());
+    assert!(resp.is_body_read_complete());
+    drop(resp);
+
+    let mut resp = client.send(&mut Request::new(methods::GET, &url).unwrap()).await.unwrap();
+    assert_eq!(resp.body_bytes().await.unwrap().len(), large_body().len());
+    assert_eq!(accepted.load(Ordering::SeqCst), 1);
+}
+
+#[async_std::test]
+async fn dropped_stream_does_not_reuse_the_connection() {
+    let (port, accepted) = spawn_server(content_length_response(), false).await;
+    let client = ZJHttpClient::builder().build().unwrap();
+    let url = format!("http://127.0.0.1:{port}/");
+
+    let mut resp = client.send(&mut Request::new(methods::GET, &url).unwrap()).await.unwrap();
+    {
+        let mut stream = Box::pin(resp.bytes_stream());
+        assert!(!stream.next().await.unwrap().unwrap().is_empty());
+    }
+    assert!(!resp.is_body_read_complete());
+    drop(resp);
+
+    let mut resp = client.send(&mut Request::new(methods::GET, &url).unwrap()).await.unwrap();
+    assert_eq!(resp.body_bytes().await.unwrap().len(), large_body().len());
+    assert_eq!(accepted.load(Ordering::SeqCst), 2);
+}
+
+#[async_std::test]
+async fn bytes_stream_after_body_read_fails() {
+    let (port, _) = spawn_server(content_length_response(), false).await;
+    let client = ZJHttpClient::builder().build().unwrap();
+    let mut resp = client
+        .send(&mut Request::new(methods::GET, format!("http://127.0.0.1:{port}/")).unwrap())
+        .await
+        .unwrap();
+    resp.body_bytes().await.unwrap();
+    let items: Vec<_> = resp.bytes_stream().collect().await;
+    assert_eq!(items.len(), 1);
+    assert!(matches!(items[0], Err(zjhttpc::ZjhttpcError::BodyAlreadyRead { .. })));
+}
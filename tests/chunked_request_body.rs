@@ -0,0 +1,146 @@
+use std::collections::VecDeque;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use async_std::channel::{Receiver, unbounded};
+use async_std::io::{BufReader, ReadExt, WriteExt};
+use async_std::net::{TcpListener, TcpStream};
+use async_std::prelude::*;
+use async_std::task;
+use zjhttpc::client::ZJHttpClient;
+use zjhttpc::methods;
+use zjhttpc::requestx::Request;
+
+/// A body of unknown length that hands out one piece per read.
+struct Pieces(VecDeque<Vec<u8>>);
+
+impl async_std::io::Read for Pieces {
+    fn poll_read(mut self: Pin<&mut Self>, _cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<std::io::Result<usize>> {
+        let Some(mut piece) = self.0.pop_front() else {
+            return Poll::Ready(Ok(0));
+        };
+        let n = piece.len().min(buf.len());
+        buf[..n].copy_from_slice(&piece[..n]);
+        if n < piece.len() {
+            self.0.push_front(piece.split_off(n));
+        }
+        Poll::Ready(Ok(n))
+    }
+}
+
+fn pieces(parts: &[&str]) -> Pieces {
+    Pieces(parts.iter().map(|p| p.as_bytes().to_vec()).collect())
+}
+
+struct Received {
+    head: String,
+    chunks: Vec<Vec<u8>>,
+    /// Bytes arrived before the server sent `100 Continue`.
+    early_bytes: bool,
+}
+
+async fn read_line(reader: &mut BufReader<TcpStream>) -> String {
+    let mut line = String::new();
+    reader.read_line(&mut line).await.unwrap();
+    assert!(line.ends_with("\r\n"), "line not CRLF-terminated: {line:?}");
+    line.truncate(line.len() - 2);
+    line
+}
+
+/// Answers one request after strictly decoding its chunked body.
+async fn spawn_chunked_server() -> (u16, Receiver<Received>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let (tx, rx) = unbounded();
+    task::spawn(async move {
+        let (stream, _) = listener.accept().await.unwrap();
+        let mut writer = stream.clone();
+        let mut reader = BufReader::new(stream);
+        let mut head = String::new();
+        loop {
+            let line = read_line(&mut reader).await;
+            if line.is_empty() {
+                break;
+            }
+            head.push_str(&line);
+            head.push_str("\r\n");
+        }
+        let mut early_bytes = false;
+        if head.to_ascii_lowercase().contains("expect: 100-continue") {
+            let mut byte = [0u8; 1];
+            let early = async_std::io::timeout(Duration::from_millis(300), reader.read(&mut byte)).await;
+            early_bytes = matches!(early, Ok(1));
+            writer.write_all(b"HTTP/1.1 100 Continue\r\n\r\n").await.unwrap();
+        }
+        let mut chunks = Vec::new();
+        loop {
+            let size = usize::from_str_radix(&read_line(&mut reader).await, 16).expect("hex chunk size");
+            if size == 0 {
+                assert_eq!(read_line(&mut reader).await, "", "no trailers expected");
+                break;
+            }
+            let mut data = vec![0u8; size + 2];
+            reader.read_exact(&mut data).await.unwrap();
+            assert!(data.ends_with(b"\r\n"), "chunk data not followed by CRLF");
+            data.truncate(size);
+            chunks.push(data);
+        }
+        writer.write_all(b"HTTP/1.1 204 No Content\r\n\r\n").await.unwrap();
+        tx.send(Received { head, chunks, early_bytes }).await.unwrap();
+    });
+    (port, rx)
+}
+
+#[async_std::test]
+async fn unknown_length_stream_is_sent_chunked() {
+    let (port, received) = spawn_chunked_server().await;
+    let client = ZJHttpClient::builder().build().unwrap();
+    let mut req = Request::new(methods::POST, format!("http://127.0.0.1:{port}/upload"))
+        .unwrap()
+        .set_body_stream_chunked(pieces(&["compressed ", "output, ", "piece by piece"]));
+    let resp = client.send(&mut req).await.unwrap();
+    assert_eq!(resp.status_code, 204);
+
+    let received = received.recv().await.unwrap();
+    let head = received.head.to_ascii_lowercase();
+    assert!(head.contains("\r\ntransfer-encoding: chunked\r\n"), "{head}");
+    assert!(!head.contains("content-length"), "{head}");
+    assert_eq!(received.chunks, [&b"compressed "[..], b"output, ", b"piece by piece"]);
+}
+
+#[async_std::test]
+async fn empty_stream_sends_only_the_terminator() {
+    let (port, received) = spawn_chunked_server().await;
+    let client = ZJHttpClient::builder().build().unwrap();
+    let mut req = Request::new(methods::PUT, format!("http://127.0.0.1:{port}/"))
+        .unwrap()
+        .set_body_stream_chunked(pieces(&[]));
+    client.send(&mut req).await.unwrap();
+    assert!(received.recv().await.unwrap().chunks.is_empty());
+}
+
+#[async_std::test]
+async fn chunks_wait_for_100_continue() {
+    let (port, received) = spawn_chunked_server().await;
+    let client = ZJHttpClient::builder().build().unwrap();
+    let mut req = Request::new(methods::POST, format!("http://127.0.0.1:{port}/"))
+        .unwrap()
+        .set_body_stream_chunked(pieces(&["after ", "continue"]))
+        .put_expect_continue();
+    client.send(&mut req).await.unwrap();
+
+    let received = received.recv().await.unwrap();
+    assert!(!received.early_bytes, "body started before 100 Continue");
+    assert_eq!(received.chunks.concat(), b"after continue");
+}
+
+#[test]
+fn a_later_body_setter_turns_chunking_off() {
+    let req = Request::new(methods::POST, "http://example.com/")
+        .unwrap()
+        .set_body_stream_chunked(pieces(&["x"]))
+        .set_body_string("fixed");
+    assert!(!req.use_chunked);
+    assert_eq!(req.content_length, 5);
+}
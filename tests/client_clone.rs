@@ -0,0 +1,109 @@
+mod common;
+
+use std::time::{Duration, SystemTime};
+
+use zjhttpc::client::ZJHttpClient;
+use zjhttpc::memory_budget::MemoryBudget;
+use zjhttpc::methods;
+use zjhttpc::requestx::Request;
+
+use common::{Mock, MockServer, OK};
+
+/// Keep-alive server answering `ok` to every request; returns the port and
+/// the server, which counts the connections accepted.
+async fn spawn_server() -> (u16, Mock) {
+    let server = MockServer::always(OK).spawn().await;
+    (server.port, server)
+}
+
+/// Send a GET and read the body; returns whether the connection was pooled.
+async fn get(client: &ZJHttpClient, port: u16) -> bool {
+    let mut req = Request::new(methods::GET, format!("http://127.0.0.1:{port}/")).unwrap();
+    let mut resp = Box::pin(client.send(&mut req)).await.unwrap();
+    assert_eq!(resp.body_string().await.unwrap(), "ok");
+    resp.reused_connection
+}
+
+#[test]
+fn client_handle_is_send_sync_static() {
+    fn assert_embeddable<T: Clone + Send + Sync + 'static>() {}
+    assert_embeddable::<ZJHttpClient>();
+}
+
+#[async_std::test]
+async fn clones_share_the_connection_pool() {
+    let (port, dials) = spawn_server().await;
+    let client = ZJHttpClient::builder().build().unwrap();
+    let clone = client.clone();
+
+    assert!(!get(&client, port).await);
+    assert_eq!(clone.idle_connections(), 1);
+    assert!(get(&clone, port).await, "the clone reuses the original's connection");
+    assert_eq!(dials.accepts(), 1);
+}
+
+#[async_std::test]
+async fn fork_isolated_has_its_own_pool_and_same_config() {
+    let (port, dials) = spawn_server().await;
+    let client = ZJHttpClient::builder()
+        .set_global_connect_timeout(Duration::from_millis(1500))
+        .build()
+        .unwrap();
+    assert!(!get(&client, port).await);
+
+    let fork = client.fork_isolated();
+    assert_eq!(fork.global_connect_timeout, Duration::from_millis(1500));
+    assert_eq!(fork.idle_connections(), 0);
+    assert!(!get(&fork, port).await, "the fork must dial its own connection");
+    assert_eq!(dials.accepts(), 2);
+
+    assert_eq!(client.idle_connections(), 1);
+    assert_eq!(fork.idle_connections(), 1);
+    assert!(get(&client, port).await);
+}
+
+#[async_std::test]
+async fn fork_isolated_has_its_own_memory_budget() {
+    let (port, _) = spawn_server().await;
+    let client = ZJHttpClient::builder().set_memory_budget(MemoryBudget::new(1024)).build().unwrap();
+    let fork = client.fork_isolated();
+    assert_eq!(fork.memory_budget.as_ref().unwrap().max_bytes(), 1024);
+
+    let mut req = Request::new(methods::GET, format!("http://127.0.0.1:{port}/")).unwrap();
+    let mut resp = Box::pin(client.send(&mut req)).await.unwrap();
+    assert_eq!(resp.peek_body(2).await.unwrap(), b"ok");
+    assert_eq!((client.buffered_bytes(), fork.buffered_bytes()), (2, 0));
+
+    let mut req = Request::new(methods::GET, format!("http://127.0.0.1:{port}/")).unwrap();
+    let mut fork_resp = Box::pin(fork.send(&mut req)).await.unwrap();
+    assert_eq!(fork_resp.peek_body(1).await.unwrap(), b"o");
+    assert_eq!((client.buffered_bytes(), fork.buffered_bytes()), (2, 1));
+
+    drop(resp);
+    assert_eq!((client.buffered_bytes(), fork.buffered_bytes()), (0, 1));
+}
+
+#[test]
+fn fork_isolated_copies_hsts_hosts_once() {
+    let client = ZJHttpClient::builder().hsts(true).build().unwrap();
+    let expires = SystemTime::now() + Duration::from_secs(60);
+    client.hsts.as_ref().unwrap().preload("known.example", false, expires);
+
+    let fork = client.fork_isolated();
+    let fork_store = fork.hsts.as_ref().unwrap();
+    assert!(fork_store.get("known.example").is_some());
+    fork_store.preload("fork-only.example", false, expires);
+    assert!(client.hsts.as_ref().unwrap().get("fork-only.example").is_none());
+    assert!(client.clone().hsts.as_ref().unwrap().get("known.example").is_some());
+}
+
+#[async_std::test]
+async fn by_value_setters_detach_from_clones() {
+    let (port, dials) = spawn_server().await;
+    let client = ZJHttpClient::builder().build().unwrap();
+    let resized = client.clone().set_pool_config(4, 8, Duration::from_secs(30));
+
+    assert!(!get(&client, port).await);
+    assert!(!get(&resized, port).await, "a new pool does not see the old one's connections");
+    assert_eq!(dials.accepts(), 2);
+}
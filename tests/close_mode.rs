@@ -0,0 +1,166 @@
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use async_std::io::{self, ReadExt, WriteExt};
+use async_std::net::{TcpListener, TcpStream};
+use async_std::task;
+use futures::future::{BoxFuture, join_all};
+use zjhttpc::client::ZJHttpClient;
+use zjhttpc::connector::Connector;
+use zjhttpc::methods;
+use zjhttpc::requestx::Request;
+use zjhttpc::stream::BoxedStream;
+
+/// Keep-alive server that, once `closing` is set, closes its idle
+/// connections and answers with `Connection: close`, like a server being
+/// deployed. Responses are delayed a little so concurrent requests overlap.
+async fn spawn_server(closing: Arc<AtomicBool>) -> u16 {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    task::spawn(async move {
+        while let Ok((mut stream, _)) = listener.accept().await {
+            let closing = closing.clone();
+            task::spawn(async move {
+                loop {
+                    let mut head = Vec::new();
+                    let mut byte = [0u8; 1];
+                    while !head.ends_with(b"\r\n\r\n") {
+                        match io::timeout(Duration::from_millis(10), stream.read(&mut byte)).await {
+                            Ok(1) => head.push(byte[0]),
+                            Err(e) if e.kind() == std::io::ErrorKind::TimedOut => {
+                                if head.is_empty() && closing.load(Ordering::SeqCst) {
+                                    return;
+                                }
+                            }
+                            _ => return,
+                        }
+                    }
+                    task::sleep(Duration::from_millis(20)).await;
+                    let close = closing.load(Ordering::SeqCst);
+                    let connection = if close { "close" } else { "keep-alive" };
+                    let response = format!("HTTP/1.1 200 OK\r\nConnection: {connection}\r\nContent-Length: 2\r\n\r\nok");
+                    if stream.write_all(response.as_bytes()).await.is_err() || close {
+                        return;
+                    }
+                }
+            });
+        }
+    });
+    port
+}
+
+/// TCP connector whose streams count dead pickups: a request written to a
+/// connection the server has already closed.
+struct Counting {
+    dead: Arc<AtomicUsize>,
+}
+
+impl Connector for Counting {
+    fn connect<'a>(&'a self, _host: &'a str, addr: SocketAddr, _timeout: Duration) -> BoxFuture<'a, zjhttpc::Result<BoxedStream>> {
+        Box::pin(async move {
+            let inner = TcpStream::connect(addr).await.map_err(zjhttpc::ZjhttpcError::from)?;
+            Ok(Box::new(Watched { inner, awaiting: false, dead: self.dead.clone() }) as BoxedStream)
+        })
+    }
+}
+
+struct Watched {
+    inner: TcpStream,
+    /// A request was written and no response byte has come back yet.
+    awaiting: bool,
+    dead: Arc<AtomicUsize>,
+}
+
+impl io::Read for Watched {
+    fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<std::io::Result<usize>> {
+        let result = Pin::new(&mut self.inner).poll_read(cx, buf);
+        match &result {
+            Poll::Ready(Ok(0)) | Poll::Ready(Err(_)) if self.awaiting => {
+                self.awaiting = false;
+                self.dead.fetch_add(1, Ordering::SeqCst);
+            }
+            Poll::Ready(Ok(_)) => self.awaiting = false,
+            _ => {}
+        }
+        result
+    }
+}
+
+impl io::Write for Watched {
+    fn poll_write(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<std::io::Result<usize>> {
+        let result = Pin::new(&mut self.inner).poll_write(cx, buf);
+        match &result {
+            Poll::Ready(Err(_)) => {
+                self.dead.fetch_add(1, Ordering::SeqCst);
+            }
+            Poll::Ready(Ok(_)) => self.awaiting = true,
+            Poll::Pending => {}
+        }
+        result
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.inner).poll_close(cx)
+    }
+}
+
+async fn get(client: &ZJHttpClient, port: u16) {
+    let mut req = Request::new(methods::GET, format!("http://127.0.0.1:{port}/")).unwrap();
+    let mut resp = Box::pin(client.send(&mut req)).await.unwrap();
+    assert_eq!(resp.body_string().await.unwrap(), "ok");
+}
+
+/// Fill the pool with 8 connections, switch the server to closing, then send
+/// 8 requests one after another. Returns the dead pickups of that burst.
+async fn burst_during_deploy(cooldown: Option<Duration>) -> (usize, ZJHttpClient) {
+    let closing = Arc::new(AtomicBool::new(false));
+    let port = spawn_server(closing.clone()).await;
+    let dead = Arc::new(AtomicUsize::new(0));
+    let mut builder = ZJHttpClient::builder();
+    builder.set_connector(Arc::new(Counting { dead: dead.clone() }) as Arc<dyn Connector>);
+    if let Some(cooldown) = cooldown {
+        builder.set_close_mode_cooldown(cooldown);
+    }
+    let client = builder.build().unwrap();
+
+    join_all((0..8).map(|_| get(&client, port))).await;
+    assert_eq!(client.idle_connections(), 8);
+
+    closing.store(true, Ordering::SeqCst);
+    task::sleep(Duration::from_millis(50)).await;
+    dead.store(0, Ordering::SeqCst);
+    for _ in 0..8 {
+        get(&client, port).await;
+    }
+    (dead.load(Ordering::SeqCst), client)
+}
+
+#[async_std::test]
+async fn without_close_mode_every_request_picks_a_dead_connection() {
+    let (dead, client) = burst_during_deploy(None).await;
+    assert_eq!(dead, 8);
+    assert_eq!(client.keys_in_close_mode(), 0);
+}
+
+#[async_std::test]
+async fn close_mode_stops_picking_after_the_first_close() {
+    let (dead, client) = burst_during_deploy(Some(Duration::from_secs(30))).await;
+    assert_eq!(dead, 1, "only the request that learned of the deploy hit a dead connection");
+    assert_eq!(client.keys_in_close_mode(), 1);
+    assert_eq!(client.idle_connections(), 0);
+}
+
+#[async_std::test]
+async fn close_mode_ends_after_the_cooldown() {
+    let (_, client) = burst_during_deploy(Some(Duration::from_millis(100))).await;
+    task::sleep(Duration::from_millis(150)).await;
+    assert_eq!(client.keys_in_close_mode(), 0);
+}
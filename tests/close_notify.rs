@@ -0,0 +1,141 @@
+mod common;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_std::channel::{self, Receiver, Sender};
+use async_std::io::{ReadExt, WriteExt};
+use async_std::net::TcpListener;
+use async_std::task;
+use zjhttpc::client::ZJHttpClient;
+use zjhttpc::methods;
+use zjhttpc::misc::TrustStorePem;
+use zjhttpc::requestx::Request;
+use zjhttpc::resolver::Resolver;
+
+use common::{CA_A, Loopback, tls_acceptor};
+
+/// How a connection to the server ended.
+#[derive(Debug, PartialEq)]
+enum Closed {
+    /// The client sent `close_notify`.
+    Graceful,
+    /// The TCP connection ended without it.
+    Abrupt,
+}
+
+/// TLS server for `localhost` answering keep-alive requests with `ok`,
+/// except `/big`, whose body is announced as 1 MiB but never sent. Reports
+/// how each connection ended.
+async fn spawn_server() -> (u16, Receiver<Closed>) {
+    let acceptor = tls_acceptor();
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let (tx, rx) = channel::unbounded();
+    task::spawn(async move {
+        while let Ok((tcp, _)) = listener.accept().await {
+            let acceptor = acceptor.clone();
+            let tx: Sender<Closed> = tx.clone();
+            task::spawn(async move {
+                let Ok(mut stream) = acceptor.accept(tcp).await else {
+                    let _ = tx.send(Closed::Abrupt).await;
+                    return;
+                };
+                loop {
+                    let mut head = Vec::new();
+                    let mut byte = [0u8; 1];
+                    while !head.ends_with(b"\r\n\r\n") {
+                        // rustls reports the end of the stream as Ok(0) only
+                        // after close_notify, and as an error otherwise.
+                        let closed = match stream.read(&mut byte).await {
+                            Ok(0) if head.is_empty() => Closed::Graceful,
+                            Ok(0) | Err(_) => Closed::Abrupt,
+                            Ok(_) => {
+                                head.push(byte[0]);
+                                continue;
+                            }
+                        };
+                        let _ = tx.send(closed).await;
+                        return;
+                    }
+                    let response: &[u8] = if head.starts_with(b"GET /big ") {
+                        b"HTTP/1.1 200 OK\r\nContent-Length: 1048576\r\n\r\npartial"
+                    } else {
+                        b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok"
+                    };
+                    if stream.write_all(response).await.is_err() {
+                        return;
+                    }
+                }
+            });
+        }
+    });
+    (port, rx)
+}
+
+fn client() -> ZJHttpClient {
+    ZJHttpClient::builder()
+        .set_resolver(Arc::new(Loopback) as Arc<dyn Resolver>)
+        .set_global_trust_store_pem(TrustStorePem::Bytes(CA_A.to_vec()))
+        .build()
+        .unwrap()
+}
+
+async fn get(client: &ZJHttpClient, port: u16, path: &str) -> zjhttpc::response::Response {
+    let mut req = Request::new(methods::GET, format!("https://localhost:{port}{path}")).unwrap();
+    Box::pin(client.send(&mut req)).await.unwrap()
+}
+
+async fn next_close(closes: &Receiver<Closed>) -> Closed {
+    async_std::future::timeout(Duration::from_secs(5), closes.recv()).await.expect("connection closed").unwrap()
+}
+
+#[async_std::test]
+async fn evicted_connections_send_close_notify() {
+    let (port, closes) = spawn_server().await;
+    let client = client();
+    get(&client, port, "/").await.body_string().await.unwrap();
+    assert_eq!(client.idle_connections(), 1);
+    assert_eq!(client.evict_host("localhost"), 1);
+    assert_eq!(next_close(&closes).await, Closed::Graceful);
+}
+
+#[async_std::test]
+async fn close_idle_connections_and_client_drop_send_close_notify() {
+    let (port, closes) = spawn_server().await;
+    let client = client();
+    // The first response holds its connection, so the second opens another.
+    let mut a = get(&client, port, "/").await;
+    let mut b = get(&client, port, "/").await;
+    a.body_string().await.unwrap();
+    b.body_string().await.unwrap();
+    drop((a, b));
+    assert_eq!(client.idle_connections(), 2);
+    assert_eq!(client.close_idle_connections(), 2);
+    assert_eq!(client.idle_connections(), 0);
+    assert_eq!(next_close(&closes).await, Closed::Graceful);
+    assert_eq!(next_close(&closes).await, Closed::Graceful);
+
+    get(&client, port, "/").await.body_string().await.unwrap();
+    drop(client);
+    assert_eq!(next_close(&closes).await, Closed::Graceful);
+}
+
+#[async_std::test]
+async fn undrainable_dropped_response_sends_close_notify() {
+    let (port, closes) = spawn_server().await;
+    let client = client();
+    drop(get(&client, port, "/big").await);
+    assert_eq!(next_close(&closes).await, Closed::Graceful);
+    assert_eq!(client.in_flight_connections(), 0);
+}
+
+#[async_std::test]
+async fn abandoned_split_send_is_closed_abruptly() {
+    let (port, closes) = spawn_server().await;
+    let client = client();
+    let mut req = Request::new(methods::GET, format!("https://localhost:{port}/")).unwrap();
+    let (stream, _) = client.send_header_only(&mut req).await.unwrap();
+    drop(stream);
+    assert_eq!(next_close(&closes).await, Closed::Abrupt);
+}
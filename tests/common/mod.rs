@@ -0,0 +1,373 @@
+//! Helpers shared by the integration tests: [`MockServer`], a scriptable
+//! HTTP/1.1 server on 127.0.0.1, a resolver that sends every host there and
+//! the `localhost` certificate in `tests/fixtures`, issued by CA A.
+#![allow(dead_code)]
+
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use async_std::channel::{self, Receiver};
+use async_std::io::{Read, ReadExt, Write, WriteExt};
+use async_std::net::TcpListener;
+use async_std::task;
+use async_tls::TlsAcceptor;
+use futures::future::BoxFuture;
+use zjhttpc::resolver::Resolver;
+
+pub const CA_A: &[u8] = include_bytes!("../fixtures/ca_a.pem");
+pub const SERVER_CERT: &[u8] = include_bytes!("../fixtures/server_cert.pem");
+pub const SERVER_KEY: &[u8] = include_bytes!("../fixtures/server_key.pem");
+
+/// A keep-alive response with the body `ok`.
+pub const OK: &str = "HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok";
+
+/// Sends every host to 127.0.0.1, so the certificate's `localhost` name, and
+/// names it does not cover, reach the local test server without depending on
+/// /etc/hosts.
+pub struct Loopback;
+
+impl Resolver for Loopback {
+    fn resolve<'a>(&'a self, _host: &'a str, port: u16) -> BoxFuture<'a, zjhttpc::Result<Vec<SocketAddr>>> {
+        Box::pin(async move { Ok(vec![SocketAddr::from(([127, 0, 0, 1], port))]) })
+    }
+}
+
+/// Read a request head up to and including its blank line; `None` when the
+/// connection ends or fails first.
+pub async fn read_head<S: Read + Unpin>(stream: &mut S) -> Option<String> {
+    let mut head = Vec::new();
+    let mut byte = [0u8; 1];
+    while !head.ends_with(b"\r\n\r\n") {
+        match stream.read(&mut byte).await {
+            Ok(1) => head.push(byte[0]),
+            _ => return None,
+        }
+    }
+    Some(String::from_utf8(head).unwrap())
+}
+
+/// Read the body the request `head` announces, chunked or by
+/// `Content-Length`, decoding chunked framing; `None` when the connection
+/// ends first.
+pub async fn read_body<S: Read + Unpin>(stream: &mut S, head: &str) -> Option<Vec<u8>> {
+    let mut body = Vec::new();
+    if header(head, "transfer-encoding").is_some_and(|value| value.eq_ignore_ascii_case("chunked")) {
+        loop {
+            let mut line = Vec::new();
+            let mut byte = [0u8; 1];
+            while !line.ends_with(b"\r\n") {
+                stream.read_exact(&mut byte).await.ok()?;
+                line.push(byte[0]);
+            }
+            let size = usize::from_str_radix(std::str::from_utf8(&line).ok()?.trim_end(), 16).ok()?;
+            let mut chunk = vec![0u8; size + 2];
+            stream.read_exact(&mut chunk).await.ok()?;
+            if size == 0 {
+                return Some(body);
+            }
+            body.extend_from_slice(&chunk[..size]);
+        }
+    }
+    let len = header(head, "content-length").map_or(0, |value| value.parse().unwrap());
+    body.resize(len, 0);
+    stream.read_exact(&mut body).await.ok()?;
+    Some(body)
+}
+
+/// The value of the first `name` field of a request or response `head`.
+pub fn header<'a>(head: &'a str, name: &str) -> Option<&'a str> {
+    head.lines()
+        .skip(1)
+        .filter_map(|line| line.split_once(':'))
+        .find(|(field, _)| field.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.trim())
+}
+
+/// Server config presenting `cert` (a PEM chain) with `key` (PEM, PKCS #8),
+/// stapling `ocsp` unless it is empty.
+pub fn server_config(cert: &[u8], key: &[u8], ocsp: &[u8]) -> rustls::ServerConfig {
+    let certs = rustls_pemfile::certs(&mut &cert[..]).map(|c| rustls::Certificate(c.unwrap().to_vec())).collect();
+    let key = rustls_pemfile::pkcs8_private_keys(&mut &key[..]).next().unwrap().unwrap();
+    rustls::ServerConfig::builder()
+        .with_safe_defaults()
+        .with_no_client_auth()
+        .with_single_cert_with_ocsp_and_sct(certs, rustls::PrivateKey(key.secret_pkcs8_der().to_vec()), ocsp.to_vec(), Vec::new())
+        .unwrap()
+}
+
+/// Acceptor for the `localhost` certificate issued by CA A.
+pub fn tls_acceptor() -> TlsAcceptor {
+    TlsAcceptor::from(Arc::new(server_config(SERVER_CERT, SERVER_KEY, &[])))
+}
+
+/// A request as the server read it.
+#[derive(Debug, Clone)]
+pub struct Received {
+    /// Request line and header fields, up to and including the blank line.
+    pub head: String,
+    /// The body, when the server reads bodies; chunked framing removed.
+    pub body: Vec<u8>,
+    /// Which connection it came over, counting accepted connections from 1.
+    pub connection: usize,
+}
+
+impl Received {
+    pub fn method(&self) -> &str {
+        self.head.split(' ').next().unwrap()
+    }
+
+    pub fn path(&self) -> &str {
+        self.head.split(' ').nth(1).unwrap()
+    }
+
+    pub fn header(&self, name: &str) -> Option<&str> {
+        header(&self.head, name)
+    }
+}
+
+/// What the server does with one request, step by step.
+#[derive(Debug, Clone, Default)]
+pub struct Reply {
+    steps: Vec<Step>,
+}
+
+#[derive(Debug, Clone)]
+enum Step {
+    Write(Vec<u8>),
+    Sleep(Duration),
+    Close,
+}
+
+impl Reply {
+    /// Write `bytes`, as they are, and wait for the next request.
+    pub fn new(bytes: impl AsRef<[u8]>) -> Self {
+        Reply::default().then(bytes)
+    }
+
+    /// `200 OK` with `body` and its `Content-Length`.
+    pub fn ok(body: impl AsRef<[u8]>) -> Self {
+        let body = body.as_ref();
+        Reply::new(format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n", body.len())).then(body)
+    }
+
+    /// Close the connection without answering.
+    pub fn close() -> Self {
+        Reply::default().then_close()
+    }
+
+    pub fn then(mut self, bytes: impl AsRef<[u8]>) -> Self {
+        self.steps.push(Step::Write(bytes.as_ref().to_vec()));
+        self
+    }
+
+    pub fn then_sleep(mut self, duration: Duration) -> Self {
+        self.steps.push(Step::Sleep(duration));
+        self
+    }
+
+    pub fn then_close(mut self) -> Self {
+        self.steps.push(Step::Close);
+        self
+    }
+}
+
+impl From<&str> for Reply {
+    fn from(bytes: &str) -> Self {
+        Reply::new(bytes)
+    }
+}
+
+impl From<String> for Reply {
+    fn from(bytes: String) -> Self {
+        Reply::new(bytes)
+    }
+}
+
+impl From<&[u8]> for Reply {
+    fn from(bytes: &[u8]) -> Self {
+        Reply::new(bytes)
+    }
+}
+
+impl<const N: usize> From<&[u8; N]> for Reply {
+    fn from(bytes: &[u8; N]) -> Self {
+        Reply::new(bytes)
+    }
+}
+
+impl From<Vec<u8>> for Reply {
+    fn from(bytes: Vec<u8>) -> Self {
+        Reply::new(bytes)
+    }
+}
+
+type Respond = Arc<dyn Fn(&Received) -> Reply + Send + Sync>;
+
+/// A keep-alive HTTP/1.1 server on 127.0.0.1 for tests. Every connection it
+/// accepts is served in a loop: read a request head (and with
+/// [`read_body`](Self::read_body) its body), answer with the [`Reply`] for
+/// it, and read the next one, until the client closes or a reply closes.
+///
+/// ```ignore
+/// let server = MockServer::always(common::OK).spawn().await;
+/// // ... send to server.url("/") ...
+/// assert_eq!(server.request().await.path(), "/");
+/// assert_eq!(server.accepts(), 1);
+/// ```
+pub struct MockServer {
+    respond: Respond,
+    read_body: bool,
+    tls: bool,
+    idle_timeout: Option<Duration>,
+}
+
+impl MockServer {
+    /// Answer every request with `respond(request)`.
+    pub fn new<R: Into<Reply>>(respond: impl Fn(&Received) -> R + Send + Sync + 'static) -> Self {
+        MockServer { respond: Arc::new(move |req| respond(req).into()), read_body: false, tls: false, idle_timeout: None }
+    }
+
+    /// Answer every request with `reply`.
+    pub fn always(reply: impl Into<Reply>) -> Self {
+        let reply = reply.into();
+        MockServer::new(move |_| reply.clone())
+    }
+
+    /// Answer the requests, over whichever connections, with `replies` in
+    /// order, and close the connection of any request after them.
+    pub fn script<R: Into<Reply>>(replies: impl IntoIterator<Item = R>) -> Self {
+        let replies: Vec<Reply> = replies.into_iter().map(Into::into).collect();
+        let next = AtomicUsize::new(0);
+        MockServer::new(move |_| replies.get(next.fetch_add(1, Ordering::SeqCst)).cloned().unwrap_or_else(Reply::close))
+    }
+
+    /// Read each request's body before answering; see [`read_body`].
+    pub fn read_body(mut self) -> Self {
+        self.read_body = true;
+        self
+    }
+
+    /// Serve TLS with the `localhost` certificate issued by CA A.
+    /// [`Mock::accepts`] then counts completed handshakes.
+    pub fn tls(mut self) -> Self {
+        self.tls = true;
+        self
+    }
+
+    /// Close a connection once it has waited `idle` for a request.
+    pub fn idle_timeout(mut self, idle: Duration) -> Self {
+        self.idle_timeout = Some(idle);
+        self
+    }
+
+    pub async fn spawn(self) -> Mock {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let accepts = Arc::new(AtomicUsize::new(0));
+        let (sender, requests) = channel::unbounded();
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let recorder = Recorder { sender, log: log.clone() };
+        let counter = accepts.clone();
+        let acceptor = self.tls.then(tls_acceptor);
+        let server = Arc::new(self);
+        task::spawn(async move {
+            while let Ok((tcp, _)) = listener.accept().await {
+                let (server, recorder) = (server.clone(), recorder.clone());
+                match acceptor.clone() {
+                    Some(acceptor) => {
+                        let counter = counter.clone();
+                        task::spawn(async move {
+                            let Ok(stream) = acceptor.accept(tcp).await else {
+                                return;
+                            };
+                            let connection = counter.fetch_add(1, Ordering::SeqCst) + 1;
+                            server.serve(stream, connection, recorder).await;
+                        });
+                    }
+                    None => {
+                        let connection = counter.fetch_add(1, Ordering::SeqCst) + 1;
+                        task::spawn(async move { server.serve(tcp, connection, recorder).await });
+                    }
+                }
+            }
+        });
+        Mock { port, accepts, requests, log }
+    }
+
+    async fn serve<S: Read + Write + Unpin>(&self, mut stream: S, connection: usize, recorder: Recorder) {
+        loop {
+            let head = match self.idle_timeout {
+                Some(idle) => async_std::future::timeout(idle, read_head(&mut stream)).await.ok().flatten(),
+                None => read_head(&mut stream).await,
+            };
+            let Some(head) = head else {
+                return;
+            };
+            let body = if self.read_body {
+                let Some(body) = read_body(&mut stream, &head).await else {
+                    return;
+                };
+                body
+            } else {
+                Vec::new()
+            };
+            let received = Received { head, body, connection };
+            let reply = (self.respond)(&received);
+            recorder.log.lock().unwrap().push(received.clone());
+            let _ = recorder.sender.try_send(received);
+            for step in reply.steps {
+                match step {
+                    Step::Write(bytes) => {
+                        if stream.write_all(&bytes).await.is_err() || stream.flush().await.is_err() {
+                            return;
+                        }
+                    }
+                    Step::Sleep(duration) => task::sleep(duration).await,
+                    Step::Close => return,
+                }
+            }
+        }
+    }
+}
+
+#[derive(Clone)]
+struct Recorder {
+    sender: channel::Sender<Received>,
+    log: Arc<Mutex<Vec<Received>>>,
+}
+
+/// A running [`MockServer`].
+pub struct Mock {
+    pub port: u16,
+    accepts: Arc<AtomicUsize>,
+    requests: Receiver<Received>,
+    log: Arc<Mutex<Vec<Received>>>,
+}
+
+impl Mock {
+    /// `http://127.0.0.1:{port}{path}`.
+    pub fn url(&self, path: &str) -> String {
+        format!("http://127.0.0.1:{}{path}", self.port)
+    }
+
+    /// Connections accepted so far.
+    pub fn accepts(&self) -> usize {
+        self.accepts.load(Ordering::SeqCst)
+    }
+
+    /// The next request the server read, waiting up to 5 s for it. Each
+    /// request is handed out once, in order.
+    pub async fn request(&self) -> Received {
+        async_std::future::timeout(Duration::from_secs(5), self.requests.recv())
+            .await
+            .expect("the server got no request")
+            .unwrap()
+    }
+
+    /// Every request read so far, in order, without waiting.
+    pub fn requests(&self) -> Vec<Received> {
+        self.log.lock().unwrap().clone()
+    }
+}
@@ -0,0 +1,169 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use async_std::io::{ReadExt, WriteExt};
+use async_std::net::TcpListener;
+use async_std::task;
+use futures::future::BoxFuture;
+use zjhttpc::client::ZJHttpClient;
+use zjhttpc::connection::ConnectionState;
+use zjhttpc::connector::{Connector, TcpConnector};
+use zjhttpc::methods;
+use zjhttpc::requestx::Request;
+use zjhttpc::resolver::Resolver;
+use zjhttpc::stream::BoxedStream;
+
+#[derive(Default)]
+struct Calls {
+    resolves: AtomicUsize,
+    dials: AtomicUsize,
+}
+
+struct CountingResolver(Arc<Calls>);
+
+impl Resolver for CountingResolver {
+    fn resolve<'a>(&'a self, _host: &'a str, port: u16) -> BoxFuture<'a, zjhttpc::Result<Vec<SocketAddr>>> {
+        self.0.resolves.fetch_add(1, Ordering::SeqCst);
+        Box::pin(async move { Ok(vec![SocketAddr::from(([127, 0, 0, 1], port))]) })
+    }
+}
+
+struct CountingConnector(Arc<Calls>);
+
+impl Connector for CountingConnector {
+    fn connect<'a>(
+        &'a self,
+        host: &'a str,
+        addr: SocketAddr,
+        connect_timeout: Duration,
+    ) -> BoxFuture<'a, zjhttpc::Result<BoxedStream>> {
+        self.0.dials.fetch_add(1, Ordering::SeqCst);
+        TcpConnector.connect(host, addr, connect_timeout)
+    }
+}
+
+fn counting_client() -> (ZJHttpClient, Arc<Calls>) {
+    let calls = Arc::new(Calls::default());
+    let client = ZJHttpClient::builder()
+        .set_resolver(Arc::new(CountingResolver(calls.clone())) as Arc<dyn Resolver>)
+        .set_connector(Arc::new(CountingConnector(calls.clone())) as Arc<dyn Connector>)
+        .build()
+        .unwrap();
+    (client, calls)
+}
+
+fn counts(calls: &Calls) -> (usize, usize) {
+    (calls.resolves.load(Ordering::SeqCst), calls.dials.load(Ordering::SeqCst))
+}
+
+/// Reads one chunked request and answers with its body.
+async fn spawn_echo_server() -> u16 {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    task::spawn(async move {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let mut request = Vec::new();
+        let mut byte = [0u8; 1];
+        while !request.ends_with(b"0\r\n\r\n") && stream.read(&mut byte).await.unwrap() == 1 {
+            request.push(byte[0]);
+        }
+        let request = String::from_utf8(request).unwrap();
+        let body: String = request
+            .split("\r\n\r\n")
+            .nth(1)
+            .unwrap()
+            .split("\r\n")
+            .skip(1)
+            .step_by(2)
+            .collect();
+        let response = format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n{body}", body.len());
+        stream.write_all(response.as_bytes()).await.unwrap();
+    });
+    port
+}
+
+fn chunked_request(port: u16) -> Request {
+    let mut req = Request::new(methods::POST, format!("http://example.test:{port}/")).unwrap();
+    req.use_chunked = true;
+    req
+}
+
+#[async_std::test]
+async fn prepared_then_dropped_does_no_network_work() {
+    let (client, calls) = counting_client();
+    let conn = client.connect_lazy(chunked_request(1)).unwrap();
+    assert!(matches!(conn.state(), ConnectionState::Prepared));
+    assert!(!conn.is_connected());
+    drop(conn);
+    assert_eq!(counts(&calls), (0, 0));
+}
+
+#[async_std::test]
+async fn first_write_resolves_and_dials() {
+    let port = spawn_echo_server().await;
+    let (client, calls) = counting_client();
+    let mut conn = client.connect_lazy(chunked_request(port)).unwrap();
+    assert_eq!(counts(&calls), (0, 0));
+
+    conn.write_body_chunk("hello ").await.unwrap();
+    assert!(matches!(conn.state(), ConnectionState::SendingBody { .. }));
+    assert_eq!(counts(&calls), (1, 1));
+    conn.write_body_chunk("world").await.unwrap();
+    let mut resp = conn.finish().await.unwrap();
+    assert_eq!(resp.body_string().await.unwrap(), "hello world");
+    assert_eq!(counts(&calls), (1, 1));
+}
+
+#[async_std::test]
+async fn ensure_connected_dials_before_any_write() {
+    let port = spawn_echo_server().await;
+    let (client, calls) = counting_client();
+    let mut conn = client.connect_lazy(chunked_request(port)).unwrap();
+    conn.ensure_connected().await.unwrap();
+    assert!(matches!(conn.state(), ConnectionState::Connected { .. }));
+    assert_eq!(counts(&calls), (1, 1));
+
+    conn.write_body_chunk("hi").await.unwrap();
+    let mut resp = conn.finish().await.unwrap();
+    assert_eq!(resp.body_string().await.unwrap(), "hi");
+    assert_eq!(counts(&calls), (1, 1));
+}
+
+#[async_std::test]
+async fn failed_dial_leaves_the_handle_failed() {
+    let port = {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        listener.local_addr().unwrap().port()
+    };
+    let (client, _) = counting_client();
+    let mut conn = client.connect_lazy(chunked_request(port)).unwrap();
+    assert!(conn.write_body_chunk("x").await.is_err());
+    assert!(matches!(conn.state(), ConnectionState::Failed));
+    let err = conn.ensure_connected().await.unwrap_err();
+    assert!(err.to_string().contains("failed earlier"), "{err}");
+}
+
+#[async_std::test]
+async fn eager_connect_and_content_length_framing() {
+    let (client, calls) = counting_client();
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let req = Request::new(methods::PUT, format!("http://example.test:{port}/")).unwrap().set_content_length(4);
+    let mut conn = client.connect(req).await.unwrap();
+    assert_eq!(counts(&calls), (1, 1));
+
+    let err = conn.write_body_chunk("too long").await.unwrap_err();
+    assert!(err.to_string().contains("exceeds the remaining 4 bytes"), "{err}");
+    conn.write_body_chunk("ab").await.unwrap();
+    let err = conn.finish().await.err().expect("body is 2 bytes short");
+    assert!(err.to_string().contains("2 bytes short"), "{err}");
+}
+
+#[test]
+fn body_must_go_through_the_handle() {
+    let (client, _) = counting_client();
+    let req = Request::new(methods::POST, "http://example.test/").unwrap().set_body_string("x");
+    assert!(client.connect_lazy(req).is_err());
+}
@@ -74,12 +74,11 @@ async fn handle_conn(conn_no: u64, mut stream: TcpStream, style: RespStyle) {
 
         // Read body
         let mut body = vec![0u8; content_length];
-        if content_length > 0 {
-            if let Err(e) = stream.read_exact(&mut body).await {
+        if content_length > 0
+            && let Err(e) = stream.read_exact(&mut body).await {
                 eprintln!("[server] conn#{conn_no} read body err: {e}");
                 return;
             }
-        }
 
         let resp_body = br#"{"ok":true,"echoed":true}"#;
         let (head, do_close) = match style {
@@ -0,0 +1,97 @@
+mod common;
+
+use zjhttpc::client::ZJHttpClient;
+use zjhttpc::cookie::{Cookie, CookieJar, StoredCookie};
+use zjhttpc::requestx::Request;
+use zjhttpc::methods;
+
+use common::MockServer;
+
+/// Plain HTTP server answering each request with the Cookie header it got
+/// (empty without one) as the body. Some paths also set cookies:
+/// `/login` a session, `/account/prefs` a cookie scoped to `/account`,
+/// `/logout` deletes the session and `/hop` redirects to `/` while setting
+/// one.
+async fn spawn_server() -> u16 {
+    MockServer::new(|req| {
+        let cookie = req.header("cookie").unwrap_or_default();
+        let (status, set_cookies) = match req.path() {
+            "/login" => ("200 OK", "Set-Cookie: session=abc; Path=/; HttpOnly\r\n"),
+            "/account/prefs" => ("200 OK", "Set-Cookie: theme=dark\r\nSet-Cookie: token=x; Secure\r\n"),
+            "/logout" => ("200 OK", "Set-Cookie: session=; Path=/; Max-Age=0\r\n"),
+            "/hop" => ("302 Found", "Set-Cookie: hop=1\r\nLocation: /\r\n"),
+            _ => ("200 OK", ""),
+        };
+        format!("HTTP/1.1 {status}\r\n{set_cookies}Content-Length: {}\r\n\r\n{cookie}", cookie.len())
+    })
+    .spawn()
+    .await
+    .port
+}
+
+async fn get(client: &ZJHttpClient, port: u16, path: &str) -> String {
+    let mut req = Request::new(methods::GET, format!("http://127.0.0.1:{port}{path}")).unwrap();
+    get_with(client, &mut req).await
+}
+
+async fn get_with(client: &ZJHttpClient, req: &mut Request) -> String {
+    let mut resp = Box::pin(client.send(req)).await.unwrap();
+    resp.body_string().await.unwrap()
+}
+
+fn client() -> ZJHttpClient {
+    ZJHttpClient::builder().cookie_store(true).build().unwrap()
+}
+
+#[async_std::test]
+async fn cookies_follow_their_path_and_expire() {
+    let port = spawn_server().await;
+    let client = client();
+    assert_eq!(get(&client, port, "/").await, "");
+    get(&client, port, "/login").await;
+    assert_eq!(get(&client, port, "/").await, "session=abc");
+
+    get(&client, port, "/account/prefs").await;
+    assert_eq!(get(&client, port, "/account/other").await, "theme=dark; session=abc");
+    // Outside /account, and the Secure cookie over plain HTTP was refused.
+    assert_eq!(get(&client, port, "/").await, "session=abc");
+    assert!(client.cookie_jar.as_ref().unwrap().cookies().iter().all(|c| c.name != "token"));
+
+    get(&client, port, "/logout").await;
+    assert_eq!(get(&client, port, "/account").await, "theme=dark");
+}
+
+#[async_std::test]
+async fn clones_share_the_jar_and_redirects_use_it() {
+    let port = spawn_server().await;
+    let client = ZJHttpClient::builder().cookie_store(true).set_max_redirects(1).build().unwrap();
+    // The cookie set on the 302 goes with the request it redirects to.
+    assert_eq!(get(&client, port, "/hop").await, "hop=1");
+    assert_eq!(get(&client.clone(), port, "/").await, "hop=1");
+    assert_eq!(get(&client.fork_isolated(), port, "/").await, "hop=1");
+}
+
+#[async_std::test]
+async fn preseeded_jar_and_explicit_cookie_header() {
+    let port = spawn_server().await;
+    let jar = CookieJar::new();
+    jar.insert(StoredCookie::new("seed", "1", "127.0.0.1"));
+    let client = ZJHttpClient::builder().set_cookie_jar(jar.clone()).build().unwrap();
+    assert_eq!(get(&client, port, "/login").await, "seed=1");
+    assert_eq!(jar.cookies().len(), 2, "the caller's handle sees stored cookies");
+
+    // A Cookie header set on the request replaces the jar's.
+    let mut req = Request::new(methods::GET, format!("http://127.0.0.1:{port}/"))
+        .unwrap()
+        .set_cookie(&[Cookie::new("mine", "2")]);
+    assert_eq!(get_with(&client, &mut req).await, "mine=2");
+}
+
+#[async_std::test]
+async fn off_by_default() {
+    let port = spawn_server().await;
+    let client = ZJHttpClient::builder().build().unwrap();
+    assert!(client.cookie_jar.is_none());
+    get(&client, port, "/login").await;
+    assert_eq!(get(&client, port, "/").await, "");
+}
@@ -0,0 +1,138 @@
+mod common;
+
+use std::io::Write;
+use std::time::Duration;
+
+use serde::Deserialize;
+use zjhttpc::ZjhttpcError;
+use zjhttpc::client::ZJHttpClient;
+use zjhttpc::methods;
+use zjhttpc::requestx::Request;
+
+use common::{Mock, MockServer, Reply};
+
+/// `{"id":7,"name":"alice","bio":"compressible compressible ..."}` (552
+/// bytes), gzipped.
+const GZIP_JSON: &[u8] = b"\x1f\x8b\x08\x00\x00\x00\x00\x00\x02\x03\xab\x56\xca\x4c\x51\xb2\x32\xd7\x51\xca\x4b\xcc\x4d\x55\xb2\x52\x4a\xcc\xc9\x4c\x4e\x55\xd2\x51\x4a\xca\xcc\x07\xf2\x92\xf3\x73\x0b\x8a\x52\x8b\x8b\x33\x93\x72\x52\x15\x46\x39\x23\x96\xa3\x54\x0b\x00\x0a\xda\x32\xaf\x28\x02\x00\x00";
+
+/// `hello deflate ` twenty times, zlib-wrapped.
+const DEFLATE_TEXT: &[u8] = b"\x78\x9c\xcb\x48\xcd\xc9\xc9\x57\x48\x49\x4d\xcb\x49\x2c\x49\x55\xc8\x18\xe5\x41\x79\x00\xc5\x84\x67\x35";
+
+/// Keep-alive server answering every request with `head` and, a moment
+/// later so that it is not read with the head, `body`. Returns the port and
+/// the server, which records the requests it received.
+async fn spawn_server(head: &'static str, body: Vec<u8>) -> (u16, Mock) {
+    let head = head.replace("{len}", &body.len().to_string());
+    let server = MockServer::always(Reply::new(head).then_sleep(Duration::from_millis(10)).then(body)).spawn().await;
+    (server.port, server)
+}
+
+fn client() -> ZJHttpClient {
+    ZJHttpClient::builder().set_auto_decompress(true).build().unwrap()
+}
+
+async fn get(client: &ZJHttpClient, port: u16) -> zjhttpc::response::Response {
+    let mut req = Request::new(methods::GET, format!("http://127.0.0.1:{port}/")).unwrap();
+    Box::pin(client.send(&mut req)).await.unwrap()
+}
+
+#[derive(Debug, Deserialize)]
+struct User {
+    id: u32,
+    name: String,
+    bio: String,
+}
+
+#[async_std::test]
+async fn gzip_body_is_decoded_and_the_connection_reused() {
+    let (port, requests) = spawn_server(
+        "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Encoding: gzip\r\nContent-Length: {len}\r\n\r\n",
+        GZIP_JSON.to_vec(),
+    )
+    .await;
+    let client = client();
+
+    let mut resp = get(&client, port).await;
+    let user: User = resp.body_json_as().await.unwrap();
+    assert_eq!((user.id, user.name.as_str()), (7, "alice"));
+    assert_eq!(user.bio, "compressible ".repeat(40));
+    assert!(resp.is_body_read_complete());
+    drop(resp);
+
+    // The compressed length framed the body: the next response starts cleanly.
+    let mut resp = get(&client, port).await;
+    assert!(resp.reused_connection);
+    assert_eq!(resp.body_bytes().await.unwrap().len(), 552);
+
+    assert_eq!(requests.request().await.header("accept-encoding"), Some("gzip, deflate"));
+}
+
+#[async_std::test]
+async fn chunked_deflate_body_is_decoded() {
+    let mut wire = Vec::new();
+    for chunk in DEFLATE_TEXT.chunks(10) {
+        wire.extend_from_slice(format!("{:x}\r\n", chunk.len()).as_bytes());
+        wire.extend_from_slice(chunk);
+        wire.extend_from_slice(b"\r\n");
+    }
+    wire.extend_from_slice(b"0\r\n\r\n");
+    let (port, _) =
+        spawn_server("HTTP/1.1 200 OK\r\nContent-Encoding: deflate\r\nTransfer-Encoding: chunked\r\n\r\n", wire).await;
+
+    let mut resp = get(&client(), port).await;
+    assert_eq!(resp.body_string().await.unwrap(), "hello deflate ".repeat(20));
+}
+
+#[async_std::test]
+async fn corrupt_body_names_the_encoding() {
+    let (port, _) = spawn_server(
+        "HTTP/1.1 200 OK\r\nContent-Encoding: gzip\r\nContent-Length: {len}\r\n\r\n",
+        b"this was never gzip".to_vec(),
+    )
+    .await;
+
+    let mut resp = get(&client(), port).await;
+    let err = resp.body_string().await.expect_err("not gzip");
+    assert!(matches!(&err, ZjhttpcError::Decompress { encoding, .. } if encoding == "gzip"), "{err:?}");
+    assert!(err.to_string().starts_with("[ZJ-BODY-007] response body is not valid gzip"), "{err}");
+}
+
+#[async_std::test]
+async fn without_the_flag_bodies_are_left_alone() {
+    let (port, requests) =
+        spawn_server("HTTP/1.1 200 OK\r\nContent-Encoding: gzip\r\nContent-Length: {len}\r\n\r\n", GZIP_JSON.to_vec())
+            .await;
+    let client = ZJHttpClient::builder().build().unwrap();
+
+    let mut resp = get(&client, port).await;
+    assert_eq!(resp.body_bytes().await.unwrap(), GZIP_JSON);
+    assert_eq!(requests.request().await.header("accept-encoding"), None);
+}
+
+#[async_std::test]
+async fn decompression_bomb_fails_and_its_connection_is_closed() {
+    let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::best());
+    encoder.write_all(&vec![0; 4 * 1024 * 1024]).unwrap();
+    let bomb = encoder.finish().unwrap();
+    let (port, _) =
+        spawn_server("HTTP/1.1 200 OK\r\nContent-Encoding: gzip\r\nContent-Length: {len}\r\n\r\n", bomb.clone()).await;
+    let client = client();
+
+    let mut resp = get(&client, port).await;
+    let err = resp.body_bytes().await.expect_err("a bomb");
+    let ZjhttpcError::DecompressionBomb { wire_bytes, decoded_bytes, .. } = err else {
+        panic!("{err:?}");
+    };
+    assert!(wire_bytes <= bomb.len() as u64 && decoded_bytes > 100 * wire_bytes, "{wire_bytes} -> {decoded_bytes}");
+    assert!(err.to_string().starts_with("[ZJ-BODY-010]"), "{err}");
+    drop(resp);
+
+    // The whole bomb had arrived, so its connection went back to the pool
+    // before the ratio tripped; it is not reused all the same.
+    let resp = get(&client, port).await;
+    assert!(!resp.reused_connection);
+
+    // Without a ratio the same body decodes in full.
+    let client = ZJHttpClient::builder().set_auto_decompress(true).decompression_ratio(None).build().unwrap();
+    assert_eq!(get(&client, port).await.body_bytes().await.unwrap().len(), 4 * 1024 * 1024);
+}
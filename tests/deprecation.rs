@@ -0,0 +1,56 @@
+mod common;
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, UNIX_EPOCH};
+
+use zjhttpc::client::ZJHttpClient;
+use zjhttpc::methods;
+use zjhttpc::requestx::Request;
+
+use common::MockServer;
+
+/// Keep-alive server: `/old` answers with deprecation headers, anything else
+/// with a plain 200.
+async fn spawn_server() -> u16 {
+    MockServer::new(|req| {
+        if req.path() == "/old" {
+            "HTTP/1.1 200 OK\r\nDeprecation: @1688169599\r\nSunset: Wed, 11 Nov 2026 23:59:59 GMT\r\n\
+             Link: </docs/migrate>; rel=\"deprecation\"\r\nWarning: 299 - \"use /new\"\r\nContent-Length: 0\r\n\r\n"
+        } else {
+            "HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n"
+        }
+    })
+    .spawn()
+    .await
+    .port
+}
+
+#[async_std::test]
+async fn hook_sees_only_deprecated_responses() {
+    let port = spawn_server().await;
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let log = seen.clone();
+    let client = ZJHttpClient::builder()
+        .on_deprecated_response(move |resp| log.lock().unwrap().push((resp.url.clone(), resp.deprecation())))
+        .build()
+        .unwrap();
+
+    let mut req = Request::new(methods::GET, format!("http://127.0.0.1:{port}/new")).unwrap();
+    let resp = client.send(&mut req).await.unwrap();
+    assert!(resp.deprecation().is_none() && resp.warnings().is_empty());
+    drop(resp);
+
+    let mut req = Request::new(methods::GET, format!("http://127.0.0.1:{port}/old")).unwrap();
+    let resp = client.send(&mut req).await.unwrap();
+    let info = resp.deprecation().unwrap();
+    assert_eq!(info.deprecated_since, Some(UNIX_EPOCH + Duration::from_secs(1688169599)));
+    assert_eq!(info.sunset, Some(UNIX_EPOCH + Duration::from_secs(1794441599)));
+    assert_eq!(info.link.unwrap().as_str(), format!("http://127.0.0.1:{port}/docs/migrate"));
+    let warnings = resp.warnings();
+    assert_eq!((warnings[0].code, warnings[0].text.as_str()), (299, "use /new"));
+
+    let seen = seen.lock().unwrap();
+    assert_eq!(seen.len(), 1);
+    assert_eq!(seen[0].0.as_ref().unwrap().path(), "/old");
+    assert!(seen[0].1.is_some());
+}
@@ -0,0 +1,116 @@
+mod common;
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use async_std::net::TcpListener;
+use async_std::task;
+use futures::future::BoxFuture;
+use zjhttpc::client::{ZJHttpClient, ZJHttpClientBuilder};
+use zjhttpc::methods;
+use zjhttpc::requestx::Request;
+use zjhttpc::resolver::Resolver;
+
+use common::{MockServer, Reply};
+
+/// Answers every lookup with `127.0.0.1` and counts them.
+#[derive(Default)]
+struct CountingResolver(AtomicUsize);
+
+impl Resolver for CountingResolver {
+    fn resolve<'a>(&'a self, _host: &'a str, port: u16) -> BoxFuture<'a, zjhttpc::Result<Vec<SocketAddr>>> {
+        self.0.fetch_add(1, Ordering::SeqCst);
+        Box::pin(async move { Ok(vec![SocketAddr::from(([127, 0, 0, 1], port))]) })
+    }
+}
+
+/// Answers every request with a 200 and closes the connection.
+async fn spawn_server() -> u16 {
+    MockServer::always(Reply::new("HTTP/1.1 200 OK\r\nConnection: close\r\nContent-Length: 0\r\n\r\n").then_close())
+        .spawn()
+        .await
+        .port
+}
+
+fn client(resolver: &Arc<CountingResolver>, configure: impl FnOnce(&mut ZJHttpClientBuilder)) -> ZJHttpClient {
+    let mut builder = ZJHttpClient::builder();
+    builder.set_resolver(resolver.clone() as Arc<dyn Resolver>);
+    configure(&mut builder);
+    builder.build().unwrap()
+}
+
+async fn get(client: &ZJHttpClient, port: u16) -> zjhttpc::Result<u16> {
+    let mut req = Request::new(methods::GET, format!("http://api.test:{port}/")).unwrap();
+    Box::pin(client.send(&mut req)).await.map(|resp| resp.status_code)
+}
+
+#[async_std::test]
+async fn repeated_requests_resolve_once() {
+    let port = spawn_server().await;
+    let resolver = Arc::new(CountingResolver::default());
+    let client = client(&resolver, |_| {});
+    for _ in 0..3 {
+        assert_eq!(get(&client, port).await.unwrap(), 200);
+    }
+    assert_eq!(resolver.0.load(Ordering::SeqCst), 1);
+    assert_eq!(client.dns_cache.as_ref().unwrap().len(), 1);
+}
+
+#[async_std::test]
+async fn fresh_connection_skips_the_cache_and_refreshes_it() {
+    let port = spawn_server().await;
+    let resolver = Arc::new(CountingResolver::default());
+    let client = client(&resolver, |_| {});
+    assert_eq!(get(&client, port).await.unwrap(), 200);
+
+    let mut fresh = Request::new(methods::GET, format!("http://api.test:{port}/")).unwrap().set_fresh_connection(true);
+    assert_eq!(Box::pin(client.send(&mut fresh)).await.unwrap().status_code, 200);
+    assert_eq!(resolver.0.load(Ordering::SeqCst), 2);
+
+    assert_eq!(get(&client, port).await.unwrap(), 200);
+    assert_eq!(resolver.0.load(Ordering::SeqCst), 2);
+    assert_eq!(client.dns_cache.as_ref().unwrap().len(), 1);
+}
+
+#[async_std::test]
+async fn entries_expire_after_the_ttl() {
+    let port = spawn_server().await;
+    let resolver = Arc::new(CountingResolver::default());
+    let client = client(&resolver, |b| {
+        b.dns_cache_ttl(Some(Duration::from_millis(100)));
+    });
+    get(&client, port).await.unwrap();
+    get(&client, port).await.unwrap();
+    assert_eq!(resolver.0.load(Ordering::SeqCst), 1);
+    task::sleep(Duration::from_millis(150)).await;
+    get(&client, port).await.unwrap();
+    assert_eq!(resolver.0.load(Ordering::SeqCst), 2);
+}
+
+#[async_std::test]
+async fn failed_connect_invalidates_the_entry() {
+    let port = {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        listener.local_addr().unwrap().port()
+    };
+    let resolver = Arc::new(CountingResolver::default());
+    let client = client(&resolver, |_| {});
+    assert!(get(&client, port).await.is_err());
+    assert!(client.dns_cache.as_ref().unwrap().is_empty());
+    assert!(get(&client, port).await.is_err());
+    assert_eq!(resolver.0.load(Ordering::SeqCst), 2);
+}
+
+#[async_std::test]
+async fn disabled_cache_resolves_every_time() {
+    let port = spawn_server().await;
+    let resolver = Arc::new(CountingResolver::default());
+    let client = client(&resolver, |b| {
+        b.dns_cache_ttl(None);
+    });
+    get(&client, port).await.unwrap();
+    get(&client, port).await.unwrap();
+    assert_eq!(resolver.0.load(Ordering::SeqCst), 2);
+}
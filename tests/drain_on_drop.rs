@@ -0,0 +1,126 @@
+mod common;
+
+use std::time::{Duration, Instant};
+
+use async_std::task;
+use zjhttpc::client::ZJHttpClient;
+use zjhttpc::methods;
+use zjhttpc::requestx::Request;
+
+use common::{Mock, MockServer, Reply};
+
+/// Keep-alive server that answers every request with `head`, then after a
+/// short pause `body`, so the body is still on the wire when the response
+/// is handed out. Returns the port and the server, which counts the
+/// connections accepted.
+async fn spawn_server(head: &'static str, body: Vec<u8>) -> (u16, Mock) {
+    let server = MockServer::always(Reply::new(head).then_sleep(Duration::from_millis(20)).then(body)).spawn().await;
+    (server.port, server)
+}
+
+async fn get(client: &ZJHttpClient, port: u16) -> zjhttpc::response::Response {
+    let mut req = Request::new(methods::GET, format!("http://127.0.0.1:{port}/")).unwrap();
+    Box::pin(client.send(&mut req)).await.unwrap()
+}
+
+async fn wait_for_drains(client: &ZJHttpClient) {
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while client.draining_connections() > 0 {
+        assert!(Instant::now() < deadline, "drains did not finish");
+        task::sleep(Duration::from_millis(10)).await;
+    }
+}
+
+#[async_std::test]
+async fn dropped_unread_responses_are_drained_into_the_pool() {
+    let (port, dials) = spawn_server("HTTP/1.1 200 OK\r\nContent-Length: 1000\r\n\r\n", vec![b'x'; 1000]).await;
+    let client = ZJHttpClient::builder().build().unwrap();
+
+    let mut responses = Vec::new();
+    for _ in 0..100 {
+        responses.push(get(&client, port).await);
+    }
+    assert_eq!(dials.accepts(), 100);
+
+    let started = Instant::now();
+    drop(responses);
+    assert!(started.elapsed() < Duration::from_millis(200), "dropping must not wait for the drains");
+    assert!(client.draining_connections() <= 16, "drains past the limit close instead");
+
+    wait_for_drains(&client).await;
+    let pooled = client.idle_connections();
+    assert!(pooled > 0, "drained connections are pooled");
+    assert!(pooled <= 30, "{pooled} pooled past the per-host limit");
+    assert_eq!(client.in_flight_connections(), 0);
+
+    let mut resp = get(&client, port).await;
+    assert!(resp.reused_connection);
+    assert_eq!(resp.body_bytes().await.unwrap().len(), 1000);
+    assert_eq!(dials.accepts(), 100);
+}
+
+#[async_std::test]
+async fn chunked_body_is_drained() {
+    let (port, dials) = spawn_server(
+        "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n",
+        b"5\r\nhello\r\n6\r\n world\r\n0\r\n\r\n".to_vec(),
+    )
+    .await;
+    let client = ZJHttpClient::builder().build().unwrap();
+
+    drop(get(&client, port).await);
+    wait_for_drains(&client).await;
+    assert_eq!(client.idle_connections(), 1);
+
+    let mut resp = get(&client, port).await;
+    assert!(resp.reused_connection);
+    assert_eq!(resp.body_string().await.unwrap(), "hello world");
+    assert_eq!(dials.accepts(), 1);
+}
+
+#[async_std::test]
+async fn large_body_closes_the_connection() {
+    let (port, _) = spawn_server("HTTP/1.1 200 OK\r\nContent-Length: 1000000\r\n\r\n", vec![b'x'; 1_000_000]).await;
+    let client = ZJHttpClient::builder().build().unwrap();
+
+    drop(get(&client, port).await);
+    assert_eq!(client.draining_connections(), 0);
+    assert_eq!((client.idle_connections(), client.in_flight_connections()), (0, 0));
+}
+
+#[async_std::test]
+async fn peeked_body_is_drained() {
+    let (port, _) = spawn_server("HTTP/1.1 200 OK\r\nContent-Length: 1000\r\n\r\n", vec![b'x'; 1000]).await;
+    let client = ZJHttpClient::builder().build().unwrap();
+
+    let mut resp = get(&client, port).await;
+    assert_eq!(resp.peek_body(4).await.unwrap(), b"xxxx");
+    drop(resp);
+    wait_for_drains(&client).await;
+    assert_eq!(client.idle_connections(), 1);
+}
+
+#[async_std::test]
+async fn body_read_with_the_head_is_pooled_on_drop() {
+    let (port, dials) = spawn_server("HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello", Vec::new()).await;
+    // Bodies that never arrive keep every drain slot taken, so a response
+    // that needed a drain would be closed instead.
+    let (stalled, _) = spawn_server("HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\n", Vec::new()).await;
+    let client = ZJHttpClient::builder().build().unwrap();
+
+    let resp = get(&client, port).await;
+    let mut stalled_responses = Vec::new();
+    for _ in 0..16 {
+        stalled_responses.push(get(&client, stalled).await);
+    }
+    drop(stalled_responses);
+    assert_eq!(client.draining_connections(), 16);
+
+    drop(resp);
+    assert_eq!(client.idle_connections(), 1);
+
+    let mut resp = get(&client, port).await;
+    assert!(resp.reused_connection);
+    assert_eq!(resp.body_string().await.unwrap(), "hello");
+    assert_eq!(dials.accepts(), 1);
+}
@@ -0,0 +1,86 @@
+mod common;
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use zjhttpc::client::ZJHttpClient;
+use zjhttpc::methods;
+use zjhttpc::requestx::Request;
+
+use common::{MockServer, Reply};
+
+/// Reads a request head, then writes each part of `parts` after its delay
+/// and closes the connection.
+async fn spawn_server(parts: Vec<(Duration, &'static [u8])>) -> u16 {
+    let reply = parts.into_iter().fold(Reply::default(), |reply, (delay, data)| reply.then_sleep(delay).then(data));
+    MockServer::always(reply.then_close()).spawn().await.port
+}
+
+const EARLY_HINTS: &[u8] = b"HTTP/1.1 103 Early Hints\r\n\
+Link: </style.css>; rel=preload; as=style\r\n\
+Link: </app.js>; rel=preload; as=script, </next>; rel=prefetch\r\n\r\n";
+const FINAL: &[u8] = b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok";
+
+#[async_std::test]
+async fn early_hints_handler_fires_before_final_response() {
+    let delay = Duration::from_millis(300);
+    let port = spawn_server(vec![(Duration::ZERO, EARLY_HINTS), (delay, FINAL)]).await;
+
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let seen_in_handler = seen.clone();
+    let client = ZJHttpClient::builder().build().unwrap();
+    let mut req = Request::new(methods::GET, format!("http://127.0.0.1:{port}/"))
+        .unwrap()
+        .set_early_hints_handler(Box::new(move |hints| {
+            let uris: Vec<String> = hints.preloads().map(|l| l.uri.clone()).collect();
+            seen_in_handler.lock().unwrap().push((Instant::now(), uris, hints.links.len()));
+        }));
+
+    let mut resp = client.send(&mut req).await.unwrap();
+    let resolved_at = Instant::now();
+    assert_eq!(resp.status_code(), 200);
+    assert_eq!(resp.body_string().await.unwrap(), "ok");
+
+    let seen = seen.lock().unwrap();
+    assert_eq!(seen.len(), 1);
+    let (fired_at, preloads, link_count) = &seen[0];
+    assert_eq!(preloads, &["/style.css", "/app.js"]);
+    assert_eq!(*link_count, 3);
+    assert!(resolved_at.duration_since(*fired_at) >= delay / 2, "handler fired too late");
+}
+
+#[async_std::test]
+async fn interim_and_final_heads_in_one_packet() {
+    let mut packet = EARLY_HINTS.to_vec();
+    packet.extend_from_slice(FINAL);
+    let packet: &'static [u8] = packet.leak();
+    let port = spawn_server(vec![(Duration::ZERO, packet)]).await;
+
+    let count = Arc::new(Mutex::new(0));
+    let count_in_handler = count.clone();
+    let client = ZJHttpClient::builder().build().unwrap();
+    let mut req = Request::new(methods::GET, format!("http://127.0.0.1:{port}/"))
+        .unwrap()
+        .set_early_hints_handler(Box::new(move |_| *count_in_handler.lock().unwrap() += 1));
+    let mut resp = client.send(&mut req).await.unwrap();
+    assert_eq!(resp.status_code(), 200);
+    assert_eq!(resp.body_string().await.unwrap(), "ok");
+    assert_eq!(*count.lock().unwrap(), 1);
+}
+
+#[async_std::test]
+async fn interim_responses_are_skipped_without_handler() {
+    let port = spawn_server(vec![
+        (Duration::ZERO, b"HTTP/1.1 100 Continue\r\n\r\n"),
+        (Duration::ZERO, EARLY_HINTS),
+        (Duration::ZERO, FINAL),
+    ])
+    .await;
+    let client = ZJHttpClient::builder().build().unwrap();
+    let mut req = Request::new(methods::GET, format!("http://127.0.0.1:{port}/")).unwrap();
+    let mut resp = client.send(&mut req).await.unwrap();
+    assert_eq!(resp.status_code(), 200);
+    let statuses: Vec<u16> = resp.interim_headers().iter().map(|i| i.status).collect();
+    assert_eq!(statuses, [100, 103]);
+    assert_eq!(resp.body_string().await.unwrap(), "ok");
+}
@@ -0,0 +1,81 @@
+mod common;
+
+use async_std::io::{ReadExt, WriteExt};
+use async_std::net::TcpListener;
+use async_std::task;
+use zjhttpc::ZjhttpcError;
+use zjhttpc::client::ZJHttpClient;
+use zjhttpc::error::RequestPhase;
+use zjhttpc::methods;
+use zjhttpc::requestx::Request;
+
+use common::read_head;
+
+const BODY_LEN: usize = 32 * 1024 * 1024;
+
+/// Reads the request head and a first piece of the body, then writes `reply`
+/// (if any) and closes the connection with the rest of the body unread.
+async fn spawn_rejecting_server(reply: Option<&'static str>) -> u16 {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    task::spawn(async move {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        read_head(&mut stream).await;
+        let mut chunk = [0u8; 4096];
+        let _ = stream.read(&mut chunk).await;
+        if let Some(reply) = reply {
+            stream.write_all(reply.as_bytes()).await.unwrap();
+        }
+        stream.shutdown(std::net::Shutdown::Both).unwrap();
+    });
+    port
+}
+
+fn upload(port: u16) -> Request {
+    Request::new(methods::PUT, format!("http://127.0.0.1:{port}/upload"))
+        .unwrap()
+        .set_body_slice(vec![b'x'; BODY_LEN])
+}
+
+#[async_std::test]
+async fn early_413_is_returned_instead_of_the_write_error() {
+    let reply = "HTTP/1.1 413 Content Too Large\r\nConnection: close\r\nContent-Length: 13\r\n\r\nbody too big!";
+    let port = spawn_rejecting_server(Some(reply)).await;
+    let client = ZJHttpClient::builder().build().unwrap();
+    let mut resp = client.send(&mut upload(port)).await.unwrap();
+    assert_eq!(resp.status_code, 413);
+    assert!(resp.request_body_truncated);
+    assert_eq!(resp.body_string().await.unwrap(), "body too big!");
+    drop(resp);
+    assert_eq!(client.idle_connections(), 0);
+}
+
+#[async_std::test]
+async fn without_a_response_the_write_error_stays() {
+    let port = spawn_rejecting_server(None).await;
+    let client = ZJHttpClient::builder().build().unwrap();
+    let err = client.send(&mut upload(port)).await.err().expect("nothing was answered");
+    assert!(matches!(err, ZjhttpcError::Io { .. }), "{err}");
+    assert_eq!(err.phase(), Some(RequestPhase::SendBody));
+}
+
+#[async_std::test]
+async fn complete_uploads_are_not_flagged() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    task::spawn(async move {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let mut buf = [0u8; 64];
+        let mut request = Vec::new();
+        while !request.ends_with(b"\r\n\r\nhi") {
+            let n = stream.read(&mut buf).await.unwrap();
+            request.extend_from_slice(&buf[..n]);
+        }
+        stream.write_all(b"HTTP/1.1 201 Created\r\nContent-Length: 0\r\n\r\n").await.unwrap();
+    });
+    let client = ZJHttpClient::builder().build().unwrap();
+    let mut req = Request::new(methods::PUT, format!("http://127.0.0.1:{port}/")).unwrap().set_body_string("hi");
+    let resp = client.send(&mut req).await.unwrap();
+    assert_eq!(resp.status_code, 201);
+    assert!(!resp.request_body_truncated);
+}
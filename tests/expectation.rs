@@ -0,0 +1,142 @@
+mod common;
+
+use async_std::channel::{Receiver, unbounded};
+use async_std::io::{ReadExt, WriteExt};
+use async_std::net::TcpListener;
+use async_std::task;
+use std::time::Duration;
+use zjhttpc::client::ZJHttpClient;
+use zjhttpc::methods;
+use zjhttpc::requestx::{Expectation, Request};
+
+use common::read_head;
+
+struct Received {
+    head: String,
+    /// Body bytes arrived before the server sent `100 Continue`.
+    body_before_continue: bool,
+    body: Vec<u8>,
+}
+
+/// Answers one request. Heads asking for 100-continue get it only after the
+/// server has checked, for a while, whether the body arrives unasked.
+async fn spawn_server() -> (u16, Receiver<Received>) {
+    spawn_server_with(b"HTTP/1.1 100 Continue\r\n\r\n").await
+}
+
+/// Like [`spawn_server`], answering 100-continue with `continue_head`.
+async fn spawn_server_with(continue_head: &'static [u8]) -> (u16, Receiver<Received>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let (tx, rx) = unbounded();
+    task::spawn(async move {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let head = read_head(&mut stream).await.unwrap();
+        let lower = head.to_ascii_lowercase();
+        let len: usize = lower
+            .lines()
+            .find_map(|l| l.strip_prefix("content-length: "))
+            .map(|v| v.trim().parse().unwrap())
+            .unwrap_or(0);
+        let mut body = Vec::new();
+        let mut body_before_continue = false;
+        if lower.contains("\r\nexpect: 100-continue\r\n") {
+            let mut byte = [0u8; 1];
+            let early = async_std::io::timeout(Duration::from_millis(300), stream.read(&mut byte)).await;
+            if let Ok(1) = early {
+                body_before_continue = true;
+                body.push(byte[0]);
+            }
+            stream.write_all(continue_head).await.unwrap();
+        }
+        let mut rest = vec![0u8; len - body.len()];
+        stream.read_exact(&mut rest).await.unwrap();
+        body.extend_from_slice(&rest);
+        stream.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n").await.unwrap();
+        tx.send(Received { head, body_before_continue, body }).await.unwrap();
+    });
+    (port, rx)
+}
+
+fn upload(port: u16) -> Request {
+    Request::new(methods::PUT, format!("http://127.0.0.1:{port}/object"))
+        .unwrap()
+        .set_body_string("payload")
+}
+
+fn expect_lines(head: &str) -> Vec<&str> {
+    head.split("\r\n").filter(|l| l.to_ascii_lowercase().starts_with("expect:")).collect()
+}
+
+#[async_std::test]
+async fn continue_waits_for_the_go_ahead() {
+    let (port, received) = spawn_server().await;
+    let client = ZJHttpClient::builder().build().unwrap();
+    let mut req = upload(port).set_expectation(Expectation::Continue);
+    assert_eq!(req.expectation(), Expectation::Continue);
+    client.send(&mut req).await.unwrap();
+    let received = received.recv().await.unwrap();
+    assert_eq!(expect_lines(&received.head), ["Expect: 100-continue"]);
+    assert!(!received.body_before_continue);
+    assert_eq!(received.body, b"payload");
+}
+
+#[async_std::test]
+async fn custom_expectation_is_sent_without_waiting() {
+    let (port, received) = spawn_server().await;
+    let client = ZJHttpClient::builder().build().unwrap();
+    let mut req = upload(port).set_expectation(Expectation::Custom("x-vendor-hold=5".to_string()));
+    client.send(&mut req).await.unwrap();
+    let received = received.recv().await.unwrap();
+    assert_eq!(expect_lines(&received.head), ["Expect: x-vendor-hold=5"]);
+    assert_eq!(received.body, b"payload");
+}
+
+#[async_std::test]
+async fn a_manual_expect_header_replaces_the_expectation() {
+    let (port, received) = spawn_server().await;
+    let client = ZJHttpClient::builder().build().unwrap();
+    let mut req = upload(port).put_expect_continue().set_header("Expect", "x-vendor-hold");
+    assert_eq!(req.expectation(), Expectation::Custom("x-vendor-hold".to_string()));
+    client.send(&mut req).await.unwrap();
+    let received = received.recv().await.unwrap();
+    assert_eq!(expect_lines(&received.head), ["Expect: x-vendor-hold"], "emitted once");
+    assert_eq!(received.body, b"payload");
+}
+
+#[async_std::test]
+async fn a_manual_100_continue_header_waits_too() {
+    let (port, received) = spawn_server().await;
+    let client = ZJHttpClient::builder().build().unwrap();
+    let mut req = upload(port).set_header("expect", "100-Continue");
+    assert_eq!(req.expectation(), Expectation::Continue);
+    client.send(&mut req).await.unwrap();
+    let received = received.recv().await.unwrap();
+    assert_eq!(expect_lines(&received.head).len(), 1);
+    assert!(!received.body_before_continue);
+}
+
+#[async_std::test]
+async fn continue_headers_are_kept_on_the_response() {
+    let (port, received) =
+        spawn_server_with(b"HTTP/1.1 103 Early Hints\r\nLink: </a.css>; rel=preload\r\n\r\nHTTP/1.1 100 Continue\r\nx-req-id: 7f3a\r\n\r\n").await;
+    let client = ZJHttpClient::builder().build().unwrap();
+    let mut req = upload(port).set_expectation(Expectation::Continue);
+    let resp = client.send(&mut req).await.unwrap();
+    assert_eq!(received.recv().await.unwrap().body, b"payload");
+
+    let interim = resp.interim_headers();
+    assert_eq!(interim.iter().map(|i| i.status).collect::<Vec<_>>(), [103, 100]);
+    assert_eq!(interim[1].headers.get("x-req-id"), Some("7f3a"));
+    assert_eq!(resp.status_code(), 200);
+}
+
+#[test]
+fn expectation_none_removes_the_header() {
+    let req = Request::new(methods::PUT, "http://example.com/")
+        .unwrap()
+        .put_expect_continue()
+        .set_expectation(Expectation::None);
+    assert_eq!(req.expectation(), Expectation::None);
+    assert!(req.header_one("expect").is_none());
+}
@@ -0,0 +1,117 @@
+mod common;
+
+use zjhttpc::ZjhttpcError;
+use zjhttpc::content_type::SniffedType;
+use zjhttpc::client::ZJHttpClient;
+use zjhttpc::methods;
+use zjhttpc::requestx::Request;
+
+use common::{Mock, MockServer};
+
+/// Answers requests with `response`; the server records the request heads.
+async fn spawn_server(response: String) -> (u16, Mock) {
+    let server = MockServer::always(response).spawn().await;
+    (server.port, server)
+}
+
+fn response(status: &str, content_type: &str, body: &str) -> String {
+    format!(
+        "HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\n\r\n{body}",
+        body.len()
+    )
+}
+
+const ERROR_PAGE: &str = "<html><head><title>502 Bad Gateway</title></head><body>upstream timed out</body></html>";
+
+#[async_std::test]
+async fn html_error_page_to_json_expectation() {
+    let (port, head) = spawn_server(response("502 Bad Gateway", "text/html; charset=utf-8", ERROR_PAGE)).await;
+    let client = ZJHttpClient::builder().build().unwrap();
+    let mut req = Request::new(methods::GET, format!("http://127.0.0.1:{port}/api")).unwrap().expect_json();
+    let mut resp = client.send(&mut req).await.unwrap();
+
+    let err = resp.body_json().await.expect_err("HTML must not be parsed as JSON");
+    match &err {
+        ZjhttpcError::UnexpectedContentType { expected, actual, preview, .. } => {
+            assert_eq!(expected, "application/json");
+            assert_eq!(actual, "text/html; charset=utf-8");
+            assert_eq!(preview, ERROR_PAGE);
+        }
+        other => panic!("unexpected error: {other:?}"),
+    }
+    assert!(err.to_string().starts_with("[ZJ-BODY-004] expected application/json, server sent text/html"));
+    assert_eq!(head.request().await.header("accept"), Some("application/json"));
+}
+
+#[async_std::test]
+async fn json_suffix_media_type_is_accepted() {
+    let (port, _) = spawn_server(response("400 Bad Request", "application/problem+json", r#"{"title":"bad"}"#)).await;
+    let client = ZJHttpClient::builder().build().unwrap();
+    let mut req = Request::new(methods::GET, format!("http://127.0.0.1:{port}/")).unwrap().expect_json();
+    let mut resp = client.send(&mut req).await.unwrap();
+    resp.check_content_type().await.unwrap();
+    assert_eq!(resp.body_json().await.unwrap()["title"], "bad");
+}
+
+#[async_std::test]
+async fn without_expectation_json_parsing_reports_the_parse_error() {
+    let (port, _) = spawn_server(response("502 Bad Gateway", "text/html", ERROR_PAGE)).await;
+    let client = ZJHttpClient::builder().build().unwrap();
+    let mut req = Request::new(methods::GET, format!("http://127.0.0.1:{port}/")).unwrap();
+    let mut resp = client.send(&mut req).await.unwrap();
+    let err = resp.body_json().await.expect_err("HTML is not JSON");
+    assert!(matches!(err, ZjhttpcError::JsonParsing { .. }), "{err:?}");
+}
+
+#[async_std::test]
+async fn text_expectation_rejects_binary() {
+    let (port, _) = spawn_server(response("200 OK", "application/octet-stream", "\u{1}\u{2}")).await;
+    let client = ZJHttpClient::builder().build().unwrap();
+    let mut req = Request::new(methods::GET, format!("http://127.0.0.1:{port}/")).unwrap().expect_text();
+    let mut resp = client.send(&mut req).await.unwrap();
+    let err = resp.check_content_type().await.expect_err("binary is not text");
+    assert!(matches!(err, ZjhttpcError::UnexpectedContentType { .. }), "{err:?}");
+}
+
+#[async_std::test]
+async fn lenient_json_expectation_sniffs_mislabelled_body() {
+    let body = r#"  {"ok":true}"#;
+    let (port, _) = spawn_server(response("200 OK", "text/plain", body)).await;
+    let client = ZJHttpClient::builder().build().unwrap();
+    let mut req = Request::new(methods::GET, format!("http://127.0.0.1:{port}/"))
+        .unwrap()
+        .expect_json()
+        .set_lenient_content_type(true);
+    let mut resp = client.send(&mut req).await.unwrap();
+    assert_eq!(resp.sniff_content_type().await.unwrap(), SniffedType::Json);
+    assert_eq!(resp.body_json().await.unwrap()["ok"], true);
+}
+
+#[async_std::test]
+async fn strict_json_expectation_ignores_the_sniffer() {
+    let (port, _) = spawn_server(response("200 OK", "text/plain", r#"{"ok":true}"#)).await;
+    let client = ZJHttpClient::builder().build().unwrap();
+    let mut req = Request::new(methods::GET, format!("http://127.0.0.1:{port}/")).unwrap().expect_json();
+    let mut resp = client.send(&mut req).await.unwrap();
+    let err = resp.body_json().await.expect_err("text/plain is not JSON without lenient mode");
+    match err {
+        ZjhttpcError::UnexpectedContentType { preview, .. } => assert_eq!(preview, r#"{"ok":true}"#),
+        other => panic!("unexpected error: {other:?}"),
+    }
+}
+
+#[async_std::test]
+async fn lenient_json_expectation_still_rejects_html() {
+    let (port, _) = spawn_server(response("502 Bad Gateway", "text/plain", ERROR_PAGE)).await;
+    let client = ZJHttpClient::builder().build().unwrap();
+    let mut req = Request::new(methods::GET, format!("http://127.0.0.1:{port}/"))
+        .unwrap()
+        .expect_json()
+        .set_lenient_content_type(true);
+    let mut resp = client.send(&mut req).await.unwrap();
+    let err = resp.body_json().await.expect_err("HTML is not JSON");
+    match err {
+        ZjhttpcError::UnexpectedContentType { preview, .. } => assert_eq!(preview, ERROR_PAGE),
+        other => panic!("unexpected error: {other:?}"),
+    }
+}
@@ -0,0 +1,95 @@
+mod common;
+
+use serde::{Deserialize, Serialize};
+use zjhttpc::ZjhttpcError;
+use zjhttpc::body::BodyMultipartForm;
+use zjhttpc::client::ZJHttpClient;
+use zjhttpc::methods;
+use zjhttpc::requestx::Request;
+
+use common::{Mock, MockServer};
+
+/// Answers requests with 204; the server records their heads and bodies.
+async fn spawn_server() -> (u16, Mock) {
+    let server = MockServer::always("HTTP/1.1 204 No Content\r\nContent-Length: 0\r\n\r\n").read_body().spawn().await;
+    (server.port, server)
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Signup {
+    name: String,
+    age: u32,
+    ratio: f64,
+    subscribed: bool,
+    nickname: Option<String>,
+    referrer: Option<String>,
+    tags: Vec<String>,
+}
+
+fn signup() -> Signup {
+    Signup {
+        name: "Alice & Bob".to_string(),
+        age: 42,
+        ratio: 0.5,
+        subscribed: true,
+        nickname: Some("a+b=c".to_string()),
+        referrer: None,
+        tags: vec!["rust".to_string(), "http client".to_string()],
+    }
+}
+
+#[async_std::test]
+async fn urlencoded_form_round_trips_through_serde_qs() {
+    let (port, received) = spawn_server().await;
+    let client = ZJHttpClient::builder().build().unwrap();
+    let mut req = Request::new(methods::POST, format!("http://127.0.0.1:{port}/signup"))
+        .unwrap()
+        .set_body_form_serde(&signup())
+        .unwrap();
+    client.send(&mut req).await.unwrap();
+
+    let received = received.request().await;
+    let (head, body) = (received.head, String::from_utf8(received.body).unwrap());
+    assert!(head.contains("Content-Type: application/x-www-form-urlencoded\r\n"));
+    assert!(!body.contains("referrer"), "None fields are left out: {body}");
+    assert!(body.contains("tags[0]=rust&tags[1]=http+client"), "{body}");
+    let decoded: Signup = serde_qs::from_str(&body).unwrap();
+    assert_eq!(decoded, signup());
+}
+
+#[async_std::test]
+async fn multipart_form_from_serialize_has_one_text_part_per_field() {
+    let (port, received) = spawn_server().await;
+    let client = ZJHttpClient::builder().build().unwrap();
+    let form = BodyMultipartForm::from_serialize(&signup()).unwrap();
+    assert_eq!(form.len(), 7);
+    let mut req = Request::new(methods::POST, format!("http://127.0.0.1:{port}/signup"))
+        .unwrap()
+        .set_body_multipart_form(form);
+    client.send(&mut req).await.unwrap();
+
+    let received = received.request().await;
+    let (head, body) = (received.head, String::from_utf8(received.body).unwrap());
+    assert!(head.contains("Content-Type: multipart/form-data; boundary="));
+    for (name, value) in [
+        ("name", "Alice & Bob"),
+        ("age", "42"),
+        ("ratio", "0.5"),
+        ("subscribed", "true"),
+        ("nickname", "a+b=c"),
+        ("tags[0]", "rust"),
+        ("tags[1]", "http client"),
+    ] {
+        let part = format!("Content-Disposition: form-data; name=\"{name}\"\r\n\r\n{value}\r\n");
+        assert!(body.contains(&part), "missing {part:?} in {body}");
+    }
+    assert!(!body.contains("referrer"));
+}
+
+#[test]
+fn unsupported_top_level_shapes_are_rejected() {
+    let req = Request::new(methods::POST, "http://example.com/").unwrap();
+    let err = req.set_body_form_serde(&42).err().expect("a number is not a form");
+    assert!(matches!(err, ZjhttpcError::QuerySerialize { .. }), "{err:?}");
+    assert!(BodyMultipartForm::from_serialize(&"plain string").is_err());
+}
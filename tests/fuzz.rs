@@ -0,0 +1,334 @@
+//! Malformed-input harness: every parser that sees server bytes is fed
+//! seeded random and mutated input, and must return (an error is fine)
+//! without panicking and without holding more than the input it was given.
+//!
+//! Each run is deterministic. `ZJHTTPC_FUZZ_SEED` picks another seed and
+//! `ZJHTTPC_FUZZ_ITERATIONS` lengthens the run; a panicking input is printed
+//! so it can be added to the regression lists below.
+
+use std::net::SocketAddr;
+use std::panic::{AssertUnwindSafe, catch_unwind};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, SystemTime};
+
+use async_std::io::ReadExt;
+use futures::future::BoxFuture;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use zjhttpc::byteranges::{parse_byteranges, parse_content_range};
+use zjhttpc::client::ZJHttpClient;
+use zjhttpc::connector::Connector;
+use zjhttpc::cookie::{Cookie, StoredCookie, parse_cookie_header, parse_set_cookie};
+use zjhttpc::json_seq::RecordSplitter;
+use zjhttpc::link::parse_link_header;
+use zjhttpc::methods;
+use zjhttpc::requestx::Request;
+use zjhttpc::response::ChunkedDecoderStream;
+use zjhttpc::stream::{BoxedStream, ChainRead, SliceRead};
+
+/// Fragments that tend to reach parser edge cases when spliced in.
+const TOKENS: &[&[u8]] = &[
+    b"\r\n",
+    b"\r\n\r\n",
+    b"\n",
+    b":",
+    b";",
+    b",",
+    b"=",
+    b"-",
+    b"--",
+    b"\"",
+    b"<",
+    b">",
+    b"0",
+    b"+",
+    b"*",
+    b"\x1e",
+    b"\xff\xfe",
+    b"\xc3",
+    b"ffffffffffffffff",
+    b"10000000000000000",
+    b"18446744073709551615",
+    b"18446744073709551616",
+    b"HTTP/1.1 ",
+    b"Content-Length: ",
+    b"Transfer-Encoding: chunked",
+    b"bytes ",
+];
+
+struct Fuzzer {
+    rng: StdRng,
+    iterations: usize,
+}
+
+impl Fuzzer {
+    fn new(iterations: usize) -> Self {
+        let seed = std::env::var("ZJHTTPC_FUZZ_SEED").ok().and_then(|s| s.parse().ok()).unwrap_or(0x5eed);
+        let scale = std::env::var("ZJHTTPC_FUZZ_ITERATIONS").ok().and_then(|s| s.parse().ok()).unwrap_or(1);
+        Fuzzer { rng: StdRng::seed_from_u64(seed), iterations: iterations * scale }
+    }
+
+    /// A corpus entry with a few random edits, or (one time in ten) pure noise.
+    fn input(&mut self, corpus: &[&[u8]]) -> Vec<u8> {
+        if self.rng.random_range(0..10) == 0 {
+            let len = self.rng.random_range(0..512);
+            return (0..len).map(|_| self.rng.random()).collect();
+        }
+        let mut data = corpus[self.rng.random_range(0..corpus.len())].to_vec();
+        for _ in 0..self.rng.random_range(1..8) {
+            let at = self.rng.random_range(0..=data.len());
+            match self.rng.random_range(0..6) {
+                0 if at < data.len() => data[at] = self.rng.random(),
+                1 => data.insert(at, self.rng.random()),
+                2 if at < data.len() => {
+                    let end = self.rng.random_range(at..=data.len());
+                    data.drain(at..end);
+                }
+                3 => {
+                    let token = TOKENS[self.rng.random_range(0..TOKENS.len())];
+                    data.splice(at..at, token.iter().copied());
+                }
+                4 => {
+                    let end = self.rng.random_range(at..=data.len());
+                    let copy = data[at..end].to_vec();
+                    data.splice(at..at, copy);
+                }
+                _ => data.truncate(at),
+            }
+        }
+        data
+    }
+
+    /// Run `target` on `iterations` inputs derived from `corpus`, then on
+    /// every `regressions` input as is.
+    fn run(&mut self, corpus: &[&[u8]], regressions: &[&[u8]], target: impl Fn(&[u8], u64)) {
+        let inputs: Vec<Vec<u8>> = (0..self.iterations)
+            .map(|_| self.input(corpus))
+            .chain(regressions.iter().map(|r| r.to_vec()))
+            .collect();
+        for input in inputs {
+            let read_seed = self.rng.random();
+            if catch_unwind(AssertUnwindSafe(|| target(&input, read_seed))).is_err() {
+                panic!("panicked on input {:?}", String::from_utf8_lossy(&input));
+            }
+        }
+    }
+}
+
+/// In-memory connection that serves `data` in reads of random size and
+/// discards writes.
+struct MemStream {
+    data: Vec<u8>,
+    pos: usize,
+    rng: StdRng,
+}
+
+impl MemStream {
+    fn new(data: &[u8], seed: u64) -> Self {
+        MemStream { data: data.to_vec(), pos: 0, rng: StdRng::seed_from_u64(seed) }
+    }
+}
+
+impl async_std::io::Read for MemStream {
+    fn poll_read(mut self: Pin<&mut Self>, _cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<std::io::Result<usize>> {
+        let left = self.data.len() - self.pos;
+        if left == 0 || buf.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+        let n = self.rng.random_range(1..=left.min(buf.len()));
+        let pos = self.pos;
+        buf[..n].copy_from_slice(&self.data[pos..pos + n]);
+        self.pos += n;
+        Poll::Ready(Ok(n))
+    }
+}
+
+impl async_std::io::Write for MemStream {
+    fn poll_write(self: Pin<&mut Self>, _cx: &mut Context<'_>, buf: &[u8]) -> Poll<std::io::Result<usize>> {
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// Hands out the next server reply as the connection.
+#[derive(Default)]
+struct ReplayConnector {
+    next: Mutex<Option<(Vec<u8>, u64)>>,
+}
+
+impl Connector for ReplayConnector {
+    fn connect<'a>(&'a self, _: &'a str, _: SocketAddr, _: Duration) -> BoxFuture<'a, zjhttpc::Result<BoxedStream>> {
+        let (data, seed) = self.next.lock().unwrap().take().unwrap_or_default();
+        Box::pin(async move { Ok(Box::new(MemStream::new(&data, seed)) as BoxedStream) })
+    }
+}
+
+const RESPONSES: &[&[u8]] = &[
+    b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello",
+    b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhello\r\n0\r\n\r\n",
+    b"HTTP/1.0 200 OK\r\nContent-Type: text/plain\r\n\r\nuntil eof",
+    b"HTTP/1.1 100 Continue\r\n\r\nHTTP/1.1 103 Early Hints\r\nLink: </a.css>; rel=preload\r\n\r\nHTTP/1.1 204 No Content\r\n\r\n",
+    b"HTTP/1.1 206 Partial Content\r\nContent-Range: bytes 0-4/10\r\nContent-Length: 5\r\nSet-Cookie: a=b; Path=/\r\n\r\nhello",
+    b"HTTP/1.1 304 Not Modified\r\nAge: 100\r\nDate: Tue, 15 Nov 1994 08:12:31 GMT\r\n\r\n",
+];
+
+#[test]
+fn response_heads_and_bodies() {
+    let connector = Arc::new(ReplayConnector::default());
+    let client = ZJHttpClient::builder()
+        .set_connector(connector.clone() as Arc<dyn Connector>)
+        .build()
+        .unwrap();
+    Fuzzer::new(500).run(RESPONSES, &[], |input, seed| {
+        *connector.next.lock().unwrap() = Some((input.to_vec(), seed));
+        async_std::task::block_on(async {
+            let mut req = Request::new(methods::GET, "http://127.0.0.1:8080/").unwrap().set_fresh_connection(true);
+            if let Ok(mut resp) = Box::pin(client.send(&mut req)).await {
+                let _ = resp.date();
+                let _ = resp.age();
+                let _ = resp.read_cookies();
+                if let Ok(body) = resp.body_bytes().await {
+                    assert!(body.len() <= input.len(), "body larger than the reply");
+                }
+            }
+        });
+    });
+}
+
+const CHUNKED: &[&[u8]] = &[
+    b"5\r\nhello\r\n0\r\n\r\n",
+    b"1\r\na\r\n10\r\n0123456789abcdef\r\n0\r\n\r\n",
+    b"\r\n3\r\nabc\r\n0\r\n\r\n",
+    b"0\r\n\r\n",
+];
+
+const CHUNKED_REGRESSIONS: &[&[u8]] = &[
+    // Sizes that overflow u64 or carry a sign.
+    b"10000000000000000\r\nx\r\n0\r\n\r\n",
+    b"ffffffffffffffffffffffffffffffff\r\n",
+    b"+5\r\nhello\r\n0\r\n\r\n",
+    b"-1\r\n",
+    // Size fits but the data never comes.
+    b"ffffffffffffffff\r\nabc",
+];
+
+#[test]
+fn chunked_decoder() {
+    Fuzzer::new(3000).run(CHUNKED, CHUNKED_REGRESSIONS, |input, seed| {
+        let chain = ChainRead::new(SliceRead::new(&[]), Box::new(MemStream::new(input, seed)) as BoxedStream);
+        let mut decoder = ChunkedDecoderStream::new(chain);
+        let mut out = Vec::new();
+        let _ = async_std::task::block_on(decoder.read_to_end(&mut out));
+        assert!(out.len() <= input.len(), "decoded more than the input");
+    });
+}
+
+#[test]
+fn chunked_decoder_rejects_bad_sizes() {
+    for input in CHUNKED_REGRESSIONS.iter().take(4) {
+        let chain = ChainRead::new(SliceRead::new(&[]), Box::new(MemStream::new(input, 1)) as BoxedStream);
+        let mut out = Vec::new();
+        let err = async_std::task::block_on(ChunkedDecoderStream::new(chain).read_to_end(&mut out))
+            .expect_err("bad chunk size must fail");
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData, "{:?}", String::from_utf8_lossy(input));
+    }
+}
+
+#[test]
+fn chunked_decoder_bounds_its_line_buffers() {
+    for input in [
+        vec![b'a'; 1024 * 1024],
+        [b"0\r\n".as_slice(), &[b'x'; 1024 * 1024]].concat(),
+        [b"1\r\nx".as_slice(), &[b'y'; 1024 * 1024]].concat(),
+    ] {
+        let chain = ChainRead::new(SliceRead::new(&[]), Box::new(MemStream::new(&input, 1)) as BoxedStream);
+        let mut decoder = ChunkedDecoderStream::new(chain);
+        let mut out = Vec::new();
+        let err = async_std::task::block_on(decoder.read_to_end(&mut out)).expect_err("endless line must fail");
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        assert!(err.to_string().contains("longer than"), "{err}");
+    }
+}
+
+const BYTERANGES: &[&[u8]] = &[
+    b"--B\r\nContent-Range: bytes 0-4/10\r\n\r\nhello\r\n--B\r\nContent-Range: bytes 5-9/10\r\n\r\nworld\r\n--B--",
+    b"preamble\r\n--B\r\nContent-Type: text/plain\r\nContent-Range: bytes 0-0/*\r\n\r\nx\r\n--B--\r\n",
+];
+
+const BYTERANGES_REGRESSIONS: &[&[u8]] = &[
+    // The range length overflowed u64.
+    b"--B\r\nContent-Range: bytes 0-18446744073709551615/*\r\n\r\nx\r\n--B--",
+];
+
+#[test]
+fn multipart_byteranges() {
+    Fuzzer::new(3000).run(BYTERANGES, BYTERANGES_REGRESSIONS, |input, _| {
+        if let Ok(parts) = parse_byteranges(input, "B") {
+            assert!(parts.iter().map(|p| p.data.len()).sum::<usize>() <= input.len());
+        }
+        let _ = parse_content_range(&String::from_utf8_lossy(input));
+    });
+}
+
+const HEADER_VALUES: &[&[u8]] = &[
+    b"session=abc123; Path=/; HttpOnly",
+    b"=novalue; Secure",
+    b"id=1; Domain=.x.test; Path=/a; Max-Age=9223372036854775807; Expires=Wed, 21-Oct-2015 07:28:00 GMT",
+    b"</a.css>; rel=preload; as=style, <https://x.test/b>; rel=\"next\"; title=\"a\\\"b\"",
+    b"<>;;;=,,<",
+    b"bytes 0-99/1000",
+    b"bytes */1000",
+];
+
+#[test]
+fn cookie_link_and_range_headers() {
+    let url = zjhttpc::url::Url::parse("https://www.x.test/a/b").unwrap();
+    Fuzzer::new(3000).run(HEADER_VALUES, &[], |input, _| {
+        let value = String::from_utf8_lossy(input);
+        for cookie in Cookie::parse_from_set_cookie([value.as_ref()]) {
+            assert!(cookie.name.len() + cookie.value.len() <= value.len());
+        }
+        let _ = StoredCookie::parse(&value, &url, SystemTime::UNIX_EPOCH);
+        if let Ok(cookie) = parse_set_cookie(&value) {
+            let _ = cookie.to_header_value();
+        }
+        for (name, pair_value) in parse_cookie_header(&value) {
+            assert!(name.len() + pair_value.len() <= value.len());
+        }
+        let _ = parse_link_header(&value);
+        let _ = parse_content_range(&value);
+    });
+}
+
+const JSON_SEQ: &[&[u8]] = &[b"\x1e{\"a\":1}\n\x1e[1,2]\n", b"\x1e\x1e\n{\"partial\"", b"no rs at all\n"];
+
+#[test]
+fn json_seq_records() {
+    Fuzzer::new(3000).run(JSON_SEQ, &[], |input, seed| {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut splitter = RecordSplitter::new();
+        let mut total = 0;
+        let mut rest = input;
+        while !rest.is_empty() {
+            let (piece, tail) = rest.split_at(rng.random_range(1..=rest.len()));
+            splitter.push(piece);
+            rest = tail;
+            while let Some(record) = splitter.next_record(false) {
+                total += record.len();
+            }
+        }
+        while let Some(record) = splitter.next_record(true) {
+            total += record.len();
+        }
+        assert!(total <= input.len());
+    });
+}
@@ -0,0 +1,80 @@
+mod common;
+
+use std::time::Duration;
+
+use zjhttpc::client::ZJHttpClient;
+use zjhttpc::methods;
+use zjhttpc::requestx::Request;
+use zjhttpc::{RequestPhase, ZjhttpcError};
+
+use common::{MockServer, Reply};
+
+/// Reads a request head, then writes `pieces`, sleeping before each one
+/// for the paired delay.
+async fn spawn_server(pieces: Vec<(Duration, &'static [u8])>) -> u16 {
+    // Holds the connection open past every timeout under test.
+    let reply = pieces.into_iter().fold(Reply::default(), |reply, (delay, piece)| reply.then_sleep(delay).then(piece));
+    MockServer::always(reply.then_sleep(Duration::from_secs(5)).then_close()).spawn().await.port
+}
+
+fn request(port: u16) -> Request {
+    Request::new(methods::GET, format!("http://127.0.0.1:{port}/"))
+        .unwrap()
+        .set_first_byte_timeout(Duration::from_millis(300))
+        .set_header_complete_timeout(Duration::from_millis(300))
+}
+
+const SHORT: Duration = Duration::from_millis(20);
+const STALL: Duration = Duration::from_secs(2);
+
+#[async_std::test]
+async fn silent_server_trips_the_first_byte_timeout() {
+    let port = spawn_server(vec![(STALL, b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n")]).await;
+    let client = ZJHttpClient::builder().build().unwrap();
+    let err = client.send(&mut request(port)).await.err().expect("server stalls");
+    assert!(matches!(err, ZjhttpcError::FirstByteTimeout { duration, .. } if duration == Duration::from_millis(300)), "{err}");
+    assert_eq!(err.phase(), Some(RequestPhase::ReadHeader));
+}
+
+#[async_std::test]
+async fn stall_between_header_lines_trips_the_header_complete_timeout() {
+    let port = spawn_server(vec![
+        (SHORT, b"HTTP/1.1 200 OK\r\n"),
+        (SHORT, b"X-First: 1\r\n"),
+        (STALL, b"Content-Length: 0\r\n\r\n"),
+    ])
+    .await;
+    let client = ZJHttpClient::builder().build().unwrap();
+    let err = client.send(&mut request(port)).await.err().expect("headers stall");
+    assert!(matches!(err, ZjhttpcError::HeaderCompleteTimeout { .. }), "{err}");
+    assert_eq!(err.phase(), Some(RequestPhase::ReadHeader));
+}
+
+#[async_std::test]
+async fn dribbled_headers_within_both_limits_succeed() {
+    let step = Duration::from_millis(50);
+    let port = spawn_server(vec![
+        (step, b"HTTP/1.1 200 OK\r\n"),
+        (step, b"X-First: 1\r\n"),
+        (step, b"Content-Length: 2\r\n"),
+        (step, b"\r\nok"),
+    ])
+    .await;
+    let client = ZJHttpClient::builder().build().unwrap();
+    let mut resp = client.send(&mut request(port)).await.unwrap();
+    assert_eq!(resp.header_one("x-first"), Some("1"));
+    assert_eq!(resp.body_string().await.unwrap(), "ok");
+}
+
+#[async_std::test]
+async fn read_header_timeout_still_bounds_the_whole_head() {
+    let port = spawn_server(vec![(STALL, b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n")]).await;
+    let client = ZJHttpClient::builder()
+        .set_global_read_header_timeout(Duration::from_millis(100))
+        .set_global_first_byte_timeout(Duration::from_secs(1))
+        .build()
+        .unwrap();
+    let mut req = Request::new(methods::GET, format!("http://127.0.0.1:{port}/")).unwrap();
+    let err = client.send(&mut req).await.err().expect("server stalls");
+    assert!(matches!(err, ZjhttpcError::ReadHeaderTimeout { .. }), "{err}");
+}
@@ -0,0 +1,128 @@
+mod common;
+
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, SystemTime};
+
+use zjhttpc::client::ZJHttpClient;
+use zjhttpc::hsts::HstsStore;
+use zjhttpc::misc::TrustStorePem;
+use zjhttpc::requestx::Request;
+use zjhttpc::resolver::Resolver;
+use zjhttpc::{ZjhttpcError, methods};
+
+use common::{CA_A, Loopback, MockServer};
+
+/// TLS-only server (certificate for `localhost`) answering every request
+/// with `ok` and `sts` as its Strict-Transport-Security value, except
+/// `/downgrade`, which redirects to the same port over plain `http`.
+async fn spawn_sts_server(sts: &'static str) -> u16 {
+    let port = Arc::new(OnceLock::new());
+    let own_port = port.clone();
+    let server = MockServer::new(move |req| {
+        if req.path() == "/downgrade" {
+            let port = own_port.get().unwrap();
+            format!("HTTP/1.1 302 Found\r\nLocation: http://localhost:{port}/landed\r\nContent-Length: 0\r\n\r\n")
+        } else {
+            format!("HTTP/1.1 200 OK\r\nStrict-Transport-Security: {sts}\r\nContent-Length: 2\r\n\r\nok")
+        }
+    })
+    .tls()
+    .spawn()
+    .await;
+    port.set(server.port).unwrap();
+    server.port
+}
+
+fn client(hsts: bool) -> ZJHttpClient {
+    ZJHttpClient::builder()
+        .set_resolver(Arc::new(Loopback) as Arc<dyn Resolver>)
+        .set_global_trust_store_pem(TrustStorePem::Bytes(CA_A.to_vec()))
+        .set_global_read_header_timeout(Duration::from_secs(2))
+        .hsts(hsts)
+        .build()
+        .unwrap()
+}
+
+#[async_std::test]
+async fn https_response_upgrades_later_http_requests() {
+    let port = spawn_sts_server("max-age=3600").await;
+    let client = client(true);
+
+    let mut first = Request::new(methods::GET, format!("https://localhost:{port}/")).unwrap();
+    client.send(&mut first).await.unwrap().body_string().await.unwrap();
+    assert!(client.hsts.as_ref().unwrap().get("localhost").is_some());
+
+    let mut req = Request::new(methods::GET, format!("http://localhost:{port}/page")).unwrap();
+    let mut resp = client.send(&mut req).await.unwrap();
+    assert!(resp.is_tls, "the http request must go over TLS");
+    assert_eq!(req.url.as_str(), format!("https://localhost:{port}/page"));
+    assert_eq!(resp.body_string().await.unwrap(), "ok");
+}
+
+#[async_std::test]
+async fn redirect_to_http_is_upgraded_again() {
+    let port = spawn_sts_server("max-age=3600").await;
+    let client = ZJHttpClient::builder()
+        .set_resolver(Arc::new(Loopback) as Arc<dyn Resolver>)
+        .set_global_trust_store_pem(TrustStorePem::Bytes(CA_A.to_vec()))
+        .set_global_read_header_timeout(Duration::from_secs(2))
+        .set_max_redirects(3)
+        .hsts(true)
+        .build()
+        .unwrap();
+
+    let mut first = Request::new(methods::GET, format!("https://localhost:{port}/")).unwrap();
+    client.send(&mut first).await.unwrap().body_string().await.unwrap();
+
+    let mut req = Request::new(methods::GET, format!("https://localhost:{port}/downgrade")).unwrap();
+    let mut resp = client.send(&mut req).await.unwrap();
+    assert!(resp.is_tls, "the redirect must not leave TLS");
+    assert_eq!(resp.url.as_ref().unwrap().as_str(), format!("https://localhost:{port}/landed"));
+    assert_eq!(resp.body_string().await.unwrap(), "ok");
+}
+
+#[async_std::test]
+async fn hsts_is_off_by_default() {
+    let port = spawn_sts_server("max-age=3600").await;
+    let client = client(false);
+    assert!(client.hsts.is_none());
+
+    let mut first = Request::new(methods::GET, format!("https://localhost:{port}/")).unwrap();
+    client.send(&mut first).await.unwrap().body_string().await.unwrap();
+
+    let mut req = Request::new(methods::GET, format!("http://localhost:{port}/")).unwrap();
+    let _ = client.send(&mut req).await;
+    assert_eq!(req.url.scheme(), "http");
+}
+
+#[async_std::test]
+async fn max_age_zero_clears_the_entry() {
+    let port = spawn_sts_server("max-age=0").await;
+    let client = client(true);
+    let store = client.hsts.as_ref().unwrap();
+    store.preload("localhost", false, SystemTime::now() + Duration::from_secs(3600));
+
+    let mut req = Request::new(methods::GET, format!("https://localhost:{port}/")).unwrap();
+    client.send(&mut req).await.unwrap().body_string().await.unwrap();
+    assert!(store.get("localhost").is_none());
+}
+
+#[async_std::test]
+async fn preloaded_parent_domain_upgrades_subdomains() {
+    let port = spawn_sts_server("max-age=3600").await;
+    let store = HstsStore::new();
+    store.preload("test", true, SystemTime::now() + Duration::from_secs(3600));
+    let client = ZJHttpClient::builder()
+        .set_resolver(Arc::new(Loopback) as Arc<dyn Resolver>)
+        .set_global_trust_store_pem(TrustStorePem::Bytes(CA_A.to_vec()))
+        .set_hsts(store)
+        .build()
+        .unwrap();
+
+    // The certificate only covers localhost, so reaching the TLS handshake
+    // at all shows the request was upgraded.
+    let mut req = Request::new(methods::GET, format!("http://api.test:{port}/")).unwrap();
+    let err = client.send(&mut req).await.err().expect("certificate does not cover api.test");
+    assert!(matches!(err, ZjhttpcError::Tls { .. }), "{err:?}");
+    assert_eq!(req.url.scheme(), "https");
+}
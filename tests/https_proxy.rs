@@ -0,0 +1,108 @@
+mod common;
+
+use std::sync::{Arc, Mutex};
+
+use async_std::io::WriteExt;
+use async_std::net::{TcpListener, TcpStream};
+use async_std::task;
+use zjhttpc::client::ZJHttpClient;
+use zjhttpc::misc::TrustStorePem;
+use zjhttpc::proxy::HttpsProxyOption;
+use zjhttpc::requestx::Request;
+use zjhttpc::{RequestPhase, ZjhttpcError, methods};
+
+use common::{CA_A, MockServer, OK, read_head};
+
+/// CONNECT proxy recording the tunnel targets it was asked for; every
+/// tunnel leads to the port on 127.0.0.1, whatever the host. With
+/// `refusal`, every CONNECT is answered with that status line instead.
+async fn spawn_connect_proxy(refusal: Option<&'static str>) -> (u16, Arc<Mutex<Vec<String>>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let targets = Arc::new(Mutex::new(Vec::new()));
+    let log = targets.clone();
+    task::spawn(async move {
+        while let Ok((mut client, _)) = listener.accept().await {
+            let log = log.clone();
+            task::spawn(async move {
+                let head = read_head(&mut client).await.unwrap();
+                let target = head.strip_prefix("CONNECT ").unwrap().split(' ').next().unwrap().to_string();
+                log.lock().unwrap().push(target.clone());
+                if let Some(status_line) = refusal {
+                    let reply = format!("{status_line}\r\nProxy-Authenticate: Basic realm=\"test\"\r\nContent-Length: 0\r\n\r\n");
+                    let _ = client.write_all(reply.as_bytes()).await;
+                    return;
+                }
+                let port: u16 = target.rsplit(':').next().unwrap().parse().unwrap();
+                let upstream = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+                client.write_all(b"HTTP/1.1 200 Connection established\r\n\r\n").await.unwrap();
+                let (mut client_read, mut client_write) = (client.clone(), client);
+                let (mut upstream_read, mut upstream_write) = (upstream.clone(), upstream);
+                task::spawn(async move { async_std::io::copy(&mut client_read, &mut upstream_write).await });
+                let _ = async_std::io::copy(&mut upstream_read, &mut client_write).await;
+            });
+        }
+    });
+    (port, targets)
+}
+
+fn client_via(proxy_port: u16) -> ZJHttpClient {
+    ZJHttpClient::builder()
+        .set_global_proxy(HttpsProxyOption::new(format!("http://127.0.0.1:{proxy_port}")).unwrap())
+        .set_global_trust_store_pem(TrustStorePem::Bytes(CA_A.to_vec()))
+        .build()
+        .unwrap()
+}
+
+async fn get(client: &ZJHttpClient, url: &str) -> zjhttpc::Result<(String, bool)> {
+    let mut req = Request::new(methods::GET, url)?;
+    let mut resp = Box::pin(client.send(&mut req)).await?;
+    Ok((resp.body_string().await?, resp.reused_connection))
+}
+
+#[async_std::test]
+async fn https_goes_through_a_pooled_tunnel_per_origin() {
+    let server_port = MockServer::always(OK).tls().spawn().await.port;
+    let (proxy_port, targets) = spawn_connect_proxy(None).await;
+    let client = client_via(proxy_port);
+    let by_name = format!("https://localhost:{server_port}/");
+    let other = format!("https://other.test:{server_port}/");
+
+    assert_eq!(get(&client, &by_name).await.unwrap(), ("ok".to_string(), false));
+    assert_eq!(get(&client, &by_name).await.unwrap(), ("ok".to_string(), true));
+    // Same proxy, same server, but another origin: the tunnel verified for
+    // localhost is not handed out, a new one is verified for other.test.
+    let err = get(&client, &other).await.expect_err("the certificate does not cover other.test");
+    assert!(matches!(err, ZjhttpcError::Tls { .. }), "{err:?}");
+    assert_eq!(client.idle_connections(), 1);
+
+    assert_eq!(*targets.lock().unwrap(), [format!("localhost:{server_port}"), format!("other.test:{server_port}")]);
+}
+
+#[async_std::test]
+async fn proxy_authentication_required_is_typed() {
+    let (proxy_port, _) = spawn_connect_proxy(Some("HTTP/1.1 407 Proxy Authentication Required")).await;
+    let err = get(&client_via(proxy_port), "https://localhost:8443/").await.expect_err("refused");
+
+    assert!(
+        matches!(&err, ZjhttpcError::ProxyRefused { status: 407, target, .. } if target == "localhost:8443"),
+        "{err:?}"
+    );
+    assert_eq!(err.code(), "ZJ-PROXY-002");
+    assert_eq!(err.phase(), Some(RequestPhase::Connect));
+    assert!(!err.is_transient());
+}
+
+#[async_std::test]
+async fn other_refusals_carry_the_status() {
+    let (proxy_port, _) = spawn_connect_proxy(Some("HTTP/1.1 503 Service Unavailable")).await;
+    let err = get(&client_via(proxy_port), "https://localhost:8443/").await.expect_err("refused");
+
+    match err {
+        ZjhttpcError::ProxyRefused { status, status_line, .. } => {
+            assert_eq!(status, 503);
+            assert_eq!(status_line, "HTTP/1.1 503 Service Unavailable");
+        }
+        other => panic!("unexpected error: {other}"),
+    }
+}
@@ -0,0 +1,56 @@
+mod common;
+
+use std::net::SocketAddr;
+
+use zjhttpc::ZjhttpcError;
+use zjhttpc::client::ZJHttpClient;
+use zjhttpc::error::RequestPhase;
+use zjhttpc::methods;
+use zjhttpc::requestx::Request;
+
+use common::{MockServer, Reply};
+
+/// Answers every request head with `response` and closes the connection.
+async fn spawn_server(response: &'static [u8]) -> SocketAddr {
+    let server = MockServer::always(Reply::new(response).then_close()).spawn().await;
+    SocketAddr::from(([127, 0, 0, 1], server.port))
+}
+
+async fn send_expecting_error(addr: SocketAddr) -> ZjhttpcError {
+    let client = ZJHttpClient::builder().build().unwrap();
+    let mut req = Request::new(methods::GET, format!("http://{addr}/")).unwrap();
+    client.send(&mut req).await.err().expect("should fail")
+}
+
+#[async_std::test]
+async fn http_3_status_line_is_reported_with_connection_details() {
+    let addr = spawn_server(b"HTTP/3.0 200 OK\r\nContent-Length: 0\r\n\r\n").await;
+    let err = send_expecting_error(addr).await;
+    assert_eq!(err.phase(), Some(RequestPhase::ReadHeader));
+    match err {
+        ZjhttpcError::InvalidStatusLine { status_line, message, addr: peer, reused, .. } => {
+            assert_eq!(status_line, "HTTP/3.0 200 OK");
+            assert!(message.contains("unsupported HTTP version: 3.0"), "got {message}");
+            assert_eq!(peer, addr);
+            assert!(!reused);
+        }
+        other => panic!("unexpected error: {other:?}"),
+    }
+}
+
+#[async_std::test]
+async fn status_999_is_rejected() {
+    let addr = spawn_server(b"HTTP/1.1 999 Whatever\r\nContent-Length: 0\r\n\r\n").await;
+    let err = send_expecting_error(addr).await;
+    let display = err.to_string();
+    match err {
+        ZjhttpcError::InvalidStatusLine { status_line, message, addr: peer, reused, .. } => {
+            assert_eq!(status_line, "HTTP/1.1 999 Whatever");
+            assert!(message.contains("999 is outside 100-599"), "got {message}");
+            assert_eq!(peer, addr);
+            assert!(!reused);
+        }
+        other => panic!("unexpected error: {other:?}"),
+    }
+    assert!(display.contains(&addr.to_string()), "display should name the peer, got {display}");
+}
@@ -0,0 +1,71 @@
+mod common;
+
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+use serde_json::json;
+use zjhttpc::client::ZJHttpClient;
+use zjhttpc::requestx::Request;
+use zjhttpc::{ErrorCategory, ZjhttpcError, methods};
+
+use common::{Mock, MockServer};
+
+/// Answers requests with 204; the server records their heads and bodies.
+async fn spawn_server() -> (u16, Mock) {
+    let server = MockServer::always("HTTP/1.1 204 No Content\r\nContent-Length: 0\r\n\r\n").read_body().spawn().await;
+    (server.port, server)
+}
+
+#[derive(Serialize)]
+struct Order {
+    id: u32,
+    items: Vec<&'static str>,
+    note: Option<&'static str>,
+}
+
+async fn send(req: &mut Request) {
+    let client = ZJHttpClient::builder().build().unwrap();
+    assert_eq!(Box::pin(client.send(req)).await.unwrap().status_code, 204);
+}
+
+#[async_std::test]
+async fn struct_body_is_sent_as_json() {
+    let (port, received) = spawn_server().await;
+    let order = Order { id: 7, items: vec!["tea", "cake"], note: None };
+    let mut req = Request::new(methods::POST, format!("http://127.0.0.1:{port}/orders"))
+        .unwrap()
+        .set_body_json(&order)
+        .unwrap();
+    send(&mut req).await;
+    let received = received.request().await;
+    let (head, body) = (received.head, String::from_utf8(received.body).unwrap());
+    assert!(head.contains("\r\nContent-Type: application/json\r\n"), "{head}");
+    assert!(head.contains("\r\nContent-Length: 43\r\n"), "{head}");
+    assert_eq!(body, r#"{"id":7,"items":["tea","cake"],"note":null}"#);
+}
+
+#[async_std::test]
+async fn value_body_can_be_pretty() {
+    let (port, received) = spawn_server().await;
+    let mut req = Request::new(methods::PUT, format!("http://127.0.0.1:{port}/config"))
+        .unwrap()
+        .set_body_json_pretty(&json!({"debug": true}))
+        .unwrap();
+    send(&mut req).await;
+    let received = received.request().await;
+    let (head, body) = (received.head, String::from_utf8(received.body).unwrap());
+    assert!(head.contains("\r\nContent-Type: application/json\r\n"), "{head}");
+    assert_eq!(body, "{\n  \"debug\": true\n}");
+}
+
+#[test]
+fn unserializable_value_is_an_error() {
+    let map = BTreeMap::from([((1, 2), "non-string key")]);
+    let err = Request::new(methods::POST, "http://example.com/")
+        .unwrap()
+        .set_body_json(&map)
+        .err()
+        .expect("serde_json refuses non-string keys");
+    assert!(matches!(err, ZjhttpcError::JsonSerialize { .. }), "{err:?}");
+    assert_eq!((err.code(), err.category()), ("ZJ-REQ-007", ErrorCategory::Config));
+}
@@ -0,0 +1,91 @@
+use async_std::io::prelude::BufReadExt;
+use async_std::io::{BufReader, WriteExt};
+use async_std::net::TcpListener;
+use async_std::task;
+use futures::StreamExt;
+use serde::Deserialize;
+use zjhttpc::ZjhttpcError;
+use zjhttpc::client::ZJHttpClient;
+use zjhttpc::json_seq::RecordErrors;
+use zjhttpc::methods;
+use zjhttpc::requestx::Request;
+
+#[derive(Debug, Deserialize, PartialEq)]
+struct Reading {
+    sensor: String,
+    value: i64,
+}
+
+/// Three records, the middle one malformed, sent chunked with chunk
+/// boundaries falling inside records. The last record has no trailing LF.
+const SEQUENCE: &[u8] =
+    b"\x1e{\"sensor\":\"a\",\"value\":1}\n\x1e{\"sensor\":\"b\",\"value\":\n\x1e{\"sensor\":\"c\",\"value\":3}";
+
+async fn spawn_server() -> u16 {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    task::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            task::spawn(async move {
+                let mut writer = stream.clone();
+                let mut reader = BufReader::new(stream);
+                let mut line = String::new();
+                while reader.read_line(&mut line).await.unwrap_or(0) > 0 && line != "\r\n" {
+                    line.clear();
+                }
+                let mut resp = b"HTTP/1.1 200 OK\r\nContent-Type: application/json-seq\r\nTransfer-Encoding: chunked\r\n\r\n".to_vec();
+                for chunk in SEQUENCE.chunks(10) {
+                    resp.extend_from_slice(format!("{:x}\r\n", chunk.len()).as_bytes());
+                    resp.extend_from_slice(chunk);
+                    resp.extend_from_slice(b"\r\n");
+                }
+                resp.extend_from_slice(b"0\r\n\r\n");
+                let _ = writer.write_all(&resp).await;
+            });
+        }
+    });
+    port
+}
+
+async fn collect(on_error: RecordErrors) -> Vec<Result<Reading, ZjhttpcError>> {
+    let port = spawn_server().await;
+    let client = ZJHttpClient::builder().build().unwrap();
+    let mut req = Request::new(methods::GET, format!("http://127.0.0.1:{port}/readings")).unwrap();
+    let mut resp = client.send(&mut req).await.unwrap();
+    resp.json_seq_with::<Reading>(on_error).collect().await
+}
+
+fn reading(sensor: &str, value: i64) -> Reading {
+    Reading { sensor: sensor.to_string(), value }
+}
+
+#[async_std::test]
+async fn stop_mode_ends_at_the_malformed_record() {
+    let items = collect(RecordErrors::Stop).await;
+    assert_eq!(items.len(), 2);
+    assert_eq!(items[0].as_ref().unwrap(), &reading("a", 1));
+    match &items[1] {
+        Err(ZjhttpcError::JsonParsing { preview, .. }) => assert_eq!(preview, r#"{"sensor":"b","value":"#),
+        other => panic!("unexpected item: {other:?}"),
+    }
+}
+
+#[async_std::test]
+async fn skip_mode_reports_the_malformed_record_and_carries_on() {
+    let items = collect(RecordErrors::Skip).await;
+    assert_eq!(items.len(), 3);
+    assert_eq!(items[0].as_ref().unwrap(), &reading("a", 1));
+    assert!(matches!(items[1], Err(ZjhttpcError::JsonParsing { .. })));
+    assert_eq!(items[2].as_ref().unwrap(), &reading("c", 3));
+}
+
+#[async_std::test]
+async fn json_seq_defaults_to_stopping() {
+    let port = spawn_server().await;
+    let client = ZJHttpClient::builder().build().unwrap();
+    let mut req = Request::new(methods::GET, format!("http://127.0.0.1:{port}/readings")).unwrap();
+    let mut resp = client.send(&mut req).await.unwrap();
+    let values: Vec<_> = resp.json_seq::<serde_json::Value>().collect().await;
+    assert_eq!(values.len(), 2);
+    assert!(values[1].is_err());
+}
@@ -0,0 +1,103 @@
+use std::sync::{Arc, Mutex};
+
+use async_std::io::ReadExt;
+use async_std::net::TcpListener;
+use async_std::task;
+use futures::channel::oneshot;
+use zjhttpc::body::{BodyMultipartForm, CancelToken};
+use zjhttpc::client::ZJHttpClient;
+use zjhttpc::requestx::Request;
+use zjhttpc::{ZjhttpcError, methods};
+
+/// Server that reads one connection to EOF without answering and hands
+/// back everything it received.
+async fn spawn_sink() -> (u16, oneshot::Receiver<Vec<u8>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let (tx, rx) = oneshot::channel();
+    task::spawn(async move {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let mut received = Vec::new();
+        let _ = stream.read_to_end(&mut received).await;
+        let _ = tx.send(received);
+    });
+    (port, rx)
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+#[async_std::test]
+async fn cancel_between_parts_sends_exactly_one_part() {
+    let dir = std::env::temp_dir();
+    let first_path = dir.join(format!("zjhttpc-cancel-first-{}.bin", std::process::id()));
+    let second_path = dir.join(format!("zjhttpc-cancel-second-{}.bin", std::process::id()));
+    // Larger than the 64 KiB copy buffer, so part 0 reports several times.
+    let first = vec![b'a'; 150 * 1024];
+    std::fs::write(&first_path, &first).unwrap();
+    std::fs::write(&second_path, vec![b'b'; 1024]).unwrap();
+
+    let (port, received) = spawn_sink().await;
+    let token = CancelToken::new();
+    let reports = Arc::new(Mutex::new(Vec::new()));
+    let form = {
+        let canceller = token.clone();
+        let reports = reports.clone();
+        BodyMultipartForm::new()
+            .add_file_path("first", &first_path)
+            .unwrap()
+            .on_part_progress(move |p| {
+                reports.lock().unwrap().push((p.index, p.name.to_string(), p.sent, p.done));
+                if p.done {
+                    canceller.cancel();
+                }
+            })
+            .add_file_path("second", &second_path)
+            .unwrap()
+            .set_cancel_token(token)
+    };
+
+    let client = ZJHttpClient::builder().build().unwrap();
+    let mut req = Request::new(methods::POST, format!("http://127.0.0.1:{port}/upload"))
+        .unwrap()
+        .set_body_multipart_form(form);
+    let err = Box::pin(client.send(&mut req)).await.err().expect("upload was cancelled");
+    assert!(matches!(err, ZjhttpcError::UploadCancelled { parts_sent: 1, .. }), "{err:?}");
+    assert_eq!(client.idle_connections(), 0, "an incomplete body must not be pooled");
+
+    let received = received.await.unwrap();
+    let body = &received[find(&received, b"\r\n\r\n").unwrap() + 4..];
+    let content = find(body, b"name=\"first\"").and_then(|at| find(&body[at..], b"\r\n\r\n").map(|end| at + end + 4)).unwrap();
+    assert_eq!(&body[content..content + first.len()], &first[..]);
+    assert_eq!(&body[content + first.len()..], b"\r\n", "part ends cleanly, nothing follows");
+    assert!(find(body, b"name=\"second\"").is_none());
+
+    let reports = reports.lock().unwrap();
+    assert!(reports.len() >= 3, "{reports:?}");
+    assert!(reports.iter().all(|(index, name, _, _)| *index == 0 && name == "first"));
+    assert!(reports.windows(2).all(|w| w[0].2 <= w[1].2));
+    assert_eq!(reports.last().unwrap().2, first.len() as u64);
+    assert_eq!(reports.iter().filter(|r| r.3).count(), 1);
+
+    let _ = std::fs::remove_file(first_path);
+    let _ = std::fs::remove_file(second_path);
+}
+
+#[async_std::test]
+async fn token_cancelled_up_front_sends_no_part() {
+    let (port, received) = spawn_sink().await;
+    let token = CancelToken::new();
+    token.cancel();
+    let form = BodyMultipartForm::new().add("field", "value").set_cancel_token(token);
+
+    let client = ZJHttpClient::builder().build().unwrap();
+    let mut req = Request::new(methods::POST, format!("http://127.0.0.1:{port}/"))
+        .unwrap()
+        .set_body_multipart_form(form);
+    let err = Box::pin(client.send(&mut req)).await.err().expect("upload was cancelled");
+    assert!(matches!(err, ZjhttpcError::UploadCancelled { parts_sent: 0, .. }), "{err:?}");
+
+    let received = received.await.unwrap();
+    assert!(received.ends_with(b"\r\n\r\n"), "only the head was sent");
+}
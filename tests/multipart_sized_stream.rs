@@ -0,0 +1,67 @@
+mod common;
+
+use async_std::io::Cursor;
+use zjhttpc::body::BodyMultipartForm;
+use zjhttpc::client::ZJHttpClient;
+use zjhttpc::requestx::Request;
+use zjhttpc::{ZjhttpcError, methods};
+
+use common::{Mock, MockServer};
+
+/// Server that reads requests, framed by their Content-Length, and answers
+/// 204; it records the heads and bodies. A failed upload closes the
+/// connection before the body is complete, and is not recorded.
+async fn spawn_server() -> (u16, Mock) {
+    let server = MockServer::always("HTTP/1.1 204 No Content\r\nContent-Length: 0\r\n\r\n").read_body().spawn().await;
+    (server.port, server)
+}
+
+fn upload(port: u16, form: BodyMultipartForm) -> Request {
+    Request::new(methods::POST, format!("http://127.0.0.1:{port}/upload"))
+        .unwrap()
+        .set_body_multipart_form(form)
+}
+
+#[async_std::test]
+async fn sized_stream_keeps_content_length() {
+    let (port, received) = spawn_server().await;
+    let form = BodyMultipartForm::new()
+        .add("note", "a & b")
+        .add_stream_with_len("report", Box::new(Cursor::new(b"1,2\n3,4\n".to_vec())), 8, Some("r.csv"), Some("text/csv"))
+        // Only the declared bytes are sent.
+        .add_stream_with_len("head", Box::new(Cursor::new(b"keep|drop".to_vec())), 4, Some("h.bin"), None::<&str>);
+    let boundary = form.boundary().to_string();
+    let mut req = upload(port, form);
+    let resp = Box::pin(ZJHttpClient::builder().build().unwrap().send(&mut req)).await.unwrap();
+    assert_eq!(resp.status_code, 204);
+
+    let received = received.request().await;
+    let (head, body) = (received.head, received.body);
+    assert!(!head.contains("Transfer-Encoding"), "{head}");
+    let expected = format!(
+        "--{boundary}\r\nContent-Disposition: form-data; name=\"note\"\r\n\r\na & b\r\n\
+         --{boundary}\r\nContent-Disposition: form-data; name=\"report\"; filename=\"r.csv\"\r\nContent-Type: text/csv\r\n\r\n1,2\n3,4\n\r\n\
+         --{boundary}\r\nContent-Disposition: form-data; name=\"head\"; filename=\"h.bin\"\r\nContent-Type: application/octet-stream\r\n\r\nkeep\r\n\
+         --{boundary}--\r\n"
+    );
+    assert_eq!(String::from_utf8(body).unwrap(), expected);
+}
+
+#[async_std::test]
+async fn short_stream_fails_the_send() {
+    let (port, _received) = spawn_server().await;
+    let form = BodyMultipartForm::new().add_stream_with_len(
+        "data",
+        Box::new(Cursor::new(b"tiny".to_vec())),
+        100,
+        Some("d.bin"),
+        None::<&str>,
+    );
+    let mut req = upload(port, form);
+    let err = Box::pin(ZJHttpClient::builder().build().unwrap().send(&mut req)).await.err().expect("short part");
+    match &err {
+        ZjhttpcError::MultipartContentLength { message, .. } => assert!(message.contains("ended after 4"), "{message}"),
+        other => panic!("expected MultipartContentLength, got {other:?}"),
+    }
+    assert!(!err.is_transient());
+}
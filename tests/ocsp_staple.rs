@@ -0,0 +1,105 @@
+mod common;
+
+use std::sync::Arc;
+
+use async_std::io::WriteExt;
+use async_std::net::TcpListener;
+use async_std::task;
+use async_tls::TlsAcceptor;
+use zjhttpc::client::ZJHttpClient;
+use zjhttpc::misc::TrustStorePem;
+use zjhttpc::requestx::Request;
+use zjhttpc::resolver::Resolver;
+use zjhttpc::{ErrorCategory, RequestPhase, ZjhttpcError, methods};
+
+use common::{Loopback, read_head, server_config};
+
+const CA: &[u8] = include_bytes!("fixtures/ocsp_ca.pem");
+const SERVER_CERT: &[u8] = include_bytes!("fixtures/ocsp_server_cert.pem");
+const SERVER_KEY: &[u8] = include_bytes!("fixtures/ocsp_server_key.pem");
+/// Responses signed by the CA for the server certificate, valid for ten
+/// years from when the fixtures were made.
+const GOOD: &[u8] = include_bytes!("fixtures/ocsp_good.der");
+const REVOKED: &[u8] = include_bytes!("fixtures/ocsp_revoked.der");
+
+/// TLS server for `localhost` stapling `staple` (nothing when empty),
+/// answering every request with `ok`.
+async fn spawn_stapling_server(staple: &[u8]) -> u16 {
+    let acceptor = TlsAcceptor::from(Arc::new(server_config(SERVER_CERT, SERVER_KEY, staple)));
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    task::spawn(async move {
+        while let Ok((tcp, _)) = listener.accept().await {
+            let acceptor = acceptor.clone();
+            task::spawn(async move {
+                let Ok(mut stream) = acceptor.accept(tcp).await else {
+                    return;
+                };
+                if read_head(&mut stream).await.is_none() {
+                    return;
+                }
+                let _ = stream.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok").await;
+            });
+        }
+    });
+    port
+}
+
+fn client(require_ocsp_staple: bool) -> ZJHttpClient {
+    ZJHttpClient::builder()
+        .set_resolver(Arc::new(Loopback) as Arc<dyn Resolver>)
+        .set_global_trust_store_pem(TrustStorePem::Bytes(CA.to_vec()))
+        .set_require_ocsp_staple(require_ocsp_staple)
+        .build()
+        .unwrap()
+}
+
+async fn get(client: &ZJHttpClient, port: u16) -> zjhttpc::Result<String> {
+    let mut req = Request::new(methods::GET, format!("https://localhost:{port}/"))?;
+    let mut resp = Box::pin(client.send(&mut req)).await?;
+    resp.body_string().await
+}
+
+#[async_std::test]
+async fn good_staple_passes() {
+    let port = spawn_stapling_server(GOOD).await;
+    assert_eq!(get(&client(true), port).await.unwrap(), "ok");
+}
+
+#[async_std::test]
+async fn missing_staple_is_rejected_only_when_required() {
+    let port = spawn_stapling_server(&[]).await;
+    assert_eq!(get(&client(false), port).await.unwrap(), "ok");
+
+    let err = get(&client(true), port).await.expect_err("no staple");
+    assert!(matches!(err, ZjhttpcError::OcspStapleMissing { .. }), "{err:?}");
+    assert_eq!((err.code(), err.category(), err.phase()), ("ZJ-TLS-003", ErrorCategory::Tls, Some(RequestPhase::Tls)));
+    assert!(!err.is_transient());
+}
+
+#[async_std::test]
+async fn revoked_staple_is_rejected() {
+    let port = spawn_stapling_server(REVOKED).await;
+    let err = get(&client(true), port).await.expect_err("revoked");
+    match &err {
+        ZjhttpcError::OcspStapleInvalid { reason, .. } => assert!(reason.contains("revoked"), "{reason}"),
+        other => panic!("expected OcspStapleInvalid, got {other:?}"),
+    }
+    assert_eq!(err.code(), "ZJ-TLS-004");
+    // Without the requirement the staple is not looked at.
+    assert_eq!(get(&client(false), port).await.unwrap(), "ok");
+}
+
+#[async_std::test]
+async fn request_trust_store_keeps_the_requirement() {
+    let port = spawn_stapling_server(&[]).await;
+    let client = ZJHttpClient::builder()
+        .set_resolver(Arc::new(Loopback) as Arc<dyn Resolver>)
+        .set_require_ocsp_staple(true)
+        .build()
+        .unwrap();
+    let mut req = Request::new(methods::GET, format!("https://localhost:{port}/")).unwrap();
+    req.trust_store_pem = Some(TrustStorePem::Bytes(CA.to_vec()));
+    let err = Box::pin(client.send(&mut req)).await.err().expect("no staple");
+    assert!(matches!(err, ZjhttpcError::OcspStapleMissing { .. }), "{err:?}");
+}
@@ -0,0 +1,74 @@
+mod common;
+
+use async_std::io::ReadExt;
+use std::time::Duration;
+use zjhttpc::client::ZJHttpClient;
+use zjhttpc::requestx::Request;
+use zjhttpc::{RequestPhase, ZjhttpcError, methods};
+
+use common::MockServer;
+
+/// Keep-alive server answering every request with `response`.
+async fn spawn_server(response: &'static [u8]) -> u16 {
+    MockServer::always(response).spawn().await.port
+}
+
+fn get(port: u16) -> Request {
+    Request::new(methods::GET, format!("http://127.0.0.1:{port}/")).unwrap()
+}
+
+const JSON: &[u8] = b"HTTP/1.1 200 OK\r\nContent-Length: 13\r\n\r\n{\"ok\": true}\n";
+const CHUNKED: &[u8] = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n3\r\n\x1f\x8b\x08\r\n4\r\nrest\r\n0\r\n\r\n";
+
+#[async_std::test]
+async fn peeked_bytes_are_not_lost() {
+    let port = spawn_server(JSON).await;
+    let client = ZJHttpClient::builder().build().unwrap();
+    let mut resp = client.send(&mut get(port)).await.unwrap();
+    assert_eq!(resp.peek_body(4).await.unwrap(), b"{\"ok");
+    assert_eq!(resp.body_bytes().await.unwrap(), b"{\"ok\": true}\n");
+    drop(resp);
+
+    // The peek did not break pooling.
+    let mut resp = client.send(&mut get(port)).await.unwrap();
+    assert!(resp.reused_connection);
+    assert_eq!(resp.peek_body(1).await.unwrap(), b"{");
+    assert_eq!(resp.body_string().await.unwrap(), "{\"ok\": true}\n");
+}
+
+#[async_std::test]
+async fn peek_reads_decoded_chunked_bytes() {
+    let port = spawn_server(CHUNKED).await;
+    let client = ZJHttpClient::builder().build().unwrap();
+    let mut resp = client.send(&mut get(port)).await.unwrap();
+    // Spans two chunks; framing is already removed.
+    assert_eq!(resp.peek_body(5).await.unwrap(), b"\x1f\x8b\x08re");
+    assert_eq!(resp.peek_body(2).await.unwrap(), b"\x1f\x8b", "a smaller peek reuses the buffer");
+    let mut body = Vec::new();
+    resp.body_managed_stream().unwrap().read_to_end(&mut body).await.unwrap();
+    assert_eq!(body, b"\x1f\x8b\x08rest");
+}
+
+#[async_std::test]
+async fn peek_past_the_end_returns_the_whole_body() {
+    let port = spawn_server(JSON).await;
+    let client = ZJHttpClient::builder().build().unwrap();
+    let mut resp = client.send(&mut get(port)).await.unwrap();
+    assert_eq!(resp.peek_body(1000).await.unwrap(), b"{\"ok\": true}\n");
+    assert!(resp.is_body_read_complete(), "the framed body was read to its end");
+    assert_eq!(resp.body_bytes().await.unwrap(), b"{\"ok\": true}\n");
+    let err = resp.body_bytes().await.expect_err("already read");
+    assert!(matches!(err, ZjhttpcError::BodyAlreadyRead { .. }), "{err:?}");
+}
+
+#[async_std::test]
+async fn peek_is_bounded_by_the_read_body_timeout() {
+    // Promises 10 bytes and sends 2.
+    let port = spawn_server(b"HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\nab").await;
+    let client = ZJHttpClient::builder().build().unwrap();
+    let mut req = get(port).set_read_body_timeout(Duration::from_millis(200));
+    let mut resp = client.send(&mut req).await.unwrap();
+    let err = resp.peek_body(4).await.expect_err("body stalls");
+    assert!(matches!(err, ZjhttpcError::ReadBodyTimeout { .. }), "{err:?}");
+    assert_eq!(err.phase(), Some(RequestPhase::ReadBody));
+}
@@ -0,0 +1,125 @@
+mod common;
+
+use std::time::Duration;
+
+use async_std::io::{ReadExt, WriteExt};
+use async_std::net::TcpListener;
+use async_std::task;
+use zjhttpc::client::ZJHttpClient;
+use zjhttpc::config::PoolLimits;
+use zjhttpc::methods;
+use zjhttpc::requestx::Request;
+
+use common::{MockServer, OK};
+
+/// Keep-alive server answering every request with `ok`.
+async fn spawn_server() -> u16 {
+    MockServer::always(OK).spawn().await.port
+}
+
+async fn get(client: &ZJHttpClient, port: u16) -> bool {
+    let mut req = Request::new(methods::GET, format!("http://127.0.0.1:{port}/")).unwrap();
+    let mut resp = Box::pin(client.send(&mut req)).await.unwrap();
+    assert_eq!(resp.body_string().await.unwrap(), "ok");
+    resp.reused_connection
+}
+
+fn limits(client: &ZJHttpClient) -> PoolLimits {
+    client.effective_config(&Request::new(methods::GET, "http://example.com/").unwrap()).pool
+}
+
+#[test]
+fn max_idle_per_host_keeps_the_other_limits() {
+    let mut builder = ZJHttpClient::builder();
+    builder.max_idle_per_host(4);
+    let client = builder.build().unwrap();
+    assert_eq!(limits(&client), PoolLimits::new(4, 1000, Duration::from_secs(90)));
+}
+
+#[test]
+fn pool_can_be_turned_off_and_on_again() {
+    let mut builder = ZJHttpClient::builder();
+    builder.max_idle_per_host(4).pool_enabled(false);
+    assert_eq!(limits(&builder.build().unwrap()).max_total, 0);
+    builder.pool_enabled(true);
+    assert_eq!(limits(&builder.build().unwrap()), PoolLimits::new(30, 1000, Duration::from_secs(90)));
+}
+
+#[async_std::test]
+async fn disabled_pool_dials_every_request() {
+    let port = spawn_server().await;
+    let mut builder = ZJHttpClient::builder();
+    builder.pool_enabled(false);
+    let client = builder.build().unwrap();
+    assert!(!get(&client, port).await);
+    assert!(!get(&client, port).await);
+    assert_eq!(client.idle_connections(), 0);
+}
+
+#[async_std::test]
+async fn clear_pool_drops_idle_and_checked_out_connections() {
+    let port = spawn_server().await;
+    let client = ZJHttpClient::builder().build().unwrap();
+    assert!(!get(&client, port).await);
+    assert!(get(&client, port).await);
+
+    let mut req = Request::new(methods::GET, format!("http://127.0.0.1:{port}/")).unwrap();
+    let mut in_flight = client.send(&mut req).await.unwrap();
+    assert_eq!(client.clear_pool(), 0);
+    assert_eq!(in_flight.body_string().await.unwrap(), "ok");
+    drop(in_flight);
+    assert_eq!(client.idle_connections(), 0);
+
+    assert!(!get(&client, port).await);
+    assert_eq!(client.idle_connections(), 1);
+    assert_eq!(client.clear_pool(), 1);
+    assert!(!get(&client, port).await);
+}
+
+#[async_std::test]
+async fn clients_do_not_share_pooled_connections() {
+    let port = spawn_server().await;
+    let first = ZJHttpClient::builder().build().unwrap();
+    let second = ZJHttpClient::builder().build().unwrap();
+    assert!(!get(&first, port).await);
+    assert!(!get(&second, port).await);
+    assert!(get(&first.clone(), port).await);
+    assert_eq!((first.idle_connections(), second.idle_connections()), (1, 1));
+}
+
+#[test]
+fn pool_idle_timeout_keeps_the_other_limits() {
+    let mut builder = ZJHttpClient::builder();
+    builder.max_idle_per_host(4).pool_idle_timeout(Duration::from_secs(15));
+    let client = builder.build().unwrap();
+    assert_eq!(limits(&client), PoolLimits::new(4, 1000, Duration::from_secs(15)));
+}
+
+#[async_std::test]
+async fn keep_alive_timeout_shortens_the_idle_time() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    task::spawn(async move {
+        while let Ok((mut stream, _)) = listener.accept().await {
+            task::spawn(async move {
+                let mut buf = [0u8; 1024];
+                while stream.read(&mut buf).await.unwrap_or(0) > 0 {
+                    let reply = b"HTTP/1.1 200 OK\r\nKeep-Alive: timeout=1, max=100\r\nContent-Length: 2\r\n\r\nok";
+                    if stream.write_all(reply).await.is_err() {
+                        return;
+                    }
+                }
+            });
+        }
+    });
+    let client = ZJHttpClient::builder().build().unwrap();
+    let mut req = Request::new(methods::GET, format!("http://127.0.0.1:{port}/")).unwrap();
+    let mut resp = Box::pin(client.send(&mut req)).await.unwrap();
+    assert_eq!(resp.keep_alive_timeout(), Some(Duration::from_secs(1)));
+    resp.body_string().await.unwrap();
+    drop(resp);
+
+    assert!(get(&client, port).await);
+    task::sleep(Duration::from_millis(1100)).await;
+    assert!(!get(&client, port).await, "expired without being tried");
+}
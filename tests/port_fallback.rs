@@ -0,0 +1,65 @@
+mod common;
+
+use async_std::net::TcpListener;
+use zjhttpc::client::ZJHttpClient;
+use zjhttpc::requestx::Request;
+use zjhttpc::{RequestPhase, ZjhttpcError, methods};
+
+use common::{MockServer, OK};
+
+/// Keep-alive server answering every request with `ok`.
+async fn spawn_server() -> u16 {
+    MockServer::always(OK).spawn().await.port
+}
+
+/// A port nothing listens on, so connecting to it is refused.
+async fn closed_port() -> u16 {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    listener.local_addr().unwrap().port()
+}
+
+#[async_std::test]
+async fn connects_to_the_first_fallback_port_that_accepts() {
+    let (first, second) = (closed_port().await, closed_port().await);
+    let open = spawn_server().await;
+    let client = ZJHttpClient::builder().build().unwrap();
+
+    let mut req = Request::new(methods::GET, format!("http://127.0.0.1:{first}/"))
+        .unwrap()
+        .set_port_fallbacks(&[second, open]);
+    let mut resp = client.send(&mut req).await.unwrap();
+    assert_eq!(resp.addr.port(), open);
+    assert!(!resp.reused_connection);
+    assert_eq!(resp.body_string().await.unwrap(), "ok");
+    drop(resp);
+
+    // The connection was pooled under the port it actually uses.
+    let mut req = Request::new(methods::GET, format!("http://127.0.0.1:{first}/"))
+        .unwrap()
+        .set_port_fallbacks(&[second, open]);
+    let mut resp = client.send(&mut req).await.unwrap();
+    assert_eq!(resp.addr.port(), open);
+    assert!(resp.reused_connection);
+    assert_eq!(resp.body_string().await.unwrap(), "ok");
+}
+
+#[async_std::test]
+async fn fails_with_the_last_connect_error_when_no_port_accepts() {
+    let (first, second) = (closed_port().await, closed_port().await);
+    let client = ZJHttpClient::builder().build().unwrap();
+    let mut req = Request::new(methods::GET, format!("http://127.0.0.1:{first}/"))
+        .unwrap()
+        .set_port_fallbacks(&[second]);
+    let err = client.send(&mut req).await.err().expect("nothing listens");
+    assert!(matches!(err, ZjhttpcError::Connection { .. }), "{err:?}");
+    assert_eq!(err.phase(), Some(RequestPhase::Connect));
+}
+
+#[async_std::test]
+async fn without_fallbacks_only_the_url_port_is_tried() {
+    let closed = closed_port().await;
+    let client = ZJHttpClient::builder().build().unwrap();
+    let mut req = Request::new(methods::GET, format!("http://127.0.0.1:{closed}/")).unwrap();
+    let err = client.send(&mut req).await.err().expect("nothing listens");
+    assert!(matches!(err, ZjhttpcError::Connection { .. }), "{err:?}");
+}
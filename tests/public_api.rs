@@ -0,0 +1,615 @@
+//! Snapshot of the public API. Lists every `pub` item reachable through a
+//! `pub mod` chain from `src/lib.rs` and compares it with
+//! `tests/public_api.txt`, so a change to the public surface is a deliberate
+//! one. After an intended change, regenerate the listing with
+//!
+//! ```text
+//! ZJHTTPC_UPDATE_PUBLIC_API=1 cargo test --test public_api
+//! ```
+//!
+//! The listing is read off the source text. Besides the items themselves it
+//! has, each under the path of the type they belong to:
+//!
+//! - the `pub` fields of public structs and every variant and variant field
+//!   of public enums;
+//! - the items of public traits;
+//! - the `pub` methods and consts of public types, and the traits they
+//!   implement by hand;
+//! - the attributes that change what callers may do with an item: `derive`,
+//!   `non_exhaustive`, `deprecated`, `repr`, `cfg` and `doc(hidden)`.
+//!
+//! Test code is skipped, and each declaration is cut at its body.
+
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+
+const SNAPSHOT: &str = "tests/public_api.txt";
+
+/// Attributes that are part of an item's API.
+const KEPT_ATTRIBUTES: &[&str] =
+    &["#[derive(", "#[non_exhaustive]", "#[deprecated", "#[repr(", "#[cfg(", "#[doc(hidden)]"];
+
+#[test]
+fn public_api_matches_snapshot() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let mut listing = Listing::default();
+    listing.module(&root.join("src/lib.rs"), &root.join("src"), "zjhttpc");
+    let listing = listing.finish().join("\n") + "\n";
+
+    let snapshot = root.join(SNAPSHOT);
+    if std::env::var_os("ZJHTTPC_UPDATE_PUBLIC_API").is_some() {
+        fs::write(&snapshot, &listing).unwrap();
+        return;
+    }
+    let expected = fs::read_to_string(&snapshot).unwrap_or_default();
+    if listing != expected {
+        let (new, old): (Vec<&str>, Vec<&str>) = (listing.lines().collect(), expected.lines().collect());
+        let added = new.iter().filter(|line| !old.contains(line)).map(|line| format!("+ {line}"));
+        let removed = old.iter().filter(|line| !new.contains(line)).map(|line| format!("- {line}"));
+        let diff: Vec<String> = added.chain(removed).collect();
+        panic!(
+            "the public API changed; if that is intended, rerun with ZJHTTPC_UPDATE_PUBLIC_API=1\n{}",
+            diff.join("\n")
+        );
+    }
+}
+
+#[test]
+fn listing_covers_variants_fields_trait_items_and_attributes() {
+    let source = r#"
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub enum Error {
+    #[snafu(display("{expected} '{{' bytes"))]
+    Short {
+        expected: u64,
+        received: u64,
+    },
+    Closed,
+    Io(std::io::Error),
+}
+
+pub struct Body {
+    pub len: u64,
+    inner: Vec<u8>,
+}
+
+impl Body {
+    pub fn new() -> Self {
+        Body { len: 0, inner: Vec::new() }
+    }
+
+    fn private(&self) {}
+}
+
+impl Default for Body {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub trait Source: Send {
+    fn next(&mut self, limit: usize) -> Option<Vec<u8>>;
+
+    fn size_hint(&self) -> u64 {
+        0
+    }
+}
+
+struct Hidden {
+    pub leaked: bool,
+}
+
+impl Hidden {
+    pub fn leaked(&self) {}
+}
+
+#[cfg(test)]
+mod tests {
+    pub fn helper() {}
+}
+"#;
+    let mut listing = Listing::default();
+    listing.source(source, Path::new("."), "m");
+    assert_eq!(
+        listing.finish(),
+        [
+            "m: #[derive(Debug, Clone)] #[non_exhaustive] pub enum Error",
+            "m::Error: Short { .. }",
+            "m::Error::Short: expected: u64",
+            "m::Error::Short: received: u64",
+            "m::Error: Closed",
+            "m::Error: Io(std::io::Error)",
+            "m: pub struct Body",
+            "m::Body: pub len: u64",
+            "m::Body: pub fn new() -> Self",
+            "m::Body: impl Default for Body",
+            "m: pub trait Source: Send",
+            "m::Source: fn next(&mut self, limit: usize) -> Option<Vec<u8>>",
+            "m::Source: fn size_hint(&self) -> u64",
+        ]
+    );
+}
+
+/// One trimmed source line: `text` without comments, and `code` with the
+/// insides of string and char literals blanked as well, for finding the
+/// brackets and separators. Both have the same number of chars.
+struct Line {
+    text: Vec<char>,
+    code: Vec<char>,
+}
+
+impl Line {
+    fn code(&self) -> String {
+        self.code.iter().collect()
+    }
+}
+
+type Lines<'a> = std::slice::Iter<'a, Line>;
+
+/// Split `source` into [`Line`]s, following literals and block comments
+/// across line ends.
+fn lex(source: &str) -> Vec<Line> {
+    enum State {
+        Code,
+        Str,
+        RawStr(usize),
+        Comment,
+    }
+    let mut state = State::Code;
+    let mut lines = Vec::new();
+    for raw in source.lines() {
+        let chars: Vec<char> = raw.chars().collect();
+        let (mut text, mut code) = (Vec::new(), Vec::new());
+        let mut i = 0;
+        while i < chars.len() {
+            let c = chars[i];
+            let next = chars.get(i + 1).copied();
+            match state {
+                State::Comment => {
+                    if c == '*' && next == Some('/') {
+                        state = State::Code;
+                        i += 1;
+                    }
+                }
+                State::Str | State::RawStr(_) => {
+                    let end = match state {
+                        State::RawStr(hashes) => {
+                            c == '"' && chars[i + 1..].iter().take_while(|&&c| c == '#').count() >= hashes
+                        }
+                        _ => c == '"',
+                    };
+                    if c == '\\' && matches!(state, State::Str) && next.is_some() {
+                        text.extend([c, chars[i + 1]]);
+                        code.extend("  ".chars());
+                        i += 1;
+                    } else if end {
+                        let hashes = if let State::RawStr(hashes) = state { hashes } else { 0 };
+                        text.extend(&chars[i..=i + hashes]);
+                        code.extend(&chars[i..=i + hashes]);
+                        i += hashes;
+                        state = State::Code;
+                    } else {
+                        text.push(c);
+                        code.push(' ');
+                    }
+                }
+                State::Code => {
+                    let after_ident = i > 0 && (chars[i - 1].is_alphanumeric() || chars[i - 1] == '_');
+                    if c == '/' && next == Some('/') {
+                        break;
+                    } else if c == '/' && next == Some('*') {
+                        state = State::Comment;
+                        i += 1;
+                    } else if c == '"' {
+                        state = State::Str;
+                        text.push(c);
+                        code.push(c);
+                    } else if c == 'r' && !after_ident && matches!(next, Some('"' | '#')) {
+                        let hashes = chars[i + 1..].iter().take_while(|&&c| c == '#').count();
+                        if chars.get(i + 1 + hashes) == Some(&'"') {
+                            state = State::RawStr(hashes);
+                            text.extend(&chars[i..=i + 1 + hashes]);
+                            code.extend(&chars[i..=i + 1 + hashes]);
+                            i += 1 + hashes;
+                        } else {
+                            text.push(c);
+                            code.push(c);
+                        }
+                    } else if c == '\'' && (next == Some('\\') || chars.get(i + 2) == Some(&'\'')) {
+                        // A char literal; anything else is a lifetime.
+                        let close = (i + 2..chars.len()).find(|&j| chars[j] == '\'').unwrap_or(chars.len() - 1);
+                        text.extend(&chars[i..=close]);
+                        code.push('\'');
+                        code.extend(std::iter::repeat_n(' ', close - i - 1));
+                        code.push('\'');
+                        i = close;
+                    } else {
+                        text.push(c);
+                        code.push(c);
+                    }
+                }
+            }
+            i += 1;
+        }
+        let start = text.iter().position(|c| !c.is_whitespace()).unwrap_or(text.len());
+        let end = text.iter().rposition(|c| !c.is_whitespace()).map_or(start, |end| end + 1);
+        lines.push(Line { text: text[start..end].to_vec(), code: code[start..end].to_vec() });
+    }
+    lines
+}
+
+/// Listed lines, each with the type that has to be public for it to count.
+#[derive(Default)]
+struct Listing {
+    entries: Vec<(Option<String>, String)>,
+    types: HashSet<String>,
+}
+
+impl Listing {
+    fn finish(self) -> Vec<String> {
+        let types = self.types;
+        self.entries
+            .into_iter()
+            .filter(|(owner, _)| owner.as_ref().is_none_or(|owner| types.contains(owner)))
+            .map(|(_, line)| line)
+            .collect()
+    }
+
+    fn push(&mut self, owner: Option<&str>, path: &str, attrs: &[String], decl: &str) {
+        let attrs: String = attrs.iter().map(|attr| format!("{attr} ")).collect();
+        self.entries.push((owner.map(str::to_string), format!("{path}: {attrs}{decl}")));
+    }
+
+    /// List the module in `file`, whose child modules live in `dir`.
+    fn module(&mut self, file: &Path, dir: &Path, path: &str) {
+        self.source(&fs::read_to_string(file).unwrap(), dir, path);
+    }
+
+    fn source(&mut self, source: &str, dir: &Path, path: &str) {
+        // Builders derived with `#[builder(name = "..")]` are public types too.
+        for rest in source.split("#[builder(name = \"").skip(1) {
+            self.types.insert(rest[..rest.find('"').unwrap()].to_string());
+        }
+        let lines = lex(source);
+        self.module_items(&mut lines.iter(), dir, path);
+    }
+
+    /// List module items up to the end of the enclosing block.
+    fn module_items(&mut self, lines: &mut Lines, dir: &Path, path: &str) {
+        let mut attrs = Vec::new();
+        while let Some(line) = lines.next() {
+            let code = line.code();
+            let Some(attrs) = item_start(&code, line, lines, &mut attrs) else {
+                if code.starts_with('}') {
+                    return;
+                }
+                continue;
+            };
+            if code.starts_with("mod tests") || attrs.iter().any(|attr| attr == "#[cfg(test)]") {
+                skip_item(line, lines, false);
+            } else if let Some(name) = code.strip_prefix("pub mod ").and_then(|rest| rest.strip_suffix(';')) {
+                self.push(None, path, &attrs, &format!("pub mod {name}"));
+                let child = [dir.join(format!("{name}.rs")), dir.join(name).join("mod.rs")]
+                    .into_iter()
+                    .find(|candidate| candidate.exists())
+                    .unwrap_or_else(|| panic!("no file for module {path}::{name}"));
+                self.module(&child, &dir.join(name), &format!("{path}::{name}"));
+            } else if let Some(name) = code.strip_prefix("pub mod ").and_then(|rest| rest.strip_suffix(" {")) {
+                self.push(None, path, &attrs, &format!("pub mod {name}"));
+                self.module_items(lines, &dir.join(name), &format!("{path}::{name}"));
+            } else if code.starts_with("impl") || code.starts_with("unsafe impl") {
+                self.impl_block(line, lines, path, &attrs);
+            } else if code.starts_with("pub ") {
+                let decl = declaration(line, lines, false);
+                self.push(None, path, &attrs, &decl.text);
+                let Some((kind, name)) = type_name(&decl.text) else {
+                    decl.skip_body(lines);
+                    continue;
+                };
+                self.types.insert(name.to_string());
+                let owner = format!("{path}::{name}");
+                match kind {
+                    _ if decl.open == 0 => {}
+                    "struct" | "union" => self.fields(lines, &owner),
+                    "enum" => self.variants(lines, &owner),
+                    "trait" => self.trait_items(lines, &owner),
+                    _ => decl.skip_body(lines),
+                }
+            } else {
+                skip_item(line, lines, false);
+            }
+        }
+    }
+
+    /// List the `pub` fields of a struct.
+    fn fields(&mut self, lines: &mut Lines, owner: &str) {
+        let mut attrs = Vec::new();
+        while let Some(line) = lines.next() {
+            let code = line.code();
+            let Some(attrs) = item_start(&code, line, lines, &mut attrs) else {
+                if code.starts_with('}') {
+                    return;
+                }
+                continue;
+            };
+            if code.starts_with("pub ") {
+                let decl = declaration(line, lines, true);
+                self.push(None, owner, &attrs, &decl.text);
+            } else {
+                skip_item(line, lines, true);
+            }
+        }
+    }
+
+    /// List the variants of an enum, and the fields of struct-like ones.
+    fn variants(&mut self, lines: &mut Lines, owner: &str) {
+        let mut attrs = Vec::new();
+        while let Some(line) = lines.next() {
+            let code = line.code();
+            let Some(attrs) = item_start(&code, line, lines, &mut attrs) else {
+                if code.starts_with('}') {
+                    return;
+                }
+                continue;
+            };
+            let decl = declaration(line, lines, true);
+            if decl.end != Some('{') {
+                self.push(None, owner, &attrs, &decl.text);
+            } else if decl.open == 0 {
+                let fields = decl.rest.trim_end_matches(',');
+                self.push(None, owner, &attrs, &tidy(&format!("{} {{ {fields}", decl.text)));
+            } else {
+                self.push(None, owner, &attrs, &format!("{} {{ .. }}", decl.text));
+                self.variant_fields(lines, &format!("{owner}::{}", decl.text));
+            }
+        }
+    }
+
+    /// List every field of a struct-like enum variant.
+    fn variant_fields(&mut self, lines: &mut Lines, owner: &str) {
+        let mut attrs = Vec::new();
+        while let Some(line) = lines.next() {
+            let code = line.code();
+            let Some(attrs) = item_start(&code, line, lines, &mut attrs) else {
+                if code.starts_with('}') {
+                    return;
+                }
+                continue;
+            };
+            let decl = declaration(line, lines, true);
+            self.push(None, owner, &attrs, &decl.text);
+        }
+    }
+
+    /// List the items of a trait, default bodies cut off.
+    fn trait_items(&mut self, lines: &mut Lines, owner: &str) {
+        let mut attrs = Vec::new();
+        while let Some(line) = lines.next() {
+            let code = line.code();
+            let Some(attrs) = item_start(&code, line, lines, &mut attrs) else {
+                if code.starts_with('}') {
+                    return;
+                }
+                continue;
+            };
+            let decl = declaration(line, lines, false);
+            self.push(None, owner, &attrs, &decl.text);
+            decl.skip_body(lines);
+        }
+    }
+
+    /// List the trait an `impl` block implements, or the `pub` items of an
+    /// inherent one, under the type it is for.
+    fn impl_block(&mut self, first: &Line, lines: &mut Lines, path: &str, attrs: &[String]) {
+        let header = declaration(first, lines, false);
+        let (trait_name, self_type) = impl_target(&header.text);
+        let name = self_type.split('<').next().unwrap().rsplit("::").next().unwrap().trim().to_string();
+        let owner = format!("{path}::{name}");
+        if trait_name.is_some() {
+            let text = header.text.split_once(" where ").map_or(header.text.as_str(), |(text, _)| text);
+            self.push(Some(&name), &owner, attrs, text);
+            header.skip_body(lines);
+            return;
+        }
+        if header.open == 0 {
+            return;
+        }
+        let mut attrs = Vec::new();
+        while let Some(line) = lines.next() {
+            let code = line.code();
+            let Some(attrs) = item_start(&code, line, lines, &mut attrs) else {
+                if code.starts_with('}') {
+                    return;
+                }
+                continue;
+            };
+            if code.starts_with("pub ") && !attrs.iter().any(|attr| attr == "#[cfg(test)]") {
+                let decl = declaration(line, lines, false);
+                self.push(Some(&name), &owner, &attrs, &decl.text);
+                decl.skip_body(lines);
+            } else {
+                skip_item(line, lines, false);
+            }
+        }
+    }
+}
+
+/// Whether `code` starts an item rather than being blank, closing a block or
+/// an attribute. Attributes are collected into `pending` and handed over
+/// with the item they belong to.
+fn item_start(code: &str, line: &Line, lines: &mut Lines, pending: &mut Vec<String>) -> Option<Vec<String>> {
+    if code.is_empty() || code.starts_with('}') || code.starts_with("#![") {
+        return None;
+    }
+    if code.starts_with("#[") {
+        let attr = attribute(line, lines);
+        if KEPT_ATTRIBUTES.iter().any(|kept| attr.starts_with(kept)) {
+            pending.push(attr);
+        }
+        return None;
+    }
+    Some(std::mem::take(pending))
+}
+
+/// The attribute starting at `first`, joined onto one line.
+fn attribute(first: &Line, lines: &mut Lines) -> String {
+    let mut text = String::new();
+    let mut depth = 0;
+    let mut line = Some(first);
+    while let Some(current) = line {
+        for (&c, &code) in current.text.iter().zip(&current.code) {
+            text.push(c);
+            match code {
+                '[' | '(' => depth += 1,
+                ']' | ')' => depth -= 1,
+                _ => {}
+            }
+            if depth == 0 && code == ']' {
+                return tidy(&text);
+            }
+        }
+        text.push(' ');
+        line = lines.next();
+    }
+    tidy(&text)
+}
+
+/// An item's declaration, read up to its body or its end.
+struct Decl {
+    text: String,
+    /// What cut the declaration: `{`, `;`, or `,` between fields.
+    end: Option<char>,
+    /// What the line holds after the cut, and how many of its `{` are still
+    /// open at the line end when the cut was a `{`.
+    rest: String,
+    open: i32,
+}
+
+impl Decl {
+    fn skip_body(&self, lines: &mut Lines) {
+        if self.open > 0 {
+            skip_block(lines, self.open);
+        }
+    }
+}
+
+/// The declaration starting at `first`, joined onto one line and cut where its
+/// body, value list or, with `fields`, field separator starts.
+fn declaration(first: &Line, lines: &mut Lines, fields: bool) -> Decl {
+    let is_use = first.code().starts_with("pub use ");
+    let mut text = String::new();
+    let mut depth = 0i32;
+    let mut line = Some(first);
+    while let Some(current) = line {
+        let mut prev = ' ';
+        for (i, (&c, &code)) in current.text.iter().zip(&current.code).enumerate() {
+            match code {
+                '(' | '[' => depth += 1,
+                '{' if is_use => depth += 1,
+                '<' if !is_use => depth += 1,
+                ')' | ']' => depth -= 1,
+                '}' if is_use => depth -= 1,
+                '>' if !is_use && prev != '-' && prev != '=' => depth -= 1,
+                '{' | ';' if depth == 0 => {
+                    let open = if code == '{' { 1 + brace_delta(&current.code[i + 1..]) } else { 0 };
+                    let rest = current.text[i + 1..].iter().collect();
+                    return Decl { text: tidy(&text), end: Some(code), rest, open };
+                }
+                ',' if depth == 0 && fields => {
+                    return Decl { text: tidy(&text), end: Some(','), rest: String::new(), open: 0 };
+                }
+                _ => {}
+            }
+            text.push(c);
+            prev = code;
+        }
+        if fields && depth == 0 && !text.trim().is_empty() && lines.as_slice().first().is_some_and(is_block_end) {
+            break;
+        }
+        text.push(' ');
+        line = lines.next();
+    }
+    Decl { text: tidy(&text), end: None, rest: String::new(), open: 0 }
+}
+
+fn is_block_end(line: &Line) -> bool {
+    line.code.first() == Some(&'}')
+}
+
+/// Consume a non-public item: up to its `;`, up to the `}` closing its
+/// body, or, with `fields`, up to the `,` after it.
+fn skip_item(first: &Line, lines: &mut Lines, fields: bool) {
+    let mut depth = 0;
+    let mut line = Some(first);
+    while let Some(current) = line {
+        for &code in &current.code {
+            match code {
+                '(' | '[' | '{' => depth += 1,
+                ')' | ']' | '}' => depth -= 1,
+                _ => {}
+            }
+            let closed = code == '}' && depth == 0 && !fields;
+            if depth == 0 && (code == ';' || closed || (code == ',' && fields)) {
+                return;
+            }
+        }
+        if fields && depth == 0 && lines.as_slice().first().is_some_and(is_block_end) {
+            return;
+        }
+        line = lines.next();
+    }
+}
+
+/// Consume lines up to the `}` closing a block with `depth` open braces.
+fn skip_block(lines: &mut Lines, mut depth: i32) {
+    for line in lines {
+        depth += brace_delta(&line.code);
+        if depth <= 0 {
+            return;
+        }
+    }
+}
+
+fn brace_delta(code: &[char]) -> i32 {
+    code.iter().map(|&c| i32::from(c == '{') - i32::from(c == '}')).sum()
+}
+
+/// The kind and name of a declared type, if `decl` declares one.
+fn type_name(decl: &str) -> Option<(&str, &str)> {
+    let rest = decl.strip_prefix("pub ")?;
+    let (kind, rest) = rest.split_once(' ')?;
+    if !matches!(kind, "struct" | "enum" | "trait" | "union" | "type") {
+        return None;
+    }
+    let end = rest.find(|c: char| !c.is_alphanumeric() && c != '_').unwrap_or(rest.len());
+    Some((kind, &rest[..end]))
+}
+
+/// The trait and the type of an `impl` header.
+fn impl_target(header: &str) -> (Option<&str>, &str) {
+    let mut rest = header.trim_start_matches("unsafe ").trim_start_matches("impl");
+    if rest.starts_with('<') {
+        let mut depth = 0;
+        let end = rest
+            .char_indices()
+            .find(|&(_, c)| {
+                depth += i32::from(c == '<') - i32::from(c == '>');
+                depth == 0
+            })
+            .map_or(rest.len(), |(i, _)| i + 1);
+        rest = &rest[end..];
+    }
+    let rest = rest.split(" where ").next().unwrap().trim();
+    match rest.split_once(" for ") {
+        Some((trait_name, self_type)) => (Some(trait_name), self_type),
+        None => (None, rest),
+    }
+}
+
+fn tidy(text: &str) -> String {
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    let text = text.replace("( ", "(").replace(", )", ")").replace(" )", ")").replace(",}", "}").replace(", }", " }");
+    text.trim_end_matches(',').to_string()
+}
@@ -0,0 +1,67 @@
+mod common;
+
+use zjhttpc::client::ZJHttpClient;
+use zjhttpc::methods;
+use zjhttpc::requestx::Request;
+use zjhttpc::response::BodyFraming;
+
+use common::{MockServer, Reply};
+
+/// Answers every request head with `response`, then closes the connection.
+async fn spawn_server(response: &'static [u8]) -> u16 {
+    MockServer::always(Reply::new(response).then_close()).spawn().await.port
+}
+
+/// Claims chunked encoding but sends the body unframed.
+const LYING_CHUNKED: &[u8] = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\nhello, raw world";
+
+#[async_std::test]
+async fn chunked_decoding_fails_on_unframed_body() {
+    let port = spawn_server(LYING_CHUNKED).await;
+    let client = ZJHttpClient::builder().build().unwrap();
+    let mut req = Request::new(methods::GET, format!("http://127.0.0.1:{port}/")).unwrap();
+    let mut resp = client.send(&mut req).await.unwrap();
+    assert_eq!(resp.effective_encoding(), BodyFraming::Chunked);
+    resp.body_bytes().await.expect_err("chunked decoding should fail");
+}
+
+#[async_std::test]
+async fn raw_framing_reads_socket_bytes_to_eof() {
+    let port = spawn_server(LYING_CHUNKED).await;
+    let client = ZJHttpClient::builder().build().unwrap();
+    let mut req = Request::new(methods::GET, format!("http://127.0.0.1:{port}/"))
+        .unwrap()
+        .set_raw_body_framing(true);
+    let mut resp = client.send(&mut req).await.unwrap();
+    assert_eq!(resp.effective_encoding(), BodyFraming::Raw);
+    assert_eq!(resp.body_string().await.unwrap(), "hello, raw world");
+    drop(resp);
+    assert_eq!(client.idle_connections(), 0, "raw-framed connections are never pooled");
+}
+
+#[async_std::test]
+async fn raw_framing_ignores_content_length() {
+    let port = spawn_server(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nokay and more").await;
+    let client = ZJHttpClient::builder().build().unwrap();
+
+    let mut req = Request::new(methods::GET, format!("http://127.0.0.1:{port}/")).unwrap();
+    let mut resp = client.send(&mut req).await.unwrap();
+    assert_eq!(resp.effective_encoding(), BodyFraming::ContentLength(2));
+    assert_eq!(resp.body_string().await.unwrap(), "ok");
+
+    let mut req = Request::new(methods::GET, format!("http://127.0.0.1:{port}/"))
+        .unwrap()
+        .set_raw_body_framing(true);
+    let mut resp = client.send(&mut req).await.unwrap();
+    assert_eq!(resp.body_string().await.unwrap(), "okay and more");
+}
+
+#[async_std::test]
+async fn body_without_framing_headers_reads_to_eof() {
+    let port = spawn_server(b"HTTP/1.1 200 OK\r\nConnection: close\r\n\r\nuntil close").await;
+    let client = ZJHttpClient::builder().build().unwrap();
+    let mut req = Request::new(methods::GET, format!("http://127.0.0.1:{port}/")).unwrap();
+    let mut resp = client.send(&mut req).await.unwrap();
+    assert_eq!(resp.effective_encoding(), BodyFraming::ReadToEof);
+    assert_eq!(resp.body_string().await.unwrap(), "until close");
+}
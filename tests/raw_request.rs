@@ -0,0 +1,56 @@
+mod common;
+
+use zjhttpc::client::ZJHttpClient;
+use zjhttpc::requestx::Request;
+
+use common::{Mock, MockServer, Received};
+
+/// Keep-alive server recording each request's head and body verbatim.
+async fn spawn_server() -> (u16, Mock) {
+    let server = MockServer::always("HTTP/1.1 204 No Content\r\n\r\n").read_body().spawn().await;
+    (server.port, server)
+}
+
+/// Header lines of `head` in order, without the ones the client manages.
+fn unmanaged_lines(head: &str) -> Vec<&str> {
+    head.split("\r\n")
+        .filter(|l| !l.is_empty() && !l.starts_with("Content-Length:") && !l.starts_with("Connection:"))
+        .collect()
+}
+
+#[async_std::test]
+async fn captured_request_is_replayed_as_captured() {
+    let (port, seen) = spawn_server().await;
+    let captured = format!(
+        "PUT /v1/users/7?notify=false HTTP/1.1\r\nHost: 127.0.0.1:{port}\r\nuser-agent: curl/8.5.0\r\n\
+         Accept: */*\r\nCookie: session=abc\r\nContent-Type: application/json\r\nContent-Length: 15\r\n\
+         X-Request-Id: 42\r\n\r\n{{\"name\":\"bob\"}}\n"
+    );
+    let client = ZJHttpClient::builder().build().unwrap();
+
+    let mut req = Request::parse_from_head(captured.as_bytes(), None).unwrap();
+    let resp = Box::pin(client.send(&mut req)).await.unwrap();
+    assert_eq!(resp.status_code, 204);
+
+    let Received { head, body, .. } = seen.request().await;
+    let (captured_head, captured_body) = captured.split_once("\r\n\r\n").unwrap();
+    assert_eq!(unmanaged_lines(&head), unmanaged_lines(captured_head));
+    assert!(head.contains("\r\nContent-Length: 15\r\n"), "{head}");
+    assert_eq!(body, captured_body.as_bytes());
+}
+
+#[async_std::test]
+async fn override_redirects_the_replay_and_a_reader_supplies_the_body() {
+    let (port, seen) = spawn_server().await;
+    let captured = b"POST /upload HTTP/1.1\r\nHost: files.example.com\r\nContent-Length: 11\r\n\r\n";
+    let client = ZJHttpClient::builder().build().unwrap();
+
+    let body = async_std::io::Cursor::new(b"hello world".to_vec());
+    let mut req = Request::parse_from_head_with_body(captured, Some(&format!("127.0.0.1:{port}")), body, 11).unwrap();
+    Box::pin(client.send(&mut req)).await.unwrap();
+
+    let Received { head, body, .. } = seen.request().await;
+    assert!(head.starts_with("POST /upload HTTP/1.1\r\nHost: 127.0.0.1\r\n"), "{head}");
+    assert!(!head.contains("files.example.com"), "{head}");
+    assert_eq!(body, b"hello world");
+}
@@ -0,0 +1,323 @@
+mod common;
+
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use futures::future::BoxFuture;
+use zjhttpc::ZjhttpcError;
+use zjhttpc::client::ZJHttpClient;
+use zjhttpc::connector::{Connector, TcpConnector};
+use zjhttpc::methods;
+use zjhttpc::redirect::RedirectPolicy;
+use zjhttpc::requestx::Request;
+use zjhttpc::resolver::{AddressFilter, Resolver};
+use zjhttpc::stream::BoxedStream;
+
+use common::{Mock, MockServer, Reply};
+
+/// A request as the server saw it: request line, lower-cased head, body.
+#[derive(Debug, Clone)]
+struct Seen {
+    line: String,
+    head: String,
+    body: Vec<u8>,
+}
+
+type Route = fn(&str) -> String;
+
+/// Keep-alive server answering each request with `route(request line)`,
+/// matched lower-cased; a thin wrapper over the shared [`MockServer`].
+async fn spawn_server(route: Route) -> (u16, Mock) {
+    let server = MockServer::new(move |req| {
+        let response = route(&req.head.lines().next().unwrap().to_ascii_lowercase());
+        // The body follows the head after a pause, so it is still on the
+        // wire when the client gets the response.
+        let (head, body) = response.split_at(response.find("\r\n\r\n").unwrap() + 4);
+        Reply::new(head).then_sleep(Duration::from_millis(10)).then(body)
+    })
+    .read_body()
+    .spawn()
+    .await;
+    (server.port, server)
+}
+
+/// The requests `server` has read, as it saw them.
+fn log(server: &Mock) -> Vec<Seen> {
+    server
+        .requests()
+        .into_iter()
+        .map(|req| {
+            let head = req.head.to_ascii_lowercase();
+            Seen { line: head.lines().next().unwrap().to_string(), head, body: req.body }
+        })
+        .collect()
+}
+
+fn redirect(status: &str, location: &str) -> String {
+    format!("HTTP/1.1 {status}\r\nLocation: {location}\r\nContent-Length: 5\r\n\r\nmoved")
+}
+
+fn ok(body: &str) -> String {
+    format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n{body}", body.len())
+}
+
+fn client(max_redirects: usize) -> ZJHttpClient {
+    ZJHttpClient::builder().set_max_redirects(max_redirects).build().unwrap()
+}
+
+fn lines(seen: &Mock) -> Vec<String> {
+    log(seen).into_iter().map(|s| s.line).collect()
+}
+
+#[async_std::test]
+async fn relative_locations_are_followed_to_the_end() {
+    let (port, seen) = spawn_server(|line| match line {
+        "get /a?x=1 http/1.1" => redirect("301 Moved Permanently", "b/c"),
+        "get /b/c http/1.1" => redirect("302 Found", "../final?y=2"),
+        _ => ok("arrived"),
+    })
+    .await;
+    let mut req = Request::new(methods::GET, format!("http://127.0.0.1:{port}/a?x=1#top")).unwrap();
+    let mut resp = Box::pin(client(5).send(&mut req)).await.unwrap();
+
+    assert_eq!(resp.status_code, 200);
+    assert_eq!(resp.body_string().await.unwrap(), "arrived");
+    assert_eq!(resp.url.as_ref().unwrap().as_str(), format!("http://127.0.0.1:{port}/final?y=2#top"));
+    assert_eq!(req.url.path(), "/final");
+    assert_eq!(lines(&seen), ["get /a?x=1 http/1.1", "get /b/c http/1.1", "get /final?y=2 http/1.1"]);
+}
+
+#[async_std::test]
+async fn see_other_switches_to_get_without_a_body() {
+    let (port, seen) = spawn_server(|line| match line {
+        "post /form http/1.1" => redirect("303 See Other", "/done"),
+        _ => ok("thanks"),
+    })
+    .await;
+    let mut req = Request::new(methods::POST, format!("http://127.0.0.1:{port}/form"))
+        .unwrap()
+        .set_body_string("name=alice")
+        .set_content_type("application/x-www-form-urlencoded");
+    let mut resp = Box::pin(client(5).send(&mut req)).await.unwrap();
+    assert_eq!(resp.body_string().await.unwrap(), "thanks");
+
+    let seen = log(&seen);
+    assert_eq!(seen[0].body, b"name=alice");
+    assert_eq!(seen[1].line, "get /done http/1.1");
+    assert!(seen[1].body.is_empty());
+    assert!(!seen[1].head.contains("content-type"), "{}", seen[1].head);
+    assert!(!seen[1].head.contains("content-length"), "{}", seen[1].head);
+}
+
+#[async_std::test]
+async fn temporary_redirect_keeps_method_and_body() {
+    let (port, seen) = spawn_server(|line| match line {
+        "put /v1/item http/1.1" => redirect("307 Temporary Redirect", "/v2/item"),
+        _ => ok("stored"),
+    })
+    .await;
+    let mut req = Request::new(methods::PUT, format!("http://127.0.0.1:{port}/v1/item"))
+        .unwrap()
+        .set_body_slice(b"payload");
+    let mut resp = Box::pin(client(5).send(&mut req)).await.unwrap();
+    assert_eq!(resp.body_string().await.unwrap(), "stored");
+
+    let seen = log(&seen);
+    assert_eq!(seen[1].line, "put /v2/item http/1.1");
+    assert_eq!(seen[1].body, b"payload");
+}
+
+#[async_std::test]
+async fn stream_body_cannot_follow_a_permanent_redirect() {
+    let (port, seen) = spawn_server(|_| redirect("308 Permanent Redirect", "/elsewhere")).await;
+    let mut req = Request::new(methods::POST, format!("http://127.0.0.1:{port}/upload"))
+        .unwrap()
+        .set_body_stream(async_std::io::Cursor::new(b"streamed".to_vec()), 8);
+    let err = Box::pin(client(5).send(&mut req)).await.err().expect("the stream is spent");
+
+    assert!(matches!(err, ZjhttpcError::RedirectBodyNotReplayable { status: 308, .. }), "{err:?}");
+    assert_eq!(err.code(), "ZJ-REDIRECT-002");
+    assert_eq!(lines(&seen), ["post /upload http/1.1"]);
+}
+
+#[async_std::test]
+async fn redirect_loop_stops_at_the_limit() {
+    let (port, seen) = spawn_server(|line| match line {
+        "get /ping http/1.1" => redirect("302 Found", "/pong"),
+        _ => redirect("302 Found", "/ping"),
+    })
+    .await;
+    let mut req = Request::new(methods::GET, format!("http://127.0.0.1:{port}/ping")).unwrap();
+    let err = Box::pin(client(3).send(&mut req)).await.err().expect("loops forever");
+
+    assert!(matches!(&err, ZjhttpcError::TooManyRedirects { limit: 3, url, .. } if url.ends_with("/ping")), "{err:?}");
+    assert_eq!(log(&seen).len(), 4, "the first request and three redirects");
+}
+
+#[async_std::test]
+async fn redirects_are_not_followed_by_default() {
+    let (port, seen) = spawn_server(|_| redirect("302 Found", "/elsewhere")).await;
+    let mut req = Request::new(methods::GET, format!("http://127.0.0.1:{port}/")).unwrap();
+    let resp = Box::pin(ZJHttpClient::builder().build().unwrap().send(&mut req)).await.unwrap();
+
+    assert_eq!(resp.status_code, 302);
+    assert_eq!(resp.header_one("location"), Some("/elsewhere"));
+    assert_eq!(resp.url.as_ref(), Some(&req.url));
+    assert_eq!(log(&seen).len(), 1);
+}
+
+#[async_std::test]
+async fn credentials_stay_with_their_origin() {
+    let (other_port, other_seen) = spawn_server(|_| ok("other")).await;
+    let (port, seen) = spawn_server(|line| match line {
+        "get /same http/1.1" => redirect("302 Found", "/next"),
+        "get /next http/1.1" => redirect("302 Found", &OTHER.lock().unwrap()),
+        _ => unreachable!(),
+    })
+    .await;
+    *OTHER.lock().unwrap() = format!("http://127.0.0.1:{other_port}/landing");
+
+    let mut req = Request::new(methods::GET, format!("http://127.0.0.1:{port}/same"))
+        .unwrap()
+        .set_header("Cookie", "session=secret")
+        .set_basic_auth("alice", "hunter2");
+    let mut resp = Box::pin(client(5).send(&mut req)).await.unwrap();
+    assert_eq!(resp.body_string().await.unwrap(), "other");
+
+    let seen = log(&seen);
+    assert!(seen[1].head.contains("authorization: basic"), "same origin keeps it: {}", seen[1].head);
+    assert!(seen[1].head.contains("cookie: session=secret"));
+    let landing = &log(&other_seen)[0];
+    assert!(!landing.head.contains("authorization"), "{}", landing.head);
+    assert!(!landing.head.contains("cookie"), "{}", landing.head);
+}
+
+struct CountingConnector(Arc<AtomicUsize>);
+
+impl Connector for CountingConnector {
+    fn connect<'a>(
+        &'a self,
+        host: &'a str,
+        addr: SocketAddr,
+        connect_timeout: Duration,
+    ) -> BoxFuture<'a, zjhttpc::Result<BoxedStream>> {
+        self.0.fetch_add(1, Ordering::SeqCst);
+        TcpConnector.connect(host, addr, connect_timeout)
+    }
+}
+
+#[async_std::test]
+async fn same_origin_hops_reuse_the_connection() {
+    let (port, seen) = spawn_server(|line| match line {
+        "get /a http/1.1" => redirect("302 Found", "/b"),
+        "get /b http/1.1" => redirect("307 Temporary Redirect", "/c"),
+        "get /c http/1.1" => redirect("301 Moved Permanently", "/final"),
+        "get /final http/1.1" => ok("done"),
+        _ => unreachable!(),
+    })
+    .await;
+    // With pooling off, every call dials once and carries that connection
+    // through its hops; with pooling on, the first connection serves all.
+    for (pooled, expected_dials) in [(true, 1), (false, 5)] {
+        let dials = Arc::new(AtomicUsize::new(0));
+        let inline = Arc::new(AtomicUsize::new(0));
+        let counted = inline.clone();
+        let client = ZJHttpClient::builder()
+            .set_max_redirects(5)
+            .set_connector(Arc::new(CountingConnector(dials.clone())) as Arc<dyn Connector>)
+            .pool_enabled(pooled)
+            .on_complete(move |summary| {
+                counted.fetch_add(summary.reused_inline as usize, Ordering::SeqCst);
+            })
+            .build()
+            .unwrap();
+
+        for _ in 0..5 {
+            let mut req = Request::new(methods::GET, format!("http://127.0.0.1:{port}/a")).unwrap();
+            let mut resp = Box::pin(client.send(&mut req)).await.unwrap();
+            assert!(resp.reused_connection);
+            assert_eq!(resp.body_string().await.unwrap(), "done");
+        }
+        assert_eq!(dials.load(Ordering::SeqCst), expected_dials, "pooled: {pooled}");
+        assert_eq!(inline.load(Ordering::SeqCst), 5, "pooled: {pooled}");
+    }
+    assert_eq!(log(&seen).len(), 40);
+}
+
+static OTHER: Mutex<String> = Mutex::new(String::new());
+
+#[async_std::test]
+async fn hop_to_a_private_address_is_blocked() {
+    let (internal_port, internal_seen) = spawn_server(|_| ok("secret")).await;
+    let (port, _) = spawn_server(|_| redirect("302 Found", &INTERNAL.lock().unwrap())).await;
+    *INTERNAL.lock().unwrap() = format!("http://127.0.0.1:{internal_port}/admin");
+    // Private ranges are denied, apart from the public server stood in for
+    // by the first mock.
+    let deny_private = AddressFilter::deny_private_ranges();
+    let client = ZJHttpClient::builder()
+        .set_max_redirects(5)
+        .set_address_filter(AddressFilter::new(move |addr| addr.port() == port || deny_private.allows(addr)))
+        .build()
+        .unwrap();
+
+    let mut req = Request::new(methods::GET, format!("http://127.0.0.1:{port}/hook")).unwrap();
+    let err = Box::pin(client.send(&mut req)).await.err().expect("the hop is blocked");
+    match &err {
+        ZjhttpcError::RedirectBlocked { url, chain, .. } => {
+            assert_eq!(url, &format!("http://127.0.0.1:{internal_port}/admin"));
+            assert_eq!(chain, &[format!("http://127.0.0.1:{port}/hook")]);
+        }
+        other => panic!("expected RedirectBlocked, got {other:?}"),
+    }
+    assert!(log(&internal_seen).is_empty());
+}
+
+static INTERNAL: Mutex<String> = Mutex::new(String::new());
+
+/// Resolves every host to the loopback address, recording the names asked for.
+struct RecordingResolver(Arc<Mutex<Vec<String>>>);
+
+impl Resolver for RecordingResolver {
+    fn resolve<'a>(&'a self, host: &'a str, port: u16) -> BoxFuture<'a, zjhttpc::Result<Vec<SocketAddr>>> {
+        self.0.lock().unwrap().push(host.to_string());
+        Box::pin(async move { Ok(vec![SocketAddr::from(([127, 0, 0, 1], port))]) })
+    }
+}
+
+#[async_std::test]
+async fn vetoed_hops_are_never_resolved() {
+    let (port, _) = spawn_server(|line| match line {
+        "get /start http/1.1" => redirect("302 Found", "/next"),
+        "get /next http/1.1" => redirect("302 Found", "http://metadata.internal/latest"),
+        "get /ftp http/1.1" => redirect("302 Found", "ftp://files.example/x"),
+        _ => unreachable!(),
+    })
+    .await;
+    let resolved = Arc::new(Mutex::new(Vec::new()));
+    let client = ZJHttpClient::builder()
+        .set_max_redirects(5)
+        .set_resolver(Arc::new(RecordingResolver(resolved.clone())) as Arc<dyn Resolver>)
+        .set_redirect_policy(
+            RedirectPolicy::new().validate_target(Box::new(|url| !url.host_str().unwrap_or_default().ends_with(".internal"))),
+        )
+        .build()
+        .unwrap();
+
+    let mut req = Request::new(methods::GET, format!("http://localhost:{port}/start")).unwrap();
+    let err = Box::pin(client.send(&mut req)).await.err().expect("the hop is vetoed");
+    match &err {
+        ZjhttpcError::RedirectBlocked { url, reason, chain, .. } => {
+            assert_eq!(url, "http://metadata.internal/latest");
+            assert!(reason.contains("redirect policy"), "{reason}");
+            assert_eq!(chain, &[format!("http://localhost:{port}/start"), format!("http://localhost:{port}/next")]);
+        }
+        other => panic!("expected RedirectBlocked, got {other:?}"),
+    }
+    assert!(resolved.lock().unwrap().iter().all(|host| host == "localhost"));
+
+    let mut req = Request::new(methods::GET, format!("http://localhost:{port}/ftp")).unwrap();
+    let err = Box::pin(client.send(&mut req)).await.err().expect("ftp is not followed");
+    assert!(matches!(&err, ZjhttpcError::RedirectBlocked { url, .. } if url == "ftp://files.example/x"), "{err:?}");
+}
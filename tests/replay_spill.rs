@@ -0,0 +1,145 @@
+mod common;
+
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use async_std::io::Cursor;
+use zjhttpc::client::ZJHttpClient;
+use zjhttpc::requestx::Request;
+use zjhttpc::{ZjhttpcError, methods};
+
+use common::{Mock, MockServer, OK, Reply};
+
+const BODY_LEN: usize = 10 * 1024 * 1024;
+
+fn payload() -> Vec<u8> {
+    (0..BODY_LEN).map(|i| (i * 31 % 251) as u8).collect()
+}
+
+/// How the server fails the first upload it gets.
+#[derive(Clone, Copy)]
+enum FirstUpload {
+    /// Read it, then close the connection without answering.
+    Drop,
+    /// Read it and answer `307` to `/again`.
+    Redirect,
+}
+
+/// Keep-alive server answering `GET`s with `ok` and failing the first upload
+/// as `first` says; later uploads are answered `204`.
+async fn spawn_server(first: FirstUpload) -> (u16, Mock) {
+    let failed = AtomicBool::new(false);
+    let server = MockServer::new(move |req| {
+        if req.method() == methods::GET {
+            Reply::new(OK)
+        } else if !failed.swap(true, Ordering::SeqCst) {
+            match first {
+                FirstUpload::Drop => Reply::close(),
+                FirstUpload::Redirect => {
+                    Reply::new("HTTP/1.1 307 Temporary Redirect\r\nLocation: /again\r\nContent-Length: 0\r\n\r\n")
+                }
+            }
+        } else {
+            Reply::new("HTTP/1.1 204 No Content\r\nContent-Length: 0\r\n\r\n")
+        }
+    })
+    .read_body()
+    .spawn()
+    .await;
+    (server.port, server)
+}
+
+/// The body of the first upload the server accepted: the one after the
+/// upload it failed.
+async fn accepted_upload(server: &Mock) -> Vec<u8> {
+    let mut uploads = 0;
+    loop {
+        let req = server.request().await;
+        if req.method() != methods::GET {
+            uploads += 1;
+            if uploads == 2 {
+                return req.body;
+            }
+        }
+    }
+}
+
+fn spill_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("zjhttpc-replay-spill-{}-{name}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn files_in(dir: &PathBuf) -> usize {
+    std::fs::read_dir(dir).unwrap().count()
+}
+
+/// A client whose next send reuses a pooled connection.
+async fn warm_client(port: u16) -> ZJHttpClient {
+    let client = ZJHttpClient::builder().set_max_redirects(1).build().unwrap();
+    let mut req = Request::new(methods::GET, format!("http://127.0.0.1:{port}/")).unwrap();
+    Box::pin(client.send(&mut req)).await.unwrap().body_string().await.unwrap();
+    assert_eq!(client.idle_connections(), 1);
+    client
+}
+
+fn upload(port: u16, body: Vec<u8>) -> Request {
+    let len = body.len() as u64;
+    Request::new(methods::POST, format!("http://127.0.0.1:{port}/upload"))
+        .unwrap()
+        .set_body_stream(Cursor::new(body), len)
+}
+
+#[async_std::test]
+async fn failed_pooled_connection_retries_from_the_spill() {
+    let (port, received) = spawn_server(FirstUpload::Drop).await;
+    let client = warm_client(port).await;
+    let dir = spill_dir("retry");
+    let mut req = upload(port, payload()).set_replay_spill(&dir, BODY_LEN as u64);
+    let resp = Box::pin(client.send(&mut req)).await.unwrap();
+    assert_eq!(resp.status_code, 204);
+    assert!(accepted_upload(&received).await == payload(), "replayed body differs");
+    assert_eq!(files_in(&dir), 0, "spill file removed after send");
+}
+
+#[async_std::test]
+async fn redirect_replays_a_chunked_stream_from_the_spill() {
+    let (port, received) = spawn_server(FirstUpload::Redirect).await;
+    let client = warm_client(port).await;
+    let dir = spill_dir("redirect");
+    let mut req = Request::new(methods::PUT, format!("http://127.0.0.1:{port}/upload"))
+        .unwrap()
+        .set_body_stream_chunked(Cursor::new(payload()))
+        .set_replay_spill(&dir, BODY_LEN as u64);
+    let resp = Box::pin(client.send(&mut req)).await.unwrap();
+    assert_eq!(resp.status_code, 204);
+    assert_eq!(req.url.path(), "/again");
+    assert!(accepted_upload(&received).await == payload(), "replayed body differs");
+    assert_eq!(files_in(&dir), 0, "spill file removed after send");
+}
+
+#[async_std::test]
+async fn body_over_the_cap_is_not_replayed() {
+    let (port, _received) = spawn_server(FirstUpload::Redirect).await;
+    let client = warm_client(port).await;
+    let dir = spill_dir("cap");
+    let mut req = upload(port, payload()).set_replay_spill(&dir, BODY_LEN as u64 - 1);
+    let err = Box::pin(client.send(&mut req)).await.err().expect("not replayable");
+    assert!(matches!(err, ZjhttpcError::RedirectBodyNotReplayable { .. }), "{err:?}");
+    assert_eq!(files_in(&dir), 0);
+}
+
+#[async_std::test]
+async fn unwritable_spill_dir_leaves_the_upload_alone() {
+    let (port, received) = spawn_server(FirstUpload::Drop).await;
+    let client = warm_client(port).await;
+    let missing = spill_dir("unwritable").join("missing");
+    let mut req = upload(port, payload()).set_replay_spill(&missing, BODY_LEN as u64);
+    // Without a copy the stream isn't retried, as without a spill.
+    let err = Box::pin(client.send(&mut req)).await.err().expect("not retried");
+    assert!(err.is_transient(), "{err:?}");
+
+    let mut req = upload(port, b"small".to_vec()).set_replay_spill(&missing, 1024);
+    assert_eq!(Box::pin(client.send(&mut req)).await.unwrap().status_code, 204);
+    assert_eq!(accepted_upload(&received).await, b"small");
+}
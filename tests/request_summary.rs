@@ -0,0 +1,109 @@
+mod common;
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use async_std::net::TcpListener;
+use async_std::task;
+use zjhttpc::client::ZJHttpClient;
+use zjhttpc::methods;
+use zjhttpc::requestx::Request;
+use zjhttpc::summary::RequestSummary;
+
+use common::{MockServer, OK, Reply};
+
+/// Keep-alive server: `/redirect` answers 302 to `/`, `/once` answers 200
+/// and closes the connection without saying so, anything else answers 200.
+async fn spawn_server() -> u16 {
+    MockServer::new(|req| match req.path() {
+        "/redirect" => Reply::new("HTTP/1.1 302 Found\r\nLocation: /\r\nContent-Length: 0\r\n\r\n"),
+        "/once" => Reply::new(OK).then_close(),
+        _ => Reply::new(OK),
+    })
+    .spawn()
+    .await
+    .port
+}
+
+/// A client whose hook records every summary.
+fn recording_client(max_redirects: usize) -> (ZJHttpClient, Arc<Mutex<Vec<RequestSummary>>>) {
+    let summaries = Arc::new(Mutex::new(Vec::new()));
+    let log = summaries.clone();
+    let client = ZJHttpClient::builder()
+        .set_max_redirects(max_redirects)
+        .on_complete(move |summary| log.lock().unwrap().push(summary.clone()))
+        .build()
+        .unwrap();
+    (client, summaries)
+}
+
+#[async_std::test]
+async fn success_is_reported_once() {
+    let port = spawn_server().await;
+    let (client, summaries) = recording_client(0);
+    let mut req = Request::new(methods::POST, format!("http://127.0.0.1:{port}/")).unwrap().set_body_string("hello");
+    let resp = Box::pin(client.send(&mut req)).await.unwrap();
+
+    let summaries = summaries.lock().unwrap();
+    assert_eq!(summaries.len(), 1);
+    let summary = &summaries[0];
+    assert_eq!((summary.method, summary.host.as_str()), ("POST", "127.0.0.1"));
+    assert_eq!((summary.status, summary.error_code), (Some(200), None));
+    assert_eq!((summary.attempts, summary.redirects), (1, 0));
+    let head_len = resp.sent_request().unwrap().head.len() as u64;
+    assert_eq!(summary.bytes_sent, head_len + 5);
+    assert!(summary.bytes_received >= 38, "{summary:?}");
+    assert_eq!(summary.connection_id, resp.connection_id);
+    assert!(!summary.reused_connection && !summary.deduplicated);
+    assert!(summary.duration >= summary.phases.connect + summary.phases.exchange);
+}
+
+#[async_std::test]
+async fn stale_connection_retry_is_reported_once() {
+    let port = spawn_server().await;
+    let (client, summaries) = recording_client(0);
+    let mut req = Request::new(methods::GET, format!("http://127.0.0.1:{port}/once")).unwrap();
+    let mut resp = Box::pin(client.send(&mut req)).await.unwrap();
+    assert_eq!(resp.body_string().await.unwrap(), "ok");
+    drop(resp);
+    // Let the server's close reach the pooled connection.
+    task::sleep(Duration::from_millis(50)).await;
+
+    let mut req = Request::new(methods::GET, format!("http://127.0.0.1:{port}/")).unwrap();
+    let resp = Box::pin(client.send(&mut req)).await.unwrap();
+    assert!(!resp.reused_connection);
+
+    let summaries = summaries.lock().unwrap();
+    assert_eq!(summaries.len(), 2);
+    assert_eq!((summaries[1].status, summaries[1].attempts), (Some(200), 2));
+    assert!(!summaries[1].reused_connection);
+}
+
+#[async_std::test]
+async fn redirects_are_reported_once() {
+    let port = spawn_server().await;
+    let (client, summaries) = recording_client(5);
+    let mut req = Request::new(methods::GET, format!("http://127.0.0.1:{port}/redirect")).unwrap();
+    Box::pin(client.send(&mut req)).await.unwrap();
+
+    let summaries = summaries.lock().unwrap();
+    assert_eq!(summaries.len(), 1);
+    assert_eq!((summaries[0].status, summaries[0].redirects, summaries[0].attempts), (Some(200), 1, 2));
+}
+
+#[async_std::test]
+async fn failure_is_reported_once() {
+    let port = {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        listener.local_addr().unwrap().port()
+    };
+    let (client, summaries) = recording_client(0);
+    let mut req = Request::new(methods::GET, format!("http://127.0.0.1:{port}/")).unwrap();
+    let err = Box::pin(client.send(&mut req)).await.err().expect("nothing listens on the port");
+
+    let summaries = summaries.lock().unwrap();
+    assert_eq!(summaries.len(), 1);
+    let summary = &summaries[0];
+    assert_eq!((summary.status, summary.error_code), (None, Some(err.code())));
+    assert_eq!((summary.attempts, summary.bytes_sent, summary.connection_id), (0, 0, None));
+}
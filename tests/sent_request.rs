@@ -0,0 +1,128 @@
+mod common;
+
+use zjhttpc::client::ZJHttpClient;
+use zjhttpc::methods;
+use zjhttpc::url::Url;
+use zjhttpc::requestx::{CanonicalizeMode, Request, SentRequestCapture};
+
+use common::{Mock, MockServer, OK};
+
+/// Answers every request with `ok`; the server records each received head.
+async fn spawn_server() -> (u16, Mock) {
+    let server = MockServer::always(OK).read_body().spawn().await;
+    (server.port, server)
+}
+
+fn post(port: u16) -> Request {
+    Request::new(methods::POST, format!("http://127.0.0.1:{port}/submit?x=1"))
+        .unwrap()
+        .set_basic_auth("user", "secret")
+        .set_header("Cookie", "session=abc")
+        .set_header("X-Trace", "t-1")
+        .set_body_string("hello")
+}
+
+#[async_std::test]
+async fn snapshot_matches_the_wire_with_credentials_redacted() {
+    let (port, received) = spawn_server().await;
+    let client = ZJHttpClient::builder().build().unwrap();
+    let mut req = post(port);
+    let resp = client.send(&mut req).await.unwrap();
+    let wire = received.request().await.head;
+
+    let sent = resp.sent_request().expect("captured by default");
+    assert_eq!(sent.method, "POST");
+    assert_eq!(sent.url.as_str(), format!("http://127.0.0.1:{port}/submit?x=1"));
+    assert!(sent.head.starts_with("POST /submit?x=1 HTTP/1.1\r\n"), "{}", sent.head);
+    assert_eq!(sent.header_all("host").len(), 1);
+    assert_eq!(sent.header_all("content-length"), ["5"]);
+    assert_eq!(sent.header_all("x-trace"), ["t-1"]);
+    assert_eq!(sent.header_all("user-agent").len(), 1);
+    assert_eq!(sent.header_all("authorization"), ["Basic [redacted]"]);
+    assert_eq!(sent.header_all("cookie"), ["[redacted]"]);
+    assert!(!sent.head.contains("session=abc"));
+
+    // Apart from the redacted values the snapshot is byte-for-byte the wire head.
+    let wire_lines: Vec<&str> = wire.split("\r\n").collect();
+    let sent_lines: Vec<&str> = sent.head.split("\r\n").collect();
+    assert_eq!(wire_lines.len(), sent_lines.len());
+    for (wire_line, sent_line) in wire_lines.iter().zip(&sent_lines) {
+        if !sent_line.contains("[redacted]") {
+            assert_eq!(wire_line, sent_line);
+        }
+    }
+}
+
+#[async_std::test]
+async fn header_names_match_whatever_their_case() {
+    let (port, received) = spawn_server().await;
+    let client = ZJHttpClient::builder().build().unwrap();
+    let mut req = Request::new(methods::GET, format!("http://127.0.0.1:{port}/"))
+        .unwrap()
+        .set_header("Accept", "text/html")
+        .set_header("accept", "application/json")
+        .add_header("X-Tag", "a")
+        .add_header("x-TAG", "b");
+    assert_eq!(req.header_one("ACCEPT").unwrap(), "application/json");
+    assert_eq!(req.header_all("x-tag").unwrap().iter().collect::<Vec<_>>(), ["a", "b"]);
+
+    let resp = client.send(&mut req).await.unwrap();
+    let wire = received.request().await.head;
+    assert_eq!(wire.to_ascii_lowercase().matches("\r\naccept: ").count(), 1, "{wire}");
+    assert!(wire.contains("\r\nAccept: application/json\r\n"), "{wire}");
+    assert!(wire.contains("\r\nX-Tag: a\r\nX-Tag: b\r\n"), "{wire}");
+
+    assert_eq!(resp.header_one("CONTENT-length"), Some("2"));
+    assert_eq!(resp.header_all("content-length"), ["2"]);
+    assert!(resp.header_all("x-missing").is_empty());
+}
+
+#[async_std::test]
+async fn unredacted_capture_keeps_credentials() {
+    let (port, received) = spawn_server().await;
+    let client = ZJHttpClient::builder().build().unwrap();
+    let mut req = post(port).set_sent_request_capture(SentRequestCapture::Unredacted);
+    let resp = client.send(&mut req).await.unwrap();
+    let wire = received.request().await.head;
+    assert_eq!(resp.sent_request().unwrap().head, wire);
+}
+
+#[async_std::test]
+async fn capture_can_be_turned_off() {
+    let (port, _received) = spawn_server().await;
+    let client = ZJHttpClient::builder().build().unwrap();
+    let mut req = post(port).set_sent_request_capture(SentRequestCapture::Off);
+    let resp = client.send(&mut req).await.unwrap();
+    assert!(resp.sent_request().is_none());
+}
+
+#[async_std::test]
+async fn canonical_query_is_what_goes_on_the_wire() {
+    let (port, received) = spawn_server().await;
+    let client = ZJHttpClient::builder().build().unwrap();
+    let mut req = Request::new(methods::GET, format!("http://127.0.0.1:{port}/list?b=2&a=x+y"))
+        .unwrap()
+        .canonicalize_query(CanonicalizeMode::SortedByKey)
+        .add_query("a", "first");
+    let resp = client.send(&mut req).await.unwrap();
+    let wire = received.request().await.head;
+    assert!(wire.starts_with("GET /list?a=x%20y&a=first&b=2 HTTP/1.1\r\n"), "{wire}");
+    assert!(resp.sent_request().unwrap().head.starts_with("GET /list?a=x%20y&a=first&b=2 "));
+}
+
+#[async_std::test]
+async fn url_from_from_url_reaches_the_wire_unchanged() {
+    let (port, received) = spawn_server().await;
+    let client = ZJHttpClient::builder().build().unwrap();
+    // Lower-case escapes, an encoded slash, an empty segment, a matrix
+    // parameter, an empty query pair and a bare key.
+    let mut url = Url::parse(&format!("http://127.0.0.1:{port}/")).unwrap();
+    url.set_path("/a%2fb//c;v=1");
+    url.set_query(Some("q=%7e&&flag"));
+    let expected = url.clone();
+    let mut req = Request::from_url(methods::GET, url).unwrap();
+    assert_eq!(req.url(), &expected);
+    client.send(&mut req).await.unwrap();
+    let wire = received.request().await.head;
+    assert!(wire.starts_with("GET /a%2fb//c;v=1?q=%7e&&flag HTTP/1.1\r\n"), "{wire}");
+}
@@ -0,0 +1,98 @@
+mod common;
+
+use std::time::Duration;
+
+use futures::future::join_all;
+use zjhttpc::client::ZJHttpClient;
+use zjhttpc::methods;
+use zjhttpc::requestx::Request;
+use zjhttpc::single_flight::SingleFlight;
+
+use common::{Mock, MockServer, Reply};
+
+/// Keep-alive server that answers every request with `body` after `delay`;
+/// the server counts connections and requests.
+async fn spawn_server(body: &'static str, delay: Duration) -> (u16, Mock) {
+    let response = format!("HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nContent-Length: {}\r\n\r\n{body}", body.len());
+    let server = MockServer::always(Reply::default().then_sleep(delay).then(response)).spawn().await;
+    (server.port, server)
+}
+
+fn client(single_flight: SingleFlight) -> ZJHttpClient {
+    ZJHttpClient::builder().set_single_flight(single_flight).build().unwrap()
+}
+
+async fn get_body(client: &ZJHttpClient, url: &str) -> (String, bool) {
+    let mut req = Request::new(methods::GET, url).unwrap();
+    let mut resp = Box::pin(client.send(&mut req)).await.unwrap();
+    (resp.body_string().await.unwrap(), resp.deduplicated)
+}
+
+#[async_std::test]
+async fn concurrent_identical_gets_share_one_request() {
+    let (port, counters) = spawn_server("shared body", Duration::from_millis(300)).await;
+    let client = client(SingleFlight::new());
+    let url = format!("http://127.0.0.1:{port}/resource");
+
+    let results = join_all((0..20).map(|_| get_body(&client, &url))).await;
+
+    assert_eq!(counters.requests().len(), 1);
+    assert_eq!(counters.accepts(), 1);
+    assert!(results.iter().all(|(body, _)| body == "shared body"));
+    assert_eq!(results.iter().filter(|(_, deduplicated)| *deduplicated).count(), 19);
+    assert_eq!(client.deduplicated_requests(), 19);
+}
+
+#[async_std::test]
+async fn sequential_gets_are_not_cached() {
+    let (port, counters) = spawn_server("fresh", Duration::ZERO).await;
+    let client = client(SingleFlight::new());
+    let url = format!("http://127.0.0.1:{port}/");
+
+    assert_eq!(get_body(&client, &url).await, ("fresh".to_string(), false));
+    assert_eq!(get_body(&client, &url).await, ("fresh".to_string(), false));
+    assert_eq!(counters.requests().len(), 2);
+    assert_eq!(client.deduplicated_requests(), 0);
+}
+
+#[async_std::test]
+async fn different_headers_are_not_shared() {
+    let (port, counters) = spawn_server("per user", Duration::from_millis(200)).await;
+    let client = client(SingleFlight::new());
+    let url = format!("http://127.0.0.1:{port}/");
+
+    let send_as = |user: &'static str| {
+        let client = client.clone();
+        let url = url.clone();
+        async move {
+            let mut req = Request::new(methods::GET, url).unwrap().set_basic_auth(user, "secret");
+            let mut resp = Box::pin(client.send(&mut req)).await.unwrap();
+            resp.body_string().await.unwrap()
+        }
+    };
+    join_all([send_as("alice"), send_as("bob")]).await;
+    assert_eq!(counters.requests().len(), 2);
+}
+
+#[async_std::test]
+async fn oversized_body_makes_followers_send_themselves() {
+    let (port, counters) = spawn_server("more than eight bytes", Duration::from_millis(200)).await;
+    let client = client(SingleFlight::new().max_body_bytes(8));
+    let url = format!("http://127.0.0.1:{port}/");
+
+    let results = join_all((0..3).map(|_| get_body(&client, &url))).await;
+
+    assert!(results.iter().all(|(body, deduplicated)| body == "more than eight bytes" && !deduplicated));
+    assert_eq!(counters.requests().len(), 3);
+    assert_eq!(client.deduplicated_requests(), 0);
+}
+
+#[async_std::test]
+async fn excluded_requests_are_sent_individually() {
+    let (port, counters) = spawn_server("live", Duration::from_millis(200)).await;
+    let client = client(SingleFlight::new().exclude(|req| req.url.path() == "/live"));
+    let url = format!("http://127.0.0.1:{port}/live");
+
+    join_all((0..3).map(|_| get_body(&client, &url))).await;
+    assert_eq!(counters.requests().len(), 3);
+}
@@ -0,0 +1,48 @@
+mod common;
+
+use std::time::Duration;
+
+use async_std::io::{ReadExt, WriteExt};
+use async_std::net::TcpListener;
+use async_std::task;
+use futures::channel::oneshot;
+use zjhttpc::client::ZJHttpClient;
+use zjhttpc::methods;
+use zjhttpc::requestx::Request;
+
+use common::read_head;
+
+/// Reads one request head and `body_len` body bytes, then reports the body
+/// and whether anything else arrived before answering.
+async fn spawn_server(body_len: usize) -> (u16, oneshot::Receiver<(Vec<u8>, bool)>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let (tx, rx) = oneshot::channel();
+    task::spawn(async move {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        read_head(&mut stream).await.unwrap();
+        let mut body = vec![0u8; body_len];
+        stream.read_exact(&mut body).await.unwrap();
+        let mut byte = [0u8; 1];
+        let extra = async_std::io::timeout(Duration::from_millis(200), stream.read(&mut byte)).await;
+        let _ = tx.send((body, matches!(extra, Ok(n) if n > 0)));
+        stream.write_all(b"HTTP/1.1 204 No Content\r\n\r\n").await.unwrap();
+    });
+    (port, rx)
+}
+
+#[async_std::test]
+async fn stream_longer_than_its_length_sends_only_the_length() {
+    let (port, received) = spawn_server(10).await;
+    let client = ZJHttpClient::builder().build().unwrap();
+    let source = async_std::io::Cursor::new(b"0123456789-not-part-of-the-body".to_vec());
+    let mut req = Request::new(methods::PUT, format!("http://127.0.0.1:{port}/"))
+        .unwrap()
+        .set_body_stream(source, 10);
+    let resp = Box::pin(client.send(&mut req)).await.unwrap();
+    assert_eq!(resp.status_code, 204);
+
+    let (body, extra) = received.await.unwrap();
+    assert_eq!(body, b"0123456789");
+    assert!(!extra, "bytes past Content-Length were written");
+}
@@ -0,0 +1,48 @@
+mod common;
+
+use std::sync::Arc;
+
+use zjhttpc::client::ZJHttpClient;
+use zjhttpc::misc::TrustStorePem;
+use zjhttpc::requestx::Request;
+use zjhttpc::resolver::Resolver;
+use zjhttpc::{RequestPhase, ZjhttpcError, methods};
+
+use common::{CA_A, Loopback, MockServer, OK};
+
+fn client() -> ZJHttpClient {
+    ZJHttpClient::builder()
+        .set_resolver(Arc::new(Loopback) as Arc<dyn Resolver>)
+        .set_global_trust_store_pem(TrustStorePem::Bytes(CA_A.to_vec()))
+        .build()
+        .unwrap()
+}
+
+async fn get(client: &ZJHttpClient, host: &str, port: u16) -> zjhttpc::Result<(String, bool)> {
+    let mut req = Request::new(methods::GET, format!("https://{host}:{port}/"))?;
+    let mut resp = client.send(&mut req).await?;
+    Ok((resp.body_string().await?, resp.reused_connection))
+}
+
+#[async_std::test]
+async fn pooled_tls_connection_is_reused_for_the_same_name() {
+    let port = MockServer::always(OK).tls().spawn().await.port;
+    let client = client();
+    assert_eq!(get(&client, "localhost", port).await.unwrap(), ("ok".to_string(), false));
+    assert_eq!(get(&client, "localhost", port).await.unwrap(), ("ok".to_string(), true));
+}
+
+#[async_std::test]
+async fn pooled_tls_connection_is_not_reused_for_another_name() {
+    let port = MockServer::always(OK).tls().spawn().await.port;
+    let client = client();
+    assert_eq!(get(&client, "localhost", port).await.unwrap().0, "ok");
+    assert_eq!(client.idle_connections(), 1);
+
+    // Same address, but the certificate doesn't cover this name: the
+    // connection verified for localhost must not be handed out.
+    let err = get(&client, "other.test", port).await.expect_err("other.test must be verified on its own");
+    assert!(matches!(err, ZjhttpcError::Tls { .. }), "{err:?}");
+    assert_eq!(err.phase(), Some(RequestPhase::Tls));
+    assert_eq!(client.idle_connections(), 1);
+}
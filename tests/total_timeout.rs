@@ -0,0 +1,96 @@
+mod common;
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use zjhttpc::client::ZJHttpClient;
+use zjhttpc::methods;
+use zjhttpc::requestx::Request;
+use zjhttpc::resolver::Resolver;
+use zjhttpc::{ErrorCategory, ZjhttpcError};
+
+use common::{Loopback, MockServer, Reply};
+
+/// Reads a request head, writes `head`, then holds the connection open
+/// past every timeout under test.
+async fn spawn_server(head: &'static [u8]) -> u16 {
+    MockServer::always(Reply::new(head).then_sleep(Duration::from_secs(5)).then_close()).spawn().await.port
+}
+
+fn client(total: Duration) -> ZJHttpClient {
+    ZJHttpClient::builder().set_global_total_timeout(total).build().unwrap()
+}
+
+fn request(port: u16) -> Request {
+    Request::new(methods::GET, format!("http://127.0.0.1:{port}/")).unwrap()
+}
+
+const TOTAL: Duration = Duration::from_millis(300);
+
+#[async_std::test]
+async fn silent_server_trips_the_total_timeout() {
+    let port = spawn_server(b"").await;
+    let started = Instant::now();
+    let err = client(TOTAL).send(&mut request(port)).await.err().expect("server never answers");
+    assert!(matches!(err, ZjhttpcError::TotalTimeout { duration, .. } if duration == TOTAL), "{err}");
+    assert_eq!((err.code(), err.category()), ("ZJ-TIMEOUT-TOTAL", ErrorCategory::Timeout));
+    assert!(started.elapsed() < Duration::from_secs(2), "the 30 s read header timeout did not apply");
+}
+
+#[async_std::test]
+async fn body_read_gets_what_is_left_of_the_total_timeout() {
+    let port = spawn_server(b"HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\nab").await;
+    let client = client(TOTAL);
+    let mut resp = client.send(&mut request(port)).await.unwrap();
+    let started = Instant::now();
+    let err = resp.body_bytes().await.expect_err("body stalls");
+    assert!(matches!(err, ZjhttpcError::TotalTimeout { .. }), "{err}");
+    assert!(started.elapsed() < TOTAL + Duration::from_millis(100));
+}
+
+#[async_std::test]
+async fn shorter_read_body_timeout_still_wins() {
+    let port = spawn_server(b"HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\nab").await;
+    let client = client(Duration::from_secs(3));
+    let mut req = request(port).set_read_body_timeout(Duration::from_millis(100));
+    let mut resp = client.send(&mut req).await.unwrap();
+    let err = resp.body_bytes().await.expect_err("body stalls");
+    assert!(matches!(err, ZjhttpcError::ReadBodyTimeout { .. }), "{err}");
+}
+
+#[async_std::test]
+async fn request_total_timeout_wins_over_the_client_one() {
+    let port = spawn_server(b"").await;
+    let started = Instant::now();
+    let mut req = request(port).set_total_timeout(TOTAL);
+    let err = client(Duration::from_secs(5)).send(&mut req).await.err().expect("server never answers");
+    assert!(matches!(err, ZjhttpcError::TotalTimeout { duration, .. } if duration == TOTAL), "{err}");
+    assert!(started.elapsed() < Duration::from_secs(2));
+}
+
+#[async_std::test]
+async fn connect_timeout_covers_the_tls_handshake() {
+    // Accepts the TCP connection, then never answers the ClientHello.
+    let port = spawn_server(b"").await;
+    let client = ZJHttpClient::builder()
+        .set_resolver(Arc::new(Loopback) as Arc<dyn Resolver>)
+        .build()
+        .unwrap();
+    let connect = Duration::from_millis(200);
+    let mut req = Request::new(methods::GET, format!("https://silent.test:{port}/")).unwrap().set_connect_timeout(connect);
+    let started = Instant::now();
+    let err = client.send(&mut req).await.err().expect("handshake never completes");
+    assert!(matches!(err, ZjhttpcError::ConnectionTimeout { duration, .. } if duration == connect), "{err}");
+    assert!(started.elapsed() < Duration::from_secs(2), "the 30 s read header timeout did not apply");
+}
+
+#[test]
+fn effective_config_reports_the_total_timeout() {
+    let req = request(80);
+    assert_eq!(client(TOTAL).effective_config(&req).total_timeout, Some(TOTAL));
+    assert_eq!(ZJHttpClient::new().effective_config(&req).total_timeout, Some(Duration::from_secs(300)));
+    let off = ZJHttpClient::builder().total_timeout(None).build().unwrap();
+    assert_eq!(off.effective_config(&req).total_timeout, None);
+    let req = req.set_total_timeout(Duration::from_secs(2));
+    assert_eq!(client(TOTAL).effective_config(&req).total_timeout, Some(Duration::from_secs(2)));
+}
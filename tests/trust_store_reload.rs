@@ -0,0 +1,118 @@
+mod common;
+
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use zjhttpc::client::ZJHttpClient;
+use zjhttpc::misc::TrustStorePem;
+use zjhttpc::requestx::Request;
+use zjhttpc::resolver::Resolver;
+use zjhttpc::{RequestPhase, ZjhttpcError, methods};
+
+use common::{CA_A, Loopback, MockServer, OK};
+
+const CA_B: &[u8] = include_bytes!("fixtures/ca_b.pem");
+
+fn client(trust_store: TrustStorePem) -> ZJHttpClient {
+    ZJHttpClient::builder()
+        .set_resolver(Arc::new(Loopback) as Arc<dyn Resolver>)
+        .set_global_trust_store_pem(trust_store)
+        .build()
+        .unwrap()
+}
+
+async fn get(client: &ZJHttpClient, port: u16) -> zjhttpc::Result<String> {
+    let mut req = Request::new(methods::GET, format!("https://localhost:{port}/"))?;
+    client.send(&mut req).await?.body_string().await
+}
+
+fn assert_untrusted(result: zjhttpc::Result<String>) {
+    let err = result.expect_err("the server must not be trusted");
+    assert!(matches!(err, ZjhttpcError::Tls { .. }), "{err:?}");
+    assert_eq!(err.phase(), Some(RequestPhase::Tls));
+}
+
+#[async_std::test]
+async fn reload_to_untrusting_store_evicts_pool_and_fails_new_connections() {
+    let port = MockServer::always(OK).tls().spawn().await.port;
+    let client = client(TrustStorePem::Bytes(CA_A.to_vec()));
+    assert_eq!(get(&client, port).await.unwrap(), "ok");
+    assert_eq!(client.idle_connections(), 1);
+
+    client.reload_trust_store(TrustStorePem::Bytes(CA_B.to_vec())).unwrap();
+    assert_eq!(client.idle_connections(), 0);
+    assert_untrusted(get(&client, port).await);
+
+    client.reload_trust_store(TrustStorePem::Bytes(CA_A.to_vec())).unwrap();
+    assert_eq!(get(&client, port).await.unwrap(), "ok");
+}
+
+#[async_std::test]
+async fn failed_reload_keeps_the_previous_roots() {
+    let port = MockServer::always(OK).tls().spawn().await.port;
+    let client = client(TrustStorePem::Bytes(CA_A.to_vec()));
+    assert_eq!(get(&client, port).await.unwrap(), "ok");
+
+    let err = client
+        .reload_trust_store(TrustStorePem::Bytes(b"this is not a PEM bundle".to_vec()))
+        .expect_err("garbage must be rejected");
+    assert!(matches!(err, ZjhttpcError::Certificate { .. }), "{err:?}");
+    assert_eq!(client.idle_connections(), 1);
+
+    assert_eq!(get(&client, port).await.unwrap(), "ok");
+    assert_eq!(client.idle_connections(), 1);
+}
+
+#[async_std::test]
+async fn connection_in_flight_during_reload_is_not_pooled() {
+    let port = MockServer::always(OK).tls().spawn().await.port;
+    let client = client(TrustStorePem::Bytes(CA_A.to_vec()));
+    let mut req = Request::new(methods::GET, format!("https://localhost:{port}/")).unwrap();
+    let mut resp = client.send(&mut req).await.unwrap();
+
+    client.reload_trust_store(TrustStorePem::Bytes(CA_A.to_vec())).unwrap();
+    assert_eq!(resp.body_string().await.unwrap(), "ok");
+    drop(resp);
+    assert_eq!(client.idle_connections(), 0);
+    assert_eq!(client.in_flight_connections(), 0);
+}
+
+/// Overwrite `path` and move its mtime forward, so the change is seen even on
+/// filesystems with coarse timestamps.
+fn rewrite(path: &PathBuf, contents: &[u8], step: u64) {
+    std::fs::write(path, contents).unwrap();
+    let file = std::fs::File::options().write(true).open(path).unwrap();
+    file.set_modified(SystemTime::now() + Duration::from_secs(step)).unwrap();
+}
+
+#[async_std::test]
+async fn watched_file_is_reread_when_it_changes() {
+    let port = MockServer::always(OK).tls().spawn().await.port;
+    let path = std::env::temp_dir().join(format!("zjhttpc-trust-{}.pem", std::process::id()));
+    rewrite(&path, CA_A, 0);
+
+    let client = ZJHttpClient::builder()
+        .set_resolver(Arc::new(Loopback) as Arc<dyn Resolver>)
+        .build()
+        .unwrap();
+    client.watch_trust_store_file(&path).unwrap();
+    assert_eq!(get(&client, port).await.unwrap(), "ok");
+    let cfg = client.effective_config(&Request::new(methods::GET, "https://localhost/").unwrap());
+    assert_eq!(cfg.trust_store, zjhttpc::config::TrustStoreSource::Client);
+
+    assert_eq!(client.idle_connections(), 1);
+
+    // A broken bundle is reported and the previous roots stay active.
+    rewrite(&path, b"garbage", 10);
+    assert_eq!(get(&client, port).await.unwrap(), "ok");
+    assert_eq!(client.idle_connections(), 1);
+
+    rewrite(&path, CA_B, 20);
+    assert_untrusted(get(&client, port).await);
+    assert_eq!(client.idle_connections(), 0);
+
+    rewrite(&path, b"garbage", 30);
+    client.watch_trust_store_file(&path).expect_err("garbage must be rejected");
+    std::fs::remove_file(&path).unwrap();
+}
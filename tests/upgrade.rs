@@ -0,0 +1,123 @@
+mod common;
+
+use async_std::io::{ReadExt, WriteExt};
+use async_std::net::TcpListener;
+use async_std::task;
+use futures::StreamExt;
+use zjhttpc::ZjhttpcError;
+use zjhttpc::client::ZJHttpClient;
+use zjhttpc::methods;
+use zjhttpc::requestx::Request;
+
+use common::read_head;
+
+/// Reads one request head, answers with `head`, then echoes every byte back.
+async fn spawn_server(head: &'static [u8]) -> u16 {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    task::spawn(async move {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        read_head(&mut stream).await;
+        stream.write_all(head).await.unwrap();
+        let mut buf = [0u8; 1024];
+        while let Ok(n) = stream.read(&mut buf).await
+            && n > 0
+        {
+            stream.write_all(&buf[..n]).await.unwrap();
+        }
+    });
+    port
+}
+
+const SWITCHING: &[u8] = b"HTTP/1.1 101 Switching Protocols\r\nConnection: Upgrade\r\nUpgrade: tcp\r\n\r\nhello";
+
+async fn upgrade(port: u16) -> (ZJHttpClient, zjhttpc::response::Response) {
+    let client = ZJHttpClient::builder().build().unwrap();
+    let mut req = Request::new(methods::POST, format!("http://127.0.0.1:{port}/attach"))
+        .unwrap()
+        .set_header("Connection", "Upgrade")
+        .set_header("Upgrade", "tcp");
+    let resp = client.send(&mut req).await.unwrap();
+    (client, resp)
+}
+
+#[async_std::test]
+async fn upgraded_stream_carries_early_bytes_and_echoes() {
+    let port = spawn_server(SWITCHING).await;
+    let (client, resp) = upgrade(port).await;
+    assert_eq!(resp.status_code(), 101);
+    assert!(resp.is_upgraded());
+
+    let mut stream = resp.into_upgraded_stream().unwrap();
+    let mut early = [0u8; 5];
+    stream.read_exact(&mut early).await.unwrap();
+    assert_eq!(&early, b"hello");
+
+    stream.write_all(b"ping").await.unwrap();
+    let mut echoed = [0u8; 4];
+    stream.read_exact(&mut echoed).await.unwrap();
+    assert_eq!(&echoed, b"ping");
+
+    assert_eq!(client.in_flight_connections(), 1);
+    drop(stream);
+    assert_eq!((client.in_flight_connections(), client.idle_connections()), (0, 0));
+}
+
+#[async_std::test]
+async fn body_accessors_fail_on_101() {
+    let port = spawn_server(SWITCHING).await;
+    let (client, mut resp) = upgrade(port).await;
+
+    let err = resp.body_bytes().await.expect_err("101 has no body");
+    assert!(matches!(err, ZjhttpcError::ProtocolSwitched { .. }), "{err:?}");
+    let err = resp.body_string().await.expect_err("101 has no body");
+    assert!(matches!(err, ZjhttpcError::ProtocolSwitched { .. }), "{err:?}");
+    let items: Vec<_> = resp.bytes_stream().collect().await;
+    assert!(matches!(items[..], [Err(ZjhttpcError::ProtocolSwitched { .. })]));
+    assert!(resp.body_managed_stream().is_none());
+
+    // The connection is still intact for the new protocol.
+    let mut stream = resp.into_upgraded_stream().unwrap();
+    let mut early = [0u8; 5];
+    stream.read_exact(&mut early).await.unwrap();
+    assert_eq!(&early, b"hello");
+    drop(stream);
+    assert_eq!(client.idle_connections(), 0);
+}
+
+#[async_std::test]
+async fn dropped_101_response_is_not_pooled() {
+    let port = spawn_server(SWITCHING).await;
+    let (client, mut resp) = upgrade(port).await;
+    resp.mark_body_read_complete();
+    drop(resp);
+    assert_eq!((client.in_flight_connections(), client.idle_connections()), (0, 0));
+}
+
+#[async_std::test]
+async fn into_upgraded_stream_requires_101() {
+    let port = spawn_server(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n").await;
+    let (_client, resp) = upgrade(port).await;
+    let err = resp.into_upgraded_stream().err().expect("200 is not an upgrade");
+    assert!(matches!(err, ZjhttpcError::NotUpgraded { status: 200, .. }), "{err:?}");
+}
+
+#[async_std::test]
+async fn connect_200_is_a_tunnel_whatever_its_content_length() {
+    let port = spawn_server(b"HTTP/1.1 200 Connection established\r\nContent-Length: 5\r\n\r\nhello").await;
+    let client = ZJHttpClient::builder().build().unwrap();
+    let mut req = Request::new(methods::CONNECT, format!("http://127.0.0.1:{port}/")).unwrap();
+    let resp = client.send(&mut req).await.unwrap();
+    assert!(resp.is_upgraded());
+
+    let mut stream = resp.into_upgraded_stream().unwrap();
+    let mut early = [0u8; 5];
+    stream.read_exact(&mut early).await.unwrap();
+    assert_eq!(&early, b"hello", "tunnel bytes are not taken for a body");
+    stream.write_all(b"ping").await.unwrap();
+    let mut echoed = [0u8; 4];
+    stream.read_exact(&mut echoed).await.unwrap();
+    assert_eq!(&echoed, b"ping");
+    drop(stream);
+    assert_eq!(client.idle_connections(), 0);
+}
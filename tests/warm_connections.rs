@@ -0,0 +1,81 @@
+mod common;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_std::task;
+use zjhttpc::client::ZJHttpClient;
+use zjhttpc::misc::TrustStorePem;
+use zjhttpc::requestx::Request;
+use zjhttpc::resolver::Resolver;
+use zjhttpc::warm::WarmPolicy;
+use zjhttpc::methods;
+
+use common::{CA_A, Loopback, MockServer, OK};
+
+const SERVER_IDLE_TIMEOUT: Duration = Duration::from_secs(1);
+
+async fn get(client: &ZJHttpClient, url: &str) -> bool {
+    let mut req = Request::new(methods::GET, url).unwrap();
+    let mut resp = client.send(&mut req).await.unwrap();
+    assert_eq!(resp.body_string().await.unwrap(), "ok");
+    resp.reused_connection
+}
+
+#[async_std::test]
+async fn maintenance_replaces_connections_before_the_server_drops_them() {
+    // The server drops connections idle for `SERVER_IDLE_TIMEOUT`.
+    let server = MockServer::always(OK).tls().idle_timeout(SERVER_IDLE_TIMEOUT).spawn().await;
+    let url = format!("https://localhost:{}/", server.port);
+    let client = ZJHttpClient::builder()
+        .set_resolver(Arc::new(Loopback) as Arc<dyn Resolver>)
+        .set_global_trust_store_pem(TrustStorePem::Bytes(CA_A.to_vec()))
+        .build()
+        .unwrap();
+
+    let policy = WarmPolicy::new(1)
+        .set_refresh_after(Duration::from_millis(600))
+        .set_check_interval(Duration::from_millis(100));
+    let warm = client.maintain_warm_connections_with(&url, policy).unwrap();
+    task::sleep(Duration::from_millis(300)).await;
+    assert_eq!(client.idle_connections(), 1);
+
+    for _ in 0..2 {
+        task::sleep(SERVER_IDLE_TIMEOUT * 2).await;
+        assert!(get(&client, &url).await, "request had to open a connection");
+    }
+    assert!(warm.maintenance_connects() >= 4, "connects: {}", warm.maintenance_connects());
+    assert_eq!(warm.maintenance_failures(), 0);
+    assert_eq!(server.accepts() as u64, warm.maintenance_connects());
+
+    // Without maintenance the idle connection goes stale and the request
+    // has to reconnect.
+    warm.stop();
+    task::sleep(SERVER_IDLE_TIMEOUT * 2).await;
+    assert!(!get(&client, &url).await);
+}
+
+#[async_std::test]
+async fn failed_connects_are_counted_and_retried() {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    drop(listener);
+    let client = ZJHttpClient::builder().build().unwrap();
+    let policy = WarmPolicy::new(1)
+        .set_check_interval(Duration::from_millis(20))
+        .set_max_backoff(Duration::from_millis(40));
+    let warm = client.maintain_warm_connections_with(format!("http://127.0.0.1:{port}/"), policy).unwrap();
+    task::sleep(Duration::from_millis(300)).await;
+    assert!(warm.maintenance_failures() >= 2);
+    assert_eq!(warm.maintenance_connects(), 0);
+    assert_eq!(client.idle_connections(), 0);
+}
+
+#[test]
+fn proxy_is_rejected() {
+    let client = ZJHttpClient::builder()
+        .set_global_proxy(zjhttpc::proxy::HttpsProxyOption::new("http://127.0.0.1:3128").unwrap())
+        .build()
+        .unwrap();
+    assert!(client.maintain_warm_connections("http://127.0.0.1:8080/", 1).is_err());
+}